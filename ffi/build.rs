@@ -0,0 +1,33 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/**
+ * generates srdb.h from src/ffi.rs of srdb into OUT_DIR, tests/header.rs compares it with checked in include/srdb.h
+ * source tree is written only on explicit request: SRDB_UPDATE_HEADER=1 cargo build -p srdb-ffi
+ */
+fn main() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let source = root.join("src/ffi.rs");
+    let config = Path::new(env!("CARGO_MANIFEST_DIR")).join("cbindgen.toml");
+    let header = PathBuf::from(env::var("OUT_DIR").unwrap()).join("srdb.h");
+
+    println!("cargo:rerun-if-changed={}", source.display());
+    println!("cargo:rerun-if-changed={}", config.display());
+    println!("cargo:rerun-if-env-changed=SRDB_UPDATE_HEADER");
+
+    let mut generated = vec![];
+
+    cbindgen::Builder::new()
+        .with_config(cbindgen::Config::from_file(&config).unwrap())
+        .with_src(&source)
+        .generate()
+        .expect("cbindgen fails on src/ffi.rs")
+        .write(&mut generated);
+
+    fs::write(&header, &generated).unwrap();
+
+    if env::var_os("SRDB_UPDATE_HEADER").is_some() {
+        fs::write(root.join("include/srdb.h"), generated).unwrap();
+    }
+}
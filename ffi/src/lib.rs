@@ -0,0 +1,8 @@
+/*!
+ * libsrdb shared library, functions of include/srdb.h are exported from srdb built with ffi feature
+ */
+
+pub use srdb_core::{
+    srdb_contains, srdb_delete, srdb_free, srdb_insert, srdb_iter_free, srdb_iter_key, srdb_iter_new, srdb_iter_next,
+    srdb_iter_valid, srdb_len, srdb_new, SrdbHandle, SrdbIter, SRDB_INVALID, SRDB_NULL, SRDB_OK, SRDB_PANIC,
+};
@@ -0,0 +1,33 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+/**
+ * compiles include/srdb_test.c against libsrdb built for this test run and runs it,
+ * C compiler is taken from CC, cc by default
+ */
+#[test]
+fn c_test_runs_against_shared_library() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf();
+    let libs = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let binary = libs.join("srdb_test");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let status = Command::new(&compiler)
+        .arg(format!("-I{}", root.join("include").display()))
+        .arg(root.join("include/srdb_test.c"))
+        .arg(format!("-L{}", libs.display()))
+        .arg(format!("-Wl,-rpath,{}", libs.display()))
+        .arg("-lsrdb")
+        .arg("-o")
+        .arg(&binary)
+        .status()
+        .unwrap_or_else(|error| panic!("{} does not run: {}", compiler, error));
+
+    assert!(status.success(), "{} fails on srdb_test.c", compiler);
+
+    let output = Command::new(&binary).output().unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, b"ok\n");
+}
@@ -0,0 +1,17 @@
+use std::fs;
+use std::path::PathBuf;
+
+/**
+ * checked in include/srdb.h is the one build script generates from src/ffi.rs
+ */
+#[test]
+fn checked_in_header_is_up_to_date() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf();
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/srdb.h"));
+    let checked_in = fs::read_to_string(root.join("include/srdb.h")).unwrap();
+
+    assert!(
+        checked_in == generated,
+        "include/srdb.h is stale, regenerate it with SRDB_UPDATE_HEADER=1 cargo build -p srdb-ffi"
+    );
+}
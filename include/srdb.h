@@ -0,0 +1,105 @@
+/*
+ * C interface to srdb trees of int64_t keys, generated by cbindgen from src/ffi.rs, do not edit
+ * build: cargo build --release -p srdb-ffi, gives target/release/libsrdb.so
+ * functions returning int give SRDB_OK, 1/0 answers or a negative error code
+ */
+
+#ifndef SRDB_H
+#define SRDB_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define SRDB_OK 0
+
+#define SRDB_NULL -1
+
+#define SRDB_INVALID -2
+
+#define SRDB_PANIC -3
+
+/*
+ * C interface to BTree<i64>, include/srdb.h is generated from this file by cbindgen of ffi/build.rs,
+ * after changes here: SRDB_UPDATE_HEADER=1 cargo build -p srdb-ffi
+ * shared library is crate srdb-ffi in ffi directory: cargo build --release -p srdb-ffi
+ * pointers given to C are only keys of registries below, they are never dereferenced,
+ * so null, freed or foreign pointer gets SRDB_INVALID instead of undefined behaviour,
+ * unless memory of freed object was given to a new one meanwhile
+ * every call holds registry lock, so calls from several threads are safe and run one by one
+ * panic inside call is caught and reported as SRDB_PANIC
+ */
+typedef struct SrdbHandle SrdbHandle;
+
+/*
+ * ordered walk over keys of handle as of srdb_iter_new, later changes of tree are not seen
+ */
+typedef struct SrdbIter SrdbIter;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ * new empty tree, null if t < 2
+ */
+struct SrdbHandle *srdb_new(size_t t);
+
+/*
+ * SRDB_OK or error code, handle is invalid after it
+ */
+int srdb_free(struct SrdbHandle *handle);
+
+/*
+ * SRDB_OK or error code, duplicates are kept
+ */
+int srdb_insert(struct SrdbHandle *handle, int64_t key);
+
+/*
+ * 1 if key is in tree, 0 if not, or error code
+ */
+int srdb_contains(const struct SrdbHandle *handle, int64_t key);
+
+/*
+ * removes one occurrence of key, 1 if it was there, 0 if not, or error code
+ */
+int srdb_delete(struct SrdbHandle *handle, int64_t key);
+
+/*
+ * number of keys or error code
+ */
+int64_t srdb_len(const struct SrdbHandle *handle);
+
+/*
+ * iterator at the smallest key, null if handle is invalid
+ * it does not borrow tree, so it may outlive handle and must be freed by srdb_iter_free
+ */
+struct SrdbIter *srdb_iter_new(const struct SrdbHandle *handle);
+
+/*
+ * 1 if iterator is at key, 0 if it is past the last one, or error code
+ */
+int srdb_iter_valid(const struct SrdbIter *iter);
+
+/*
+ * key iterator is at, 0 if srdb_iter_valid is not 1
+ */
+int64_t srdb_iter_key(const struct SrdbIter *iter);
+
+/*
+ * moves iterator to the next key, SRDB_OK or error code, it stays past the last key once there
+ */
+int srdb_iter_next(struct SrdbIter *iter);
+
+/*
+ * SRDB_OK or error code, iterator is invalid after it
+ */
+int srdb_iter_free(struct SrdbIter *iter);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SRDB_H */
@@ -0,0 +1,32 @@
+/*
+ * smoke test of include/srdb.h, cargo test -p srdb-ffi builds and runs it, by hand from repository root:
+ * cargo build --release -p srdb-ffi
+ * cc -Iinclude include/srdb_test.c -Ltarget/release -lsrdb -o target/srdb_test
+ * LD_LIBRARY_PATH=target/release target/srdb_test
+ */
+#include <assert.h>
+#include <stdio.h>
+#include "srdb.h"
+int main(void) {
+    assert(srdb_new(1) == NULL);
+    SrdbHandle *h = srdb_new(3);
+    assert(h);
+    for (int64_t i = 100; i > 0; i--) assert(srdb_insert(h, i * 2) == SRDB_OK);
+    assert(srdb_insert(h, 10) == SRDB_OK);
+    assert(srdb_len(h) == 101);
+    assert(srdb_contains(h, 10) == 1 && srdb_contains(h, 11) == 0);
+    assert(srdb_delete(h, 10) == 1 && srdb_contains(h, 10) == 1);
+    assert(srdb_delete(h, 10) == 1 && srdb_contains(h, 10) == 0 && srdb_delete(h, 10) == 0);
+    SrdbIter *it = srdb_iter_new(h);
+    int64_t prev = 0, n = 0;
+    for (; srdb_iter_valid(it) == 1; srdb_iter_next(it)) { assert(srdb_iter_key(it) > prev); prev = srdb_iter_key(it); n++; }
+    assert(n == 99 && srdb_iter_next(it) == SRDB_OK && srdb_iter_valid(it) == 0 && srdb_iter_key(it) == 0);
+    assert(srdb_len(NULL) == SRDB_NULL && srdb_insert(NULL, 1) == SRDB_NULL && srdb_iter_new(NULL) == NULL);
+    assert(srdb_contains((SrdbHandle *)it, 1) == SRDB_INVALID);
+    assert(srdb_iter_valid((SrdbIter *)h) == SRDB_INVALID);
+    assert(srdb_free(h) == SRDB_OK && srdb_free(h) == SRDB_INVALID && srdb_len(h) == SRDB_INVALID);
+    assert(srdb_iter_valid(it) == 0);
+    assert(srdb_iter_free(it) == SRDB_OK && srdb_iter_free(it) == SRDB_INVALID && srdb_iter_free(NULL) == SRDB_NULL);
+    puts("ok");
+    return 0;
+}
@@ -0,0 +1,5 @@
+/*!
+ * srdb python extension, PyInit_srdb is exported from srdb built with python feature
+ */
+
+pub use srdb_core::python_module;
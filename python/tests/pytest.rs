@@ -0,0 +1,46 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/**
+ * copies extension built for this test run as srdb.so and runs tests/test_srdb.py against it,
+ * with pytest when it is installed and as plain script otherwise, interpreter is taken from PYTHON, python3 by default
+ */
+#[test]
+fn pytest_runs_against_extension_module() {
+    let tests = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let libs = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let module = env::temp_dir().join(format!("srdb-python-{}", std::process::id()));
+    let python = env::var("PYTHON").unwrap_or_else(|_| "python3".to_string());
+
+    fs::create_dir_all(&module).unwrap();
+    fs::copy(libs.join("libsrdb_python.so"), module.join("srdb.so")).unwrap();
+
+    let has_pytest = Command::new(&python)
+        .args(["-c", "import pytest"])
+        .output()
+        .is_ok_and(|output| output.status.success());
+
+    let mut command = Command::new(&python);
+
+    if has_pytest {
+        command.args(["-m", "pytest", "-q", "-p", "no:cacheprovider"]);
+    }
+
+    let output = command
+        .arg(tests.join("test_srdb.py"))
+        .env("PYTHONPATH", &module)
+        .env("PYTHONDONTWRITEBYTECODE", "1")
+        .output()
+        .unwrap_or_else(|error| panic!("{} does not run: {}", python, error));
+
+    fs::remove_dir_all(&module).unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
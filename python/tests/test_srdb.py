@@ -0,0 +1,214 @@
+"""
+tests of srdb python module, run by pytest or as script with srdb.so in python path,
+cargo test -p srdb-python builds module and runs this file
+"""
+
+import random
+import sys
+import threading
+import time
+
+import srdb
+
+
+def raises(error, call):
+    try:
+        call()
+    except error:
+        return
+    raise AssertionError(f"{error.__name__} is not raised")
+
+
+def test_btree_against_sorted_list():
+    for t in [2, 3, 16]:
+        tree = srdb.BTree(t)
+        model = []
+        rng = random.Random(t)
+
+        for _ in range(3000):
+            key = rng.randrange(500)
+
+            if rng.random() < 0.3:
+                assert tree.remove(key) == (key in model)
+                if key in model:
+                    model.remove(key)
+            else:
+                tree.insert(key)
+                model.append(key)
+
+        model.sort()
+        tree.check_invariants()
+
+        assert len(tree) == len(model)
+        assert tree.to_list() == model
+        assert list(tree) == model
+        assert all(key in tree for key in model)
+        assert 500 not in tree
+
+
+def test_btree_keys_of_each_type():
+    ints = srdb.BTree.bulk_load([3, -1, 2**62, 0])
+    strs = srdb.BTree.bulk_load(["b", "a", "ä", ""])
+    blobs = srdb.BTree.bulk_load([b"\xff", b"", b"a\x00"])
+
+    assert ints.to_list() == [-1, 0, 3, 2**62]
+    assert strs.to_list() == sorted(["b", "a", "ä", ""])
+    assert blobs.to_list() == [b"", b"a\x00", b"\xff"]
+
+    mixed = srdb.BTree()
+
+    for key in [b"x", "x", 1]:
+        mixed.insert(key)
+
+    assert mixed.to_list() == [1, "x", b"x"], "ints go before strs and strs before bytes"
+
+    raises(TypeError, lambda: mixed.insert(1.5))
+    raises(TypeError, lambda: None in mixed)
+    raises(OverflowError, lambda: mixed.insert(2**64))
+    raises(ValueError, lambda: srdb.BTree(1))
+
+
+def test_btree_range_and_bulk_load():
+    keys = list(range(0, 10000, 2))
+    shuffled = keys[:]
+
+    random.Random(1).shuffle(shuffled)
+
+    tree = srdb.BTree.bulk_load(shuffled, t=8)
+
+    tree.check_invariants()
+
+    assert tree.to_list() == keys
+    assert tree.range(100, 111) == [100, 102, 104, 106, 108, 110]
+    assert tree.range(101, 102) == []
+    assert tree.range(hi=5) == [0, 2, 4]
+    assert tree.range(9995) == [9996, 9998]
+    assert tree.range() == keys
+
+    tree.update(range(1, 10000, 2))
+    tree.update([0])
+
+    assert tree.to_list() == [0] + list(range(10000))
+    assert tree.range(-5, 1) == [0, 0]
+
+
+def test_map_like_dict():
+    tree = srdb.Map(t=3)
+    model = {}
+    rng = random.Random(7)
+
+    for i in range(2000):
+        key = f"key{rng.randrange(300):03}"
+
+        if rng.random() < 0.25:
+            if key in model:
+                del tree[key]
+                del model[key]
+            else:
+                raises(KeyError, lambda: tree.__delitem__(key))
+        else:
+            tree[key] = [i]
+            model[key] = [i]
+
+        assert len(tree) == len(model)
+
+    assert list(tree) == sorted(model)
+    assert tree.keys() == sorted(model)
+    assert tree.values() == [model[key] for key in sorted(model)]
+    assert tree.items() == sorted(model.items())
+    assert all(tree[key] is tree.get(key) for key in model)
+    assert tree.get("missing") is None
+    assert tree.get("missing", 5) == 5
+
+    raises(KeyError, lambda: tree["missing"])
+    raises(TypeError, lambda: tree.__setitem__(1.5, 0))
+
+
+def test_map_keeps_values_alive():
+    tree = srdb.Map()
+    value = object()
+
+    tree[b"k"] = value
+    tree[1] = {"nested": [value]}
+
+    assert tree[b"k"] is value
+    assert tree[1]["nested"][0] is value
+
+    tree[b"k"] = None
+
+    assert tree[b"k"] is None
+    assert list(tree) == [1, b"k"]
+
+
+def ticks_during(call):
+    """
+    ticks counted by another python thread while call runs, zero unless call releases GIL,
+    long switch interval keeps GIL with this thread until it waits or call releases GIL
+    """
+    done = False
+    ticks = 0
+
+    def count():
+        nonlocal ticks
+        while not done:
+            ticks += 1
+            time.sleep(0)
+
+    interval = sys.getswitchinterval()
+    sys.setswitchinterval(100)
+
+    try:
+        counter = threading.Thread(target=count)
+        counter.start()
+
+        before = ticks
+        call()
+        after = ticks
+
+        done = True
+        counter.join()
+    finally:
+        sys.setswitchinterval(interval)
+
+    return after - before
+
+
+def test_long_operations_release_gil():
+    keys = list(range(200_000))
+    tree = srdb.BTree.bulk_load(keys, t=32)
+
+    assert ticks_during(lambda: tree.range(0, 150_000)) > 0
+    assert ticks_during(lambda: srdb.BTree.bulk_load(keys[::-1])) > 0
+    assert ticks_during(lambda: tree.update(keys)) > 0
+    assert ticks_during(lambda: tree.check_invariants()) == 0, "GIL is kept by other calls"
+
+
+def test_threads_share_tree():
+    tree = srdb.BTree(4)
+
+    def write(thread):
+        for key in range(thread, 20000, 4):
+            tree.insert(key)
+
+    threads = [threading.Thread(target=write, args=(thread,)) for thread in range(4)]
+
+    for thread in threads:
+        thread.start()
+
+    while any(thread.is_alive() for thread in threads):
+        keys = tree.range(0, 1000)
+        assert keys == sorted(keys)
+
+    for thread in threads:
+        thread.join()
+
+    tree.check_invariants()
+
+    assert tree.to_list() == list(range(20000))
+
+
+if __name__ == "__main__":
+    for name, test in list(globals().items()):
+        if name.startswith("test_"):
+            test()
+    print("ok")
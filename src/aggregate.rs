@@ -0,0 +1,419 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::convert::Infallible;
+use core::fmt::Debug;
+use core::ops::{Add, Bound, RangeBounds};
+
+use crate::node_store::{self, Event, NodeStore};
+use crate::observer::Change;
+use crate::{BTree, Node, NodeId};
+
+/**
+ * fold of keys kept by AggBTree for every subtree, see AggBTree::aggregate_range
+ * combine must be associative with identity as neutral value, it need not be commutative:
+ * values are always combined in key order
+ */
+pub trait Monoid<T> {
+    type Value: Clone;
+
+    fn identity(&self) -> Self::Value;
+
+    fn lift(&self, key: &T) -> Self::Value;
+
+    fn combine(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/**
+ * number of keys
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Count;
+
+impl<T> Monoid<T> for Count {
+    type Value = usize;
+
+    fn identity(&self) -> usize {
+        0
+    }
+
+    fn lift(&self, _key: &T) -> usize {
+        1
+    }
+
+    fn combine(&self, a: &usize, b: &usize) -> usize {
+        a + b
+    }
+}
+
+/**
+ * sum of values f gives for keys, e.g. sizes of entries
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SumBy<F>(pub F);
+
+impl<T, V: Clone + Default + Add<Output = V>, F: Fn(&T) -> V> Monoid<T> for SumBy<F> {
+    type Value = V;
+
+    fn identity(&self) -> V {
+        V::default()
+    }
+
+    fn lift(&self, key: &T) -> V {
+        (self.0)(key)
+    }
+
+    fn combine(&self, a: &V, b: &V) -> V {
+        a.clone() + b.clone()
+    }
+}
+
+/**
+ * least value f gives for keys, None when there are no keys
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MinBy<F>(pub F);
+
+impl<T, V: Clone + Ord, F: Fn(&T) -> V> Monoid<T> for MinBy<F> {
+    type Value = Option<V>;
+
+    fn identity(&self) -> Option<V> {
+        None
+    }
+
+    fn lift(&self, key: &T) -> Option<V> {
+        Some((self.0)(key))
+    }
+
+    fn combine(&self, a: &Option<V>, b: &Option<V>) -> Option<V> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b).clone()),
+            _ => a.clone().or_else(|| b.clone()),
+        }
+    }
+}
+
+/**
+ * greatest value f gives for keys, None when there are no keys
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MaxBy<F>(pub F);
+
+impl<T, V: Clone + Ord, F: Fn(&T) -> V> Monoid<T> for MaxBy<F> {
+    type Value = Option<V>;
+
+    fn identity(&self) -> Option<V> {
+        None
+    }
+
+    fn lift(&self, key: &T) -> Option<V> {
+        Some((self.0)(key))
+    }
+
+    fn combine(&self, a: &Option<V>, b: &Option<V>) -> Option<V> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.max(b).clone()),
+            _ => a.clone().or_else(|| b.clone()),
+        }
+    }
+}
+
+/**
+ * BTree whose nodes cache fold of monoid over their subtrees, so folds of ranges take O(t * height)
+ * caches are recomputed from keys and caches of children whenever shared insert and delete refresh node,
+ * which they do for every node whose subtree changed, so splits, merges, borrows and root collapse keep them
+ */
+pub struct AggBTree<T: Ord + Debug, M: Monoid<T>> {
+    tree: BTree<T>,
+    monoid: M,
+    /**
+     * fold of subtree of every arena slot, identity for free slots
+     */
+    aggs: Vec<M::Value>,
+}
+
+/**
+ * part of tree aggregate_range still has to fold, flags tell that all keys of subtree are after start
+ * and before end of range
+ */
+enum Step<'a, T> {
+    Node(NodeId, bool, bool),
+    Key(&'a T),
+}
+
+impl<T: Ord + Debug, M: Monoid<T>> AggBTree<T, M> {
+    pub fn new(t: usize, monoid: M) -> Self {
+        let aggs = vec![monoid.identity()];
+
+        AggBTree { tree: BTree::new(t), monoid, aggs }
+    }
+
+    /**
+     * tree of keys, e.g. for iter, contains or stats
+     */
+    pub fn tree(&self) -> &BTree<T> {
+        &self.tree
+    }
+
+    pub fn monoid(&self) -> &M {
+        &self.monoid
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /**
+     * value goes after keys equal to it
+     */
+    pub fn insert(&mut self, value: T) {
+        self.tree.len += 1;
+
+        node_store::infallible(node_store::insert(self, value));
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.tree.validate_touched("insert");
+    }
+
+    /**
+     * removes one occurrence of value
+     */
+    pub fn delete(&mut self, value: &T) -> bool {
+        let removed = node_store::infallible(node_store::delete(self, |_, key| key.cmp(value)));
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.tree.validate_touched("delete");
+
+        if removed.is_some() {
+            self.tree.len -= 1;
+        }
+
+        removed.is_some()
+    }
+
+    /**
+     * fold of all keys
+     */
+    pub fn aggregate(&self) -> M::Value {
+        self.aggs[self.tree.root as usize].clone()
+    }
+
+    /**
+     * fold of keys in range in key order, e.g. sum of sizes of keys in a..b
+     * subtrees wholly inside range give their caches, so only nodes on paths to both ends of range are opened
+     */
+    pub fn aggregate_range(&self, range: impl RangeBounds<T>) -> M::Value {
+        let mut acc = self.monoid.identity();
+        let unbounded_start = matches!(range.start_bound(), Bound::Unbounded);
+        let unbounded_end = matches!(range.end_bound(), Bound::Unbounded);
+        let mut stack = vec![Step::Node(self.tree.root, unbounded_start, unbounded_end)];
+
+        while let Some(step) = stack.pop() {
+            let (id, after_start, before_end) = match step {
+                Step::Key(key) => {
+                    acc = self.monoid.combine(&acc, &self.monoid.lift(key));
+
+                    continue;
+                }
+                Step::Node(id, true, true) => {
+                    acc = self.monoid.combine(&acc, &self.aggs[id as usize]);
+
+                    continue;
+                }
+                Step::Node(id, after_start, before_end) => (id, after_start, before_end),
+            };
+
+            let node = self.tree.node(id);
+
+            for i in (0..=node.count).rev() {
+                if let Some(key) = node.keys.get(i) {
+                    if range.contains(key) {
+                        stack.push(Step::Key(key));
+                    }
+                }
+
+                if node.leaf {
+                    continue;
+                }
+
+                let below = node.keys.get(i).is_some_and(|key| before(&range, key));
+                let above = i > 0 && after(&range, &node.keys[i - 1]);
+
+                if below || above {
+                    continue;
+                }
+
+                let child_after_start = after_start || (i > 0 && !before(&range, &node.keys[i - 1]));
+                let child_before_end = before_end || node.keys.get(i).is_some_and(|key| !after(&range, key));
+
+                stack.push(Step::Node(node.children[i], child_after_start, child_before_end));
+            }
+        }
+
+        acc
+    }
+
+    /**
+     * folds keys of node and caches of its children, caches of children must be up to date
+     */
+    fn refresh_agg(&mut self, id: NodeId) {
+        let node = self.tree.node(id);
+        let child = |i: usize| node.children.get(i).map(|child| &self.aggs[*child as usize]);
+        let mut acc = child(0).cloned().unwrap_or_else(|| self.monoid.identity());
+
+        for (i, key) in node.keys.iter().enumerate() {
+            acc = self.monoid.combine(&acc, &self.monoid.lift(key));
+
+            if let Some(agg) = child(i + 1) {
+                acc = self.monoid.combine(&acc, agg);
+            }
+        }
+
+        self.aggs[id as usize] = acc;
+    }
+}
+
+impl<T: Ord + Debug, M: Monoid<T>> AggBTree<T, M>
+where
+    M::Value: PartialEq + Debug,
+{
+    /**
+     * invariants of tree, then caches of all nodes against folds recomputed bottom-up from keys
+     */
+    pub fn check_invariants(&self) -> Result<(), String> {
+        self.tree.check_invariants()?;
+        self.check_aggs(self.tree.root)?;
+
+        Ok(())
+    }
+
+    /**
+     * returns recomputed fold of subtree
+     */
+    fn check_aggs(&self, id: NodeId) -> Result<M::Value, String> {
+        let node = self.tree.node(id);
+        let mut acc = self.monoid.identity();
+
+        for i in 0..=node.count {
+            if let Some(&child) = node.children.get(i) {
+                acc = self.monoid.combine(&acc, &self.check_aggs(child)?);
+            }
+
+            if let Some(key) = node.keys.get(i) {
+                acc = self.monoid.combine(&acc, &self.monoid.lift(key));
+            }
+        }
+
+        if self.aggs[id as usize] != acc {
+            return Err(format!("node {}: cached aggregate {:?}, expected {:?}", id, self.aggs[id as usize], acc));
+        }
+
+        Ok(acc)
+    }
+}
+
+/**
+ * key is before start of range
+ */
+fn before<T: Ord>(range: &impl RangeBounds<T>, key: &T) -> bool {
+    match range.start_bound() {
+        Bound::Included(start) => key < start,
+        Bound::Excluded(start) => key <= start,
+        Bound::Unbounded => false,
+    }
+}
+
+/**
+ * key is after end of range
+ */
+fn after<T: Ord>(range: &impl RangeBounds<T>, key: &T) -> bool {
+    match range.end_bound() {
+        Bound::Included(end) => key > end,
+        Bound::Excluded(end) => key >= end,
+        Bound::Unbounded => false,
+    }
+}
+
+/**
+ * arena of inner tree with caches refreshed alongside first and last leaves
+ */
+impl<T: Ord + Debug, M: Monoid<T>> NodeStore<T> for AggBTree<T, M> {
+    type Error = Infallible;
+
+    fn t(&self) -> usize {
+        self.tree.t
+    }
+
+    fn root(&self) -> NodeId {
+        self.tree.root
+    }
+
+    fn set_root(&mut self, id: NodeId) {
+        NodeStore::set_root(&mut self.tree, id)
+    }
+
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn allocator(&self) -> &() {
+        &()
+    }
+
+    fn load(&mut self, id: NodeId) -> Result<Node<T>, Infallible> {
+        NodeStore::load(&mut self.tree, id)
+    }
+
+    fn store(&mut self, id: NodeId, node: Node<T>) -> Result<(), Infallible> {
+        NodeStore::store(&mut self.tree, id, node)
+    }
+
+    fn unload(&mut self, id: NodeId, node: Node<T>) {
+        NodeStore::unload(&mut self.tree, id, node)
+    }
+
+    fn count(&mut self, id: NodeId) -> Result<usize, Infallible> {
+        NodeStore::count(&mut self.tree, id)
+    }
+
+    fn alloc(&mut self, node: Node<T>) -> Result<NodeId, Infallible> {
+        let id = NodeStore::alloc(&mut self.tree, node)?;
+
+        if id as usize == self.aggs.len() {
+            self.aggs.push(self.monoid.identity());
+        }
+
+        Ok(id)
+    }
+
+    fn empty(&mut self) -> Node<T> {
+        NodeStore::empty(&mut self.tree)
+    }
+
+    fn record(&self, event: Event) {
+        NodeStore::record(&self.tree, event)
+    }
+
+    fn observe(&mut self, change: Change<'_, T>) {
+        NodeStore::observe(&mut self.tree, change)
+    }
+
+    fn free(&mut self, id: NodeId) -> Result<(), Infallible> {
+        self.aggs[id as usize] = self.monoid.identity();
+
+        NodeStore::free(&mut self.tree, id)
+    }
+
+    fn refresh(&mut self, id: NodeId) -> Result<(), Infallible> {
+        NodeStore::refresh(&mut self.tree, id)?;
+        self.refresh_agg(id);
+
+        Ok(())
+    }
+}
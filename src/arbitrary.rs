@@ -0,0 +1,201 @@
+use std::fmt::Debug;
+
+use proptest::arbitrary::{any, Arbitrary};
+use proptest::bool::weighted;
+use proptest::collection::vec;
+use proptest::sample::{select, Index};
+use proptest::strategy::{BoxedStrategy, Just, Strategy, Union};
+use proptest::test_runner::{Config, RngAlgorithm, TestCaseError, TestError, TestRng, TestRunner};
+
+use crate::store_script::{ScriptOp, StoreScript, SCRIPT_KEYS, SCRIPT_OPS};
+use crate::{BTree, SharedMap};
+
+/**
+ * operations applied to build random tree, at most
+ */
+const MAX_OPS: usize = 200;
+
+/**
+ * kind of tree built by BTree::arbitrary_shaped
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Empty,
+    /**
+     * root is the only node
+     */
+    Leaf,
+    /**
+     * bulk loaded with t - 1 keys in every node below root
+     */
+    MinFill,
+    /**
+     * inserts and deletes of a few distinct keys
+     */
+    Duplicates,
+    /**
+     * inserts and deletes of random keys
+     */
+    Random,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 5] = [Shape::Empty, Shape::Leaf, Shape::MinFill, Shape::Duplicates, Shape::Random];
+}
+
+/**
+ * operation of random sequence: delete of key inserted before, picked by index, or insert of key
+ * delete with nothing inserted yet inserts its key instead
+ */
+type RandomOp<T> = (bool, Index, T);
+
+/**
+ * about every fourth operation deletes
+ */
+fn random_ops<T: Debug + 'static>(key: impl Strategy<Value = T> + 'static) -> impl Strategy<Value = Vec<RandomOp<T>>> {
+    vec((weighted(0.25), any::<Index>(), key), 0..MAX_OPS)
+}
+
+impl<T: Arbitrary + Ord + Clone + 'static> BTree<T> {
+    /**
+     * strategy of trees of shape with t in 2..=8, built by random operations, so invariants hold by construction
+     * trees shrink with their operations: operations are dropped and keys shrunk, t goes toward 2
+     */
+    pub fn arbitrary_shaped(shape: Shape) -> BoxedStrategy<BTree<T>> {
+        match shape {
+            Shape::Empty => (2..=8usize).prop_map(BTree::new).boxed(),
+            Shape::Leaf => (2..=8usize)
+                .prop_flat_map(|t| (Just(t), vec(any::<T>(), 1..2 * t)))
+                .prop_map(|(t, keys)| Self::rebuilt(t, keys))
+                .boxed(),
+            Shape::MinFill => (2..=8usize, vec(any::<T>(), 0..MAX_OPS))
+                .prop_map(|(t, mut keys)| {
+                    keys.sort();
+
+                    BTree::from_sorted(t, keys, 0.0)
+                })
+                .boxed(),
+            Shape::Duplicates => (2..=8usize, vec(any::<T>(), 1..=3))
+                .prop_flat_map(|(t, pool)| (Just(t), random_ops(select(pool))))
+                .prop_map(|(t, ops)| Self::applied(t, ops))
+                .boxed(),
+            Shape::Random => (2..=8usize, random_ops(any::<T>()))
+                .prop_map(|(t, ops)| Self::applied(t, ops))
+                .boxed(),
+        }
+    }
+
+    fn applied(t: usize, ops: Vec<RandomOp<T>>) -> BTree<T> {
+        let mut tree = BTree::new(t);
+        let mut inserted = Vec::new();
+
+        for (delete, victim, key) in ops {
+            if delete && !inserted.is_empty() {
+                let victim: T = inserted.swap_remove(victim.index(inserted.len()));
+
+                tree.delete(&victim);
+            } else {
+                inserted.push(key.clone());
+                tree.insert(key);
+            }
+        }
+
+        tree
+    }
+
+    /**
+     * inserts keys in order into empty tree
+     */
+    fn rebuilt(t: usize, keys: Vec<T>) -> BTree<T> {
+        let mut tree = BTree::new(t);
+
+        for key in keys {
+            tree.insert(key);
+        }
+
+        tree
+    }
+}
+
+impl<T: Arbitrary + Ord + Clone + 'static> Arbitrary for BTree<T> {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<BTree<T>>;
+
+    /**
+     * each shape is equally likely, shrinking goes toward earlier shapes, so toward empty tree
+     */
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        Union::new(Shape::ALL.map(BTree::arbitrary_shaped)).boxed()
+    }
+}
+
+impl<K, V> Arbitrary for SharedMap<K, V>
+where
+    K: Arbitrary + Ord + Clone + Debug + 'static,
+    V: Arbitrary + Clone + Debug + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<SharedMap<K, V>>;
+
+    /**
+     * t in 2..=8, inserts of random entries and removes of keys inserted before, shrinks like BTree
+     */
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (2..=8usize, random_ops((any::<K>(), any::<V>())))
+            .prop_map(|(t, ops)| {
+                let map = SharedMap::new(t);
+                let mut inserted = Vec::new();
+
+                for (remove, victim, (key, value)) in ops {
+                    if remove && !inserted.is_empty() {
+                        map.remove(&inserted.swap_remove(victim.index(inserted.len())));
+                    } else {
+                        inserted.push(key.clone());
+                        map.insert(key, value);
+                    }
+                }
+
+                map
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for StoreScript {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<StoreScript>;
+
+    /**
+     * t in 2..=8, about every third operation is delete, shrinks toward fewer operations and t = 2
+     */
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let op = (weighted(1.0 / 3.0), 0..SCRIPT_KEYS).prop_map(|(delete, key)| {
+            if delete {
+                ScriptOp::Delete(key)
+            } else {
+                ScriptOp::Insert(key)
+            }
+        });
+
+        (2..=8usize, vec(op, 0..SCRIPT_OPS)).prop_map(|(t, ops)| StoreScript { t, ops }).boxed()
+    }
+}
+
+/**
+ * proptest run of cases random store scripts generated from seed, both stores run the same insert and delete,
+ * see NodeStore, so a failure names the store whose part of it broke, failing script comes shrunk
+ */
+pub fn check_stores(cases: u32, seed: u64) -> Result<(), TestError<StoreScript>> {
+    let mut bytes = [0; 32];
+
+    bytes[..8].copy_from_slice(&seed.to_le_bytes());
+
+    let config = Config {
+        cases,
+        failure_persistence: None,
+        ..Config::default()
+    };
+    let mut runner = TestRunner::new_with_rng(config, TestRng::from_seed(RngAlgorithm::ChaCha, &bytes));
+
+    runner.run(&any::<StoreScript>(), |script| script.run().map_err(TestCaseError::fail))
+}
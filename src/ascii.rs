@@ -0,0 +1,77 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Write};
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::{BTree, Comparator, NodeLayout};
+
+/**
+ * keys shown per node, the rest is counted
+ */
+const MAX_KEYS: usize = 8;
+
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    /**
+     * draws tree top-down like tree(1) draws directories, one node per line indented by depth,
+     * keys are written with Debug and joined with commas, wide nodes show first MAX_KEYS keys and count the rest
+     * nodes are visited with explicit stack, so depth of tree does not grow call stack
+     */
+    pub fn write_ascii(&self, mut w: impl Write) -> fmt::Result {
+        let mut stack = vec![(self.root, String::new(), None)];
+
+        while let Some((id, prefix, last)) = stack.pop() {
+            let node = self.node(id);
+            let shown: Vec<String> = node.keys.iter().take(MAX_KEYS).map(|key| format!("{:?}", key)).collect();
+            let rest = node.keys.len() - shown.len();
+            let branch = match last {
+                None => "",
+                Some(true) => "└── ",
+                Some(false) => "├── ",
+            };
+
+            write!(w, "{}{}[{}", prefix, branch, shown.join(", "))?;
+
+            if rest > 0 {
+                write!(w, ", … +{}", rest)?;
+            }
+
+            writeln!(w, "]")?;
+
+            let inner = match last {
+                None => prefix,
+                Some(true) => prefix + "    ",
+                Some(false) => prefix + "│   ",
+            };
+            let count = node.children.len();
+
+            stack.extend(
+                node.children
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(i, child)| (*child, inner.clone(), Some(i + 1 == count))),
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn to_ascii_string(&self) -> String {
+        let mut out = String::new();
+
+        self.write_ascii(&mut out).unwrap();
+
+        out
+    }
+
+    /**
+     * see write_ascii, drawing is built in memory first
+     */
+    #[cfg(feature = "std")]
+    pub fn print_ascii(&self, mut w: impl io::Write) -> io::Result<()> {
+        w.write_all(self.to_ascii_string().as_bytes())
+    }
+}
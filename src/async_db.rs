@@ -0,0 +1,210 @@
+use std::future::Future;
+use std::ops::{Bound, RangeBounds};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use crate::batch::WriteBatch;
+use crate::db::Db;
+use crate::error::Result;
+
+/**
+ * worker threads of AsyncDb::new
+ */
+pub const DEFAULT_WORKERS: usize = 2;
+
+type Job = Box<dyn FnOnce(&mut Db) + Send>;
+
+type Operation<R> = Box<dyn FnOnce(&mut Db) -> R + Send>;
+
+type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/**
+ * result of operation, done is set once job is gone, with result or by panic
+ */
+struct Slot<R> {
+    result: Option<R>,
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/**
+ * end of job, fills slot and wakes future even when operation panics
+ */
+struct Completion<R>(Arc<Mutex<Slot<R>>>);
+
+impl<R> Drop for Completion<R> {
+    fn drop(&mut self) {
+        let mut slot = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        slot.done = true;
+
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/**
+ * handle of database for async code, operations run on pool of worker threads and futures only wait for them,
+ * so no method blocks thread polling it, whatever runtime it belongs to, database is locked per operation
+ * handle is cloned into tasks, the last one gone lets workers finish queued operations and drop database,
+ * which flushes it, call flush before to see its errors
+ * every method is cancellation safe: operation of dropped future is either never started or done whole,
+ * so select and timeout never leave half of batch applied, see DbFuture
+ */
+#[derive(Clone)]
+pub struct AsyncDb {
+    jobs: Arc<Mutex<Sender<Job>>>,
+}
+
+impl AsyncDb {
+    pub fn new(db: Db) -> AsyncDb {
+        AsyncDb::with_workers(db, DEFAULT_WORKERS)
+    }
+
+    /**
+     * pool of workers threads, at least one, they take operations in order they are started
+     */
+    pub fn with_workers(db: Db, workers: usize) -> AsyncDb {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let db = Arc::new(Mutex::new(db));
+
+        for _ in 0..workers.max(1) {
+            let receiver = Arc::clone(&receiver);
+            let db = Arc::clone(&db);
+
+            thread::spawn(move || AsyncDb::work(&receiver, &db));
+        }
+
+        AsyncDb {
+            jobs: Arc::new(Mutex::new(sender)),
+        }
+    }
+
+    fn work(receiver: &Mutex<Receiver<Job>>, db: &Mutex<Db>) {
+        loop {
+            let job = receiver.lock().unwrap_or_else(PoisonError::into_inner).recv();
+            let Ok(job) = job else {
+                return;
+            };
+
+            let mut db = db.lock().unwrap_or_else(PoisonError::into_inner);
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| job(&mut db)));
+        }
+    }
+
+    /**
+     * runs f with database on worker, see DbFuture
+     */
+    pub fn run<R: Send + 'static>(&self, f: impl FnOnce(&mut Db) -> R + Send + 'static) -> DbFuture<R> {
+        DbFuture {
+            jobs: Arc::clone(&self.jobs),
+            job: Some(Box::new(f)),
+            slot: Arc::new(Mutex::new(Slot {
+                result: None,
+                done: false,
+                waker: None,
+            })),
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> DbFuture<Result<Option<Vec<u8>>>> {
+        let key = key.to_vec();
+
+        self.run(move |db| db.get(&key))
+    }
+
+    pub fn put(&self, key: &[u8], value: &[u8]) -> DbFuture<Result<()>> {
+        let key = key.to_vec();
+        let value = value.to_vec();
+
+        self.run(move |db| db.insert(&key, &value))
+    }
+
+    /**
+     * returns status of operation: did element remove
+     */
+    pub fn remove(&self, key: &[u8]) -> DbFuture<Result<bool>> {
+        let key = key.to_vec();
+
+        self.run(move |db| db.delete(&key))
+    }
+
+    /**
+     * see Db::write, batch is applied whole or not at all, also when future is dropped while worker applies it
+     */
+    pub fn write(&self, batch: WriteBatch) -> DbFuture<Result<()>> {
+        self.run(move |db| db.write(&batch))
+    }
+
+    /**
+     * entries with keys in range in key order, see Db::range
+     */
+    pub fn scan<K: AsRef<[u8]> + ?Sized>(&self, range: impl RangeBounds<K>) -> DbFuture<Result<Entries>> {
+        let bounds: (Bound<Vec<u8>>, Bound<Vec<u8>>) = (
+            range.start_bound().map(|start| start.as_ref().to_vec()),
+            range.end_bound().map(|end| end.as_ref().to_vec()),
+        );
+
+        self.run(move |db| db.range::<Vec<u8>>(bounds)?.collect())
+    }
+
+    pub fn flush(&self) -> DbFuture<Result<()>> {
+        self.run(Db::flush)
+    }
+}
+
+/**
+ * operation of AsyncDb, it is queued once future is polled first and is done by worker thread, which wakes task
+ * cancellation: future dropped before first poll does nothing, dropped after it does not stop operation,
+ * it runs to its end without anyone to take result, so write is applied whole or, failing, as Db::write leaves it,
+ * never cut in the middle by drop
+ * panic of operation is resumed by poll
+ */
+pub struct DbFuture<R> {
+    jobs: Arc<Mutex<Sender<Job>>>,
+    job: Option<Operation<R>>,
+    slot: Arc<Mutex<Slot<R>>>,
+}
+
+impl<R> Unpin for DbFuture<R> {}
+
+impl<R: Send + 'static> Future for DbFuture<R> {
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        {
+            let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+
+            if let Some(result) = slot.result.take() {
+                return Poll::Ready(result);
+            }
+
+            if slot.done {
+                panic!("database operation panicked");
+            }
+
+            slot.waker = Some(cx.waker().clone());
+        }
+
+        if let Some(f) = self.job.take() {
+            let completion = Completion(Arc::clone(&self.slot));
+            let job: Job = Box::new(move |db| {
+                let result = f(db);
+
+                completion.0.lock().unwrap_or_else(PoisonError::into_inner).result = Some(result);
+            });
+
+            if self.jobs.lock().unwrap_or_else(PoisonError::into_inner).send(job).is_err() {
+                panic!("workers of database are gone");
+            }
+        }
+
+        Poll::Pending
+    }
+}
@@ -0,0 +1,253 @@
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::pager::PageId;
+use crate::snapshot::Checksummed;
+
+/**
+ * manifest layout, integers are little-endian:
+ * magic (8 bytes), version (u32), fields (see below), crc32 of everything before it (u32)
+ * fields are file id (u64) and generation (u64) of source database, generation of base (u64, u64::MAX for
+ * full backup), page size (u32), page count (u32), sequence number (u64) and log segment (u64) of copied state
+ *
+ * delta layout: magic (8 bytes), version (u32), fields as in manifest, number of pages (u32),
+ * pages as (page id u32, page contents), crc32 of everything before it (u32)
+ */
+const MANIFEST_MAGIC: &[u8; 8] = b"SRDBMNFT";
+const DELTA_MAGIC: &[u8; 8] = b"SRDBDLTA";
+const VERSION: u32 = 1;
+const FULL: u64 = u64::MAX;
+
+/**
+ * describes what backup holds, written next to it by Backup::finish and Db::backup_incremental
+ * delta made on top of backup holds pages of source written by commits after its generation
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupManifest {
+    /**
+     * id of source database file, vacuum makes new file with another id
+     */
+    pub file_id: u64,
+    /**
+     * commit of source backup is a copy of
+     */
+    pub generation: u64,
+    /**
+     * generation of backup delta is made on top of, None for full backup
+     */
+    pub base: Option<u64>,
+    /**
+     * size of page in file, checksum trailer included
+     */
+    pub page_size: usize,
+    pub page_count: PageId,
+    pub seq: u64,
+    pub wal_segment: u64,
+}
+
+/**
+ * backup ending early is invalid, not an i/o failure
+ */
+fn read_error(error: io::Error) -> Error {
+    match error.kind() {
+        io::ErrorKind::UnexpectedEof => Error::InvalidBackup("it is truncated".to_string()),
+        _ => Error::Io(error),
+    }
+}
+
+impl BackupManifest {
+    /**
+     * manifest lives next to backup, with "-manifest" appended to its name
+     */
+    pub fn path_for(backup: &Path) -> PathBuf {
+        let mut name = OsString::from(backup.as_os_str());
+
+        name.push("-manifest");
+
+        PathBuf::from(name)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.base.is_none()
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<BackupManifest> {
+        let mut input = Checksummed::new(BufReader::new(File::open(path)?));
+
+        check_magic(&mut input, MANIFEST_MAGIC)?;
+
+        let manifest = BackupManifest::read_fields(&mut input)?;
+
+        check_checksum(input)?;
+
+        Ok(manifest)
+    }
+
+    /**
+     * writes manifest to new file at path and syncs it, fails if file exists
+     */
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let mut out = Checksummed::new(BufWriter::new(file));
+
+        out.write_all(MANIFEST_MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        self.write_fields(&mut out)?;
+        finish(out)
+    }
+
+    fn write_fields<W: Write>(&self, out: &mut Checksummed<W>) -> io::Result<()> {
+        out.write_all(&self.file_id.to_le_bytes())?;
+        out.write_all(&self.generation.to_le_bytes())?;
+        out.write_all(&self.base.unwrap_or(FULL).to_le_bytes())?;
+        out.write_all(&(self.page_size as u32).to_le_bytes())?;
+        out.write_all(&self.page_count.to_le_bytes())?;
+        out.write_all(&self.seq.to_le_bytes())?;
+        out.write_all(&self.wal_segment.to_le_bytes())
+    }
+
+    fn read_fields<R: Read>(input: &mut Checksummed<R>) -> Result<BackupManifest> {
+        let file_id = input.read_u64().map_err(read_error)?;
+        let generation = input.read_u64().map_err(read_error)?;
+        let base = input.read_u64().map_err(read_error)?;
+        let page_size = input.read_u32().map_err(read_error)?;
+        let page_count = input.read_u32().map_err(read_error)?;
+        let seq = input.read_u64().map_err(read_error)?;
+        let wal_segment = input.read_u64().map_err(read_error)?;
+
+        Ok(BackupManifest {
+            file_id,
+            generation,
+            base: Some(base).filter(|&base| base != FULL),
+            page_size: page_size as usize,
+            page_count,
+            seq,
+            wal_segment,
+        })
+    }
+}
+
+fn check_magic<R: Read>(input: &mut Checksummed<R>, magic: &[u8; 8]) -> Result<()> {
+    if &input.read_exact::<8>().map_err(read_error)? != magic {
+        return Err(Error::InvalidBackup("wrong magic bytes".to_string()));
+    }
+
+    let version = input.read_u32().map_err(read_error)?;
+
+    if version != VERSION {
+        return Err(Error::InvalidBackup(format!("unsupported version {}, expected {}", version, VERSION)));
+    }
+
+    Ok(())
+}
+
+fn check_checksum<R: Read>(mut input: Checksummed<R>) -> Result<()> {
+    let actual = input.crc.finish();
+    let mut stored = [0u8; 4];
+
+    input.inner.read_exact(&mut stored).map_err(read_error)?;
+
+    let expected = u32::from_le_bytes(stored);
+
+    if expected != actual {
+        return Err(Error::InvalidBackup(format!(
+            "checksum mismatch: stored {:08x}, computed {:08x}",
+            expected, actual
+        )));
+    }
+
+    Ok(())
+}
+
+/**
+ * writes checksum and syncs file
+ */
+fn finish(mut out: Checksummed<BufWriter<File>>) -> Result<()> {
+    let checksum = out.crc.finish();
+
+    out.inner.write_all(&checksum.to_le_bytes())?;
+    out.inner.into_inner().map_err(|error| error.into_error())?.sync_all()?;
+
+    Ok(())
+}
+
+/**
+ * writes delta file, number of pages is known upfront
+ */
+pub(crate) struct DeltaWriter {
+    out: Checksummed<BufWriter<File>>,
+}
+
+impl DeltaWriter {
+    /**
+     * creates delta file at path, fails if file exists
+     */
+    pub(crate) fn create(path: &Path, manifest: &BackupManifest, pages: usize) -> Result<DeltaWriter> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let mut out = Checksummed::new(BufWriter::new(file));
+
+        out.write_all(DELTA_MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        manifest.write_fields(&mut out)?;
+        out.write_all(&(pages as u32).to_le_bytes())?;
+
+        Ok(DeltaWriter { out })
+    }
+
+    pub(crate) fn page(&mut self, page_id: PageId, data: &[u8]) -> Result<()> {
+        self.out.write_all(&page_id.to_le_bytes())?;
+        self.out.write_all(data)?;
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Result<()> {
+        finish(self.out)
+    }
+}
+
+/**
+ * reads delta file page by page, checksum is checked by finish
+ */
+pub(crate) struct DeltaReader {
+    input: Checksummed<BufReader<File>>,
+}
+
+impl DeltaReader {
+    /**
+     * returns reader with manifest of delta and number of pages in it
+     */
+    pub(crate) fn open(path: &Path) -> Result<(DeltaReader, BackupManifest, usize)> {
+        let mut input = Checksummed::new(BufReader::new(File::open(path)?));
+
+        check_magic(&mut input, DELTA_MAGIC)?;
+
+        let manifest = BackupManifest::read_fields(&mut input)?;
+        let pages = input.read_u32().map_err(read_error)? as usize;
+
+        if manifest.is_full() {
+            return Err(Error::InvalidBackup("delta names no base".to_string()));
+        }
+
+        Ok((DeltaReader { input }, manifest, pages))
+    }
+
+    /**
+     * reads the next page into buf and returns its id
+     */
+    pub(crate) fn page(&mut self, buf: &mut Vec<u8>) -> Result<PageId> {
+        let page_id = self.input.read_u32().map_err(read_error)?;
+        let len = buf.len() as u64;
+
+        self.input.read_bytes(len, buf).map_err(read_error)?;
+
+        Ok(page_id)
+    }
+
+    pub(crate) fn finish(self) -> Result<()> {
+        check_checksum(self.input)
+    }
+}
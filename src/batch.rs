@@ -0,0 +1,193 @@
+use crate::codec::{take, Codec};
+
+const PUT: u8 = 1;
+const DELETE: u8 = 2;
+const CREATE_TABLE: u8 = 3;
+const DROP_TABLE: u8 = 4;
+const TABLE_PUT: u8 = 5;
+const TABLE_DELETE: u8 = 6;
+
+/**
+ * single change of Db, Put and Delete change its default tree, the rest change named tables
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Op {
+    Put { key: Vec<u8>, value: Vec<u8> },
+    Delete { key: Vec<u8> },
+    CreateTable { name: String },
+    DropTable { name: String },
+    TablePut { table: String, key: Vec<u8>, value: Vec<u8> },
+    TableDelete { table: String, key: Vec<u8> },
+}
+
+/**
+ * group of changes applied to Db as one write ahead log record,
+ * after crash either all of them are visible or none
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WriteBatch {
+    ops: Vec<Op>,
+}
+
+impl WriteBatch {
+    pub fn new() -> WriteBatch {
+        WriteBatch { ops: vec![] }
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.ops.push(Op::Put {
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.ops.push(Op::Delete { key: key.to_vec() });
+    }
+
+    /**
+     * table must not exist when batch reaches it, see Db::create_table
+     */
+    pub fn create_table(&mut self, name: &str) {
+        self.ops.push(Op::CreateTable { name: name.to_string() });
+    }
+
+    pub fn drop_table(&mut self, name: &str) {
+        self.ops.push(Op::DropTable { name: name.to_string() });
+    }
+
+    pub fn put_in(&mut self, table: &str, key: &[u8], value: &[u8]) {
+        self.ops.push(Op::TablePut {
+            table: table.to_string(),
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+    }
+
+    pub fn delete_in(&mut self, table: &str, key: &[u8]) {
+        self.ops.push(Op::TableDelete {
+            table: table.to_string(),
+            key: key.to_vec(),
+        });
+    }
+
+    /**
+     * adds op as it is
+     */
+    pub fn push(&mut self, op: Op) {
+        self.ops.push(op);
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+
+    /**
+     * changes in order they were added
+     */
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+}
+
+fn take_bytes(bytes: &mut &[u8]) -> Option<Vec<u8>> {
+    let len = u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()) as usize;
+
+    Some(take(bytes, len)?.to_vec())
+}
+
+fn take_name(bytes: &mut &[u8]) -> Option<String> {
+    String::from_utf8(take_bytes(bytes)?).ok()
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/**
+ * op count (u32), then ops as tag (u8), table name for table ops, key and for puts value,
+ * all as (length u32, bytes)
+ */
+impl Codec for WriteBatch {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.ops.len() as u32).to_le_bytes());
+
+        for op in self.ops.iter() {
+            match op {
+                Op::Put { key, value } => {
+                    out.push(PUT);
+                    put_bytes(out, key);
+                    put_bytes(out, value);
+                }
+                Op::Delete { key } => {
+                    out.push(DELETE);
+                    put_bytes(out, key);
+                }
+                Op::CreateTable { name } => {
+                    out.push(CREATE_TABLE);
+                    put_bytes(out, name.as_bytes());
+                }
+                Op::DropTable { name } => {
+                    out.push(DROP_TABLE);
+                    put_bytes(out, name.as_bytes());
+                }
+                Op::TablePut { table, key, value } => {
+                    out.push(TABLE_PUT);
+                    put_bytes(out, table.as_bytes());
+                    put_bytes(out, key);
+                    put_bytes(out, value);
+                }
+                Op::TableDelete { table, key } => {
+                    out.push(TABLE_DELETE);
+                    put_bytes(out, table.as_bytes());
+                    put_bytes(out, key);
+                }
+            }
+        }
+    }
+
+    fn decode(mut bytes: &[u8]) -> Option<Self> {
+        let bytes = &mut bytes;
+        let count = u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap());
+        let mut ops = vec![];
+
+        for _ in 0..count {
+            let op = match take(bytes, 1)?[0] {
+                PUT => Op::Put {
+                    key: take_bytes(bytes)?,
+                    value: take_bytes(bytes)?,
+                },
+                DELETE => Op::Delete { key: take_bytes(bytes)? },
+                CREATE_TABLE => Op::CreateTable { name: take_name(bytes)? },
+                DROP_TABLE => Op::DropTable { name: take_name(bytes)? },
+                TABLE_PUT => Op::TablePut {
+                    table: take_name(bytes)?,
+                    key: take_bytes(bytes)?,
+                    value: take_bytes(bytes)?,
+                },
+                TABLE_DELETE => Op::TableDelete {
+                    table: take_name(bytes)?,
+                    key: take_bytes(bytes)?,
+                },
+                _ => return None,
+            };
+
+            ops.push(op);
+        }
+
+        if !bytes.is_empty() {
+            return None;
+        }
+
+        Some(WriteBatch { ops })
+    }
+}
@@ -0,0 +1,255 @@
+use clap::{Parser, Subcommand};
+use srdb::SyncMode;
+
+use crate::bench::Workload;
+use crate::config::{sync_mode, LogLevel, ProtocolName};
+use crate::import::Format;
+
+/**
+ * command line of srdb, without command it starts repl
+ */
+#[derive(Parser)]
+#[command(
+    name = "srdb",
+    about = "b-tree database on one file",
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    after_help = "with signals feature serve, replicate and import stop on SIGINT or SIGTERM, finish what is in flight \
+and checkpoint database
+
+exit codes: 0 success, 1 key not found, 2 usage error, 3 database or i/o error, 4 verify found problems, \
+130 import interrupted"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub repl: ReplArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    #[command(about = "inserts entry, file is created if missing")]
+    Put(PutArgs),
+    #[command(about = "prints value")]
+    Get(KeyArgs),
+    #[command(about = "removes entry")]
+    Del(KeyArgs),
+    #[command(about = "prints entries in key order")]
+    Scan(ScanArgs),
+    #[command(about = "writes portable dump to file or stdout")]
+    Dump(DumpArgs),
+    #[command(about = "creates database from dump in file or on stdin")]
+    Restore(RestoreArgs),
+    #[command(about = "prints disk usage, shape of trees and cache size, also while file is in use")]
+    Stats(FileArgs),
+    #[command(about = "checks every page and lists fixes for problems found")]
+    Verify(VerifyArgs),
+    #[command(about = "rewrites file without free pages")]
+    Vacuum(FileArgs),
+    #[command(about = "loads lines of file or stdin given as -")]
+    Import(ImportArgs),
+    #[command(about = "runs workload and reports throughput, latency percentiles and disk stats")]
+    Bench(BenchArgs),
+    #[command(about = "serves database over tcp")]
+    Serve(ServeArgs),
+    #[command(about = "checks config and flags as serve would and prints settings it would start with")]
+    ConfigCheck(ConfigCheckArgs),
+    #[command(about = "follows primary, applying its batches in order to local copy nothing else writes")]
+    Replicate(ReplicateArgs),
+    #[command(about = "interactive shell over in-memory tree, the default without command")]
+    Repl(ReplArgs),
+}
+
+#[derive(clap::Args)]
+pub struct PutArgs {
+    pub file: String,
+    pub key: String,
+    pub value: String,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct KeyArgs {
+    pub file: String,
+    pub key: String,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct FileArgs {
+    pub file: String,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct ScanArgs {
+    pub file: String,
+    #[arg(long, help = "only keys starting with prefix", default_value = "")]
+    pub prefix: String,
+    #[arg(long, help = "at most this many entries")]
+    pub limit: Option<usize>,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct DumpArgs {
+    pub file: String,
+    #[arg(help = "dump file, stdout without it")]
+    pub dump: Option<String>,
+    #[arg(long, help = "only these tables, comma-separated", value_name = "NAME,...", value_delimiter = ',')]
+    pub table: Option<Vec<String>>,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct RestoreArgs {
+    pub file: String,
+    #[arg(help = "dump file, stdin without it")]
+    pub dump: Option<String>,
+    #[arg(long, help = "only these tables, comma-separated", value_name = "NAME,...", value_delimiter = ',')]
+    pub table: Option<Vec<String>>,
+    #[arg(long, help = "replaces existing file")]
+    pub force: bool,
+    #[arg(long, help = "compares database with dump after")]
+    pub verify: bool,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct VerifyArgs {
+    pub file: String,
+    #[arg(long, help = "reads every value too")]
+    pub full: bool,
+    #[arg(long, help = "lists fixes for problems found")]
+    pub repair: bool,
+    #[arg(long, help = "applies fixes of --repair to file", requires = "repair")]
+    pub in_place: bool,
+    #[arg(long, help = "copies entries which can still be read into new file", value_name = "OUT")]
+    pub salvage: Option<String>,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct ImportArgs {
+    #[arg(help = "file to read, - for stdin")]
+    pub input: String,
+    pub file: String,
+    #[arg(long, help = "lines are keys, or tsv keys and values", default_value = "lines", value_parser = Format::parse)]
+    pub format: Format,
+    #[arg(long, help = "bulk loads empty database from file in key order")]
+    pub sorted: bool,
+    #[arg(long, help = "rows per commit", value_name = "ROWS")]
+    pub batch: Option<usize>,
+    #[arg(long, help = "lines to skip, to resume failed import", value_name = "LINES", default_value_t = 0)]
+    pub skip: u64,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct BenchArgs {
+    #[arg(help = "database file, created if missing", required_unless_present = "memory", conflicts_with = "memory")]
+    pub file: Option<String>,
+    #[arg(long, help = "database lives in memory and is dropped at the end")]
+    pub memory: bool,
+    #[arg(
+        long,
+        help = "fillrandom, fillseq, readrandom or readwrite",
+        default_value = "fillrandom",
+        value_parser = Workload::parse
+    )]
+    pub workload: Workload,
+    #[arg(long, default_value_t = 100_000)]
+    pub ops: u64,
+    #[arg(long, help = "bytes of key", default_value_t = 16)]
+    pub keysize: usize,
+    #[arg(long, help = "bytes of value", default_value_t = 100)]
+    pub valuesize: usize,
+    #[arg(long, default_value_t = 1)]
+    pub threads: u64,
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+    #[arg(long, help = "branching factor of new database")]
+    pub t: Option<usize>,
+    #[arg(long)]
+    pub cache_pages: Option<usize>,
+    #[arg(long, help = "always, commit or off", value_parser = sync_mode)]
+    pub sync: Option<SyncMode>,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+/**
+ * flags of serve, each overrides the same setting of --config file, see ServeConfig
+ */
+#[derive(clap::Args)]
+#[command(about = None, long_about = None)]
+pub struct ServeArgs {
+    #[arg(help = "database file, or db of config")]
+    pub file: Option<String>,
+    #[arg(long, help = "file of key = value lines with the same settings")]
+    pub config: Option<String>,
+    #[arg(long, help = "address to listen on, 127.0.0.1:7878 by default", value_name = "ADDRESS")]
+    pub bind: Option<String>,
+    #[arg(long)]
+    pub cache_pages: Option<usize>,
+    #[arg(long, help = "always, commit or off", value_parser = sync_mode)]
+    pub sync: Option<SyncMode>,
+    #[arg(long)]
+    pub read_only: bool,
+    #[arg(long, help = "clients served at once, 64 by default")]
+    pub max_connections: Option<usize>,
+    #[arg(long, help = "seconds before silent client is dropped, 300 by default, 0 never", value_name = "SECONDS")]
+    pub idle_timeout: Option<u64>,
+    #[arg(long, help = "off, error, warn or info, info by default", value_parser = LogLevel::parse)]
+    pub log_level: Option<LogLevel>,
+    #[arg(long, help = "text, resp or http", value_parser = ProtocolName::parse, conflicts_with_all = ["resp", "http"])]
+    pub protocol: Option<ProtocolName>,
+    #[arg(long, help = "subset of redis protocol in place of line protocol", conflicts_with = "http")]
+    pub resp: bool,
+    #[arg(long, help = "json over http, when built with http feature")]
+    pub http: bool,
+    #[arg(long, help = "address followers connect to", value_name = "ADDRESS")]
+    pub replication_bind: Option<String>,
+    #[arg(long, help = "bytes of batches kept for followers behind, 64 MiB by default", value_name = "BYTES")]
+    pub replication_backlog: Option<u64>,
+}
+
+#[derive(clap::Args)]
+pub struct ConfigCheckArgs {
+    #[command(flatten)]
+    pub serve: ServeArgs,
+    #[arg(long, help = "prints json")]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct ReplicateArgs {
+    pub file: String,
+    #[arg(long, help = "host:port of primary")]
+    pub from: String,
+    #[arg(long, help = "starts missing copy from backup of primary", value_name = "BACKUP")]
+    pub bootstrap: Option<String>,
+    #[arg(long, help = "copies primary when it no longer has batches copy needs")]
+    pub resync: bool,
+    #[arg(long, help = "stops when connection ends")]
+    pub once: bool,
+}
+
+#[derive(clap::Args)]
+pub struct ReplArgs {
+    #[arg(long, help = "keys are strings, not integers")]
+    pub str: bool,
+    #[arg(long, help = "prints tree after every change")]
+    pub trace: bool,
+    #[arg(long, help = "highlights changed nodes and prints result of invariant check")]
+    pub watch: bool,
+}
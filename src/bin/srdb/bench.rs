@@ -0,0 +1,403 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use srdb::{Db, MemStorage, SrdbOptions};
+
+use crate::args::BenchArgs;
+use crate::commands::{print_aligned, stats_fields};
+use crate::{json, Failure};
+
+/**
+ * sub-buckets per power of two in histogram, as bits, percentiles are off by 1/8 at most
+ */
+const SUB_BITS: u32 = 3;
+
+/**
+ * buckets covering every u64: values below 2 << SUB_BITS have one each, then SUB_BITS sub-buckets per power of two
+ */
+const BUCKETS: usize = (2 << SUB_BITS) + ((63 - SUB_BITS as usize) << SUB_BITS);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Workload {
+    /**
+     * inserts of random keys
+     */
+    FillRandom,
+    /**
+     * inserts of keys in ascending order
+     */
+    FillSeq,
+    /**
+     * gets of random keys of preloaded database
+     */
+    ReadRandom,
+    /**
+     * gets and inserts of random keys of preloaded database, half each
+     */
+    ReadWrite,
+}
+
+impl Workload {
+    pub fn parse(name: &str) -> Result<Workload, String> {
+        match name {
+            "fillrandom" => Ok(Workload::FillRandom),
+            "fillseq" => Ok(Workload::FillSeq),
+            "readrandom" => Ok(Workload::ReadRandom),
+            "readwrite" => Ok(Workload::ReadWrite),
+            other => Err(format!("unknown workload {}, expected fillrandom, fillseq, readrandom or readwrite", other)),
+        }
+    }
+
+    fn reads(self) -> bool {
+        matches!(self, Workload::ReadRandom | Workload::ReadWrite)
+    }
+}
+
+/**
+ * latencies in nanoseconds counted in log-linear buckets, so memory does not grow with ops
+ */
+#[derive(Clone)]
+struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        Histogram {
+            counts: vec![0; BUCKETS],
+            total: 0,
+            max: 0,
+        }
+    }
+
+    fn bucket(value: u64) -> usize {
+        if value < 2 << SUB_BITS {
+            return value as usize;
+        }
+
+        let exp = 63 - value.leading_zeros();
+        let sub = (value >> (exp - SUB_BITS)) & ((1 << SUB_BITS) - 1);
+
+        (2 << SUB_BITS) + (((exp - SUB_BITS - 1) as usize) << SUB_BITS) + sub as usize
+    }
+
+    /**
+     * the largest value falling into bucket
+     */
+    fn upper(bucket: usize) -> u64 {
+        if bucket < 2 << SUB_BITS {
+            return bucket as u64;
+        }
+
+        let exp = ((bucket - (2 << SUB_BITS)) >> SUB_BITS) as u32 + SUB_BITS + 1;
+        let sub = (bucket & ((1 << SUB_BITS) - 1)) as u64;
+        let width = 1u64 << (exp - SUB_BITS);
+
+        ((1 << SUB_BITS) + sub) * width + (width - 1)
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let nanos = latency.as_nanos().min(u64::MAX as u128) as u64;
+
+        self.counts[Histogram::bucket(nanos)] += 1;
+        self.total += 1;
+        self.max = self.max.max(nanos);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+
+    /**
+     * value at or below which share of recorded values is, upper bound of its bucket, 0 when empty
+     */
+    fn percentile(&self, share: f64) -> u64 {
+        let target = ((self.total as f64 * share).ceil() as u64).max(1);
+        let mut seen = 0;
+
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+
+            if seen >= target {
+                return Histogram::upper(bucket).min(self.max);
+            }
+        }
+
+        0
+    }
+}
+
+struct Config {
+    workload: Workload,
+    ops: u64,
+    key_size: usize,
+    value: Vec<u8>,
+    threads: u64,
+    seed: u64,
+}
+
+impl Config {
+    /**
+     * number zero-padded to key size, so byte order of keys is their numeric order
+     */
+    fn key(&self, n: u64) -> Vec<u8> {
+        format!("{:0width$}", n, width = self.key_size).into_bytes()
+    }
+}
+
+fn lock(db: &Mutex<Db>) -> MutexGuard<'_, Db> {
+    db.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/**
+ * share of ops given to thread, the first ops % threads threads take one more
+ */
+fn share(config: &Config, thread: u64) -> (u64, u64) {
+    let base = config.ops / config.threads;
+    let extra = config.ops % config.threads;
+    let start = thread * base + thread.min(extra);
+
+    (start, start + base + (thread < extra) as u64)
+}
+
+/**
+ * runs share of thread, every op is timed with wait for lock included
+ */
+fn worker(db: &Mutex<Db>, config: &Config, thread: u64) -> Result<Histogram, srdb::Error> {
+    let mut histogram = Histogram::new();
+    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(thread));
+    let (start, end) = share(config, thread);
+
+    for i in start..end {
+        let n = match config.workload {
+            Workload::FillSeq => i,
+            _ => rng.gen_range(0..config.ops),
+        };
+        let key = config.key(n);
+        let read = match config.workload {
+            Workload::ReadRandom => true,
+            Workload::ReadWrite => rng.gen_bool(0.5),
+            _ => false,
+        };
+
+        let started = Instant::now();
+
+        if read {
+            lock(db).get(&key)?;
+        } else {
+            lock(db).insert(&key, &config.value)?;
+        }
+
+        histogram.record(started.elapsed());
+    }
+
+    Ok(histogram)
+}
+
+/**
+ * fills empty database with every key read workloads ask for, in one bulk load
+ */
+fn preload(db: &mut Db, config: &Config) -> Result<u64, srdb::Error> {
+    if !db.is_empty() {
+        return Ok(0);
+    }
+
+    db.load_sorted(config.ops as usize, (0..config.ops).map(|n| Ok((config.key(n), config.value.clone()))))?;
+
+    Ok(config.ops)
+}
+
+fn options(args: &BenchArgs) -> SrdbOptions {
+    let mut options = SrdbOptions::new();
+
+    if let Some(t) = args.t {
+        options = options.branching_factor(t);
+    }
+
+    if let Some(cache_pages) = args.cache_pages {
+        options = options.cache_pages(cache_pages);
+    }
+
+    if let Some(sync_mode) = args.sync {
+        options = options.sync_mode(sync_mode);
+    }
+
+    options
+}
+
+/**
+ * drives database with generated workload in the spirit of db_bench and reports throughput, latency and disk stats
+ * keys are numbers zero-padded to --keysize, values are --valuesize random bytes, both depend on --seed only,
+ * so the same command repeats the same ops, order of ops of different threads aside
+ * read workloads bulk load every key first if database is empty, that is not timed
+ * database is file, created if missing, or with --memory lives in memory and is dropped at the end
+ */
+pub fn bench(args: &BenchArgs) -> Result<(), Failure> {
+    let workload = args.workload;
+    let ops = args.ops.max(1);
+    let key_size = args.keysize;
+    let value_size = args.valuesize;
+    let threads = args.threads.clamp(1, ops);
+    let seed = args.seed;
+
+    if key_size < (ops - 1).to_string().len() {
+        return Err(Failure::Usage(format!("keys of {} bytes can not number {} ops", key_size, ops)));
+    }
+
+    let mut value = vec![0; value_size];
+
+    StdRng::seed_from_u64(seed).fill_bytes(&mut value);
+
+    let config = Config {
+        workload,
+        ops,
+        key_size,
+        value,
+        threads,
+        seed,
+    };
+
+    let options = options(args);
+    let mut db = match &args.file {
+        Some(path) => options.open_or_create(path)?,
+        None => options.create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new()))?,
+    };
+
+    let preloaded = if workload.reads() { preload(&mut db, &config)? } else { 0 };
+
+    let db = Arc::new(Mutex::new(db));
+    let config = Arc::new(config);
+    let started = Instant::now();
+
+    let workers: Vec<_> = (0..threads)
+        .map(|thread| {
+            let db = db.clone();
+            let config = config.clone();
+
+            thread::spawn(move || worker(&db, &config, thread))
+        })
+        .collect();
+
+    let mut histogram = Histogram::new();
+
+    for worker in workers {
+        histogram.merge(&worker.join().expect("bench thread panicked")?);
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    let mut db = lock(&db);
+
+    db.flush()?;
+
+    let bytes = ops * (key_size + value_size) as u64;
+    let micros = |nanos: u64| format!("{:.1}", nanos as f64 / 1000.0);
+
+    let fields = vec![
+        ("ops", ops.to_string()),
+        ("threads", threads.to_string()),
+        ("preloaded", preloaded.to_string()),
+        ("seconds", format!("{:.3}", elapsed)),
+        ("ops_per_sec", format!("{:.0}", ops as f64 / elapsed)),
+        ("mb_per_sec", format!("{:.2}", bytes as f64 / elapsed / (1 << 20) as f64)),
+        ("p50_us", micros(histogram.percentile(0.5))),
+        ("p99_us", micros(histogram.percentile(0.99))),
+        ("p999_us", micros(histogram.percentile(0.999))),
+        ("max_us", micros(histogram.max)),
+    ];
+    let stats = stats_fields(&db.disk_stats());
+
+    if args.json {
+        let mut fields = fields;
+
+        fields.push(("stats", json::object(&stats)));
+        println!("{}", json::object(&fields));
+    } else {
+        let rows: Vec<(String, String)> =
+            fields.into_iter().chain(stats).map(|(name, value)| (name.to_string(), value)).collect();
+
+        print_aligned(&rows);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lcg(seed: u64) -> impl FnMut() -> u64 {
+        let mut state = seed;
+
+        move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            state >> 33
+        }
+    }
+
+    fn recorded(values: impl IntoIterator<Item = u64>) -> Histogram {
+        let mut histogram = Histogram::new();
+
+        for value in values {
+            histogram.record(Duration::from_nanos(value));
+        }
+
+        histogram
+    }
+
+    /**
+     * every value falls into bucket whose upper bound is at most 1/8 above it and below which the previous one ends
+     */
+    #[test]
+    fn buckets_cover_values_within_one_eighth() {
+        let mut next = lcg(7);
+        let values = (0..64).flat_map(|exp| [1u64 << exp, (1u64 << exp) - 1, (1u64 << exp) + 1]).chain([u64::MAX]);
+
+        for value in values.chain((0..10_000).map(|_| next() << (next() % 32))) {
+            let bucket = Histogram::bucket(value);
+
+            assert!(bucket < BUCKETS, "{}", value);
+            assert!(Histogram::upper(bucket) >= value, "{}", value);
+            assert!(Histogram::upper(bucket) - value <= value / 8, "{}", value);
+            assert!(bucket == 0 || Histogram::upper(bucket - 1) < value, "{}", value);
+        }
+    }
+
+    #[test]
+    fn percentiles_of_known_latencies() {
+        assert_eq!(Histogram::new().percentile(0.5), 0, "empty");
+
+        let small = recorded(0..16);
+
+        assert_eq!(small.percentile(0.5), 7, "values below 16 have buckets of their own");
+        assert_eq!(small.percentile(1.0), 15);
+        assert_eq!(small.percentile(0.0), 0);
+
+        let spread = recorded(1..=1000);
+
+        for (share, exact) in [(0.5, 500), (0.9, 900), (0.99, 990), (0.999, 999)] {
+            let percentile = spread.percentile(share);
+
+            assert!(percentile >= exact && percentile <= exact + exact / 8, "{}: {}", share, percentile);
+        }
+
+        assert_eq!(spread.percentile(1.0), 1000, "capped by max");
+
+        let mut merged = recorded(1..=500);
+
+        merged.merge(&recorded(501..=1000));
+
+        assert_eq!(merged.counts, spread.counts);
+        assert_eq!((merged.total, merged.max), (1000, 1000));
+    }
+}
@@ -0,0 +1,498 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use srdb::{Db, DiskStats, Problem, Repair, SrdbOptions, TreeDigest, VerifyMode, VerifyReport};
+
+use crate::args::{DumpArgs, FileArgs, KeyArgs, PutArgs, RestoreArgs, ScanArgs, VerifyArgs};
+use crate::{json, Failure};
+
+/**
+ * key and value as one line of text: bytes outside printable ascii, tab and backslash are escaped
+ */
+fn text_entry(key: &[u8], value: &[u8]) -> String {
+    format!("{}\t{}", key.escape_ascii(), value.escape_ascii())
+}
+
+fn json_entry(key: &[u8], value: &[u8]) -> String {
+    json::object(&[("key", json::string(key)), ("value", json::string(value))])
+}
+
+pub fn put(args: &PutArgs) -> Result<(), Failure> {
+    let mut db = Db::open_or_create(&args.file)?;
+
+    db.insert(args.key.as_bytes(), args.value.as_bytes())?;
+    db.close()?;
+
+    if args.json {
+        println!("{}", json::object(&[("key", json::string(args.key.as_bytes()))]));
+    }
+
+    Ok(())
+}
+
+/**
+ * value is written as is, followed by newline
+ */
+pub fn get(args: &KeyArgs) -> Result<(), Failure> {
+    let key = args.key.as_bytes();
+    let value = Db::open(&args.file)?.get(key)?.ok_or_else(|| Failure::NotFound(args.key.clone()))?;
+    let mut out = io::stdout().lock();
+
+    if args.json {
+        writeln!(out, "{}", json_entry(key, &value))?;
+    } else {
+        out.write_all(&value)?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+pub fn del(args: &KeyArgs) -> Result<(), Failure> {
+    let mut db = Db::open(&args.file)?;
+
+    if !db.delete(args.key.as_bytes())? {
+        return Err(Failure::NotFound(args.key.clone()));
+    }
+
+    db.close()?;
+
+    if args.json {
+        println!("{}", json::object(&[("key", json::string(args.key.as_bytes()))]));
+    }
+
+    Ok(())
+}
+
+/**
+ * entries in key order, one per line as key and value separated by tab, or json array of them
+ */
+pub fn scan(args: &ScanArgs) -> Result<(), Failure> {
+    let prefix = args.prefix.as_bytes();
+    let limit = args.limit.unwrap_or(usize::MAX);
+    let mut db = Db::open(&args.file)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut entries = vec![];
+
+    for entry in db.range(prefix..)?.take(limit) {
+        let (key, value) = entry?;
+
+        if !key.starts_with(prefix) {
+            break;
+        }
+
+        if args.json {
+            entries.push(json_entry(&key, &value));
+        } else {
+            writeln!(out, "{}", text_entry(&key, &value))?;
+        }
+    }
+
+    if args.json {
+        writeln!(out, "{}", json::array(&entries))?;
+    }
+
+    Ok(out.flush()?)
+}
+
+/**
+ * writes dump to file or to stdout, see Db::dump, with --table only listed tables, see Db::dump_tables
+ */
+pub fn dump(args: &DumpArgs) -> Result<(), Failure> {
+    let mut db = Db::open(&args.file)?;
+    let tables = table_list(&args.table);
+
+    let Some(path) = &args.dump else {
+        return Ok(write_dump(&db, tables.as_deref(), BufWriter::new(io::stdout().lock()))?);
+    };
+
+    write_dump(&db, tables.as_deref(), BufWriter::new(File::create_new(path)?))?;
+
+    let entries = match &tables {
+        Some(tables) => tables.iter().try_fold(0, |sum, name| Ok::<_, srdb::Error>(sum + db.table(name)?.len()?))?,
+        None => db.len(),
+    };
+    let bytes = std::fs::metadata(path)?.len();
+
+    if args.json {
+        println!("{}", json::object(&[("entries", entries.to_string()), ("bytes", bytes.to_string())]));
+    } else {
+        println!("dumped {} entries in {} bytes", entries, bytes);
+    }
+
+    Ok(())
+}
+
+/**
+ * names of --table, empty ones left out
+ */
+fn table_list(tables: &Option<Vec<String>>) -> Option<Vec<&str>> {
+    tables.as_ref().map(|tables| tables.iter().map(String::as_str).filter(|name| !name.is_empty()).collect())
+}
+
+fn write_dump(db: &Db, tables: Option<&[&str]>, out: impl Write) -> Result<(), srdb::Error> {
+    match tables {
+        Some(tables) => db.dump_tables(out, tables),
+        None => db.dump(out),
+    }
+}
+
+fn digest_name(digest: &TreeDigest) -> String {
+    match &digest.table {
+        Some(table) => format!("table {:?}", table),
+        None => "default tree".to_string(),
+    }
+}
+
+/**
+ * digests of reopened database which differ from those of dump, as messages
+ */
+fn mismatches(path: &str, expected: &[TreeDigest]) -> Result<Vec<String>, Failure> {
+    let found = Db::open(path)?.digests()?;
+    let mut messages = vec![];
+
+    for digest in expected {
+        match found.iter().find(|found| found.table == digest.table) {
+            None => messages.push(format!("{} is missing", digest_name(digest))),
+            Some(found) if found.entries != digest.entries => messages.push(format!(
+                "{} holds {} entries, dump has {}",
+                digest_name(digest),
+                found.entries,
+                digest.entries
+            )),
+            Some(found) if found.checksum != digest.checksum => messages.push(format!(
+                "{} has checksum {:08x}, dump has {:08x}",
+                digest_name(digest),
+                found.checksum,
+                digest.checksum
+            )),
+            Some(_) => {}
+        }
+    }
+
+    Ok(messages)
+}
+
+/**
+ * creates database from dump in file or on stdin, see Db::restore_tables, entries are streamed into it
+ * existing database is removed first with --force only, if restore then fails it is gone
+ * with --verify database is reopened and its digests compared to those of dump, differences are Failure::Problems
+ */
+pub fn restore(args: &RestoreArgs) -> Result<(), Failure> {
+    let path = args.file.as_str();
+
+    if Path::new(path).exists() {
+        if !args.force {
+            return Err(Failure::Usage(format!("{} exists, --force replaces it", path)));
+        }
+
+        Db::remove(path)?;
+    }
+
+    let tables = table_list(&args.table);
+    let (db, digests) = match &args.dump {
+        Some(dump) => Db::restore_tables(BufReader::new(File::open(dump)?), path, tables.as_deref())?,
+        None => Db::restore_tables(io::stdin().lock(), path, tables.as_deref())?,
+    };
+
+    let entries: u64 = digests.iter().map(|digest| digest.entries).sum();
+    let restored = db.tables()?.len();
+
+    db.close()?;
+
+    let problems = if args.verify { mismatches(path, &digests)? } else { vec![] };
+
+    if args.json {
+        let problems: Vec<String> = problems.iter().map(|problem| json::string(problem.as_bytes())).collect();
+        let mut fields = vec![("entries", entries.to_string()), ("tables", restored.to_string())];
+
+        if args.verify {
+            fields.push(("problems", json::array(&problems)));
+        }
+
+        println!("{}", json::object(&fields));
+    } else {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+
+        println!("restored {} entries and {} tables", entries, restored);
+
+        if args.verify && problems.is_empty() {
+            println!("verified against dump");
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(Failure::Problems(problems.len()));
+    }
+
+    Ok(())
+}
+
+pub fn stats_fields(stats: &DiskStats) -> Vec<(&'static str, String)> {
+    vec![
+        ("page_size", stats.page_size.to_string()),
+        ("file_pages", stats.file_pages.to_string()),
+        ("file_bytes", stats.file_bytes.to_string()),
+        ("pages", stats.pages.to_string()),
+        ("free_pages", stats.free_pages.to_string()),
+        ("tree_pages", stats.tree_pages.to_string()),
+        ("node_pages", json::number(stats.node_pages)),
+        ("overflow_pages", json::number(stats.overflow_pages)),
+        ("used_bytes", json::number(stats.used_bytes)),
+        ("entries", stats.entries.to_string()),
+        ("wal_bytes", stats.wal_bytes.to_string()),
+        ("fragmentation", format!("{:.4}", stats.fragmentation)),
+    ]
+}
+
+/**
+ * names and values in two columns
+ */
+pub fn print_aligned(rows: &[(String, String)]) {
+    let width = rows.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+
+    for (name, value) in rows {
+        println!("{:<width$}  {}", name, value, width = width);
+    }
+}
+
+/**
+ * exact disk stats with shape of trees and cache size, as aligned table of names and values or json object
+ * file is opened read only without lock, so it can be watched while writer holds it, see SrdbOptions::lock
+ */
+pub fn stats(args: &FileArgs) -> Result<(), Failure> {
+    let mut db = SrdbOptions::new().read_only(true).lock(false).open(&args.file)?;
+    let disk = db.disk_stats_exact()?;
+    let tree_bytes = disk.tree_pages * disk.page_size as u64;
+    let fill = disk.used_bytes.filter(|_| tree_bytes > 0).map(|used| format!("{:.4}", used as f64 / tree_bytes as f64));
+    let cache_pages = db.cache().capacity();
+
+    let mut fields = stats_fields(&disk);
+
+    fields.extend([
+        ("fill", json::number(fill)),
+        ("height", db.height()?.to_string()),
+        ("t", db.t().to_string()),
+        ("cache_pages", cache_pages.to_string()),
+        ("cache_bytes", (cache_pages * disk.page_size).to_string()),
+    ]);
+
+    let mut tables = vec![];
+
+    for name in db.tables()? {
+        let table = db.table(&name)?;
+
+        tables.push((name.clone(), table.len()?, table.height()?));
+    }
+
+    if args.json {
+        let tables: Vec<String> = tables
+            .iter()
+            .map(|(name, entries, height)| {
+                json::object(&[
+                    ("name", json::string(name.as_bytes())),
+                    ("entries", entries.to_string()),
+                    ("height", height.to_string()),
+                ])
+            })
+            .collect();
+
+        fields.push(("tables", json::array(&tables)));
+        println!("{}", json::object(&fields));
+
+        return Ok(());
+    }
+
+    let mut rows: Vec<(String, String)> = fields.into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+
+    rows.extend(tables.into_iter().map(|(name, entries, height)| {
+        (format!("table {}", name.escape_default()), format!("{} entries, height {}", entries, height))
+    }));
+
+    print_aligned(&rows);
+
+    Ok(())
+}
+
+fn repair_name(repair: Repair) -> &'static str {
+    match repair {
+        Repair::TrimTornPage => "trim torn page",
+        Repair::RebuildFreeList => "rebuild free list",
+        Repair::Salvage => "salvage",
+    }
+}
+
+fn problems_json(problems: &[Problem]) -> String {
+    let problems: Vec<String> = problems
+        .iter()
+        .map(|problem| {
+            json::object(&[("page", json::number(problem.page_id)), ("message", json::string(problem.message.as_bytes()))])
+        })
+        .collect();
+
+    json::array(&problems)
+}
+
+/**
+ * file is opened read only, when log holds changes missing from it file alone is checked
+ */
+fn open_for_verify(path: &str, in_place: bool) -> Result<(Db, bool), Failure> {
+    if in_place {
+        return Ok((Db::open(path)?, false));
+    }
+
+    match SrdbOptions::new().read_only(true).open(path) {
+        Err(srdb::Error::RecoveryNeeded) => Ok((SrdbOptions::new().read_only(true).lock(false).open(path)?, true)),
+        opened => Ok((opened?, false)),
+    }
+}
+
+/**
+ * lists problems found with fixes for them, database with any left is Failure::Problems
+ * original file is never written without --in-place: --repair alone lists what would be done,
+ * --repair --in-place trims torn page and rebuilds free list, then checks file again,
+ * --salvage copies entries which can still be read into new file, it is the only fix for damaged trees
+ */
+pub fn verify(args: &VerifyArgs) -> Result<(), Failure> {
+    let in_place = args.in_place;
+    let mode = if args.full { VerifyMode::Full } else { VerifyMode::Quick };
+    let (mut db, log_skipped) = open_for_verify(&args.file, in_place)?;
+    let found = db.verify(mode);
+    let mut remaining = found.clone();
+    let mut applied = vec![];
+
+    if in_place && !found.repairs.is_empty() {
+        for repair in &found.repairs {
+            match repair {
+                Repair::TrimTornPage => applied.push((*repair, format!("cut {} bytes", db.trim_torn_page()?))),
+                Repair::RebuildFreeList => applied.push((*repair, format!("{} pages free", db.rebuild_free_list()?))),
+                Repair::Salvage => {}
+            }
+        }
+
+        remaining = db.verify(mode);
+    }
+
+    let salvaged = match &args.salvage {
+        Some(out) => Some(db.salvage(out)?),
+        None => None,
+    };
+
+    db.close()?;
+
+    if args.json {
+        let repairs: Vec<String> =
+            found.repairs.iter().map(|repair| json::string(repair_name(*repair).as_bytes())).collect();
+        let applied: Vec<String> = applied
+            .iter()
+            .map(|(repair, result)| {
+                json::object(&[
+                    ("repair", json::string(repair_name(*repair).as_bytes())),
+                    ("result", json::string(result.as_bytes())),
+                ])
+            })
+            .collect();
+        let mut fields = vec![
+            ("ok", remaining.is_ok().to_string()),
+            ("entries", remaining.entries.to_string()),
+            ("log_skipped", log_skipped.to_string()),
+            ("problems", problems_json(&found.problems)),
+            ("repairs", json::array(&repairs)),
+            ("applied", json::array(&applied)),
+            ("remaining", problems_json(&remaining.problems)),
+        ];
+
+        if let Some(salvaged) = &salvaged {
+            fields.push((
+                "salvage",
+                json::object(&[
+                    ("entries", salvaged.entries.to_string()),
+                    ("tables", salvaged.tables.to_string()),
+                    ("problems", problems_json(&salvaged.problems)),
+                ]),
+            ));
+        }
+
+        println!("{}", json::object(&fields));
+    } else {
+        let dry_run = args.repair && !in_place;
+
+        print_verify(&args.file, &found, &applied, &remaining, dry_run, log_skipped);
+
+        if let (Some(salvaged), Some(out)) = (&salvaged, &args.salvage) {
+            for problem in &salvaged.problems {
+                println!("skipped {}", problem);
+            }
+
+            println!("salvaged {} entries and {} tables into {}", salvaged.entries, salvaged.tables, out);
+        }
+    }
+
+    if !remaining.is_ok() {
+        return Err(Failure::Problems(remaining.problems.len()));
+    }
+
+    Ok(())
+}
+
+fn print_verify(
+    path: &str,
+    found: &VerifyReport,
+    applied: &[(Repair, String)],
+    remaining: &VerifyReport,
+    dry_run: bool,
+    log_skipped: bool,
+) {
+    if log_skipped {
+        println!("log holds changes missing from file, file alone is checked, --repair --in-place replays them");
+    }
+
+    for problem in &found.problems {
+        println!("{}", problem);
+    }
+
+    for (repair, result) in applied {
+        println!("{}: {}", repair_name(*repair), result);
+    }
+
+    if !applied.is_empty() {
+        for problem in &remaining.problems {
+            println!("still {}", problem);
+        }
+    }
+
+    if remaining.is_ok() {
+        println!("ok, {} entries", remaining.entries);
+    }
+
+    for planned in &remaining.repairs {
+        let command = match planned {
+            Repair::Salvage => format!("srdb verify {} --salvage <out>", path),
+            _ => format!("srdb verify {} --repair --in-place", path),
+        };
+
+        println!("fix: {}, run {}", repair_name(*planned), command);
+    }
+
+    if dry_run && !remaining.repairs.is_empty() {
+        println!("nothing is changed without --in-place");
+    }
+}
+
+pub fn vacuum(args: &FileArgs) -> Result<(), Failure> {
+    let mut db = Db::open(&args.file)?;
+    let shrunk = db.vacuum()?;
+
+    db.close()?;
+
+    if args.json {
+        println!("{}", json::object(&[("shrunk_bytes", shrunk.to_string())]));
+    } else {
+        println!("file shrank by {} bytes", shrunk);
+    }
+
+    Ok(())
+}
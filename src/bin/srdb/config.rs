@@ -0,0 +1,477 @@
+use std::fs;
+use std::path::Path;
+
+use srdb::{SrdbOptions, SyncMode, DEFAULT_CACHE_PAGES, MIN_CACHE_PAGES};
+
+use crate::args::{ConfigCheckArgs, ServeArgs};
+use crate::commands::print_aligned;
+use crate::{json, Failure};
+
+const DEFAULT_BIND: &str = "127.0.0.1:7878";
+
+const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/**
+ * seconds connection may stay silent before it is closed
+ */
+const DEFAULT_IDLE_TIMEOUT: u64 = 300;
+
+/**
+ * bytes of batches kept for followers that fall behind
+ */
+const DEFAULT_REPLICATION_BACKLOG: u64 = 64 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+}
+
+impl LogLevel {
+    pub fn parse(name: &str) -> Result<LogLevel, String> {
+        match name {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            other => Err(format!("unknown log level {}, expected off, error, warn or info", other)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+        }
+    }
+
+    /**
+     * writes message of level to stderr if this level lets it through
+     */
+    pub fn log(self, level: LogLevel, message: impl std::fmt::Display) {
+        if level <= self {
+            eprintln!("srdb: {}", message);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtocolName {
+    Text,
+    Resp,
+    Http,
+}
+
+impl ProtocolName {
+    pub fn parse(name: &str) -> Result<ProtocolName, String> {
+        match name {
+            "text" => Ok(ProtocolName::Text),
+            "resp" => Ok(ProtocolName::Resp),
+            "http" => Ok(ProtocolName::Http),
+            other => Err(format!("unknown protocol {}, expected text, resp or http", other)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ProtocolName::Text => "text",
+            ProtocolName::Resp => "resp",
+            ProtocolName::Http => "http",
+        }
+    }
+}
+
+/**
+ * sync mode by its name on command line and in config file
+ */
+pub fn sync_mode(name: &str) -> Result<SyncMode, String> {
+    match name {
+        "always" => Ok(SyncMode::Always),
+        "commit" => Ok(SyncMode::OnCommit),
+        "off" => Ok(SyncMode::Off),
+        other => Err(format!("unknown sync mode {}, expected always, commit or off", other)),
+    }
+}
+
+fn sync_mode_name(sync_mode: SyncMode) -> &'static str {
+    match sync_mode {
+        SyncMode::Always => "always",
+        SyncMode::OnCommit => "commit",
+        SyncMode::Off => "off",
+    }
+}
+
+/**
+ * value of config file: string in double quotes, integer or boolean
+ */
+#[derive(Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl Value {
+    fn string(self, key: &str) -> Result<String, String> {
+        match self {
+            Value::Str(value) => Ok(value),
+            _ => Err(format!("{} must be string", key)),
+        }
+    }
+
+    fn count(self, key: &str) -> Result<u64, String> {
+        match self {
+            Value::Int(value) if value >= 0 => Ok(value as u64),
+            _ => Err(format!("{} must be non-negative integer", key)),
+        }
+    }
+
+    fn bool(self, key: &str) -> Result<bool, String> {
+        match self {
+            Value::Bool(value) => Ok(value),
+            _ => Err(format!("{} must be true or false", key)),
+        }
+    }
+}
+
+/**
+ * string after its opening quote up to closing one, returns it with the rest of line after quote
+ */
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &text[i + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(other) => return Err(format!("unknown escape \\{}", other)),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+
+    Err("string is not closed".to_string())
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(quoted) = text.strip_prefix('"') {
+        let (value, rest) = parse_string(quoted)?;
+        let rest = rest.trim_start();
+
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(format!("unexpected {} after string", rest));
+        }
+
+        return Ok(Value::Str(value));
+    }
+
+    let text = text.split('#').next().unwrap_or_default().trim_end();
+
+    match text {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        "" => Err("value is missing".to_string()),
+        _ => text
+            .replace('_', "")
+            .parse()
+            .map(Value::Int)
+            .map_err(|_| format!("{} is not string, integer or boolean", text)),
+    }
+}
+
+/**
+ * keys of config file with their values and numbers of their lines
+ */
+type Settings = Vec<(usize, String, Value)>;
+
+/**
+ * key = value lines of subset of toml, with # comments and blank lines, tables and arrays are not supported
+ * key given twice is error
+ */
+fn parse_file(text: &str) -> Result<Settings, (usize, String)> {
+    let mut entries: Settings = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            return Err((number, "tables are not supported, keys go at top level".to_string()));
+        }
+
+        let (key, value) = line.split_once('=').ok_or((number, "expected key = value".to_string()))?;
+        let key = key.trim();
+
+        if key.is_empty() || !key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-') {
+            return Err((number, format!("invalid key {:?}", key)));
+        }
+
+        if entries.iter().any(|(_, seen, _)| seen == key) {
+            return Err((number, format!("key {} is given twice", key)));
+        }
+
+        let value = parse_value(value.trim()).map_err(|message| (number, message))?;
+
+        entries.push((number, key.to_string(), value));
+    }
+
+    Ok(entries)
+}
+
+/**
+ * settings of serve: defaults, overridden by config file, overridden by flags
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServeConfig {
+    pub path: Option<String>,
+    pub bind: String,
+    pub cache_pages: usize,
+    pub sync_mode: SyncMode,
+    pub read_only: bool,
+    pub max_connections: usize,
+    /**
+     * seconds, 0 never closes silent connection
+     */
+    pub idle_timeout: u64,
+    pub log_level: LogLevel,
+    pub protocol: ProtocolName,
+    /**
+     * address followers connect to, None serves none
+     */
+    pub replication_bind: Option<String>,
+    /**
+     * bytes, see Db::set_replication_backlog
+     */
+    pub replication_backlog: u64,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig {
+            path: None,
+            bind: DEFAULT_BIND.to_string(),
+            cache_pages: DEFAULT_CACHE_PAGES,
+            sync_mode: SyncMode::default(),
+            read_only: false,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            log_level: LogLevel::Info,
+            protocol: ProtocolName::Text,
+            replication_bind: None,
+            replication_backlog: DEFAULT_REPLICATION_BACKLOG,
+        }
+    }
+}
+
+impl ServeConfig {
+    /**
+     * config of --config file if there is one, with flags and positional database file applied over it
+     */
+    pub fn load(args: &ServeArgs) -> Result<ServeConfig, Failure> {
+        let mut config = ServeConfig::default();
+
+        if let Some(path) = &args.config {
+            config.read_file(path)?;
+        }
+
+        config.apply_args(args);
+        config.check().map_err(Failure::Usage)?;
+
+        Ok(config)
+    }
+
+    /**
+     * rejects settings serve can not start with, before database is opened or address is bound
+     */
+    fn check(&self) -> Result<(), String> {
+        if self.path.is_none() {
+            return Err("database file is given neither as argument nor as db in config".to_string());
+        }
+
+        for bind in std::iter::once(&self.bind).chain(&self.replication_bind) {
+            if !bind.rsplit_once(':').is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok()) {
+                return Err(format!("bind address {} is not host:port", bind));
+            }
+        }
+
+        if self.read_only && self.replication_bind.is_some() {
+            return Err("read only database commits no batches to replicate".to_string());
+        }
+
+        if self.cache_pages < MIN_CACHE_PAGES {
+            return Err(format!("cache of {} pages is smaller than {}", self.cache_pages, MIN_CACHE_PAGES));
+        }
+
+        if self.max_connections == 0 {
+            return Err("max_connections must be at least 1".to_string());
+        }
+
+        if self.protocol == ProtocolName::Http && !cfg!(feature = "http") {
+            return Err("http protocol needs srdb built with http feature".to_string());
+        }
+
+        Ok(())
+    }
+
+    /**
+     * relative path of database is taken from directory of config file, so config works from any directory
+     */
+    fn read_file(&mut self, path: &str) -> Result<(), Failure> {
+        let text = fs::read_to_string(path)?;
+        let at = |line: usize, message: String| Failure::Usage(format!("{}:{}: {}", path, line, message));
+
+        for (line, key, value) in parse_file(&text).map_err(|(line, message)| at(line, message))? {
+            self.set(&key, value).map_err(|message| at(line, message))?;
+        }
+
+        if let Some(db) = self.path.as_mut().filter(|db| Path::new(db.as_str()).is_relative()) {
+            if let Some(dir) = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                *db = dir.join(&*db).to_string_lossy().into_owned();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        match key {
+            "db" => self.path = Some(value.string(key)?),
+            "bind" => self.bind = value.string(key)?,
+            "cache_pages" => self.cache_pages = value.count(key)? as usize,
+            "sync" => self.sync_mode = sync_mode(&value.string(key)?)?,
+            "read_only" => self.read_only = value.bool(key)?,
+            "max_connections" => self.max_connections = value.count(key)? as usize,
+            "idle_timeout" => self.idle_timeout = value.count(key)?,
+            "log_level" => self.log_level = LogLevel::parse(&value.string(key)?)?,
+            "protocol" => self.protocol = ProtocolName::parse(&value.string(key)?)?,
+            "replication_bind" => self.replication_bind = Some(value.string(key)?),
+            "replication_backlog" => self.replication_backlog = value.count(key)?,
+            _ => return Err(format!("unknown key {}", key)),
+        }
+
+        Ok(())
+    }
+
+    /**
+     * protocol switches and --protocol conflict with each other, clap rejects more than one
+     */
+    fn apply_args(&mut self, args: &ServeArgs) {
+        if let Some(path) = &args.file {
+            self.path = Some(path.clone());
+        }
+
+        if let Some(bind) = &args.bind {
+            self.bind = bind.clone();
+        }
+
+        if let Some(cache_pages) = args.cache_pages {
+            self.cache_pages = cache_pages;
+        }
+
+        if let Some(sync_mode) = args.sync {
+            self.sync_mode = sync_mode;
+        }
+
+        if args.read_only {
+            self.read_only = true;
+        }
+
+        if let Some(max_connections) = args.max_connections {
+            self.max_connections = max_connections;
+        }
+
+        if let Some(idle_timeout) = args.idle_timeout {
+            self.idle_timeout = idle_timeout;
+        }
+
+        if let Some(log_level) = args.log_level {
+            self.log_level = log_level;
+        }
+
+        if let Some(bind) = &args.replication_bind {
+            self.replication_bind = Some(bind.clone());
+        }
+
+        if let Some(replication_backlog) = args.replication_backlog {
+            self.replication_backlog = replication_backlog;
+        }
+
+        if let Some(protocol) = args.protocol {
+            self.protocol = protocol;
+        } else if args.resp {
+            self.protocol = ProtocolName::Resp;
+        } else if args.http {
+            self.protocol = ProtocolName::Http;
+        }
+    }
+
+    /**
+     * options database is opened with, read only one is never created, backlog is kept for followers only
+     */
+    pub fn options(&self) -> SrdbOptions {
+        SrdbOptions::new()
+            .cache_pages(self.cache_pages)
+            .sync_mode(self.sync_mode)
+            .read_only(self.read_only)
+            .replication_backlog(self.replication_bind.is_some().then_some(self.replication_backlog))
+    }
+
+    /**
+     * settings with their values as config file names them
+     */
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("db", self.path.clone().unwrap_or_default()),
+            ("bind", self.bind.clone()),
+            ("cache_pages", self.cache_pages.to_string()),
+            ("sync", sync_mode_name(self.sync_mode).to_string()),
+            ("read_only", self.read_only.to_string()),
+            ("max_connections", self.max_connections.to_string()),
+            ("idle_timeout", self.idle_timeout.to_string()),
+            ("log_level", self.log_level.name().to_string()),
+            ("protocol", self.protocol.name().to_string()),
+            ("replication_bind", self.replication_bind.clone().unwrap_or_default()),
+            ("replication_backlog", self.replication_backlog.to_string()),
+        ]
+    }
+}
+
+/**
+ * loads config the way serve does, with the same flags, and prints settings serve would start with,
+ * invalid config fails with line and key at fault, database is not opened and address is not bound
+ */
+pub fn config_check(args: &ConfigCheckArgs) -> Result<(), Failure> {
+    let fields = ServeConfig::load(&args.serve)?.fields();
+
+    if args.json {
+        let fields: Vec<(&str, String)> = fields
+            .into_iter()
+            .map(|(name, value)| match name {
+                "db" | "bind" | "sync" | "log_level" | "protocol" | "replication_bind" => (name, json::string(value.as_bytes())),
+                _ => (name, value),
+            })
+            .collect();
+
+        println!("{}", json::object(&fields));
+    } else {
+        let rows: Vec<(String, String)> = fields.into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+
+        print_aligned(&rows);
+    }
+
+    Ok(())
+}
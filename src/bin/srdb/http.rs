@@ -0,0 +1,313 @@
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::TcpStream;
+
+use srdb::Error;
+
+use crate::commands::stats_fields;
+use crate::json;
+use crate::server::Shared;
+
+/**
+ * length of request line or header, at most
+ */
+const MAX_LINE: usize = 8 << 10;
+
+const MAX_HEADERS: usize = 100;
+
+/**
+ * length of request body, at most
+ */
+const MAX_BODY: usize = 64 << 20;
+
+/**
+ * entries returned by /range without limit
+ */
+const DEFAULT_RANGE_LIMIT: usize = 1000;
+
+/**
+ * reply to connection over limit
+ */
+pub const BUSY: &str = "HTTP/1.1 503 Service Unavailable\r\nContent-Type: application/json\r\nContent-Length: 33\r\n\
+Connection: close\r\n\r\n{\"error\":\"too many connections\"}\n";
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    body: Vec<u8>,
+    close: bool,
+}
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn ok(body: String) -> Response {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Response {
+        Response {
+            status,
+            body: json::object(&[("error", json::string(message.to_string().as_bytes()))]),
+        }
+    }
+}
+
+/**
+ * database errors caused by state of database rather than by server are conflicts
+ */
+impl From<Error> for Response {
+    fn from(error: Error) -> Self {
+        let status = match error {
+            Error::KeyTooLarge { .. } => 400,
+            Error::ReadOnly | Error::DatabaseLocked | Error::RecoveryNeeded => 409,
+            _ => 500,
+        };
+
+        Response::error(status, error)
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+/**
+ * %XX sequences decoded, + is kept as is
+ */
+fn percent_decode(text: &str) -> Result<Vec<u8>, Response> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            out.push(bytes[i]);
+            i += 1;
+
+            continue;
+        }
+
+        let byte = text
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| Response::error(400, format!("invalid escape in {:?}", text)))?;
+
+        out.push(byte);
+        i += 3;
+    }
+
+    Ok(out)
+}
+
+/**
+ * line without \r\n, None at clean end of input
+ */
+fn read_line(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+
+    input.take(MAX_LINE as u64).read_until(b'\n', &mut line)?;
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    if !line.ends_with(b"\n") {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line is too long or cut"));
+    }
+
+    let line = String::from_utf8(line).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "line is not utf-8"))?;
+
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/**
+ * None when client closed connection between requests, malformed request is Err response
+ */
+fn read_request(input: &mut impl BufRead) -> io::Result<Option<Result<Request, Response>>> {
+    let Some(line) = read_line(input)? else {
+        return Ok(None);
+    };
+
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Ok(Some(Err(Response::error(400, "malformed request line"))));
+    };
+
+    if !version.starts_with("HTTP/1.") {
+        return Ok(Some(Err(Response::error(400, format!("unsupported version {}", version)))));
+    }
+
+    let mut close = version == "HTTP/1.0";
+    let mut length = 0;
+
+    for i in 0.. {
+        let line = read_line(input)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+        if line.is_empty() {
+            break;
+        }
+
+        if i == MAX_HEADERS {
+            return Ok(Some(Err(Response::error(400, "too many headers"))));
+        }
+
+        let Some((name, value)) = line.split_once(':') else {
+            return Ok(Some(Err(Response::error(400, "malformed header"))));
+        };
+
+        let value = value.trim();
+
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => {
+                let Ok(len) = value.parse::<usize>() else {
+                    return Ok(Some(Err(Response::error(400, "invalid content-length"))));
+                };
+
+                if len > MAX_BODY {
+                    return Ok(Some(Err(Response::error(413, format!("body is over {} bytes", MAX_BODY)))));
+                }
+
+                length = len;
+            }
+            "connection" => close = value.eq_ignore_ascii_case("close"),
+            "transfer-encoding" => return Ok(Some(Err(Response::error(400, "transfer-encoding is not supported")))),
+            _ => {}
+        }
+    }
+
+    let mut body = vec![0; length];
+
+    input.read_exact(&mut body)?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    Ok(Some(Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        body,
+        close,
+    })))
+}
+
+fn write_response(out: &mut impl Write, response: &Response, close: bool) -> io::Result<()> {
+    write!(
+        out,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}\r\n{}",
+        response.status,
+        reason(response.status),
+        response.body.len() + 1,
+        if close { "Connection: close\r\n" } else { "" },
+        response.body
+    )?;
+    writeln!(out)?;
+    out.flush()
+}
+
+/**
+ * answers requests of one client, connection is kept open between them unless client asks otherwise
+ * malformed request gets 400 and connection is closed, body may not have been read whole
+ */
+pub fn handle(stream: TcpStream, db: &Shared) -> io::Result<()> {
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut out = BufWriter::new(stream);
+
+    loop {
+        match read_request(&mut input)? {
+            None => return Ok(()),
+            Some(Err(response)) => return write_response(&mut out, &response, true),
+            Some(Ok(request)) => {
+                let response = route(db, &request).unwrap_or_else(|response| response);
+
+                write_response(&mut out, &response, request.close)?;
+
+                if request.close {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/**
+ * GET, PUT and DELETE of /keys/{key}, GET of /range?start=&end=&limit= and /stats
+ */
+fn route(db: &Shared, request: &Request) -> Result<Response, Response> {
+    let method = request.method.as_str();
+
+    if let Some(key) = request.path.strip_prefix("/keys/") {
+        let key = percent_decode(key)?;
+
+        return match method {
+            "GET" => {
+                let value = db.read().get(&key)?.ok_or_else(|| Response::error(404, "key not found"))?;
+
+                Ok(Response::ok(json::object(&[("key", json::string(&key)), ("value", json::string(&value))])))
+            }
+            "PUT" => {
+                db.write().insert(&key, &request.body)?;
+
+                Ok(Response::ok(json::object(&[("key", json::string(&key))])))
+            }
+            "DELETE" => match db.write().delete(&key)? {
+                true => Ok(Response::ok(json::object(&[("key", json::string(&key))]))),
+                false => Err(Response::error(404, "key not found")),
+            },
+            _ => Err(Response::error(405, format!("{} is not allowed on /keys", method))),
+        };
+    }
+
+    match (method, request.path.as_str()) {
+        ("GET", "/range") => range(db, &request.query),
+        ("GET", "/stats") => Ok(Response::ok(json::object(&stats_fields(&db.read().disk_stats())))),
+        (_, "/range" | "/stats") => Err(Response::error(405, format!("{} is not allowed on {}", method, request.path))),
+        _ => Err(Response::error(404, format!("no route {}", request.path))),
+    }
+}
+
+/**
+ * entries from start inclusive to end exclusive, both optional
+ */
+fn range(db: &Shared, query: &str) -> Result<Response, Response> {
+    let mut start = Vec::new();
+    let mut end = None;
+    let mut limit = DEFAULT_RANGE_LIMIT;
+
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (name, value) = param.split_once('=').unwrap_or((param, ""));
+        let value = percent_decode(value)?;
+
+        match name {
+            "start" => start = value,
+            "end" => end = Some(value),
+            "limit" => {
+                limit = std::str::from_utf8(&value)
+                    .ok()
+                    .and_then(|limit| limit.parse().ok())
+                    .ok_or_else(|| Response::error(400, "limit is not a number"))?
+            }
+            _ => return Err(Response::error(400, format!("unknown parameter {}", name))),
+        }
+    }
+
+    let entries: Vec<String> = db
+        .scan(&start, limit, |key| end.as_ref().is_none_or(|end| key < end.as_slice()))?
+        .iter()
+        .map(|(key, value)| json::object(&[("key", json::string(key)), ("value", json::string(value))]))
+        .collect();
+
+    Ok(Response::ok(json::array(&entries)))
+}
@@ -0,0 +1,361 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::time::{Duration, Instant};
+
+use srdb::{Db, WriteBatch};
+
+use crate::args::ImportArgs;
+use crate::{json, signals, Failure};
+
+const DEFAULT_BATCH_ROWS: usize = 10_000;
+
+/**
+ * how often progress is reported to stderr
+ */
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/**
+ * parse errors listed in summary, the rest are only counted
+ */
+const MAX_LISTED_ERRORS: usize = 20;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    /**
+     * whole line is key, value is empty
+     */
+    Lines,
+    /**
+     * key and value separated by the first tab
+     */
+    Tsv,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Format, String> {
+        match name {
+            "lines" => Ok(Format::Lines),
+            "tsv" => Ok(Format::Tsv),
+            other => Err(format!("unknown format {}, expected lines or tsv", other)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Summary {
+    rows: u64,
+    duplicates: u64,
+    errors: Vec<(u64, String)>,
+    error_count: u64,
+}
+
+impl Summary {
+    fn error(&mut self, line: u64, message: String) {
+        self.error_count += 1;
+
+        if self.errors.len() < MAX_LISTED_ERRORS {
+            self.errors.push((line, message));
+        }
+    }
+}
+
+/**
+ * rate of rows reported to stderr at most once per PROGRESS_INTERVAL
+ */
+struct Progress {
+    start: Instant,
+    last: Instant,
+}
+
+impl Progress {
+    fn new() -> Progress {
+        let now = Instant::now();
+
+        Progress { start: now, last: now }
+    }
+
+    fn tick(&mut self, rows: u64) {
+        if self.last.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+
+        self.last = Instant::now();
+        eprintln!("{} rows, {:.0} rows/s", rows, rows as f64 / self.start.elapsed().as_secs_f64());
+    }
+}
+
+/**
+ * line without line ending, None at end of input
+ */
+fn read_line(input: &mut impl BufRead, line: &mut Vec<u8>) -> io::Result<bool> {
+    line.clear();
+
+    if input.read_until(b'\n', line)? == 0 {
+        return Ok(false);
+    }
+
+    if line.ends_with(b"\n") {
+        line.pop();
+    }
+
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+
+    Ok(true)
+}
+
+fn parse(line: &[u8], format: Format) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let (key, value) = match format {
+        Format::Lines => (line, &[][..]),
+        Format::Tsv => {
+            let tab = line.iter().position(|byte| *byte == b'\t').ok_or("no tab between key and value")?;
+
+            (&line[..tab], &line[tab + 1..])
+        }
+    };
+
+    if key.is_empty() {
+        return Err("key is empty".to_string());
+    }
+
+    Ok((key.to_vec(), value.to_vec()))
+}
+
+fn open_input(path: &str) -> io::Result<Box<dyn BufRead>> {
+    if path == "-" {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+
+    Ok(Box::new(BufReader::new(File::open(path)?)))
+}
+
+/**
+ * imports keys or key and value pairs from file or stdin given as -, one row per line
+ * rows go in write batches, each committed as a whole, row whose key is already there is skipped
+ * after failure summary names the last committed line, import continues after it with --skip
+ * with signals feature SIGINT and SIGTERM stop it the same way once rows read so far are committed
+ * with --sorted input must be a file in key order and database must be empty,
+ * file is read twice: first to count rows, then to bulk load them, failure leaves database empty
+ */
+pub fn import(args: &ImportArgs) -> Result<(), Failure> {
+    let format = args.format;
+    let skip = args.skip;
+    let sorted = args.sorted;
+
+    if sorted && (args.input == "-" || skip > 0) {
+        return Err(Failure::Usage("--sorted reads input twice, it needs a file and can not skip".to_string()));
+    }
+
+    let input = open_input(&args.input)?;
+    let mut db = Db::open_or_create(&args.file)?;
+    let mut summary = Summary::default();
+
+    let result = if sorted {
+        import_sorted(&mut db, input, &args.input, format, &mut summary)
+    } else {
+        let batch = args.batch.unwrap_or(DEFAULT_BATCH_ROWS).max(1);
+
+        import_batches(&mut db, input, format, batch, skip, &mut summary)
+    };
+
+    db.close()?;
+
+    let committed = result?;
+
+    report(&summary, committed, args.json);
+
+    Ok(())
+}
+
+fn report(summary: &Summary, lines: u64, json: bool) {
+    if json {
+        let errors: Vec<String> = summary
+            .errors
+            .iter()
+            .map(|(line, message)| json::object(&[("line", line.to_string()), ("message", json::string(message.as_bytes()))]))
+            .collect();
+
+        println!(
+            "{}",
+            json::object(&[
+                ("lines", lines.to_string()),
+                ("rows", summary.rows.to_string()),
+                ("duplicates", summary.duplicates.to_string()),
+                ("error_count", summary.error_count.to_string()),
+                ("errors", json::array(&errors)),
+            ])
+        );
+
+        return;
+    }
+
+    println!(
+        "{} lines read, {} rows imported, {} duplicates skipped, {} parse errors",
+        lines, summary.rows, summary.duplicates, summary.error_count
+    );
+
+    for (line, message) in &summary.errors {
+        println!("line {}: {}", line, message);
+    }
+
+    if summary.error_count > summary.errors.len() as u64 {
+        println!("... {} more", summary.error_count - summary.errors.len() as u64);
+    }
+}
+
+/**
+ * rows waiting to be written together with their keys, so key repeated inside batch is found
+ */
+struct Pending {
+    batch: WriteBatch,
+    keys: HashSet<Vec<u8>>,
+    committed: u64,
+}
+
+impl Pending {
+    /**
+     * writes batch, lines up to number are committed after it
+     */
+    fn commit(&mut self, db: &mut Db, summary: &mut Summary, number: u64) -> Result<(), Failure> {
+        if let Err(error) = db.write(&self.batch) {
+            eprintln!("import stopped, lines up to {} are imported, rerun with --skip {}", self.committed, self.committed);
+
+            return Err(error.into());
+        }
+
+        summary.rows += self.batch.len() as u64;
+        self.committed = number;
+        self.batch.clear();
+        self.keys.clear();
+
+        Ok(())
+    }
+}
+
+/**
+ * returns number of lines read, failure reports line after which import can be resumed
+ */
+fn import_batches(
+    db: &mut Db,
+    mut input: impl BufRead,
+    format: Format,
+    rows: usize,
+    skip: u64,
+    summary: &mut Summary,
+) -> Result<u64, Failure> {
+    let mut progress = Progress::new();
+    let mut line = Vec::new();
+    let mut number = 0;
+    let mut pending = Pending {
+        batch: WriteBatch::new(),
+        keys: HashSet::new(),
+        committed: skip,
+    };
+
+    signals::install();
+
+    while read_line(&mut input, &mut line)? {
+        if signals::requested() {
+            pending.commit(db, summary, number)?;
+            eprintln!("import interrupted, lines up to {} are imported, rerun with --skip {}", number, number);
+
+            return Err(Failure::Interrupted);
+        }
+
+        number += 1;
+
+        if number <= skip {
+            continue;
+        }
+
+        let (key, value) = match parse(&line, format) {
+            Ok(row) => row,
+            Err(message) => {
+                summary.error(number, message);
+
+                continue;
+            }
+        };
+
+        if pending.keys.contains(&key) || db.contains(&key)? {
+            summary.duplicates += 1;
+
+            continue;
+        }
+
+        pending.batch.put(&key, &value);
+        pending.keys.insert(key);
+
+        if pending.batch.len() == rows {
+            pending.commit(db, summary, number)?;
+            progress.tick(number - skip);
+        }
+    }
+
+    pending.commit(db, summary, number)?;
+
+    Ok(number)
+}
+
+/**
+ * counts rows of input, skipping parse errors and repeated keys, then bulk loads the same rows read again from path
+ */
+fn import_sorted(
+    db: &mut Db,
+    mut input: impl BufRead,
+    path: &str,
+    format: Format,
+    summary: &mut Summary,
+) -> Result<u64, Failure> {
+    let mut line = Vec::new();
+    let mut previous: Option<Vec<u8>> = None;
+    let mut number = 0;
+    let mut rows = 0;
+
+    while read_line(&mut input, &mut line)? {
+        number += 1;
+
+        match parse(&line, format) {
+            Ok((key, _)) if previous.as_ref() == Some(&key) => summary.duplicates += 1,
+            Ok((key, _)) => {
+                rows += 1;
+                previous = Some(key);
+            }
+            Err(message) => summary.error(number, message),
+        }
+    }
+
+    let mut input = open_input(path)?;
+    let mut progress = Progress::new();
+    let mut previous: Option<Vec<u8>> = None;
+    let mut loaded = 0;
+
+    let entries = std::iter::from_fn(|| loop {
+        match read_line(&mut input, &mut line) {
+            Ok(false) => return None,
+            Ok(true) => {}
+            Err(error) => return Some(Err(srdb::Error::Io(error))),
+        }
+
+        let Ok((key, value)) = parse(&line, format) else {
+            continue;
+        };
+
+        if previous.as_ref() == Some(&key) {
+            continue;
+        }
+
+        previous = Some(key.clone());
+        loaded += 1;
+        progress.tick(loaded);
+
+        return Some(Ok((key, value)));
+    });
+
+    db.load_sorted(rows as usize, entries)?;
+    summary.rows = rows;
+
+    Ok(number)
+}
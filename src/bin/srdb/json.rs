@@ -0,0 +1,46 @@
+use std::fmt::Write;
+
+/**
+ * bytes as json string, bytes which are not utf-8 are replaced with U+FFFD
+ */
+pub fn string(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() + 2);
+
+    out.push('"');
+
+    for c in String::from_utf8_lossy(bytes).chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+
+    out
+}
+
+/**
+ * object from fields whose values are already json
+ */
+pub fn object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields.iter().map(|(name, value)| format!("{}:{}", string(name.as_bytes()), value)).collect();
+
+    format!("{{{}}}", fields.join(","))
+}
+
+pub fn array(items: &[String]) -> String {
+    format!("[{}]", items.join(","))
+}
+
+/**
+ * optional number, null when missing
+ */
+pub fn number(value: Option<impl ToString>) -> String {
+    value.map_or_else(|| "null".to_string(), |value| value.to_string())
+}
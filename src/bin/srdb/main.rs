@@ -0,0 +1,104 @@
+use std::fmt::{self, Display};
+use std::io;
+
+use clap::Parser;
+
+use crate::args::{Cli, Command};
+
+mod args;
+mod bench;
+mod commands;
+mod config;
+#[cfg(feature = "http")]
+mod http;
+mod import;
+mod json;
+mod repl;
+mod replicate;
+mod resp;
+mod server;
+mod signals;
+
+/**
+ * why command failed, each kind has its own exit code
+ */
+#[derive(Debug)]
+pub enum Failure {
+    Usage(String),
+    NotFound(String),
+    Problems(usize),
+    Db(srdb::Error),
+    Io(io::Error),
+    Interrupted,
+}
+
+impl Failure {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Failure::NotFound(_) => 1,
+            Failure::Usage(_) => 2,
+            Failure::Db(_) | Failure::Io(_) => 3,
+            Failure::Problems(_) => 4,
+            Failure::Interrupted => 130,
+        }
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Usage(message) => write!(f, "{}", message),
+            Failure::NotFound(key) => write!(f, "key {} not found", key),
+            Failure::Problems(count) => write!(f, "verify found {} problems", count),
+            Failure::Db(error) => write!(f, "{}", error),
+            Failure::Io(error) => write!(f, "i/o error: {}", error),
+            Failure::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl From<srdb::Error> for Failure {
+    fn from(error: srdb::Error) -> Self {
+        Failure::Db(error)
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        Failure::Io(error)
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Failure> {
+    let Some(command) = cli.command else {
+        return Ok(repl::start(&cli.repl)?);
+    };
+
+    match command {
+        Command::Put(args) => commands::put(&args),
+        Command::Get(args) => commands::get(&args),
+        Command::Del(args) => commands::del(&args),
+        Command::Scan(args) => commands::scan(&args),
+        Command::Dump(args) => commands::dump(&args),
+        Command::Restore(args) => commands::restore(&args),
+        Command::Stats(args) => commands::stats(&args),
+        Command::Verify(args) => commands::verify(&args),
+        Command::Vacuum(args) => commands::vacuum(&args),
+        Command::Import(args) => import::import(&args),
+        Command::Bench(args) => bench::bench(&args),
+        Command::Serve(args) => server::serve(&args),
+        Command::ConfigCheck(args) => config::config_check(&args),
+        Command::Replicate(args) => replicate::replicate(&args),
+        Command::Repl(args) => Ok(repl::start(&args)?),
+    }
+}
+
+/**
+ * malformed command line is reported by clap and exits with 2 like Failure::Usage
+ */
+fn main() {
+    if let Err(failure) = run(Cli::parse()) {
+        eprintln!("srdb: {}", failure);
+        std::process::exit(failure.exit_code());
+    }
+}
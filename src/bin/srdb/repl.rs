@@ -0,0 +1,247 @@
+use std::collections::HashSet;
+use std::fmt::{Debug, Display};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use srdb::BTree;
+
+use crate::args::ReplArgs;
+
+const T: usize = 3;
+
+const HELP: &str = "commands: insert <key>, delete <key>, contains <key>, range <from> <to>, print, stats, clear, \
+seed <n> <ops>, help, quit";
+
+/**
+ * ascii view of tree shown after every change with result of invariant check, lines of nodes missing from view
+ * shown before are highlighted, in color when stdout is terminal and NO_COLOR is not set, otherwise marked with *
+ */
+struct Watch {
+    previous: HashSet<String>,
+    color: bool,
+}
+
+impl Watch {
+    fn new<K: Ord + Clone + Debug>(tree: &BTree<K>) -> Watch {
+        Watch {
+            previous: tree.to_ascii_string().lines().map(str::to_string).collect(),
+            color: io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+
+    fn show<K: Ord + Clone + Debug>(&mut self, tree: &BTree<K>) -> Result<(), String> {
+        let view = tree.to_ascii_string();
+        let mut out = io::stdout().lock();
+
+        for line in view.lines() {
+            let node = line.find('[').unwrap_or(0);
+
+            let written = match (self.previous.contains(line), self.color) {
+                (true, _) => writeln!(out, "{}", line),
+                (false, true) => writeln!(out, "{}\x1b[1;33m{}\x1b[0m", &line[..node], &line[node..]),
+                (false, false) => writeln!(out, "{} *", line),
+            };
+
+            written.map_err(|error| error.to_string())?;
+        }
+
+        self.previous = view.lines().map(str::to_string).collect();
+
+        Ok(())
+    }
+}
+
+/**
+ * interactive shell over in-memory tree, keys are i64 or strings with --str
+ * with --trace tree is printed after every change, with --watch changed nodes are highlighted in it, see Watch
+ */
+pub fn start(args: &ReplArgs) -> io::Result<()> {
+    if args.str {
+        repl::<String>(args.trace, args.watch, |i| format!("{:04}", i))
+    } else {
+        repl::<i64>(args.trace, args.watch, |i| i)
+    }
+}
+
+/**
+ * reads commands until quit or end of input, key makes key of random workload from number
+ */
+fn repl<K>(trace: bool, watch: bool, key: fn(i64) -> K) -> io::Result<()>
+where
+    K: Ord + Clone + Debug + Display + FromStr,
+{
+    let mut tree = BTree::<K>::new(T);
+    let mut watch = watch.then(|| Watch::new(&tree));
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    println!("{}", HELP);
+
+    loop {
+        print!("> ");
+        stdout.flush()?;
+
+        let mut line = String::new();
+
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        if words.first() == Some(&"quit") {
+            return Ok(());
+        }
+
+        match run(&mut tree, &words, key, watch.as_mut()) {
+            Ok(changed) => {
+                if changed && trace && watch.is_none() {
+                    tree.print_ascii(&mut stdout)?;
+                }
+            }
+            Err(message) => println!("error: {}", message),
+        }
+    }
+}
+
+fn parse<K: FromStr>(word: Option<&&str>) -> Result<K, String> {
+    let word = word.ok_or("key is missing")?;
+
+    word.parse().map_err(|_| format!("{} is not a valid key", word))
+}
+
+/**
+ * runs one command and prints its result, returns whether tree changed
+ * every change is followed by invariant check, so broken tree is reported right after the command breaking it
+ */
+fn run<K>(
+    tree: &mut BTree<K>,
+    words: &[&str],
+    key: fn(i64) -> K,
+    mut watch: Option<&mut Watch>,
+) -> Result<bool, String>
+where
+    K: Ord + Clone + Debug + Display + FromStr,
+{
+    let Some((&command, args)) = words.split_first() else {
+        return Ok(false);
+    };
+
+    let expected = match command {
+        "insert" | "delete" | "contains" => 1,
+        "range" | "seed" => 2,
+        "print" | "stats" | "clear" | "help" => 0,
+        _ => return Err(format!("unknown command {}, {}", command, HELP)),
+    };
+
+    if args.len() != expected {
+        return Err(format!("{} takes {} arguments, {}", command, expected, HELP));
+    }
+
+    match command {
+        "insert" => tree.insert(parse(args.first())?),
+        "delete" => println!("{}", tree.delete(&parse(args.first())?)),
+        "contains" => {
+            println!("{}", tree.contains(parse(args.first())?));
+
+            return Ok(false);
+        }
+        "range" => {
+            let from: K = parse(args.first())?;
+            let to: K = parse(args.get(1))?;
+            let keys: Vec<String> = tree
+                .iter()
+                .skip_while(|k| **k < from)
+                .take_while(|k| **k <= to)
+                .map(|k| k.to_string())
+                .collect();
+
+            println!("[{}]", keys.join(", "));
+
+            return Ok(false);
+        }
+        "print" => {
+            print!("{}", tree.to_ascii_string());
+
+            return Ok(false);
+        }
+        "stats" => {
+            println!("{:?}", tree.stats());
+
+            return Ok(false);
+        }
+        "clear" => *tree = BTree::new(tree.t()),
+        "seed" => {
+            let seed: u64 = parse(args.first())?;
+            let ops: usize = parse(args.get(1))?;
+
+            replay(tree, seed, ops, key, watch)?;
+
+            return Ok(true);
+        }
+        "help" => {
+            println!("{}", HELP);
+
+            return Ok(false);
+        }
+        _ => unreachable!(),
+    }
+
+    if let Some(watch) = watch.as_deref_mut() {
+        watch.show(tree)?;
+    }
+
+    tree.check_invariants().map_err(|error| format!("invariant broken: {}", error))?;
+
+    if watch.is_some() {
+        println!("invariants ok");
+    }
+
+    Ok(true)
+}
+
+/**
+ * applies ops random inserts and deletes of keys below ops, the same seed gives the same workload
+ * stops at the first operation breaking invariants and reports it with its number
+ * when watched, every operation is shown with tree after it
+ */
+fn replay<K>(
+    tree: &mut BTree<K>,
+    seed: u64,
+    ops: usize,
+    key: fn(i64) -> K,
+    mut watch: Option<&mut Watch>,
+) -> Result<(), String>
+where
+    K: Ord + Clone + Debug + Display,
+{
+    let mut rng = StdRng::seed_from_u64(seed);
+    let bound = ops.max(1) as i64;
+
+    for i in 0..ops {
+        let k = key(rng.gen_range(0..bound));
+        let delete = rng.gen_bool(0.5);
+
+        if delete {
+            tree.delete(&k);
+        } else {
+            tree.insert(k.clone());
+        }
+
+        if let Some(watch) = watch.as_deref_mut() {
+            println!("op {}: {} {}", i, if delete { "delete" } else { "insert" }, k);
+            watch.show(tree)?;
+        }
+
+        tree.check_invariants().map_err(|error| {
+            format!("invariant broken by op {} ({} {}): {}", i, if delete { "delete" } else { "insert" }, k, error)
+        })?;
+    }
+
+    println!("len {}", tree.len());
+
+    Ok(())
+}
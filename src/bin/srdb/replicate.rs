@@ -0,0 +1,106 @@
+use std::io::{BufReader, BufWriter};
+use std::net::TcpStream;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use srdb::{Db, Error, SrdbOptions, HEARTBEAT_INTERVAL};
+
+use crate::args::ReplicateArgs;
+use crate::{signals, Failure};
+
+/**
+ * pause before connecting again after primary went away
+ */
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/**
+ * heartbeats that may go missing before connection is taken as lost
+ */
+const MISSED_HEARTBEATS: u32 = 3;
+
+fn connect(from: &str) -> Result<TcpStream, Failure> {
+    let stream = TcpStream::connect(from)?;
+
+    stream.set_read_timeout(Some(HEARTBEAT_INTERVAL * MISSED_HEARTBEATS))?;
+
+    Ok(stream)
+}
+
+/**
+ * new database at path as copy of primary, see srdb::bootstrap_follower
+ */
+fn bootstrap(from: &str, path: &str) -> Result<Db, Failure> {
+    let stream = connect(from)?;
+    let db = srdb::bootstrap_follower(path, &SrdbOptions::new(), BufReader::new(stream.try_clone()?), stream)?;
+
+    println!("copied primary at batch {}", db.seq());
+
+    Ok(db)
+}
+
+/**
+ * keeps local database in step with primary serving --replication-bind at --from, see srdb::follow_primary
+ * batches are applied in order and logged, so follower resumes from where it stopped after restart,
+ * lost connection is retried every second, nothing else writes database, stats reads it meanwhile
+ * database missing is created empty, or as copy of backup of primary with --bootstrap <backup>,
+ * follower behind backlog of primary fails with need full resync, with --resync it takes copy of primary instead
+ * --once returns when connection ends instead of connecting again
+ * with signals feature SIGINT and SIGTERM stop it after batch in flight, database is checkpointed
+ */
+pub fn replicate(args: &ReplicateArgs) -> Result<(), Failure> {
+    let from = args.from.as_str();
+    let path = args.file.as_str();
+    let resync = args.resync;
+
+    let mut db = match &args.bootstrap {
+        Some(_) if Path::new(path).exists() => {
+            return Err(Failure::Usage(format!("{} exists, --bootstrap creates new database", path)));
+        }
+        Some(backup) => Db::restore_backup(backup, &[] as &[&str], path)?,
+        None if resync && !Path::new(path).exists() => bootstrap(from, path)?,
+        None => Db::open_or_create(path)?,
+    };
+
+    signals::install();
+
+    while !signals::requested() {
+        println!("following {} from batch {}", from, db.seq());
+
+        let result = connect(from).and_then(|stream| {
+            let input = BufReader::new(stream.try_clone()?);
+
+            Ok(srdb::follow_primary(&mut db, input, BufWriter::new(stream), signals::requested)?)
+        });
+
+        match result {
+            Ok(()) if args.once => break,
+            Ok(()) => eprintln!("primary closed connection at batch {}", db.seq()),
+            Err(Failure::Db(Error::ResyncNeeded(reason))) if resync => {
+                eprintln!("need full resync: {}, copying primary", reason);
+
+                db.close()?;
+                Db::remove(path)?;
+                db = bootstrap(from, path)?;
+
+                continue;
+            }
+            Err(Failure::Db(Error::ResyncNeeded(reason))) => {
+                db.close()?;
+                eprintln!("rerun with --resync to copy primary, or remove {} and use --bootstrap <backup>", path);
+
+                return Err(Failure::Db(Error::ResyncNeeded(reason)));
+            }
+            Err(error) if args.once || matches!(error, Failure::Db(Error::ReplicationGap { .. })) => {
+                db.close()?;
+
+                return Err(error);
+            }
+            Err(error) => eprintln!("replication from {} failed: {}", from, error),
+        }
+
+        thread::sleep(RECONNECT_INTERVAL);
+    }
+
+    Ok(db.close()?)
+}
@@ -0,0 +1,401 @@
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::TcpStream;
+
+use crate::server::Shared;
+
+/**
+ * arguments of one command, at most
+ */
+const MAX_ARGS: usize = 1024 * 1024;
+
+/**
+ * length of bulk string, at most
+ */
+const MAX_BULK: usize = 64 << 20;
+
+/**
+ * length of inline command, at most
+ */
+const MAX_INLINE: usize = 64 << 10;
+
+/**
+ * reply to connection over limit, as redis sends it
+ */
+pub const BUSY: &str = "-ERR max number of clients reached\r\n";
+
+/**
+ * why command could not be read
+ */
+enum ReadError {
+    Closed,
+    Protocol(String),
+    Io(io::Error),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => ReadError::Protocol("unexpected end of input".to_string()),
+            _ => ReadError::Io(error),
+        }
+    }
+}
+
+/**
+ * reply of RESP2, serialized by write_reply
+ */
+enum Reply {
+    Simple(&'static str),
+    Error(String),
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Null,
+    Array(Vec<Reply>),
+}
+
+/**
+ * line ending with \r\n, without them, longer lines are protocol errors
+ */
+fn read_line(input: &mut impl BufRead, limit: usize) -> Result<Vec<u8>, ReadError> {
+    let mut line = Vec::new();
+
+    input.take(limit as u64 + 2).read_until(b'\n', &mut line)?;
+
+    if line.is_empty() {
+        return Err(ReadError::Closed);
+    }
+
+    if !line.ends_with(b"\r\n") {
+        let reason = if line.len() > limit { "line is too long" } else { "line does not end with CRLF" };
+
+        return Err(ReadError::Protocol(reason.to_string()));
+    }
+
+    line.truncate(line.len() - 2);
+
+    Ok(line)
+}
+
+/**
+ * number after type byte, as in *<count> or $<len>
+ */
+fn read_length(input: &mut impl BufRead, kind: u8, max: usize) -> Result<usize, ReadError> {
+    let line = read_line(input, 32).map_err(|error| match error {
+        ReadError::Closed => ReadError::Protocol("unexpected end of input".to_string()),
+        error => error,
+    })?;
+
+    if line.first() != Some(&kind) {
+        return Err(ReadError::Protocol(format!("expected '{}', got {:?}", kind as char, String::from_utf8_lossy(&line))));
+    }
+
+    let len = std::str::from_utf8(&line[1..])
+        .ok()
+        .and_then(|len| len.parse::<usize>().ok())
+        .ok_or_else(|| ReadError::Protocol(format!("invalid length {:?}", String::from_utf8_lossy(&line[1..]))))?;
+
+    if len > max {
+        return Err(ReadError::Protocol(format!("length {} is over {}", len, max)));
+    }
+
+    Ok(len)
+}
+
+/**
+ * reads command sent as array of bulk strings or as inline command split by spaces,
+ * empty inline command gives no arguments
+ */
+fn read_command(input: &mut impl BufRead) -> Result<Vec<Vec<u8>>, ReadError> {
+    let first = match input.fill_buf()?.first() {
+        Some(byte) => *byte,
+        None => return Err(ReadError::Closed),
+    };
+
+    if first != b'*' {
+        let line = read_line(input, MAX_INLINE)?;
+
+        return Ok(line.split(|byte| *byte == b' ').filter(|arg| !arg.is_empty()).map(<[u8]>::to_vec).collect());
+    }
+
+    let count = read_length(input, b'*', MAX_ARGS)?;
+    let mut args = Vec::with_capacity(count.min(64));
+
+    for _ in 0..count {
+        let len = read_length(input, b'$', MAX_BULK)?;
+        let mut arg = Vec::new();
+
+        input.take(len as u64 + 2).read_to_end(&mut arg)?;
+
+        if arg.len() < len + 2 {
+            return Err(ReadError::Protocol("unexpected end of input".to_string()));
+        }
+
+        if !arg.ends_with(b"\r\n") {
+            return Err(ReadError::Protocol("bulk string does not end with CRLF".to_string()));
+        }
+
+        arg.truncate(len);
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
+fn write_reply(out: &mut impl Write, reply: &Reply) -> io::Result<()> {
+    match reply {
+        Reply::Simple(text) => write!(out, "+{}\r\n", text),
+        Reply::Error(message) => write!(out, "-{}\r\n", message.replace(['\r', '\n'], " ")),
+        Reply::Integer(n) => write!(out, ":{}\r\n", n),
+        Reply::Bulk(bytes) => {
+            write!(out, "${}\r\n", bytes.len())?;
+            out.write_all(bytes)?;
+            out.write_all(b"\r\n")
+        }
+        Reply::Null => write!(out, "$-1\r\n"),
+        Reply::Array(items) => {
+            write!(out, "*{}\r\n", items.len())?;
+
+            for item in items {
+                write_reply(out, item)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/**
+ * answers RESP2 commands until client closes connection or sends malformed input,
+ * which gets error reply before connection is closed
+ */
+pub fn handle(stream: TcpStream, db: &Shared) -> io::Result<()> {
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut out = BufWriter::new(stream);
+
+    loop {
+        let args = match read_command(&mut input) {
+            Ok(args) => args,
+            Err(ReadError::Closed) => return Ok(()),
+            Err(ReadError::Io(error)) => return Err(error),
+            Err(ReadError::Protocol(reason)) => {
+                write_reply(&mut out, &Reply::Error(format!("ERR Protocol error: {}", reason)))?;
+
+                return out.flush();
+            }
+        };
+
+        let Some((name, args)) = args.split_first() else {
+            continue;
+        };
+
+        let name = String::from_utf8_lossy(name).to_ascii_uppercase();
+
+        write_reply(&mut out, &execute(db, &name, args))?;
+
+        if name == "QUIT" {
+            return out.flush();
+        }
+
+        if input.buffer().is_empty() {
+            out.flush()?;
+        }
+    }
+}
+
+fn wrong_arity(name: &str) -> Reply {
+    Reply::Error(format!("ERR wrong number of arguments for '{}' command", name.to_ascii_lowercase()))
+}
+
+/**
+ * keys matching pattern, only * and prefix followed by * are supported
+ */
+fn keys(db: &Shared, pattern: &[u8]) -> Reply {
+    let Some(prefix) = pattern.strip_suffix(b"*").filter(|prefix| !prefix.iter().any(|b| b"*?[\\".contains(b))) else {
+        return Reply::Error("ERR only patterns of prefix followed by * are supported".to_string());
+    };
+
+    match db.scan(prefix, usize::MAX, |key| key.starts_with(prefix)) {
+        Ok(entries) => Reply::Array(entries.into_iter().map(|(key, _)| Reply::Bulk(key)).collect()),
+        Err(error) => Reply::Error(format!("ERR {}", error)),
+    }
+}
+
+fn info(db: &Shared) -> Reply {
+    let db = db.read();
+    let stats = db.disk_stats();
+
+    let text = format!(
+        "# Server\r\nsrdb_version:{}\r\n\r\n# Persistence\r\nfile_bytes:{}\r\npage_size:{}\r\nfree_pages:{}\r\nwal_bytes:{}\r\n\
+\r\n# Keyspace\r\ndb0:keys={}\r\n",
+        env!("CARGO_PKG_VERSION"),
+        stats.file_bytes,
+        stats.page_size,
+        stats.free_pages,
+        stats.wal_bytes,
+        db.len()
+    );
+
+    Reply::Bulk(text.into_bytes())
+}
+
+/**
+ * GET, SET, DEL, EXISTS, KEYS, DBSIZE, PING, INFO, plus SELECT 0, COMMAND and QUIT clients send on their own
+ */
+fn execute(db: &Shared, name: &str, args: &[Vec<u8>]) -> Reply {
+    let result = match (name, args) {
+        ("PING", []) => return Reply::Simple("PONG"),
+        ("PING", [message]) => return Reply::Bulk(message.clone()),
+        ("GET", [key]) => db.read().get(key).map(|value| value.map_or(Reply::Null, Reply::Bulk)),
+        ("SET", [key, value]) => db.write().insert(key, value).map(|()| Reply::Simple("OK")),
+        ("SET", [_, _, ..]) => return Reply::Error("ERR SET options are not supported".to_string()),
+        ("DEL", [_, ..]) => {
+            let mut db = db.write();
+
+            args.iter().try_fold(0, |deleted, key| Ok(deleted + db.delete(key)? as i64)).map(Reply::Integer)
+        }
+        ("EXISTS", [_, ..]) => {
+            let db = db.read();
+
+            args.iter().try_fold(0, |found, key| Ok(found + db.contains(key)? as i64)).map(Reply::Integer)
+        }
+        ("KEYS", [pattern]) => return keys(db, pattern),
+        ("DBSIZE", []) => return Reply::Integer(db.read().len() as i64),
+        ("INFO", [] | [_]) => return info(db),
+        ("SELECT", [index]) if index == b"0" => return Reply::Simple("OK"),
+        ("SELECT", [_]) => return Reply::Error("ERR DB index is out of range".to_string()),
+        ("COMMAND", _) => return Reply::Array(vec![]),
+        ("QUIT", _) => return Reply::Simple("OK"),
+        ("PING" | "GET" | "SET" | "DEL" | "EXISTS" | "KEYS" | "DBSIZE" | "INFO" | "SELECT", _) => return wrong_arity(name),
+        _ => return Reply::Error(format!("ERR unknown command '{}'", name.to_ascii_lowercase())),
+    };
+
+    result.unwrap_or_else(|error: srdb::Error| Reply::Error(format!("ERR {}", error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lcg(seed: u64) -> impl FnMut() -> u64 {
+        let mut state = seed;
+
+        move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            state >> 33
+        }
+    }
+
+    fn encode(args: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = format!("*{}\r\n", args.len()).into_bytes();
+
+        for arg in args {
+            bytes.extend(format!("${}\r\n", arg.len()).bytes());
+            bytes.extend(arg);
+            bytes.extend(b"\r\n");
+        }
+
+        bytes
+    }
+
+    /**
+     * arguments of any bytes, \r and \n included, as bulk strings carry them
+     */
+    fn random_args(next: &mut impl FnMut() -> u64) -> Vec<Vec<u8>> {
+        const ALPHABET: &[u8] = b"GETSabc*$:+-\r\n 0123456789\0\xff";
+
+        let mut args = vec![];
+
+        for _ in 0..next() % 5 {
+            args.push((0..next() % 12).map(|_| ALPHABET[next() as usize % ALPHABET.len()]).collect());
+        }
+
+        args
+    }
+
+    /**
+     * commands until error, every Ok consumes input, so there are never more of them than bytes
+     */
+    fn read_all(bytes: &[u8]) -> (Vec<Vec<Vec<u8>>>, ReadError) {
+        let mut input = bytes;
+        let mut commands = vec![];
+
+        loop {
+            match read_command(&mut input) {
+                Ok(args) => commands.push(args),
+                Err(error) => return (commands, error),
+            }
+
+            assert!(commands.len() <= bytes.len(), "{:?}", String::from_utf8_lossy(bytes));
+        }
+    }
+
+    #[test]
+    fn pipelined_commands_read_back() {
+        let mut next = lcg(0);
+
+        for _ in 0..1000 {
+            let commands: Vec<_> = (0..1 + next() % 4).map(|_| random_args(&mut next)).collect();
+            let bytes: Vec<u8> = commands.iter().flat_map(|args| encode(args)).collect();
+            let (read, error) = read_all(&bytes);
+
+            assert_eq!(read, commands);
+            assert!(matches!(error, ReadError::Closed));
+        }
+
+        let (read, _) = read_all(b"SET  key value\r\n\r\nPING\r\n");
+
+        let set = vec![b"SET".to_vec(), b"key".to_vec(), b"value".to_vec()];
+
+        assert_eq!(read, [set, vec![], vec![b"PING".to_vec()]], "inline commands split on spaces");
+    }
+
+    /**
+     * valid pipelines with bytes flipped, inserted, removed or cut off,
+     * reader must stop with error instead of panicking or looping
+     */
+    #[test]
+    fn mutated_input_never_panics() {
+        let mut next = lcg(1);
+
+        for _ in 0..20_000 {
+            let mut bytes: Vec<u8> = (0..1 + next() % 3).flat_map(|_| encode(&random_args(&mut next))).collect();
+
+            for _ in 0..1 + next() % 3 {
+                let at = next() as usize % (bytes.len() + 1);
+
+                match next() % 4 {
+                    0 if at < bytes.len() => bytes[at] = next() as u8,
+                    1 => bytes.insert(at, b"*$\r\n-1:9"[next() as usize % 8]),
+                    2 if at < bytes.len() => drop(bytes.remove(at)),
+                    _ => bytes.truncate(at),
+                }
+            }
+
+            let (read, error) = read_all(&bytes);
+
+            for args in read {
+                assert!(args.iter().map(Vec::len).sum::<usize>() <= bytes.len());
+            }
+
+            assert!(!matches!(error, ReadError::Io(_)));
+        }
+    }
+
+    #[test]
+    fn limits_are_checked_before_reading() {
+        let protocol = |bytes: &[u8]| match read_all(bytes) {
+            (read, ReadError::Protocol(reason)) if read.is_empty() => reason,
+            _ => panic!("{:?} is read", String::from_utf8_lossy(bytes)),
+        };
+
+        assert_eq!(protocol(b"*99999999999\r\n"), "length 99999999999 is over 1048576");
+        assert_eq!(protocol(b"*1\r\n$1000000000\r\nab\r\n"), "length 1000000000 is over 67108864");
+        assert_eq!(protocol(b"*1\r\n$-1\r\n"), "invalid length \"-1\"");
+        assert_eq!(protocol(b"*1\r\n$3\r\nab\r\n"), "unexpected end of input");
+        assert_eq!(protocol(b"*1\r\n$2\r\nabcd"), "bulk string does not end with CRLF");
+        assert_eq!(protocol(b"*1\r\n:2\r\n"), "expected '$', got \":2\"");
+        assert_eq!(protocol(b"*2\r\n$1\r\na\r\n"), "unexpected end of input");
+        assert_eq!(protocol(b"PING\n"), "line does not end with CRLF");
+        assert_eq!(protocol(&[b'a'; MAX_INLINE + 10]), "line is too long");
+    }
+}
@@ -0,0 +1,429 @@
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::Bound;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+use std::time::Duration;
+
+use srdb::Db;
+
+use crate::args::ServeArgs;
+use crate::config::{LogLevel, ProtocolName, ServeConfig};
+#[cfg(feature = "http")]
+use crate::http;
+use crate::resp;
+use crate::signals;
+use crate::Failure;
+
+/**
+ * entries returned by SCAN without limit
+ */
+const DEFAULT_SCAN_LIMIT: usize = 1000;
+
+/**
+ * entries scan takes from its snapshot at once, before it looks whether they are kept
+ */
+const SCAN_CHUNK: usize = 256;
+
+/**
+ * how often listener looks for connections while it also waits for signal
+ */
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(10);
+
+pub type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/**
+ * database shared by connection threads, every command is isolated from the others:
+ * point reads share database, changes hold it alone for their own duration only,
+ * scans read snapshot pinned when they start, see srdb::ReadTxn, so they neither wait for changes nor hold them up
+ */
+#[derive(Clone)]
+pub struct Shared(Arc<RwLock<Db>>);
+
+impl Shared {
+    fn new(db: Db) -> Shared {
+        Shared(Arc::new(RwLock::new(db)))
+    }
+
+    /**
+     * database behind lock, for followers, see serve_followers
+     */
+    pub fn db(&self) -> &RwLock<Db> {
+        &self.0
+    }
+
+    /**
+     * database for reads, shared with other readers,
+     * panic of one connection thread does not stop others from using it
+     */
+    pub fn read(&self) -> RwLockReadGuard<'_, Db> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /**
+     * database for change, waits for reads in progress to end, scans are not waited for
+     */
+    pub fn write(&self) -> RwLockWriteGuard<'_, Db> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /**
+     * up to limit entries from start on while keep accepts their keys, as of one moment:
+     * they come from read transaction begun by scan, database is locked only while it begins,
+     * so changes landing meanwhile are not seen and do not wait for scan
+     * entries are collected before any is written, so slow client does not hold up anyone
+     */
+    pub fn scan(&self, start: &[u8], limit: usize, keep: impl Fn(&[u8]) -> bool) -> Result<Entries, srdb::Error> {
+        let txn = self.read().begin_read()?;
+        let mut entries: Entries = vec![];
+        let mut from = Bound::Included(start.to_vec());
+
+        while entries.len() < limit {
+            let wanted = (limit - entries.len()).min(SCAN_CHUNK);
+            let chunk = txn.range_limit((from, Bound::Unbounded), wanted)?;
+            let full = chunk.len() == wanted;
+
+            for (key, value) in chunk {
+                if !keep(&key) {
+                    return Ok(entries);
+                }
+
+                entries.push((key, value));
+            }
+
+            match entries.last() {
+                Some((key, _)) if full => from = Bound::Excluded(key.clone()),
+                _ => break,
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+type Handler = fn(TcpStream, &Shared) -> io::Result<()>;
+
+/**
+ * handler of protocol with reply it sends to connection refused for going over limit
+ */
+struct Protocol {
+    handle: Handler,
+    busy: &'static str,
+}
+
+/**
+ * protocol of config, ServeConfig::load rejects http unless it is built in
+ */
+fn protocol(name: ProtocolName) -> Protocol {
+    match name {
+        ProtocolName::Resp => Protocol {
+            handle: resp::handle,
+            busy: resp::BUSY,
+        },
+        #[cfg(feature = "http")]
+        ProtocolName::Http => Protocol {
+            handle: http::handle,
+            busy: http::BUSY,
+        },
+        _ => Protocol {
+            handle,
+            busy: "ERR too many connections\n",
+        },
+    }
+}
+
+/**
+ * ships committed batches to followers connecting to listener, one thread per follower, until signal comes
+ */
+fn serve_followers(listener: TcpListener, db: Shared, log_level: LogLevel) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+
+    while !signals::requested() {
+        let stream = match listener.accept().and_then(|(stream, _)| stream.set_nonblocking(false).map(|()| stream)) {
+            Ok(stream) => stream,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_INTERVAL);
+
+                continue;
+            }
+            Err(error) => {
+                log_level.log(LogLevel::Error, format_args!("accept of follower failed: {}", error));
+
+                continue;
+            }
+        };
+
+        let peer = stream.peer_addr().map_or_else(|_| "unknown peer".to_string(), |peer| peer.to_string());
+        let db = db.clone();
+
+        log_level.log(LogLevel::Info, format_args!("follower {} connected", peer));
+
+        thread::spawn(move || {
+            let result = stream.try_clone().map_err(srdb::Error::Io).and_then(|input| {
+                srdb::serve_follower(db.db(), BufReader::new(input), BufWriter::new(stream), signals::requested)
+            });
+
+            match result {
+                Ok(()) => log_level.log(LogLevel::Info, format_args!("follower {} disconnected", peer)),
+                Err(srdb::Error::Io(error))
+                    if matches!(error.kind(), io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset) =>
+                {
+                    log_level.log(LogLevel::Info, format_args!("follower {} disconnected", peer))
+                }
+                Err(error) => log_level.log(LogLevel::Warn, format_args!("follower {} failed: {}", peer, error)),
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/**
+ * open connection, counted until it is dropped
+ */
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/**
+ * serves newline-delimited text protocol, one thread per client:
+ *   PING                 -> OK
+ *   SET <key> <value>    -> OK, value is the rest of line and may hold spaces
+ *   GET <key>            -> VALUE <value> or NOT_FOUND
+ *   DEL <key>            -> OK or NOT_FOUND
+ *   SCAN [prefix [limit]] -> VALUE <key> <value> per entry, then OK
+ * anything else gets ERR <message> and connection stays open
+ * keys and values in responses have backslash, newline and carriage return escaped as \\, \n and \r
+ * with --resp clients speak subset of redis protocol instead, see resp::handle,
+ * with --http json over http, see http::handle, it is there with http feature only
+ * commands of all clients are isolated from each other, see Shared
+ * client over --max-connections is refused with error, client silent for --idle-timeout seconds is disconnected,
+ * 0 turns timeout off
+ * settings may come from --config file instead, flags given too override it, see ServeConfig
+ * --read-only database is never created nor checkpointed, changes of clients fail
+ * with --replication-bind followers connect there, see srdb::serve_follower and replicate::replicate,
+ * --replication-backlog bytes of batches are kept for those behind
+ * with signals feature SIGINT and SIGTERM stop accepting clients, commands in flight finish, database is
+ * checkpointed and the rest of commands wait for process to exit, so restart does not replay log
+ */
+pub fn serve(args: &ServeArgs) -> Result<(), Failure> {
+    let config = ServeConfig::load(args)?;
+    let path = config.path.as_deref().unwrap_or_default();
+    let protocol = protocol(config.protocol);
+    let log_level = config.log_level;
+    let max_connections = config.max_connections;
+    let idle_timeout = match config.idle_timeout {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    };
+
+    let options = config.options();
+    let db = Shared::new(if config.read_only { options.open(path)? } else { options.open_or_create(path)? });
+    let listener = TcpListener::bind(&config.bind)?;
+    let open = Arc::new(AtomicUsize::new(0));
+
+    println!("listening on {}", listener.local_addr()?);
+
+    signals::install();
+
+    if let Some(bind) = &config.replication_bind {
+        let listener = TcpListener::bind(bind)?;
+        let db = db.clone();
+
+        println!("listening for followers on {}", listener.local_addr()?);
+
+        thread::spawn(move || {
+            if let Err(error) = serve_followers(listener, db, log_level) {
+                log_level.log(LogLevel::Error, format_args!("replication stopped: {}", error));
+            }
+        });
+    }
+    listener.set_nonblocking(signals::ENABLED)?;
+
+    while !signals::requested() {
+        let mut stream = match listener.accept().and_then(|(stream, _)| stream.set_nonblocking(false).map(|()| stream)) {
+            Ok(stream) => stream,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_INTERVAL);
+
+                continue;
+            }
+            Err(error) => {
+                log_level.log(LogLevel::Error, format_args!("accept failed: {}", error));
+
+                continue;
+            }
+        };
+
+        let peer = stream.peer_addr().map_or_else(|_| "unknown peer".to_string(), |peer| peer.to_string());
+
+        if open.fetch_add(1, Ordering::SeqCst) >= max_connections {
+            open.fetch_sub(1, Ordering::SeqCst);
+            log_level.log(LogLevel::Warn, format_args!("refused {}, {} connections are open", peer, max_connections));
+
+            let _ = stream.write_all(protocol.busy.as_bytes());
+
+            continue;
+        }
+
+        let connection = Connection(open.clone());
+        let handler = protocol.handle;
+        let db = db.clone();
+
+        thread::spawn(move || {
+            let _connection = connection;
+            let result = stream.set_read_timeout(idle_timeout).and_then(|()| handler(stream, &db));
+
+            match result {
+                Ok(()) => {}
+                Err(error) if matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    log_level.log(LogLevel::Info, format_args!("closed idle connection with {}", peer))
+                }
+                Err(error) => log_level.log(LogLevel::Warn, format_args!("connection with {} failed: {}", peer, error)),
+            }
+        });
+    }
+
+    log_level.log(LogLevel::Info, format_args!("shutting down, {} connections are open", open.load(Ordering::SeqCst)));
+
+    let mut writer = db.write();
+
+    if !config.read_only {
+        writer.checkpoint()?;
+    }
+
+    std::mem::forget(writer);
+
+    Ok(())
+}
+
+/**
+ * answers commands until client closes connection
+ */
+fn handle(stream: TcpStream, db: &Shared) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut out = BufWriter::new(stream);
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let command = line.trim_end_matches(['\n', '\r']);
+
+        writeln!(out, "{}", execute(db, command))?;
+        out.flush()?;
+    }
+}
+
+fn escape(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+
+    for c in String::from_utf8_lossy(bytes).chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/**
+ * response to command, failures of command are ERR responses
+ */
+fn execute(db: &Shared, command: &str) -> String {
+    let (name, rest) = command.split_once(' ').unwrap_or((command, ""));
+    let args: Vec<&str> = rest.split(' ').filter(|arg| !arg.is_empty()).collect();
+
+    let result = match (name.to_ascii_uppercase().as_str(), args.as_slice()) {
+        ("PING", []) => Ok("OK".to_string()),
+        ("SET", [key, ..]) => {
+            let value = rest.trim_start_matches(' ')[key.len()..].strip_prefix(' ').unwrap_or("");
+
+            db.write().insert(key.as_bytes(), value.as_bytes()).map(|()| "OK".to_string())
+        }
+        ("GET", [key]) => db.read().get(key.as_bytes()).map(|value| match value {
+            Some(value) => format!("VALUE {}", escape(&value)),
+            None => "NOT_FOUND".to_string(),
+        }),
+        ("DEL", [key]) => db
+            .write()
+            .delete(key.as_bytes())
+            .map(|found| if found { "OK" } else { "NOT_FOUND" }.to_string()),
+        ("SCAN", [] | [_] | [_, _]) => {
+            let prefix = args.first().unwrap_or(&"").as_bytes();
+            let Ok(limit) = args.get(1).map_or(Ok(DEFAULT_SCAN_LIMIT), |limit| limit.parse()) else {
+                return format!("ERR {} is not a valid limit", args[1]);
+            };
+
+            db.scan(prefix, limit, |key| key.starts_with(prefix)).map(|entries| {
+                let mut response = String::new();
+
+                for (key, value) in entries {
+                    response.push_str(&format!("VALUE {} {}\n", escape(&key), escape(&value)));
+                }
+
+                response + "OK"
+            })
+        }
+        ("PING" | "SET" | "GET" | "DEL" | "SCAN", _) => return format!("ERR wrong number of arguments for {}", name),
+        _ => return format!("ERR unknown command {:?}", name),
+    };
+
+    result.unwrap_or_else(|error| format!("ERR {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use srdb::MemStorage;
+
+    use super::*;
+
+    fn key(i: usize) -> Vec<u8> {
+        format!("key{:04}", i).into_bytes()
+    }
+
+    /**
+     * scan holds no lock while it goes, writes and reads of others run between its keys, scan does not see writes
+     */
+    #[test]
+    fn scan_reads_snapshot_without_holding_database() {
+        let db = Db::create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new())).unwrap();
+        let shared = Shared::new(db);
+
+        for i in 0..3 * SCAN_CHUNK {
+            shared.write().insert(&key(i), b"old").unwrap();
+        }
+
+        let entries = shared
+            .scan(b"key", usize::MAX, |scanned| {
+                let i = String::from_utf8_lossy(&scanned[3..]).parse::<usize>().unwrap();
+                let mut db = shared.write();
+
+                db.delete(&key(3 * SCAN_CHUNK - 1 - i)).unwrap();
+                db.insert(&key(3 * SCAN_CHUNK + i), b"new").unwrap();
+                drop(db);
+
+                assert_eq!(shared.read().get(&key(3 * SCAN_CHUNK + i)).unwrap(), Some(b"new".to_vec()));
+
+                scanned.starts_with(b"key")
+            })
+            .unwrap();
+        let expected: Entries = (0..3 * SCAN_CHUNK).map(|i| (key(i), b"old".to_vec())).collect();
+
+        assert!(entries == expected);
+        assert_eq!(shared.read().len(), 3 * SCAN_CHUNK);
+        assert_eq!(shared.scan(b"key", 10, |scanned| scanned < key(3 * SCAN_CHUNK + 5).as_slice()).unwrap().len(), 5);
+    }
+}
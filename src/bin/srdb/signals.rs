@@ -0,0 +1,34 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/**
+ * whether install catches signals, with signals feature on unix only
+ */
+pub const ENABLED: bool = cfg!(all(feature = "signals", unix));
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(all(feature = "signals", unix))]
+extern "C" fn on_signal(_: libc::c_int) {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/**
+ * from now on SIGINT and SIGTERM ask for shutdown instead of killing process, see requested
+ * without ENABLED process keeps default handling
+ */
+pub fn install() {
+    #[cfg(all(feature = "signals", unix))]
+    unsafe {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/**
+ * whether SIGINT or SIGTERM came since install
+ */
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,410 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::encryption::Cipher;
+use crate::error::{Error, Result};
+use crate::pager::{PageId, Pager};
+
+/**
+ * cached copy of page
+ * tick is the time of last access, key of frame in lru order
+ */
+#[derive(Debug)]
+struct Frame {
+    data: Vec<u8>,
+    pins: usize,
+    dirty: bool,
+    tick: u64,
+}
+
+/**
+ * keeps up to capacity pages in memory in front of pager
+ * least recently used unpinned page is evicted first, dirty page is written back on eviction
+ * unless stealing is turned off, then dirty pages stay in memory until flush
+ */
+#[derive(Debug)]
+pub struct PageCache {
+    pager: Pager,
+    capacity: usize,
+    frames: HashMap<PageId, Frame>,
+    lru: BTreeMap<u64, PageId>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+    mapped: u64,
+    steal: bool,
+}
+
+impl PageCache {
+    pub fn new(pager: Pager, capacity: usize) -> PageCache {
+        assert!(capacity > 0, "cache must hold at least one page");
+
+        PageCache {
+            pager,
+            capacity,
+            frames: HashMap::with_capacity(capacity),
+            lru: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+            mapped: 0,
+            steal: true,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.pager.page_size()
+    }
+
+    pub fn file_page_size(&self) -> usize {
+        self.pager.file_page_size()
+    }
+
+    pub fn page_count(&self) -> PageId {
+        self.pager.page_count()
+    }
+
+    pub fn file_pages(&self) -> PageId {
+        self.pager.file_pages()
+    }
+
+    pub(crate) fn cipher(&self) -> Option<&Cipher> {
+        self.pager.cipher()
+    }
+
+    pub fn torn_bytes(&self) -> Result<u64> {
+        self.pager.torn_bytes()
+    }
+
+    pub fn trim(&mut self) -> Result<u64> {
+        self.pager.trim()
+    }
+
+    pub fn metadata_pages(&self) -> PageId {
+        self.pager.metadata_pages()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.pager.generation()
+    }
+
+    pub fn file_id(&self) -> u64 {
+        self.pager.file_id()
+    }
+
+    /**
+     * see Pager::changed_since
+     */
+    pub fn changed_since(&self, generation: u64) -> Vec<PageId> {
+        self.pager.changed_since(generation)
+    }
+
+    /**
+     * with steal turned off dirty pages are never written on eviction,
+     * so file changes only on flush
+     */
+    pub fn set_steal(&mut self, steal: bool) {
+        self.steal = steal;
+    }
+
+    /**
+     * number of reads served from memory
+     */
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /**
+     * number of reads which went to disk
+     */
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /**
+     * number of reads served from mapping without copying page into cache
+     */
+    pub fn mapped_reads(&self) -> u64 {
+        self.mapped
+    }
+
+    /**
+     * reads go through memory mapping of file if it can be mapped, returns whether they do
+     */
+    pub fn set_mmap(&mut self, mmap: bool) -> bool {
+        self.pager.set_mmap(mmap)
+    }
+
+    /**
+     * number of cached pages modified since they were loaded or flushed
+     */
+    pub fn dirty_pages(&self) -> usize {
+        self.frames.values().filter(|frame| frame.dirty).count()
+    }
+
+    pub fn allocate_page(&mut self) -> Result<PageId> {
+        self.pager.allocate_page()
+    }
+
+    /**
+     * drops cached copy without writing it back and returns page to pager
+     */
+    pub fn free_page(&mut self, page_id: PageId) -> Result<()> {
+        if let Some(frame) = self.frames.get(&page_id) {
+            if frame.pins > 0 {
+                return Err(Error::PagePinned(page_id));
+            }
+
+            self.lru.remove(&frame.tick);
+            self.frames.remove(&page_id);
+        }
+
+        self.pager.free_page(page_id)
+    }
+
+    /**
+     * moves frame to the most recently used end
+     */
+    fn touch(&mut self, page_id: PageId) -> &mut Frame {
+        self.tick += 1;
+
+        let frame = self.frames.get_mut(&page_id).unwrap();
+
+        self.lru.remove(&frame.tick);
+        self.lru.insert(self.tick, page_id);
+        frame.tick = self.tick;
+
+        frame
+    }
+
+    /**
+     * makes room for one more frame, fails if no cached page can be evicted
+     */
+    fn evict(&mut self) -> Result<()> {
+        if self.frames.len() < self.capacity {
+            return Ok(());
+        }
+
+        let victim = self
+            .lru
+            .values()
+            .copied()
+            .find(|page_id| {
+                let frame = &self.frames[page_id];
+
+                frame.pins == 0 && (self.steal || !frame.dirty)
+            })
+            .ok_or(Error::CacheFull(self.capacity))?;
+
+        let frame = &self.frames[&victim];
+
+        if frame.dirty {
+            self.pager.write_page(victim, &frame.data)?;
+        }
+
+        let frame = self.frames.remove(&victim).unwrap();
+        self.lru.remove(&frame.tick);
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(page = victim, dirty = frame.dirty, "evict");
+
+        Ok(())
+    }
+
+    /**
+     * loads page into cache if it is not there yet
+     */
+    fn load(&mut self, page_id: PageId) -> Result<&mut Frame> {
+        if self.frames.contains_key(&page_id) {
+            self.hits += 1;
+
+            return Ok(self.touch(page_id));
+        }
+
+        self.misses += 1;
+
+        let mut data = vec![0; self.pager.page_size()];
+
+        self.pager.read_page(page_id, &mut data)?;
+        self.evict()?;
+
+        Ok(self.insert(page_id, data, false))
+    }
+
+    /**
+     * adds frame as the most recently used, caller makes room for it
+     */
+    fn insert(&mut self, page_id: PageId, data: Vec<u8>, dirty: bool) -> &mut Frame {
+        self.tick += 1;
+        self.lru.insert(self.tick, page_id);
+
+        self.frames.entry(page_id).or_insert(Frame {
+            data,
+            pins: 0,
+            dirty,
+            tick: self.tick,
+        })
+    }
+
+    /**
+     * page not in cache is served straight from mapping when file is mapped, see Pager::set_mmap
+     */
+    pub fn read(&mut self, page_id: PageId) -> Result<&[u8]> {
+        if !self.frames.contains_key(&page_id) && self.pager.maps(page_id) {
+            self.mapped += 1;
+
+            return self.pager.mapped_page(page_id);
+        }
+
+        Ok(&self.load(page_id)?.data)
+    }
+
+    /**
+     * replaces contents of page in cache and marks it dirty, disk is written on eviction
+     * data must be exactly one page long
+     */
+    pub fn write(&mut self, page_id: PageId, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), self.pager.page_size(), "buffer must hold exactly one page");
+
+        if !self.frames.contains_key(&page_id) {
+            if page_id >= self.pager.page_count() {
+                return Err(Error::PageOutOfBounds {
+                    page_id,
+                    page_count: self.pager.page_count(),
+                });
+            }
+
+            self.evict()?;
+            self.insert(page_id, data.to_vec(), true);
+
+            return Ok(());
+        }
+
+        let frame = self.touch(page_id);
+
+        frame.data.copy_from_slice(data);
+        frame.dirty = true;
+
+        Ok(())
+    }
+
+    /**
+     * keeps page in cache until matching unpin, pins are counted
+     */
+    pub fn pin(&mut self, page_id: PageId) -> Result<()> {
+        self.load(page_id)?.pins += 1;
+
+        Ok(())
+    }
+
+    pub fn unpin(&mut self, page_id: PageId) {
+        let frame = self.frames.get_mut(&page_id).expect("page is not pinned");
+
+        assert!(frame.pins > 0, "page {} is not pinned", page_id);
+
+        frame.pins -= 1;
+    }
+
+    /**
+     * dirty pages in ascending page id order
+     */
+    pub fn dirty(&self) -> Vec<(PageId, &[u8])> {
+        let mut dirty: Vec<(PageId, &[u8])> = self
+            .frames
+            .iter()
+            .filter(|(_, frame)| frame.dirty)
+            .map(|(page_id, frame)| (*page_id, frame.data.as_slice()))
+            .collect();
+
+        dirty.sort_unstable_by_key(|(page_id, _)| *page_id);
+
+        dirty
+    }
+
+    /**
+     * writes dirty pages in ascending page id order, they are not committed
+     */
+    pub fn flush(&mut self) -> Result<()> {
+        self.flush_pages(usize::MAX)?;
+
+        Ok(())
+    }
+
+    /**
+     * writes up to limit dirty pages with the lowest ids, returns how many were written
+     * like flush, pages are not committed
+     */
+    pub fn flush_pages(&mut self, limit: usize) -> Result<usize> {
+        let mut dirty: Vec<PageId> = self.frames.iter().filter(|(_, frame)| frame.dirty).map(|(page_id, _)| *page_id).collect();
+
+        dirty.sort_unstable();
+        dirty.truncate(limit);
+
+        for &page_id in &dirty {
+            let frame = self.frames.get_mut(&page_id).unwrap();
+
+            self.pager.write_page(page_id, &frame.data)?;
+            frame.dirty = false;
+        }
+
+        Ok(dirty.len())
+    }
+
+    /**
+     * whether every page written to pager is committed
+     */
+    pub fn is_committed(&self) -> bool {
+        self.pager.is_committed()
+    }
+
+    /**
+     * see Pager::pin_snapshot, cache must be flushed
+     */
+    pub fn pin_snapshot(&mut self) -> Vec<PageId> {
+        self.pager.pin_snapshot()
+    }
+
+    pub fn unpin_snapshot(&mut self, generation: u64) {
+        self.pager.unpin_snapshot(generation)
+    }
+
+    pub fn has_snapshot(&self) -> bool {
+        self.pager.has_snapshot()
+    }
+
+    pub fn oldest_snapshot(&self) -> Option<u64> {
+        self.pager.oldest_snapshot()
+    }
+
+    pub fn held_pages(&self) -> usize {
+        self.pager.held_pages()
+    }
+
+    pub fn collect_garbage(&mut self) -> usize {
+        self.pager.collect_garbage()
+    }
+
+    /**
+     * reads page of pinned snapshot straight from file, cached pages may be newer
+     */
+    pub fn read_snapshot(&mut self, page_id: PageId, physical: PageId, buf: &mut [u8]) -> Result<()> {
+        self.pager.read_snapshot(page_id, physical, buf)
+    }
+
+    /**
+     * flushes everything written to file to disk
+     */
+    pub fn sync(&mut self) -> Result<()> {
+        self.pager.sync()
+    }
+
+    /**
+     * makes flushed pages the committed state of file, see Pager::commit
+     */
+    pub fn commit(&mut self, sync: bool) -> Result<()> {
+        self.pager.commit(sync)
+    }
+}
@@ -0,0 +1,69 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/**
+ * conversion of keys to bytes and back, used by snapshots and disk pages
+ * encoding is fixed little-endian, so files are portable between machines
+ */
+pub trait Codec: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /**
+     * bytes hold exactly one encoded value, None if they are not a valid encoding
+     */
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+/**
+ * splits len bytes off the front of bytes, None if there are fewer
+ */
+#[cfg(feature = "std")]
+pub(crate) fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+
+    let (head, tail) = bytes.split_at(len);
+
+    *bytes = tail;
+
+    Some(head)
+}
+
+macro_rules! int_codec {
+    ($($int:ty),*) => {
+        $(
+            impl Codec for $int {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(bytes: &[u8]) -> Option<Self> {
+                    Some(<$int>::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+int_codec!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl Codec for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+impl Codec for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt::Debug;
+use std::hint;
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::PersistentBTree;
+
+/**
+ * Arc which is loaded and replaced atomically, loads never wait
+ * load counts itself in reader slot of current epoch only while it takes pointer and reference to it,
+ * and tries again if epoch changed after it counted itself, so it is never in slot store no longer watches
+ * store flips epoch and waits for slot of previous epoch to empty before it drops its reference to old value
+ */
+struct AtomicArc<T> {
+    ptr: AtomicPtr<T>,
+    epoch: AtomicUsize,
+    readers: [AtomicUsize; 2],
+    _value: PhantomData<Arc<T>>,
+}
+
+impl<T> AtomicArc<T> {
+    fn new(value: Arc<T>) -> AtomicArc<T> {
+        AtomicArc {
+            ptr: AtomicPtr::new(Arc::into_raw(value) as *mut T),
+            epoch: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            _value: PhantomData,
+        }
+    }
+
+    fn load(&self) -> Arc<T> {
+        loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            let readers = &self.readers[epoch & 1];
+
+            readers.fetch_add(1, Ordering::SeqCst);
+
+            if self.epoch.load(Ordering::SeqCst) != epoch {
+                readers.fetch_sub(1, Ordering::SeqCst);
+
+                continue;
+            }
+
+            let ptr = self.ptr.load(Ordering::SeqCst);
+            let value = unsafe {
+                Arc::increment_strong_count(ptr);
+                Arc::from_raw(ptr)
+            };
+
+            readers.fetch_sub(1, Ordering::SeqCst);
+
+            return value;
+        }
+    }
+
+    /**
+     * stores must not run concurrently, loads may
+     */
+    fn store(&self, value: Arc<T>) {
+        let old = self.ptr.swap(Arc::into_raw(value) as *mut T, Ordering::SeqCst);
+        let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+
+        while self.readers[epoch & 1].load(Ordering::SeqCst) != 0 {
+            hint::spin_loop();
+        }
+
+        drop(unsafe { Arc::from_raw(old) });
+    }
+}
+
+impl<T> Drop for AtomicArc<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Arc::from_raw(*self.ptr.get_mut()) });
+    }
+}
+
+struct Inner<T: Ord + Clone + Debug> {
+    current: AtomicArc<PersistentBTree<T>>,
+    writer: Mutex<()>,
+}
+
+/**
+ * B-tree for many reader threads and one writer at a time, handle is cloned into every thread
+ * readers take immutable snapshot of the current version without any lock and traverse it while writer goes on,
+ * writer copies nodes on its path, see PersistentBTree, and publishes new version atomically,
+ * per operation or once for write_batch, writers wait for each other on mutex
+ * version is freed when the last snapshot of it is dropped, unchanged nodes are shared between versions
+ */
+pub struct ConcurrentBTree<T: Ord + Clone + Debug> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T: Ord + Clone + Debug> Clone for ConcurrentBTree<T> {
+    fn clone(&self) -> Self {
+        ConcurrentBTree {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: Ord + Clone + Debug> Debug for ConcurrentBTree<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+impl<T: Ord + Clone + Debug> From<PersistentBTree<T>> for ConcurrentBTree<T> {
+    fn from(tree: PersistentBTree<T>) -> Self {
+        ConcurrentBTree {
+            inner: Arc::new(Inner {
+                current: AtomicArc::new(Arc::new(tree)),
+                writer: Mutex::new(()),
+            }),
+        }
+    }
+}
+
+impl<T: Ord + Clone + Debug> ConcurrentBTree<T> {
+    pub fn new(t: usize) -> ConcurrentBTree<T> {
+        PersistentBTree::new(t).into()
+    }
+
+    /**
+     * the current version, later writes do not change it
+     */
+    pub fn snapshot(&self) -> Arc<PersistentBTree<T>> {
+        self.inner.current.load()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshot().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshot().is_empty()
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.snapshot().contains(value)
+    }
+
+    pub fn range(&self, range: impl RangeBounds<T>) -> Vec<T> {
+        self.snapshot().range(range)
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.snapshot().to_vec()
+    }
+
+    /**
+     * changes copy of the current version with f and publishes it, readers see all changes of f at once
+     */
+    pub fn write_batch<R>(&self, f: impl FnOnce(&mut PersistentBTree<T>) -> R) -> R {
+        let _writer = self.inner.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut tree = PersistentBTree::clone(&self.snapshot());
+        let result = f(&mut tree);
+
+        self.inner.current.store(Arc::new(tree));
+
+        result
+    }
+
+    pub fn insert(&self, value: T) {
+        self.write_batch(|tree| tree.insert(value))
+    }
+
+    pub fn delete(&self, value: &T) -> bool {
+        self.write_batch(|tree| tree.delete(value))
+    }
+}
@@ -0,0 +1,2719 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind};
+use std::ops::{Bound, Deref, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::batch::{Op, WriteBatch};
+use crate::cache::PageCache;
+use crate::codec::{take, Codec};
+use crate::encryption::Key;
+use crate::error::{Error, Result};
+use crate::header::{Header, BYTES_CODEC, LZ4_CODEC};
+use crate::lock::FileLock;
+use crate::options::SrdbOptions;
+use crate::node_store::{self, NodeStore};
+use crate::page::{
+    decode_free_page, decode_overflow_page, encode_free_page, encode_overflow_page, max_t, node_count,
+    OVERFLOW_HEADER_SIZE,
+};
+use crate::pager::{PageId, Pager, HEADER_PAGE, PAGE_SIZE};
+use crate::replication::Backlog;
+use crate::storage::Storage;
+use crate::wal::{Record, Wal, FIRST_SEGMENT};
+use crate::{BTree, Node};
+
+mod backup;
+mod catalog;
+mod transactions;
+mod verify;
+
+pub use backup::{Backup, BackupReport, StreamProgress, TreeDigest};
+pub use catalog::Table;
+pub use transactions::{ReadTxn, WriteTxn};
+pub use verify::{Problem, Repair, SalvageReport, VerifyMode, VerifyReport};
+
+use catalog::{Tree, GARBAGE, RECLAIM_STEPS};
+use transactions::WriterSlot;
+
+/**
+ * pages kept in memory by default, 1 MiB with 4 KiB pages
+ */
+pub const DEFAULT_CACHE_PAGES: usize = 256;
+
+/**
+ * largest encoded entry (key length, key and value) stored in node page,
+ * node fanout is derived from it
+ * bigger values are moved to overflow pages, key must fit with overflow reference
+ */
+pub const MAX_ENTRY_SIZE: usize = 256;
+
+/**
+ * encoded entry without value bytes: key length (u16), value tag (u8)
+ */
+const ENTRY_HEADER_SIZE: usize = 3;
+
+/**
+ * length (u64) and first page (u32) of overflow value
+ */
+const OVERFLOW_REF_SIZE: usize = 12;
+
+/**
+ * entries between calls of progress of table stream
+ */
+pub const PROGRESS_KEYS: u64 = 1024;
+
+/**
+ * log size after which write checkpoints database
+ */
+pub const DEFAULT_WAL_LIMIT: u64 = 16 << 20;
+
+/**
+ * log segment size after which the next segment is started
+ */
+pub const DEFAULT_WAL_SEGMENT_SIZE: u64 = 4 << 20;
+
+const INLINE: u8 = 0;
+const OVERFLOW: u8 = 1;
+
+/**
+ * added to tag of compressed value
+ */
+const COMPRESSED: u8 = 2;
+
+/**
+ * when log and pages are synced to disk, only synced data survives power loss
+ * process crash without power loss keeps everything written in any mode
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SyncMode {
+    /**
+     * log is synced after every record, every returned insert, delete and write survives
+     */
+    #[default]
+    Always,
+    /**
+     * log is synced after write of batch, single inserts and deletes are synced
+     * by the next write or flush, so after power loss they may be lost,
+     * but everything before the last returned write survives
+     */
+    OnCommit,
+    /**
+     * log and pages are never synced, operating system decides when data reaches disk,
+     * after power loss any suffix of changes may be lost
+     */
+    Off,
+}
+
+/**
+ * value is kept in node page if entry fits into MAX_ENTRY_SIZE,
+ * otherwise in chain of overflow pages
+ * compressed value is stored either way as its original length (u64) and lz4 block
+ */
+#[derive(Clone)]
+enum Value {
+    Inline(Vec<u8>),
+    Overflow { len: u64, first: PageId },
+    Compressed(Box<Value>),
+}
+
+impl Value {
+    fn is_inline(&self) -> bool {
+        matches!(self, Value::Inline(_))
+    }
+}
+
+/**
+ * how pages missing from cache are read
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ReadPath {
+    /**
+     * page is read from file into cache
+     */
+    #[default]
+    Io,
+    /**
+     * file is memory mapped and nodes are decoded straight from mapping, writes still go through file,
+     * falls back to Io where file can not be mapped
+     */
+    Mmap,
+}
+
+/**
+ * when background thread flushes dirty pages of database, see Db::set_background_flush
+ * flush commits pages like Db::flush, so log is not replayed for them after crash
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackgroundFlush {
+    /**
+     * time between flushes
+     */
+    pub interval: Duration,
+    /**
+     * flush starts early once change leaves this many dirty pages
+     */
+    pub dirty_pages: usize,
+    /**
+     * pages written under one lock of database, operations of handle wait at most for one batch
+     */
+    pub batch_pages: usize,
+}
+
+impl Default for BackgroundFlush {
+    fn default() -> Self {
+        BackgroundFlush {
+            interval: Duration::from_secs(1),
+            dirty_pages: DEFAULT_CACHE_PAGES / 4,
+            batch_pages: 16,
+        }
+    }
+}
+
+/**
+ * space taken by database, see Db::disk_stats
+ * pages are logical pages of page_size bytes, header included, file_pages are physical ones,
+ * file also holds superblocks, page table and pages kept by shadow paging until the next commit
+ * tree_pages are node and overflow pages, their split and used_bytes are known to exact stats only
+ * fragmentation is share of file held by neither tree, header nor page table, close to 0 after vacuum
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiskStats {
+    pub page_size: usize,
+    pub file_pages: u64,
+    pub file_bytes: u64,
+    pub pages: u64,
+    pub free_pages: u64,
+    pub tree_pages: u64,
+    pub node_pages: Option<u64>,
+    pub overflow_pages: Option<u64>,
+    pub used_bytes: Option<u64>,
+    pub entries: u64,
+    pub wal_bytes: u64,
+    pub fragmentation: f64,
+}
+
+/**
+ * what open found, recovered is set when log held batches missing from pages and replayed of them were applied,
+ * database closed by Db::close is opened without recovery
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpenStats {
+    pub recovered: bool,
+    pub replayed: u64,
+}
+
+/**
+ * key with its value, entries are ordered and compared by key only
+ */
+#[derive(Clone)]
+struct Entry {
+    key: Vec<u8>,
+    value: Value,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl Debug for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", String::from_utf8_lossy(&self.key))
+    }
+}
+
+/**
+ * key length (u16), key bytes, value tag (u8),
+ * then INLINE value bytes up to the end or OVERFLOW value length (u64) and first page (u32),
+ * tag of compressed value is one of them plus COMPRESSED
+ */
+impl Codec for Entry {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.key.len() as u16).to_le_bytes());
+        out.extend_from_slice(&self.key);
+
+        let (value, compressed) = match &self.value {
+            Value::Compressed(value) => (value.as_ref(), COMPRESSED),
+            value => (value, 0),
+        };
+
+        match value {
+            Value::Inline(value) => {
+                out.push(INLINE + compressed);
+                out.extend_from_slice(value);
+            }
+            Value::Overflow { len, first } => {
+                out.push(OVERFLOW + compressed);
+                out.extend_from_slice(&len.to_le_bytes());
+                out.extend_from_slice(&first.to_le_bytes());
+            }
+            Value::Compressed(_) => unreachable!("value is compressed twice"),
+        }
+    }
+
+    fn decode(mut bytes: &[u8]) -> Option<Self> {
+        let bytes = &mut bytes;
+        let key_len = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()) as usize;
+        let key = take(bytes, key_len)?.to_vec();
+
+        let tag = take(bytes, 1)?[0];
+
+        let value = match tag % COMPRESSED {
+            INLINE => Value::Inline(bytes.to_vec()),
+            _ => {
+                let len = u64::from_le_bytes(take(bytes, 8)?.try_into().unwrap());
+                let first = PageId::from_le_bytes(take(bytes, 4)?.try_into().unwrap());
+
+                Value::Overflow { len, first }
+            }
+        };
+
+        let value = match tag / COMPRESSED {
+            0 => value,
+            1 => Value::Compressed(Box::new(value)),
+            _ => return None,
+        };
+
+        Some(Entry { key, value })
+    }
+}
+
+/**
+ * disk backed B-tree of byte keys and values, keys are unique
+ * nodes are read and written through page cache, one node per page
+ * every change is appended to write ahead log first, pages reach the file only on flush,
+ * so after crash open replays batches missing from pages
+ * dropping Db flushes too, close reports its errors
+ * with background flush turned on, see set_background_flush, pages are flushed by a thread as well
+ */
+#[derive(Debug)]
+pub struct Db {
+    core: Arc<Mutex<Core>>,
+    flusher: Option<Flusher>,
+    writer: WriterSlot,
+}
+
+/**
+ * state of database shared by handle and background flusher
+ * handle keeps it locked for the whole operation, so flusher never sees change halfway
+ */
+#[derive(Debug)]
+struct Core {
+    cache: PageCache,
+    /**
+     * None for read only handle
+     */
+    wal: Option<Wal>,
+    root: PageId,
+    len: usize,
+    t: usize,
+    header_dirty: bool,
+    /**
+     * sequence number of the last batch written to log
+     */
+    seq: u64,
+    /**
+     * sequence number of the last batch fully applied to pages,
+     * differs from seq only while batch is being applied
+     */
+    applied_seq: u64,
+    sync_mode: SyncMode,
+    read_path: ReadPath,
+    /**
+     * checkpoint is made once log grows past it, None turns automatic checkpoints off
+     */
+    wal_limit: Option<u64>,
+    /**
+     * oldest log segment recovery needs, recorded in header, checkpoint moves it to a new empty segment
+     */
+    wal_segment: u64,
+    /**
+     * freed pages are linked through themselves, header page id ends the list
+     */
+    free_head: PageId,
+    free_count: usize,
+    /**
+     * lock on database file, held while handle lives, None over plain storages
+     */
+    lock: Option<FileLock>,
+    path: Option<PathBuf>,
+    /**
+     * error of background flush, returned by the next operation of handle
+     */
+    background_error: Option<Error>,
+    /**
+     * long values are compressed, set at creation and stored in header as value codec
+     */
+    compression: bool,
+    /**
+     * key of encrypted file, backup and vacuum encrypt new files with it
+     */
+    key: Option<Key>,
+    catalog: Tree,
+    /**
+     * catalog may hold dropped trees, reclaim clears it once it finds none
+     */
+    garbage: bool,
+    /**
+     * default tree while with_tree runs on another one, header is written with it
+     */
+    outer: Option<Tree>,
+    open_stats: OpenStats,
+    /**
+     * batches kept for followers, None unless replication backlog is turned on
+     */
+    backlog: Option<Backlog>,
+}
+
+/**
+ * database handle, Srdb::open reads as what it does
+ */
+pub type Srdb = Db;
+
+impl Db {
+    fn new(core: Core) -> Db {
+        Db {
+            core: Arc::new(Mutex::new(core)),
+            flusher: None,
+            writer: WriterSlot::new(),
+        }
+    }
+
+    /**
+     * applies options of handle to freshly created or opened core
+     */
+    fn configure(core: Core, options: &SrdbOptions) -> Result<Db> {
+        let mut db = Db::new(core);
+
+        if !db.is_read_only() {
+            db.set_sync_mode(options.sync_mode)?;
+        }
+
+        db.set_read_path(options.read_path);
+        db.set_wal_limit(options.wal_limit);
+        db.set_wal_segment_size(options.wal_segment_size);
+        db.set_background_flush(options.background_flush)?;
+        db.set_replication_backlog(options.replication_backlog)?;
+        db.set_wait_for_writer(options.wait_for_writer);
+
+        Ok(db)
+    }
+
+    pub(crate) fn create_with(path: &Path, options: &SrdbOptions) -> Result<Db> {
+        options.check(true)?;
+
+        Db::configure(Core::create(path, options)?, options)
+    }
+
+    pub(crate) fn open_with(path: &Path, options: &SrdbOptions) -> Result<Db> {
+        options.check(false)?;
+
+        let core = if options.read_only {
+            Core::open_read_only_with(path, options)?
+        } else {
+            Core::open_locked(path, options)?
+        };
+
+        Db::configure(core, options)
+    }
+
+    pub(crate) fn create_from_storage(
+        storage: Box<dyn Storage>,
+        wal_storage: Box<dyn Storage>,
+        options: &SrdbOptions,
+    ) -> Result<Db> {
+        options.check(true)?;
+
+        Db::configure(Core::create_with_storage(storage, wal_storage, options)?, options)
+    }
+
+    pub(crate) fn open_from_storage(
+        storage: Box<dyn Storage>,
+        wal_storage: Box<dyn Storage>,
+        options: &SrdbOptions,
+    ) -> Result<Db> {
+        options.check(false)?;
+
+        if options.read_only {
+            return Err(Error::InvalidOptions("storages can not be opened read only".to_string()));
+        }
+
+        Db::configure(Core::open_with_storage(storage, wal_storage, options)?, options)
+    }
+
+    /**
+     * creates new database file with empty tree, fails if file exists, see SrdbOptions
+     */
+    pub fn create(path: impl AsRef<Path>) -> Result<Db> {
+        SrdbOptions::new().create(path)
+    }
+
+    /**
+     * removes database file at path with its log, fails with Error::DatabaseLocked while a handle holds it
+     */
+    pub fn remove(path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let _lock = FileLock::acquire(path, true, None)?;
+
+        Wal::remove(path)?;
+        fs::remove_file(path)?;
+
+        sync_parent(path)
+    }
+
+    /**
+     * same as create over given storages of database and log, their contents are discarded
+     */
+    pub fn create_with_storage(storage: Box<dyn Storage>, wal_storage: Box<dyn Storage>) -> Result<Db> {
+        SrdbOptions::new().create_with_storage(storage, wal_storage)
+    }
+
+    /**
+     * opens database file written by create and flush
+     * batches from log which did not reach pages are applied again
+     * only header is read upfront, nodes are paged in by lookups
+     * file is locked exclusively, open fails with Error::DatabaseLocked while another handle holds it
+     */
+    pub fn open(path: impl AsRef<Path>) -> Result<Db> {
+        SrdbOptions::new().open(path)
+    }
+
+    /**
+     * same as open, but waits up to timeout for other handles to release the file
+     */
+    pub fn open_with_lock_timeout(path: impl AsRef<Path>, timeout: Duration) -> Result<Db> {
+        SrdbOptions::new().lock_timeout(Some(timeout)).open(path)
+    }
+
+    /**
+     * opens database file without write access, log is read but never created or modified
+     * every change fails with Error::ReadOnly
+     * if log holds changes missing from file, open fails with Error::RecoveryNeeded,
+     * opening it once for writing recovers it
+     * file lock is shared with other readers, so writer can not change file under it
+     */
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Db> {
+        SrdbOptions::new().read_only(true).open(path)
+    }
+
+    /**
+     * same as open_read_only, but pages failing checksum are read as they are,
+     * for recovery tooling salvaging damaged file, page table and header are still verified
+     */
+    pub fn open_unverified(path: impl AsRef<Path>) -> Result<Db> {
+        SrdbOptions::new().read_only(true).verify(false).open(path)
+    }
+
+    /**
+     * opens database file if it exists, otherwise creates it
+     */
+    pub fn open_or_create(path: impl AsRef<Path>) -> Result<Db> {
+        SrdbOptions::new().open_or_create(path)
+    }
+
+    /**
+     * same as open over given storages of database and log
+     */
+    pub fn open_with_storage(storage: Box<dyn Storage>, wal_storage: Box<dyn Storage>) -> Result<Db> {
+        SrdbOptions::new().open_with_storage(storage, wal_storage)
+    }
+
+    /**
+     * core stays usable after panic of another holder, every change keeps tree consistent between pages
+     */
+    fn core(&self) -> MutexGuard<'_, Core> {
+        self.core.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /**
+     * locks core for operation, error of background flush is returned instead, once
+     */
+    fn checked(&self) -> Result<MutexGuard<'_, Core>> {
+        let mut core = self.core();
+
+        match core.background_error.take() {
+            Some(error) => Err(error),
+            None => Ok(core),
+        }
+    }
+
+    /**
+     * wakes flusher early once enough pages are dirty
+     */
+    fn changed(&self, core: &Core) {
+        if let Some(flusher) = &self.flusher {
+            if core.cache.dirty_pages() >= flusher.policy.dirty_pages {
+                flusher.wake();
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.core().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * levels of default tree, 1 while root is leaf, each costs page read on the way to a leaf
+     */
+    pub fn height(&self) -> Result<usize> {
+        let mut core = self.checked()?;
+        let root = core.root;
+
+        core.height(root)
+    }
+
+    pub fn t(&self) -> usize {
+        self.core().t
+    }
+
+    /**
+     * page cache of database, handle is locked while guard lives
+     */
+    pub fn cache(&self) -> CacheGuard<'_> {
+        CacheGuard(self.core())
+    }
+
+    pub fn sync_mode(&self) -> SyncMode {
+        self.core().sync_mode
+    }
+
+    /**
+     * switches sync mode, records written so far are synced first,
+     * so they are covered by the new mode too
+     */
+    pub fn set_sync_mode(&mut self, sync_mode: SyncMode) -> Result<()> {
+        self.checked()?.set_sync_mode(sync_mode)
+    }
+
+    /**
+     * sets sync mode right after create or open, see set_sync_mode
+     */
+    pub fn with_sync_mode(mut self, sync_mode: SyncMode) -> Result<Db> {
+        self.set_sync_mode(sync_mode)?;
+
+        Ok(self)
+    }
+
+    pub fn read_path(&self) -> ReadPath {
+        self.core().read_path
+    }
+
+    /**
+     * switches how pages are read, ReadPath::Mmap falls back to ReadPath::Io
+     * when file can not be mapped, read_path tells which one is used
+     */
+    pub fn set_read_path(&mut self, read_path: ReadPath) {
+        self.core().set_read_path(read_path)
+    }
+
+    /**
+     * number of pages in database file, header included
+     */
+    pub fn page_count(&self) -> PageId {
+        self.core().cache.page_count()
+    }
+
+    /**
+     * number of pages in free list, they are reused before file grows
+     */
+    pub fn free_pages(&self) -> usize {
+        self.core().free_count
+    }
+
+    /**
+     * size of write ahead log in bytes, read only handle of file sums sizes of segments on disk
+     */
+    pub fn wal_len(&self) -> u64 {
+        self.core().wal_len()
+    }
+
+    pub fn wal_limit(&self) -> Option<u64> {
+        self.core().wal_limit
+    }
+
+    /**
+     * log size after which write checkpoints database, None lets log grow until checkpoint is called
+     */
+    pub fn set_wal_limit(&mut self, wal_limit: Option<u64>) {
+        self.core().wal_limit = wal_limit;
+    }
+
+    /**
+     * None for read only handle
+     */
+    pub fn wal_segment_size(&self) -> Option<u64> {
+        self.core().wal.as_ref().and_then(Wal::segment_size)
+    }
+
+    /**
+     * log segment size after which write starts the next segment, None keeps one segment until checkpoint
+     * segments older than the one named in header are removed by checkpoint, so limit keeps their count low
+     * log over storage given to create_with_storage is always one segment
+     */
+    pub fn set_wal_segment_size(&mut self, wal_segment_size: Option<u64>) {
+        if let Some(wal) = self.core().wal.as_mut() {
+            wal.set_segment_size(wal_segment_size);
+        }
+    }
+
+    /**
+     * number of log segment files, 0 for read only handle
+     */
+    pub fn wal_segments(&self) -> u64 {
+        self.core().wal.as_ref().map_or(0, Wal::segments)
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.core().wal.is_none()
+    }
+
+    pub fn background_flush(&self) -> Option<BackgroundFlush> {
+        self.flusher.as_ref().map(|flusher| flusher.policy)
+    }
+
+    /**
+     * starts thread flushing dirty pages by policy, None stops it, see BackgroundFlush
+     * flusher is stopped before the new one starts, so its last flush is not lost
+     * read only handle has nothing to flush and fails with Error::ReadOnly
+     */
+    pub fn set_background_flush(&mut self, policy: Option<BackgroundFlush>) -> Result<()> {
+        if policy.is_some() && self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+
+        if let Some(flusher) = self.flusher.take() {
+            flusher.stop();
+        }
+
+        self.flusher = policy.map(|policy| Flusher::start(self.core.clone(), policy));
+
+        Ok(())
+    }
+
+    /**
+     * turns background flush on right after create or open, see set_background_flush
+     */
+    pub fn with_background_flush(mut self, policy: BackgroundFlush) -> Result<Db> {
+        self.set_background_flush(Some(policy))?;
+
+        Ok(self)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.checked()?.flush()
+    }
+
+    pub fn checkpoint(&mut self) -> Result<()> {
+        self.checked()?.checkpoint()
+    }
+
+    pub fn vacuum(&mut self) -> Result<u64> {
+        self.checked()?.vacuum()
+    }
+
+    /**
+     * stops background flush, flushes database and checkpoints it, so the next open has no log to replay,
+     * file lock is released on return, unlike drop reports errors of all of them
+     * read only handle has nothing to write and is just closed
+     */
+    pub fn close(mut self) -> Result<()> {
+        if let Some(flusher) = self.flusher.take() {
+            flusher.stop();
+        }
+
+        let mut core = self.checked()?;
+
+        if core.wal.is_none() {
+            return Ok(());
+        }
+
+        core.checkpoint()
+    }
+
+    pub fn open_stats(&self) -> OpenStats {
+        self.core().open_stats
+    }
+
+    /**
+     * reads one node per level from root through cache, tree is never loaded whole,
+     * so the first lookup after open costs height page reads on top of header read by open
+     */
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.checked()?.get(key)
+    }
+
+    /**
+     * like get, but value is not loaded, so key with overflow value costs no reads of overflow pages
+     */
+    pub fn contains(&self, key: &[u8]) -> Result<bool> {
+        self.checked()?.contains(key)
+    }
+
+    /**
+     * applies batch as one log record, it is synced before any page changes, so after crash the whole batch
+     * is visible or none of it, batch naming tables changes all of them at once
+     */
+    pub fn write(&mut self, batch: &WriteBatch) -> Result<()> {
+        let mut core = self.checked()?;
+
+        core.write(batch)?;
+        self.changed(&core);
+
+        Ok(())
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("insert", key = ?String::from_utf8_lossy(key)).entered();
+
+        let mut core = self.checked()?;
+
+        core.insert(key, value)?;
+        self.changed(&core);
+
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: &[u8]) -> Result<bool> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("delete", key = ?String::from_utf8_lossy(key)).entered();
+
+        let mut core = self.checked()?;
+        let removed = core.delete(key)?;
+
+        self.changed(&core);
+
+        Ok(removed)
+    }
+
+    pub fn to_vec(&mut self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.checked()?.entries()
+    }
+
+    /**
+     * iterates entries with keys in range in key order, see DbIter
+     * bounds are anything holding bytes, like b"a".as_slice()..b"b".as_slice() or from.clone()..=to
+     */
+    pub fn range<K: AsRef<[u8]> + ?Sized>(&mut self, range: impl RangeBounds<K>) -> Result<DbIter<'_>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "range",
+            start = ?range.start_bound().map(|key| String::from_utf8_lossy(key.as_ref())),
+            end = ?range.end_bound().map(|key| String::from_utf8_lossy(key.as_ref()))
+        )
+        .entered();
+
+        let core = self.checked()?;
+        let root = core.root;
+
+        DbIter::new(core, root, range)
+    }
+
+    pub fn iter(&mut self) -> Result<DbIter<'_>> {
+        self.range::<[u8]>(..)
+    }
+
+    /**
+     * fills empty database with encoded keys of tree and empty values,
+     * leaves are written fully packed in key order and levels above are built from them,
+     * so import does not go through log and per-key inserts
+     * database is checkpointed before import and import is committed by the next flush,
+     * crash in between leaves it empty
+     * fails with Error::NotEmpty if database holds entries, Table::import_tree fills table instead
+     */
+    pub fn import_tree<T: Ord + Clone + Debug + Codec>(&mut self, tree: &BTree<T>) -> Result<()> {
+        let mut core = self.checked()?;
+
+        core.import_tree(tree)?;
+        core.flush()
+    }
+
+    /**
+     * fills empty database with len entries coming in strictly increasing key order, entries are bulk loaded
+     * as they come, so they may be streamed from disk without being held in memory
+     * like import_tree it does not go through log: database is checkpointed first and load is committed
+     * by flush at the end, failure before it leaves database empty
+     * error of entries stops load, so does key out of order, which is Error::Unsorted,
+     * entries must number exactly len, otherwise load fails with Error::Corrupt
+     */
+    pub fn load_sorted<I>(&mut self, len: usize, entries: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<(Vec<u8>, Vec<u8>)>>,
+    {
+        let mut core = self.checked()?;
+
+        core.load_sorted(len, entries)?;
+        core.flush()
+    }
+
+    /**
+     * page counts of file from counters, cheap enough to poll, see DiskStats
+     */
+    pub fn disk_stats(&self) -> DiskStats {
+        self.core().disk_stats()
+    }
+
+    /**
+     * same as disk_stats, but tree and free list are walked, so node and overflow pages are counted
+     */
+    pub fn disk_stats_exact(&mut self) -> Result<DiskStats> {
+        self.checked()?.disk_stats_exact()
+    }
+
+    /**
+     * sequence number of the last committed batch, it is the position of follower, see replicate
+     */
+    pub fn seq(&self) -> u64 {
+        self.core().seq
+    }
+
+    /**
+     * keeps batches committed from now on, and those of log since the last checkpoint, in memory for followers,
+     * up to limit bytes of them, oldest go first, None turns it off, see changes_since and serve_follower
+     */
+    pub fn set_replication_backlog(&mut self, limit: Option<u64>) -> Result<()> {
+        self.checked()?.set_replication_backlog(limit)
+    }
+
+    /**
+     * up to max batches committed after seq, in order, empty when there are none yet
+     * Error::ResyncNeeded if backlog no longer holds batch right after seq or seq is ahead of this database,
+     * bulk loads bypassing log, like load_sorted, empty backlog, so followers take new copy after them
+     */
+    pub fn changes_since(&self, seq: u64, max: usize) -> Result<Vec<(u64, WriteBatch)>> {
+        self.checked()?.changes_since(seq, max)
+    }
+
+    /**
+     * commits batch of primary with its seq, batches must come in order of seq:
+     * batch already committed is skipped and false is returned, so replay after reconnect is harmless,
+     * batch after gap is Error::ReplicationGap
+     */
+    pub fn replicate(&mut self, seq: u64, batch: &WriteBatch) -> Result<bool> {
+        let mut core = self.checked()?;
+        let replicated = core.replicate(seq, batch)?;
+
+        self.changed(&core);
+
+        Ok(replicated)
+    }
+
+    /**
+     * makes database continue from seq, for copy of primary restored from its dump taken at seq
+     */
+    pub fn set_seq(&mut self, seq: u64) -> Result<()> {
+        self.checked()?.set_seq(seq)
+    }
+}
+
+/**
+ * in-order iterator over entries of database, see Db::range
+ * keeps decoded path from root, one node per level with index of its next key,
+ * nodes are read through cache as iterator advances, so scan needs no more cache than lookup
+ * and decoded path needs no pins, handle is locked while iterator lives
+ * read error is yielded once, then iterator ends
+ */
+pub struct DbIter<'a> {
+    core: MutexGuard<'a, Core>,
+    stack: Vec<(Node<Entry>, usize)>,
+    end: Bound<Vec<u8>>,
+}
+
+impl<'a> DbIter<'a> {
+    fn new<K: AsRef<[u8]> + ?Sized>(core: MutexGuard<'a, Core>, root: PageId, range: impl RangeBounds<K>) -> Result<DbIter<'a>> {
+        let mut iter = DbIter {
+            core,
+            stack: vec![],
+            end: range.end_bound().map(|end| end.as_ref().to_vec()),
+        };
+
+        iter.descend(root, range.start_bound().map(AsRef::as_ref))?;
+
+        Ok(iter)
+    }
+
+    /**
+     * pushes path to the first key of subtree not below start
+     */
+    fn descend(&mut self, mut page_id: PageId, start: Bound<&[u8]>) -> Result<()> {
+        loop {
+            let node = self.core.read_node(page_id)?;
+            let i = match start {
+                Bound::Included(start) => node.keys.partition_point(|entry| entry.key.as_slice() < start),
+                Bound::Excluded(start) => node.keys.partition_point(|entry| entry.key.as_slice() <= start),
+                Bound::Unbounded => 0,
+            };
+            let child = (!node.leaf).then(|| node.children[i]);
+
+            self.stack.push((node, i));
+
+            match child {
+                Some(child) => page_id = child,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    fn next_entry(&mut self) -> Result<Option<Entry>> {
+        loop {
+            let Some((node, i)) = self.stack.last_mut() else {
+                return Ok(None);
+            };
+
+            if *i == node.count {
+                self.stack.pop();
+
+                continue;
+            }
+
+            let entry = std::mem::replace(&mut node.keys[*i], Entry { key: vec![], value: Value::Inline(vec![]) });
+            let child = (!node.leaf).then(|| node.children[*i + 1]);
+
+            *i += 1;
+
+            let beyond = match &self.end {
+                Bound::Included(end) => entry.key > *end,
+                Bound::Excluded(end) => entry.key >= *end,
+                Bound::Unbounded => false,
+            };
+
+            if beyond {
+                self.stack.clear();
+
+                return Ok(None);
+            }
+
+            if let Some(child) = child {
+                self.descend(child, Bound::Unbounded)?;
+            }
+
+            return Ok(Some(entry));
+        }
+    }
+
+    /**
+     * entries up to the end of current leaf, so caller pays per leaf rather than per entry
+     * batch starting at key of internal node holds it and the leaf after it, empty batch ends iteration
+     */
+    pub fn next_batch(&mut self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut batch = vec![];
+
+        loop {
+            let in_leaf = self.stack.last().is_some_and(|(node, i)| node.leaf && *i < node.count);
+
+            if !batch.is_empty() && !in_leaf {
+                return Ok(batch);
+            }
+
+            match self.next() {
+                Some(entry) => batch.push(entry?),
+                None => return Ok(batch),
+            }
+        }
+    }
+}
+
+impl Iterator for DbIter<'_> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self
+            .next_entry()
+            .transpose()?
+            .and_then(|Entry { key, value }| Ok((key, self.core.load_value(value)?)));
+
+        if result.is_err() {
+            self.stack.clear();
+        }
+
+        Some(result)
+    }
+}
+
+/**
+ * reads overflow chain, inline value is returned as is, compressed one is decompressed
+ * read_page appends data of overflow page to out and returns the next page of chain
+ */
+fn read_value(value: Value, read_page: &mut impl FnMut(PageId, &mut Vec<u8>) -> Result<PageId>) -> Result<Vec<u8>> {
+    let (len, mut page_id) = match value {
+        Value::Inline(value) => return Ok(value),
+        Value::Overflow { len, first } => (len as usize, first),
+        Value::Compressed(value) => {
+            let packed = read_value(*value, read_page)?;
+
+            return unpack(&packed).ok_or_else(|| Error::Corrupt("compressed value is damaged".to_string()));
+        }
+    };
+
+    let mut out = Vec::with_capacity(len);
+
+    while page_id != HEADER_PAGE {
+        let next = read_page(page_id, &mut out)?;
+
+        if out.len() > len {
+            return Err(Error::Corrupt(format!("overflow chain at page {} is longer than {} bytes", page_id, len)));
+        }
+
+        page_id = next;
+    }
+
+    if out.len() != len {
+        return Err(Error::Corrupt(format!("overflow chain holds {} bytes, expected {}", out.len(), len)));
+    }
+
+    Ok(out)
+}
+
+/**
+ * page cache borrowed from database handle, see Db::cache
+ */
+pub struct CacheGuard<'a>(MutexGuard<'a, Core>);
+
+impl Deref for CacheGuard<'_> {
+    type Target = PageCache;
+
+    fn deref(&self) -> &PageCache {
+        &self.0.cache
+    }
+}
+
+/**
+ * wakeup of flusher thread, stop is set once by handle
+ */
+#[derive(Debug, Default)]
+struct Wakeup {
+    pending: bool,
+    stop: bool,
+}
+
+/**
+ * background thread flushing database, see BackgroundFlush
+ */
+#[derive(Debug)]
+struct Flusher {
+    policy: BackgroundFlush,
+    signal: Arc<(Mutex<Wakeup>, Condvar)>,
+    thread: JoinHandle<()>,
+}
+
+impl Flusher {
+    fn start(core: Arc<Mutex<Core>>, policy: BackgroundFlush) -> Flusher {
+        let signal = Arc::new((Mutex::new(Wakeup::default()), Condvar::new()));
+        let thread_signal = signal.clone();
+        let thread = thread::spawn(move || Flusher::run(&core, &thread_signal, policy));
+
+        Flusher { policy, signal, thread }
+    }
+
+    fn wake(&self) {
+        let (wakeup, condvar) = &*self.signal;
+
+        wakeup.lock().unwrap().pending = true;
+        condvar.notify_one();
+    }
+
+    /**
+     * asks thread to make the last flush and waits for it
+     */
+    fn stop(self) {
+        let (wakeup, condvar) = &*self.signal;
+
+        wakeup.lock().unwrap().stop = true;
+        condvar.notify_one();
+
+        let _ = self.thread.join();
+    }
+
+    /**
+     * flushes every interval or on wakeup, after stop flushes once more and exits
+     */
+    fn run(core: &Mutex<Core>, signal: &(Mutex<Wakeup>, Condvar), policy: BackgroundFlush) {
+        let (wakeup, condvar) = signal;
+
+        loop {
+            let mut state = wakeup.lock().unwrap();
+
+            if !state.pending && !state.stop {
+                state = condvar.wait_timeout(state, policy.interval).unwrap().0;
+            }
+
+            let stop = state.stop;
+
+            state.pending = false;
+            drop(state);
+
+            Flusher::flush(core, policy.batch_pages.max(1));
+
+            if stop {
+                return;
+            }
+        }
+    }
+
+    /**
+     * writes dirty pages in batches, lock is released between them so foreground is not stalled,
+     * then flushes the rest and commits under one lock, so commit sees tree between changes
+     * error is kept for the next operation of handle, nothing is flushed until it is taken
+     */
+    fn flush(core: &Mutex<Core>, batch_pages: usize) {
+        loop {
+            let mut core = core.lock().unwrap_or_else(PoisonError::into_inner);
+
+            if core.background_error.is_some() {
+                return;
+            }
+
+            let result = match core.cache.flush_pages(batch_pages) {
+                Ok(written) if written == batch_pages => continue,
+                Ok(_) => core.flush(),
+                Err(error) => Err(error),
+            };
+
+            core.background_error = result.err();
+
+            return;
+        }
+    }
+}
+
+impl Drop for Db {
+    /**
+     * flusher makes its last flush, core flushes once more when the last reference is gone,
+     * errors are lost here, call close to see them
+     */
+    fn drop(&mut self) {
+        if let Some(flusher) = self.flusher.take() {
+            flusher.stop();
+        }
+    }
+}
+
+impl Core {
+    /**
+     * file is locked exclusively before log is touched
+     */
+    fn create(path: &Path, options: &SrdbOptions) -> Result<Core> {
+        let pager = Core::create_pager(path, options.page_size.unwrap_or(PAGE_SIZE), options.key.as_ref())?;
+        let lock = FileLock::acquire(path, true, None)?;
+        let wal = Wal::create(path, FIRST_SEGMENT, pager.cipher())?;
+
+        let mut db = Core::create_from(pager, Some(wal), options)?;
+
+        db.lock = Some(lock);
+        db.path = Some(path.to_path_buf());
+
+        Ok(db)
+    }
+
+    fn create_with_storage(
+        storage: Box<dyn Storage>,
+        wal_storage: Box<dyn Storage>,
+        options: &SrdbOptions,
+    ) -> Result<Core> {
+        let pager = Pager::create_with(storage, options.page_size.unwrap_or(PAGE_SIZE), options.key.as_ref())?;
+        let wal = Wal::create_with_storage(wal_storage, pager.cipher())?;
+
+        Core::create_from(pager, Some(wal), options)
+    }
+
+    /**
+     * new database file with pages of page_size bytes, fails if file exists
+     */
+    fn create_pager(path: &Path, page_size: usize, key: Option<&Key>) -> Result<Pager> {
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(path)?;
+
+        Pager::create_with(Box::new(file), page_size, key)
+    }
+
+    fn create_from(pager: Pager, wal: Option<Wal>, options: &SrdbOptions) -> Result<Core> {
+        let max = max_t(pager.page_size(), MAX_ENTRY_SIZE);
+        let t = options.t.unwrap_or(max);
+
+        if t < 2 || t > max {
+            return Err(Error::InvalidOptions(format!(
+                "branching factor {} is out of range 2..={} for page size {}",
+                t,
+                max,
+                pager.file_page_size()
+            )));
+        }
+
+        let mut db = Core {
+            cache: PageCache::new(pager, options.cache_pages),
+            wal,
+            root: HEADER_PAGE,
+            len: 0,
+            t,
+            header_dirty: true,
+            seq: 0,
+            applied_seq: 0,
+            sync_mode: SyncMode::default(),
+            read_path: ReadPath::default(),
+            wal_limit: Some(DEFAULT_WAL_LIMIT),
+            wal_segment: FIRST_SEGMENT,
+            free_head: HEADER_PAGE,
+            free_count: 0,
+            lock: None,
+            path: None,
+            background_error: None,
+            compression: options.compression.unwrap_or(cfg!(feature = "lz4")),
+            key: options.key.clone(),
+            catalog: Tree::NONE,
+            garbage: false,
+            outer: None,
+            open_stats: OpenStats::default(),
+            backlog: None,
+        };
+
+        db.cache.set_steal(false);
+
+        db.root = db.alloc_node(&Node::leaf(t))?;
+        db.flush()?;
+
+        Ok(db)
+    }
+
+    fn open_locked(path: &Path, options: &SrdbOptions) -> Result<Core> {
+        let lock = FileLock::acquire(path, true, options.lock_timeout)?;
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut pager = Pager::open_with(Box::new(file), options.key.as_ref())?;
+        let header = Core::read_header(&mut pager)?;
+        let (wal, records) = Wal::open(path, header.wal_segment, pager.cipher())?;
+
+        let mut db = Core::open_from(pager, header, Some(wal), records, options)?;
+
+        db.lock = Some(lock);
+        db.path = Some(path.to_path_buf());
+
+        Ok(db)
+    }
+
+    /**
+     * without lock log is not read, see SrdbOptions::lock
+     */
+    fn open_read_only_with(path: &Path, options: &SrdbOptions) -> Result<Core> {
+        let lock = match options.lock {
+            true => Some(FileLock::acquire(path, false, options.lock_timeout)?),
+            false => None,
+        };
+        let file = OpenOptions::new().read(true).open(path)?;
+        let mut pager = Pager::open_with(Box::new(file), options.key.as_ref())?;
+
+        pager.set_verify(options.verify);
+
+        let header = Core::read_header(&mut pager)?;
+        let records = match options.lock {
+            true => Wal::read_segments(path, header.wal_segment, pager.cipher())?,
+            false => vec![],
+        };
+
+        let mut db = Core::open_from(pager, header, None, records, options)?;
+
+        db.lock = lock;
+        db.path = Some(path.to_path_buf());
+
+        Ok(db)
+    }
+
+    fn open_with_storage(
+        storage: Box<dyn Storage>,
+        wal_storage: Box<dyn Storage>,
+        options: &SrdbOptions,
+    ) -> Result<Core> {
+        let mut pager = Pager::open_with(storage, options.key.as_ref())?;
+        let header = Core::read_header(&mut pager)?;
+        let (wal, records) = Wal::open_with_storage(wal_storage, header.wal_segment, pager.cipher())?;
+
+        Core::open_from(pager, header, Some(wal), records, options)
+    }
+
+    /**
+     * committed header, log is opened from segment named in it before recovery
+     * it is read once, open_from goes on with it
+     */
+    fn read_header(pager: &mut Pager) -> Result<Header> {
+        let mut page = vec![0; pager.page_size()];
+
+        pager.read_page(HEADER_PAGE, &mut page)?;
+
+        Header::decode(&page)
+    }
+
+    /**
+     * recovery: pager opens the last commit, flush cut halfway never reaches it,
+     * then batches newer than header are applied again, writable handle cuts torn page flush left at the end
+     * read only handle has no log and fails instead if recovery is needed
+     */
+    fn open_from(
+        pager: Pager,
+        header: Header,
+        wal: Option<Wal>,
+        records: Vec<Record>,
+        options: &SrdbOptions,
+    ) -> Result<Core> {
+        let page_count = pager.page_count();
+
+        if let Some(expected) = options.page_size.filter(|&page_size| page_size != pager.file_page_size()) {
+            return Err(Error::PageSizeMismatch {
+                expected,
+                found: pager.file_page_size(),
+            });
+        }
+
+        let cache = PageCache::new(pager, options.cache_pages);
+        let page_size = cache.page_size();
+
+        let value_codecs: &[u32] = if cfg!(feature = "lz4") {
+            &[BYTES_CODEC, LZ4_CODEC]
+        } else {
+            &[BYTES_CODEC]
+        };
+
+        if header.key_codec != BYTES_CODEC || !value_codecs.contains(&header.value_codec) {
+            return Err(Error::CorruptHeader(format!(
+                "unknown codecs {} and {}",
+                header.key_codec, header.value_codec
+            )));
+        }
+
+        if header.t < 2 || header.t as usize > max_t(page_size, MAX_ENTRY_SIZE) {
+            return Err(Error::CorruptHeader(format!("branching factor {} is out of range", header.t)));
+        }
+
+        if options.t.is_some_and(|t| t != header.t as usize) {
+            return Err(Error::InvalidOptions(format!(
+                "database has branching factor {}, {} is requested",
+                header.t,
+                options.t.unwrap()
+            )));
+        }
+
+        if options.compression.is_some_and(|compression| compression != (header.value_codec == LZ4_CODEC)) {
+            return Err(Error::InvalidOptions(format!(
+                "database has compression {}, it can be chosen only at creation",
+                if header.value_codec == LZ4_CODEC { "on" } else { "off" }
+            )));
+        }
+
+        if header.root == HEADER_PAGE || header.root >= page_count {
+            return Err(Error::CorruptHeader(format!("root page {} is out of bounds", header.root)));
+        }
+
+        if header.catalog >= page_count {
+            return Err(Error::CorruptHeader(format!("catalog page {} is out of bounds", header.catalog)));
+        }
+
+        if header.free_head >= page_count || header.free_count >= page_count as u64 {
+            return Err(Error::CorruptHeader(format!(
+                "free list of {} pages at page {} is out of bounds",
+                header.free_count, header.free_head
+            )));
+        }
+
+        let mut db = Core {
+            cache,
+            wal,
+            root: header.root,
+            len: header.len as usize,
+            t: header.t as usize,
+            header_dirty: false,
+            seq: header.seq,
+            applied_seq: header.seq,
+            sync_mode: SyncMode::default(),
+            read_path: ReadPath::default(),
+            wal_limit: Some(DEFAULT_WAL_LIMIT),
+            wal_segment: header.wal_segment,
+            free_head: header.free_head,
+            free_count: header.free_count as usize,
+            lock: None,
+            path: None,
+            background_error: None,
+            compression: header.value_codec == LZ4_CODEC,
+            key: options.key.clone(),
+            catalog: Tree {
+                root: header.catalog,
+                len: header.catalog_len as usize,
+            },
+            garbage: header.catalog_len > 0,
+            outer: None,
+            open_stats: OpenStats::default(),
+            backlog: None,
+        };
+
+        db.cache.set_steal(false);
+
+        if db.wal.is_some() {
+            db.cache.trim()?;
+        }
+
+        for record in records {
+            let Record::Batch { seq, batch } = record;
+
+            if seq <= db.seq {
+                continue;
+            }
+
+            if db.wal.is_none() {
+                return Err(Error::RecoveryNeeded);
+            }
+
+            db.seq = seq;
+
+            for op in batch.ops() {
+                db.apply(op)?;
+            }
+
+            db.applied_seq = seq;
+            db.header_dirty = true;
+            db.open_stats.replayed += 1;
+        }
+
+        db.open_stats.recovered = db.open_stats.replayed > 0;
+
+        Ok(db)
+    }
+
+    fn set_sync_mode(&mut self, sync_mode: SyncMode) -> Result<()> {
+        self.wal()?.sync()?;
+        self.sync_mode = sync_mode;
+
+        Ok(())
+    }
+
+    fn set_read_path(&mut self, read_path: ReadPath) {
+        let mapped = self.cache.set_mmap(read_path == ReadPath::Mmap);
+
+        self.read_path = if mapped { ReadPath::Mmap } else { ReadPath::Io };
+    }
+
+    fn wal_len(&self) -> u64 {
+        match (&self.wal, &self.path) {
+            (Some(wal), _) => wal.len(),
+            (None, Some(path)) => Wal::disk_len(path),
+            (None, None) => 0,
+        }
+    }
+
+    /**
+     * log of writable handle, read only one fails here before any change
+     */
+    fn wal(&mut self) -> Result<&mut Wal> {
+        self.wal.as_mut().ok_or(Error::ReadOnly)
+    }
+
+    /**
+     * all leaves are at the same depth, so leftmost path tells it
+     */
+    fn height(&mut self, root: PageId) -> Result<usize> {
+        let mut node = self.read_node(root)?;
+        let mut height = 1;
+
+        while !node.leaf {
+            node = self.read_node(node.children[0])?;
+            height += 1;
+        }
+
+        Ok(height)
+    }
+
+    fn read_node(&mut self, page_id: PageId) -> Result<Node<Entry>> {
+        Node::from_page(self.cache.read(page_id)?)
+    }
+
+    fn write_node(&mut self, page_id: PageId, node: &Node<Entry>) -> Result<()> {
+        let page = node.to_page(self.cache.page_size())?;
+
+        self.cache.write(page_id, &page)
+    }
+
+    /**
+     * takes page from free list, file grows only when list is empty
+     */
+    fn alloc_page(&mut self) -> Result<PageId> {
+        if self.free_head == HEADER_PAGE {
+            return self.cache.allocate_page();
+        }
+
+        let page_id = self.free_head;
+
+        self.free_head = decode_free_page(self.cache.read(page_id)?)?;
+        self.free_count -= 1;
+        self.header_dirty = true;
+
+        Ok(page_id)
+    }
+
+    /**
+     * pushes page to free list, list is stored in pages, so it is committed like nodes
+     */
+    fn free_page(&mut self, page_id: PageId) -> Result<()> {
+        let page = encode_free_page(self.free_head, self.cache.page_size());
+
+        self.cache.write(page_id, &page)?;
+        self.free_head = page_id;
+        self.free_count += 1;
+        self.header_dirty = true;
+
+        Ok(())
+    }
+
+    fn alloc_node(&mut self, node: &Node<Entry>) -> Result<PageId> {
+        let page_id = self.alloc_page()?;
+
+        self.write_node(page_id, node)?;
+
+        Ok(page_id)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let page_size = self.cache.page_size();
+        let default = self.outer.unwrap_or(Tree {
+            root: self.root,
+            len: self.len,
+        });
+
+        let header = Header {
+            key_codec: BYTES_CODEC,
+            value_codec: if self.compression { LZ4_CODEC } else { BYTES_CODEC },
+            t: self.t as u32,
+            root: default.root,
+            len: default.len as u64,
+            seq: self.applied_seq,
+            free_head: self.free_head,
+            free_count: self.free_count as u64,
+            catalog: self.catalog.root,
+            catalog_len: self.catalog.len as u64,
+            wal_segment: self.wal_segment,
+        };
+
+        self.cache.write(HEADER_PAGE, &header.encode(page_size))
+    }
+
+    /**
+     * writes header and all dirty pages, then commits them, see Pager::commit
+     * log is synced first, so batch applied halfway in committed pages is replayed by open
+     */
+    fn flush(&mut self) -> Result<()> {
+        if self.header_dirty {
+            self.write_header()?;
+            self.header_dirty = false;
+        }
+
+        if self.cache.dirty_pages() == 0 && self.cache.is_committed() {
+            return Ok(());
+        }
+
+        let sync = self.sync_mode != SyncMode::Off;
+
+        if let Some(wal) = self.wal.as_mut().filter(|_| sync) {
+            wal.sync()?;
+        }
+
+        self.cache.flush()?;
+        self.cache.commit(sync)?;
+
+        Ok(())
+    }
+
+    /**
+     * flushes all changes, syncs file even with SyncMode::Off and starts new log segment,
+     * header naming it is committed before older segments are removed,
+     * batches in them are all committed to pages by then, so crash at any point loses nothing
+     */
+    fn checkpoint(&mut self) -> Result<()> {
+        self.wal()?;
+        self.flush()?;
+        self.cache.sync()?;
+        self.wal_segment = self.wal()?.rotate()?;
+        self.header_dirty = true;
+        self.flush()?;
+        self.cache.sync()?;
+        self.wal()?.remove_old()
+    }
+
+    /**
+     * rewrites database into a new file next to it and renames it over the original,
+     * tree is bulk loaded with fully packed nodes and free pages are left behind
+     * original stays intact until rename, leftover of vacuum cut by crash is removed by the next one
+     * returns number of bytes file shrank by
+     */
+    fn vacuum(&mut self) -> Result<u64> {
+        let path = self
+            .path
+            .clone()
+            .ok_or_else(|| io::Error::new(ErrorKind::Unsupported, "vacuum needs database opened from path"))?;
+
+        if self.cache.has_snapshot() {
+            let message = "vacuum waits for backup or read transaction to finish";
+
+            return Err(io::Error::new(ErrorKind::WouldBlock, message).into());
+        }
+
+        self.checkpoint()?;
+
+        let before = fs::metadata(&path)?.len();
+        let temp = vacuum_path(&path);
+
+        match fs::remove_file(&temp) {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+            _ => {}
+        }
+
+        let result = self.vacuum_into(&path, &temp);
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+
+        result?;
+
+        let after = fs::metadata(&path)?.len();
+
+        Ok(before.saturating_sub(after))
+    }
+
+    /**
+     * builds copy at temp, renames it to path and switches handle to it
+     */
+    fn vacuum_into(&mut self, path: &Path, temp: &Path) -> Result<()> {
+        let pager = Core::create_pager(temp, self.cache.file_page_size(), self.key.as_ref())?;
+        let lock = FileLock::acquire(temp, true, None)?;
+        let mut options = SrdbOptions::new().branching_factor(self.t).cache_pages(self.cache.capacity());
+
+        options.compression = Some(self.compression);
+        options.key = self.key.clone();
+
+        let mut copy = Core::create_from(pager, None, &options)?;
+
+        copy.sync_mode = SyncMode::Off;
+        copy.load_from(self)?;
+        copy.seq = self.seq;
+        copy.applied_seq = self.applied_seq;
+        copy.wal_segment = self.wal_segment;
+        copy.header_dirty = true;
+        copy.sync_mode = SyncMode::Always;
+        copy.flush()?;
+
+        fs::rename(temp, path)?;
+        sync_parent(path)?;
+
+        std::mem::swap(&mut self.cache, &mut copy.cache);
+
+        self.root = copy.root;
+        self.catalog = copy.catalog;
+        self.garbage = false;
+        self.t = copy.t;
+        self.free_head = copy.free_head;
+        self.free_count = copy.free_count;
+        self.lock = Some(lock);
+        self.set_read_path(self.read_path);
+
+        Ok(())
+    }
+
+    /**
+     * fills empty database with default tree and tables of source, dropped trees are left behind
+     * loading table may flush with its tree in place of default one, header is written again once copy is done
+     */
+    fn load_from(&mut self, source: &mut Core) -> Result<()> {
+        self.load_tree(
+            source,
+            Tree {
+                root: source.root,
+                len: source.len,
+            },
+        )?;
+
+        for (key, tree) in source.catalog_entries()? {
+            if key.first() == Some(&GARBAGE) {
+                continue;
+            }
+
+            let mut target = Tree {
+                root: self.alloc_node(&Node::leaf(self.t))?,
+                len: 0,
+            };
+
+            self.with_tree(&mut target, |copy| copy.load_tree(source, tree))?;
+            self.catalog_put(&key, target)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * fills empty tree with entries of tree of source in key order, values are copied to own pages
+     */
+    fn load_tree(&mut self, source: &mut Core, tree: Tree) -> Result<()> {
+        let mut loader = Loader::new(self.t, tree.len);
+
+        source.for_each_entry(tree.root, &mut |source, Entry { key, value }| {
+            let value = if value.is_inline() {
+                value
+            } else {
+                let value = source.load_value(value)?;
+
+                self.store_value(&key, &value)?
+            };
+
+            loader.push(self, Entry { key, value })
+        })?;
+
+        loader.finish(self)
+    }
+
+    /**
+     * fills empty database with keys of tree and empty values, see Db::import_tree
+     * keys are streamed when their encodings are ordered like tree, otherwise encodings are sorted first
+     */
+    fn import_tree<T: Ord + Clone + Debug + Codec>(&mut self, tree: &BTree<T>) -> Result<()> {
+        if self.len != 0 {
+            return Err(Error::NotEmpty(self.len));
+        }
+
+        let encode = |key: &T| {
+            let mut out = vec![];
+
+            key.encode(&mut out);
+
+            out
+        };
+
+        let mut previous: Option<Vec<u8>> = None;
+        let mut sorted = true;
+
+        for key in tree.iter().map(encode) {
+            Core::check_key(&key)?;
+
+            if previous.is_some_and(|previous| previous >= key) {
+                sorted = false;
+            }
+
+            previous = Some(key);
+        }
+
+        self.checkpoint()?;
+        self.unlogged_change();
+
+        if sorted {
+            let mut loader = Loader::new(self.t, tree.len());
+
+            for key in tree.iter().map(encode) {
+                loader.push(self, Entry { key, value: Value::Inline(vec![]) })?;
+            }
+
+            return loader.finish(self);
+        }
+
+        let mut keys: Vec<Vec<u8>> = tree.iter().map(encode).collect();
+
+        keys.sort_unstable();
+        keys.dedup();
+
+        let mut loader = Loader::new(self.t, keys.len());
+
+        for key in keys {
+            loader.push(self, Entry { key, value: Value::Inline(vec![]) })?;
+        }
+
+        loader.finish(self)
+    }
+
+    /**
+     * fills empty table with keys of tree, see Table::import_tree
+     * tree is built under new root and replaces empty one in catalog once it is complete
+     */
+    fn import_table_tree<T: Ord + Clone + Debug + Codec>(&mut self, name: &str, tree: &BTree<T>) -> Result<()> {
+        let table = self.table(name)?;
+
+        if table.len != 0 {
+            return Err(Error::NotEmpty(table.len));
+        }
+
+        let mut target = Tree {
+            root: self.alloc_node(&Node::leaf(self.t))?,
+            len: 0,
+        };
+
+        self.with_tree(&mut target, |core| core.import_tree(tree))?;
+        self.catalog_put(name.as_bytes(), target)?;
+        self.free_page(table.root)?;
+        self.flush()
+    }
+
+    /**
+     * fills empty database from sorted entries, see Db::load_sorted
+     */
+    fn load_sorted(&mut self, len: usize, entries: impl IntoIterator<Item = Result<(Vec<u8>, Vec<u8>)>>) -> Result<()> {
+        if self.len != 0 {
+            return Err(Error::NotEmpty(self.len));
+        }
+
+        self.checkpoint()?;
+        self.unlogged_change();
+
+        let mut loader = Loader::new(self.t, len);
+        let mut previous: Option<Vec<u8>> = None;
+
+        for entry in entries {
+            let (key, value) = entry?;
+
+            Core::check_key(&key)?;
+
+            if previous.is_some_and(|previous| previous >= key) {
+                return Err(Error::Unsorted(format!("{:?}", String::from_utf8_lossy(&key))));
+            }
+
+            let value = self.store_value(&key, &value)?;
+
+            previous = Some(key.clone());
+            loader.push(self, Entry { key, value })?;
+        }
+
+        loader.finish(self)
+    }
+
+    /**
+     * writes root into page of the current root, other nodes into new pages
+     */
+    fn place_node(&mut self, node: &Node<Entry>, root: bool) -> Result<PageId> {
+        if !root {
+            return self.alloc_node(node);
+        }
+
+        self.write_node(self.root, node)?;
+
+        Ok(self.root)
+    }
+
+    /**
+     * calls f with every entry of subtree in key order
+     */
+    fn for_each_entry(&mut self, page_id: PageId, f: &mut impl FnMut(&mut Core, Entry) -> Result<()>) -> Result<()> {
+        let node = self.read_node(page_id)?;
+        let count = node.count;
+        let leaf = node.leaf;
+        let children = node.children.clone();
+
+        for (i, entry) in node.keys.into_iter().enumerate() {
+            if !leaf {
+                self.for_each_entry(children[i], f)?;
+            }
+
+            f(self, entry)?;
+        }
+
+        if !leaf {
+            self.for_each_entry(children[count], f)?;
+        }
+
+        Ok(())
+    }
+
+    fn get(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut page_id = self.root;
+
+        loop {
+            let mut node = self.read_node(page_id)?;
+            let i = node.keys.partition_point(|entry| entry.key.as_slice() < key);
+
+            if i < node.count && node.keys[i].key == key {
+                let value = std::mem::replace(&mut node.keys[i].value, Value::Inline(vec![]));
+
+                return self.load_value(value).map(Some);
+            }
+
+            if node.leaf {
+                return Ok(None);
+            }
+
+            page_id = node.children[i];
+        }
+    }
+
+    /**
+     * presence is told by entry in node, value is not loaded, so overflow pages are never read
+     */
+    fn contains(&mut self, key: &[u8]) -> Result<bool> {
+        let mut page_id = self.root;
+
+        loop {
+            let node = self.read_node(page_id)?;
+            let i = node.keys.partition_point(|entry| entry.key.as_slice() < key);
+
+            if i < node.count && node.keys[i].key == key {
+                return Ok(true);
+            }
+
+            if node.leaf {
+                return Ok(false);
+            }
+
+            page_id = node.children[i];
+        }
+    }
+
+    /**
+     * replaces value of existing key in place
+     * returns replaced value, None if key is not found
+     */
+    fn replace(&mut self, entry: &Entry) -> Result<Option<Value>> {
+        let mut page_id = self.root;
+
+        loop {
+            let mut node = self.read_node(page_id)?;
+            let i = node.keys.partition_point(|key| key < entry);
+
+            if i < node.count && node.keys[i] == *entry {
+                let old_value = std::mem::replace(&mut node.keys[i].value, entry.value.clone());
+
+                self.write_node(page_id, &node)?;
+
+                return Ok(Some(old_value));
+            }
+
+            if node.leaf {
+                return Ok(None);
+            }
+
+            page_id = node.children[i];
+        }
+    }
+
+    /**
+     * flushes when half of cache is dirty, dirty pages can not be evicted
+     * called before every page written by change, so tree is consistent at this point
+     */
+    fn reserve(&mut self) -> Result<()> {
+        if self.cache.dirty_pages() * 2 >= self.cache.capacity() {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * value not fitting into node page is compressed if compression is on and it gets shorter,
+     * so stored value never grows
+     */
+    fn store_value(&mut self, key: &[u8], value: &[u8]) -> Result<Value> {
+        #[cfg(feature = "lz4")]
+        if self.compression && ENTRY_HEADER_SIZE + key.len() + value.len() > MAX_ENTRY_SIZE {
+            let mut packed = (value.len() as u64).to_le_bytes().to_vec();
+
+            packed.extend_from_slice(&lz4_flex::block::compress(value));
+
+            if packed.len() < value.len() {
+                return Ok(Value::Compressed(Box::new(self.store_raw(key, &packed)?)));
+            }
+        }
+
+        self.store_raw(key, value)
+    }
+
+    /**
+     * value stays inline if entry fits into node page, otherwise it is written to new overflow chain
+     */
+    fn store_raw(&mut self, key: &[u8], value: &[u8]) -> Result<Value> {
+        if ENTRY_HEADER_SIZE + key.len() + value.len() <= MAX_ENTRY_SIZE {
+            return Ok(Value::Inline(value.to_vec()));
+        }
+
+        let page_size = self.cache.page_size();
+        let chunks: Vec<&[u8]> = value.chunks(page_size - OVERFLOW_HEADER_SIZE).collect();
+        let mut page_ids = Vec::with_capacity(chunks.len());
+
+        for _ in 0..chunks.len() {
+            page_ids.push(self.alloc_page()?);
+        }
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let next = page_ids.get(i + 1).copied().unwrap_or(HEADER_PAGE);
+
+            self.reserve()?;
+            self.cache.write(page_ids[i], &encode_overflow_page(next, chunk, page_size))?;
+        }
+
+        Ok(Value::Overflow {
+            len: value.len() as u64,
+            first: page_ids[0],
+        })
+    }
+
+    /**
+     * reads overflow chain, inline value is returned as is, compressed one is decompressed
+     */
+    fn load_value(&mut self, value: Value) -> Result<Vec<u8>> {
+        read_value(value, &mut |page_id, out| {
+            let (next, data) = decode_overflow_page(self.cache.read(page_id)?)?;
+
+            out.extend_from_slice(data);
+
+            Ok(next)
+        })
+    }
+
+    /**
+     * returns pages of overflow chain to free list
+     */
+    fn free_value(&mut self, value: Value) -> Result<()> {
+        let (mut len, first) = match value {
+            Value::Inline(_) => return Ok(()),
+            Value::Overflow { len, first } => (len, first),
+            Value::Compressed(value) => return self.free_value(*value),
+        };
+
+        let mut page_id = first;
+
+        while page_id != HEADER_PAGE {
+            let (next, data) = decode_overflow_page(self.cache.read(page_id)?)?;
+
+            len = len
+                .checked_sub(data.len() as u64)
+                .ok_or_else(|| Error::Corrupt(format!("overflow chain at page {} is too long", first)))?;
+
+            self.reserve()?;
+            self.free_page(page_id)?;
+            page_id = next;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * entry key is not in tree
+     */
+    fn insert_entry(&mut self, entry: Entry) -> Result<()> {
+        self.header_dirty = true;
+        self.len += 1;
+
+        node_store::insert(self, entry)
+    }
+
+    /**
+     * removes key, its overflow pages are freed
+     * returns status of operation: did element remove
+     */
+    fn delete_key(&mut self, key: &[u8]) -> Result<bool> {
+        match self.remove_key(key)? {
+            Some(value) => {
+                self.free_value(value)?;
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /**
+     * removes key from tree, freeing its value is left to caller
+     */
+    fn remove_key(&mut self, key: &[u8]) -> Result<Option<Value>> {
+        let removed = node_store::delete(self, |_, entry| entry.key.as_slice().cmp(key))?;
+
+        if removed.is_some() {
+            self.len -= 1;
+            self.header_dirty = true;
+        }
+
+        Ok(removed.map(|entry| entry.value))
+    }
+
+    /**
+     * inserts key or replaces its value, overflow pages of replaced value are freed
+     */
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let entry = Entry {
+            key: key.to_vec(),
+            value: self.store_value(key, value)?,
+        };
+
+        self.reserve()?;
+
+        match self.place(entry)? {
+            Some(old_value) => self.free_value(old_value),
+            None => Ok(()),
+        }
+    }
+
+    /**
+     * inserts entry or replaces value of its key, replaced value is returned to be freed by caller
+     */
+    fn place(&mut self, entry: Entry) -> Result<Option<Value>> {
+        match self.replace(&entry)? {
+            Some(old_value) => Ok(Some(old_value)),
+            None => self.insert_entry(entry).map(|_| None),
+        }
+    }
+
+    /**
+     * applies single change to pages
+     * returns status of operation: did tree change
+     */
+    fn apply(&mut self, op: &Op) -> Result<bool> {
+        self.reserve()?;
+
+        match op {
+            Op::Put { key, value } => {
+                self.put(key, value)?;
+
+                Ok(true)
+            }
+            Op::Delete { key } => self.delete_key(key),
+            Op::CreateTable { name } => self.add_table(name),
+            Op::DropTable { name } => self.remove_table(name),
+            Op::TablePut { table, key, value } => self.put_in(table, key, value),
+            Op::TableDelete { table, key } => self.delete_in(table, key),
+        }
+    }
+
+    /**
+     * checks that key fits into node page with reference to overflow value
+     */
+    fn check_key(key: &[u8]) -> Result<()> {
+        let size = ENTRY_HEADER_SIZE + key.len() + OVERFLOW_REF_SIZE;
+
+        if size > MAX_ENTRY_SIZE {
+            return Err(Error::KeyTooLarge {
+                key: format!("{:?}", String::from_utf8_lossy(key)),
+                size,
+                available: MAX_ENTRY_SIZE,
+            });
+        }
+
+        Ok(())
+    }
+
+    /**
+     * checks that key of every put fits into node page and that every table op finds its table
+     * as batch leaves it up to that op, so logged batch is never rejected halfway
+     */
+    fn check_batch(&mut self, batch: &WriteBatch) -> Result<()> {
+        let mut tables: HashMap<&str, bool> = HashMap::new();
+
+        for op in batch.ops() {
+            let (table, exists) = match op {
+                Op::Put { key, .. } => {
+                    Core::check_key(key)?;
+
+                    continue;
+                }
+                Op::Delete { .. } => continue,
+                Op::CreateTable { name } => {
+                    Core::check_table_name(name)?;
+
+                    (name, false)
+                }
+                Op::DropTable { name } => (name, true),
+                Op::TablePut { table, key, .. } => {
+                    Core::check_key(key)?;
+
+                    (table, true)
+                }
+                Op::TableDelete { table, .. } => (table, true),
+            };
+
+            let found = match tables.get(table.as_str()) {
+                Some(&found) => found,
+                None => self.catalog_get(table.as_bytes())?.is_some(),
+            };
+
+            match (found, exists) {
+                (true, false) => return Err(Error::TableExists(table.clone())),
+                (false, true) => return Err(Error::TableNotFound(table.clone())),
+                _ => {}
+            }
+
+            tables.insert(table, !matches!(op, Op::DropTable { .. }));
+        }
+
+        Ok(())
+    }
+
+    /**
+     * load about to bypass log takes sequence number of its own, so no follower takes state before it for state
+     * after it, backlog has no batches to replay it with and starts over, log must be empty
+     */
+    fn unlogged_change(&mut self) {
+        self.seq += 1;
+        self.applied_seq = self.seq;
+        self.header_dirty = true;
+
+        if let Some(backlog) = self.backlog.as_mut() {
+            backlog.reset(self.seq);
+        }
+    }
+
+    /**
+     * turns backlog on with limit in bytes or off, new one is filled with batches of log since checkpoint
+     */
+    fn set_replication_backlog(&mut self, limit: Option<u64>) -> Result<()> {
+        let Some(limit) = limit else {
+            self.backlog = None;
+
+            return Ok(());
+        };
+
+        let mut backlog = Backlog::new(limit, self.seq);
+
+        if let (Some(path), Some(_)) = (&self.path, &self.wal) {
+            let records: Vec<(u64, WriteBatch)> = Wal::read_segments(path, self.wal_segment, self.cache.cipher())?
+                .into_iter()
+                .map(|Record::Batch { seq, batch }| (seq, batch))
+                .filter(|(seq, _)| *seq <= self.seq)
+                .collect();
+
+            if let (Some((first, _)), Some((last, _))) = (records.first(), records.last()) {
+                if *last == self.seq {
+                    backlog.reset(first - 1);
+
+                    for (seq, batch) in &records {
+                        backlog.push(*seq, batch);
+                    }
+                }
+            }
+        }
+
+        self.backlog = Some(backlog);
+
+        Ok(())
+    }
+
+    fn changes_since(&self, position: u64, max: usize) -> Result<Vec<(u64, WriteBatch)>> {
+        let backlog = self
+            .backlog
+            .as_ref()
+            .ok_or_else(|| Error::InvalidOptions("replication backlog is off".to_string()))?;
+
+        backlog.since(position, self.seq, max)
+    }
+
+    /**
+     * commits batch of primary unless it is committed already, see Db::replicate
+     */
+    fn replicate(&mut self, seq: u64, batch: &WriteBatch) -> Result<bool> {
+        if seq <= self.seq {
+            return Ok(false);
+        }
+
+        if seq != self.seq + 1 {
+            return Err(Error::ReplicationGap {
+                expected: self.seq + 1,
+                found: seq,
+            });
+        }
+
+        self.commit(batch, true)?;
+
+        Ok(true)
+    }
+
+    /**
+     * checkpoints, so log has nothing to replay over new seq, then makes it durable in header
+     */
+    fn set_seq(&mut self, seq: u64) -> Result<()> {
+        self.checkpoint()?;
+        self.seq = seq;
+        self.applied_seq = seq;
+        self.header_dirty = true;
+
+        if let Some(backlog) = self.backlog.as_mut() {
+            backlog.reset(seq);
+        }
+
+        self.flush()?;
+        self.cache.sync()
+    }
+
+    /**
+     * appends batch to log, syncing it as sync mode requires, then applies it to pages
+     * commit is true for write of batch and false for single inserts and deletes
+     */
+    fn commit(&mut self, batch: &WriteBatch, commit: bool) -> Result<()> {
+        self.check_batch(batch)?;
+
+        let seq = self.seq + 1;
+
+        self.wal()?.append_batch(seq, batch)?;
+        self.seq = seq;
+
+        let sync = match self.sync_mode {
+            SyncMode::Always => true,
+            SyncMode::OnCommit => commit,
+            SyncMode::Off => false,
+        };
+
+        if sync {
+            self.wal()?.sync()?;
+        }
+
+        if let Some(backlog) = self.backlog.as_mut() {
+            backlog.push(seq, batch);
+        }
+
+        for op in batch.ops() {
+            self.apply(op)?;
+        }
+
+        self.applied_seq = self.seq;
+        self.header_dirty = true;
+
+        if self.garbage {
+            self.reclaim(RECLAIM_STEPS)?;
+        }
+
+        if self.wal_limit.is_some_and(|limit| self.wal_len() > limit) {
+            self.checkpoint()?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * applies all changes of batch, after crash either all of them are visible or none
+     */
+    fn write(&mut self, batch: &WriteBatch) -> Result<()> {
+        self.commit(batch, true)
+    }
+
+    /**
+     * inserts key with value, value of existing key is replaced
+     */
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut batch = WriteBatch::new();
+
+        batch.put(key, value);
+
+        self.commit(&batch, false)
+    }
+
+    /**
+     * removes key with its value
+     * returns status of operation: did element remove
+     */
+    fn delete(&mut self, key: &[u8]) -> Result<bool> {
+        self.wal()?;
+
+        if !self.contains(key)? {
+            return Ok(false);
+        }
+
+        let mut batch = WriteBatch::new();
+
+        batch.delete(key);
+        self.commit(&batch, false)?;
+
+        Ok(true)
+    }
+
+    fn collect_into(&mut self, page_id: PageId, out: &mut Vec<(Vec<u8>, Value)>) -> Result<()> {
+        let node = self.read_node(page_id)?;
+
+        for (i, entry) in node.keys.iter().enumerate() {
+            if !node.leaf {
+                self.collect_into(node.children[i], out)?;
+            }
+
+            out.push((entry.key.clone(), entry.value.clone()));
+        }
+
+        if !node.leaf {
+            self.collect_into(node.children[node.count], out)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * all entries in key order
+     */
+    fn entries(&mut self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = Vec::with_capacity(self.len);
+
+        self.collect_into(self.root, &mut entries)?;
+
+        entries.into_iter().map(|(key, value)| Ok((key, self.load_value(value)?))).collect()
+    }
+
+    /**
+     * stats from counters kept in header and pager, nothing is read
+     */
+    fn disk_stats(&self) -> DiskStats {
+        let page_size = self.cache.file_page_size();
+        let file_pages = self.cache.file_pages() as u64;
+        let pages = self.cache.page_count() as u64;
+        let free_pages = self.free_count as u64;
+        let used = pages - free_pages + self.cache.metadata_pages() as u64;
+
+        DiskStats {
+            page_size,
+            file_pages,
+            file_bytes: file_pages * page_size as u64,
+            pages,
+            free_pages,
+            tree_pages: pages - free_pages - 1,
+            node_pages: None,
+            overflow_pages: None,
+            used_bytes: None,
+            entries: self.len as u64,
+            wal_bytes: self.wal_len(),
+            fragmentation: file_pages.saturating_sub(used) as f64 / file_pages as f64,
+        }
+    }
+
+    /**
+     * counts of verify, so exact stats always agree with it, fails with the first problem found
+     */
+    fn disk_stats_exact(&mut self) -> Result<DiskStats> {
+        let report = self.verify(VerifyMode::Quick);
+
+        match report.problems.into_iter().next() {
+            Some(problem) => Err(Error::Corrupt(problem.to_string())),
+            None => Ok(report.stats),
+        }
+    }
+}
+
+/**
+ * pages as store of shared insert and delete, loaded node is a decoded copy, so unload just drops it
+ */
+impl NodeStore<Entry> for Core {
+    type Error = Error;
+
+    fn t(&self) -> usize {
+        self.t
+    }
+
+    fn root(&self) -> PageId {
+        self.root
+    }
+
+    fn set_root(&mut self, page_id: PageId) {
+        self.root = page_id;
+        self.header_dirty = true;
+    }
+
+    fn compare(&self, a: &Entry, b: &Entry) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn allocator(&self) -> &() {
+        &()
+    }
+
+    fn load(&mut self, page_id: PageId) -> Result<Node<Entry>> {
+        self.read_node(page_id)
+    }
+
+    fn store(&mut self, page_id: PageId, node: Node<Entry>) -> Result<()> {
+        self.write_node(page_id, &node)
+    }
+
+    fn unload(&mut self, _page_id: PageId, _node: Node<Entry>) {}
+
+    fn count(&mut self, page_id: PageId) -> Result<usize> {
+        node_count(self.cache.read(page_id)?)
+    }
+
+    fn alloc(&mut self, node: Node<Entry>) -> Result<PageId> {
+        self.alloc_node(&node)
+    }
+
+    fn free(&mut self, page_id: PageId) -> Result<()> {
+        self.free_page(page_id)
+    }
+}
+
+/**
+ * builds tree of empty database from entries pushed in key order
+ * nodes are fully packed and built level by level like in BTree::from_sorted,
+ * leaves are written as they fill up, levels above are built by finish
+ */
+struct Loader {
+    t: usize,
+    len: usize,
+    width: usize,
+    leaf_keys: usize,
+    per_node: usize,
+    level: Vec<PageId>,
+    delimeters: Vec<Entry>,
+    leaf: Node<Entry>,
+}
+
+impl Loader {
+    fn new(t: usize, len: usize) -> Loader {
+        let per_node = BTree::<Entry>::keys_per_node(t, 1.0) + 1;
+        let width = BTree::<Entry>::level_width(len + 1, per_node, t);
+
+        Loader {
+            t,
+            len,
+            width,
+            leaf_keys: len + 1 - width,
+            per_node,
+            level: Vec::with_capacity(width),
+            delimeters: Vec::with_capacity(width),
+            leaf: Node::leaf(t),
+        }
+    }
+
+    fn push(&mut self, db: &mut Core, entry: Entry) -> Result<()> {
+        if self.leaf.keys.len() < BTree::<Entry>::share(self.leaf_keys, self.width, self.level.len()) {
+            self.leaf.keys.push(entry);
+
+            return Ok(());
+        }
+
+        if self.level.len() + 1 == self.width {
+            return Err(Error::Corrupt(format!("tree holds more than {} entries", self.len)));
+        }
+
+        let mut leaf = std::mem::replace(&mut self.leaf, Node::leaf(self.t));
+
+        leaf.count = leaf.keys.len();
+        db.reserve()?;
+        self.level.push(db.alloc_node(&leaf)?);
+        self.delimeters.push(entry);
+
+        Ok(())
+    }
+
+    /**
+     * writes the last leaf and levels above it, root goes into page of the current root
+     */
+    fn finish(mut self, db: &mut Core) -> Result<()> {
+        let t = self.t;
+        let width = self.width;
+
+        if self.level.len() + 1 != width || self.leaf.keys.len() != BTree::<Entry>::share(self.leaf_keys, width, self.level.len()) {
+            return Err(Error::Corrupt(format!("tree holds fewer than {} entries", self.len)));
+        }
+
+        self.leaf.count = self.leaf.keys.len();
+        db.reserve()?;
+        self.level.push(db.place_node(&self.leaf, width == 1)?);
+
+        let mut level = self.level;
+        let mut delimeters = self.delimeters;
+
+        while level.len() > 1 {
+            let children_count = level.len();
+            let width = BTree::<Entry>::level_width(children_count, self.per_node, t);
+
+            let mut children = level.into_iter();
+            let mut keys = delimeters.into_iter();
+
+            level = Vec::with_capacity(width);
+            delimeters = Vec::with_capacity(width);
+
+            for j in 0..width {
+                let share = BTree::<Entry>::share(children_count, width, j);
+                let mut node = Node::<Entry>::empty(t);
+
+                node.children.extend(children.by_ref().take(share));
+                node.keys.extend(keys.by_ref().take(share - 1));
+                node.count = share - 1;
+
+                db.reserve()?;
+                level.push(db.place_node(&node, width == 1)?);
+
+                if j + 1 != width {
+                    delimeters.push(keys.next().unwrap());
+                }
+            }
+        }
+
+        db.len = self.len;
+        db.header_dirty = true;
+
+        Ok(())
+    }
+}
+
+/**
+ * original value of compressed one, None if it is damaged
+ * lz4 block expands at most 255 times, so damaged length is refused before anything is allocated for it
+ */
+#[cfg(feature = "lz4")]
+fn unpack(packed: &[u8]) -> Option<Vec<u8>> {
+    let len = usize::try_from(u64::from_le_bytes(packed.get(..8)?.try_into().unwrap())).ok()?;
+    let block = &packed[8..];
+
+    if len > block.len().saturating_mul(255) {
+        return None;
+    }
+
+    lz4_flex::block::decompress(block, len).ok().filter(|value| value.len() == len)
+}
+
+/**
+ * database with compressed values is not opened without lz4 feature
+ */
+#[cfg(not(feature = "lz4"))]
+fn unpack(_packed: &[u8]) -> Option<Vec<u8>> {
+    None
+}
+
+/**
+ * vacuum builds new file next to database, with "-vacuum" appended to its name
+ */
+fn vacuum_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+
+    name.push("-vacuum");
+
+    PathBuf::from(name)
+}
+
+/**
+ * makes rename of file durable, directories can not be synced on windows
+ */
+pub(crate) fn sync_parent(path: &Path) -> Result<()> {
+    if cfg!(windows) {
+        return Ok(());
+    }
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    File::open(parent)?.sync_all()?;
+
+    Ok(())
+}
+
+impl Drop for Core {
+    /**
+     * errors are lost here, call close to see them
+     */
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
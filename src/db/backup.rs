@@ -0,0 +1,697 @@
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::backup::{BackupManifest, DeltaReader, DeltaWriter};
+use crate::crc32::Crc32;
+use crate::dump::{DumpReader, DumpWriter};
+use crate::error::{Error, Result};
+use crate::lock::FileLock;
+use crate::options::SrdbOptions;
+use crate::pager::{PageId, Pager, HEADER_PAGE};
+use crate::wal::Wal;
+use crate::Node;
+
+use super::catalog::Tree;
+use super::{sync_parent, Core, Db, Entry, Loader, PROGRESS_KEYS};
+
+/**
+ * how far stream of table got, see Table::export_stream
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamProgress {
+    pub keys: u64,
+    pub bytes: u64,
+}
+
+/**
+ * entry count and checksum of one tree, table is None for default tree, see Db::digests
+ * checksum is crc32 of entries in key order, each as in dump: key and value both after their length
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeDigest {
+    pub table: Option<String>,
+    pub entries: u64,
+    pub checksum: u32,
+}
+
+/**
+ * result of backup, see Db::backup_to and Db::backup_incremental
+ * pages are logical pages copied, seq is sequence number of the last batch in backup
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BackupReport {
+    pub pages: u64,
+    pub bytes: u64,
+    pub seq: u64,
+}
+
+/**
+ * adds entry to checksum of TreeDigest in its dump encoding
+ */
+fn digest_entry(crc: &mut Crc32, key: &[u8], value: &[u8]) {
+    crc.update(&(key.len() as u32).to_le_bytes());
+    crc.update(key);
+    crc.update(&(value.len() as u32).to_le_bytes());
+    crc.update(value);
+}
+
+/**
+ * copy of database in progress, made by Db::backup
+ * pages of committed state at start are pinned in file, so handle keeps working and flushing meanwhile,
+ * step copies a few pages under lock of database, then lets it go, backup sees none of later changes
+ * file is complete and opens on its own once finish returns, dropped backup removes it
+ */
+pub struct Backup {
+    core: Arc<Mutex<Core>>,
+    path: PathBuf,
+    /**
+     * None once backup is finished
+     */
+    pager: Option<Pager>,
+    _lock: FileLock,
+    snapshot: Vec<PageId>,
+    next: PageId,
+    manifest: BackupManifest,
+}
+
+impl Backup {
+    /**
+     * pages copied under one lock of database
+     */
+    pub const STEP_PAGES: usize = 64;
+
+    /**
+     * copies up to pages more pages, returns whether all of them are copied
+     */
+    pub fn step(&mut self, pages: usize) -> Result<bool> {
+        let Some(pager) = self.pager.as_mut() else {
+            return Ok(true);
+        };
+
+        let mut core = self.core.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut buf = vec![0; pager.page_size()];
+        let end = self.snapshot.len().min(self.next as usize + pages) as PageId;
+
+        for page_id in self.next..end {
+            core.cache.read_snapshot(page_id, self.snapshot[page_id as usize], &mut buf)?;
+
+            if page_id != HEADER_PAGE {
+                pager.allocate_page()?;
+            }
+
+            pager.write_page(page_id, &buf)?;
+            self.next = page_id + 1;
+        }
+
+        Ok(self.next as usize == self.snapshot.len())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.snapshot.len() - self.next as usize
+    }
+
+    /**
+     * copies the rest of pages step by step, then commits and syncs backup with its empty log
+     */
+    pub fn finish(mut self) -> Result<BackupReport> {
+        while !self.step(Backup::STEP_PAGES)? {}
+
+        let Some(mut pager) = self.pager.take() else {
+            unreachable!("backup is finished once");
+        };
+
+        Wal::create(&self.path, self.manifest.wal_segment, pager.cipher())?.sync()?;
+        pager.commit(true)?;
+        drop(pager);
+        self.manifest.write(&BackupManifest::path_for(&self.path))?;
+        sync_parent(&self.path)?;
+
+        Ok(BackupReport {
+            pages: self.snapshot.len() as u64,
+            bytes: fs::metadata(&self.path)?.len(),
+            seq: self.manifest.seq,
+        })
+    }
+}
+
+impl Drop for Backup {
+    fn drop(&mut self) {
+        let generation = self.manifest.generation;
+
+        self.core.lock().unwrap_or_else(PoisonError::into_inner).cache.unpin_snapshot(generation);
+
+        if self.pager.take().is_some() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Db {
+    /**
+     * creates new database file at path and fills it from dump written by Db::dump, fails if file exists
+     * dump does not depend on page size or format version of file it came from, see SrdbOptions::restore
+     */
+    pub fn restore<R: Read>(r: R, path: impl AsRef<Path>) -> Result<Db> {
+        SrdbOptions::new().restore(r, path)
+    }
+
+    /**
+     * same as restore, but with tables given only they are restored and default tree is left empty,
+     * every one of them must be in dump, returns digests of restored trees computed from dump as it was read,
+     * so reopened database can be checked against them with Db::digests
+     */
+    pub fn restore_tables<R: Read>(
+        r: R,
+        path: impl AsRef<Path>,
+        tables: Option<&[&str]>,
+    ) -> Result<(Db, Vec<TreeDigest>)> {
+        SrdbOptions::new().restore_tables(r, path, tables)
+    }
+
+    /**
+     * creates database file at path from full backup and deltas made on top of it in order by
+     * Db::backup_incremental, fails if file exists
+     * every delta must continue the one before, otherwise Error::InvalidBackup is returned
+     * full backup of encrypted database is not restored, it is opened in place with its key
+     */
+    pub fn restore_backup<P: AsRef<Path>>(base: impl AsRef<Path>, deltas: &[P], path: impl AsRef<Path>) -> Result<Db> {
+        let path = path.as_ref();
+        let target = OpenOptions::new().write(true).create_new(true).open(path)?;
+
+        if let Err(error) = Db::apply_backups(base.as_ref(), deltas, path, target) {
+            fs::remove_file(path)?;
+            Wal::remove(path)?;
+
+            return Err(error);
+        }
+
+        Db::open(path)
+    }
+
+    /**
+     * copies base into target, then writes pages of every delta and commits it
+     */
+    fn apply_backups<P: AsRef<Path>>(base: &Path, deltas: &[P], path: &Path, mut target: File) -> Result<()> {
+        let mut manifest = BackupManifest::read(BackupManifest::path_for(base))?;
+
+        if !manifest.is_full() {
+            return Err(Error::InvalidBackup(format!("{} is not a full backup", base.display())));
+        }
+
+        io::copy(&mut File::open(base)?, &mut target)?;
+        target.sync_all()?;
+        drop(target);
+
+        let mut pager = Pager::open(path)?;
+        let mut buf = vec![0; pager.page_size()];
+        let cipher = pager.cipher().cloned();
+
+        for delta in deltas {
+            let delta = delta.as_ref();
+            let (mut reader, next, pages) = DeltaReader::open(delta)?;
+
+            if next.file_id != manifest.file_id
+                || next.page_size != manifest.page_size
+                || next.base != Some(manifest.generation)
+            {
+                return Err(Error::InvalidBackup(format!(
+                    "{} does not continue backup of commit {}",
+                    delta.display(),
+                    manifest.generation
+                )));
+            }
+
+            while pager.page_count() < next.page_count {
+                pager.allocate_page()?;
+            }
+
+            for _ in 0..pages {
+                let page_id = reader.page(&mut buf)?;
+
+                if page_id >= next.page_count {
+                    return Err(Error::InvalidBackup(format!("page {} is out of bounds", page_id)));
+                }
+
+                pager.write_page(page_id, &buf)?;
+            }
+
+            reader.finish()?;
+            pager.commit(true)?;
+            manifest = next;
+        }
+
+        drop(pager);
+        Wal::create(path, manifest.wal_segment, cipher.as_ref())?.sync()?;
+        sync_parent(path)
+    }
+
+    /**
+     * file left by failed restore is removed with its log
+     */
+    pub(crate) fn restore_with(
+        r: impl Read,
+        path: &Path,
+        tables: Option<&[&str]>,
+        options: &SrdbOptions,
+    ) -> Result<(Db, Vec<TreeDigest>)> {
+        let db = Db::create_with(path, options)?;
+        let restored = db.checked().and_then(|mut core| core.restore(r, tables));
+
+        match restored {
+            Ok(digests) => Ok((db, digests)),
+            Err(error) => {
+                drop(db);
+                fs::remove_file(path)?;
+                Wal::remove(path)?;
+
+                Err(error)
+            }
+        }
+    }
+
+    /**
+     * writes entries of default tree and of every table to w in portable dump format, see Db::restore
+     * handle stays locked until dump is written, so it is a consistent copy
+     */
+    pub fn dump<W: Write>(&self, w: W) -> Result<()> {
+        self.checked()?.dump(w, None)
+    }
+
+    /**
+     * same as dump, but only given tables are written and default tree is written empty,
+     * missing table is Error::TableNotFound
+     */
+    pub fn dump_tables<W: Write>(&self, w: W, tables: &[&str]) -> Result<()> {
+        self.checked()?.dump(w, Some(tables))
+    }
+
+    /**
+     * digest of default tree, then of every table in byte order of names, see TreeDigest
+     * every value is read, so it costs as much as dump
+     */
+    pub fn digests(&self) -> Result<Vec<TreeDigest>> {
+        self.checked()?.digests()
+    }
+
+    /**
+     * copies database as of this call to a new file at path, fails if file exists, see Backup
+     */
+    pub fn backup_to(&self, path: impl AsRef<Path>) -> Result<BackupReport> {
+        self.backup(path)?.finish()
+    }
+
+    /**
+     * flushes database and starts copying its committed state to a new file at path, see Backup
+     * backup of encrypted database is encrypted with the same key and fresh salt
+     */
+    pub fn backup(&self, path: impl AsRef<Path>) -> Result<Backup> {
+        let path = path.as_ref();
+        let mut core = self.checked()?;
+
+        core.flush()?;
+
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(path)?;
+        let started = FileLock::acquire(path, true, None).and_then(|lock| {
+            let pager = Pager::create_with(Box::new(file), core.cache.file_page_size(), core.key.as_ref())?;
+
+            Ok((lock, pager))
+        });
+
+        let (lock, pager) = match started {
+            Ok(started) => started,
+            Err(error) => {
+                let _ = fs::remove_file(path);
+
+                return Err(error);
+            }
+        };
+
+        let snapshot = core.cache.pin_snapshot();
+
+        Ok(Backup {
+            core: Arc::clone(&self.core),
+            path: path.to_path_buf(),
+            pager: Some(pager),
+            _lock: lock,
+            snapshot,
+            next: HEADER_PAGE,
+            manifest: core.manifest(None),
+        })
+    }
+
+    /**
+     * writes pages changed since backup described by base manifest into new delta file at dest,
+     * base is full backup or delta made before, manifest of delta is written next to it, see Db::restore_backup
+     * commits of database after base are found from generations stored in page table, so file is not scanned
+     * base of another file is Error::InvalidBackup, vacuum makes new file and needs new full backup
+     * deltas hold pages in plaintext, so encrypted database is backed up only in full
+     */
+    pub fn backup_incremental(&self, base_manifest: impl AsRef<Path>, dest: impl AsRef<Path>) -> Result<BackupReport> {
+        let base_manifest = base_manifest.as_ref();
+        let dest = dest.as_ref();
+        let base = BackupManifest::read(base_manifest)?;
+
+        let (snapshot, changed, manifest) = {
+            let mut core = self.checked()?;
+
+            if core.key.is_some() {
+                return Err(Error::InvalidBackup("encrypted database is backed up only in full".to_string()));
+            }
+
+            core.flush()?;
+
+            let manifest = core.manifest(Some(base.generation));
+
+            if manifest.file_id != base.file_id
+                || manifest.page_size != base.page_size
+                || manifest.generation < base.generation
+            {
+                return Err(Error::InvalidBackup(format!(
+                    "{} is not a backup of this database file",
+                    base_manifest.display()
+                )));
+            }
+
+            let changed = core.cache.changed_since(base.generation);
+
+            (core.cache.pin_snapshot(), changed, manifest)
+        };
+
+        let written = self.write_delta(dest, &snapshot, &changed, &manifest);
+
+        self.core().cache.unpin_snapshot(manifest.generation);
+
+        if let Err(error) = written {
+            let _ = fs::remove_file(dest);
+            let _ = fs::remove_file(BackupManifest::path_for(dest));
+
+            return Err(error);
+        }
+
+        Ok(BackupReport {
+            pages: changed.len() as u64,
+            bytes: fs::metadata(dest)?.len(),
+            seq: manifest.seq,
+        })
+    }
+
+    /**
+     * copies pages of pinned snapshot, a few of them under one lock like Backup::step
+     */
+    fn write_delta(&self, dest: &Path, snapshot: &[PageId], changed: &[PageId], manifest: &BackupManifest) -> Result<()> {
+        let mut delta = DeltaWriter::create(dest, manifest, changed.len())?;
+        let mut buf = vec![0; self.core().cache.page_size()];
+
+        for chunk in changed.chunks(Backup::STEP_PAGES) {
+            let mut core = self.core();
+
+            for &page_id in chunk {
+                core.cache.read_snapshot(page_id, snapshot[page_id as usize], &mut buf)?;
+                delta.page(page_id, &buf)?;
+            }
+        }
+
+        delta.finish()?;
+        manifest.write(&BackupManifest::path_for(dest))?;
+        sync_parent(dest)
+    }
+}
+
+impl Core {
+    /**
+     * describes committed state, cache must be flushed
+     */
+    fn manifest(&self, base: Option<u64>) -> BackupManifest {
+        BackupManifest {
+            file_id: self.cache.file_id(),
+            generation: self.cache.generation(),
+            base,
+            page_size: self.cache.file_page_size(),
+            page_count: self.cache.page_count(),
+            seq: self.seq,
+            wal_segment: self.wal_segment,
+        }
+    }
+
+    /**
+     * writes default tree and tables in byte order of their names, see DumpWriter
+     * with only given, default tree is written empty and only tables listed there follow
+     */
+    pub(super) fn dump(&mut self, w: impl Write, only: Option<&[&str]>) -> Result<()> {
+        let tables = match only {
+            None => self.named_tables()?,
+            Some(only) => {
+                let mut names = only.to_vec();
+
+                names.sort_unstable();
+                names.dedup();
+                names.into_iter().map(|name| Ok((name.to_string(), self.table(name)?))).collect::<Result<_>>()?
+            }
+        };
+        let names: Vec<String> = tables.iter().map(|(name, _)| name.clone()).collect();
+        let default = Tree {
+            root: self.root,
+            len: if only.is_some() { 0 } else { self.len },
+        };
+
+        let mut out = DumpWriter::new(w, &names)?;
+
+        for tree in std::iter::once(default).chain(tables.into_iter().map(|(_, tree)| tree)) {
+            out.tree(tree.len)?;
+
+            if tree.len == 0 {
+                continue;
+            }
+
+            self.for_each_entry(tree.root, &mut |core, Entry { key, value }| {
+                let value = core.load_value(value)?;
+
+                out.entry(&key, &value)
+            })?;
+        }
+
+        out.finish()
+    }
+
+    /**
+     * fills new database from dump, default tree and every table are bulk loaded like import_tree does,
+     * header is written once dump is read up to its checksum
+     * with only given, default tree and tables not listed there are read past, listed table missing from dump
+     * is Error::TableNotFound, returns digests of trees restored
+     */
+    fn restore(&mut self, r: impl Read, only: Option<&[&str]>) -> Result<Vec<TreeDigest>> {
+        let (mut dump, tables) = DumpReader::new(r)?;
+
+        if let Some(missing) = only.into_iter().flatten().find(|name| !tables.iter().any(|table| table == *name)) {
+            return Err(Error::TableNotFound(missing.to_string()));
+        }
+
+        let mut digests = vec![];
+        let len = dump.tree()?;
+
+        if only.is_some() {
+            Core::skip_tree(&mut dump, len)?;
+        } else {
+            let checksum = self.restore_tree(&mut dump, len, &mut |_| {})?;
+
+            digests.push(TreeDigest {
+                table: None,
+                entries: len as u64,
+                checksum,
+            });
+        }
+
+        for name in tables {
+            Core::check_table_name(&name)?;
+
+            let len = dump.tree()?;
+
+            if only.is_some_and(|only| !only.contains(&name.as_str())) {
+                Core::skip_tree(&mut dump, len)?;
+
+                continue;
+            }
+
+            let mut target = Tree {
+                root: self.alloc_node(&Node::leaf(self.t))?,
+                len: 0,
+            };
+
+            let checksum = self.with_tree(&mut target, |core| core.restore_tree(&mut dump, len, &mut |_| {}))?;
+
+            self.catalog_put(name.as_bytes(), target)?;
+            digests.push(TreeDigest {
+                table: Some(name),
+                entries: len as u64,
+                checksum,
+            });
+        }
+
+        dump.finish()?;
+        self.header_dirty = true;
+        self.flush()?;
+
+        Ok(digests)
+    }
+
+    /**
+     * reads entries of tree without storing them
+     */
+    fn skip_tree(dump: &mut DumpReader<impl Read>, len: usize) -> Result<()> {
+        for _ in 0..len {
+            dump.entry()?;
+        }
+
+        Ok(())
+    }
+
+    fn digests(&mut self) -> Result<Vec<TreeDigest>> {
+        let default = Tree {
+            root: self.root,
+            len: self.len,
+        };
+        let tables = self.named_tables()?.into_iter().map(|(name, tree)| (Some(name), tree));
+        let mut digests = vec![];
+
+        for (table, tree) in std::iter::once((None, default)).chain(tables) {
+            let mut crc = Crc32::new();
+            let mut entries = 0;
+
+            self.for_each_entry(tree.root, &mut |core, Entry { key, value }| {
+                digest_entry(&mut crc, &key, &core.load_value(value)?);
+                entries += 1;
+
+                Ok(())
+            })?;
+
+            digests.push(TreeDigest {
+                table,
+                entries,
+                checksum: crc.finish(),
+            });
+        }
+
+        Ok(digests)
+    }
+
+    /**
+     * progress is called every PROGRESS_KEYS entries, returns checksum of entries as TreeDigest has it
+     */
+    fn restore_tree(
+        &mut self,
+        dump: &mut DumpReader<impl Read>,
+        len: usize,
+        progress: &mut impl FnMut(StreamProgress),
+    ) -> Result<u32> {
+        let mut loader = Loader::new(self.t, len);
+        let mut crc = Crc32::new();
+
+        for i in 1..=len as u64 {
+            let (key, value) = dump.entry()?;
+
+            Core::check_key(&key)?;
+            digest_entry(&mut crc, &key, &value);
+
+            let value = self.store_value(&key, &value)?;
+
+            loader.push(self, Entry { key, value })?;
+
+            if i % PROGRESS_KEYS == 0 {
+                progress(StreamProgress {
+                    keys: i,
+                    bytes: dump.position(),
+                });
+            }
+        }
+
+        loader.finish(self)?;
+
+        Ok(crc.finish())
+    }
+
+    /**
+     * writes tree as dump with no tables, see Table::export_stream
+     */
+    pub(super) fn export_tree(
+        &mut self,
+        tree: Tree,
+        w: impl Write,
+        mut progress: impl FnMut(StreamProgress),
+    ) -> Result<StreamProgress> {
+        let mut out = DumpWriter::new(w, &[])?;
+        let mut done = StreamProgress::default();
+
+        out.tree(tree.len)?;
+
+        self.for_each_entry(tree.root, &mut |core, Entry { key, value }| {
+            let value = core.load_value(value)?;
+
+            out.entry(&key, &value)?;
+            done.keys += 1;
+
+            if done.keys % PROGRESS_KEYS == 0 {
+                done.bytes = out.position();
+                progress(done);
+            }
+
+            Ok(())
+        })?;
+
+        out.finish()?;
+        done.bytes = out.position();
+        progress(done);
+
+        Ok(done)
+    }
+
+    /**
+     * bulk loads empty table from dump with no tables, see Table::import_stream
+     * tree is built under new root and replaces empty one in catalog once dump is read up to its checksum,
+     * flushes meanwhile commit only pages nothing points to yet
+     */
+    pub(super) fn import_tree_stream(
+        &mut self,
+        name: &str,
+        r: impl Read,
+        mut progress: impl FnMut(StreamProgress),
+    ) -> Result<StreamProgress> {
+        let table = self.table(name)?;
+
+        if table.len != 0 {
+            return Err(Error::NotEmpty(table.len));
+        }
+
+        let (mut dump, tables) = DumpReader::new(r)?;
+
+        if !tables.is_empty() {
+            return Err(Error::CorruptDump(format!("stream of one table holds {} more tables", tables.len())));
+        }
+
+        self.checkpoint()?;
+        self.unlogged_change();
+
+        let len = dump.tree()?;
+        let mut target = Tree {
+            root: self.alloc_node(&Node::leaf(self.t))?,
+            len: 0,
+        };
+
+        self.with_tree(&mut target, |core| core.restore_tree(&mut dump, len, &mut progress))?;
+        dump.finish()?;
+        self.catalog_put(name.as_bytes(), target)?;
+        self.free_page(table.root)?;
+        self.flush()?;
+
+        let done = StreamProgress {
+            keys: len as u64,
+            bytes: dump.position(),
+        };
+
+        progress(done);
+
+        Ok(done)
+    }
+}
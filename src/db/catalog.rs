@@ -0,0 +1,571 @@
+use std::fmt::Debug;
+use std::io::{Read, Write};
+use std::ops::RangeBounds;
+
+use crate::batch::{Op, WriteBatch};
+use crate::error::{Error, Result};
+use crate::pager::{PageId, HEADER_PAGE};
+use crate::codec::Codec;
+use crate::{BTree, Node};
+
+use super::backup::StreamProgress;
+use super::{Core, Db, DbIter, Entry, Value, ENTRY_HEADER_SIZE, MAX_ENTRY_SIZE, OVERFLOW_REF_SIZE};
+
+/**
+ * root page and entry count of one tree in file
+ * catalog is a tree of its own mapping names of tables to their trees,
+ * trees of dropped tables wait in it under GARBAGE keys until reclaim frees their pages
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Tree {
+    pub(super) root: PageId,
+    pub(super) len: usize,
+}
+
+/**
+ * first byte of catalog keys of dropped trees, followed by root page id, utf-8 name never starts with it
+ */
+pub(super) const GARBAGE: u8 = 0xFF;
+
+/**
+ * steps of reclaim made after every change, each frees one overflow chain or one node
+ */
+pub(super) const RECLAIM_STEPS: usize = 8;
+
+impl Tree {
+    /**
+     * catalog of file without tables
+     */
+    pub(super) const NONE: Tree = Tree { root: HEADER_PAGE, len: 0 };
+
+    /**
+     * root (u32) and len (u64), as long as reference to overflow value, so catalog entry fits like one
+     */
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(OVERFLOW_REF_SIZE);
+
+        out.extend_from_slice(&self.root.to_le_bytes());
+        out.extend_from_slice(&(self.len as u64).to_le_bytes());
+
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Tree> {
+        if bytes.len() != OVERFLOW_REF_SIZE {
+            return Err(Error::Corrupt(format!("catalog entry of {} bytes", bytes.len())));
+        }
+
+        Ok(Tree {
+            root: u32::from_le_bytes(bytes[..4].try_into().unwrap()),
+            len: u64::from_le_bytes(bytes[4..].try_into().unwrap()) as usize,
+        })
+    }
+}
+
+fn garbage_key(root: PageId) -> Vec<u8> {
+    let mut key = vec![GARBAGE];
+
+    key.extend_from_slice(&root.to_be_bytes());
+
+    key
+}
+
+/**
+ * named table of database, see Db::table
+ * works like Db itself, batch written through it changes this table with its puts and deletes
+ */
+pub struct Table<'a> {
+    db: &'a mut Db,
+    name: String,
+}
+
+impl Table<'_> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn len(&self) -> Result<usize> {
+        Ok(self.db.checked()?.table(&self.name)?.len)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /**
+     * see Db::height
+     */
+    pub fn height(&self) -> Result<usize> {
+        let mut core = self.db.checked()?;
+        let tree = core.table(&self.name)?;
+
+        core.height(tree.root)
+    }
+
+    pub fn get(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut core = self.db.checked()?;
+        let mut tree = core.table(&self.name)?;
+
+        core.with_tree(&mut tree, |core| core.get(key))
+    }
+
+    pub fn contains(&mut self, key: &[u8]) -> Result<bool> {
+        let mut core = self.db.checked()?;
+        let mut tree = core.table(&self.name)?;
+
+        core.with_tree(&mut tree, |core| core.contains(key))
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut batch = WriteBatch::new();
+
+        batch.put_in(&self.name, key, value);
+
+        let mut core = self.db.checked()?;
+
+        core.commit(&batch, false)?;
+        self.db.changed(&core);
+
+        Ok(())
+    }
+
+    /**
+     * returns status of operation: did element remove
+     */
+    pub fn delete(&mut self, key: &[u8]) -> Result<bool> {
+        if !self.contains(key)? {
+            return Ok(false);
+        }
+
+        let mut batch = WriteBatch::new();
+
+        batch.delete_in(&self.name, key);
+
+        let mut core = self.db.checked()?;
+
+        core.commit(&batch, false)?;
+        self.db.changed(&core);
+
+        Ok(true)
+    }
+
+    /**
+     * applies batch as one log record with its puts and deletes going to this table,
+     * ops naming other tables keep them, so one batch changes several tables at once, see Db::write
+     */
+    pub fn apply(&mut self, batch: &WriteBatch) -> Result<()> {
+        let mut scoped = WriteBatch::new();
+
+        for op in batch.ops() {
+            match op {
+                Op::Put { key, value } => scoped.put_in(&self.name, key, value),
+                Op::Delete { key } => scoped.delete_in(&self.name, key),
+                op => scoped.push(op.clone()),
+            }
+        }
+
+        let mut core = self.db.checked()?;
+
+        core.commit(&scoped, true)?;
+        self.db.changed(&core);
+
+        Ok(())
+    }
+
+    pub fn to_vec(&mut self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut core = self.db.checked()?;
+        let mut tree = core.table(&self.name)?;
+
+        core.with_tree(&mut tree, Core::entries)
+    }
+
+    /**
+     * writes entries of table to w as dump holding them in place of default tree and no tables,
+     * so Db::restore reads it too, entries are streamed one by one and memory does not grow with table
+     * progress is called every PROGRESS_KEYS keys and once at the end, handle is locked until stream is written
+     */
+    pub fn export_stream<W: Write>(&mut self, w: W, progress: impl FnMut(StreamProgress)) -> Result<StreamProgress> {
+        let mut core = self.db.checked()?;
+        let tree = core.table(&self.name)?;
+
+        core.export_tree(tree, w, progress)
+    }
+
+    /**
+     * fills empty table with encoded keys of tree and empty values, packed like Db::import_tree does
+     * table is built under new root and replaces empty one in catalog once it is complete,
+     * crash or error before it leaves table empty, table holding entries is Error::NotEmpty
+     */
+    pub fn import_tree<T: Ord + Clone + Debug + Codec>(&mut self, tree: &BTree<T>) -> Result<()> {
+        let mut core = self.db.checked()?;
+
+        core.import_table_tree(&self.name, tree)
+    }
+
+    /**
+     * fills empty table from stream written by export_stream, entries are bulk loaded into packed pages
+     * as they are read, keeping one node per level and one key per leaf in memory
+     * like Db::import_tree it does not go through log: database is checkpointed first and table
+     * is committed by flush at the end, crash or error before it leaves table empty
+     * and pages written for it unreachable until vacuum, table holding entries is Error::NotEmpty
+     */
+    pub fn import_stream<R: Read>(&mut self, r: R, progress: impl FnMut(StreamProgress)) -> Result<StreamProgress> {
+        let mut core = self.db.checked()?;
+
+        core.import_tree_stream(&self.name, r, progress)
+    }
+
+    /**
+     * see Db::range
+     */
+    pub fn range<K: AsRef<[u8]> + ?Sized>(&mut self, range: impl RangeBounds<K>) -> Result<DbIter<'_>> {
+        let mut core = self.db.checked()?;
+        let root = core.table(&self.name)?.root;
+
+        DbIter::new(core, root, range)
+    }
+
+    pub fn iter(&mut self) -> Result<DbIter<'_>> {
+        self.range::<[u8]>(..)
+    }
+}
+
+impl Db {
+    /**
+     * creates empty table, fails with Error::TableExists if there is one with this name
+     * tables are trees of their own in the same file, named in catalog and changed under the same log
+     */
+    pub fn create_table(&mut self, name: &str) -> Result<()> {
+        let mut batch = WriteBatch::new();
+
+        batch.create_table(name);
+
+        let mut core = self.checked()?;
+
+        core.commit(&batch, false)?;
+        self.changed(&core);
+
+        Ok(())
+    }
+
+    /**
+     * removes table with all its entries, pages are freed a few at a time by later changes
+     * returns status of operation: did table exist
+     */
+    pub fn drop_table(&mut self, name: &str) -> Result<bool> {
+        let mut core = self.checked()?;
+
+        if core.catalog_get(name.as_bytes())?.is_none() {
+            return Ok(false);
+        }
+
+        let mut batch = WriteBatch::new();
+
+        batch.drop_table(name);
+        core.commit(&batch, false)?;
+        self.changed(&core);
+
+        Ok(true)
+    }
+
+    /**
+     * names of tables in byte order
+     */
+    pub fn tables(&self) -> Result<Vec<String>> {
+        self.checked()?.tables()
+    }
+
+    /**
+     * handle of existing table, fails with Error::TableNotFound if there is none
+     */
+    pub fn table(&mut self, name: &str) -> Result<Table<'_>> {
+        self.checked()?.table(name)?;
+
+        Ok(Table {
+            db: self,
+            name: name.to_string(),
+        })
+    }
+}
+
+impl Core {
+    /**
+     * trees of tables in byte order of their names, dropped trees left out
+     */
+    pub(super) fn named_tables(&mut self) -> Result<Vec<(String, Tree)>> {
+        Ok(self
+            .catalog_entries()?
+            .into_iter()
+            .filter(|(key, _)| key.first() != Some(&GARBAGE))
+            .map(|(key, tree)| (String::from_utf8_lossy(&key).into_owned(), tree))
+            .collect())
+    }
+
+    /**
+     * runs f on tree as if it were the default one, root and len of tree are updated by it
+     * header written by flush inside f keeps default tree, catalog gets tree only after f returns
+     */
+    pub(super) fn with_tree<R>(&mut self, tree: &mut Tree, f: impl FnOnce(&mut Core) -> Result<R>) -> Result<R> {
+        std::mem::swap(&mut self.root, &mut tree.root);
+        std::mem::swap(&mut self.len, &mut tree.len);
+
+        let outermost = self.outer.is_none();
+
+        if outermost {
+            self.outer = Some(*tree);
+        }
+
+        let result = f(self);
+
+        if outermost {
+            self.outer = None;
+        }
+
+        std::mem::swap(&mut self.root, &mut tree.root);
+        std::mem::swap(&mut self.len, &mut tree.len);
+
+        result
+    }
+
+    /**
+     * with_tree over catalog, which is created on first change
+     */
+    fn in_catalog<R>(&mut self, f: impl FnOnce(&mut Core) -> Result<R>) -> Result<R> {
+        if self.catalog.root == HEADER_PAGE {
+            self.catalog.root = self.alloc_node(&Node::leaf(self.t))?;
+            self.header_dirty = true;
+        }
+
+        let mut catalog = self.catalog;
+        let result = self.with_tree(&mut catalog, f);
+
+        if catalog != self.catalog {
+            self.catalog = catalog;
+            self.header_dirty = true;
+        }
+
+        result
+    }
+
+    pub(super) fn catalog_get(&mut self, key: &[u8]) -> Result<Option<Tree>> {
+        if self.catalog.root == HEADER_PAGE {
+            return Ok(None);
+        }
+
+        let mut catalog = self.catalog;
+        let value = self.with_tree(&mut catalog, |core| core.get(key))?;
+
+        value.map(|value| Tree::decode(&value)).transpose()
+    }
+
+    pub(super) fn catalog_put(&mut self, key: &[u8], tree: Tree) -> Result<()> {
+        let entry = Entry {
+            key: key.to_vec(),
+            value: Value::Inline(tree.encode()),
+        };
+
+        self.in_catalog(|core| core.place(entry)).map(|_| ())
+    }
+
+    fn catalog_remove(&mut self, key: &[u8]) -> Result<Option<Tree>> {
+        let removed = self.in_catalog(|core| core.remove_key(key))?;
+
+        match removed {
+            Some(value) => Ok(Some(Tree::decode(&self.load_value(value)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /**
+     * names of tables and dropped trees with trees, in key order
+     */
+    pub(super) fn catalog_entries(&mut self) -> Result<Vec<(Vec<u8>, Tree)>> {
+        if self.catalog.root == HEADER_PAGE {
+            return Ok(vec![]);
+        }
+
+        let mut catalog = self.catalog;
+        let entries = self.with_tree(&mut catalog, Core::entries)?;
+
+        entries
+            .into_iter()
+            .map(|(key, value)| Ok((key, Tree::decode(&value)?)))
+            .collect()
+    }
+
+    pub(super) fn table(&mut self, name: &str) -> Result<Tree> {
+        self.catalog_get(name.as_bytes())?
+            .ok_or_else(|| Error::TableNotFound(name.to_string()))
+    }
+
+    fn tables(&mut self) -> Result<Vec<String>> {
+        Ok(self.named_tables()?.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /**
+     * table ops are skipped when table is missing or already there, check_batch rules that out for new batch,
+     * so replay of batch over pages holding part of it ends in the same state as applying it once
+     */
+    pub(super) fn add_table(&mut self, name: &str) -> Result<bool> {
+        if self.catalog_get(name.as_bytes())?.is_some() {
+            return Ok(false);
+        }
+
+        let root = self.alloc_node(&Node::leaf(self.t))?;
+
+        self.catalog_put(name.as_bytes(), Tree { root, len: 0 })?;
+
+        Ok(true)
+    }
+
+    /**
+     * tree of table moves to garbage of catalog in one step, reclaim frees its pages later
+     */
+    pub(super) fn remove_table(&mut self, name: &str) -> Result<bool> {
+        let Some(tree) = self.catalog_remove(name.as_bytes())? else {
+            return Ok(false);
+        };
+
+        self.catalog_put(&garbage_key(tree.root), tree)?;
+        self.garbage = true;
+
+        Ok(true)
+    }
+
+    /**
+     * value is stored before tree of table is changed, catalog is updated right after it, see with_tree
+     */
+    pub(super) fn put_in(&mut self, table: &str, key: &[u8], value: &[u8]) -> Result<bool> {
+        let Some(mut tree) = self.catalog_get(table.as_bytes())? else {
+            return Ok(false);
+        };
+
+        let entry = Entry {
+            key: key.to_vec(),
+            value: self.store_value(key, value)?,
+        };
+
+        self.reserve()?;
+
+        let before = tree;
+        let old_value = self.with_tree(&mut tree, |core| core.place(entry))?;
+
+        if tree != before {
+            self.catalog_put(table.as_bytes(), tree)?;
+        }
+
+        if let Some(old_value) = old_value {
+            self.free_value(old_value)?;
+        }
+
+        Ok(true)
+    }
+
+    /**
+     * merges on the way down may collapse root of table even if key is absent, so its tree is saved either way
+     */
+    pub(super) fn delete_in(&mut self, table: &str, key: &[u8]) -> Result<bool> {
+        let Some(mut tree) = self.catalog_get(table.as_bytes())? else {
+            return Ok(false);
+        };
+
+        let before = tree;
+        let removed = self.with_tree(&mut tree, |core| core.remove_key(key))?;
+
+        if tree != before {
+            self.catalog_put(table.as_bytes(), tree)?;
+        }
+
+        let Some(value) = removed else {
+            return Ok(false);
+        };
+
+        self.free_value(value)?;
+
+        Ok(true)
+    }
+
+    /**
+     * frees pages of dropped tables step by step, trees in catalog stay whole between steps,
+     * so flush may come between them, crash within step at most leaks pages it was freeing
+     */
+    pub(super) fn reclaim(&mut self, steps: usize) -> Result<()> {
+        for _ in 0..steps {
+            let garbage = self.catalog_entries()?.into_iter().find(|(key, _)| key.first() == Some(&GARBAGE));
+
+            let Some((key, tree)) = garbage else {
+                self.garbage = false;
+
+                return Ok(());
+            };
+
+            self.reserve()?;
+            self.reclaim_step(&key, tree.root)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * frees the leftmost leaf of dropped tree, values of leaf and of delimeter above it go first,
+     * each detached from its node before its chain is freed
+     * parent loses first child and delimeter, one without children turns into empty leaf,
+     * root is freed last together with its catalog entry
+     */
+    fn reclaim_step(&mut self, key: &[u8], root: PageId) -> Result<()> {
+        let mut parent = None;
+        let mut page_id = root;
+        let mut node = self.read_node(page_id)?;
+
+        while !node.leaf {
+            let child = node.children[0];
+
+            parent = Some((page_id, node));
+            page_id = child;
+            node = self.read_node(page_id)?;
+        }
+
+        if let Some(i) = node.keys.iter().position(|entry| !entry.value.is_inline()) {
+            let value = std::mem::replace(&mut node.keys[i].value, Value::Inline(vec![]));
+
+            self.write_node(page_id, &node)?;
+
+            return self.free_value(value);
+        }
+
+        let Some((parent_id, mut parent)) = parent else {
+            self.free_page(page_id)?;
+            self.catalog_remove(key)?;
+
+            return Ok(());
+        };
+
+        if parent.count == 0 {
+            parent.leaf = true;
+        } else if !parent.keys[0].value.is_inline() {
+            let value = std::mem::replace(&mut parent.keys[0].value, Value::Inline(vec![]));
+
+            self.write_node(parent_id, &parent)?;
+
+            return self.free_value(value);
+        } else {
+            parent.keys.remove(0);
+            parent.count -= 1;
+        }
+
+        parent.children.remove(0);
+        self.write_node(parent_id, &parent)?;
+        self.free_page(page_id)
+    }
+
+    /**
+     * name must be non empty and fit into catalog entry
+     */
+    pub(super) fn check_table_name(name: &str) -> Result<()> {
+        if name.is_empty() || ENTRY_HEADER_SIZE + name.len() + OVERFLOW_REF_SIZE > MAX_ENTRY_SIZE {
+            return Err(Error::InvalidTableName(name.to_string()));
+        }
+
+        Ok(())
+    }
+}
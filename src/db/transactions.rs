@@ -0,0 +1,429 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+
+use crate::batch::WriteBatch;
+use crate::error::{Error, Result};
+use crate::page::decode_overflow_page;
+use crate::pager::PageId;
+use crate::Node;
+
+use super::{read_value, Core, Db, DbIter, Entry, Value};
+
+/**
+ * state of database as of Db::begin_read, changes committed after it are not seen, it covers default tree only
+ * its pages stay pinned in file until it is dropped, commits meanwhile do not reuse pages they replace in it,
+ * transactions begun between commits read different versions, see Db::gc, vacuum waits for all of them
+ * database is locked for one page at a time, so writers go on between them,
+ * transaction does not borrow handle and may be sent to another thread
+ */
+pub struct ReadTxn {
+    core: Arc<Mutex<Core>>,
+    pages: Vec<PageId>,
+    version: u64,
+    root: PageId,
+    len: usize,
+    seq: u64,
+}
+
+impl ReadTxn {
+    /**
+     * logical page as of transaction, read from its physical page pinned in file
+     */
+    fn read_page(&self, page_id: PageId, buf: &mut Vec<u8>) -> Result<()> {
+        let physical = *self.pages.get(page_id as usize).ok_or(Error::PageOutOfBounds {
+            page_id,
+            page_count: self.pages.len() as PageId,
+        })?;
+        let mut core = self.core.lock().unwrap_or_else(PoisonError::into_inner);
+
+        buf.resize(core.cache.page_size(), 0);
+        core.cache.read_snapshot(page_id, physical, buf)
+    }
+
+    fn read_node(&self, page_id: PageId) -> Result<Node<Entry>> {
+        let mut buf = vec![];
+
+        self.read_page(page_id, &mut buf)?;
+        Node::from_page(&buf)
+    }
+
+    fn load_value(&self, value: Value) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+
+        read_value(value, &mut |page_id, out| {
+            self.read_page(page_id, &mut buf)?;
+
+            let (next, data) = decode_overflow_page(&buf)?;
+
+            out.extend_from_slice(data);
+
+            Ok(next)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /**
+     * seq of the last batch transaction sees, see Db::seq
+     */
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /**
+     * generation of commit transaction reads, see Db::oldest_pinned_version
+     */
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut page_id = self.root;
+
+        loop {
+            let mut node = self.read_node(page_id)?;
+            let i = node.keys.partition_point(|entry| entry.key.as_slice() < key);
+
+            if i < node.count && node.keys[i].key == key {
+                let value = std::mem::replace(&mut node.keys[i].value, Value::Inline(vec![]));
+
+                return self.load_value(value).map(Some);
+            }
+
+            if node.leaf {
+                return Ok(None);
+            }
+
+            page_id = node.children[i];
+        }
+    }
+
+    /**
+     * value is not loaded, see Db::contains
+     */
+    pub fn contains(&self, key: &[u8]) -> Result<bool> {
+        let mut page_id = self.root;
+
+        loop {
+            let node = self.read_node(page_id)?;
+            let i = node.keys.partition_point(|entry| entry.key.as_slice() < key);
+
+            if i < node.count && node.keys[i].key == key {
+                return Ok(true);
+            }
+
+            if node.leaf {
+                return Ok(false);
+            }
+
+            page_id = node.children[i];
+        }
+    }
+
+    /**
+     * entries with keys in range in key order, see Db::range
+     */
+    pub fn range<K: AsRef<[u8]> + ?Sized>(&self, range: impl RangeBounds<K>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.range_limit(range, usize::MAX)
+    }
+
+    /**
+     * first limit entries of range, nodes beyond them are not read, so range may be taken in chunks
+     */
+    pub fn range_limit<K: AsRef<[u8]> + ?Sized>(
+        &self,
+        range: impl RangeBounds<K>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = vec![];
+
+        if limit > 0 {
+            self.collect(
+                self.root,
+                range.start_bound().map(AsRef::as_ref),
+                range.end_bound().map(AsRef::as_ref),
+                limit,
+                &mut entries,
+            )?;
+        }
+
+        Ok(entries)
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.range::<[u8]>(..)
+    }
+
+    /**
+     * appends entries of subtree from start on to out, returns false once it reaches key beyond end
+     * or out holds limit entries
+     */
+    fn collect(
+        &self,
+        page_id: PageId,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+        limit: usize,
+        out: &mut Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<bool> {
+        let mut node = self.read_node(page_id)?;
+        let first = match start {
+            Bound::Included(start) => node.keys.partition_point(|entry| entry.key.as_slice() < start),
+            Bound::Excluded(start) => node.keys.partition_point(|entry| entry.key.as_slice() <= start),
+            Bound::Unbounded => 0,
+        };
+
+        for i in first..=node.count {
+            if !node.leaf && !self.collect(node.children[i], start, end, limit, out)? {
+                return Ok(false);
+            }
+
+            if i == node.count {
+                break;
+            }
+
+            let Entry { key, value } =
+                std::mem::replace(&mut node.keys[i], Entry { key: vec![], value: Value::Inline(vec![]) });
+            let beyond = match end {
+                Bound::Included(end) => key.as_slice() > end,
+                Bound::Excluded(end) => key.as_slice() >= end,
+                Bound::Unbounded => false,
+            };
+
+            if beyond {
+                return Ok(false);
+            }
+
+            out.push((key, self.load_value(value)?));
+
+            if out.len() == limit {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl Drop for ReadTxn {
+    fn drop(&mut self) {
+        self.core.lock().unwrap_or_else(PoisonError::into_inner).cache.unpin_snapshot(self.version);
+    }
+}
+
+/**
+ * whether write transaction of handle is open, see Db::begin_write
+ */
+#[derive(Debug)]
+pub(super) struct WriterSlot {
+    taken: Mutex<bool>,
+    released: Condvar,
+    wait: bool,
+}
+
+impl WriterSlot {
+    pub(super) fn new() -> WriterSlot {
+        WriterSlot {
+            taken: Mutex::new(false),
+            released: Condvar::new(),
+            wait: true,
+        }
+    }
+}
+
+/**
+ * changes staged by Db::begin_write, kept in transaction until commit and seen by its own reads only
+ * commit applies them as one batch, so readers see none or all of them and crash keeps none or all of them,
+ * transaction dropped without commit, panic unwinding through it included, is rolled back and leaves no trace
+ */
+pub struct WriteTxn<'a> {
+    db: &'a Db,
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl WriteTxn<'_> {
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.changes.get(key) {
+            Some(change) => Ok(change.clone()),
+            None => self.db.checked()?.get(key),
+        }
+    }
+
+    pub fn contains(&self, key: &[u8]) -> Result<bool> {
+        match self.changes.get(key) {
+            Some(change) => Ok(change.is_some()),
+            None => self.db.checked()?.contains(key),
+        }
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        self.changes.insert(key.to_vec(), Some(value.to_vec()));
+    }
+
+    /**
+     * returns status of operation: did element remove
+     */
+    pub fn delete(&mut self, key: &[u8]) -> Result<bool> {
+        let removed = self.contains(key)?;
+
+        self.changes.insert(key.to_vec(), None);
+
+        Ok(removed)
+    }
+
+    /**
+     * entries with keys in range in key order with changes of transaction applied, see Db::range
+     */
+    pub fn range<K: AsRef<[u8]> + ?Sized>(&self, range: impl RangeBounds<K>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let bounds = (range.start_bound().map(AsRef::as_ref), range.end_bound().map(AsRef::as_ref));
+        let mut entries = {
+            let core = self.db.checked()?;
+            let root = core.root;
+
+            DbIter::new::<[u8]>(core, root, bounds)?.collect::<Result<BTreeMap<_, _>>>()?
+        };
+
+        for (key, change) in self.changes.range::<[u8], _>(bounds) {
+            match change {
+                Some(value) => entries.insert(key.clone(), value.clone()),
+                None => entries.remove(key),
+            };
+        }
+
+        Ok(entries.into_iter().collect())
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.range::<[u8]>(..)
+    }
+
+    /**
+     * applies changes as one batch through log of database, see Db::write
+     */
+    pub fn commit(self) -> Result<()> {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = WriteBatch::new();
+
+        for (key, change) in &self.changes {
+            match change {
+                Some(value) => batch.put(key, value),
+                None => batch.delete(key),
+            }
+        }
+
+        let mut core = self.db.checked()?;
+
+        core.write(&batch)?;
+        self.db.changed(&core);
+
+        Ok(())
+    }
+
+    /**
+     * discards changes, same as dropping transaction
+     */
+    pub fn rollback(self) {}
+}
+
+impl Drop for WriteTxn<'_> {
+    fn drop(&mut self) {
+        *self.db.writer.taken.lock().unwrap_or_else(PoisonError::into_inner) = false;
+        self.db.writer.released.notify_one();
+    }
+}
+
+impl Db {
+    /**
+     * read transaction over state of database as of now, see ReadTxn
+     * changes are flushed first, so its snapshot is committed state holding all of them
+     */
+    pub fn begin_read(&self) -> Result<ReadTxn> {
+        let mut core = self.checked()?;
+
+        core.flush()?;
+
+        Ok(ReadTxn {
+            core: Arc::clone(&self.core),
+            pages: core.cache.pin_snapshot(),
+            version: core.cache.generation(),
+            root: core.root,
+            len: core.len,
+            seq: core.seq,
+        })
+    }
+
+    /**
+     * write transaction, see WriteTxn, handle has one open at a time
+     * while another is open it waits for it to end, or fails with Error::WriteTxnActive, see set_wait_for_writer
+     * transaction borrows handle, so nothing writes around it, thread waiting for its own transaction never wakes
+     */
+    pub fn begin_write(&self) -> Result<WriteTxn<'_>> {
+        if self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+
+        let mut taken = self.writer.taken.lock().unwrap_or_else(PoisonError::into_inner);
+
+        while *taken {
+            if !self.writer.wait {
+                return Err(Error::WriteTxnActive);
+            }
+
+            taken = self.writer.released.wait(taken).unwrap_or_else(PoisonError::into_inner);
+        }
+
+        *taken = true;
+
+        Ok(WriteTxn {
+            db: self,
+            changes: BTreeMap::new(),
+        })
+    }
+
+    /**
+     * version of the oldest state read transaction or backup reads, None when there are none
+     * versions are generations of commits, pages replaced since it are kept in file for them, see gc
+     */
+    pub fn oldest_pinned_version(&self) -> Option<u64> {
+        self.core().cache.oldest_snapshot()
+    }
+
+    /**
+     * pages of file kept only for states read transactions and backups still read
+     */
+    pub fn retained_pages(&self) -> usize {
+        self.core().cache.held_pages()
+    }
+
+    /**
+     * makes pages of versions no read transaction or backup reads anymore reusable, returns their number
+     * page is kept while some transaction reads version between commit which wrote it and commit which replaced it,
+     * so several transactions pin different versions at the cost of pages which differ between them only
+     * commit after transaction ends collects them too, gc does not wait for it
+     */
+    pub fn gc(&self) -> usize {
+        self.core().cache.collect_garbage()
+    }
+
+    pub fn wait_for_writer(&self) -> bool {
+        self.writer.wait
+    }
+
+    /**
+     * whether begin_write waits for open write transaction to end, true by default
+     */
+    pub fn set_wait_for_writer(&mut self, wait: bool) {
+        self.writer.wait = wait;
+    }
+}
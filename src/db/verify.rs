@@ -0,0 +1,657 @@
+use std::collections::HashSet;
+use std::fmt::{Debug, Display};
+use std::path::Path;
+
+use crate::batch::WriteBatch;
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::options::SrdbOptions;
+use crate::page::{decode_free_page, decode_overflow_page, OVERFLOW_HEADER_SIZE};
+use crate::pager::{PageId, HEADER_PAGE};
+
+use super::catalog::{Tree, GARBAGE};
+use super::{Core, Db, DiskStats, Entry, SyncMode, Value};
+
+/**
+ * how deep verify goes
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum VerifyMode {
+    /**
+     * checksums of every page, node invariants, overflow chains, free list and page ownership
+     */
+    #[default]
+    Quick,
+    /**
+     * Quick and every value is read back, compressed ones are decompressed
+     */
+    Full,
+}
+
+/**
+ * what page was found to be part of by verify
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PageUse {
+    Header,
+    Node,
+    Overflow,
+    Free,
+}
+
+/**
+ * problem found by verify, page_id is None for problems of database as a whole
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub page_id: Option<PageId>,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.page_id {
+            Some(page_id) => write!(f, "page {}: {}", page_id, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/**
+ * fix verify suggests for problems it found, in order they should be applied
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repair {
+    /**
+     * file ends with part of page, write of it was cut by crash, see Db::trim_torn_page
+     */
+    TrimTornPage,
+    /**
+     * trees are intact, but free list is damaged or pages are lost, see Db::rebuild_free_list
+     */
+    RebuildFreeList,
+    /**
+     * trees are damaged, entries which can still be read are copied into new file, see Db::salvage
+     */
+    Salvage,
+}
+
+/**
+ * result of Db::verify, database is healthy when problems are empty
+ * stats are counted by the walk, pages which could not be read are not in them
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VerifyReport {
+    pub problems: Vec<Problem>,
+    pub repairs: Vec<Repair>,
+    pub stats: DiskStats,
+    pub entries: u64,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn problem(&mut self, page_id: Option<PageId>, message: String) {
+        self.problems.push(Problem { page_id, message });
+    }
+
+    /**
+     * marks page as used for kind, reports page out of bounds and page used twice
+     * returns whether page can be read
+     */
+    fn claim(&mut self, uses: &mut [Option<PageUse>], page_id: PageId, kind: PageUse) -> bool {
+        match uses.get_mut(page_id as usize) {
+            None => self.problem(Some(page_id), format!("{:?} page is out of {} pages", kind, uses.len())),
+            Some(Some(used)) => self.problem(Some(page_id), format!("{:?} page is used as {:?} page already", kind, used)),
+            Some(free) => {
+                *free = Some(kind);
+
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/**
+ * result of Db::salvage, entries and tables copied into new file and pages or values left behind
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SalvageReport {
+    pub entries: u64,
+    pub tables: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl SalvageReport {
+    fn problem(&mut self, page_id: Option<PageId>, message: String) {
+        self.problems.push(Problem { page_id, message });
+    }
+}
+
+/**
+ * entries salvage writes to new file in one batch
+ */
+const SALVAGE_BATCH: usize = 1024;
+
+impl Db {
+    pub fn check_invariants(&mut self) -> Result<()> {
+        self.checked()?.check_invariants()
+    }
+
+    /**
+     * checks every page of database and lists all problems found, see VerifyMode
+     * stats of report are counted by the same walk, disk_stats_exact returns them for healthy file
+     * pages are read through cache, flush first to check file alone
+     */
+    pub fn verify(&self, mode: VerifyMode) -> VerifyReport {
+        self.core().verify(mode)
+    }
+
+    /**
+     * makes every page no tree reaches free again, the fix for Repair::RebuildFreeList
+     * fails with Error::Corrupt if trees are damaged, see salvage then
+     * returns number of free pages
+     */
+    pub fn rebuild_free_list(&mut self) -> Result<u64> {
+        self.checked()?.rebuild_free_list()
+    }
+
+    /**
+     * cuts part of page left at the end of file by crash, the fix for Repair::TrimTornPage
+     * returns number of bytes cut
+     */
+    pub fn trim_torn_page(&mut self) -> Result<u64> {
+        self.checked()?.trim_torn_page()
+    }
+
+    /**
+     * copies entries of default tree and tables which can still be read into new database created at dest,
+     * the fix for Repair::Salvage, nodes and values which can not be read are skipped and listed in report
+     * this database is only read, dest is removed if salvage fails
+     */
+    pub fn salvage(&self, dest: impl AsRef<Path>) -> Result<SalvageReport> {
+        let dest = dest.as_ref();
+        let mut salvaged = SrdbOptions::new().sync_mode(SyncMode::Off).create(dest)?;
+
+        match self.checked()?.salvage(&mut salvaged) {
+            Ok(report) => {
+                salvaged.close()?;
+
+                Ok(report)
+            }
+            Err(error) => {
+                drop(salvaged);
+                Db::remove(dest)?;
+
+                Err(error)
+            }
+        }
+    }
+}
+
+impl Core {
+    /**
+     * walks overflow chain of value, in full mode compressed value is decompressed too
+     */
+    fn verify_value(
+        &mut self,
+        value: &Value,
+        mode: VerifyMode,
+        uses: &mut [Option<PageUse>],
+        report: &mut VerifyReport,
+        node_id: PageId,
+    ) {
+        let inner = match value {
+            Value::Compressed(inner) => inner,
+            value => value,
+        };
+
+        if let Value::Overflow { len, first } = *inner {
+            let mut page_id = first;
+            let mut stored = 0;
+
+            while page_id != HEADER_PAGE {
+                if !report.claim(uses, page_id, PageUse::Overflow) {
+                    return;
+                }
+
+                let (next, data) = match self.cache.read(page_id).and_then(decode_overflow_page) {
+                    Ok((next, data)) => (next, data.len() as u64),
+                    Err(error) => return report.problem(Some(page_id), error.to_string()),
+                };
+
+                stored += data;
+                report.stats.used_bytes = report.stats.used_bytes.map(|used| used + OVERFLOW_HEADER_SIZE as u64 + data);
+                report.stats.overflow_pages = report.stats.overflow_pages.map(|pages| pages + 1);
+                page_id = next;
+            }
+
+            if stored != len {
+                return report.problem(Some(first), format!("overflow chain holds {} bytes, expected {}", stored, len));
+            }
+        }
+
+        if mode == VerifyMode::Full && !value.is_inline() {
+            if let Err(error) = self.load_value(value.clone()) {
+                report.problem(Some(node_id), format!("value can not be read: {}", error));
+            }
+        }
+    }
+
+    /**
+     * walks nodes of tree with their overflow chains, returns number of entries found
+     * dropped tree is partly reclaimed, so only its pages are claimed and node sizes and depths are not checked
+     */
+    fn verify_tree(
+        &mut self,
+        tree: Tree,
+        dropped: bool,
+        mode: VerifyMode,
+        uses: &mut [Option<PageUse>],
+        report: &mut VerifyReport,
+    ) -> u64 {
+        let t = self.t;
+        let mut entries = 0;
+        let mut stack: Vec<(PageId, usize, Option<Entry>, Option<Entry>)> = vec![(tree.root, 0, None, None)];
+        let mut leaf_depth: Option<usize> = None;
+
+        while let Some((page_id, depth, lower, upper)) = stack.pop() {
+            if !report.claim(uses, page_id, PageUse::Node) {
+                continue;
+            }
+
+            let node = match self.read_node(page_id) {
+                Ok(node) => node,
+                Err(error) => {
+                    report.problem(Some(page_id), error.to_string());
+                    continue;
+                }
+            };
+
+            let stats = &mut report.stats;
+
+            stats.node_pages = stats.node_pages.map(|pages| pages + 1);
+            stats.used_bytes = stats.used_bytes.map(|used| used + node.encoded_len() as u64);
+            entries += node.count as u64;
+
+            if !dropped && (node.count > 2 * t - 1 || (page_id != tree.root && node.count < t - 1)) {
+                report.problem(Some(page_id), format!("{} keys is out of bounds", node.count));
+            }
+
+            if node.keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+                report.problem(Some(page_id), format!("keys are not sorted {:?}", &node.keys[..]));
+            }
+
+            let below = lower.as_ref().is_some_and(|lower| node.keys.first().is_some_and(|first| first <= lower));
+            let above = upper.as_ref().is_some_and(|upper| node.keys.last().is_some_and(|last| last >= upper));
+
+            if below || above {
+                report.problem(Some(page_id), format!("keys {:?} are out of delimeters", &node.keys[..]));
+            }
+
+            for entry in node.keys.iter() {
+                self.verify_value(&entry.value, mode, uses, report, page_id);
+            }
+
+            if node.leaf {
+                match leaf_depth {
+                    None => leaf_depth = Some(depth),
+                    Some(expected) if expected != depth && !dropped => {
+                        report.problem(Some(page_id), format!("leaf at depth {}, expected {}", depth, expected));
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            for i in 0..=node.count {
+                let child_lower = if i == 0 { lower.clone() } else { Some(node.keys[i - 1].clone()) };
+                let child_upper = if i == node.count { upper.clone() } else { Some(node.keys[i].clone()) };
+
+                stack.push((node.children[i], depth + 1, child_lower, child_upper));
+            }
+        }
+
+        if !dropped && entries != tree.len as u64 {
+            report.problem(Some(tree.root), format!("tree has {} keys, but len is {}", entries, tree.len));
+        }
+
+        entries
+    }
+
+    /**
+     * walks header, default tree, catalog with trees of tables and dropped ones, overflow chains and free list,
+     * every page must be used exactly once, entries of report are those of default tree
+     * pages are read through cache, so changes not flushed yet are checked as well
+     */
+    pub(super) fn verify(&mut self, mode: VerifyMode) -> VerifyReport {
+        let page_count = self.cache.page_count();
+        let mut uses = vec![None; page_count as usize];
+        let mut report = VerifyReport {
+            problems: vec![],
+            repairs: vec![],
+            stats: self.disk_stats(),
+            entries: 0,
+        };
+
+        report.stats.node_pages = Some(0);
+        report.stats.overflow_pages = Some(0);
+        report.stats.used_bytes = Some(0);
+
+        report.claim(&mut uses, HEADER_PAGE, PageUse::Header);
+
+        if let Err(error) = self.cache.read(HEADER_PAGE).and_then(Header::decode) {
+            report.problem(Some(HEADER_PAGE), error.to_string());
+        }
+
+        report.entries = self.verify_trees(mode, &mut uses, &mut report);
+
+        let tree_problems = report.problems.len();
+        let mut free_pages = 0;
+        let mut page_id = self.free_head;
+
+        while page_id != HEADER_PAGE && report.claim(&mut uses, page_id, PageUse::Free) {
+            match self.cache.read(page_id).and_then(decode_free_page) {
+                Ok(next) => page_id = next,
+                Err(error) => {
+                    report.problem(Some(page_id), error.to_string());
+                    break;
+                }
+            }
+
+            free_pages += 1;
+        }
+
+        if free_pages != self.free_count as u64 {
+            report.problem(None, format!("free list has {} pages, but header counts {}", free_pages, self.free_count));
+        }
+
+        for (page_id, used) in uses.iter().enumerate() {
+            if used.is_none() {
+                report.problem(Some(page_id as PageId), "page is neither reachable nor free".to_string());
+            }
+        }
+
+        let free_problems = report.problems.len() - tree_problems;
+
+        match self.cache.torn_bytes() {
+            Ok(0) => {}
+            Ok(torn) => {
+                report.problem(None, format!("file ends with {} bytes of torn page", torn));
+                report.repairs.push(Repair::TrimTornPage);
+            }
+            Err(error) => report.problem(None, error.to_string()),
+        }
+
+        if tree_problems > 0 {
+            report.repairs.push(Repair::Salvage);
+        } else if free_problems > 0 {
+            report.repairs.push(Repair::RebuildFreeList);
+        }
+
+        let stats = &mut report.stats;
+        let tree_pages = stats.node_pages.unwrap_or(0) + stats.overflow_pages.unwrap_or(0);
+        let used = tree_pages + 1 + self.cache.metadata_pages() as u64;
+
+        stats.free_pages = free_pages;
+        stats.tree_pages = tree_pages;
+        stats.fragmentation = stats.file_pages.saturating_sub(used) as f64 / stats.file_pages as f64;
+
+        report
+    }
+
+    /**
+     * claims pages of default tree, catalog and trees of tables, dropped ones included, returns entries of default tree
+     */
+    fn verify_trees(&mut self, mode: VerifyMode, uses: &mut [Option<PageUse>], report: &mut VerifyReport) -> u64 {
+        let default = Tree {
+            root: self.root,
+            len: self.len,
+        };
+
+        let entries = self.verify_tree(default, false, mode, uses, report);
+
+        if self.catalog.root != HEADER_PAGE {
+            self.verify_tree(self.catalog, false, mode, uses, report);
+
+            match self.catalog_entries() {
+                Ok(tables) => {
+                    for (key, tree) in tables {
+                        self.verify_tree(tree, key.first() == Some(&GARBAGE), mode, uses, report);
+                    }
+                }
+                Err(error) => report.problem(Some(self.catalog.root), format!("catalog can not be read: {}", error)),
+            }
+        }
+
+        entries
+    }
+
+    /**
+     * frees again every page no tree reaches, old free list is not read, so its damage does not matter
+     * trees must be intact, Error::Corrupt names the first problem otherwise
+     * database is checkpointed before and after, so log never replays over the old list
+     * returns number of free pages
+     */
+    fn rebuild_free_list(&mut self) -> Result<u64> {
+        self.checkpoint()?;
+
+        let page_count = self.cache.page_count();
+        let mut uses = vec![None; page_count as usize];
+        let mut report = VerifyReport::default();
+
+        report.claim(&mut uses, HEADER_PAGE, PageUse::Header);
+        self.verify_trees(VerifyMode::Quick, &mut uses, &mut report);
+
+        if let Some(problem) = report.problems.first() {
+            return Err(Error::Corrupt(format!("free list can not be rebuilt over damaged tree, {}", problem)));
+        }
+
+        self.free_head = HEADER_PAGE;
+        self.free_count = 0;
+        self.header_dirty = true;
+
+        for page_id in (0..page_count).rev() {
+            if uses[page_id as usize].is_none() {
+                self.free_page(page_id)?;
+            }
+        }
+
+        self.checkpoint()?;
+
+        Ok(self.free_count as u64)
+    }
+
+    /**
+     * cuts bytes after the last whole page of file, see Repair::TrimTornPage
+     */
+    fn trim_torn_page(&mut self) -> Result<u64> {
+        self.wal()?;
+        self.cache.trim()
+    }
+
+    /**
+     * copies entries of default tree and tables which can still be read into dest, see Db::salvage
+     */
+    fn salvage(&mut self, dest: &mut Db) -> Result<SalvageReport> {
+        let mut report = SalvageReport::default();
+        let root = self.root;
+
+        self.salvage_tree(root, None, dest, &mut report)?;
+
+        if self.catalog.root != HEADER_PAGE {
+            match self.named_tables() {
+                Ok(tables) => {
+                    for (name, tree) in tables {
+                        dest.create_table(&name)?;
+                        report.tables += 1;
+                        self.salvage_tree(tree.root, Some(&name), dest, &mut report)?;
+                    }
+                }
+                Err(error) => report.problem(Some(self.catalog.root), format!("catalog can not be read: {}", error)),
+            }
+        }
+
+        report.entries = dest.len() as u64;
+
+        for name in dest.tables()? {
+            report.entries += dest.table(&name)?.len()? as u64;
+        }
+
+        Ok(report)
+    }
+
+    /**
+     * walks every node reachable from root, nodes and values which can not be read are skipped and reported,
+     * entries of the rest are written to dest in batches, errors of dest stop the walk
+     */
+    fn salvage_tree(
+        &mut self,
+        root: PageId,
+        table: Option<&str>,
+        dest: &mut Db,
+        report: &mut SalvageReport,
+    ) -> Result<()> {
+        let mut stack = vec![root];
+        let mut seen = HashSet::new();
+        let mut batch = WriteBatch::new();
+
+        while let Some(page_id) = stack.pop() {
+            if !seen.insert(page_id) {
+                report.problem(Some(page_id), "node is reached twice".to_string());
+                continue;
+            }
+
+            let node = match self.read_node(page_id) {
+                Ok(node) => node,
+                Err(error) => {
+                    report.problem(Some(page_id), error.to_string());
+                    continue;
+                }
+            };
+
+            for Entry { key, value } in node.keys.iter().cloned() {
+                let value = match Core::check_key(&key).and_then(|_| self.load_value(value)) {
+                    Ok(value) => value,
+                    Err(error) => {
+                        report.problem(Some(page_id), format!("key {:?}: {}", String::from_utf8_lossy(&key), error));
+                        continue;
+                    }
+                };
+
+                match table {
+                    Some(table) => batch.put_in(table, &key, &value),
+                    None => batch.put(&key, &value),
+                }
+
+                if batch.len() == SALVAGE_BATCH {
+                    dest.write(&batch)?;
+                    batch.clear();
+                }
+            }
+
+            if !node.leaf {
+                stack.extend(node.children.iter().take(node.count + 1).rev());
+            }
+        }
+
+        if !batch.is_empty() {
+            dest.write(&batch)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * checks default tree, catalog and trees of tables, see check_tree
+     */
+    fn check_invariants(&mut self) -> Result<()> {
+        self.check_tree()?;
+
+        if self.catalog.root == HEADER_PAGE {
+            return Ok(());
+        }
+
+        let mut catalog = self.catalog;
+
+        self.with_tree(&mut catalog, Core::check_tree)?;
+
+        for (key, mut tree) in self.catalog_entries()? {
+            if key.first() != Some(&GARBAGE) {
+                self.with_tree(&mut tree, Core::check_tree)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * checks key order, bounds from delimeters, node sizes, equal leaf depth, len
+     * and that overflow chains hold exactly their values
+     * violation is reported as Error::Corrupt
+     */
+    fn check_tree(&mut self) -> Result<()> {
+        let t = self.t;
+        let mut stack: Vec<(PageId, usize, Option<Entry>, Option<Entry>)> = vec![(self.root, 0, None, None)];
+        let mut leaf_depth: Option<usize> = None;
+        let mut total = 0;
+
+        while let Some((page_id, depth, lower, upper)) = stack.pop() {
+            let node = self.read_node(page_id)?;
+            let is_root = page_id == self.root;
+
+            if node.count > 2 * t - 1 || (!is_root && node.count < t - 1) {
+                return Err(Error::Corrupt(format!("page {}: {} keys is out of bounds", page_id, node.count)));
+            }
+
+            if node.keys.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(Error::Corrupt(format!("page {}: keys are not sorted {:?}", page_id, &node.keys[..])));
+            }
+
+            let below = lower.as_ref().is_some_and(|lower| node.keys.first().is_some_and(|first| first <= lower));
+            let above = upper.as_ref().is_some_and(|upper| node.keys.last().is_some_and(|last| last >= upper));
+
+            if below || above {
+                return Err(Error::Corrupt(format!("page {}: keys {:?} are out of delimeters", page_id, &node.keys[..])));
+            }
+
+            total += node.count;
+
+            for entry in node.keys.iter() {
+                if !entry.value.is_inline() {
+                    self.load_value(entry.value.clone())?;
+                }
+            }
+
+            if node.leaf {
+                match leaf_depth {
+                    None => leaf_depth = Some(depth),
+                    Some(expected) if expected != depth => {
+                        return Err(Error::Corrupt(format!("leaf page {} at depth {}, expected {}", page_id, depth, expected)));
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            for i in 0..=node.count {
+                let child_lower = if i == 0 { lower.clone() } else { Some(node.keys[i - 1].clone()) };
+                let child_upper = if i == node.count { upper.clone() } else { Some(node.keys[i].clone()) };
+
+                stack.push((node.children[i], depth + 1, child_lower, child_upper));
+            }
+        }
+
+        if total != self.len {
+            return Err(Error::Corrupt(format!("tree has {} keys, but len is {}", total, self.len)));
+        }
+
+        Ok(())
+    }
+}
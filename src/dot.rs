@@ -0,0 +1,100 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use core::fmt::{Debug, Write};
+
+use crate::{BTree, Comparator, NodeLayout};
+
+/**
+ * escapes characters with meaning inside record label
+ */
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '{' | '}' | '|' | '<' | '>' | '"' | '\\' | ' ' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    /**
+     * structure of tree as graphviz digraph, see to_dot_limited
+     */
+    pub fn to_dot(&self) -> String {
+        self.to_dot_limited(usize::MAX)
+    }
+
+    /**
+     * structure of tree as graphviz digraph, `dot -Tpng` draws it
+     * node is record of its keys written with Debug and ports c0..cN between them, edge goes from port to child
+     * nodes are named n0, n1, ... in preorder, so names are stable within one export
+     * at most max_nodes nodes are drawn, children left out are replaced by one "..." node under their parent
+     */
+    pub fn to_dot_limited(&self, max_nodes: usize) -> String {
+        let mut out = String::from("digraph btree {\n    node [shape=record];\n");
+        let mut stack = vec![(self.root, None)];
+        let mut drawn = 0;
+        let mut cut = vec![];
+
+        while let Some((id, parent)) = stack.pop() {
+            if drawn == max_nodes {
+                if let Some((parent, _)) = parent {
+                    if cut.last() != Some(&parent) {
+                        cut.push(parent);
+                    }
+                }
+
+                continue;
+            }
+
+            let node = self.node(id);
+            let name = drawn;
+            let mut label = String::new();
+
+            drawn += 1;
+
+            for (i, key) in node.keys.iter().enumerate() {
+                if !node.leaf {
+                    write!(label, "<c{}>|", i).unwrap();
+                }
+
+                write!(label, "{}|", escape(&format!("{:?}", key))).unwrap();
+            }
+
+            if node.leaf {
+                label.pop();
+            } else {
+                write!(label, "<c{}>", node.keys.len()).unwrap();
+            }
+
+            writeln!(out, "    n{} [label=\"{}\"];", name, label).unwrap();
+
+            if let Some((parent, port)) = parent {
+                writeln!(out, "    n{}:c{} -> n{};", parent, port, name).unwrap();
+            }
+
+            stack.extend(node.children.iter().enumerate().rev().map(|(i, child)| (*child, Some((name, i)))));
+        }
+
+        cut.sort_unstable();
+        cut.dedup();
+
+        for parent in cut {
+            writeln!(out, "    e{} [label=\"...\", shape=plaintext];", parent).unwrap();
+            writeln!(out, "    n{} -> e{} [style=dashed];", parent, parent).unwrap();
+        }
+
+        out.push_str("}\n");
+
+        out
+    }
+}
@@ -0,0 +1,201 @@
+use std::io::{self, Read, Write};
+
+use crate::error::{Error, Result};
+use crate::snapshot::Checksummed;
+
+/**
+ * dump layout, integers are little-endian whatever machine writes it:
+ * magic (8 bytes), version (u32),
+ * table count (u32), table names as (length u32, utf-8 bytes) in byte order,
+ * then default tree and every table in the same order, each as entry count (u64)
+ * followed by entries in ascending key order as (key length u32, key, value length u32, value),
+ * crc32 of everything before it (u32)
+ * nothing in it depends on page size, branching factor, compression or format version of file
+ */
+const MAGIC: &[u8; 8] = b"SRDBDUMP";
+const VERSION: u32 = 1;
+
+/**
+ * writes dump part by part, caller keeps order of layout
+ */
+pub(crate) struct DumpWriter<W: Write> {
+    out: Checksummed<W>,
+    bytes: u64,
+}
+
+impl<W: Write> DumpWriter<W> {
+    pub(crate) fn new(w: W, tables: &[String]) -> Result<DumpWriter<W>> {
+        let mut writer = DumpWriter {
+            out: Checksummed::new(w),
+            bytes: 0,
+        };
+
+        writer.write(MAGIC)?;
+        writer.write(&VERSION.to_le_bytes())?;
+        writer.write(&(tables.len() as u32).to_le_bytes())?;
+
+        for name in tables {
+            writer.write(&(name.len() as u32).to_le_bytes())?;
+            writer.write(name.as_bytes())?;
+        }
+
+        Ok(writer)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.out.write_all(bytes)?;
+        self.bytes += bytes.len() as u64;
+
+        Ok(())
+    }
+
+    /**
+     * number of bytes written so far
+     */
+    pub(crate) fn position(&self) -> u64 {
+        self.bytes
+    }
+
+    pub(crate) fn tree(&mut self, len: usize) -> Result<()> {
+        self.write(&(len as u64).to_le_bytes())
+    }
+
+    pub(crate) fn entry(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.write(&(key.len() as u32).to_le_bytes())?;
+        self.write(key)?;
+        self.write(&(value.len() as u32).to_le_bytes())?;
+        self.write(value)
+    }
+
+    pub(crate) fn finish(&mut self) -> Result<()> {
+        let checksum = self.out.crc.finish();
+
+        self.out.inner.write_all(&checksum.to_le_bytes())?;
+        self.out.inner.flush()?;
+        self.bytes += 4;
+
+        Ok(())
+    }
+}
+
+/**
+ * reads dump part by part in order of layout, checks key order and checksum
+ */
+pub(crate) struct DumpReader<R: Read> {
+    input: Checksummed<R>,
+    last: Option<Vec<u8>>,
+    bytes: u64,
+}
+
+/**
+ * dump ending early is corrupt, not an i/o failure
+ */
+fn read_error(error: io::Error) -> Error {
+    match error.kind() {
+        io::ErrorKind::UnexpectedEof => Error::CorruptDump("it is truncated".to_string()),
+        _ => Error::Io(error),
+    }
+}
+
+impl<R: Read> DumpReader<R> {
+    /**
+     * checks magic and version and returns reader with names of tables
+     */
+    pub(crate) fn new(r: R) -> Result<(DumpReader<R>, Vec<String>)> {
+        let mut input = Checksummed::new(r);
+
+        if &input.read_exact::<8>().map_err(read_error)? != MAGIC {
+            return Err(Error::CorruptDump("wrong magic bytes".to_string()));
+        }
+
+        let version = input.read_u32().map_err(read_error)?;
+
+        if version != VERSION {
+            return Err(Error::CorruptDump(format!("unsupported version {}, expected {}", version, VERSION)));
+        }
+
+        let count = input.read_u32().map_err(read_error)?;
+        let mut reader = DumpReader {
+            input,
+            last: None,
+            bytes: (MAGIC.len() + 8) as u64,
+        };
+        let mut tables = vec![];
+
+        for _ in 0..count {
+            let name = reader.bytes()?;
+            let name = String::from_utf8(name).map_err(|_| Error::CorruptDump("table name is not utf-8".to_string()))?;
+
+            if tables.last().is_some_and(|last| *last >= name) {
+                return Err(Error::CorruptDump(format!("table {:?} is out of order", name)));
+            }
+
+            tables.push(name);
+        }
+
+        Ok((reader, tables))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.input.read_u32().map_err(read_error)?;
+        let mut buf = vec![];
+
+        self.input.read_bytes(len as u64, &mut buf).map_err(read_error)?;
+        self.bytes += 4 + len as u64;
+
+        Ok(buf)
+    }
+
+    /**
+     * number of bytes read so far
+     */
+    pub(crate) fn position(&self) -> u64 {
+        self.bytes
+    }
+
+    /**
+     * entry count of the next tree
+     */
+    pub(crate) fn tree(&mut self) -> Result<usize> {
+        self.last = None;
+        self.bytes += 8;
+
+        Ok(self.input.read_u64().map_err(read_error)? as usize)
+    }
+
+    pub(crate) fn entry(&mut self) -> Result<(Vec<u8>, Vec<u8>)> {
+        let key = self.bytes()?;
+
+        if self.last.as_ref().is_some_and(|last| *last >= key) {
+            return Err(Error::CorruptDump(format!("key {:?} is out of order", String::from_utf8_lossy(&key))));
+        }
+
+        let value = self.bytes()?;
+
+        self.last = Some(key.clone());
+
+        Ok((key, value))
+    }
+
+    /**
+     * checks checksum, bytes after it are left unread
+     */
+    pub(crate) fn finish(&mut self) -> Result<()> {
+        let actual = self.input.crc.finish();
+        let mut stored = [0u8; 4];
+
+        self.input.inner.read_exact(&mut stored).map_err(read_error)?;
+        self.bytes += 4;
+
+        let expected = u32::from_le_bytes(stored);
+
+        if expected != actual {
+            return Err(Error::CorruptDump(format!(
+                "checksum mismatch: stored {:08x}, computed {:08x}",
+                expected, actual
+            )));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+#[cfg(not(feature = "encryption"))]
+use std::convert::Infallible;
+use std::fmt::{self, Debug};
+
+#[cfg(feature = "encryption")]
+use aes_gcm::aead::rand_core::RngCore;
+#[cfg(feature = "encryption")]
+use aes_gcm::aead::{AeadInPlace, KeyInit, OsRng};
+#[cfg(feature = "encryption")]
+use aes_gcm::{Aes256Gcm, Nonce, Tag};
+#[cfg(feature = "encryption")]
+use hkdf::Hkdf;
+#[cfg(feature = "encryption")]
+use sha2::Sha256;
+
+use crate::error::{Error, Result};
+
+/**
+ * sealed data is followed by nonce and tag of AES-256-GCM
+ */
+pub const NONCE_SIZE: usize = 12;
+pub const TAG_SIZE: usize = 16;
+pub const SEAL_SIZE: usize = NONCE_SIZE + TAG_SIZE;
+
+/**
+ * salt is random per file, key check is derived from key and salt, both are kept in superblock in plaintext
+ */
+pub const SALT_SIZE: usize = 16;
+pub const CHECK_SIZE: usize = 16;
+
+/**
+ * 256 bit key file is encrypted with, it is never stored, see SrdbOptions::encryption_key
+ */
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key(pub(crate) [u8; 32]);
+
+impl Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key(..)")
+    }
+}
+
+/**
+ * AES-256-GCM under key derived from user key and salt of file with HKDF-SHA256
+ * every seal takes fresh random nonce, so page rewritten in place of old one never repeats it
+ * built without encryption feature it can not be made, files with cipher then fail with Error::KeyRequired
+ */
+#[derive(Clone)]
+pub struct Cipher {
+    #[cfg(feature = "encryption")]
+    aead: Aes256Gcm,
+    #[cfg(not(feature = "encryption"))]
+    never: Infallible,
+}
+
+impl Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cipher(AES-256-GCM)")
+    }
+}
+
+impl Cipher {
+    /**
+     * cipher of new file with its random salt and key check
+     */
+    #[cfg(feature = "encryption")]
+    pub fn create(key: &Key) -> Result<(Cipher, [u8; SALT_SIZE], [u8; CHECK_SIZE])> {
+        let mut salt = [0; SALT_SIZE];
+
+        OsRng.fill_bytes(&mut salt);
+
+        let (cipher, check) = Cipher::derive(key, &salt);
+
+        Ok((cipher, salt, check))
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn create(_key: &Key) -> Result<(Cipher, [u8; SALT_SIZE], [u8; CHECK_SIZE])> {
+        Err(Error::InvalidOptions("encryption needs encryption feature".to_string()))
+    }
+
+    /**
+     * cipher of existing file, key whose check differs from stored one is Error::WrongKey
+     */
+    #[cfg(feature = "encryption")]
+    pub fn open(key: &Key, salt: &[u8; SALT_SIZE], check: &[u8; CHECK_SIZE]) -> Result<Cipher> {
+        let (cipher, derived) = Cipher::derive(key, salt);
+
+        if derived != *check {
+            return Err(Error::WrongKey);
+        }
+
+        Ok(cipher)
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn open(_key: &Key, _salt: &[u8; SALT_SIZE], _check: &[u8; CHECK_SIZE]) -> Result<Cipher> {
+        Err(Error::KeyRequired)
+    }
+
+    #[cfg(feature = "encryption")]
+    fn derive(key: &Key, salt: &[u8; SALT_SIZE]) -> (Cipher, [u8; CHECK_SIZE]) {
+        let hkdf = Hkdf::<Sha256>::new(Some(salt), &key.0);
+        let mut aead_key = [0; 32];
+        let mut check = [0; CHECK_SIZE];
+
+        hkdf.expand(b"srdb page key", &mut aead_key).unwrap();
+        hkdf.expand(b"srdb key check", &mut check).unwrap();
+
+        let cipher = Cipher {
+            aead: Aes256Gcm::new(&aead_key.into()),
+        };
+
+        (cipher, check)
+    }
+
+    /**
+     * appends data encrypted, then nonce and tag, aad is authenticated along, but not stored
+     */
+    #[cfg(feature = "encryption")]
+    pub fn seal(&self, aad: &[u8], data: &[u8], out: &mut Vec<u8>) {
+        let mut nonce = [0; NONCE_SIZE];
+
+        OsRng.fill_bytes(&mut nonce);
+
+        let start = out.len();
+
+        out.extend_from_slice(data);
+
+        let tag = self
+            .aead
+            .encrypt_in_place_detached(Nonce::from_slice(&nonce), aad, &mut out[start..])
+            .expect("data fits into one message");
+
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&tag);
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn seal(&self, _aad: &[u8], _data: &[u8], _out: &mut Vec<u8>) {
+        match self.never {}
+    }
+
+    /**
+     * decrypts sealed data into out, which is SEAL_SIZE bytes shorter,
+     * returns false if data, nonce, tag or aad were changed since seal
+     */
+    #[cfg(feature = "encryption")]
+    pub fn unseal(&self, aad: &[u8], sealed: &[u8], out: &mut [u8]) -> bool {
+        let (data, trailer) = sealed.split_at(sealed.len() - SEAL_SIZE);
+        let (nonce, tag) = trailer.split_at(NONCE_SIZE);
+
+        out.copy_from_slice(data);
+
+        self.aead
+            .decrypt_in_place_detached(Nonce::from_slice(nonce), aad, out, Tag::from_slice(tag))
+            .is_ok()
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn unseal(&self, _aad: &[u8], _sealed: &[u8], _out: &mut [u8]) -> bool {
+        match self.never {}
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt::Display;
+use std::io;
+
+use crate::pager::{PageId, FORMAT_VERSION};
+
+/**
+ * errors of disk engine
+ */
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    PageOutOfBounds { page_id: PageId, page_count: PageId },
+    ReservedPage(PageId),
+    Corrupt(String),
+    KeyTooLarge { key: String, size: usize, available: usize },
+    PagePinned(PageId),
+    CacheFull(usize),
+    WrongMagic,
+    UnsupportedVersion(u32),
+    CorruptHeader(String),
+    PageSizeMismatch { expected: usize, found: usize },
+    ReadOnly,
+    RecoveryNeeded,
+    DatabaseLocked,
+    ChecksumMismatch { page_id: PageId },
+    InvalidOptions(String),
+    NotEmpty(usize),
+    TableExists(String),
+    TableNotFound(String),
+    InvalidTableName(String),
+    CorruptDump(String),
+    InvalidBackup(String),
+    Unsorted(String),
+    ResyncNeeded(String),
+    ReplicationGap { expected: u64, found: u64 },
+    WriteTxnActive,
+    KeyRequired,
+    WrongKey,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "i/o error: {}", error),
+            Error::PageOutOfBounds { page_id, page_count } => {
+                write!(f, "page {} is out of bounds, file has {} pages", page_id, page_count)
+            }
+            Error::ReservedPage(page_id) => write!(f, "page {} is reserved", page_id),
+            Error::Corrupt(reason) => write!(f, "database is corrupt: {}", reason),
+            Error::KeyTooLarge { key, size, available } => {
+                write!(f, "key {} takes {} bytes, but only {} are left in page", key, size, available)
+            }
+            Error::PagePinned(page_id) => write!(f, "page {} is pinned", page_id),
+            Error::CacheFull(capacity) => write!(f, "none of {} cached pages can be evicted", capacity),
+            Error::WrongMagic => write!(f, "not a srdb database: wrong magic bytes"),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "unsupported database format version {}, expected {}", version, FORMAT_VERSION)
+            }
+            Error::CorruptHeader(reason) => write!(f, "database header is corrupt: {}", reason),
+            Error::PageSizeMismatch { expected, found } => {
+                write!(f, "database has page size {}, expected {}", found, expected)
+            }
+            Error::ReadOnly => write!(f, "database is opened read only"),
+            Error::RecoveryNeeded => write!(f, "write ahead log holds changes missing from file, open it for writing first"),
+            Error::DatabaseLocked => write!(f, "database is locked by another handle"),
+            Error::ChecksumMismatch { page_id } => write!(f, "page {} fails checksum", page_id),
+            Error::InvalidOptions(reason) => write!(f, "invalid options: {}", reason),
+            Error::NotEmpty(len) => write!(f, "database holds {} entries, expected none", len),
+            Error::TableExists(name) => write!(f, "table {:?} already exists", name),
+            Error::TableNotFound(name) => write!(f, "table {:?} does not exist", name),
+            Error::InvalidTableName(name) => write!(f, "{:?} is not a valid table name", name),
+            Error::CorruptDump(reason) => write!(f, "dump is corrupt: {}", reason),
+            Error::InvalidBackup(reason) => write!(f, "backup can not be used: {}", reason),
+            Error::Unsorted(key) => write!(f, "key {} is not above the key before it", key),
+            Error::ResyncNeeded(reason) => write!(f, "need full resync: {}", reason),
+            Error::ReplicationGap { expected, found } => {
+                write!(f, "replicated batch {} does not follow, expected {}", found, expected)
+            }
+            Error::WriteTxnActive => write!(f, "another write transaction is open"),
+            Error::KeyRequired => write!(f, "database is encrypted, it opens only with its key and encryption feature"),
+            Error::WrongKey => write!(f, "key is not the one database is encrypted with"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
@@ -0,0 +1,207 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::storage::Storage;
+
+#[derive(Debug, Default)]
+struct MemFile {
+    data: Vec<u8>,
+    synced: Vec<u8>,
+}
+
+/**
+ * storage kept in memory, clones share the same file
+ * crash drops everything written after the last sync, like power loss does
+ */
+#[derive(Clone, Debug, Default)]
+pub struct MemStorage {
+    file: Arc<Mutex<MemFile>>,
+}
+
+impl MemStorage {
+    pub fn new() -> MemStorage {
+        MemStorage::default()
+    }
+
+    pub fn crash(&self) {
+        let mut file = self.file.lock().unwrap();
+
+        file.data = file.synced.clone();
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.file.lock().unwrap().data.clone()
+    }
+}
+
+impl Storage for MemStorage {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.file.lock().unwrap().data.len() as u64)
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.file.lock().unwrap().data.resize(len as usize, 0);
+
+        Ok(())
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let file = self.file.lock().unwrap();
+        let start = offset as usize;
+
+        match file.data.get(start..start + buf.len()) {
+            Some(bytes) => {
+                buf.copy_from_slice(bytes);
+
+                Ok(())
+            }
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        let start = offset as usize;
+
+        if file.data.len() < start + buf.len() {
+            file.data.resize(start + buf.len(), 0);
+        }
+
+        file.data[start..start + buf.len()].copy_from_slice(buf);
+
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+
+        file.synced = file.data.clone();
+
+        Ok(())
+    }
+}
+
+/**
+ * budget of storage operations shared by several FaultyStorage, clones share it
+ * once budget is spent every operation fails, as if process was killed at that point
+ * first write failing may be torn, leaving some leading bytes of its buffer written
+ */
+#[derive(Clone, Debug)]
+pub struct Faults {
+    remaining: Arc<AtomicU64>,
+    done: Arc<AtomicU64>,
+    torn: Arc<AtomicUsize>,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Faults {
+            remaining: Arc::new(AtomicU64::new(u64::MAX)),
+            done: Arc::new(AtomicU64::new(0)),
+            torn: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl Faults {
+    pub fn new() -> Faults {
+        Faults::default()
+    }
+
+    /**
+     * lets next n operations succeed and fails all after them
+     */
+    pub fn fail_after(&self, n: u64) {
+        self.remaining.store(n, Ordering::SeqCst);
+    }
+
+    /**
+     * first write failing once budget is spent still writes up to bytes of its buffer, like power cut midway does
+     * with 0 failing writes leave storage untouched
+     */
+    pub fn tear_write(&self, bytes: usize) {
+        self.torn.store(bytes, Ordering::SeqCst);
+    }
+
+    /**
+     * number of operations which succeeded
+     */
+    pub fn done(&self) -> u64 {
+        self.done.load(Ordering::SeqCst)
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.remaining.load(Ordering::SeqCst) == 0
+    }
+
+    fn take(&self) -> io::Result<()> {
+        let taken = self.remaining.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |remaining| remaining.checked_sub(1));
+
+        if taken.is_err() {
+            return Err(io::Error::other("injected fault"));
+        }
+
+        self.done.fetch_add(1, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /**
+     * bytes torn write leaves, only the first failing write is torn
+     */
+    fn take_torn(&self) -> usize {
+        self.torn.swap(0, Ordering::SeqCst)
+    }
+}
+
+/**
+ * passes operations to inner storage while faults budget lasts
+ */
+#[derive(Debug)]
+pub struct FaultyStorage<S> {
+    inner: S,
+    faults: Faults,
+}
+
+impl<S: Storage> FaultyStorage<S> {
+    pub fn new(inner: S, faults: Faults) -> FaultyStorage<S> {
+        FaultyStorage { inner, faults }
+    }
+}
+
+impl<S: Storage> Storage for FaultyStorage<S> {
+    fn len(&self) -> io::Result<u64> {
+        self.faults.take()?;
+        self.inner.len()
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.faults.take()?;
+        self.inner.set_len(len)
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.faults.take()?;
+        self.inner.read_at(offset, buf)
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        if let Err(error) = self.faults.take() {
+            let torn = self.faults.take_torn().min(buf.len());
+
+            if torn > 0 {
+                self.inner.write_at(offset, &buf[..torn])?;
+            }
+
+            return Err(error);
+        }
+
+        self.inner.write_at(offset, buf)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.faults.take()?;
+        self.inner.sync()
+    }
+}
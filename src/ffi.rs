@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::ffi::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::BTree;
+
+/**
+ * C interface to BTree<i64>, include/srdb.h is generated from this file by cbindgen of ffi/build.rs,
+ * after changes here: SRDB_UPDATE_HEADER=1 cargo build -p srdb-ffi
+ * shared library is crate srdb-ffi in ffi directory: cargo build --release -p srdb-ffi
+ * pointers given to C are only keys of registries below, they are never dereferenced,
+ * so null, freed or foreign pointer gets SRDB_INVALID instead of undefined behaviour,
+ * unless memory of freed object was given to a new one meanwhile
+ * every call holds registry lock, so calls from several threads are safe and run one by one
+ * panic inside call is caught and reported as SRDB_PANIC
+ */
+pub struct SrdbHandle {
+    tree: BTree<i64>,
+}
+
+/**
+ * ordered walk over keys of handle as of srdb_iter_new, later changes of tree are not seen
+ */
+pub struct SrdbIter {
+    keys: Vec<i64>,
+    next: usize,
+}
+
+pub const SRDB_OK: c_int = 0;
+pub const SRDB_NULL: c_int = -1;
+pub const SRDB_INVALID: c_int = -2;
+pub const SRDB_PANIC: c_int = -3;
+
+static HANDLES: Mutex<BTreeMap<usize, Box<SrdbHandle>>> = Mutex::new(BTreeMap::new());
+static ITERS: Mutex<BTreeMap<usize, Box<SrdbIter>>> = Mutex::new(BTreeMap::new());
+
+fn lock<T>(registry: &'static Mutex<T>) -> MutexGuard<'static, T> {
+    registry.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/**
+ * runs f with object of registry ptr points to, error code if there is none or f panics
+ */
+fn with<T, R>(
+    registry: &'static Mutex<BTreeMap<usize, Box<T>>>,
+    ptr: *const T,
+    f: impl FnOnce(&mut T) -> R,
+) -> Result<R, c_int> {
+    if ptr.is_null() {
+        return Err(SRDB_NULL);
+    }
+
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut objects = lock(registry);
+        let object = objects.get_mut(&(ptr as usize)).ok_or(SRDB_INVALID)?;
+
+        Ok(f(object))
+    }))
+    .unwrap_or(Err(SRDB_PANIC))
+}
+
+/**
+ * adds object to registry, its address is its handle
+ */
+fn register<T>(registry: &'static Mutex<BTreeMap<usize, Box<T>>>, object: T) -> *mut T {
+    let mut object = Box::new(object);
+    let ptr: *mut T = &mut *object;
+
+    lock(registry).insert(ptr as usize, object);
+
+    ptr
+}
+
+fn unregister<T>(registry: &'static Mutex<BTreeMap<usize, Box<T>>>, ptr: *mut T) -> c_int {
+    if ptr.is_null() {
+        return SRDB_NULL;
+    }
+
+    let object = panic::catch_unwind(AssertUnwindSafe(|| lock(registry).remove(&(ptr as usize))));
+
+    match object {
+        Ok(Some(object)) => {
+            drop(object);
+
+            SRDB_OK
+        }
+        Ok(None) => SRDB_INVALID,
+        Err(_) => SRDB_PANIC,
+    }
+}
+
+fn status(result: Result<bool, c_int>) -> c_int {
+    result.map_or_else(|code| code, c_int::from)
+}
+
+/**
+ * new empty tree, null if t < 2
+ */
+#[no_mangle]
+pub extern "C" fn srdb_new(t: usize) -> *mut SrdbHandle {
+    if t < 2 {
+        return std::ptr::null_mut();
+    }
+
+    panic::catch_unwind(|| register(&HANDLES, SrdbHandle { tree: BTree::new(t) })).unwrap_or(std::ptr::null_mut())
+}
+
+/**
+ * SRDB_OK or error code, handle is invalid after it
+ */
+#[no_mangle]
+pub extern "C" fn srdb_free(handle: *mut SrdbHandle) -> c_int {
+    unregister(&HANDLES, handle)
+}
+
+/**
+ * SRDB_OK or error code, duplicates are kept
+ */
+#[no_mangle]
+pub extern "C" fn srdb_insert(handle: *mut SrdbHandle, key: i64) -> c_int {
+    with(&HANDLES, handle, |handle| handle.tree.insert(key)).map_or_else(|code| code, |_| SRDB_OK)
+}
+
+/**
+ * 1 if key is in tree, 0 if not, or error code
+ */
+#[no_mangle]
+pub extern "C" fn srdb_contains(handle: *const SrdbHandle, key: i64) -> c_int {
+    status(with(&HANDLES, handle, |handle| handle.tree.contains(key)))
+}
+
+/**
+ * removes one occurrence of key, 1 if it was there, 0 if not, or error code
+ */
+#[no_mangle]
+pub extern "C" fn srdb_delete(handle: *mut SrdbHandle, key: i64) -> c_int {
+    status(with(&HANDLES, handle, |handle| handle.tree.delete(&key)))
+}
+
+/**
+ * number of keys or error code
+ */
+#[no_mangle]
+pub extern "C" fn srdb_len(handle: *const SrdbHandle) -> i64 {
+    with(&HANDLES, handle, |handle| handle.tree.len() as i64).unwrap_or_else(i64::from)
+}
+
+/**
+ * iterator at the smallest key, null if handle is invalid
+ * it does not borrow tree, so it may outlive handle and must be freed by srdb_iter_free
+ */
+#[no_mangle]
+pub extern "C" fn srdb_iter_new(handle: *const SrdbHandle) -> *mut SrdbIter {
+    match with(&HANDLES, handle, |handle| handle.tree.to_vec()) {
+        Ok(keys) => register(&ITERS, SrdbIter { keys, next: 0 }),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/**
+ * 1 if iterator is at key, 0 if it is past the last one, or error code
+ */
+#[no_mangle]
+pub extern "C" fn srdb_iter_valid(iter: *const SrdbIter) -> c_int {
+    status(with(&ITERS, iter, |iter| iter.next < iter.keys.len()))
+}
+
+/**
+ * key iterator is at, 0 if srdb_iter_valid is not 1
+ */
+#[no_mangle]
+pub extern "C" fn srdb_iter_key(iter: *const SrdbIter) -> i64 {
+    with(&ITERS, iter, |iter| iter.keys.get(iter.next).copied())
+        .ok()
+        .flatten()
+        .unwrap_or(0)
+}
+
+/**
+ * moves iterator to the next key, SRDB_OK or error code, it stays past the last key once there
+ */
+#[no_mangle]
+pub extern "C" fn srdb_iter_next(iter: *mut SrdbIter) -> c_int {
+    with(&ITERS, iter, |iter| iter.next = (iter.next + 1).min(iter.keys.len())).map_or_else(|code| code, |_| SRDB_OK)
+}
+
+/**
+ * SRDB_OK or error code, iterator is invalid after it
+ */
+#[no_mangle]
+pub extern "C" fn srdb_iter_free(iter: *mut SrdbIter) -> c_int {
+    unregister(&ITERS, iter)
+}
@@ -0,0 +1,105 @@
+use crate::crc32::checksum;
+use crate::error::{Error, Result};
+use crate::pager::PageId;
+
+/**
+ * header page layout, integers are little-endian:
+ * key codec (u32), value codec (u32), t (u32),
+ * root page id (u32), entry count (u64),
+ * sequence number of the last batch whose changes are in pages (u64),
+ * head of free page list (u32), number of free pages (u64),
+ * root page id of catalog (u32, header page id if there is none), entry count of catalog (u64),
+ * oldest log segment recovery needs (u64),
+ * crc32 of everything before it (u32)
+ */
+const CHECKSUM_OFFSET: usize = 64;
+
+/**
+ * codec of keys and values stored in database, Db keeps raw bytes
+ */
+pub const BYTES_CODEC: u32 = 0;
+
+/**
+ * value codec of database whose values may be compressed, see SrdbOptions::compression
+ */
+pub const LZ4_CODEC: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub key_codec: u32,
+    pub value_codec: u32,
+    pub t: u32,
+    pub root: PageId,
+    pub len: u64,
+    pub seq: u64,
+    pub free_head: PageId,
+    pub free_count: u64,
+    pub catalog: PageId,
+    pub catalog_len: u64,
+    pub wal_segment: u64,
+}
+
+fn read_u32(page: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(page[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(page: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(page[offset..offset + 8].try_into().unwrap())
+}
+
+impl Header {
+    pub fn encode(&self, page_size: usize) -> Vec<u8> {
+        let mut page = Vec::with_capacity(page_size);
+
+        page.extend_from_slice(&self.key_codec.to_le_bytes());
+        page.extend_from_slice(&self.value_codec.to_le_bytes());
+        page.extend_from_slice(&self.t.to_le_bytes());
+        page.extend_from_slice(&self.root.to_le_bytes());
+        page.extend_from_slice(&self.len.to_le_bytes());
+        page.extend_from_slice(&self.seq.to_le_bytes());
+        page.extend_from_slice(&self.free_head.to_le_bytes());
+        page.extend_from_slice(&self.free_count.to_le_bytes());
+        page.extend_from_slice(&self.catalog.to_le_bytes());
+        page.extend_from_slice(&self.catalog_len.to_le_bytes());
+        page.extend_from_slice(&self.wal_segment.to_le_bytes());
+
+        debug_assert_eq!(page.len(), CHECKSUM_OFFSET);
+
+        let crc = checksum(&page);
+
+        page.extend_from_slice(&crc.to_le_bytes());
+        page.resize(page_size, 0);
+
+        page
+    }
+
+    /**
+     * validates checksum, fields are checked by caller
+     * magic, version and page size are in superblock, see Pager
+     */
+    pub fn decode(page: &[u8]) -> Result<Header> {
+        let expected = read_u32(page, CHECKSUM_OFFSET);
+        let actual = checksum(&page[..CHECKSUM_OFFSET]);
+
+        if expected != actual {
+            return Err(Error::CorruptHeader(format!(
+                "checksum mismatch: stored {:08x}, computed {:08x}",
+                expected, actual
+            )));
+        }
+
+        Ok(Header {
+            key_codec: read_u32(page, 0),
+            value_codec: read_u32(page, 4),
+            t: read_u32(page, 8),
+            root: read_u32(page, 12),
+            len: read_u64(page, 16),
+            seq: read_u64(page, 24),
+            free_head: read_u32(page, 32),
+            free_count: read_u64(page, 36),
+            catalog: read_u32(page, 44),
+            catalog_len: read_u64(page, 48),
+            wal_segment: read_u64(page, 56),
+        })
+    }
+}
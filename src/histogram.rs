@@ -0,0 +1,115 @@
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Display};
+
+use crate::{BTree, Comparator, NodeLayout};
+
+/**
+ * number of buckets key counts of nodes are spread across, small t gets one bucket per key count
+ */
+const HISTOGRAM_BUCKETS: usize = 10;
+
+/**
+ * widest bar of Display, bars of other buckets are scaled to it
+ */
+const HISTOGRAM_BAR: usize = 40;
+
+/**
+ * key counts of nodes on every level, see BTree::histogram
+ * bucket i of level counts nodes with i * bucket_width up to (i + 1) * bucket_width - 1 keys,
+ * buckets cover 0..2t keys, so nodes at minimum fill t - 1 land in bucket (t - 1) / bucket_width
+ * with serde feature it serializes as object of these fields, e.g. for dashboards
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct TreeHistogram {
+    pub t: usize,
+    pub bucket_width: usize,
+    pub levels: Vec<LevelHistogram>,
+}
+
+/**
+ * level 0 is root, levels go down to leaves
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct LevelHistogram {
+    pub nodes: usize,
+    pub min: usize,
+    pub max: usize,
+    pub avg: f64,
+    pub buckets: Vec<usize>,
+}
+
+/**
+ * per level summary line and one bar per bucket, bucket rows are labeled with their key counts
+ */
+impl Display for TreeHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "t = {}, {} key slots per node", self.t, 2 * self.t - 1)?;
+
+        for (depth, level) in self.levels.iter().enumerate() {
+            writeln!(
+                f,
+                "level {}: {} nodes, keys min {} avg {:.2} max {}",
+                depth, level.nodes, level.min, level.avg, level.max
+            )?;
+
+            let widest = level.buckets.iter().copied().max().unwrap_or(0).max(1);
+
+            for (i, count) in level.buckets.iter().enumerate() {
+                let low = i * self.bucket_width;
+                let high = (low + self.bucket_width - 1).min(2 * self.t - 1);
+                let label = if low == high { format!("{}", low) } else { format!("{}-{}", low, high) };
+                let bar = (count * HISTOGRAM_BAR).div_ceil(widest);
+
+                writeln!(f, "  {:>7} | {:<6} {}", label, count, "#".repeat(bar))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    /**
+     * distribution of key counts of nodes per level, e.g. to see whether deletes keep some level at minimum fill
+     * tree is walked once with explicit stack
+     */
+    pub fn histogram(&self) -> TreeHistogram {
+        let slots = 2 * self.t;
+        let bucket_width = slots.div_ceil(HISTOGRAM_BUCKETS);
+        let buckets = slots.div_ceil(bucket_width);
+
+        let mut levels: Vec<LevelHistogram> = vec![];
+        let mut keys: Vec<usize> = vec![];
+        let mut stack = vec![(self.root, 0)];
+
+        while let Some((id, depth)) = stack.pop() {
+            let node = self.node(id);
+            let count = node.keys.len();
+
+            if depth == levels.len() {
+                levels.push(LevelHistogram { nodes: 0, min: count, max: count, avg: 0.0, buckets: vec![0; buckets] });
+                keys.push(0);
+            }
+
+            let level = &mut levels[depth];
+
+            level.nodes += 1;
+            level.min = level.min.min(count);
+            level.max = level.max.max(count);
+            level.buckets[count / bucket_width] += 1;
+            keys[depth] += count;
+
+            stack.extend(node.children.iter().map(|child| (*child, depth + 1)));
+        }
+
+        for (level, keys) in levels.iter_mut().zip(keys) {
+            level.avg = keys as f64 / level.nodes as f64;
+        }
+
+        TreeHistogram { t: self.t, bucket_width, levels }
+    }
+}
@@ -0,0 +1,536 @@
+use alloc::collections::TryReserveError;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+
+/**
+ * vector which keeps up to N * M elements inside itself, M lets capacity be a multiple of const parameter,
+ * e.g. 2t slots of FixedT nodes
+ * on overflow all elements are moved to heap Vec and stay there (spill)
+ * so nodes of small trees never allocate for keys and children
+ */
+pub struct InlineVec<T, const N: usize, const M: usize = 1> {
+    len: usize,
+    inline: [[MaybeUninit<T>; M]; N],
+    heap: Option<Vec<T>>,
+}
+
+#[allow(dead_code)]
+impl<T, const N: usize, const M: usize> InlineVec<T, N, M> {
+    const CAPACITY: usize = N * M;
+
+    pub fn new() -> Self {
+        InlineVec {
+            len: 0,
+            inline: [const { [const { MaybeUninit::uninit() }; M] }; N],
+            heap: None,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut result = Self::new();
+
+        if capacity > Self::CAPACITY {
+            result.heap = Some(Vec::with_capacity(capacity));
+        }
+
+        result
+    }
+
+    /**
+     * empty vector which keeps elements on heap, inline slots stay unused
+     */
+    pub fn on_heap(capacity: usize) -> Self {
+        let mut result = Self::new();
+
+        result.heap = Some(Vec::with_capacity(capacity));
+
+        result
+    }
+
+    pub fn try_on_heap(capacity: usize) -> Result<Self, TryReserveError> {
+        let mut heap = Vec::new();
+
+        heap.try_reserve_exact(capacity)?;
+
+        let mut result = Self::new();
+
+        result.heap = Some(heap);
+
+        Ok(result)
+    }
+
+    /**
+     * number of elements heap buffer can hold, zero while elements are inline
+     */
+    pub fn heap_capacity(&self) -> usize {
+        self.heap.as_ref().map_or(0, |heap| heap.capacity())
+    }
+
+    /**
+     * true if elements are stored on heap
+     */
+    pub fn spilled(&self) -> bool {
+        self.heap.is_some()
+    }
+
+    /**
+     * moves inline elements to heap, leaving room for additional elements
+     */
+    fn spill(&mut self, additional: usize) -> &mut Vec<T> {
+        self.spill_into(Vec::with_capacity(Self::spill_capacity(self.len + additional)))
+    }
+
+    fn spill_capacity(len: usize) -> usize {
+        (2 * Self::CAPACITY).max(len)
+    }
+
+    /**
+     * heap must have room for inline elements
+     */
+    fn spill_into(&mut self, mut heap: Vec<T>) -> &mut Vec<T> {
+        unsafe {
+            ptr::copy_nonoverlapping(self.inline.as_ptr() as *const T, heap.as_mut_ptr(), self.len);
+            heap.set_len(self.len);
+        }
+
+        self.len = 0;
+        self.heap.insert(heap)
+    }
+
+    /**
+     * makes room for additional elements, on failure of allocation returns error and keeps elements where they were
+     */
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if let Some(heap) = self.heap.as_mut() {
+            return heap.try_reserve(additional);
+        }
+
+        if self.len + additional <= Self::CAPACITY {
+            return Ok(());
+        }
+
+        let mut heap = Vec::new();
+
+        heap.try_reserve_exact(Self::spill_capacity(self.len + additional))?;
+        self.spill_into(heap);
+
+        Ok(())
+    }
+
+    pub fn push(&mut self, value: T) {
+        if let Some(heap) = self.heap.as_mut() {
+            return heap.push(value);
+        }
+
+        if self.len == Self::CAPACITY {
+            return self.spill(1).push(value);
+        }
+
+        self.inline.as_flattened_mut()[self.len].write(value);
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if let Some(heap) = self.heap.as_mut() {
+            return heap.pop();
+        }
+
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+
+        Some(unsafe { self.inline.as_flattened()[self.len].assume_init_read() })
+    }
+
+    pub fn insert(&mut self, index: usize, value: T) {
+        if let Some(heap) = self.heap.as_mut() {
+            return heap.insert(index, value);
+        }
+
+        assert!(index <= self.len, "insertion index {} is out of bounds {}", index, self.len);
+
+        if self.len == Self::CAPACITY {
+            return self.spill(1).insert(index, value);
+        }
+
+        unsafe {
+            let p = (self.inline.as_mut_ptr() as *mut T).add(index);
+
+            ptr::copy(p, p.add(1), self.len - index);
+            ptr::write(p, value);
+        }
+
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        if let Some(heap) = self.heap.as_mut() {
+            return heap.remove(index);
+        }
+
+        assert!(index < self.len, "removal index {} is out of bounds {}", index, self.len);
+
+        unsafe {
+            let p = (self.inline.as_mut_ptr() as *mut T).add(index);
+            let value = ptr::read(p);
+
+            ptr::copy(p.add(1), p, self.len - index - 1);
+            self.len -= 1;
+
+            value
+        }
+    }
+
+    /**
+     * spilled vector gives spilled part, so halves of split node stay where node was
+     */
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let mut result = Self::new();
+
+        if let Some(heap) = self.heap.as_mut() {
+            result.heap = Some(heap.split_off(at));
+
+            return result;
+        }
+
+        assert!(at <= self.len, "split index {} is out of bounds {}", at, self.len);
+
+        unsafe {
+            ptr::copy_nonoverlapping(
+                (self.inline.as_ptr() as *const T).add(at),
+                result.inline.as_mut_ptr() as *mut T,
+                self.len - at,
+            );
+        }
+
+        result.len = self.len - at;
+        self.len = at;
+
+        result
+    }
+
+    pub fn append(&mut self, other: &mut Self) {
+        if let Some(heap) = other.heap.as_mut() {
+            for value in heap.drain(..) {
+                self.push(value);
+            }
+
+            return;
+        }
+
+        let count = other.len;
+        other.len = 0;
+
+        for i in 0..count {
+            self.push(unsafe { other.inline.as_flattened()[i].assume_init_read() });
+        }
+    }
+}
+
+impl<T, const N: usize, const M: usize> Default for InlineVec<T, N, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize, const M: usize> Deref for InlineVec<T, N, M> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self.heap.as_ref() {
+            Some(heap) => heap,
+            None => unsafe { core::slice::from_raw_parts(self.inline.as_ptr() as *const T, self.len) },
+        }
+    }
+}
+
+impl<T, const N: usize, const M: usize> DerefMut for InlineVec<T, N, M> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self.heap.as_mut() {
+            Some(heap) => heap,
+            None => unsafe { core::slice::from_raw_parts_mut(self.inline.as_mut_ptr() as *mut T, self.len) },
+        }
+    }
+}
+
+impl<T, const N: usize, const M: usize> Drop for InlineVec<T, N, M> {
+    fn drop(&mut self) {
+        if self.heap.is_none() {
+            unsafe { ptr::drop_in_place(self.deref_mut() as *mut [T]) }
+        }
+    }
+}
+
+impl<T: Clone, const N: usize, const M: usize> Clone for InlineVec<T, N, M> {
+    fn clone(&self) -> Self {
+        let mut result = Self::with_capacity(self.len());
+
+        for value in self.iter() {
+            result.push(value.clone());
+        }
+
+        result
+    }
+}
+
+impl<T: Debug, const N: usize, const M: usize> Debug for InlineVec<T, N, M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.deref().fmt(f)
+    }
+}
+
+impl<T, const N: usize, const M: usize> Extend<T> for InlineVec<T, N, M> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+/**
+ * owning iterator, elements not yielded are dropped with it
+ */
+pub struct IntoIter<T, const N: usize, const M: usize> {
+    heap: Option<alloc::vec::IntoIter<T>>,
+    inline: [[MaybeUninit<T>; M]; N],
+    next: usize,
+    end: usize,
+}
+
+impl<T, const N: usize, const M: usize> IntoIterator for InlineVec<T, N, M> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N, M>;
+
+    fn into_iter(mut self) -> IntoIter<T, N, M> {
+        let end = self.len;
+        self.len = 0;
+
+        IntoIter {
+            heap: self.heap.take().map(|heap| heap.into_iter()),
+            inline: core::mem::replace(&mut self.inline, [const { [const { MaybeUninit::uninit() }; M] }; N]),
+            next: 0,
+            end,
+        }
+    }
+}
+
+impl<T, const N: usize, const M: usize> Iterator for IntoIter<T, N, M> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if let Some(heap) = self.heap.as_mut() {
+            return heap.next();
+        }
+
+        if self.next == self.end {
+            return None;
+        }
+
+        self.next += 1;
+
+        Some(unsafe { self.inline.as_flattened()[self.next - 1].assume_init_read() })
+    }
+}
+
+impl<T, const N: usize, const M: usize> Drop for IntoIter<T, N, M> {
+    fn drop(&mut self) {
+        for i in self.next..self.end {
+            unsafe { self.inline.as_flattened_mut()[i].assume_init_drop() }
+        }
+    }
+}
+
+/**
+ * unsafe parts are checked by running these under miri: cargo +nightly miri test --lib inline_vec
+ */
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::cell::Cell;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    use super::InlineVec;
+
+    /**
+     * element counting its drops in shared counter
+     */
+    #[derive(Debug)]
+    struct Tracked(u32, Rc<Cell<usize>>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.1.set(self.1.get() + 1);
+        }
+    }
+
+    impl Clone for Tracked {
+        fn clone(&self) -> Self {
+            assert!(self.0 != 13, "clone of 13");
+
+            Tracked(self.0, self.1.clone())
+        }
+    }
+
+    fn values<const N: usize>(v: &InlineVec<Tracked, N>) -> Vec<u32> {
+        v.iter().map(|x| x.0).collect()
+    }
+
+    #[test]
+    fn drops_every_element_once() {
+        for len in [0, 1, 3, 4, 5, 9] {
+            let drops = Rc::new(Cell::new(0));
+            let mut v = InlineVec::<Tracked, 4>::new();
+
+            v.extend((0..len).map(|i| Tracked(i, drops.clone())));
+            assert_eq!(v.spilled(), len > 4);
+
+            if len > 0 {
+                assert_eq!(v.remove(0).0, 0);
+                assert_eq!(v.pop().map(|x| x.0), (len > 1).then(|| len - 1));
+            }
+
+            drop(v);
+
+            assert_eq!(drops.get(), len as usize);
+        }
+    }
+
+    #[test]
+    fn into_iter_drops_elements_not_yielded() {
+        for len in [3, 7] {
+            let drops = Rc::new(Cell::new(0));
+            let mut v = InlineVec::<Tracked, 4>::new();
+
+            v.extend((0..len).map(|i| Tracked(i, drops.clone())));
+
+            let mut iter = v.into_iter();
+
+            assert_eq!(iter.next().map(|x| x.0), Some(0));
+            assert_eq!(drops.get(), 1);
+            drop(iter);
+
+            assert_eq!(drops.get(), len as usize);
+        }
+    }
+
+    #[test]
+    fn spills_keeping_order() {
+        let drops = Rc::new(Cell::new(0));
+        let mut v = InlineVec::<Tracked, 4>::new();
+
+        for i in [1, 3, 5, 7] {
+            v.push(Tracked(i, drops.clone()));
+        }
+
+        assert!(!v.spilled());
+        assert_eq!(v.heap_capacity(), 0);
+
+        v.insert(2, Tracked(4, drops.clone()));
+
+        assert!(v.spilled());
+        assert_eq!(values(&v), [1, 3, 4, 5, 7]);
+
+        let right = v.split_off(2);
+
+        assert!(right.spilled());
+        assert_eq!(values(&right), [4, 5, 7]);
+
+        let mut inline = InlineVec::<Tracked, 4>::new();
+
+        inline.push(Tracked(0, drops.clone()));
+        inline.append(&mut v);
+
+        assert_eq!(values(&inline), [0, 1, 3]);
+        assert!(v.is_empty());
+        assert_eq!(drops.get(), 0);
+
+        drop((inline, right, v));
+
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    fn try_reserve_spills_only_beyond_inline_slots() {
+        let mut v = InlineVec::<u32, 4>::new();
+
+        v.extend([1, 2]);
+        v.try_reserve(2).unwrap();
+
+        assert!(!v.spilled());
+
+        v.try_reserve(3).unwrap();
+
+        assert!(v.spilled());
+        assert!(v.heap_capacity() >= 5);
+        assert_eq!(&*v, [1, 2]);
+    }
+
+    #[test]
+    fn split_off_and_remove_inline() {
+        let mut v = InlineVec::<u32, 3, 2>::new();
+
+        v.extend(0..6);
+
+        let right = v.split_off(4);
+
+        assert_eq!(v.remove(1), 1);
+        assert_eq!((&*v, &*right), (&[0, 2, 3][..], &[4, 5][..]));
+        assert!(!v.spilled() && !right.spilled());
+    }
+
+    #[test]
+    fn panic_in_clone_drops_clones_made() {
+        for len in [4, 20] {
+            let drops = Rc::new(Cell::new(0));
+            let mut v = InlineVec::<Tracked, 4>::new();
+
+            v.extend((10..10 + len).map(|i| Tracked(i, drops.clone())));
+
+            assert!(catch_unwind(AssertUnwindSafe(|| v.clone())).is_err());
+            assert_eq!(drops.get(), 3);
+
+            drop(v);
+
+            assert_eq!(drops.get(), 3 + len as usize);
+        }
+    }
+
+    #[test]
+    fn panic_in_extend_keeps_elements_pushed() {
+        let drops = Rc::new(Cell::new(0));
+        let mut v = InlineVec::<Tracked, 4>::new();
+        let source = drops.clone();
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            v.extend((0..10).map(|i| {
+                assert!(i != 6, "element 6");
+
+                Tracked(i, source.clone())
+            }))
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(values(&v), [0, 1, 2, 3, 4, 5]);
+
+        drop(v);
+
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    fn node_of_big_t_starts_on_heap() {
+        use crate::layout::NodeVec;
+
+        let small = <InlineVec<u32, 15> as NodeVec<u32>>::node_in(8, 15, &());
+        let big = <InlineVec<u32, 15> as NodeVec<u32>>::node_in(9, 17, &());
+
+        assert!(!small.spilled());
+        assert!(big.spilled());
+        assert_eq!(big.heap_capacity(), 9);
+    }
+}
@@ -0,0 +1,72 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display};
+
+use ::serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{BTree, Comparator, NodeId, NodeLayout};
+
+impl<T: Debug + Serialize, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    /**
+     * structure of tree as json, every node is {"keys": [...], "leaf": bool, "children": [...]}
+     * with children nested in order, keys are written as their Serialize gives them,
+     * key serializer refuses is written as its Debug text, to_json_structure_display writes Display text instead
+     */
+    pub fn to_json_structure(&self) -> Value {
+        self.json_structure(|key| serde_json::to_value(key).unwrap_or_else(|_| Value::String(format!("{:?}", key))))
+    }
+}
+
+impl<T: Debug + Display + Serialize, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    /**
+     * to_json_structure with Display text of keys serializer refuses
+     */
+    pub fn to_json_structure_display(&self) -> Value {
+        self.json_structure(|key| serde_json::to_value(key).unwrap_or_else(|_| Value::String(key.to_string())))
+    }
+}
+
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    /**
+     * nodes are visited with explicit stack, so depth of tree does not grow call stack
+     */
+    fn json_structure(&self, key: impl Fn(&T) -> Value) -> Value {
+        let mut stack = vec![(self.root, 0, Vec::new())];
+
+        loop {
+            let (id, next, _) = stack.last_mut().unwrap();
+            let node = self.node(*id);
+
+            if *next < node.children.len() {
+                let child = node.children[*next];
+
+                *next += 1;
+                stack.push((child, 0, Vec::new()));
+
+                continue;
+            }
+
+            let (id, _, children) = stack.pop().unwrap();
+            let value = self.json_node(id, children, &key);
+
+            match stack.last_mut() {
+                Some((_, _, parent)) => parent.push(value),
+                None => return value,
+            }
+        }
+    }
+
+    fn json_node(&self, id: NodeId, children: Vec<Value>, key: impl Fn(&T) -> Value) -> Value {
+        let node = self.node(id);
+        let mut object = Map::new();
+
+        object.insert("keys".to_string(), Value::Array(node.keys.iter().map(key).collect()));
+        object.insert("leaf".to_string(), Value::Bool(node.leaf));
+        object.insert("children".to_string(), Value::Array(children));
+
+        Value::Object(object)
+    }
+}
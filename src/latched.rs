@@ -0,0 +1,532 @@
+use std::cell::UnsafeCell;
+use std::fmt::Debug;
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
+use std::convert::Infallible;
+use std::sync::{Arc, PoisonError};
+
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(loom)]
+use loom::sync::{Condvar, Mutex, MutexGuard};
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(loom))]
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use crate::node_store::{self, infallible, NodeStore};
+use crate::{Node, NodeId};
+
+/**
+ * value guarded by reader-writer latch which is taken and released by owned guards,
+ * so guard of child can outlive guard of its parent while thread walks down
+ * state is number of readers, or -1 while writer has it
+ * with --cfg loom state and len are loom primitives, so loom explores interleavings of latches
+ */
+struct Latched<D> {
+    state: Mutex<isize>,
+    released: Condvar,
+    data: UnsafeCell<D>,
+}
+
+/**
+ * data is reached only through guards, which keep the latch rules, the same as RwLock
+ */
+unsafe impl<D: Send> Send for Latched<D> {}
+unsafe impl<D: Send + Sync> Sync for Latched<D> {}
+
+impl<D> Latched<D> {
+    fn new(data: D) -> Arc<Latched<D>> {
+        Arc::new(Latched {
+            state: Mutex::new(0),
+            released: Condvar::new(),
+            data: UnsafeCell::new(data),
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, isize> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn wait_while(&self, blocked: impl Fn(isize) -> bool) -> MutexGuard<'_, isize> {
+        let mut state = self.state();
+
+        while blocked(*state) {
+            state = self.released.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+
+        state
+    }
+
+    fn read(self: &Arc<Self>) -> ReadLatch<D> {
+        *self.wait_while(|state| state < 0) += 1;
+
+        ReadLatch(self.clone())
+    }
+
+    fn write(self: &Arc<Self>) -> WriteLatch<D> {
+        *self.wait_while(|state| state != 0) = -1;
+
+        WriteLatch(self.clone())
+    }
+}
+
+struct ReadLatch<D>(Arc<Latched<D>>);
+
+impl<D> Deref for ReadLatch<D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        unsafe { &*self.0.data.get() }
+    }
+}
+
+impl<D> Drop for ReadLatch<D> {
+    fn drop(&mut self) {
+        let mut state = self.0.state();
+
+        *state -= 1;
+
+        if *state == 0 {
+            self.0.released.notify_all();
+        }
+    }
+}
+
+struct WriteLatch<D>(Arc<Latched<D>>);
+
+impl<D> Deref for WriteLatch<D> {
+    type Target = D;
+
+    fn deref(&self) -> &D {
+        unsafe { &*self.0.data.get() }
+    }
+}
+
+impl<D> DerefMut for WriteLatch<D> {
+    fn deref_mut(&mut self) -> &mut D {
+        unsafe { &mut *self.0.data.get() }
+    }
+}
+
+impl<D> Drop for WriteLatch<D> {
+    fn drop(&mut self) {
+        *self.0.state() = 0;
+        self.0.released.notify_all();
+    }
+}
+
+type NodeRef<T> = Arc<Latched<LatchedNode<T>>>;
+
+struct LatchedNode<T> {
+    leaf: bool,
+    keys: Vec<T>,
+    children: Vec<NodeRef<T>>,
+}
+
+impl<T: Ord + Clone + Debug> LatchedNode<T> {
+    fn leaf(t: usize) -> LatchedNode<T> {
+        LatchedNode {
+            leaf: true,
+            keys: Vec::with_capacity(2 * t - 1),
+            children: vec![],
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.keys.len()
+    }
+}
+
+/**
+ * what operation did with node: counted or allocated it, loaded it, or stored, unloaded or freed it
+ */
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Reached,
+    Loaded,
+    Done,
+}
+
+/**
+ * node reached by operation, parent is node it was reached from, None for root and new nodes
+ */
+struct Slot<T> {
+    node: NodeRef<T>,
+    latch: Option<WriteLatch<LatchedNode<T>>>,
+    parent: Option<NodeId>,
+    state: State,
+}
+
+/**
+ * store of shared insert and delete for one writer, ids are indices of nodes it reached in slots
+ * node is latched once it is counted or loaded, and once operation loads node below the ones it is done with,
+ * their latches and those of siblings it only counted are released, so latch of child is always taken
+ * before that of its parent is released
+ * root pointer stays latched until operation is below root, or for whole delete if root may collapse
+ */
+struct Crab<'a, T: Ord + Clone + Debug> {
+    tree: &'a LatchedBTree<T>,
+    root_latch: Option<WriteLatch<NodeRef<T>>>,
+    keep_root: bool,
+    root: NodeId,
+    slots: Vec<Slot<T>>,
+}
+
+impl<'a, T: Ord + Clone + Debug> Crab<'a, T> {
+    /**
+     * latches root pointer and root, root with one key may collapse by delete
+     */
+    fn new(tree: &'a LatchedBTree<T>, delete: bool) -> Self {
+        let root_latch = tree.root.write();
+        let latch = root_latch.write();
+
+        Crab {
+            tree,
+            keep_root: delete && !latch.leaf && latch.count() <= 1,
+            slots: vec![Slot {
+                node: (*root_latch).clone(),
+                latch: Some(latch),
+                parent: None,
+                state: State::Reached,
+            }],
+            root_latch: Some(root_latch),
+            root: 0,
+        }
+    }
+
+    fn slot(&mut self, id: NodeId) -> &mut Slot<T> {
+        &mut self.slots[id as usize]
+    }
+
+    fn latch(&mut self, id: NodeId) -> &mut WriteLatch<LatchedNode<T>> {
+        let slot = self.slot(id);
+
+        slot.latch.get_or_insert_with(|| slot.node.write())
+    }
+
+    /**
+     * releases latches of nodes operation is done with or passed by, node id is already latched
+     */
+    fn release(&mut self, id: NodeId) {
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            if i != id as usize && slot.state != State::Loaded {
+                slot.latch = None;
+            }
+        }
+
+        if id != self.root && !self.keep_root {
+            self.root_latch = None;
+        }
+    }
+
+    fn contents(&self, node: Node<T>) -> LatchedNode<T> {
+        LatchedNode {
+            leaf: node.leaf,
+            keys: node.keys.into_iter().collect(),
+            children: node.children.iter().map(|&child| self.slots[child as usize].node.clone()).collect(),
+        }
+    }
+}
+
+impl<T: Ord + Clone + Debug> NodeStore<T> for Crab<'_, T> {
+    type Error = Infallible;
+
+    fn t(&self) -> usize {
+        self.tree.t
+    }
+
+    fn root(&self) -> NodeId {
+        self.root
+    }
+
+    fn set_root(&mut self, id: NodeId) {
+        let node = self.slots[id as usize].node.clone();
+
+        self.root = id;
+        **self.root_latch.as_mut().expect("root pointer is latched while root may change") = node;
+    }
+
+    fn compare(&self, a: &T, b: &T) -> std::cmp::Ordering {
+        a.cmp(b)
+    }
+
+    fn allocator(&self) -> &() {
+        &()
+    }
+
+    /**
+     * delete loads root once more in the end to see if it collapses, if it can't, root is not latched again,
+     * it may be inner node of other writers by now, so it is answered by empty leaf
+     */
+    fn load(&mut self, id: NodeId) -> Result<Node<T>, Infallible> {
+        let Slot { state, parent, .. } = self.slots[id as usize];
+
+        if id == self.root && state == State::Done && self.root_latch.is_none() {
+            return Ok(Node::from_parts(true, [], []));
+        }
+
+        let latch = self.latch(id);
+        let keys = std::mem::take(&mut latch.keys);
+        let (leaf, children) = (latch.leaf, std::mem::take(&mut latch.children));
+
+        if parent.is_none_or(|parent| self.slot(parent).state == State::Done) {
+            self.release(id);
+        }
+
+        let first = self.slots.len() as NodeId;
+
+        self.slots.extend(children.into_iter().map(|node| Slot {
+            node,
+            latch: None,
+            parent: Some(id),
+            state: State::Reached,
+        }));
+        self.slot(id).state = State::Loaded;
+
+        Ok(Node::from_parts(leaf, keys, first..self.slots.len() as NodeId))
+    }
+
+    fn store(&mut self, id: NodeId, node: Node<T>) -> Result<(), Infallible> {
+        let contents = self.contents(node);
+
+        **self.latch(id) = contents;
+        self.slot(id).state = State::Done;
+
+        Ok(())
+    }
+
+    fn unload(&mut self, id: NodeId, node: Node<T>) {
+        if self.slot(id).state == State::Loaded {
+            infallible(self.store(id, node));
+        }
+    }
+
+    fn count(&mut self, id: NodeId) -> Result<usize, Infallible> {
+        Ok(self.latch(id).count())
+    }
+
+    /**
+     * new node is reached only through its parent, which is latched, so it is not latched until it is loaded
+     */
+    fn alloc(&mut self, node: Node<T>) -> Result<NodeId, Infallible> {
+        let node = Latched::new(self.contents(node));
+
+        self.slots.push(Slot {
+            node,
+            latch: None,
+            parent: None,
+            state: State::Done,
+        });
+
+        Ok((self.slots.len() - 1) as NodeId)
+    }
+
+    fn free(&mut self, id: NodeId) -> Result<(), Infallible> {
+        let slot = self.slot(id);
+
+        slot.latch = None;
+        slot.state = State::Done;
+
+        Ok(())
+    }
+}
+
+/**
+ * B-tree for many writer threads, every node has its own latch and threads crab down the tree:
+ * child is latched before latch of parent is released, and parent is released as soon as child is safe,
+ * so writers in different subtrees run in parallel
+ * insert and delete are the shared ones of node_store over Crab, they split full child and fill child
+ * with less than t keys before they descend, so child is always safe once latched,
+ * readers take shared latches the same way
+ * root pointer has latch of its own, held only while root may split or shrink
+ * len and to_vec are exact once writers are done, while they run to_vec sees each node as of some moment
+ */
+pub struct LatchedBTree<T: Ord + Clone + Debug> {
+    root: Arc<Latched<NodeRef<T>>>,
+    len: AtomicUsize,
+    t: usize,
+}
+
+impl<T: Ord + Clone + Debug> Debug for LatchedBTree<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LatchedBTree").field("t", &self.t).field("keys", &self.to_vec()).finish()
+    }
+}
+
+impl<T: Ord + Clone + Debug> LatchedBTree<T> {
+    pub fn new(t: usize) -> LatchedBTree<T> {
+        LatchedBTree {
+            root: Latched::new(Latched::new(LatchedNode::leaf(t))),
+            len: AtomicUsize::new(0),
+            t,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn insert(&self, value: T) {
+        node_store::infallible(node_store::insert(&mut Crab::new(self, false), value));
+
+        self.len.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /**
+     * removes one occurrence of value
+     * returns status of operation: did element remove
+     */
+    pub fn delete(&self, value: &T) -> bool {
+        let mut crab = Crab::new(self, true);
+        let removed = node_store::infallible(node_store::delete(&mut crab, |_, key| key.cmp(value))).is_some();
+
+        if removed {
+            self.len.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        removed
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        let root = self.root.read();
+        let mut node = root.read();
+
+        drop(root);
+
+        loop {
+            let i = node.keys.partition_point(|key| key < value);
+
+            if i < node.count() && node.keys[i] == *value {
+                return true;
+            }
+
+            if node.leaf {
+                return false;
+            }
+
+            node = node.children[i].read();
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.range(..)
+    }
+
+    /**
+     * keys inside range in order, latches of path to node being read are held, so writers wait only
+     * for nodes on that path
+     */
+    pub fn range(&self, range: impl RangeBounds<T>) -> Vec<T> {
+        let mut out = vec![];
+        let root = self.root.read();
+        let node = root.read();
+
+        drop(root);
+        Self::collect_range(node, &range, &mut out);
+
+        out
+    }
+
+    /**
+     * returns false once walk is past end of range
+     */
+    fn collect_range(node: ReadLatch<LatchedNode<T>>, range: &impl RangeBounds<T>, out: &mut Vec<T>) -> bool {
+        let first = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) => node.keys.partition_point(|key| key < start),
+            Bound::Unbounded => 0,
+        };
+
+        for i in first..=node.count() {
+            if !node.leaf && !Self::collect_range(node.children[i].read(), range, out) {
+                return false;
+            }
+
+            let Some(key) = node.keys.get(i) else {
+                break;
+            };
+
+            let after = match range.end_bound() {
+                Bound::Included(end) => key > end,
+                Bound::Excluded(end) => key >= end,
+                Bound::Unbounded => false,
+            };
+
+            if after {
+                return false;
+            }
+
+            if range.contains(key) {
+                out.push(key.clone());
+            }
+        }
+
+        true
+    }
+
+    /**
+     * checks key order, bounds from delimeters, node sizes, equal leaf depth and len,
+     * meaningful once writers are done
+     */
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let t = self.t;
+        let root = self.root.read();
+        let mut stack = vec![((*root).clone(), 0, None::<T>, None::<T>)];
+        let mut leaf_depth: Option<usize> = None;
+        let mut total = 0;
+
+        while let Some((node, depth, lower, upper)) = stack.pop() {
+            let is_root = Arc::ptr_eq(&node, &root);
+            let node = node.read();
+
+            if node.count() > 2 * t - 1 || (!is_root && node.count() < t - 1) {
+                return Err(format!("node at depth {}: {} keys is out of bounds", depth, node.count()));
+            }
+
+            if node.keys.windows(2).any(|pair| pair[0] > pair[1]) {
+                return Err(format!("node at depth {}: keys are not sorted {:?}", depth, node.keys));
+            }
+
+            let below = lower.as_ref().is_some_and(|lower| node.keys.first().is_some_and(|first| first < lower));
+            let above = upper.as_ref().is_some_and(|upper| node.keys.last().is_some_and(|last| last > upper));
+
+            if below || above {
+                return Err(format!("node at depth {}: keys {:?} are out of delimeters", depth, node.keys));
+            }
+
+            total += node.count();
+
+            if node.leaf {
+                match leaf_depth {
+                    None => leaf_depth = Some(depth),
+                    Some(expected) if expected != depth => {
+                        return Err(format!("leaf at depth {}, expected {}", depth, expected));
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            if node.children.len() != node.count() + 1 {
+                return Err(format!("node at depth {}: {} keys but {} children", depth, node.count(), node.children.len()));
+            }
+
+            for i in 0..=node.count() {
+                let child_lower = if i == 0 { lower.clone() } else { Some(node.keys[i - 1].clone()) };
+                let child_upper = if i == node.count() { upper.clone() } else { Some(node.keys[i].clone()) };
+
+                stack.push((node.children[i].clone(), depth + 1, child_lower, child_upper));
+            }
+        }
+
+        if total != self.len() {
+            return Err(format!("tree has {} keys, but len is {}", total, self.len()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+#[cfg(feature = "allocator_api")]
+use alloc::alloc::Allocator;
+use alloc::collections::TryReserveError;
+use alloc::vec::Vec;
+#[cfg(feature = "allocator_api")]
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use crate::inline_vec::InlineVec;
+use crate::NodeId;
+
+/**
+ * storage of keys or children of node, the part of Vec tree algorithms use
+ * A is allocator storage is taken from, () for global heap
+ */
+pub trait NodeVec<T, A = ()>: Deref<Target = [T]> + DerefMut + Extend<T> + IntoIterator<Item = T> {
+    fn with_capacity_in(capacity: usize, alloc: &A) -> Self;
+
+    /**
+     * with_capacity_in which returns error instead of aborting when memory can't be allocated
+     */
+    fn try_with_capacity_in(capacity: usize, alloc: &A) -> Result<Self, TryReserveError>
+    where
+        Self: Sized,
+    {
+        let mut result = Self::with_capacity_in(0, alloc);
+
+        result.try_reserve(capacity)?;
+
+        Ok(result)
+    }
+
+    /**
+     * storage of node which will hold up to max_len elements, by default the same as with_capacity_in
+     */
+    fn node_in(capacity: usize, _max_len: usize, alloc: &A) -> Self
+    where
+        Self: Sized,
+    {
+        Self::with_capacity_in(capacity, alloc)
+    }
+
+    fn try_node_in(capacity: usize, _max_len: usize, alloc: &A) -> Result<Self, TryReserveError>
+    where
+        Self: Sized,
+    {
+        Self::try_with_capacity_in(capacity, alloc)
+    }
+
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>;
+
+    fn push(&mut self, value: T);
+
+    fn pop(&mut self) -> Option<T>;
+
+    fn insert(&mut self, index: usize, value: T);
+
+    fn remove(&mut self, index: usize) -> T;
+
+    fn split_off(&mut self, at: usize) -> Self;
+
+    fn append(&mut self, other: &mut Self);
+
+    /**
+     * moves elements from at on to the end of other, allocates only if other has no room for them
+     */
+    fn split_off_into(&mut self, at: usize, other: &mut Self) {
+        let start = other.len();
+
+        while self.len() > at {
+            other.push(self.pop().unwrap());
+        }
+
+        other[start..].reverse();
+    }
+
+    /**
+     * number of elements storage keeps on heap, inline elements are not counted
+     */
+    fn heap_capacity(&self) -> usize;
+}
+
+impl<T> NodeVec<T> for Vec<T> {
+    fn with_capacity_in(capacity: usize, _alloc: &()) -> Self {
+        Vec::with_capacity(capacity)
+    }
+
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        Vec::try_reserve(self, additional)
+    }
+
+    fn push(&mut self, value: T) {
+        Vec::push(self, value)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        Vec::pop(self)
+    }
+
+    fn insert(&mut self, index: usize, value: T) {
+        Vec::insert(self, index, value)
+    }
+
+    fn remove(&mut self, index: usize) -> T {
+        Vec::remove(self, index)
+    }
+
+    fn split_off(&mut self, at: usize) -> Self {
+        Vec::split_off(self, at)
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        Vec::append(self, other)
+    }
+
+    fn split_off_into(&mut self, at: usize, other: &mut Self) {
+        other.extend(self.drain(at..))
+    }
+
+    fn heap_capacity(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T, const N: usize, const M: usize> NodeVec<T> for InlineVec<T, N, M> {
+    fn with_capacity_in(capacity: usize, _alloc: &()) -> Self {
+        InlineVec::with_capacity(capacity)
+    }
+
+    /**
+     * node which can outgrow inline slots starts on heap, so it is never moved there from inline slots
+     */
+    fn node_in(capacity: usize, max_len: usize, _alloc: &()) -> Self {
+        if max_len > N * M {
+            return InlineVec::on_heap(capacity);
+        }
+
+        InlineVec::with_capacity(capacity)
+    }
+
+    fn try_node_in(capacity: usize, max_len: usize, _alloc: &()) -> Result<Self, TryReserveError> {
+        if max_len > N * M {
+            return InlineVec::try_on_heap(capacity);
+        }
+
+        Ok(InlineVec::new())
+    }
+
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        InlineVec::try_reserve(self, additional)
+    }
+
+    fn push(&mut self, value: T) {
+        InlineVec::push(self, value)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        InlineVec::pop(self)
+    }
+
+    fn insert(&mut self, index: usize, value: T) {
+        InlineVec::insert(self, index, value)
+    }
+
+    fn remove(&mut self, index: usize) -> T {
+        InlineVec::remove(self, index)
+    }
+
+    fn split_off(&mut self, at: usize) -> Self {
+        InlineVec::split_off(self, at)
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        InlineVec::append(self, other)
+    }
+
+    fn heap_capacity(&self) -> usize {
+        InlineVec::heap_capacity(self)
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T, A: Allocator + Clone> NodeVec<T, A> for Vec<T, A> {
+    fn with_capacity_in(capacity: usize, alloc: &A) -> Self {
+        Vec::with_capacity_in(capacity, alloc.clone())
+    }
+
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        Vec::try_reserve(self, additional)
+    }
+
+    fn push(&mut self, value: T) {
+        Vec::push(self, value)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        Vec::pop(self)
+    }
+
+    fn insert(&mut self, index: usize, value: T) {
+        Vec::insert(self, index, value)
+    }
+
+    fn remove(&mut self, index: usize) -> T {
+        Vec::remove(self, index)
+    }
+
+    fn split_off(&mut self, at: usize) -> Self {
+        Vec::split_off(self, at)
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        Vec::append(self, other)
+    }
+
+    fn split_off_into(&mut self, at: usize, other: &mut Self) {
+        other.extend(self.drain(at..))
+    }
+
+    fn heap_capacity(&self) -> usize {
+        self.capacity()
+    }
+}
+
+/**
+ * how nodes of BTree store keys and children, the same algorithms run over any layout
+ * Buf keeps arena of nodes, free list and paths of insert and delete
+ */
+pub trait NodeLayout {
+    type Alloc: Clone;
+    type Keys<T>: NodeVec<T, Self::Alloc>;
+    type Children: NodeVec<NodeId, Self::Alloc>;
+    type Buf<U>: NodeVec<U, Self::Alloc>;
+}
+
+/**
+ * default layout, t is chosen at runtime, storage of node is Vec sized by t
+ * with smallvec feature keys and children are stored inside node for t <= 8 (15 keys, 16 children)
+ * nodes of bigger t keep keys and children on heap from the start and leave inline slots unused,
+ * so they cost 15 keys and 16 ids more than Vec, for such t build without smallvec or use FixedT
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RuntimeT;
+
+#[cfg(feature = "smallvec")]
+impl NodeLayout for RuntimeT {
+    type Alloc = ();
+    type Keys<T> = InlineVec<T, 15>;
+    type Children = InlineVec<NodeId, 16>;
+    type Buf<U> = Vec<U>;
+}
+
+#[cfg(not(feature = "smallvec"))]
+impl NodeLayout for RuntimeT {
+    type Alloc = ();
+    type Keys<T> = Vec<T>;
+    type Children = Vec<NodeId>;
+    type Buf<U> = Vec<U>;
+}
+
+/**
+ * layout for t = N known at compile time, see BTree::new_fixed
+ * keys and children live in arrays of 2N slots inside node, so node never allocates
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedT<const N: usize>;
+
+impl<const N: usize> NodeLayout for FixedT<N> {
+    type Alloc = ();
+    type Keys<T> = InlineVec<T, N, 2>;
+    type Children = InlineVec<NodeId, N, 2>;
+    type Buf<U> = Vec<U>;
+}
+
+/**
+ * layout taking all memory of tree from allocator A, e.g. bump arena of one request, see BTree::new_in
+ * keys, children, arena of nodes and paths of insert and delete are Vecs in A, t is chosen at runtime
+ * results handed out of tree, like to_vec, and scratch of bulk loads stay on global heap
+ */
+#[cfg(feature = "allocator_api")]
+pub struct InAlloc<A>(PhantomData<A>);
+
+#[cfg(feature = "allocator_api")]
+impl<A: Allocator + Clone> NodeLayout for InAlloc<A> {
+    type Alloc = A;
+    type Keys<T> = Vec<T, A>;
+    type Children = Vec<NodeId, A>;
+    type Buf<U> = Vec<U, A>;
+}
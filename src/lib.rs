@@ -0,0 +1,1679 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+extern crate alloc;
+
+#[cfg(feature = "allocator_api")]
+use alloc::alloc::Allocator;
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeSet, TryReserveError};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::convert::Infallible;
+use core::fmt::{self, Debug};
+
+#[cfg(feature = "metrics")]
+use metrics::Counters;
+use node_store::{Event, NodeStore};
+use observer::Change;
+
+mod aggregate;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod ascii;
+#[cfg(feature = "async")]
+mod async_db;
+#[cfg(feature = "std")]
+mod backup;
+#[cfg(feature = "std")]
+mod batch;
+#[cfg(feature = "std")]
+mod cache;
+mod codec;
+#[cfg(feature = "std")]
+mod concurrent;
+#[cfg(feature = "std")]
+mod crc32;
+#[cfg(feature = "std")]
+mod db;
+mod dot;
+#[cfg(feature = "std")]
+mod dump;
+#[cfg(feature = "std")]
+mod encryption;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod fault;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "std")]
+mod header;
+mod histogram;
+mod inline_vec;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "latch")]
+mod latched;
+mod layout;
+#[cfg(feature = "std")]
+mod lock;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "std")]
+mod mmap;
+mod node_store;
+mod observer;
+#[cfg(feature = "std")]
+mod olc;
+#[cfg(feature = "std")]
+mod options;
+mod ordered;
+#[cfg(feature = "std")]
+mod page;
+#[cfg(any(debug_assertions, feature = "paranoid"))]
+mod paranoid;
+#[cfg(feature = "std")]
+mod pager;
+mod persistent;
+#[cfg(feature = "postcard")]
+mod postcard;
+mod prefix_tree;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "std")]
+mod replication;
+#[cfg(feature = "std")]
+mod self_test;
+#[cfg(feature = "serde")]
+mod serde;
+#[cfg(feature = "std")]
+mod sharded;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+mod snapshot;
+#[cfg(feature = "std")]
+mod storage;
+#[cfg(feature = "std")]
+mod store_script;
+#[cfg(feature = "std")]
+mod wal;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use aggregate::{AggBTree, Count, MaxBy, MinBy, Monoid, SumBy};
+#[cfg(feature = "arbitrary")]
+pub use arbitrary::{check_stores, Shape};
+#[cfg(feature = "async")]
+pub use async_db::{AsyncDb, DbFuture, DEFAULT_WORKERS};
+#[cfg(feature = "std")]
+pub use backup::BackupManifest;
+#[cfg(feature = "std")]
+pub use batch::{Op, WriteBatch};
+#[cfg(feature = "std")]
+pub use cache::PageCache;
+pub use codec::Codec;
+#[cfg(feature = "std")]
+pub use concurrent::ConcurrentBTree;
+#[cfg(feature = "std")]
+pub use db::{
+    Backup, BackupReport, BackgroundFlush, CacheGuard, Db, DbIter, DiskStats, OpenStats, Problem, ReadPath, ReadTxn,
+    Repair, SalvageReport, Srdb, StreamProgress, SyncMode, Table, TreeDigest, VerifyMode, VerifyReport, WriteTxn,
+    DEFAULT_CACHE_PAGES, DEFAULT_WAL_LIMIT, DEFAULT_WAL_SEGMENT_SIZE, MAX_ENTRY_SIZE, PROGRESS_KEYS,
+};
+#[cfg(feature = "std")]
+pub use error::Error;
+#[cfg(feature = "std")]
+pub use fault::{FaultyStorage, Faults, MemStorage};
+#[cfg(feature = "ffi")]
+pub use ffi::{
+    srdb_contains, srdb_delete, srdb_free, srdb_insert, srdb_iter_free, srdb_iter_key, srdb_iter_new, srdb_iter_next,
+    srdb_iter_valid, srdb_len, srdb_new, SrdbHandle, SrdbIter, SRDB_INVALID, SRDB_NULL, SRDB_OK, SRDB_PANIC,
+};
+pub use histogram::{LevelHistogram, TreeHistogram};
+pub use inline_vec::InlineVec;
+#[cfg(feature = "latch")]
+pub use latched::LatchedBTree;
+#[cfg(feature = "allocator_api")]
+pub use layout::InAlloc;
+pub use layout::{FixedT, NodeLayout, NodeVec, RuntimeT};
+#[cfg(feature = "metrics")]
+pub use metrics::Metrics;
+pub use observer::{RootChange, Side, TreeObserver};
+#[cfg(feature = "std")]
+pub use olc::OlcBTree;
+#[cfg(feature = "std")]
+pub use options::{SrdbOptions, MIN_CACHE_PAGES};
+pub use ordered::{Comparator, NaturalOrder, PartialKey, TotalF64};
+#[cfg(feature = "std")]
+pub use page::max_t;
+#[cfg(feature = "std")]
+pub use pager::{PageId, Pager, FORMAT_VERSION, HEADER_PAGE, MAX_PAGE_SIZE, MIN_PAGE_SIZE, PAGE_SIZE};
+pub use persistent::PersistentBTree;
+pub use prefix_tree::{PrefixBTree, PrefixKey};
+#[cfg(feature = "python")]
+pub use python::{python_module, PyBTree, PyMap};
+#[cfg(feature = "std")]
+pub use replication::{bootstrap_follower, follow_primary, serve_follower, HEARTBEAT_INTERVAL};
+#[cfg(feature = "std")]
+pub use self_test::{SelfTestFailure, SelfTestOp};
+#[cfg(feature = "std")]
+pub use sharded::{ShardedBTree, ShardedIter};
+#[cfg(feature = "std")]
+pub use shared::{MapEntry, SharedBTree, SharedMap, SnapshotIter};
+#[cfg(feature = "std")]
+pub use snapshot::SnapshotError;
+#[cfg(feature = "std")]
+pub use storage::Storage;
+#[cfg(feature = "std")]
+pub use store_script::{ScriptOp, StoreScript};
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmBTree, WasmStringBTree};
+
+/**
+ * index of node inside BTree arena
+ */
+type NodeId = u32;
+
+/**
+ * first and last are leaves of subtree holding its min and max key, they stand in for copies of keys
+ */
+#[allow(dead_code)]
+struct Node<T: Debug, L: NodeLayout = RuntimeT> {
+    leaf: bool,
+    count: usize,
+    keys: L::Keys<T>,
+    children: L::Children,
+    first: NodeId,
+    last: NodeId,
+}
+
+impl<T: Debug, L: NodeLayout> Clone for Node<T, L>
+where
+    L::Keys<T>: Clone,
+    L::Children: Clone,
+{
+    fn clone(&self) -> Self {
+        Node {
+            leaf: self.leaf,
+            count: self.count,
+            keys: self.keys.clone(),
+            children: self.children.clone(),
+            first: self.first,
+            last: self.last,
+        }
+    }
+}
+
+impl<T: Debug, L: NodeLayout> Debug for Node<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("leaf", &self.leaf)
+            .field("count", &self.count)
+            .field("keys", &&*self.keys)
+            .field("children", &&*self.children)
+            .field("first", &self.first)
+            .field("last", &self.last)
+            .finish()
+    }
+}
+
+#[allow(dead_code)]
+impl<T: Debug, L: NodeLayout> Node<T, L> {
+    fn empty_in(t: usize, alloc: &L::Alloc) -> Self {
+        Node {
+            keys: L::Keys::node_in(t, (2 * t).saturating_sub(1), alloc),
+            children: L::Children::node_in(t + 1, 2 * t, alloc),
+            count: 0,
+            leaf: false,
+            first: 0,
+            last: 0,
+        }
+    }
+
+    fn try_empty_in(t: usize, alloc: &L::Alloc) -> Result<Self, TryReserveError> {
+        Ok(Node {
+            keys: L::Keys::try_node_in(t, (2 * t).saturating_sub(1), alloc)?,
+            children: L::Children::try_node_in(t + 1, 2 * t, alloc)?,
+            ..Node::vacant(alloc)
+        })
+    }
+
+    /**
+     * stands in arena slot of node which was moved out or freed, holds no storage
+     */
+    fn vacant(alloc: &L::Alloc) -> Self {
+        Node {
+            keys: L::Keys::with_capacity_in(0, alloc),
+            children: L::Children::with_capacity_in(0, alloc),
+            count: 0,
+            leaf: false,
+            first: 0,
+            last: 0,
+        }
+    }
+
+    fn leaf_in(t: usize, alloc: &L::Alloc) -> Self {
+        Node {
+            leaf: true,
+            ..Node::empty_in(t, alloc)
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+#[allow(dead_code)]
+impl<T: Debug> Node<T> {
+    fn empty(t: usize) -> Self {
+        Node::empty_in(t, &())
+    }
+
+    fn leaf(t: usize) -> Self {
+        Node::leaf_in(t, &())
+    }
+
+    /**
+     * node handed to shared insert and delete by store of tree whose nodes are of other kind
+     */
+    fn from_parts(leaf: bool, keys: impl IntoIterator<Item = T>, children: impl IntoIterator<Item = NodeId>) -> Self {
+        let mut node: Node<T> = Node::vacant(&());
+
+        node.keys.extend(keys);
+        node.children.extend(children);
+        node.count = node.keys.len();
+        node.leaf = leaf;
+
+        node
+    }
+}
+
+/**
+ * chunks shorter than this are inserted key by key
+ */
+const BATCH_MIN_LEN: usize = 64;
+
+/**
+ * largest t accepted from serialized trees, node storage is sized by t before any key is read,
+ * so t of untrusted input is bounded by it; nodes of 8191 keys are far beyond any useful tree
+ */
+pub const MAX_T: usize = 1 << 12;
+
+/**
+ * default size of keys storage of one node for auto tuned t
+ * about eight cache lines, so in-node search stays cheap
+ */
+const DEFAULT_NODE_BYTES: usize = 512;
+
+/**
+ * shape of tree, see BTree::stats
+ * fill is average share of 2t - 1 key slots occupied in nodes
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    pub len: usize,
+    pub height: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    pub fill: f64,
+}
+
+/**
+ * estimated memory consumed by tree, see BTree::memory_usage
+ * *_bytes are occupied parts, *_capacity_bytes are allocated parts of heap buffers,
+ * node_bytes is arena with Node structs, payload_bytes is owned data of keys
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryUsage {
+    pub nodes: usize,
+    pub key_bytes: usize,
+    pub key_capacity_bytes: usize,
+    pub child_bytes: usize,
+    pub child_capacity_bytes: usize,
+    pub node_bytes: usize,
+    pub payload_bytes: usize,
+    pub total: usize,
+}
+
+/**
+ * number of nodes before and after BTree::compact
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompactReport {
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+}
+
+/**
+ * all nodes live in one arena owned by tree
+ * children are referred by index, removed nodes go to free list
+ * tree holds no pointers, so it is Send when keys are and Sync when keys are: it moves between threads
+ * and is shared behind Mutex or RwLock like Vec, see assertions below
+ */
+pub struct BTree<T: Debug, C: Comparator<T> = NaturalOrder, L: NodeLayout = RuntimeT> {
+    nodes: L::Buf<Node<T, L>>,
+    free: L::Buf<NodeId>,
+    root: NodeId,
+    len: usize,
+    t: usize,
+    cmp: C,
+    alloc: L::Alloc,
+    /**
+     * nodes try_insert prepared for its splits, empty between operations
+     */
+    spare: L::Buf<Node<T, L>>,
+    /**
+     * nodes changed by current insert or delete, checked and cleared after it
+     */
+    #[cfg(any(debug_assertions, feature = "paranoid"))]
+    touched: L::Buf<NodeId>,
+    #[cfg(feature = "metrics")]
+    metrics: Counters,
+    observer: Option<Box<dyn TreeObserver<T>>>,
+}
+
+/**
+ * tree of string keys which may borrow from one backing string, e.g. slices of a file read once
+ * splits, merges and borrows between siblings move keys and never clone them,
+ * so borrowed keys stay borrowed and tree allocates only for nodes, but it can't outlive the string;
+ * into_owned makes tree 'static at cost of one copy per borrowed key
+ * lookups take &str, see get, contains_key and remove
+ */
+pub type StrTree<'a> = BTree<Cow<'a, str>>;
+
+impl<'a> StrTree<'a> {
+    /**
+     * copies borrowed keys, so tree no longer depends on backing string, nodes are rebuilt fully packed
+     */
+    pub fn into_owned(self) -> StrTree<'static> {
+        let t = self.t;
+        let keys = Vec::from(self).into_iter().map(|key| Cow::Owned(key.into_owned())).collect();
+
+        BTree::from_sorted(t, keys, 1.0)
+    }
+}
+
+impl<T: Debug, C: Comparator<T> + Clone, L: NodeLayout> Clone for BTree<T, C, L>
+where
+    Node<T, L>: Clone,
+{
+    fn clone(&self) -> Self {
+        let mut nodes = L::Buf::with_capacity_in(self.nodes.len(), &self.alloc);
+        let mut free = L::Buf::with_capacity_in(self.free.len(), &self.alloc);
+
+        nodes.extend(self.nodes.iter().cloned());
+        free.extend(self.free.iter().copied());
+
+        BTree {
+            nodes,
+            free,
+            root: self.root,
+            len: self.len,
+            t: self.t,
+            cmp: self.cmp.clone(),
+            alloc: self.alloc.clone(),
+            spare: L::Buf::with_capacity_in(0, &self.alloc),
+            #[cfg(any(debug_assertions, feature = "paranoid"))]
+            touched: L::Buf::with_capacity_in(0, &self.alloc),
+            #[cfg(feature = "metrics")]
+            metrics: Counters::default(),
+            observer: None,
+        }
+    }
+}
+
+/**
+ * fails to compile once tree types stop being Send or Sync for keys that are, e.g. when raw pointer gets into node
+ * persistent and shared trees share nodes between threads, so they are Send and Sync for keys that are both
+ */
+#[allow(dead_code)]
+const _: () = {
+    fn send<S: Send>() {}
+    fn sync<S: Sync>() {}
+
+    fn send_trees<T: Ord + Debug + Send>() {
+        send::<BTree<T>>();
+        send::<BTree<T, NaturalOrder, FixedT<4>>>();
+    }
+
+    fn sync_trees<T: Ord + Debug + Sync>() {
+        sync::<BTree<T>>();
+        sync::<BTree<T, NaturalOrder, FixedT<4>>>();
+        sync::<Iter<'_, T>>();
+        send::<Iter<'_, T>>();
+    }
+
+    fn shared_trees<T: Ord + Clone + Debug + Send + Sync>() {
+        send::<PersistentBTree<T>>();
+        sync::<PersistentBTree<T>>();
+    }
+
+    #[cfg(feature = "std")]
+    fn locked_trees<T: Ord + Clone + Debug + Send + Sync>() {
+        send::<SharedBTree<T>>();
+        sync::<SharedBTree<T>>();
+        send::<SnapshotIter<T>>();
+        send::<SharedMap<T, T>>();
+        sync::<SharedMap<T, T>>();
+        send::<ConcurrentBTree<T>>();
+        sync::<ConcurrentBTree<T>>();
+        send::<OlcBTree<T>>();
+        sync::<OlcBTree<T>>();
+        send::<ShardedBTree<T>>();
+        sync::<ShardedBTree<T>>();
+        send::<ShardedIter<T>>();
+        #[cfg(feature = "latch")]
+        send::<LatchedBTree<T>>();
+        #[cfg(feature = "latch")]
+        sync::<LatchedBTree<T>>();
+    }
+
+    fn prefix_trees<K: PrefixKey + Send + Sync>() {
+        send::<PrefixBTree<K>>();
+        sync::<PrefixBTree<K>>();
+    }
+
+    #[cfg(feature = "std")]
+    fn db() {
+        send::<Db>();
+        sync::<Db>();
+        send::<ReadTxn>();
+        sync::<ReadTxn>();
+        sync::<WriteTxn<'_>>();
+        #[cfg(feature = "async")]
+        send::<AsyncDb>();
+        #[cfg(feature = "async")]
+        sync::<AsyncDb>();
+    }
+};
+
+/**
+ * fails to compile once core tree needs std, checked by cargo build --no-default-features
+ */
+#[cfg(not(feature = "std"))]
+#[allow(dead_code)]
+const _: () = {
+    fn core_tree() -> Result<bool, String> {
+        let mut tree = BTree::new(2);
+
+        tree.insert_batch((0..100u32).collect());
+        tree.insert(100);
+        tree.delete(&7);
+        tree.check_invariants()?;
+
+        Ok(tree.contains(100) && !tree.contains(7) && tree.iter().count() == 100)
+    }
+};
+
+/**
+ * fails to compile once BTree needs keys to be Clone outside of to_vec and bulk_load,
+ * keys like unique handles are moved between nodes, never copied
+ */
+#[allow(dead_code)]
+const _: () = {
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Handle(Box<[u8]>);
+
+    fn unique_keys() -> Result<(), String> {
+        let mut tree = BTree::new(2);
+
+        tree.insert(Handle(Box::new([2])));
+        tree.insert_batch(vec![Handle(Box::new([1])), Handle(Box::new([3]))]);
+        tree.delete(&Handle(Box::new([1])));
+        tree.compact();
+        tree.contains(Handle(Box::new([2])));
+        tree.find(|key| key.0.as_ref().cmp(&[3]));
+        tree.iter().for_each(drop);
+        tree.stats();
+        tree.memory_usage();
+        tree.to_ascii_string();
+
+        BTree::from_sorted(2, vec![Handle(Box::new([1]))], 1.0).check_invariants()?;
+        tree.check_invariants()
+    }
+};
+
+/**
+ * fails to compile once trees in custom allocator lose operations of global heap ones
+ */
+#[cfg(feature = "allocator_api")]
+#[allow(dead_code)]
+const _: () = {
+    fn allocated_tree() -> Result<bool, String> {
+        let mut tree = BTree::new_in(2, alloc::alloc::Global);
+
+        tree.insert_batch((0..100u32).collect());
+        tree.delete(&7);
+        tree.compact();
+        tree.clone().check_invariants()?;
+
+        Ok(tree.contains(99) && tree.remove(&8).is_some() && Vec::from(tree).len() == 98)
+    }
+};
+
+/**
+ * keys in order, node layout is not shown, see print_ascii for it
+ */
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> Debug for BTree<T, C, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BTree").field("t", &self.t).field("keys", &self.iter().collect::<Vec<_>>()).finish()
+    }
+}
+
+impl<T: Ord + Debug> BTree<T> {
+    pub fn new(t: usize) -> BTree<T> {
+        BTree::new_with_comparator(t, NaturalOrder)
+    }
+
+    /**
+     * creates tree with t chosen from key size, see with_node_bytes
+     */
+    pub fn with_auto_t() -> BTree<T> {
+        Self::with_node_bytes(DEFAULT_NODE_BYTES)
+    }
+
+    /**
+     * creates tree with t chosen so that full node keeps about node_bytes of keys:
+     * 2t - 1 = node_bytes / size_of::<T>(), but t is never less than 2
+     * e.g. for 512 bytes u64 keys give t = 32, 200-byte keys give t = 2
+     */
+    pub fn with_node_bytes(node_bytes: usize) -> BTree<T> {
+        Self::new(Self::auto_t(node_bytes))
+    }
+
+    /**
+     * same as bulk_load_with_fill, but takes ownership and moves keys into nodes
+     * nodes are built level by level from leaves, insert is never called
+     */
+    pub fn from_sorted(t: usize, sorted: Vec<T>, fill: f64) -> BTree<T> {
+        let mut tree = BTree::new(t);
+
+        tree.load_sorted(sorted, fill);
+
+        tree
+    }
+}
+
+/**
+ * conversions with std collections, trees built from them get t of with_auto_t and fully packed nodes
+ * BTreeSet keeps one key of equal ones, so duplicates of tree are lost on the way to it
+ */
+impl<T: Ord + Debug> From<BTreeSet<T>> for BTree<T> {
+    fn from(set: BTreeSet<T>) -> Self {
+        BTree::from_sorted(Self::auto_t(DEFAULT_NODE_BYTES), set.into_iter().collect(), 1.0)
+    }
+}
+
+impl<T: Ord + Debug, C: Comparator<T>, L: NodeLayout> From<BTree<T, C, L>> for BTreeSet<T> {
+    fn from(mut tree: BTree<T, C, L>) -> Self {
+        tree.take_sorted().into_iter().collect()
+    }
+}
+
+/**
+ * keys in order of tree, duplicates included, moved out without copies
+ */
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> From<BTree<T, C, L>> for Vec<T> {
+    fn from(mut tree: BTree<T, C, L>) -> Self {
+        tree.take_sorted()
+    }
+}
+
+impl<T: Debug, C: Comparator<T>> BTree<T, C> {
+    /**
+     * tree ordered by cmp instead of Ord of keys, e.g. case-insensitive strings or reverse order,
+     * keys equal by cmp are duplicates, whatever Eq of keys says
+     * cmp is part of tree type, so trees of different comparator types can't be mixed, e.g. by append
+     */
+    pub fn new_with_comparator(t: usize, cmp: C) -> BTree<T, C> {
+        BTree::with_layout(t, cmp, ())
+    }
+}
+
+impl<T: Ord + Debug, const N: usize> BTree<T, NaturalOrder, FixedT<N>> {
+    /**
+     * tree with t = N fixed at compile time, keys and children are kept in arrays inside nodes,
+     * so inserts and deletes never allocate for nodes beyond arena slots, see FixedT
+     */
+    pub fn new_fixed() -> Self {
+        BTree::new_fixed_with_comparator(NaturalOrder)
+    }
+}
+
+impl<T: Debug, C: Comparator<T>, const N: usize> BTree<T, C, FixedT<N>> {
+    pub fn new_fixed_with_comparator(cmp: C) -> Self {
+        const { assert!(N >= 2, "t must be at least 2") };
+
+        BTree::with_layout(N, cmp, ())
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T: Ord + Debug, A: Allocator + Clone> BTree<T, NaturalOrder, InAlloc<A>> {
+    /**
+     * tree taking its nodes, arena and memory of inserts and deletes from alloc, see InAlloc
+     * dropping tree gives all of it back to alloc
+     */
+    pub fn new_in(t: usize, alloc: A) -> Self {
+        BTree::new_in_with_comparator(t, NaturalOrder, alloc)
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T: Debug, C: Comparator<T>, A: Allocator + Clone> BTree<T, C, InAlloc<A>> {
+    pub fn new_in_with_comparator(t: usize, cmp: C, alloc: A) -> Self {
+        BTree::with_layout(t, cmp, alloc)
+    }
+}
+
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    fn with_layout(t: usize, cmp: C, alloc: L::Alloc) -> Self {
+        let mut nodes = L::Buf::with_capacity_in(1, &alloc);
+
+        nodes.push(Node::leaf_in(t, &alloc));
+
+        BTree {
+            nodes,
+            free: L::Buf::with_capacity_in(0, &alloc),
+            root: 0,
+            len: 0,
+            t,
+            cmp,
+            spare: L::Buf::with_capacity_in(0, &alloc),
+            #[cfg(any(debug_assertions, feature = "paranoid"))]
+            touched: L::Buf::with_capacity_in(0, &alloc),
+            alloc,
+            #[cfg(feature = "metrics")]
+            metrics: Counters::default(),
+            observer: None,
+        }
+    }
+
+    fn auto_t(node_bytes: usize) -> usize {
+        let keys = node_bytes / core::mem::size_of::<T>().max(1);
+
+        keys.div_ceil(2).max(2)
+    }
+
+    pub fn t(&self) -> usize {
+        self.t
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.cmp
+    }
+
+    /**
+     * replaces keys of tree by sorted ones, see from_sorted
+     */
+    fn load_sorted(&mut self, sorted: Vec<T>, fill: f64) {
+        debug_assert!(
+            sorted.windows(2).all(|pair| self.cmp.compare(&pair[0], &pair[1]).is_le()),
+            "keys must be sorted"
+        );
+
+        let t = self.t;
+        let len = sorted.len();
+        let per_node = Self::keys_per_node(t, fill) + 1;
+        let width = Self::level_width(len + 1, per_node, t);
+
+        let (leaves, delimeters) =
+            Self::build_leaves(t, &self.alloc, sorted.into_iter(), len + 1 - width, width, 0..width);
+
+        self.build_levels(len, per_node, leaves, delimeters)
+    }
+
+    /**
+     * number of keys in node for given fill, kept within node size bounds
+     */
+    fn keys_per_node(t: usize, fill: f64) -> usize {
+        let max = 2 * t - 1;
+
+        ((fill * max as f64 + 0.5) as usize).clamp(t - 1, max)
+    }
+
+    /**
+     * builds leaves with indexes in range out of width leaves sharing leaf_keys keys
+     * keys hold keys of these leaves and delimeters after each of them except the last leaf of level
+     * returns leaves and delimeters between them
+     */
+    fn build_leaves(
+        t: usize,
+        alloc: &L::Alloc,
+        mut keys: impl Iterator<Item = T>,
+        leaf_keys: usize,
+        width: usize,
+        range: core::ops::Range<usize>,
+    ) -> (Vec<Node<T, L>>, Vec<T>) {
+        let mut leaves = Vec::with_capacity(range.len());
+        let mut delimeters = Vec::with_capacity(range.len());
+
+        for j in range {
+            let mut leaf = Node::<T, L>::leaf_in(t, alloc);
+
+            leaf.keys.extend(keys.by_ref().take(Self::share(leaf_keys, width, j)));
+            leaf.count = leaf.keys.len();
+
+            leaves.push(leaf);
+
+            if j + 1 != width {
+                delimeters.push(keys.next().unwrap());
+            }
+        }
+
+        (leaves, delimeters)
+    }
+
+    /**
+     * places leaves into arena and builds internal levels above them up to root
+     * each node gets about per_node children
+     */
+    fn build_levels(&mut self, len: usize, per_node: usize, leaves: Vec<Node<T, L>>, mut delimeters: Vec<T>) {
+        let t = self.t;
+
+        self.nodes = L::Buf::with_capacity_in(leaves.len() + leaves.len() / t + 1, &self.alloc);
+        self.free = L::Buf::with_capacity_in(0, &self.alloc);
+        self.len = len;
+
+        let mut level: Vec<NodeId> = Vec::with_capacity(leaves.len());
+
+        for leaf in leaves {
+            let id = self.alloc(leaf);
+
+            self.refresh_bounds(id);
+            level.push(id);
+        }
+
+        while level.len() > 1 {
+            let children_count = level.len();
+            let width = Self::level_width(children_count, per_node, t);
+
+            let mut children = level.into_iter();
+            let mut keys = delimeters.into_iter();
+
+            level = Vec::with_capacity(width);
+            delimeters = Vec::with_capacity(width);
+
+            for j in 0..width {
+                let share = Self::share(children_count, width, j);
+                let mut node = Node::<T, L>::empty_in(t, &self.alloc);
+
+                node.children.extend(children.by_ref().take(share));
+                node.keys.extend(keys.by_ref().take(share - 1));
+                node.count = share - 1;
+
+                let id = self.alloc(node);
+
+                self.refresh_bounds(id);
+                level.push(id);
+
+                if j + 1 != width {
+                    delimeters.push(keys.next().unwrap());
+                }
+            }
+        }
+
+        self.root = level[0];
+    }
+
+    /**
+     * number of nodes holding items (children, or keys plus one for leaves)
+     * when each node should hold about per_node of them and at least t
+     */
+    fn level_width(items: usize, per_node: usize, t: usize) -> usize {
+        let mut width = items.div_ceil(per_node).max(1);
+
+        while width > 1 && items / width < t {
+            width -= 1;
+        }
+
+        width
+    }
+
+    /**
+     * size of j-th part when items are spread evenly across width parts
+     */
+    fn share(items: usize, width: usize, j: usize) -> usize {
+        items / width + usize::from(j < items % width)
+    }
+
+    /**
+     * comparison of search loops, counted with metrics feature
+     */
+    fn compare_keys(&self, a: &T, b: &T) -> Ordering {
+        self.record(Event::Comparison);
+        self.cmp.compare(a, b)
+    }
+
+    fn node(&self, id: NodeId) -> &Node<T, L> {
+        &self.nodes[id as usize]
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node<T, L> {
+        &mut self.nodes[id as usize]
+    }
+
+    /**
+     * places node into arena, reusing slot from free list if possible
+     */
+    fn alloc(&mut self, node: Node<T, L>) -> NodeId {
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id as usize] = node;
+
+                id
+            }
+            None => {
+                self.nodes.push(node);
+
+                (self.nodes.len() - 1) as NodeId
+            }
+        }
+    }
+
+    /**
+     * node must be detached from tree
+     * drops its storage and returns slot to free list
+     */
+    fn release(&mut self, id: NodeId) {
+        self.nodes[id as usize] = Node::vacant(&self.alloc);
+        self.free.push(id);
+    }
+
+    /**
+     * recomputes first and last leaves of subtree
+     * they change only when nodes are split, merged or rotated, not when keys come and go in leaves
+     * caches of node children must be up to date
+     */
+    fn refresh_bounds(&mut self, id: NodeId) {
+        let node = self.node(id);
+
+        let (first, last) = if node.leaf {
+            (id, id)
+        } else {
+            (self.node(node.children[0]).first, self.node(node.children[node.count]).last)
+        };
+
+        let node = self.node_mut(id);
+
+        node.first = first;
+        node.last = last;
+    }
+
+    fn min(&self, id: NodeId) -> Option<&T> {
+        self.node(self.node(id).first).keys.first()
+    }
+
+    fn max(&self, id: NodeId) -> Option<&T> {
+        self.node(self.node(id).last).keys.last()
+    }
+
+    /**
+     * moves keys of subtree into out in sorted order, leaving its nodes empty
+     */
+    fn take_into(&mut self, id: NodeId, out: &mut Vec<T>) {
+        let vacant = Node::vacant(&self.alloc);
+        let Node { keys, children, .. } = core::mem::replace(self.node_mut(id), vacant);
+
+        if children.is_empty() {
+            out.extend(keys);
+
+            return;
+        }
+
+        let mut keys = keys.into_iter();
+
+        for child in children.into_iter() {
+            self.take_into(child, out);
+            out.extend(keys.next());
+        }
+    }
+
+    /**
+     * moves all keys out of tree in sorted order, tree becomes empty
+     */
+    fn take_sorted(&mut self) -> Vec<T> {
+        let mut out = Vec::with_capacity(self.len);
+
+        self.take_into(self.root, &mut out);
+
+        self.nodes = L::Buf::with_capacity_in(1, &self.alloc);
+        self.nodes.push(Node::leaf_in(self.t, &self.alloc));
+        self.free = L::Buf::with_capacity_in(0, &self.alloc);
+        self.root = 0;
+        self.len = 0;
+
+        out
+    }
+
+    /**
+     * value goes after keys equal to it
+     */
+    pub fn insert(&mut self, value: T) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("insert", key = ?value).entered();
+
+        self.len += 1;
+
+        node_store::infallible(node_store::insert(self, value));
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.validate_touched("insert");
+    }
+
+    /**
+     * insert which returns error instead of aborting when memory can't be allocated
+     * all memory insert may need is reserved before tree is changed, so on error tree stays as it was
+     */
+    pub fn try_insert(&mut self, value: T) -> Result<(), TryReserveError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("insert", key = ?value).entered();
+
+        let path = match self.reserve_insert(&value) {
+            Ok(path) => path,
+            Err(err) => {
+                self.spare = L::Buf::with_capacity_in(0, &self.alloc);
+
+                return Err(err);
+            }
+        };
+
+        self.len += 1;
+        node_store::infallible(node_store::insert_with_path(self, value, path));
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.validate_touched("try_insert");
+
+        Ok(())
+    }
+
+    /**
+     * walks path insert of value takes, full nodes on it are split by insert and get new right halves prepared,
+     * the others get room for one more key and child, full root also gets new root
+     * returns buffer for path of insert
+     */
+    fn reserve_insert(&mut self, value: &T) -> Result<L::Buf<NodeId>, TryReserveError> {
+        let max = 2 * self.t - 1;
+        let mut id = self.root;
+        let mut splits = usize::from(self.node(id).count == max);
+        let mut height = 1 + splits;
+
+        loop {
+            self.record(Event::Visit);
+
+            let node = &mut self.nodes[id as usize];
+
+            if node.count == max {
+                splits += 1;
+            } else {
+                node.keys.try_reserve(1)?;
+                node.children.try_reserve(usize::from(!node.leaf))?;
+            }
+
+            if node.leaf {
+                break;
+            }
+
+            let node = self.node(id);
+
+            id = node.children[node.keys.partition_point(|key| !self.compare_keys(key, value).is_gt())];
+            height += 1;
+        }
+
+        let path = L::Buf::try_with_capacity_in(height, &self.alloc)?;
+
+        self.nodes.try_reserve(splits.saturating_sub(self.free.len()))?;
+        self.spare.try_reserve(splits)?;
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.touched.try_reserve(height + splits)?;
+
+        for _ in 0..splits {
+            self.spare.push(Node::try_empty_in(self.t, &self.alloc)?);
+        }
+
+        Ok(path)
+    }
+
+    /**
+     * inserts all values of chunk, duplicates are kept the same way as by insert
+     * small chunks are inserted one by one, big ones are sorted and merged into tree in one descent,
+     * values are merged into leaves and nodes which overflowed are split upward
+     */
+    pub fn insert_batch(&mut self, mut chunk: Vec<T>) {
+        if chunk.len() < BATCH_MIN_LEN {
+            for value in chunk {
+                self.insert(value);
+            }
+
+            return;
+        }
+
+        chunk.sort_by(|a, b| self.cmp.compare(a, b));
+
+        self.len += chunk.len();
+        node_store::infallible(node_store::insert_sorted(self, chunk));
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.validate_touched("insert_batch");
+    }
+
+    /**
+     * moves all keys of other into tree, other becomes empty, see insert_batch
+     * keys are sorted again by cmp of tree, comparator of other is not trusted to agree with it
+     */
+    pub fn append(&mut self, other: &mut Self) {
+        self.insert_batch(other.take_sorted())
+    }
+
+    /**
+     * rebuilds tree with fully packed nodes, keys are moved, not cloned
+     * useful after heavy deletions left nodes near minimal fill
+     */
+    pub fn compact(&mut self) -> CompactReport {
+        let nodes_before = self.stats().nodes;
+        let keys = self.take_sorted();
+
+        self.load_sorted(keys, 1.0);
+
+        CompactReport {
+            nodes_before,
+            nodes_after: self.stats().nodes,
+        }
+    }
+
+    /**
+     * removes one occurrence of value
+     * returns status of operation: did element remove
+     */
+    pub fn delete(&mut self, value: &T) -> bool {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("delete", key = ?value).entered();
+
+        let removed = node_store::infallible(node_store::delete(self, |tree, key| tree.cmp.compare(key, value)));
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.validate_touched("delete");
+
+        if removed.is_some() {
+            self.len -= 1;
+        }
+
+        removed.is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /**
+     * counts of splits, merges, borrows, comparisons and visited nodes since creation, clone or reset_metrics
+     * e.g. insert into tree of height h visits h nodes, h + 1 when root splits, as insert goes down once
+     */
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.snapshot()
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn reset_metrics(&mut self) {
+        self.metrics = Counters::default();
+    }
+
+    /**
+     * observer is told about splits, merges, rotations and root changes of insert, insert_batch and delete,
+     * bulk loads, compact and other rebuilds of whole tree are not reported, clones of tree have no observer
+     */
+    pub fn set_observer(&mut self, observer: Box<dyn TreeObserver<T>>) {
+        self.observer = Some(observer);
+    }
+
+    pub fn take_observer(&mut self) -> Option<Box<dyn TreeObserver<T>>> {
+        self.observer.take()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stack = vec![(self.root, 1)];
+        let mut height = 0;
+        let mut nodes = 0;
+        let mut leaves = 0;
+
+        while let Some((id, depth)) = stack.pop() {
+            let node = self.node(id);
+
+            nodes += 1;
+            height = height.max(depth);
+
+            if node.leaf {
+                leaves += 1;
+            }
+
+            stack.extend(node.children.iter().map(|child| (*child, depth + 1)));
+        }
+
+        Stats {
+            len: self.len,
+            height,
+            nodes,
+            leaves,
+            fill: self.len as f64 / (nodes * (2 * self.t - 1)) as f64,
+        }
+    }
+
+    /**
+     * number of keys on every level, root level first
+     */
+    pub fn levels(&self) -> Vec<usize> {
+        let mut levels = vec![];
+        let mut level = vec![self.root];
+
+        while !level.is_empty() {
+            levels.push(level.iter().map(|id| self.node(*id).keys.len()).sum());
+            level = level.iter().flat_map(|id| self.node(*id).children.iter().copied()).collect();
+        }
+
+        levels
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory_usage_with(|_| 0)
+    }
+
+    /**
+     * same as memory_usage, key_size tells how many heap bytes key owns, e.g. String::capacity
+     */
+    pub fn memory_usage_with(&self, key_size: impl Fn(&T) -> usize) -> MemoryUsage {
+        let key = core::mem::size_of::<T>();
+        let child = core::mem::size_of::<NodeId>();
+
+        let mut usage = MemoryUsage {
+            node_bytes: self.nodes.heap_capacity() * core::mem::size_of::<Node<T, L>>() + self.free.heap_capacity() * child,
+            ..MemoryUsage::default()
+        };
+
+        let mut stack = vec![self.root];
+
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+
+            usage.nodes += 1;
+            usage.key_bytes += node.keys.len() * key;
+            usage.key_capacity_bytes += node.keys.heap_capacity() * key;
+            usage.child_bytes += node.children.len() * child;
+            usage.child_capacity_bytes += node.children.heap_capacity() * child;
+            usage.payload_bytes += node.keys.iter().map(&key_size).sum::<usize>();
+
+            stack.extend(node.children.iter());
+        }
+
+        usage.total = usage.node_bytes + usage.key_capacity_bytes + usage.child_capacity_bytes + usage.payload_bytes;
+
+        usage
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, C, L> {
+        Iter::new(self)
+    }
+
+    /**
+     * probe outside of min and max of tree is answered by cached bounds of root, without descent
+     * child is checked the same way before descending, on the side of delimeter it was reached through,
+     * so probe falling into gap between delimeter and nearest key of child stops at that level
+     */
+    pub fn contains(&self, value: T) -> bool {
+        self.find(|key| self.cmp.compare(key, &value)).is_some()
+    }
+
+    /**
+     * stored key for which cmp returns Equal, cmp tells how key compares with the one looked for
+     * and must agree with order of keys, e.g. compare the field keys are ordered by
+     * bounds are checked like by contains
+     */
+    pub fn find(&self, cmp: impl Fn(&T) -> Ordering) -> Option<&T> {
+        let cmp = |key: &T| {
+            self.record(Event::Comparison);
+            cmp(key)
+        };
+
+        let (mut id, mut check_min, mut check_max) = (self.root, true, true);
+
+        loop {
+            if check_min {
+                let min = self.min(id)?;
+
+                match cmp(min) {
+                    Ordering::Less => {}
+                    Ordering::Equal => return Some(min),
+                    _ => return None,
+                }
+            }
+
+            if check_max {
+                let max = self.max(id)?;
+
+                match cmp(max) {
+                    Ordering::Greater => {}
+                    Ordering::Equal => return Some(max),
+                    _ => return None,
+                }
+            }
+
+            self.record(Event::Visit);
+
+            let node = self.node(id);
+
+            match node.keys.binary_search_by(&cmp) {
+                Ok(i) => return Some(&node.keys[i]),
+                Err(_) if node.leaf => return None,
+                Err(i) => (id, check_min, check_max) = (node.children[i], i > 0, i < node.count),
+            }
+        }
+    }
+
+    /**
+     * walks whole tree and checks B-tree properties:
+     * key order, bounds from parent delimeters, node sizes, equal leaf depth and len
+     */
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let t = self.t;
+        let mut stack: Vec<(NodeId, usize, Option<&T>, Option<&T>)> = vec![(self.root, 0, None, None)];
+        let mut leaf_depth: Option<usize> = None;
+        let mut total = 0;
+
+        while let Some((id, depth, lower, upper)) = stack.pop() {
+            let node = self.node(id);
+
+            if node.count != node.keys.len() {
+                return Err(format!("node {}: count {} but {} keys", id, node.count, node.keys.len()));
+            }
+
+            if node.count > 2 * t - 1 {
+                return Err(format!("node {}: {} keys is more than {}", id, node.count, 2 * t - 1));
+            }
+
+            if id != self.root && node.count < t - 1 {
+                return Err(format!("node {}: {} keys is less than {}", id, node.count, t - 1));
+            }
+
+            if node.keys.windows(2).any(|pair| self.cmp.compare(&pair[0], &pair[1]).is_gt()) {
+                return Err(format!("node {}: keys are not sorted {:?}", id, &*node.keys));
+            }
+
+            if let (Some(lower), Some(first)) = (lower, node.keys.first()) {
+                if self.cmp.compare(first, lower).is_lt() {
+                    return Err(format!("node {}: key {:?} is less than delimeter {:?}", id, first, lower));
+                }
+            }
+
+            if let (Some(upper), Some(last)) = (upper, node.keys.last()) {
+                if self.cmp.compare(last, upper).is_gt() {
+                    return Err(format!("node {}: key {:?} is greater than delimeter {:?}", id, last, upper));
+                }
+            }
+
+            let (first, last) = if node.leaf {
+                (id, id)
+            } else {
+                (self.node(node.children[0]).first, self.node(node.children[node.count]).last)
+            };
+
+            if node.first != first || node.last != last {
+                return Err(format!(
+                    "node {}: cached leaves {}..{}, expected {}..{}",
+                    id, node.first, node.last, first, last
+                ));
+            }
+
+            total += node.count;
+
+            if node.leaf {
+                if !node.children.is_empty() {
+                    return Err(format!("node {}: leaf has {} children", id, node.children.len()));
+                }
+
+                match leaf_depth {
+                    None => leaf_depth = Some(depth),
+                    Some(expected) if expected != depth => {
+                        return Err(format!("node {}: leaf at depth {}, expected {}", id, depth, expected));
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            if node.children.len() != node.count + 1 {
+                return Err(format!("node {}: {} keys but {} children", id, node.count, node.children.len()));
+            }
+
+            for i in 0..=node.count {
+                let child_lower = if i == 0 { lower } else { Some(&node.keys[i - 1]) };
+                let child_upper = if i == node.count { upper } else { Some(&node.keys[i]) };
+
+                stack.push((node.children[i], depth + 1, child_lower, child_upper));
+            }
+        }
+
+        if total != self.len {
+            return Err(format!("tree has {} keys, but len is {}", total, self.len));
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * lookups by borrowed form of keys, e.g. &str for String or Cow<str> keys, &[u8] for Vec<u8>
+ * as with std maps Ord of Q must agree with Ord of T, so only trees in natural order have them
+ */
+impl<T: Ord + Debug, L: NodeLayout> BTree<T, NaturalOrder, L> {
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+    {
+        self.find(|stored| stored.borrow().cmp(key))
+    }
+
+    pub fn contains_key<Q: Ord + ?Sized>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    /**
+     * removes one key equal to key and gives it back, unlike delete the probe needs no owned T
+     */
+    pub fn remove<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+    {
+        let removed = node_store::infallible(node_store::delete(self, |_, stored| stored.borrow().cmp(key)));
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.validate_touched("remove");
+
+        if removed.is_some() {
+            self.len -= 1;
+        }
+
+        removed
+    }
+}
+
+/**
+ * arena as store of shared insert and delete, loaded node leaves empty placeholder in its slot until it comes back
+ */
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> NodeStore<T, L> for BTree<T, C, L> {
+    type Error = Infallible;
+
+    fn t(&self) -> usize {
+        self.t
+    }
+
+    fn root(&self) -> NodeId {
+        self.root
+    }
+
+    fn set_root(&mut self, id: NodeId) {
+        self.root = id;
+    }
+
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self.cmp.compare(a, b)
+    }
+
+    fn allocator(&self) -> &L::Alloc {
+        &self.alloc
+    }
+
+    fn load(&mut self, id: NodeId) -> Result<Node<T, L>, Infallible> {
+        let vacant = Node::vacant(&self.alloc);
+
+        Ok(core::mem::replace(self.node_mut(id), vacant))
+    }
+
+    fn store(&mut self, id: NodeId, node: Node<T, L>) -> Result<(), Infallible> {
+        *self.node_mut(id) = node;
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.touch(id);
+
+        Ok(())
+    }
+
+    fn unload(&mut self, id: NodeId, node: Node<T, L>) {
+        *self.node_mut(id) = node;
+    }
+
+    fn count(&mut self, id: NodeId) -> Result<usize, Infallible> {
+        Ok(self.node(id).count)
+    }
+
+    fn alloc(&mut self, node: Node<T, L>) -> Result<NodeId, Infallible> {
+        let id = BTree::alloc(self, node);
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.touch(id);
+
+        Ok(id)
+    }
+
+    fn empty(&mut self) -> Node<T, L> {
+        self.spare.pop().unwrap_or_else(|| Node::empty_in(self.t, &self.alloc))
+    }
+
+    #[cfg(feature = "metrics")]
+    fn record(&self, event: Event) {
+        self.metrics.record(event)
+    }
+
+    fn observe(&mut self, change: Change<'_, T>) {
+        if let Some(observer) = self.observer.as_mut() {
+            change.notify(observer);
+        }
+    }
+
+    fn free(&mut self, id: NodeId) -> Result<(), Infallible> {
+        self.release(id);
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.untouch(id);
+
+        Ok(())
+    }
+
+    fn refresh(&mut self, id: NodeId) -> Result<(), Infallible> {
+        self.refresh_bounds(id);
+
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        self.touch(id);
+
+        Ok(())
+    }
+}
+
+/**
+ * in-order iterator over keys, keeps path from root on explicit stack
+ * each stack entry is node and index of its next key
+ */
+pub struct Iter<'a, T: Debug, C: Comparator<T> = NaturalOrder, L: NodeLayout = RuntimeT> {
+    tree: &'a BTree<T, C, L>,
+    stack: Vec<(NodeId, usize)>,
+}
+
+impl<'a, T: Debug, C: Comparator<T>, L: NodeLayout> Iter<'a, T, C, L> {
+    fn new(tree: &'a BTree<T, C, L>) -> Self {
+        let mut stack = vec![];
+
+        tree.descend(tree.root, &mut stack);
+
+        Iter { tree, stack }
+    }
+}
+
+impl<'a, T: Debug, C: Comparator<T>, L: NodeLayout> Iterator for Iter<'a, T, C, L> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.tree.step(&mut self.stack)
+    }
+}
+
+/**
+ * methods copying keys out of tree or into it, the only ones which need keys to be Clone
+ */
+impl<T: Ord + Clone + Debug> BTree<T> {
+    /**
+     * builds tree from sorted keys with fully packed nodes
+     */
+    pub fn bulk_load(t: usize, sorted: &[T]) -> BTree<T> {
+        Self::bulk_load_with_fill(t, sorted, 1.0)
+    }
+
+    /**
+     * builds tree from sorted keys, fill is share of 2t - 1 keys per node to occupy
+     * fill below 1.0 leaves room for subsequent inserts without splits
+     */
+    pub fn bulk_load_with_fill(t: usize, sorted: &[T], fill: f64) -> BTree<T> {
+        Self::from_sorted(t, sorted.to_vec(), fill)
+    }
+}
+
+impl<T: Clone + Debug, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    pub fn to_vec(&self) -> Vec<T> {
+        let mut out = Vec::with_capacity(self.len);
+
+        self.collect_into(self.root, &mut out);
+
+        out
+    }
+
+    /**
+     * pushes keys of subtree into out in sorted order
+     */
+    fn collect_into(&self, id: NodeId, out: &mut Vec<T>) {
+        let node = self.node(id);
+
+        if node.leaf {
+            out.extend_from_slice(&node.keys);
+
+            return;
+        }
+
+        for i in 0..=node.count {
+            self.collect_into(node.children[i], out);
+            if i != node.count {
+                out.push(node.keys[i].clone());
+            }
+        }
+    }
+}
+
+/**
+ * walk of iterators, stack is path from root, each entry is node and index of its next key
+ */
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    /**
+     * pushes path to leftmost leaf of subtree
+     */
+    fn descend(&self, mut id: NodeId, stack: &mut Vec<(NodeId, usize)>) {
+        loop {
+            stack.push((id, 0));
+
+            let node = self.node(id);
+
+            if node.leaf {
+                return;
+            }
+
+            id = node.children[0];
+        }
+    }
+
+    /**
+     * the next key of walk, moves stack past it
+     */
+    fn step(&self, stack: &mut Vec<(NodeId, usize)>) -> Option<&T> {
+        loop {
+            let (id, i) = *stack.last()?;
+            let node = self.node(id);
+
+            if i == node.count {
+                stack.pop();
+
+                continue;
+            }
+
+            stack.last_mut().unwrap().1 += 1;
+
+            if !node.leaf {
+                self.descend(node.children[i + 1], stack);
+            }
+
+            return Some(&node.keys[i]);
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: Ord + Debug + Send> BTree<T> {
+    /**
+     * same as from_sorted(t, data sorted, 1.0), but sorting and leaf building run on rayon pool,
+     * each task builds leaves of one contiguous range of keys, internal levels are built on caller thread
+     */
+    pub fn par_bulk_load(t: usize, mut data: Vec<T>) -> BTree<T> {
+        use rayon::prelude::*;
+
+        data.par_sort();
+
+        let len = data.len();
+        let per_node = Self::keys_per_node(t, 1.0) + 1;
+        let width = Self::level_width(len + 1, per_node, t);
+        let leaf_keys = len + 1 - width;
+        let tasks = rayon::current_num_threads().min(width);
+
+        let first_key_of_leaf = |j: usize| j * (leaf_keys / width + 1) + j.min(leaf_keys % width);
+        let bounds: Vec<usize> = (0..=tasks).map(|k| k * width / tasks).collect();
+
+        let mut parts = Vec::with_capacity(tasks);
+        let mut rest = data;
+
+        for k in (0..tasks).rev() {
+            parts.push((bounds[k]..bounds[k + 1], rest.split_off(first_key_of_leaf(bounds[k]))));
+        }
+
+        parts.reverse();
+
+        let built: Vec<(Vec<Node<T>>, Vec<T>)> = parts
+            .into_par_iter()
+            .map(|(range, keys)| Self::build_leaves(t, &(), keys.into_iter(), leaf_keys, width, range))
+            .collect();
+
+        let mut leaves = Vec::with_capacity(width);
+        let mut delimeters = Vec::with_capacity(width);
+
+        for (mut part_leaves, mut part_delimeters) in built {
+            leaves.append(&mut part_leaves);
+            delimeters.append(&mut part_delimeters);
+        }
+
+        let mut tree = BTree::new(t);
+
+        tree.build_levels(len, per_node, leaves, delimeters);
+
+        tree
+    }
+}
@@ -0,0 +1,58 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+/**
+ * delay between attempts while waiting for lock
+ */
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/**
+ * advisory lock on database file, flock on unix and LockFileEx on windows
+ * writer holds it exclusively, readers share it
+ * lock belongs to its own handle, so two opens of one path conflict even within one process
+ * it is released when handle is closed, which drop does during unwinding as well
+ */
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /**
+     * locks file at path, fails with Error::DatabaseLocked if it is held in conflicting mode,
+     * with timeout attempts are repeated until it passes
+     */
+    pub fn acquire(path: &Path, exclusive: bool, timeout: Option<Duration>) -> Result<FileLock> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            let result = if exclusive {
+                file.try_lock()
+            } else {
+                file.try_lock_shared()
+            };
+
+            match result {
+                Ok(()) => return Ok(FileLock { file }),
+                Err(TryLockError::Error(error)) => return Err(error.into()),
+                Err(TryLockError::WouldBlock) => {}
+            }
+
+            match deadline {
+                Some(deadline) if Instant::now() < deadline => thread::sleep(RETRY_INTERVAL),
+                _ => return Err(Error::DatabaseLocked),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
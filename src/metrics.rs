@@ -0,0 +1,54 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::node_store::Event;
+
+/**
+ * counters of tree operations since tree was created, cloned or reset, see BTree::metrics
+ * visited are nodes search, insert and delete step into, comparisons are those of keys, probes of find included
+ * borrows are moves of key through parent from sibling of node which was about to underflow
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    pub splits: u64,
+    pub merges: u64,
+    pub borrows: u64,
+    pub comparisons: u64,
+    pub visited: u64,
+}
+
+/**
+ * counters kept by tree, searches count through shared reference, so they are atomics
+ * increments are plain load and store, concurrent searches of one tree may lose some of them
+ */
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    splits: AtomicU64,
+    merges: AtomicU64,
+    borrows: AtomicU64,
+    comparisons: AtomicU64,
+    visited: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn record(&self, event: Event) {
+        let counter = match event {
+            Event::Split => &self.splits,
+            Event::Merge => &self.merges,
+            Event::Borrow => &self.borrows,
+            Event::Comparison => &self.comparisons,
+            Event::Visit => &self.visited,
+        };
+
+        counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Metrics {
+        Metrics {
+            splits: self.splits.load(Ordering::Relaxed),
+            merges: self.merges.load(Ordering::Relaxed),
+            borrows: self.borrows.load(Ordering::Relaxed),
+            comparisons: self.comparisons.load(Ordering::Relaxed),
+            visited: self.visited.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::fs::File;
+use std::io;
+
+/**
+ * read only shared mapping of a whole file
+ * writes through the file are seen by mapping, file must not shrink while it is mapped
+ */
+#[derive(Debug)]
+pub struct Mmap {
+    ptr: *const u8,
+    len: usize,
+}
+
+impl Mmap {
+    /**
+     * maps first len bytes of file, fails with ErrorKind::Unsupported where mmap is not available
+     */
+    #[cfg(unix)]
+    pub fn map(file: &File, len: usize) -> io::Result<Mmap> {
+        use std::os::unix::io::AsRawFd;
+
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty file can not be mapped"));
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Mmap {
+            ptr: ptr as *const u8,
+            len,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn map(_file: &File, _len: usize) -> io::Result<Mmap> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+/**
+ * mapping is read only and owned by its handle, so it can move between threads
+ */
+unsafe impl Send for Mmap {}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
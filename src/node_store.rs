@@ -0,0 +1,760 @@
+use core::cmp::Ordering;
+use core::convert::Infallible;
+use core::fmt::Debug;
+
+use alloc::vec::Vec;
+
+use crate::observer::{Change, RootChange, Side};
+use crate::{Node, NodeId, NodeLayout, NodeVec, RuntimeT};
+
+/**
+ * place nodes of tree live in, BTree keeps them in arena, Db in pages,
+ * PersistentBTree, PrefixBTree, LatchedBTree and OlcBTree in nodes of their own kind
+ * insert and delete below are the only implementation of tree changes for all of them,
+ * so a fix of them fixes every tree at once
+ * load moves node out of store, it must come back by store if it changed, by unload if not, or be freed
+ */
+pub(crate) trait NodeStore<T: Debug, L: NodeLayout = RuntimeT> {
+    type Error;
+
+    fn t(&self) -> usize;
+
+    fn root(&self) -> NodeId;
+
+    fn set_root(&mut self, id: NodeId);
+
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+
+    /**
+     * allocator new nodes and paths of insert and delete come from
+     */
+    fn allocator(&self) -> &L::Alloc;
+
+    fn load(&mut self, id: NodeId) -> Result<Node<T, L>, Self::Error>;
+
+    fn store(&mut self, id: NodeId, node: Node<T, L>) -> Result<(), Self::Error>;
+
+    fn unload(&mut self, id: NodeId, node: Node<T, L>);
+
+    /**
+     * number of keys of node which is not loaded
+     */
+    fn count(&mut self, id: NodeId) -> Result<usize, Self::Error>;
+
+    fn alloc(&mut self, node: Node<T, L>) -> Result<NodeId, Self::Error>;
+
+    /**
+     * storage for node insert creates, right half of split or new root, store may hand out one prepared ahead
+     */
+    fn empty(&mut self) -> Node<T, L> {
+        Node::empty_in(self.t(), self.allocator())
+    }
+
+    /**
+     * node must be loaded and detached from tree
+     */
+    fn free(&mut self, id: NodeId) -> Result<(), Self::Error>;
+
+    /**
+     * called for every node whose subtree changed, children first, for caches kept in nodes
+     */
+    fn refresh(&mut self, _id: NodeId) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /**
+     * called at every step of insert and delete, for stores counting them
+     */
+    fn record(&self, _event: Event) {}
+
+    /**
+     * called at structural changes of insert and delete, for stores reporting them, see TreeObserver
+     */
+    fn observe(&mut self, _change: Change<'_, T>) {}
+}
+
+/**
+ * steps of tree operations, see Metrics
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Event {
+    Split,
+    Merge,
+    Borrow,
+    Comparison,
+    Visit,
+}
+
+pub(crate) fn infallible<R>(result: Result<R, Infallible>) -> R {
+    match result {
+        Ok(value) => value,
+        Err(never) => match never {},
+    }
+}
+
+/**
+ * tells store about change, with tracing feature it is also traced
+ */
+fn changed<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(store: &mut S, change: Change<'_, T>) {
+    #[cfg(feature = "tracing")]
+    change.trace();
+
+    store.observe(change);
+}
+
+fn compare<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(store: &S, a: &T, b: &T) -> Ordering {
+    store.record(Event::Comparison);
+    store.compare(a, b)
+}
+
+fn probe_key<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &S,
+    probe: &impl Fn(&S, &T) -> Ordering,
+    key: &T,
+) -> Ordering {
+    store.record(Event::Comparison);
+    probe(store, key)
+}
+
+/**
+ * value goes after keys equal to it
+ */
+pub(crate) fn insert<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(store: &mut S, value: T) -> Result<(), S::Error> {
+    let path = L::Buf::with_capacity_in(0, store.allocator());
+
+    insert_with_path(store, value, path)
+}
+
+/**
+ * insert which records visited nodes in path, path with room for height + 1 nodes never grows
+ */
+pub(crate) fn insert_with_path<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    value: T,
+    mut path: L::Buf<NodeId>,
+) -> Result<(), S::Error> {
+    let t = store.t();
+    let root = store.root();
+
+    if store.count(root)? == 2 * t - 1 {
+        let mut new_root = store.empty();
+        new_root.children.push(root);
+
+        split(store, &mut new_root, 0, 1)?;
+
+        let new_root = store.alloc(new_root)?;
+
+        store.refresh(new_root)?;
+        store.set_root(new_root);
+        changed(store, Change::Root(RootChange::Grow));
+    }
+
+    insert_nonfull(store, store.root(), value, &mut path)?;
+
+    refresh_path(store, path)
+}
+
+/**
+ * inserts sorted run, each value goes after keys equal to it, the same as inserting values one by one in order
+ * run is split among children by delimeters, so every node on the way is visited once,
+ * values are merged into leaves and nodes which overflowed are split upward
+ */
+pub(crate) fn insert_sorted<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    run: Vec<T>,
+) -> Result<(), S::Error> {
+    let mut pieces = merge_run(store, store.root(), run, 0)?;
+
+    while !pieces.is_empty() {
+        let mut root = store.empty();
+
+        root.children.push(store.root());
+        attach(&mut root, pieces);
+
+        let id = store.alloc(root)?;
+
+        store.set_root(id);
+        changed(store, Change::Root(RootChange::Grow));
+
+        let root = store.load(id)?;
+
+        pieces = split_overflow(store, id, root, 0)?;
+    }
+
+    Ok(())
+}
+
+/**
+ * merges run into subtree of node at depth
+ * returns delimeters and right siblings node was split into, they go after node in its parent
+ */
+fn merge_run<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    id: NodeId,
+    run: Vec<T>,
+    depth: usize,
+) -> Result<Vec<(T, NodeId)>, S::Error> {
+    store.record(Event::Visit);
+
+    let mut node = store.load(id)?;
+
+    if node.leaf {
+        let len = node.keys.len() + run.len();
+        let mut keys = core::mem::replace(&mut node.keys, L::Keys::with_capacity_in(len, store.allocator()))
+            .into_iter()
+            .peekable();
+        let mut run = run.into_iter().peekable();
+
+        while let (Some(key), Some(value)) = (keys.peek(), run.peek()) {
+            if compare(store, value, key).is_lt() {
+                node.keys.push(run.next().unwrap());
+            } else {
+                node.keys.push(keys.next().unwrap());
+            }
+        }
+
+        node.keys.extend(keys);
+        node.keys.extend(run);
+        node.count = len;
+
+        return split_overflow(store, id, node, depth);
+    }
+
+    let mut groups: Vec<(usize, Vec<T>)> = Vec::new();
+    let mut i = 0;
+
+    for value in run {
+        while i < node.count && !compare(store, &node.keys[i], &value).is_gt() {
+            i += 1;
+        }
+
+        match groups.last_mut() {
+            Some((child, group)) if *child == i => group.push(value),
+            _ => groups.push((i, alloc::vec![value])),
+        }
+    }
+
+    let mut grown = Vec::with_capacity(groups.len());
+
+    for (i, group) in groups {
+        grown.push((i, merge_run(store, node.children[i], group, depth + 1)?));
+    }
+
+    if grown.iter().any(|(_, pieces)| !pieces.is_empty()) {
+        let count = node.count + grown.iter().map(|(_, pieces)| pieces.len()).sum::<usize>();
+        let keys = L::Keys::with_capacity_in(count, store.allocator());
+        let children = L::Children::with_capacity_in(count + 1, store.allocator());
+        let mut keys = core::mem::replace(&mut node.keys, keys).into_iter();
+        let children = core::mem::replace(&mut node.children, children);
+        let mut grown = grown.into_iter().peekable();
+
+        for (i, child) in children.into_iter().enumerate() {
+            node.children.push(child);
+
+            if let Some((_, pieces)) = grown.next_if(|(j, _)| *j == i) {
+                attach(&mut node, pieces);
+            }
+
+            node.keys.extend(keys.next());
+        }
+
+        node.count = count;
+    }
+
+    split_overflow(store, id, node, depth)
+}
+
+/**
+ * appends delimeters and right siblings to node, count is left to caller
+ */
+fn attach<T: Debug, L: NodeLayout>(node: &mut Node<T, L>, pieces: Vec<(T, NodeId)>) {
+    for (key, child) in pieces {
+        node.keys.push(key);
+        node.children.push(child);
+    }
+
+    node.count = node.keys.len();
+}
+
+/**
+ * stores loaded node, if it has more than 2t - 1 keys it is split into the fewest nodes which fit,
+ * with keys spread evenly, pieces are cut from the right end
+ * returns delimeters and new right siblings in order
+ */
+fn split_overflow<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    id: NodeId,
+    mut node: Node<T, L>,
+    depth: usize,
+) -> Result<Vec<(T, NodeId)>, S::Error> {
+    let t = store.t();
+    let parts = (node.count + 1).div_ceil(2 * t);
+    let mut pieces = Vec::with_capacity(parts - 1);
+
+    for rest in (2..=parts).rev() {
+        let share = (node.count + 1 - rest) / rest;
+        let at = node.count - share;
+
+        store.record(Event::Split);
+
+        let mut right = store.empty();
+
+        right.leaf = node.leaf;
+        right.count = share;
+
+        node.keys.split_off_into(at, &mut right.keys);
+
+        if !node.leaf {
+            node.children.split_off_into(at, &mut right.children);
+        }
+
+        let median = node.keys.pop().unwrap();
+
+        node.count = at - 1;
+
+        changed(store, Change::Split { depth, left: &node.keys, median: &median, right: &right.keys });
+
+        let right_id = store.alloc(right)?;
+
+        store.refresh(right_id)?;
+        pieces.push((median, right_id));
+    }
+
+    pieces.reverse();
+
+    store.store(id, node)?;
+    store.refresh(id)?;
+
+    Ok(pieces)
+}
+
+/**
+ * removes one key for which probe gives Equal, probe tells how key compares to the searched one
+ * returns removed key
+ */
+pub(crate) fn delete<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    probe: impl Fn(&S, &T) -> Ordering,
+) -> Result<Option<T>, S::Error> {
+    let mut path = L::Buf::with_capacity_in(0, store.allocator());
+    let removed = delete_from(store, store.root(), &probe, &mut path)?;
+
+    refresh_path(store, path)?;
+
+    let root_id = store.root();
+    let root = store.load(root_id)?;
+
+    if root.is_empty() && !root.leaf {
+        store.set_root(root.children[0]);
+        store.free(root_id)?;
+        changed(store, Change::Root(RootChange::Collapse));
+    } else {
+        store.unload(root_id, root);
+    }
+
+    Ok(removed)
+}
+
+fn refresh_path<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(store: &mut S, path: L::Buf<NodeId>) -> Result<(), S::Error> {
+    for &id in path.iter().rev() {
+        store.refresh(id)?;
+    }
+
+    Ok(())
+}
+
+/**
+ * parent is nonfull loaded node
+ * parent.children[i] is full node at depth
+ * storing parent is left to caller
+ */
+fn split<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    parent: &mut Node<T, L>,
+    i: usize,
+    depth: usize,
+) -> Result<(), S::Error> {
+    let t = store.t();
+    let left_id = parent.children[i];
+    let mut left = store.load(left_id)?;
+
+    store.record(Event::Split);
+
+    let mut right = store.empty();
+
+    right.leaf = left.leaf;
+    right.count = t - 1;
+
+    left.keys.split_off_into(t, &mut right.keys);
+
+    if !left.leaf {
+        left.children.split_off_into(t, &mut right.children);
+    }
+
+    left.count = t - 1;
+
+    let median = left.keys.pop().unwrap();
+
+    changed(store, Change::Split { depth, left: &left.keys, median: &median, right: &right.keys });
+
+    let right_id = store.alloc(right)?;
+
+    store.store(left_id, left)?;
+    store.refresh(left_id)?;
+    store.refresh(right_id)?;
+
+    parent.keys.insert(i, median);
+    parent.children.insert(i + 1, right_id);
+    parent.count += 1;
+
+    Ok(())
+}
+
+/**
+ * visited nodes are pushed to path, they must be refreshed after
+ */
+fn insert_nonfull<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    mut id: NodeId,
+    value: T,
+    path: &mut L::Buf<NodeId>,
+) -> Result<(), S::Error> {
+    loop {
+        path.push(id);
+        store.record(Event::Visit);
+
+        let mut node = store.load(id)?;
+
+        if node.leaf {
+            let i = node.keys.partition_point(|key| compare(store, key, &value).is_le());
+
+            node.keys.insert(i, value);
+            node.count += 1;
+
+            return store.store(id, node);
+        }
+
+        let mut i = node.keys.partition_point(|key| !compare(store, key, &value).is_gt());
+
+        if store.count(node.children[i])? != 2 * store.t() - 1 {
+            let child = node.children[i];
+
+            store.unload(id, node);
+            id = child;
+
+            continue;
+        }
+
+        split(store, &mut node, i, path.len())?;
+        if !compare(store, &value, &node.keys[i]).is_lt() {
+            i += 1
+        }
+
+        let child = node.children[i];
+
+        store.store(id, node)?;
+        id = child;
+    }
+}
+
+/**
+ * parent.children[i] has t - 1 keys
+ * moves max key of left sibling through delimeter into parent.children[i]
+ * with cfg srdb_delete_bug delimeter is appended as in borrow_from_right, bug tests/paranoid.rs catches
+ */
+fn borrow_from_left<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    parent: &mut Node<T, L>,
+    i: usize,
+    depth: usize,
+) -> Result<(), S::Error> {
+    let left_id = parent.children[i - 1];
+    let target_id = parent.children[i];
+
+    store.record(Event::Borrow);
+
+    let mut left = store.load(left_id)?;
+    let max_value = left.keys.pop().unwrap();
+    let max_child = if left.leaf { None } else { left.children.pop() };
+    left.count -= 1;
+
+    store.store(left_id, left)?;
+    store.refresh(left_id)?;
+
+    let delimeter_value = core::mem::replace(&mut parent.keys[i - 1], max_value);
+
+    let mut target = store.load(target_id)?;
+    #[cfg(not(srdb_delete_bug))]
+    target.keys.insert(0, delimeter_value);
+    #[cfg(srdb_delete_bug)]
+    target.keys.push(delimeter_value);
+    if let Some(child) = max_child {
+        target.children.insert(0, child);
+    }
+    target.count += 1;
+
+    changed(store, Change::Rotate { depth, side: Side::Left, up: &parent.keys[i - 1], down: &target.keys[0] });
+
+    store.store(target_id, target)
+}
+
+/**
+ * parent.children[i] has t - 1 keys
+ * moves min key of right sibling through delimeter into parent.children[i]
+ */
+fn borrow_from_right<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    parent: &mut Node<T, L>,
+    i: usize,
+    depth: usize,
+) -> Result<(), S::Error> {
+    let target_id = parent.children[i];
+    let right_id = parent.children[i + 1];
+
+    store.record(Event::Borrow);
+
+    let mut right = store.load(right_id)?;
+    let min_value = right.keys.remove(0);
+    let min_child = if right.leaf { None } else { Some(right.children.remove(0)) };
+    right.count -= 1;
+
+    store.store(right_id, right)?;
+    store.refresh(right_id)?;
+
+    let delimeter_value = core::mem::replace(&mut parent.keys[i], min_value);
+
+    let mut target = store.load(target_id)?;
+    target.keys.push(delimeter_value);
+    if let Some(child) = min_child {
+        target.children.push(child);
+    }
+    target.count += 1;
+
+    let down = target.keys.last().unwrap();
+
+    changed(store, Change::Rotate { depth, side: Side::Right, up: &parent.keys[i], down });
+
+    store.store(target_id, target)
+}
+
+/**
+ * parent.children[i] and parent.children[i + 1] have t - 1 keys
+ * merges them into parent.children[i] with delimeter between, right node is freed
+ */
+fn merge<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    parent: &mut Node<T, L>,
+    i: usize,
+    depth: usize,
+) -> Result<(), S::Error> {
+    let right_id = parent.children.remove(i + 1);
+    let delimeter_value = parent.keys.remove(i);
+    let left_id = parent.children[i];
+    parent.count -= 1;
+
+    store.record(Event::Merge);
+
+    let mut left = store.load(left_id)?;
+    let mut right = store.load(right_id)?;
+
+    changed(store, Change::Merge { depth, left: &left.keys, separator: &delimeter_value, right: &right.keys });
+
+    left.keys.push(delimeter_value);
+    left.keys.append(&mut right.keys);
+    left.children.append(&mut right.children);
+    left.count += right.count + 1;
+
+    store.store(left_id, left)?;
+    store.free(right_id)
+}
+
+/**
+ * makes sure parent.children[i] has at least t keys before descending into it, children of parent are at depth
+ * returns index of child to descend, it changes only after merge with left sibling,
+ * and whether parent changed
+ */
+fn fill<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    parent: &mut Node<T, L>,
+    i: usize,
+    depth: usize,
+) -> Result<(usize, bool), S::Error> {
+    let t = store.t();
+
+    if store.count(parent.children[i])? >= t {
+        return Ok((i, false));
+    }
+
+    if i > 0 && store.count(parent.children[i - 1])? >= t {
+        borrow_from_left(store, parent, i, depth)?;
+
+        return Ok((i, true));
+    }
+
+    if i < parent.count && store.count(parent.children[i + 1])? >= t {
+        borrow_from_right(store, parent, i, depth)?;
+
+        return Ok((i, true));
+    }
+
+    if i < parent.count {
+        merge(store, parent, i, depth)?;
+
+        return Ok((i, true));
+    }
+
+    merge(store, parent, i - 1, depth)?;
+
+    Ok((i - 1, true))
+}
+
+/**
+ * fill of child i of loaded node id, node goes back to store, returns child to descend
+ */
+fn descend<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    id: NodeId,
+    mut node: Node<T, L>,
+    i: usize,
+    depth: usize,
+) -> Result<NodeId, S::Error> {
+    let (i, changed) = fill(store, &mut node, i, depth)?;
+    let child = node.children[i];
+
+    if changed {
+        store.store(id, node)?;
+    } else {
+        store.unload(id, node);
+    }
+
+    Ok(child)
+}
+
+/**
+ * node is root or has at least t keys
+ * removes max key of subtree and returns it
+ * visited nodes are pushed to path, they must be refreshed after
+ */
+fn delete_max<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    mut id: NodeId,
+    path: &mut L::Buf<NodeId>,
+) -> Result<T, S::Error> {
+    loop {
+        path.push(id);
+        store.record(Event::Visit);
+
+        let mut node = store.load(id)?;
+
+        if node.leaf {
+            let max_value = node.keys.pop().unwrap();
+            node.count -= 1;
+
+            store.store(id, node)?;
+
+            return Ok(max_value);
+        }
+
+        let last = node.count;
+
+        id = descend(store, id, node, last, path.len())?;
+    }
+}
+
+/**
+ * node is root or has at least t keys
+ * removes min key of subtree and returns it
+ * visited nodes are pushed to path, they must be refreshed after
+ */
+fn delete_min<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    mut id: NodeId,
+    path: &mut L::Buf<NodeId>,
+) -> Result<T, S::Error> {
+    loop {
+        path.push(id);
+        store.record(Event::Visit);
+
+        let mut node = store.load(id)?;
+
+        if node.leaf {
+            let min_value = node.keys.remove(0);
+            node.count -= 1;
+
+            store.store(id, node)?;
+
+            return Ok(min_value);
+        }
+
+        id = descend(store, id, node, 0, path.len())?;
+    }
+}
+
+/**
+ * node is root or has at least t keys
+ * keys are moved between nodes, never cloned
+ * visited nodes are pushed to path, they must be refreshed after
+ * returns removed key, None if there is no key probe gives Equal for
+ */
+fn delete_from<T: Debug, L: NodeLayout, S: NodeStore<T, L>>(
+    store: &mut S,
+    mut id: NodeId,
+    probe: &impl Fn(&S, &T) -> Ordering,
+    path: &mut L::Buf<NodeId>,
+) -> Result<Option<T>, S::Error> {
+    loop {
+        path.push(id);
+        store.record(Event::Visit);
+
+        let mut node = store.load(id)?;
+        let i = node.keys.partition_point(|key| probe_key(store, probe, key).is_lt());
+        let found = i < node.count && probe_key(store, probe, &node.keys[i]).is_eq();
+
+        if node.leaf {
+            if !found {
+                store.unload(id, node);
+
+                return Ok(None);
+            }
+
+            let value = node.keys.remove(i);
+            node.count -= 1;
+
+            store.store(id, node)?;
+
+            return Ok(Some(value));
+        }
+
+        if !found {
+            id = descend(store, id, node, i, path.len())?;
+
+            continue;
+        }
+
+        let left_id = node.children[i];
+        let right_id = node.children[i + 1];
+
+        if store.count(left_id)? >= store.t() {
+            let max_value = delete_max(store, left_id, path)?;
+            let value = core::mem::replace(&mut node.keys[i], max_value);
+
+            store.store(id, node)?;
+
+            return Ok(Some(value));
+        }
+
+        if store.count(right_id)? >= store.t() {
+            let min_value = delete_min(store, right_id, path)?;
+            let value = core::mem::replace(&mut node.keys[i], min_value);
+
+            store.store(id, node)?;
+
+            return Ok(Some(value));
+        }
+
+        merge(store, &mut node, i, path.len())?;
+        store.store(id, node)?;
+
+        id = left_id;
+    }
+}
@@ -0,0 +1,88 @@
+use alloc::boxed::Box;
+
+/**
+ * callbacks of structural changes of BTree, see BTree::set_observer
+ * called synchronously in the middle of insert or delete, depth is that of changed nodes, root has depth 0
+ * and when root grows its old root is split at depth 1, keys are those nodes hold at the moment of call
+ * observer travels with tree, so it must be Send and Sync as tree is
+ */
+pub trait TreeObserver<T>: Send + Sync {
+    /**
+     * full node was split, it keeps left keys, median goes up to parent, right keys go to new right sibling
+     */
+    fn on_split(&mut self, _depth: usize, _left: &[T], _median: &T, _right: &[T]) {}
+
+    /**
+     * two siblings with t - 1 keys are merged into left one with separator from parent between them,
+     * right one is freed
+     */
+    fn on_merge(&mut self, _depth: usize, _left: &[T], _separator: &T, _right: &[T]) {}
+
+    /**
+     * node about to underflow took key from sibling on side: up went from sibling to parent,
+     * down went from parent to node
+     */
+    fn on_rotate(&mut self, _depth: usize, _side: Side, _up: &T, _down: &T) {}
+
+    /**
+     * height of tree changed, on growth new root holds median of last split
+     */
+    fn on_root_change(&mut self, _change: RootChange) {}
+}
+
+/**
+ * sibling key is borrowed from
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RootChange {
+    Grow,
+    Collapse,
+}
+
+/**
+ * change passed from shared insert and delete to store, see NodeStore::observe
+ */
+pub(crate) enum Change<'a, T> {
+    Split { depth: usize, left: &'a [T], median: &'a T, right: &'a [T] },
+    Merge { depth: usize, left: &'a [T], separator: &'a T, right: &'a [T] },
+    Rotate { depth: usize, side: Side, up: &'a T, down: &'a T },
+    Root(RootChange),
+}
+
+impl<T> Change<'_, T> {
+    pub(crate) fn notify(self, observer: &mut Box<dyn TreeObserver<T>>) {
+        match self {
+            Change::Split { depth, left, median, right } => observer.on_split(depth, left, median, right),
+            Change::Merge { depth, left, separator, right } => observer.on_merge(depth, left, separator, right),
+            Change::Rotate { depth, side, up, down } => observer.on_rotate(depth, side, up, down),
+            Change::Root(change) => observer.on_root_change(change),
+        }
+    }
+
+    /**
+     * event of change at TRACE level, inside span of operation which made it
+     */
+    #[cfg(feature = "tracing")]
+    pub(crate) fn trace(&self)
+    where
+        T: core::fmt::Debug,
+    {
+        match self {
+            Change::Split { depth, left, median, right } => {
+                tracing::trace!(depth, left = left.len(), right = right.len(), ?median, "split")
+            }
+            Change::Merge { depth, left, separator, right } => {
+                tracing::trace!(depth, left = left.len(), right = right.len(), ?separator, "merge")
+            }
+            Change::Rotate { depth, side, up, down } => tracing::trace!(depth, ?side, ?up, ?down, "borrow"),
+            Change::Root(RootChange::Grow) => tracing::trace!("root grow"),
+            Change::Root(RootChange::Collapse) => tracing::trace!("root collapse"),
+        }
+    }
+}
@@ -0,0 +1,628 @@
+use std::convert::Infallible;
+use std::fmt::Debug;
+use std::ops::{Bound, RangeBounds};
+use std::ptr;
+use std::sync::PoisonError;
+
+#[cfg(loom)]
+use loom::sync::atomic::{fence, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+#[cfg(loom)]
+use loom::sync::{Mutex, MutexGuard};
+#[cfg(not(loom))]
+use std::sync::atomic::{fence, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+#[cfg(not(loom))]
+use std::sync::{Mutex, MutexGuard};
+
+use crate::node_store::{self, NodeStore};
+use crate::{Node, NodeId};
+
+/**
+ * reader counters of tree, thread counts itself in one of its own, so readers on different cores share no cache line
+ */
+#[cfg(not(loom))]
+const SHARDS: usize = 64;
+#[cfg(loom)]
+const SHARDS: usize = 2;
+
+/**
+ * optimistic attempts of read, after them it holds writers off and reads tree as it is
+ */
+#[cfg(not(loom))]
+const ATTEMPTS: usize = 64;
+#[cfg(loom)]
+const ATTEMPTS: usize = 2;
+
+/**
+ * replaced nodes kept before writer waits for readers to free them
+ * with --cfg loom every writer frees them, so loom explores epochs along with versions
+ */
+#[cfg(not(loom))]
+const GARBAGE: usize = 256;
+#[cfg(loom)]
+const GARBAGE: usize = 1;
+
+/**
+ * shard of thread is only a hint, so it stays std atomic under loom
+ */
+static NEXT_SHARD: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+thread_local! {
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
+}
+
+/**
+ * busy wait, loom has to be told that thread waits for another one
+ */
+fn spin() {
+    #[cfg(loom)]
+    loom::thread::yield_now();
+    #[cfg(not(loom))]
+    std::hint::spin_loop();
+}
+
+/**
+ * readers of both parities of epoch, aligned so every shard has cache line of its own
+ */
+#[repr(align(128))]
+#[derive(Default)]
+struct Shard([AtomicUsize; 2]);
+
+/**
+ * counted reader, nothing it reached is freed until it is dropped
+ */
+struct Pin<'a>(&'a AtomicUsize);
+
+impl Drop for Pin<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone)]
+struct NodeData<T> {
+    leaf: bool,
+    keys: Vec<T>,
+    children: Vec<*mut OlcNode<T>>,
+}
+
+/**
+ * node with version lock: version is odd while writer changes node and grows with every change,
+ * contents are never changed in place, writer replaces them as a whole, so reader sees old or new ones
+ */
+struct OlcNode<T> {
+    version: AtomicU64,
+    data: AtomicPtr<NodeData<T>>,
+}
+
+impl<T> OlcNode<T> {
+    fn new(data: NodeData<T>) -> *mut OlcNode<T> {
+        Box::into_raw(Box::new(OlcNode {
+            version: AtomicU64::new(0),
+            data: AtomicPtr::new(Box::into_raw(Box::new(data))),
+        }))
+    }
+}
+
+/**
+ * contents replaced or node unlinked by writer, freed once no reader which may have reached it is left
+ */
+enum Garbage<T> {
+    Data(*mut NodeData<T>),
+    Node(*mut OlcNode<T>),
+}
+
+impl<T> Garbage<T> {
+    /**
+     * nothing may reach it anymore
+     */
+    fn free(self) {
+        match self {
+            Garbage::Data(data) => drop(unsafe { Box::from_raw(data) }),
+            Garbage::Node(node) => {
+                let node = unsafe { Box::from_raw(node) };
+
+                drop(unsafe { Box::from_raw(node.data.load(Ordering::SeqCst)) });
+            }
+        }
+    }
+}
+
+/**
+ * versions of nodes read saw, read is consistent if none of them changed by the time it ends
+ */
+type Seen<'a> = Vec<(&'a AtomicU64, u64)>;
+
+/**
+ * B-tree for mostly-read workloads with optimistic lock coupling: readers take no latch at all,
+ * they note version of every node they read and check that none changed once they are done,
+ * otherwise they start over, read that keeps conflicting holds writers off after a few attempts
+ * writer locks every node it changes by making its version odd and unlocks them all once operation is done,
+ * so reader seeing any of them in between starts over and operations are atomic for readers,
+ * writers wait for each other on mutex, so only readers scale with cores
+ * node contents are immutable and replaced as a whole, replaced ones are freed once readers of the epoch
+ * they were replaced in are gone, so reader never touches freed memory
+ * insert and delete are the shared ones of node_store, the same as of BTree
+ * with --cfg loom versions, pointers and epochs are loom atomics, so loom explores interleavings of readers and writers
+ */
+pub struct OlcBTree<T: Ord + Clone + Debug> {
+    root_version: AtomicU64,
+    root: AtomicPtr<OlcNode<T>>,
+    len: AtomicUsize,
+    t: usize,
+    writer: Mutex<Vec<Garbage<T>>>,
+    epoch: AtomicUsize,
+    shards: Vec<Shard>,
+}
+
+/**
+ * nodes are shared by raw pointers and read by readers of other threads, so keys must be both Send and Sync
+ */
+unsafe impl<T: Ord + Clone + Debug + Send + Sync> Send for OlcBTree<T> {}
+unsafe impl<T: Ord + Clone + Debug + Send + Sync> Sync for OlcBTree<T> {}
+
+impl<T: Ord + Clone + Debug> Debug for OlcBTree<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OlcBTree").field("t", &self.t).field("keys", &self.to_vec()).finish()
+    }
+}
+
+impl<T: Ord + Clone + Debug> OlcBTree<T> {
+    pub fn new(t: usize) -> OlcBTree<T> {
+        let root = NodeData {
+            leaf: true,
+            keys: Vec::with_capacity(2 * t - 1),
+            children: vec![],
+        };
+
+        OlcBTree {
+            root_version: AtomicU64::new(0),
+            root: AtomicPtr::new(OlcNode::new(root)),
+            len: AtomicUsize::new(0),
+            t,
+            writer: Mutex::new(vec![]),
+            epoch: AtomicUsize::new(0),
+            shards: (0..SHARDS).map(|_| Shard::default()).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * counts reader in shard of its thread, in counter of the current epoch
+     * reader writes counter then reads epoch, writer does the opposite in synchronize, fences keep either of them
+     * from missing the other one
+     */
+    fn pin(&self) -> Pin<'_> {
+        let shard = &self.shards[SHARD.with(|shard| *shard)];
+
+        loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            let readers = &shard.0[epoch & 1];
+
+            readers.fetch_add(1, Ordering::SeqCst);
+            fence(Ordering::SeqCst);
+
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                return Pin(readers);
+            }
+
+            readers.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /**
+     * starts new epoch and waits for readers of the previous one, none of them may be held by caller
+     */
+    fn synchronize(&self) {
+        let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+
+        fence(Ordering::SeqCst);
+
+        for shard in &self.shards {
+            while shard.0[epoch & 1].load(Ordering::SeqCst) != 0 {
+                spin();
+            }
+        }
+    }
+
+    fn lock_writer(&self) -> MutexGuard<'_, Vec<Garbage<T>>> {
+        self.writer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /**
+     * runs read until no node it saw changed meanwhile, read returns None once it meets locked node
+     */
+    fn optimistic<'a, R>(&'a self, read: impl Fn(&mut Seen<'a>) -> Option<R>) -> R {
+        {
+            let _pin = self.pin();
+            let mut seen = vec![];
+
+            for _ in 0..ATTEMPTS {
+                seen.clear();
+
+                if let Some(result) = read(&mut seen) {
+                    if seen.iter().all(|(version, seen)| version.load(Ordering::SeqCst) == *seen) {
+                        return result;
+                    }
+                }
+
+                spin();
+            }
+        }
+
+        let _writer = self.lock_writer();
+
+        read(&mut vec![]).expect("no node is locked while writers are held off")
+    }
+
+    fn read_root<'a>(&'a self, seen: &mut Seen<'a>) -> Option<*mut OlcNode<T>> {
+        let version = self.root_version.load(Ordering::SeqCst);
+
+        if version & 1 == 1 {
+            return None;
+        }
+
+        seen.push((&self.root_version, version));
+
+        Some(self.root.load(Ordering::SeqCst))
+    }
+
+    /**
+     * contents of node reached by read, None while it is locked
+     */
+    fn read_node<'a>(&'a self, node: *mut OlcNode<T>, seen: &mut Seen<'a>) -> Option<&'a NodeData<T>> {
+        let node = unsafe { &*node };
+        let version = node.version.load(Ordering::SeqCst);
+
+        if version & 1 == 1 {
+            return None;
+        }
+
+        seen.push((&node.version, version));
+
+        Some(unsafe { &*node.data.load(Ordering::SeqCst) })
+    }
+
+    fn write(&self) -> Writer<'_, T> {
+        let garbage = self.lock_writer();
+
+        Writer {
+            tree: self,
+            garbage,
+            locked: vec![],
+            nodes: vec![self.root.load(Ordering::SeqCst)],
+            root: 0,
+        }
+    }
+
+    pub fn insert(&self, value: T) {
+        let mut writer = self.write();
+
+        node_store::infallible(node_store::insert(&mut writer, value));
+        self.len.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /**
+     * removes one occurrence of value
+     * returns status of operation: did element remove
+     */
+    pub fn delete(&self, value: &T) -> bool {
+        let mut writer = self.write();
+        let removed = node_store::infallible(node_store::delete(&mut writer, |_, key| key.cmp(value))).is_some();
+
+        if removed {
+            self.len.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        removed
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.optimistic(|seen| {
+            let mut node = self.read_root(seen)?;
+
+            loop {
+                let data = self.read_node(node, seen)?;
+                let i = data.keys.partition_point(|key| key < value);
+
+                if i < data.keys.len() && data.keys[i] == *value {
+                    return Some(true);
+                }
+
+                if data.leaf {
+                    return Some(false);
+                }
+
+                node = data.children[i];
+            }
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.range(..)
+    }
+
+    /**
+     * keys inside range in order as of one moment, long range under steady writes ends up holding writers off
+     */
+    pub fn range(&self, range: impl RangeBounds<T>) -> Vec<T> {
+        self.optimistic(|seen| {
+            let mut out = vec![];
+            let root = self.read_root(seen)?;
+
+            self.collect_range(root, &range, &mut out, seen)?;
+
+            Some(out)
+        })
+    }
+
+    /**
+     * returns false once walk is past end of range, None once it meets locked node
+     */
+    fn collect_range<'a>(
+        &'a self,
+        node: *mut OlcNode<T>,
+        range: &impl RangeBounds<T>,
+        out: &mut Vec<T>,
+        seen: &mut Seen<'a>,
+    ) -> Option<bool> {
+        let data = self.read_node(node, seen)?;
+        let first = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) => data.keys.partition_point(|key| key < start),
+            Bound::Unbounded => 0,
+        };
+
+        for i in first..=data.keys.len() {
+            if !data.leaf && !self.collect_range(data.children[i], range, out, seen)? {
+                return Some(false);
+            }
+
+            let Some(key) = data.keys.get(i) else {
+                break;
+            };
+
+            let after = match range.end_bound() {
+                Bound::Included(end) => key > end,
+                Bound::Excluded(end) => key >= end,
+                Bound::Unbounded => false,
+            };
+
+            if after {
+                return Some(false);
+            }
+
+            if range.contains(key) {
+                out.push(key.clone());
+            }
+        }
+
+        Some(true)
+    }
+
+    /**
+     * checks versions, key order, bounds from delimeters, node sizes, equal leaf depth and len,
+     * writers are held off meanwhile
+     */
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let t = self.t;
+        let _writer = self.lock_writer();
+        let root = self.root.load(Ordering::SeqCst);
+        let mut stack = vec![(root, 0, None::<T>, None::<T>)];
+        let mut leaf_depth: Option<usize> = None;
+        let mut total = 0;
+
+        if self.root_version.load(Ordering::SeqCst) & 1 == 1 {
+            return Err("root pointer is left locked".to_string());
+        }
+
+        while let Some((node, depth, lower, upper)) = stack.pop() {
+            let node_ref = unsafe { &*node };
+            let data = unsafe { &*node_ref.data.load(Ordering::SeqCst) };
+            let count = data.keys.len();
+
+            if node_ref.version.load(Ordering::SeqCst) & 1 == 1 {
+                return Err(format!("node at depth {} is left locked", depth));
+            }
+
+            if count > 2 * t - 1 || (node != root && count < t - 1) {
+                return Err(format!("node at depth {}: {} keys is out of bounds", depth, count));
+            }
+
+            if data.keys.windows(2).any(|pair| pair[0] > pair[1]) {
+                return Err(format!("node at depth {}: keys are not sorted {:?}", depth, data.keys));
+            }
+
+            let below = lower.as_ref().is_some_and(|lower| data.keys.first().is_some_and(|first| first < lower));
+            let above = upper.as_ref().is_some_and(|upper| data.keys.last().is_some_and(|last| last > upper));
+
+            if below || above {
+                return Err(format!("node at depth {}: keys {:?} are out of delimeters", depth, data.keys));
+            }
+
+            total += count;
+
+            if data.leaf {
+                match leaf_depth {
+                    None => leaf_depth = Some(depth),
+                    Some(expected) if expected != depth => {
+                        return Err(format!("leaf at depth {}, expected {}", depth, expected));
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            if data.children.len() != count + 1 {
+                return Err(format!("node at depth {}: {} keys but {} children", depth, count, data.children.len()));
+            }
+
+            for i in 0..=count {
+                let child_lower = if i == 0 { lower.clone() } else { Some(data.keys[i - 1].clone()) };
+                let child_upper = if i == count { upper.clone() } else { Some(data.keys[i].clone()) };
+
+                stack.push((data.children[i], depth + 1, child_lower, child_upper));
+            }
+        }
+
+        if total != self.len() {
+            return Err(format!("tree has {} keys, but len is {}", total, self.len()));
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Ord + Clone + Debug> Drop for OlcBTree<T> {
+    fn drop(&mut self) {
+        for garbage in self.writer.get_mut().unwrap_or_else(PoisonError::into_inner).drain(..) {
+            garbage.free();
+        }
+
+        let mut stack = vec![self.root.load(Ordering::SeqCst)];
+
+        while let Some(node) = stack.pop() {
+            let node = unsafe { Box::from_raw(node) };
+            let data = unsafe { Box::from_raw(node.data.load(Ordering::SeqCst)) };
+
+            stack.extend(data.children);
+        }
+    }
+}
+
+/**
+ * operation of writer, nodes it changes stay locked until it is dropped
+ * contents it replaced stay alive meanwhile, so references from data are valid for its whole duration
+ * it is store of shared insert and delete, ids are indices of nodes it reached in nodes
+ */
+struct Writer<'a, T: Ord + Clone + Debug> {
+    tree: &'a OlcBTree<T>,
+    garbage: MutexGuard<'a, Vec<Garbage<T>>>,
+    locked: Vec<&'a AtomicU64>,
+    nodes: Vec<*mut OlcNode<T>>,
+    root: NodeId,
+}
+
+impl<'a, T: Ord + Clone + Debug> Writer<'a, T> {
+    fn data(&self, id: NodeId) -> &'a NodeData<T> {
+        unsafe { &*(*self.nodes[id as usize]).data.load(Ordering::SeqCst) }
+    }
+
+    fn id(&mut self, node: *mut OlcNode<T>) -> NodeId {
+        self.nodes.push(node);
+
+        (self.nodes.len() - 1) as NodeId
+    }
+
+    fn contents(&self, node: Node<T>) -> NodeData<T> {
+        NodeData {
+            leaf: node.leaf,
+            keys: node.keys.into_iter().collect(),
+            children: node.children.iter().map(|&child| self.nodes[child as usize]).collect(),
+        }
+    }
+
+    fn lock(&mut self, version: &'a AtomicU64) {
+        if !self.locked.iter().any(|locked| ptr::eq(*locked, version)) {
+            version.fetch_add(1, Ordering::SeqCst);
+            self.locked.push(version);
+        }
+    }
+}
+
+/**
+ * loaded node is copy of contents, stored one replaces them, so readers see node as it was before or after
+ */
+impl<'a, T: Ord + Clone + Debug> NodeStore<T> for Writer<'a, T> {
+    type Error = Infallible;
+
+    fn t(&self) -> usize {
+        self.tree.t
+    }
+
+    fn root(&self) -> NodeId {
+        self.root
+    }
+
+    fn set_root(&mut self, id: NodeId) {
+        self.root = id;
+        self.lock(&self.tree.root_version);
+        self.tree.root.store(self.nodes[id as usize], Ordering::SeqCst);
+    }
+
+    fn compare(&self, a: &T, b: &T) -> std::cmp::Ordering {
+        a.cmp(b)
+    }
+
+    fn allocator(&self) -> &() {
+        &()
+    }
+
+    fn load(&mut self, id: NodeId) -> Result<Node<T>, Infallible> {
+        let data = self.data(id);
+        let children: Vec<NodeId> = data.children.iter().map(|&child| self.id(child)).collect();
+
+        Ok(Node::from_parts(data.leaf, data.keys.iter().cloned(), children))
+    }
+
+    fn store(&mut self, id: NodeId, node: Node<T>) -> Result<(), Infallible> {
+        let data = self.contents(node);
+        let node = unsafe { &*self.nodes[id as usize] };
+
+        self.lock(&node.version);
+
+        let old = node.data.swap(Box::into_raw(Box::new(data)), Ordering::SeqCst);
+
+        self.garbage.push(Garbage::Data(old));
+
+        Ok(())
+    }
+
+    fn unload(&mut self, _id: NodeId, _node: Node<T>) {}
+
+    fn count(&mut self, id: NodeId) -> Result<usize, Infallible> {
+        Ok(self.data(id).keys.len())
+    }
+
+    fn alloc(&mut self, node: Node<T>) -> Result<NodeId, Infallible> {
+        let data = self.contents(node);
+
+        Ok(self.id(OlcNode::new(data)))
+    }
+
+    /**
+     * node unlinked from tree stays locked, so readers still holding it start over
+     */
+    fn free(&mut self, id: NodeId) -> Result<(), Infallible> {
+        let node = self.nodes[id as usize];
+        let version = unsafe { &(*node).version };
+
+        self.lock(version);
+        self.locked.retain(|locked| !ptr::eq(*locked, version));
+        self.garbage.push(Garbage::Node(node));
+
+        Ok(())
+    }
+}
+
+impl<T: Ord + Clone + Debug> Drop for Writer<'_, T> {
+    fn drop(&mut self) {
+        for version in self.locked.drain(..) {
+            version.fetch_add(1, Ordering::SeqCst);
+        }
+
+        if self.garbage.len() >= GARBAGE {
+            self.tree.synchronize();
+
+            for garbage in self.garbage.drain(..) {
+                garbage.free();
+            }
+        }
+    }
+}
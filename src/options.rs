@@ -0,0 +1,293 @@
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::db::{
+    BackgroundFlush, Db, ReadPath, SyncMode, TreeDigest, DEFAULT_CACHE_PAGES, DEFAULT_WAL_LIMIT,
+    DEFAULT_WAL_SEGMENT_SIZE,
+};
+use crate::encryption::Key;
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+
+/**
+ * smallest cache, one change keeps a few pages of every level dirty until it returns
+ */
+pub const MIN_CACHE_PAGES: usize = 32;
+
+/**
+ * settings of database handle, built like SrdbOptions::new().page_size(8192).cache_pages(4096).open(path)
+ * page size, branching factor, compression and encryption are chosen at creation and stored in file,
+ * open reads them from file and fails if they are set to something else
+ * the rest applies to handle only and may differ between opens
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SrdbOptions {
+    pub(crate) page_size: Option<usize>,
+    pub(crate) t: Option<usize>,
+    pub(crate) compression: Option<bool>,
+    pub(crate) key: Option<Key>,
+    pub(crate) cache_pages: usize,
+    pub(crate) sync_mode: SyncMode,
+    pub(crate) read_path: ReadPath,
+    pub(crate) wal_limit: Option<u64>,
+    pub(crate) wal_segment_size: Option<u64>,
+    pub(crate) background_flush: Option<BackgroundFlush>,
+    pub(crate) read_only: bool,
+    pub(crate) verify: bool,
+    pub(crate) lock_timeout: Option<Duration>,
+    pub(crate) lock: bool,
+    pub(crate) replication_backlog: Option<u64>,
+    pub(crate) wait_for_writer: bool,
+}
+
+impl Default for SrdbOptions {
+    fn default() -> Self {
+        SrdbOptions {
+            page_size: None,
+            t: None,
+            compression: None,
+            key: None,
+            cache_pages: DEFAULT_CACHE_PAGES,
+            sync_mode: SyncMode::default(),
+            read_path: ReadPath::default(),
+            wal_limit: Some(DEFAULT_WAL_LIMIT),
+            wal_segment_size: Some(DEFAULT_WAL_SEGMENT_SIZE),
+            background_flush: None,
+            read_only: false,
+            verify: true,
+            lock_timeout: None,
+            lock: true,
+            replication_backlog: None,
+            wait_for_writer: true,
+        }
+    }
+}
+
+impl SrdbOptions {
+    pub fn new() -> SrdbOptions {
+        SrdbOptions::default()
+    }
+
+    /**
+     * size of page in file, PAGE_SIZE unless set, see MIN_PAGE_SIZE
+     */
+    pub fn page_size(mut self, page_size: usize) -> SrdbOptions {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /**
+     * branching factor of tree, the largest one fitting into page unless set
+     */
+    pub fn branching_factor(mut self, t: usize) -> SrdbOptions {
+        self.t = Some(t);
+        self
+    }
+
+    /**
+     * values too long for node page are compressed with lz4 when it makes them shorter,
+     * on by default with lz4 feature, file written with it can not be opened without the feature
+     */
+    #[cfg(feature = "lz4")]
+    pub fn compression(mut self, compression: bool) -> SrdbOptions {
+        self.compression = Some(compression);
+        self
+    }
+
+    /**
+     * pages and log records are encrypted with AES-256-GCM under this key, see Cipher
+     * database created with it opens only with the same key, salt and key check are stored in superblock,
+     * so wrong key fails with Error::WrongKey and none with Error::KeyRequired
+     */
+    #[cfg(feature = "encryption")]
+    pub fn encryption_key(mut self, key: [u8; 32]) -> SrdbOptions {
+        self.key = Some(Key(key));
+        self
+    }
+
+    /**
+     * pages kept in memory, at least MIN_CACHE_PAGES
+     */
+    pub fn cache_pages(mut self, cache_pages: usize) -> SrdbOptions {
+        self.cache_pages = cache_pages;
+        self
+    }
+
+    pub fn sync_mode(mut self, sync_mode: SyncMode) -> SrdbOptions {
+        self.sync_mode = sync_mode;
+        self
+    }
+
+    pub fn read_path(mut self, read_path: ReadPath) -> SrdbOptions {
+        self.read_path = read_path;
+        self
+    }
+
+    /**
+     * see Db::set_wal_limit
+     */
+    pub fn wal_limit(mut self, wal_limit: Option<u64>) -> SrdbOptions {
+        self.wal_limit = wal_limit;
+        self
+    }
+
+    /**
+     * see Db::set_wal_segment_size
+     */
+    pub fn wal_segment_size(mut self, wal_segment_size: Option<u64>) -> SrdbOptions {
+        self.wal_segment_size = wal_segment_size;
+        self
+    }
+
+    /**
+     * see Db::set_background_flush
+     */
+    pub fn background_flush(mut self, background_flush: Option<BackgroundFlush>) -> SrdbOptions {
+        self.background_flush = background_flush;
+        self
+    }
+
+    /**
+     * see Db::open_read_only
+     */
+    pub fn read_only(mut self, read_only: bool) -> SrdbOptions {
+        self.read_only = read_only;
+        self
+    }
+
+    /**
+     * false reads pages failing checksum as they are, only for read only handle, see Db::open_unverified
+     */
+    pub fn verify(mut self, verify: bool) -> SrdbOptions {
+        self.verify = verify;
+        self
+    }
+
+    /**
+     * how long open waits for other handles to release the file, see Db::open_with_lock_timeout
+     */
+    pub fn lock_timeout(mut self, lock_timeout: Option<Duration>) -> SrdbOptions {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /**
+     * with false read only open takes no file lock, so it works while writer holds the file,
+     * log is not read either, so database is seen as of the last flush,
+     * pages writer reuses after open may fail checksum or read stale, meant for monitoring
+     */
+    pub fn lock(mut self, lock: bool) -> SrdbOptions {
+        self.lock = lock;
+        self
+    }
+
+    /**
+     * see Db::set_replication_backlog
+     */
+    pub fn replication_backlog(mut self, replication_backlog: Option<u64>) -> SrdbOptions {
+        self.replication_backlog = replication_backlog;
+        self
+    }
+
+    /**
+     * see Db::set_wait_for_writer
+     */
+    pub fn wait_for_writer(mut self, wait_for_writer: bool) -> SrdbOptions {
+        self.wait_for_writer = wait_for_writer;
+        self
+    }
+
+    /**
+     * rejects combinations no handle can be opened with, options stored in file are checked by open
+     */
+    pub(crate) fn check(&self, create: bool) -> Result<()> {
+        if self.cache_pages < MIN_CACHE_PAGES {
+            return Err(Error::InvalidOptions(format!(
+                "cache of {} pages is smaller than {}",
+                self.cache_pages, MIN_CACHE_PAGES
+            )));
+        }
+
+        if self.read_only && create {
+            return Err(Error::InvalidOptions("read only database can not be created".to_string()));
+        }
+
+        if self.read_only && self.background_flush.is_some() {
+            return Err(Error::InvalidOptions("read only database has nothing to flush".to_string()));
+        }
+
+        if self.read_only && self.replication_backlog.is_some() {
+            return Err(Error::InvalidOptions("read only database commits no batches to keep".to_string()));
+        }
+
+        if !self.verify && !self.read_only {
+            return Err(Error::InvalidOptions("only read only database can skip verification".to_string()));
+        }
+
+        if !self.lock && !self.read_only {
+            return Err(Error::InvalidOptions("only read only database can be opened without lock".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /**
+     * creates new database file, fails if file exists
+     */
+    pub fn create(&self, path: impl AsRef<Path>) -> Result<Db> {
+        Db::create_with(path.as_ref(), self)
+    }
+
+    /**
+     * opens existing database file
+     */
+    pub fn open(&self, path: impl AsRef<Path>) -> Result<Db> {
+        Db::open_with(path.as_ref(), self)
+    }
+
+    /**
+     * opens database file if it exists, otherwise creates it
+     */
+    pub fn open_or_create(&self, path: impl AsRef<Path>) -> Result<Db> {
+        let path = path.as_ref();
+
+        match self.create(path) {
+            Err(Error::Io(error)) if error.kind() == std::io::ErrorKind::AlreadyExists => self.open(path),
+            result => result,
+        }
+    }
+
+    /**
+     * creates new database file with these options and fills it from dump, see Db::restore
+     */
+    pub fn restore<R: Read>(&self, r: R, path: impl AsRef<Path>) -> Result<Db> {
+        Ok(Db::restore_with(r, path.as_ref(), None, self)?.0)
+    }
+
+    /**
+     * same as restore, but only given tables are restored, see Db::restore_tables
+     */
+    pub fn restore_tables<R: Read>(
+        &self,
+        r: R,
+        path: impl AsRef<Path>,
+        tables: Option<&[&str]>,
+    ) -> Result<(Db, Vec<TreeDigest>)> {
+        Db::restore_with(r, path.as_ref(), tables, self)
+    }
+
+    /**
+     * same as create over given storages of database and log, their contents are discarded
+     */
+    pub fn create_with_storage(&self, storage: Box<dyn Storage>, wal_storage: Box<dyn Storage>) -> Result<Db> {
+        Db::create_from_storage(storage, wal_storage, self)
+    }
+
+    /**
+     * same as open over given storages of database and log, read only is not supported here
+     */
+    pub fn open_with_storage(&self, storage: Box<dyn Storage>, wal_storage: Box<dyn Storage>) -> Result<Db> {
+        Db::open_from_storage(storage, wal_storage, self)
+    }
+}
@@ -0,0 +1,126 @@
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt::{self, Debug, Display};
+
+use crate::codec::Codec;
+use crate::BTree;
+
+/**
+ * order of keys in tree, see BTree::new_with_comparator
+ * must be total order, same for the whole life of tree
+ * closures Fn(&T, &T) -> Ordering are comparators, zero-sized ones cost nothing in tree
+ */
+pub trait Comparator<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+/**
+ * Ord of keys, comparator of BTree::new
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NaturalOrder;
+
+impl<T: Ord + ?Sized> Comparator<T> for NaturalOrder {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<T: ?Sized, F: Fn(&T, &T) -> Ordering> Comparator<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/**
+ * f64 key ordered by f64::total_cmp: -NaN < -inf < ... < -0.0 < 0.0 < ... < inf < NaN
+ * NaN is a key like any other and equals only NaN with the same bits
+ */
+#[derive(Clone, Copy, Default)]
+pub struct TotalF64(pub f64);
+
+impl PartialEq for TotalF64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for TotalF64 {}
+
+impl PartialOrd for TotalF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TotalF64 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl From<f64> for TotalF64 {
+    fn from(value: f64) -> Self {
+        TotalF64(value)
+    }
+}
+
+impl Debug for TotalF64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+impl Display for TotalF64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Codec for TotalF64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.to_bits().encode(out)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Some(TotalF64(f64::from_bits(u64::decode(bytes)?)))
+    }
+}
+
+/**
+ * key ordered by PartialOrd of T, see BTree::new_partial_unchecked
+ * incomparable keys count as equal, so one of them in tree makes order of tree meaningless without any error
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PartialKey<T>(pub T);
+
+impl<T: PartialOrd> PartialEq for PartialKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl<T: PartialOrd> Eq for PartialKey<T> {}
+
+impl<T: PartialOrd> PartialOrd for PartialKey<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: PartialOrd> Ord for PartialKey<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.partial_cmp(&other.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl<T: PartialOrd + Debug> BTree<PartialKey<T>> {
+    /**
+     * tree of keys which are only PartialOrd, e.g. plain f64, wrapped into PartialKey
+     * nothing checks keys are comparable: NaN or other incomparable key breaks search and invariants silently,
+     * prefer Ord keys, e.g. TotalF64 for floats
+     */
+    pub fn new_partial_unchecked(t: usize) -> Self {
+        BTree::new(t)
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt::Debug;
+
+use crate::codec::Codec;
+use crate::error::{Error, Result};
+use crate::pager::PageId;
+use crate::Node;
+
+/**
+ * node page layout, integers are little-endian:
+ * kind (u8, LEAF or INTERNAL), key count (u16),
+ * child page ids (u32 each, count + 1 of them, internal nodes only),
+ * keys as (length u16, encoded bytes), rest of page is zeroed
+ */
+const LEAF: u8 = 1;
+const INTERNAL: u8 = 2;
+
+/**
+ * free page layout: kind (u8, FREE), id of the next free page (u32),
+ * header page id ends the list as header page is never free
+ */
+const FREE: u8 = 3;
+
+/**
+ * overflow page layout: kind (u8, OVERFLOW), id of the next page of chain (u32),
+ * length of data in this page (u16), data
+ * header page id ends the chain
+ */
+const OVERFLOW: u8 = 4;
+
+pub(crate) const OVERFLOW_HEADER_SIZE: usize = 7;
+
+const NODE_HEADER_SIZE: usize = 3;
+const CHILD_SIZE: usize = std::mem::size_of::<PageId>();
+const KEY_LEN_SIZE: usize = 2;
+
+/**
+ * largest t such that full internal node with keys of max_key_size encoded bytes fits in a page:
+ * header + 2t children + (2t - 1) keys <= page_size
+ */
+pub fn max_t(page_size: usize, max_key_size: usize) -> usize {
+    let entry = KEY_LEN_SIZE + max_key_size;
+
+    (page_size + entry - NODE_HEADER_SIZE) / (2 * (CHILD_SIZE + entry))
+}
+
+/**
+ * key count of node page without decoding its keys
+ */
+pub(crate) fn node_count(page: &[u8]) -> Result<usize> {
+    if page[0] != LEAF && page[0] != INTERNAL {
+        return Err(Error::Corrupt(format!("unknown node kind {}", page[0])));
+    }
+
+    Ok(u16::from_le_bytes(page[1..NODE_HEADER_SIZE].try_into().unwrap()) as usize)
+}
+
+pub(crate) fn encode_free_page(next: PageId, page_size: usize) -> Vec<u8> {
+    let mut page = vec![0; page_size];
+
+    page[0] = FREE;
+    page[1..5].copy_from_slice(&next.to_le_bytes());
+
+    page
+}
+
+/**
+ * returns id of the next free page
+ */
+pub(crate) fn decode_free_page(page: &[u8]) -> Result<PageId> {
+    if page[0] != FREE {
+        return Err(Error::Corrupt(format!("page of kind {} is in free list", page[0])));
+    }
+
+    Ok(PageId::from_le_bytes(page[1..5].try_into().unwrap()))
+}
+
+pub(crate) fn encode_overflow_page(next: PageId, data: &[u8], page_size: usize) -> Vec<u8> {
+    let mut page = vec![0; page_size];
+
+    page[0] = OVERFLOW;
+    page[1..5].copy_from_slice(&next.to_le_bytes());
+    page[5..OVERFLOW_HEADER_SIZE].copy_from_slice(&(data.len() as u16).to_le_bytes());
+    page[OVERFLOW_HEADER_SIZE..OVERFLOW_HEADER_SIZE + data.len()].copy_from_slice(data);
+
+    page
+}
+
+/**
+ * returns id of the next page of chain and data of this one
+ */
+pub(crate) fn decode_overflow_page(page: &[u8]) -> Result<(PageId, &[u8])> {
+    if page[0] != OVERFLOW {
+        return Err(Error::Corrupt(format!("page of kind {} is in overflow chain", page[0])));
+    }
+
+    let next = PageId::from_le_bytes(page[1..5].try_into().unwrap());
+    let len = u16::from_le_bytes(page[5..OVERFLOW_HEADER_SIZE].try_into().unwrap()) as usize;
+    let data = page
+        .get(OVERFLOW_HEADER_SIZE..OVERFLOW_HEADER_SIZE + len)
+        .ok_or_else(|| Error::Corrupt(format!("overflow data of {} bytes does not fit into page", len)))?;
+
+    Ok((next, data))
+}
+
+impl<T: Ord + Clone + Debug + Codec> Node<T> {
+    /**
+     * encodes node into page, children are page ids
+     * fails on the first key which does not fit into page
+     */
+    pub(crate) fn to_page(&self, page_size: usize) -> Result<Vec<u8>> {
+        let mut page = Vec::with_capacity(page_size);
+
+        page.push(if self.leaf { LEAF } else { INTERNAL });
+        page.extend_from_slice(&(self.count as u16).to_le_bytes());
+
+        if !self.leaf {
+            for child in self.children.iter() {
+                page.extend_from_slice(&child.to_le_bytes());
+            }
+        }
+
+        let mut buf = vec![];
+
+        for key in self.keys.iter() {
+            buf.clear();
+            key.encode(&mut buf);
+
+            if buf.len() > u16::MAX as usize || page.len() + KEY_LEN_SIZE + buf.len() > page_size {
+                return Err(Error::KeyTooLarge {
+                    key: format!("{:?}", key),
+                    size: buf.len(),
+                    available: page_size.saturating_sub(page.len() + KEY_LEN_SIZE),
+                });
+            }
+
+            page.extend_from_slice(&(buf.len() as u16).to_le_bytes());
+            page.extend_from_slice(&buf);
+        }
+
+        page.resize(page_size, 0);
+
+        Ok(page)
+    }
+
+    /**
+     * bytes of page taken by node encoded with to_page, the rest is zeroed
+     */
+    pub(crate) fn encoded_len(&self) -> usize {
+        let mut buf = vec![];
+        let children = if self.leaf { 0 } else { self.children.len() * CHILD_SIZE };
+
+        self.keys.iter().fold(NODE_HEADER_SIZE + children, |len, key| {
+            buf.clear();
+            key.encode(&mut buf);
+
+            len + KEY_LEN_SIZE + buf.len()
+        })
+    }
+
+    /**
+     * decodes node written by to_page
+     */
+    pub(crate) fn from_page(page: &[u8]) -> Result<Node<T>> {
+        let mut reader = PageReader { page, offset: 0 };
+
+        let leaf = match reader.take(1)?[0] {
+            LEAF => true,
+            INTERNAL => false,
+            kind => return Err(Error::Corrupt(format!("unknown node kind {}", kind))),
+        };
+
+        let count = u16::from_le_bytes(reader.array()?) as usize;
+        let mut node: Node<T> = if leaf { Node::leaf(count) } else { Node::empty(count) };
+
+        if !leaf {
+            for _ in 0..=count {
+                node.children.push(PageId::from_le_bytes(reader.array()?));
+            }
+        }
+
+        for i in 0..count {
+            let len = u16::from_le_bytes(reader.array()?) as usize;
+            let key = T::decode(reader.take(len)?).ok_or_else(|| Error::Corrupt(format!("key {} can not be decoded", i)))?;
+
+            node.keys.push(key);
+        }
+
+        node.count = count;
+
+        Ok(node)
+    }
+}
+
+struct PageReader<'a> {
+    page: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PageReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .page
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| Error::Corrupt(format!("node does not fit into page at offset {}", self.offset)))?;
+
+        self.offset += len;
+
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{max_t, Node, KEY_LEN_SIZE, NODE_HEADER_SIZE};
+    use crate::error::Error;
+    use crate::BTree;
+
+    /**
+     * every node of tree must come back from its page with the same kind, keys and children
+     */
+    fn assert_round_trips<T: Ord + Clone + std::fmt::Debug + crate::Codec>(tree: &BTree<T>, page_size: usize) {
+        for id in 0..tree.nodes.len() as u32 {
+            if tree.free.contains(&id) {
+                continue;
+            }
+
+            let node = tree.node(id);
+            let page = node.to_page(page_size).unwrap();
+            let decoded = Node::<T>::from_page(&page).unwrap();
+
+            assert_eq!(page.len(), page_size);
+            assert!(page[node.encoded_len()..].iter().all(|byte| *byte == 0));
+            assert_eq!((decoded.leaf, decoded.count), (node.leaf, node.count), "node {}", id);
+            assert_eq!(&*decoded.keys, &*node.keys, "node {}", id);
+            assert_eq!(&*decoded.children, if node.leaf { &[][..] } else { &*node.children }, "node {}", id);
+            assert_eq!(super::node_count(&page).unwrap(), node.count);
+        }
+    }
+
+    #[test]
+    fn nodes_of_tree_round_trip() {
+        for t in [2, 3, 8, 64] {
+            let mut tree = BTree::new(t);
+            let mut state = 17u64;
+
+            assert_round_trips(&tree, 4096);
+
+            for i in 0..3000u64 {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+
+                match i % 4 {
+                    3 => {
+                        tree.delete(&((state >> 33) % 2000));
+                    }
+                    _ => tree.insert((state >> 33) % 2000),
+                }
+            }
+
+            assert_round_trips(&tree, 4096);
+            assert_round_trips(&BTree::from_sorted(t, (0..5000u64).collect(), 0.5), 4096);
+            assert_round_trips(&BTree::from_sorted(t, vec![u64::MAX], 1.0), 4096);
+
+            let words: Vec<String> = (0..2000).map(|i| "x".repeat(i % 20) + &i.to_string()).collect();
+            let mut tree = BTree::new(t);
+
+            words.into_iter().for_each(|word| tree.insert(word));
+            tree.delete(&"x1".to_string());
+            assert_round_trips(&tree, 4096);
+        }
+    }
+
+    #[test]
+    fn full_node_of_max_t_fits() {
+        for page_size in [1024, 4092, 65532] {
+            for key_size in [1, 8, 100, 300] {
+                let t = max_t(page_size, key_size);
+                let keys: Vec<Vec<u8>> = (0..2 * t - 1).map(|i| vec![i as u8; key_size]).collect();
+                let mut node = Node::<Vec<u8>>::empty(t);
+
+                node.keys.extend(keys);
+                node.children.extend(0..2 * t as u32);
+                node.count = 2 * t - 1;
+
+                let page = node.to_page(page_size).unwrap();
+
+                assert_eq!(Node::<Vec<u8>>::from_page(&page).unwrap().keys.len(), 2 * t - 1);
+
+                node.keys.push(vec![0; key_size]);
+                node.children.push(0);
+                node.count += 1;
+                node.keys.push(vec![0; key_size]);
+                node.children.push(0);
+                node.count += 1;
+
+                assert!(node.to_page(page_size).is_err(), "page {}, key {}, t {}", page_size, key_size, t);
+            }
+        }
+    }
+
+    #[test]
+    fn key_which_does_not_fit_is_named() {
+        let mut node = Node::<String>::leaf(2);
+
+        node.keys.push("small".to_string());
+        node.keys.push("y".repeat(5000));
+        node.count = 2;
+
+        match node.to_page(4092) {
+            Err(Error::KeyTooLarge { key, size, available }) => {
+                assert!(key.starts_with("\"yyy"));
+                assert_eq!(size, 5000);
+                assert_eq!(available, 4092 - NODE_HEADER_SIZE - KEY_LEN_SIZE - 5 - KEY_LEN_SIZE);
+            }
+            result => panic!("got {:?}", result.map(|page| page.len())),
+        }
+    }
+
+    #[test]
+    fn broken_page_is_corrupt() {
+        let mut node = Node::<u64>::leaf(2);
+
+        node.keys.extend([1, 2, 3]);
+        node.count = 3;
+
+        let page = node.to_page(64).unwrap();
+        let mut kind = page.clone();
+        let mut count = page.clone();
+        let mut length = page.clone();
+
+        kind[0] = 9;
+        count[1] = 200;
+        length[3] = 7;
+
+        for page in [kind, count, length] {
+            assert!(matches!(Node::<u64>::from_page(&page), Err(Error::Corrupt(_))));
+        }
+    }
+}
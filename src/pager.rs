@@ -0,0 +1,1090 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::OpenOptions;
+use std::hash::BuildHasher;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::crc32::checksum;
+use crate::encryption::{Cipher, Key, CHECK_SIZE, SALT_SIZE, SEAL_SIZE};
+use crate::error::{Error, Result};
+use crate::mmap::Mmap;
+use crate::storage::Storage;
+
+/**
+ * index of logical page, pages are addressed by it and pager decides where in file they live
+ */
+pub type PageId = u32;
+
+/**
+ * default size of page in file, checksum trailer included
+ */
+pub const PAGE_SIZE: usize = 4096;
+
+/**
+ * page size is a power of two in this range, it is set at creation and stored in superblock
+ */
+pub const MIN_PAGE_SIZE: usize = 1024;
+pub const MAX_PAGE_SIZE: usize = 65536;
+
+/**
+ * first logical page, reserved for database header
+ */
+pub const HEADER_PAGE: PageId = 0;
+
+/**
+ * superblock layout, integers are little-endian:
+ * magic (4 bytes), format version (u32), page size (u32), generation (u64),
+ * logical page count (u32), first directory page (u32), file id (u64),
+ * cipher (u32), salt (16 bytes), key check (16 bytes), crc32 of everything before it (u32)
+ *
+ * superblock is kept in two slots, physical pages 0 and 1, commit writes the older one,
+ * so torn write leaves the other intact
+ *
+ * every other physical page ends with crc32 of its contents (u32),
+ * in encrypted file its contents are sealed instead and end with nonce and tag, see Cipher
+ * directory page is next directory page (u32), count (u32) and physical ids of table pages (u32 each)
+ * table page is entries of table_entries consecutive logical pages, each is physical id (u32)
+ * and generation of commit which wrote the page last (u64), physical id 0 stands for page never written,
+ * it reads as zeros
+ */
+const MAGIC: &[u8; 4] = b"SRDB";
+pub const FORMAT_VERSION: u32 = 6;
+
+const SLOTS: PageId = 2;
+const CHECKSUM_OFFSET: usize = 72;
+const TRAILER_SIZE: usize = 4;
+const DIRECTORY_HEADER_SIZE: usize = 8;
+const TABLE_ENTRY_SIZE: usize = 12;
+
+/**
+ * physical id which is never a data page, marks unmapped logical page and end of directory
+ */
+const UNMAPPED: PageId = 0;
+
+/**
+ * cipher of superblock, salt and key check of file without one are zeros
+ */
+const NO_CIPHER: u32 = 0;
+const AES_256_GCM: u32 = 1;
+
+#[derive(Debug)]
+struct Superblock {
+    generation: u64,
+    page_count: PageId,
+    directory: PageId,
+    file_id: u64,
+    cipher: u32,
+    salt: [u8; SALT_SIZE],
+    key_check: [u8; CHECK_SIZE],
+}
+
+fn read_u32(page: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(page[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(page: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(page[offset..offset + 8].try_into().unwrap())
+}
+
+/**
+ * random id of new file, it tells files apart even if they have the same path
+ */
+fn new_file_id() -> u64 {
+    RandomState::new().hash_one(SystemTime::now())
+}
+
+/**
+ * superblock fields with their checksum
+ */
+const SUPERBLOCK_SIZE: usize = CHECKSUM_OFFSET + 4;
+
+fn is_valid_page_size(page_size: usize) -> bool {
+    page_size.is_power_of_two() && (MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size)
+}
+
+impl Superblock {
+    fn encode(&self, page_size: usize) -> Vec<u8> {
+        let mut page = Vec::with_capacity(page_size);
+
+        page.extend_from_slice(MAGIC);
+        page.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        page.extend_from_slice(&(page_size as u32).to_le_bytes());
+        page.extend_from_slice(&self.generation.to_le_bytes());
+        page.extend_from_slice(&self.page_count.to_le_bytes());
+        page.extend_from_slice(&self.directory.to_le_bytes());
+        page.extend_from_slice(&self.file_id.to_le_bytes());
+        page.extend_from_slice(&self.cipher.to_le_bytes());
+        page.extend_from_slice(&self.salt);
+        page.extend_from_slice(&self.key_check);
+
+        debug_assert_eq!(page.len(), CHECKSUM_OFFSET);
+
+        let crc = checksum(&page);
+
+        page.extend_from_slice(&crc.to_le_bytes());
+        page.resize(page_size, 0);
+
+        page
+    }
+
+    /**
+     * page size stored in superblock if its magic, version and checksum are valid
+     */
+    fn page_size(page: &[u8]) -> Option<usize> {
+        let valid = &page[0..4] == MAGIC
+            && read_u32(page, 4) == FORMAT_VERSION
+            && read_u32(page, CHECKSUM_OFFSET) == checksum(&page[..CHECKSUM_OFFSET]);
+        let page_size = read_u32(page, 8) as usize;
+
+        (valid && is_valid_page_size(page_size)).then_some(page_size)
+    }
+
+    /**
+     * validates magic, version, checksum and page size
+     */
+    fn decode(page: &[u8], page_size: usize) -> Result<Superblock> {
+        if &page[0..4] != MAGIC {
+            return Err(Error::WrongMagic);
+        }
+
+        let version = read_u32(page, 4);
+
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        let expected = read_u32(page, CHECKSUM_OFFSET);
+        let actual = checksum(&page[..CHECKSUM_OFFSET]);
+
+        if expected != actual {
+            return Err(Error::CorruptHeader(format!(
+                "checksum mismatch: stored {:08x}, computed {:08x}",
+                expected, actual
+            )));
+        }
+
+        let found = read_u32(page, 8) as usize;
+
+        if found != page_size {
+            return Err(Error::PageSizeMismatch {
+                expected: page_size,
+                found,
+            });
+        }
+
+        Ok(Superblock {
+            generation: read_u64(page, 12),
+            page_count: read_u32(page, 20),
+            directory: read_u32(page, 24),
+            file_id: read_u64(page, 28),
+            cipher: read_u32(page, 36),
+            salt: page[40..56].try_into().unwrap(),
+            key_check: page[56..72].try_into().unwrap(),
+        })
+    }
+}
+
+/**
+ * manages database file as an array of fixed size logical pages with shadow paging
+ * written page goes to a fresh physical page, pages of the last commit are never overwritten,
+ * so file keeps committed state until commit atomically switches superblock to the new one
+ * torn write only ever hits fresh physical page or the older superblock slot, neither is read back after crash
+ * physical pages replaced since commit are reused only after the next commit
+ * freed logical pages are kept in memory and handed out again before page count grows
+ */
+#[derive(Debug)]
+pub struct Pager {
+    file: Box<dyn Storage>,
+    page_size: usize,
+    generation: u64,
+    file_id: u64,
+    /**
+     * physical page of every logical page
+     */
+    table: Vec<PageId>,
+    /**
+     * generation of commit which wrote every logical page last, it is the next one for pages written since commit
+     */
+    written: Vec<u64>,
+    /**
+     * physical pages holding committed table, one per table_entries logical pages
+     */
+    table_pages: Vec<PageId>,
+    dirty_tables: BTreeSet<usize>,
+    directory: Vec<PageId>,
+    /**
+     * physical pages written since commit, they are not part of committed state
+     */
+    fresh: HashSet<PageId>,
+    /**
+     * physical pages of committed state replaced since commit with generation they are committed at
+     */
+    released: Vec<(u64, PageId)>,
+    /**
+     * physical pages used by neither committed state nor fresh pages
+     */
+    spare: Vec<PageId>,
+    file_pages: PageId,
+    free: Vec<PageId>,
+    verify: bool,
+    /**
+     * cipher pages are sealed with, None for plain file
+     */
+    cipher: Option<Cipher>,
+    salt: [u8; SALT_SIZE],
+    key_check: [u8; CHECK_SIZE],
+    /**
+     * mapping of file pages are read from, None when reads go through storage
+     */
+    map: Option<Mmap>,
+    /**
+     * mapped physical pages whose checksum was verified since they were written
+     */
+    verified: HashSet<PageId>,
+    /**
+     * number of snapshots of committed state being read by generation they are pinned at, see pin_snapshot
+     */
+    pinned: BTreeMap<u64, usize>,
+    /**
+     * physical pages released by commits, but still read by snapshot, with generations they were committed
+     * and released at, so snapshots of generations in between read them, see collect_garbage
+     */
+    held: Vec<(u64, u64, PageId)>,
+    /**
+     * whether snapshot was unpinned since held pages were collected
+     */
+    unpinned: bool,
+}
+
+impl Pager {
+    /**
+     * creates new file with zeroed header page, fails if file exists
+     * first commit is written to both superblock slots
+     */
+    pub fn create(path: impl AsRef<Path>) -> Result<Pager> {
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(path)?;
+
+        Pager::create_with_storage(Box::new(file))
+    }
+
+    /**
+     * same as create over given storage, its contents are discarded
+     */
+    pub fn create_with_storage(file: Box<dyn Storage>) -> Result<Pager> {
+        Pager::create_with_page_size(file, PAGE_SIZE)
+    }
+
+    /**
+     * same as create_with_storage with pages of page_size bytes in file, see MIN_PAGE_SIZE
+     */
+    pub fn create_with_page_size(file: Box<dyn Storage>, page_size: usize) -> Result<Pager> {
+        Pager::create_with(file, page_size, None)
+    }
+
+    /**
+     * same as create_with_page_size, file with key gets fresh salt and every page of it is encrypted
+     */
+    pub(crate) fn create_with(mut file: Box<dyn Storage>, page_size: usize, key: Option<&Key>) -> Result<Pager> {
+        if !is_valid_page_size(page_size) {
+            return Err(Error::InvalidOptions(format!(
+                "page size {} is not a power of two between {} and {}",
+                page_size, MIN_PAGE_SIZE, MAX_PAGE_SIZE
+            )));
+        }
+
+        file.set_len(0)?;
+
+        let mut pager = Pager::new(file, page_size);
+
+        if let Some(key) = key {
+            let (cipher, salt, key_check) = Cipher::create(key)?;
+
+            pager.cipher = Some(cipher);
+            pager.salt = salt;
+            pager.key_check = key_check;
+        }
+
+        pager.file_id = new_file_id();
+        pager.allocate_page()?;
+        pager.commit(true)?;
+        pager.write_superblock(0)?;
+        pager.file.sync()?;
+
+        Ok(pager)
+    }
+
+    fn new(file: Box<dyn Storage>, page_size: usize) -> Pager {
+        Pager {
+            file,
+            page_size,
+            generation: 0,
+            file_id: 0,
+            table: vec![],
+            written: vec![],
+            table_pages: vec![],
+            dirty_tables: BTreeSet::new(),
+            directory: vec![],
+            fresh: HashSet::new(),
+            released: vec![],
+            spare: vec![],
+            file_pages: SLOTS,
+            free: vec![],
+            verify: true,
+            cipher: None,
+            salt: [0; SALT_SIZE],
+            key_check: [0; CHECK_SIZE],
+            map: None,
+            verified: HashSet::new(),
+            pinned: BTreeMap::new(),
+            held: vec![],
+            unpinned: false,
+        }
+    }
+
+    /**
+     * opens existing file in state of its last commit
+     */
+    pub fn open(path: impl AsRef<Path>) -> Result<Pager> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        Pager::open_with_storage(Box::new(file))
+    }
+
+    /**
+     * same as open over given storage, page size is read from superblock
+     * superblock with the highest generation among valid slots wins,
+     * physical pages it does not reference become spare
+     */
+    pub fn open_with_storage(file: Box<dyn Storage>) -> Result<Pager> {
+        Pager::open_with(file, None)
+    }
+
+    /**
+     * same as open_with_storage, encrypted file needs the key it was created with,
+     * without it open fails with Error::KeyRequired, with another one with Error::WrongKey
+     */
+    pub(crate) fn open_with(mut file: Box<dyn Storage>, key: Option<&Key>) -> Result<Pager> {
+        let len = file.len()?;
+        let page_size = Pager::stored_page_size(file.as_mut())?;
+        let file_pages = (len / page_size as u64) as PageId;
+
+        if file_pages < SLOTS {
+            return Err(Error::Corrupt(format!("file length {} is shorter than superblocks", len)));
+        }
+
+        let mut pager = Pager::new(file, page_size);
+        let mut buf = vec![0; page_size];
+        let mut best: Option<Superblock> = None;
+        let mut first_error = None;
+
+        pager.file_pages = file_pages;
+
+        for slot in 0..SLOTS {
+            pager.read_physical(slot, &mut buf)?;
+
+            match Superblock::decode(&buf, page_size) {
+                Ok(candidate) if best.as_ref().is_none_or(|best| candidate.generation > best.generation) => {
+                    best = Some(candidate);
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+
+        let Some(superblock) = best else {
+            return Err(first_error.unwrap());
+        };
+
+        match (superblock.cipher, key) {
+            (NO_CIPHER, None) => {}
+            (NO_CIPHER, Some(_)) => return Err(Error::InvalidOptions("database is not encrypted".to_string())),
+            (AES_256_GCM, None) => return Err(Error::KeyRequired),
+            (AES_256_GCM, Some(key)) => {
+                pager.cipher = Some(Cipher::open(key, &superblock.salt, &superblock.key_check)?);
+                pager.salt = superblock.salt;
+                pager.key_check = superblock.key_check;
+            }
+            (cipher, _) => return Err(Error::CorruptHeader(format!("unknown cipher {}", cipher))),
+        }
+
+        let mut buf = vec![0; pager.page_size()];
+        let mut used = vec![false; file_pages as usize];
+        let mut next = superblock.directory;
+
+        used[..SLOTS as usize].fill(true);
+        pager.generation = superblock.generation;
+        pager.file_id = superblock.file_id;
+
+        while next != UNMAPPED {
+            pager.mark(&mut used, next)?;
+
+            if !pager.read_checked(next, &mut buf)? {
+                return Err(Error::Corrupt(format!("directory page {} fails checksum", next)));
+            }
+
+            pager.directory.push(next);
+
+            let count = read_u32(&buf, 4) as usize;
+
+            if count > pager.directory_entries() {
+                return Err(Error::Corrupt(format!("directory page {} holds {} entries", next, count)));
+            }
+
+            for index in 0..count {
+                pager.table_pages.push(read_u32(&buf, DIRECTORY_HEADER_SIZE + index * 4));
+            }
+
+            next = read_u32(&buf, 0);
+        }
+
+        let entries = pager.table_entries();
+
+        if pager.table_pages.len() != (superblock.page_count as usize).div_ceil(entries) {
+            return Err(Error::Corrupt(format!(
+                "{} table pages do not cover {} pages",
+                pager.table_pages.len(),
+                superblock.page_count
+            )));
+        }
+
+        for table_page in pager.table_pages.clone() {
+            pager.mark(&mut used, table_page)?;
+
+            if !pager.read_checked(table_page, &mut buf)? {
+                return Err(Error::Corrupt(format!("page table page {} fails checksum", table_page)));
+            }
+
+            let count = entries.min(superblock.page_count as usize - pager.table.len());
+
+            for index in 0..count {
+                let physical = read_u32(&buf, index * TABLE_ENTRY_SIZE);
+                let written = read_u64(&buf, index * TABLE_ENTRY_SIZE + 4);
+
+                if physical != UNMAPPED {
+                    pager.mark(&mut used, physical)?;
+                }
+
+                if written > superblock.generation {
+                    return Err(Error::Corrupt(format!(
+                        "page {} is written by commit {} after the last one",
+                        pager.table.len(),
+                        written
+                    )));
+                }
+
+                pager.table.push(physical);
+                pager.written.push(written);
+            }
+        }
+
+        for physical in (SLOTS..file_pages).rev() {
+            if !used[physical as usize] {
+                pager.spare.push(physical);
+            }
+        }
+
+        Ok(pager)
+    }
+
+    /**
+     * page size of the first slot with valid superblock, slot 0 starts the file
+     * and slot 1 is tried at every valid page size in case slot 0 is torn
+     * file without valid slot gets PAGE_SIZE, then open reports what is wrong with it
+     */
+    fn stored_page_size(file: &mut dyn Storage) -> Result<usize> {
+        let len = file.len()?;
+        let mut prefix = [0; SUPERBLOCK_SIZE];
+        let candidates = std::iter::once(0).chain((MIN_PAGE_SIZE.ilog2()..=MAX_PAGE_SIZE.ilog2()).map(|shift| 1u64 << shift));
+
+        for offset in candidates {
+            if offset + SUPERBLOCK_SIZE as u64 > len {
+                continue;
+            }
+
+            file.read_at(offset, &mut prefix)?;
+
+            match Superblock::page_size(&prefix) {
+                Some(page_size) if offset == 0 || offset == page_size as u64 => return Ok(page_size),
+                _ => {}
+            }
+        }
+
+        Ok(PAGE_SIZE)
+    }
+
+    /**
+     * records physical page as referenced by committed state, each one is referenced once
+     */
+    fn mark(&self, used: &mut [bool], physical: PageId) -> Result<()> {
+        match used.get_mut(physical as usize) {
+            Some(used) if !*used => {
+                *used = true;
+
+                Ok(())
+            }
+            Some(_) => Err(Error::Corrupt(format!("physical page {} is referenced twice", physical))),
+            None => Err(Error::Corrupt(format!(
+                "physical page {} is past the end of file with {} pages",
+                physical, self.file_pages
+            ))),
+        }
+    }
+
+    /**
+     * size of page in file, checksum trailer included
+     */
+    pub fn file_page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /**
+     * bytes of page available to caller, the rest of physical page holds checksum or nonce and tag
+     */
+    pub fn page_size(&self) -> usize {
+        match self.cipher {
+            Some(_) => self.page_size - SEAL_SIZE,
+            None => self.page_size - TRAILER_SIZE,
+        }
+    }
+
+    /**
+     * cipher of encrypted file, log of database seals its records with it too
+     */
+    pub(crate) fn cipher(&self) -> Option<&Cipher> {
+        self.cipher.as_ref()
+    }
+
+    /**
+     * with verification turned off pages failing checksum are returned as they are,
+     * lets recovery tooling read what is left of damaged file
+     */
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    pub fn page_count(&self) -> PageId {
+        self.table.len() as PageId
+    }
+
+    /**
+     * number of commits since file was created
+     */
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /**
+     * random id given to file at creation, see new_file_id
+     */
+    pub fn file_id(&self) -> u64 {
+        self.file_id
+    }
+
+    /**
+     * logical pages whose contents in committed state were written after commit generation
+     */
+    pub fn changed_since(&self, generation: u64) -> Vec<PageId> {
+        assert!(self.is_committed(), "only committed state is compared");
+
+        (0..self.page_count()).filter(|&page_id| self.written[page_id as usize] > generation).collect()
+    }
+
+    /**
+     * number of physical pages in file, superblocks and page table included
+     */
+    pub fn file_pages(&self) -> PageId {
+        self.file_pages
+    }
+
+    /**
+     * bytes after the last whole physical page, left by page write cut by crash, they are never read
+     */
+    pub fn torn_bytes(&self) -> Result<u64> {
+        Ok(self.file.len()?.saturating_sub(self.offset(self.file_pages)))
+    }
+
+    /**
+     * cuts torn bytes off file and syncs it, returns their number
+     */
+    pub fn trim(&mut self) -> Result<u64> {
+        let torn = self.torn_bytes()?;
+
+        if torn > 0 {
+            self.file.set_len(self.offset(self.file_pages))?;
+            self.file.sync()?;
+
+            if self.map.is_some() {
+                self.set_mmap(true);
+            }
+        }
+
+        Ok(torn)
+    }
+
+    /**
+     * physical pages of superblocks, page table and directory of the last commit
+     */
+    pub fn metadata_pages(&self) -> PageId {
+        SLOTS + self.table_pages.len() as PageId + self.directory.len() as PageId
+    }
+
+    /**
+     * turns reading through memory mapping of file on or off, returns whether it is on,
+     * storage which can not be mapped and encrypted file keep reading through storage
+     */
+    pub fn set_mmap(&mut self, mmap: bool) -> bool {
+        self.map = None;
+        self.verified.clear();
+
+        if mmap && self.cipher.is_none() {
+            self.map = self.file.map().ok();
+        }
+
+        self.map.is_some()
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.map.is_some()
+    }
+
+    /**
+     * true if page can be read by mapped_page
+     */
+    pub fn maps(&self, page_id: PageId) -> bool {
+        self.is_mapped() && self.table.get(page_id as usize).is_some_and(|physical| *physical != UNMAPPED)
+    }
+
+    /**
+     * contents of page straight from mapping, page must be one for which maps is true
+     * mapping is renewed when file has grown past it, checksum is verified once per written page
+     */
+    pub fn mapped_page(&mut self, page_id: PageId) -> Result<&[u8]> {
+        let physical = self.table[page_id as usize];
+        let start = self.offset(physical) as usize;
+        let end = start + self.page_size;
+
+        if self.map.as_ref().is_some_and(|map| map.as_slice().len() < end) {
+            self.map = Some(self.file.map()?);
+        }
+
+        if self.verify && !self.verified.contains(&physical) {
+            let page = &self.map.as_ref().unwrap().as_slice()[start..end];
+            let (data, trailer) = page.split_at(self.page_size());
+
+            if checksum(data).to_le_bytes() != trailer {
+                return Err(Error::ChecksumMismatch { page_id });
+            }
+
+            self.verified.insert(physical);
+        }
+
+        Ok(&self.map.as_ref().unwrap().as_slice()[start..end - TRAILER_SIZE])
+    }
+
+    /**
+     * logical pages mapped by one table page
+     */
+    fn table_entries(&self) -> usize {
+        self.page_size() / TABLE_ENTRY_SIZE
+    }
+
+    /**
+     * table pages listed by one directory page
+     */
+    fn directory_entries(&self) -> usize {
+        (self.page_size() - DIRECTORY_HEADER_SIZE) / 4
+    }
+
+    fn check(&self, page_id: PageId) -> Result<()> {
+        if page_id >= self.page_count() {
+            return Err(Error::PageOutOfBounds {
+                page_id,
+                page_count: self.page_count(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn offset(&self, physical: PageId) -> u64 {
+        physical as u64 * self.page_size as u64
+    }
+
+    fn read_physical(&mut self, physical: PageId, buf: &mut [u8]) -> Result<()> {
+        self.file.read_at(self.offset(physical), buf)?;
+
+        Ok(())
+    }
+
+    fn write_physical(&mut self, physical: PageId, buf: &[u8]) -> Result<()> {
+        self.file.write_at(self.offset(physical), buf)?;
+
+        Ok(())
+    }
+
+    /**
+     * file id and physical id, sealed page is authenticated along with them, so it is not accepted at another place
+     */
+    fn aad(&self, physical: PageId) -> [u8; 12] {
+        let mut aad = [0; 12];
+
+        aad[..8].copy_from_slice(&self.file_id.to_le_bytes());
+        aad[8..].copy_from_slice(&physical.to_le_bytes());
+
+        aad
+    }
+
+    /**
+     * reads page contents, returns false if they do not match checksum trailer or fail to unseal
+     */
+    fn read_checked(&mut self, physical: PageId, buf: &mut [u8]) -> Result<bool> {
+        let mut page = vec![0; self.page_size];
+
+        self.read_physical(physical, &mut page)?;
+
+        if let Some(cipher) = &self.cipher {
+            return Ok(cipher.unseal(&self.aad(physical), &page, buf));
+        }
+
+        let (data, trailer) = page.split_at(self.page_size());
+
+        buf.copy_from_slice(data);
+
+        Ok(checksum(data).to_le_bytes() == trailer)
+    }
+
+    /**
+     * writes page contents followed by their checksum, or sealed ones in encrypted file
+     */
+    fn write_checked(&mut self, physical: PageId, buf: &[u8]) -> Result<()> {
+        self.verified.remove(&physical);
+
+        let mut page = Vec::with_capacity(self.page_size);
+
+        match &self.cipher {
+            Some(cipher) => cipher.seal(&self.aad(physical), buf, &mut page),
+            None => {
+                page.extend_from_slice(buf);
+                page.extend_from_slice(&checksum(buf).to_le_bytes());
+            }
+        }
+
+        self.write_physical(physical, &page)
+    }
+
+    /**
+     * returns physical page free to write, file grows when there are no spare pages
+     */
+    fn take_physical(&mut self) -> PageId {
+        match self.spare.pop() {
+            Some(physical) => physical,
+            None => {
+                self.file_pages += 1;
+
+                self.file_pages - 1
+            }
+        }
+    }
+
+    /**
+     * returns id of free page, reusing freed pages first, new page reads as zeros
+     */
+    pub fn allocate_page(&mut self) -> Result<PageId> {
+        if let Some(page_id) = self.free.pop() {
+            return Ok(page_id);
+        }
+
+        let page_id = self.page_count();
+
+        self.table.push(UNMAPPED);
+        self.written.push(self.generation + 1);
+        self.dirty_tables.insert(page_id as usize / self.table_entries());
+
+        Ok(page_id)
+    }
+
+    /**
+     * returns page to pager, its contents are not cleared
+     */
+    pub fn free_page(&mut self, page_id: PageId) -> Result<()> {
+        self.check(page_id)?;
+
+        if page_id == HEADER_PAGE {
+            return Err(Error::ReservedPage(page_id));
+        }
+
+        debug_assert!(!self.free.contains(&page_id), "page {} is freed twice", page_id);
+
+        self.free.push(page_id);
+
+        Ok(())
+    }
+
+    /**
+     * buf must be exactly one page long, page failing checksum is Error::ChecksumMismatch
+     */
+    pub fn read_page(&mut self, page_id: PageId, buf: &mut [u8]) -> Result<()> {
+        self.check(page_id)?;
+        assert_eq!(buf.len(), self.page_size(), "buffer must hold exactly one page");
+
+        if self.maps(page_id) {
+            buf.copy_from_slice(self.mapped_page(page_id)?);
+
+            return Ok(());
+        }
+
+        match self.table[page_id as usize] {
+            UNMAPPED => buf.fill(0),
+            physical => {
+                if !self.read_checked(physical, buf)? && self.verify {
+                    return Err(Error::ChecksumMismatch { page_id });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * buf must be exactly one page long
+     * page of committed state is left intact, data goes to a fresh physical page
+     */
+    pub fn write_page(&mut self, page_id: PageId, buf: &[u8]) -> Result<()> {
+        self.check(page_id)?;
+        assert_eq!(buf.len(), self.page_size(), "buffer must hold exactly one page");
+
+        let physical = self.table[page_id as usize];
+
+        if self.fresh.contains(&physical) {
+            return self.write_checked(physical, buf);
+        }
+
+        let fresh = self.take_physical();
+
+        self.write_checked(fresh, buf)?;
+        self.fresh.insert(fresh);
+
+        if physical != UNMAPPED {
+            self.released.push((self.written[page_id as usize], physical));
+        }
+
+        self.table[page_id as usize] = fresh;
+        self.written[page_id as usize] = self.generation + 1;
+        self.dirty_tables.insert(page_id as usize / self.table_entries());
+
+        Ok(())
+    }
+
+    /**
+     * whether no page was written or allocated since the last commit
+     */
+    pub fn is_committed(&self) -> bool {
+        self.dirty_tables.is_empty()
+    }
+
+    /**
+     * physical page of every logical page in committed state, pager must be committed
+     * those pages are not overwritten until unpin_snapshot with the current generation,
+     * commits meanwhile grow file instead of reusing them, pages of generations after it are reused as usual
+     */
+    pub fn pin_snapshot(&mut self) -> Vec<PageId> {
+        assert!(self.is_committed(), "only committed state can be pinned");
+
+        *self.pinned.entry(self.generation).or_default() += 1;
+
+        self.table.clone()
+    }
+
+    /**
+     * releases snapshot pinned at generation, pages only it read are reused after the next commit or collect_garbage
+     */
+    pub fn unpin_snapshot(&mut self, generation: u64) {
+        let Some(count) = self.pinned.get_mut(&generation) else {
+            panic!("no snapshot is pinned at generation {}", generation);
+        };
+
+        *count -= 1;
+
+        if *count == 0 {
+            self.pinned.remove(&generation);
+        }
+
+        self.unpinned = true;
+    }
+
+    pub fn has_snapshot(&self) -> bool {
+        !self.pinned.is_empty()
+    }
+
+    /**
+     * generation of the oldest snapshot pinned
+     */
+    pub fn oldest_snapshot(&self) -> Option<u64> {
+        self.pinned.keys().next().copied()
+    }
+
+    /**
+     * number of physical pages kept for pinned snapshots only
+     */
+    pub fn held_pages(&self) -> usize {
+        self.held.len()
+    }
+
+    /**
+     * makes held pages no pinned snapshot reads spare, returns their number
+     */
+    pub fn collect_garbage(&mut self) -> usize {
+        let before = self.held.len();
+        let pinned = &self.pinned;
+        let spare = &mut self.spare;
+
+        self.held.retain(|&(committed, released, physical)| {
+            let read = pinned.range(committed..released).next().is_some();
+
+            if !read {
+                spare.push(physical);
+            }
+
+            read
+        });
+
+        self.unpinned = false;
+
+        before - self.held.len()
+    }
+
+    /**
+     * reads logical page of pinned snapshot from its physical page, 0 reads as zeros
+     */
+    pub fn read_snapshot(&mut self, page_id: PageId, physical: PageId, buf: &mut [u8]) -> Result<()> {
+        assert_eq!(buf.len(), self.page_size(), "buffer must hold exactly one page");
+
+        if physical == UNMAPPED {
+            buf.fill(0);
+        } else if !self.read_checked(physical, buf)? && self.verify {
+            return Err(Error::ChecksumMismatch { page_id });
+        }
+
+        Ok(())
+    }
+
+    /**
+     * flushes everything written to file to disk
+     */
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync()?;
+
+        Ok(())
+    }
+
+    fn write_superblock(&mut self, slot: PageId) -> Result<()> {
+        let superblock = Superblock {
+            generation: self.generation,
+            page_count: self.page_count(),
+            directory: self.directory.first().copied().unwrap_or(UNMAPPED),
+            file_id: self.file_id,
+            cipher: if self.cipher.is_some() { AES_256_GCM } else { NO_CIPHER },
+            salt: self.salt,
+            key_check: self.key_check,
+        };
+
+        let page = superblock.encode(self.page_size);
+
+        self.write_physical(slot, &page)
+    }
+
+    /**
+     * makes pages written since the last commit the committed state of file:
+     * changed table pages and directory go to fresh physical pages, file is synced,
+     * then superblock pointing to them is written to the older slot and synced
+     * crash before superblock is durable leaves previous commit intact
+     * without sync ordering is up to os and commit is not crash safe
+     */
+    pub fn commit(&mut self, sync: bool) -> Result<()> {
+        if self.dirty_tables.is_empty() {
+            return Ok(());
+        }
+
+        let entries = self.table_entries();
+
+        for index in std::mem::take(&mut self.dirty_tables) {
+            let start = index * entries;
+            let end = self.table.len().min(start + entries);
+            let mut page = Vec::with_capacity(self.page_size());
+
+            for (physical, written) in self.table[start..end].iter().zip(&self.written[start..end]) {
+                page.extend_from_slice(&physical.to_le_bytes());
+                page.extend_from_slice(&written.to_le_bytes());
+            }
+
+            page.resize(self.page_size(), 0);
+
+            let physical = self.take_physical();
+
+            self.write_checked(physical, &page)?;
+
+            if index < self.table_pages.len() {
+                self.released.push((self.generation + 1, self.table_pages[index]));
+                self.table_pages[index] = physical;
+            } else {
+                self.table_pages.push(physical);
+            }
+        }
+
+        let old_directory = std::mem::take(&mut self.directory);
+        let table_pages = self.table_pages.clone();
+        let mut pages = table_pages.chunks(self.directory_entries()).collect::<Vec<_>>();
+        let mut next = UNMAPPED;
+
+        self.released.extend(old_directory.into_iter().map(|physical| (self.generation + 1, physical)));
+
+        while let Some(chunk) = pages.pop() {
+            let mut page = Vec::with_capacity(self.page_size());
+
+            page.extend_from_slice(&next.to_le_bytes());
+            page.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+
+            for physical in chunk {
+                page.extend_from_slice(&physical.to_le_bytes());
+            }
+
+            page.resize(self.page_size(), 0);
+
+            next = self.take_physical();
+
+            self.write_checked(next, &page)?;
+            self.directory.push(next);
+        }
+
+        self.directory.reverse();
+
+        if sync {
+            self.file.sync()?;
+        }
+
+        self.generation += 1;
+        self.write_superblock((self.generation % SLOTS as u64) as PageId)?;
+
+        if sync {
+            self.file.sync()?;
+        }
+
+        for (committed, physical) in std::mem::take(&mut self.released) {
+            match self.pinned.range(committed..self.generation).next() {
+                Some(_) => self.held.push((committed, self.generation, physical)),
+                None => self.spare.push(physical),
+            }
+        }
+
+        if self.unpinned {
+            self.collect_garbage();
+        }
+
+        self.fresh.clear();
+
+        Ok(())
+    }
+}
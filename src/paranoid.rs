@@ -0,0 +1,123 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Debug;
+
+use crate::{BTree, Comparator, NodeId, NodeLayout, NodeVec};
+
+impl<T: Debug, C: Comparator<T>, L: NodeLayout> BTree<T, C, L> {
+    /**
+     * remembers node insert or delete stored, allocated or refreshed, see validate_touched
+     */
+    pub(crate) fn touch(&mut self, id: NodeId) {
+        if !self.touched.contains(&id) {
+            self.touched.push(id);
+        }
+    }
+
+    pub(crate) fn untouch(&mut self, id: NodeId) {
+        if let Some(i) = self.touched.iter().position(|touched| *touched == id) {
+            self.touched.remove(i);
+        }
+    }
+
+    /**
+     * checks nodes touched by op, which are its root-to-leaf path and split, merged or rotated siblings,
+     * and panics with drawing of tree at the first broken one, so bug shows at op which made it
+     * every node is checked against its children through cached first and last leaves,
+     * so check costs about t * height per touched node instead of walk of whole tree
+     */
+    pub(crate) fn validate_touched(&mut self, op: &str) {
+        while let Some(id) = self.touched.pop() {
+            if let Err(message) = self.check_node(id) {
+                panic!("{} broke tree: {}\n{}", op, message, self.to_ascii_string());
+            }
+        }
+    }
+
+    fn check_node(&self, id: NodeId) -> Result<(), String> {
+        let t = self.t;
+        let node = self.node(id);
+
+        if node.count != node.keys.len() {
+            return Err(format!("node {}: count {} but {} keys", id, node.count, node.keys.len()));
+        }
+
+        if node.count > 2 * t - 1 {
+            return Err(format!("node {}: {} keys is more than {}", id, node.count, 2 * t - 1));
+        }
+
+        if id != self.root && node.count < t - 1 {
+            return Err(format!("node {}: {} keys is less than {}", id, node.count, t - 1));
+        }
+
+        if node.keys.windows(2).any(|pair| self.cmp.compare(&pair[0], &pair[1]).is_gt()) {
+            return Err(format!("node {}: keys are not sorted {:?}", id, &*node.keys));
+        }
+
+        if node.leaf {
+            if !node.children.is_empty() {
+                return Err(format!("node {}: leaf has {} children", id, node.children.len()));
+            }
+
+            return match (node.first, node.last) {
+                (first, last) if first == id && last == id => Ok(()),
+                (first, last) => Err(format!("node {}: leaf caches leaves {}..{}", id, first, last)),
+            };
+        }
+
+        if node.children.len() != node.count + 1 {
+            return Err(format!("node {}: {} keys but {} children", id, node.count, node.children.len()));
+        }
+
+        let height = self.height(node.children[0]);
+
+        for (i, &child) in node.children.iter().enumerate() {
+            let child_height = self.height(child);
+
+            if child_height != height {
+                return Err(format!("node {}: child {} has height {}, expected {}", id, child, child_height, height));
+            }
+
+            if let (Some(key), Some(min)) = (i.checked_sub(1).map(|i| &node.keys[i]), self.min(child)) {
+                if self.cmp.compare(min, key).is_lt() {
+                    return Err(format!("node {}: child {} has key {:?} less than delimeter {:?}", id, child, min, key));
+                }
+            }
+
+            if let (Some(key), Some(max)) = (node.keys.get(i), self.max(child)) {
+                if self.cmp.compare(max, key).is_gt() {
+                    return Err(format!(
+                        "node {}: child {} has key {:?} greater than delimeter {:?}",
+                        id, child, max, key
+                    ));
+                }
+            }
+        }
+
+        let first = self.node(node.children[0]).first;
+        let last = self.node(node.children[node.count]).last;
+
+        if node.first != first || node.last != last {
+            return Err(format!(
+                "node {}: cached leaves {}..{}, expected {}..{}",
+                id, node.first, node.last, first, last
+            ));
+        }
+
+        Ok(())
+    }
+
+    /**
+     * number of nodes down to leaf along first children, leaf has height 1
+     */
+    fn height(&self, mut id: NodeId) -> usize {
+        let mut height = 1;
+
+        while !self.node(id).leaf {
+            id = self.node(id).children[0];
+            height += 1;
+        }
+
+        height
+    }
+}
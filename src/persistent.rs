@@ -0,0 +1,372 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::convert::Infallible;
+use core::fmt::Debug;
+use core::ops::{Bound, RangeBounds};
+
+use crate::node_store::{self, NodeStore};
+use crate::{Node, NodeId};
+
+/**
+ * node shared between versions of tree
+ * mutation goes through Arc::make_mut, so shared node is copied first (path copying)
+ */
+#[derive(Clone, Debug)]
+struct PersistentNode<T: Ord + Clone + Debug> {
+    leaf: bool,
+    keys: Vec<T>,
+    children: Vec<Arc<PersistentNode<T>>>,
+}
+
+impl<T: Ord + Clone + Debug> PersistentNode<T> {
+    fn leaf(t: usize) -> Self {
+        PersistentNode {
+            leaf: true,
+            keys: Vec::with_capacity(2 * t - 1),
+            children: vec![],
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        let i = self.keys.partition_point(|key| key < value);
+
+        if i < self.count() && self.keys[i] == *value {
+            return true;
+        }
+
+        !self.leaf && self.children[i].contains(value)
+    }
+
+    fn collect_into(&self, out: &mut Vec<T>) {
+        if self.leaf {
+            out.extend_from_slice(&self.keys);
+
+            return;
+        }
+
+        for i in 0..=self.count() {
+            self.children[i].collect_into(out);
+            if i != self.count() {
+                out.push(self.keys[i].clone());
+            }
+        }
+    }
+
+    /**
+     * keys of range in order, subtrees wholly before start are skipped, walk ends at first key after end
+     * returns false once it is there
+     */
+    fn collect_range(&self, range: &impl RangeBounds<T>, out: &mut Vec<T>) -> bool {
+        let first = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start) => self.keys.partition_point(|key| key < start),
+            Bound::Unbounded => 0,
+        };
+
+        for i in first..=self.count() {
+            if !self.leaf && !self.children[i].collect_range(range, out) {
+                return false;
+            }
+
+            let Some(key) = self.keys.get(i) else {
+                break;
+            };
+
+            let after = match range.end_bound() {
+                Bound::Included(end) => key > end,
+                Bound::Excluded(end) => key >= end,
+                Bound::Unbounded => false,
+            };
+
+            if after {
+                return false;
+            }
+
+            if range.contains(key) {
+                out.push(key.clone());
+            }
+        }
+
+        true
+    }
+}
+
+/**
+ * node of one operation, untouched ones stay shared, loaded ones are owned by it until it is done
+ */
+enum Slot<T: Ord + Clone + Debug> {
+    Root,
+    Shared(Arc<PersistentNode<T>>),
+    Owned(Node<T>),
+    Loaded,
+}
+
+/**
+ * store of shared insert and delete for one operation on tree, loaded node is moved out of its Arc
+ * if no other version holds it and copied otherwise, path it changed is put into new Arcs once it is done
+ */
+struct PathCopy<'a, T: Ord + Clone + Debug> {
+    tree: &'a mut PersistentBTree<T>,
+    root: NodeId,
+    slots: Vec<Slot<T>>,
+}
+
+impl<'a, T: Ord + Clone + Debug> PathCopy<'a, T> {
+    fn new(tree: &'a mut PersistentBTree<T>) -> Self {
+        PathCopy {
+            tree,
+            root: 0,
+            slots: vec![Slot::Root],
+        }
+    }
+
+    fn take(node: &mut Arc<PersistentNode<T>>) -> (bool, Vec<T>, Vec<Arc<PersistentNode<T>>>) {
+        let node = Arc::make_mut(node);
+
+        (node.leaf, core::mem::take(&mut node.keys), core::mem::take(&mut node.children))
+    }
+
+    fn build(&mut self, id: NodeId) -> Arc<PersistentNode<T>> {
+        match core::mem::replace(&mut self.slots[id as usize], Slot::Loaded) {
+            Slot::Root => self.tree.root.clone(),
+            Slot::Shared(node) => node,
+            Slot::Owned(node) => Arc::new(PersistentNode {
+                leaf: node.leaf,
+                children: node.children.iter().map(|&child| self.build(child)).collect(),
+                keys: node.keys.into_iter().collect(),
+            }),
+            Slot::Loaded => unreachable!("node of tree is loaded and never stored"),
+        }
+    }
+
+    fn finish(mut self) {
+        self.tree.root = self.build(self.root);
+    }
+}
+
+impl<T: Ord + Clone + Debug> NodeStore<T> for PathCopy<'_, T> {
+    type Error = Infallible;
+
+    fn t(&self) -> usize {
+        self.tree.t
+    }
+
+    fn root(&self) -> NodeId {
+        self.root
+    }
+
+    fn set_root(&mut self, id: NodeId) {
+        self.root = id;
+    }
+
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn allocator(&self) -> &() {
+        &()
+    }
+
+    fn load(&mut self, id: NodeId) -> Result<Node<T>, Infallible> {
+        let (leaf, keys, children) = match core::mem::replace(&mut self.slots[id as usize], Slot::Loaded) {
+            Slot::Root => Self::take(&mut self.tree.root),
+            Slot::Shared(mut node) => Self::take(&mut node),
+            Slot::Owned(node) => return Ok(node),
+            Slot::Loaded => unreachable!("node is loaded twice"),
+        };
+        let first = self.slots.len() as NodeId;
+
+        self.slots.extend(children.into_iter().map(Slot::Shared));
+
+        Ok(Node::from_parts(leaf, keys, first..self.slots.len() as NodeId))
+    }
+
+    fn store(&mut self, id: NodeId, node: Node<T>) -> Result<(), Infallible> {
+        self.slots[id as usize] = Slot::Owned(node);
+
+        Ok(())
+    }
+
+    /**
+     * child of unchanged node may still change, so it is copied along with the rest of path
+     */
+    fn unload(&mut self, id: NodeId, node: Node<T>) {
+        self.slots[id as usize] = Slot::Owned(node);
+    }
+
+    fn count(&mut self, id: NodeId) -> Result<usize, Infallible> {
+        Ok(match &self.slots[id as usize] {
+            Slot::Root => self.tree.root.count(),
+            Slot::Shared(node) => node.count(),
+            Slot::Owned(node) => node.count,
+            Slot::Loaded => unreachable!("count of loaded node"),
+        })
+    }
+
+    fn alloc(&mut self, node: Node<T>) -> Result<NodeId, Infallible> {
+        self.slots.push(Slot::Owned(node));
+
+        Ok((self.slots.len() - 1) as NodeId)
+    }
+
+    fn free(&mut self, id: NodeId) -> Result<(), Infallible> {
+        self.slots[id as usize] = Slot::Loaded;
+
+        Ok(())
+    }
+}
+
+/**
+ * B-tree with nodes shared between clones
+ * clone is O(1), mutation copies only nodes on the changed path,
+ * so earlier clones keep seeing their own contents
+ * insert and delete are the shared ones of node_store, run over PathCopy
+ */
+#[derive(Clone, Debug)]
+pub struct PersistentBTree<T: Ord + Clone + Debug> {
+    root: Arc<PersistentNode<T>>,
+    len: usize,
+    t: usize,
+}
+
+impl<T: Ord + Clone + Debug> PersistentBTree<T> {
+    pub fn new(t: usize) -> PersistentBTree<T> {
+        PersistentBTree {
+            root: Arc::new(PersistentNode::leaf(t)),
+            len: 0,
+            t,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) {
+        let mut store = PathCopy::new(self);
+
+        node_store::infallible(node_store::insert(&mut store, value));
+        store.finish();
+
+        self.len += 1;
+    }
+
+    /**
+     * removes one occurrence of value, tree which has no value is not copied
+     * returns status of operation: did element remove
+     */
+    pub fn delete(&mut self, value: &T) -> bool {
+        if !self.contains(value) {
+            return false;
+        }
+
+        let mut store = PathCopy::new(self);
+        let removed = node_store::infallible(node_store::delete(&mut store, |_, key| key.cmp(value)));
+
+        store.finish();
+        self.len -= 1;
+
+        removed.is_some()
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.root.contains(value)
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        let mut out = Vec::with_capacity(self.len);
+
+        self.root.collect_into(&mut out);
+
+        out
+    }
+
+    /**
+     * keys inside range in order
+     */
+    pub fn range(&self, range: impl RangeBounds<T>) -> Vec<T> {
+        let mut out = vec![];
+
+        self.root.collect_range(&range, &mut out);
+
+        out
+    }
+
+    /**
+     * true if both trees share the same root node
+     */
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.root, &other.root)
+    }
+
+    /**
+     * checks key order, bounds from delimeters, node sizes, equal leaf depth and len
+     */
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let t = self.t;
+        let mut stack = vec![(self.root.as_ref(), 0, None::<&T>, None::<&T>)];
+        let mut leaf_depth: Option<usize> = None;
+        let mut total = 0;
+
+        while let Some((node, depth, lower, upper)) = stack.pop() {
+            let is_root = core::ptr::eq(node, self.root.as_ref());
+
+            if node.count() > 2 * t - 1 || (!is_root && node.count() < t - 1) {
+                return Err(format!("node at depth {}: {} keys is out of bounds", depth, node.count()));
+            }
+
+            if node.keys.windows(2).any(|pair| pair[0] > pair[1]) {
+                return Err(format!("node at depth {}: keys are not sorted {:?}", depth, node.keys));
+            }
+
+            let below = lower.is_some_and(|lower| node.keys.first().is_some_and(|first| first < lower));
+            let above = upper.is_some_and(|upper| node.keys.last().is_some_and(|last| last > upper));
+
+            if below || above {
+                return Err(format!("node at depth {}: keys {:?} are out of delimeters", depth, node.keys));
+            }
+
+            total += node.count();
+
+            if node.leaf {
+                match leaf_depth {
+                    None => leaf_depth = Some(depth),
+                    Some(expected) if expected != depth => {
+                        return Err(format!("leaf at depth {}, expected {}", depth, expected));
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            if node.children.len() != node.count() + 1 {
+                return Err(format!("node at depth {}: {} keys but {} children", depth, node.count(), node.children.len()));
+            }
+
+            for i in 0..=node.count() {
+                let child_lower = if i == 0 { lower } else { Some(&node.keys[i - 1]) };
+                let child_upper = if i == node.count() { upper } else { Some(&node.keys[i]) };
+
+                stack.push((node.children[i].as_ref(), depth + 1, child_lower, child_upper));
+            }
+        }
+
+        if total != self.len {
+            return Err(format!("tree has {} keys, but len is {}", total, self.len));
+        }
+
+        Ok(())
+    }
+}
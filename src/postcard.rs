@@ -0,0 +1,95 @@
+use std::fmt::Debug;
+
+use crate::codec::{take, Codec};
+use crate::snapshot::SnapshotError;
+use crate::{BTree, MAX_T};
+
+/**
+ * compact layout without header or checksum, laid out the way postcard lays out (t, [key bytes]):
+ * t and key count as varints, then keys in sorted order as (varint length, encoded bytes)
+ * varint is unsigned leb128: 7 bits per byte, low bits first, high bit set on every byte but the last
+ */
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, SnapshotError> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let byte = *take(bytes, 1).ok_or(SnapshotError::Truncated)?.first().unwrap();
+
+        if shift == 63 && byte > 1 {
+            break;
+        }
+
+        value |= ((byte & 0x7f) as u64) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(SnapshotError::Corrupt("varint does not fit in 64 bits".to_string()))
+}
+
+impl<T: Ord + Debug + Codec> BTree<T> {
+    /**
+     * encodes t and keys in compact layout for embedding into other files, see save_to for layout with checksum
+     */
+    pub fn to_bytes_postcard(&self) -> Vec<u8> {
+        let mut out = vec![];
+        let mut buf = vec![];
+
+        write_varint(&mut out, self.t as u64);
+        write_varint(&mut out, self.len as u64);
+
+        for key in self.iter() {
+            buf.clear();
+            key.encode(&mut buf);
+
+            write_varint(&mut out, buf.len() as u64);
+            out.extend_from_slice(&buf);
+        }
+
+        out
+    }
+
+    /**
+     * bulk loads tree from bytes written by to_bytes_postcard, bytes must hold exactly one tree
+     * key order is validated, short input is SnapshotError::Truncated and any other mismatch is SnapshotError::Corrupt
+     */
+    pub fn from_bytes_postcard(mut bytes: &[u8]) -> Result<BTree<T>, SnapshotError> {
+        let t = read_varint(&mut bytes)?;
+
+        if !(2..=MAX_T as u64).contains(&t) {
+            return Err(SnapshotError::Corrupt(format!("branching factor {} is out of range", t)));
+        }
+
+        let count = read_varint(&mut bytes)?;
+        let mut keys: Vec<T> = Vec::with_capacity(count.min(bytes.len() as u64) as usize);
+
+        for i in 0..count {
+            let len = read_varint(&mut bytes)?;
+            let buf = take(&mut bytes, len.try_into().unwrap_or(usize::MAX)).ok_or(SnapshotError::Truncated)?;
+            let key = T::decode(buf).ok_or_else(|| SnapshotError::Corrupt(format!("key {} can not be decoded", i)))?;
+
+            if keys.last().is_some_and(|last| *last > key) {
+                return Err(SnapshotError::Corrupt(format!("key {} is out of order", i)));
+            }
+
+            keys.push(key);
+        }
+
+        if !bytes.is_empty() {
+            return Err(SnapshotError::Corrupt(format!("bytes follow the last key: {}", bytes.len())));
+        }
+
+        Ok(BTree::from_sorted(t as usize, keys, 1.0))
+    }
+}
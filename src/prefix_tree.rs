@@ -0,0 +1,427 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::convert::Infallible;
+
+use crate::node_store::{self, NodeStore};
+use crate::{MemoryUsage, Node, NodeId};
+
+/**
+ * key which is stored as bytes in PrefixBTree
+ */
+pub trait PrefixKey {
+    fn as_key_bytes(&self) -> &[u8];
+    fn into_key_bytes(self) -> Vec<u8>;
+    fn from_key_bytes(bytes: Vec<u8>) -> Self;
+}
+
+impl PrefixKey for Vec<u8> {
+    fn as_key_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn into_key_bytes(self) -> Vec<u8> {
+        self
+    }
+
+    fn from_key_bytes(bytes: Vec<u8>) -> Self {
+        bytes
+    }
+}
+
+impl PrefixKey for String {
+    fn as_key_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+
+    fn into_key_bytes(self) -> Vec<u8> {
+        self.into_bytes()
+    }
+
+    /**
+     * bytes always come from concatenation of prefix and suffix of valid string
+     */
+    fn from_key_bytes(bytes: Vec<u8>) -> Self {
+        String::from_utf8(bytes).expect("key is built from valid utf-8 parts")
+    }
+}
+
+/**
+ * node keeps longest common prefix of its keys once and only suffixes per key
+ * prefix of sorted keys is common prefix of the first and the last one
+ */
+#[derive(Clone, Debug, Default)]
+struct PrefixNode {
+    leaf: bool,
+    prefix: Vec<u8>,
+    suffixes: Vec<Vec<u8>>,
+    children: Vec<NodeId>,
+}
+
+impl PrefixNode {
+    fn count(&self) -> usize {
+        self.suffixes.len()
+    }
+
+    fn key(&self, i: usize) -> Vec<u8> {
+        [self.prefix.as_slice(), self.suffixes[i].as_slice()].concat()
+    }
+
+    /**
+     * compares probe with i-th key without building the key
+     */
+    fn compare(&self, i: usize, probe: &[u8]) -> Ordering {
+        let split = self.prefix.len().min(probe.len());
+
+        match self.prefix[..split].cmp(&probe[..split]) {
+            Ordering::Equal if probe.len() < self.prefix.len() => Ordering::Greater,
+            Ordering::Equal => self.suffixes[i].as_slice().cmp(&probe[split..]),
+            ordering => ordering,
+        }
+    }
+
+    /**
+     * index of the first key which is not less than probe
+     */
+    fn lower_bound(&self, probe: &[u8]) -> usize {
+        self.position(probe, |ordering| ordering == Ordering::Less)
+    }
+
+    fn position(&self, probe: &[u8], before: impl Fn(Ordering) -> bool) -> usize {
+        let split = self.prefix.len().min(probe.len());
+
+        match self.prefix[..split].cmp(&probe[..split]) {
+            Ordering::Less => return self.count(),
+            Ordering::Greater => return 0,
+            Ordering::Equal if probe.len() < self.prefix.len() => return 0,
+            Ordering::Equal => {}
+        }
+
+        let rest = &probe[split..];
+
+        self.suffixes.partition_point(|suffix| before(suffix.as_slice().cmp(rest)))
+    }
+
+    /**
+     * takes full keys out of node
+     */
+    fn expand(&mut self) -> Vec<Vec<u8>> {
+        let prefix = core::mem::take(&mut self.prefix);
+
+        core::mem::take(&mut self.suffixes)
+            .into_iter()
+            .map(|suffix| [prefix.as_slice(), suffix.as_slice()].concat())
+            .collect()
+    }
+
+    /**
+     * puts sorted full keys into node, recomputing shared prefix
+     */
+    fn compress(&mut self, keys: Vec<Vec<u8>>) {
+        let prefix_len = match (keys.first(), keys.last()) {
+            (Some(first), Some(last)) => first.iter().zip(last.iter()).take_while(|(a, b)| a == b).count(),
+            _ => 0,
+        };
+
+        self.prefix = keys.first().map_or(vec![], |first| first[..prefix_len].to_vec());
+        self.suffixes = keys.into_iter().map(|key| key[prefix_len..].to_vec()).collect();
+    }
+
+    /**
+     * node of shared insert and delete put back into compressed form
+     */
+    fn compressed(node: Node<Vec<u8>>) -> PrefixNode {
+        let mut compressed = PrefixNode {
+            leaf: node.leaf,
+            children: node.children.into_iter().collect(),
+            ..PrefixNode::default()
+        };
+
+        compressed.compress(node.keys.into_iter().collect());
+
+        compressed
+    }
+}
+
+/**
+ * B-tree over string or byte keys with prefix compression inside nodes
+ * keys are rebuilt from prefix and suffix only when they are handed out or node is loaded,
+ * insert and delete are the shared ones of node_store, every node they store gets its prefix recomputed
+ */
+pub struct PrefixBTree<K: PrefixKey> {
+    nodes: Vec<PrefixNode>,
+    free: Vec<NodeId>,
+    root: NodeId,
+    len: usize,
+    t: usize,
+    marker: core::marker::PhantomData<K>,
+}
+
+impl<K: PrefixKey> PrefixBTree<K> {
+    pub fn new(t: usize) -> PrefixBTree<K> {
+        PrefixBTree {
+            nodes: vec![PrefixNode {
+                leaf: true,
+                ..PrefixNode::default()
+            }],
+            free: vec![],
+            root: 0,
+            len: 0,
+            t,
+            marker: core::marker::PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn node(&self, id: NodeId) -> &PrefixNode {
+        &self.nodes[id as usize]
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut PrefixNode {
+        &mut self.nodes[id as usize]
+    }
+
+    fn alloc(&mut self, node: PrefixNode) -> NodeId {
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id as usize] = node;
+
+                id
+            }
+            None => {
+                self.nodes.push(node);
+
+                (self.nodes.len() - 1) as NodeId
+            }
+        }
+    }
+
+    fn release(&mut self, id: NodeId) {
+        self.nodes[id as usize] = PrefixNode::default();
+        self.free.push(id);
+    }
+
+    pub fn insert(&mut self, key: K) {
+        node_store::infallible(node_store::insert(self, key.into_key_bytes()));
+
+        self.len += 1;
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        let probe = key.as_key_bytes();
+        let mut node = self.node(self.root);
+
+        loop {
+            let i = node.lower_bound(probe);
+
+            if i < node.count() && node.compare(i, probe) == Ordering::Equal {
+                return true;
+            }
+
+            if node.leaf {
+                return false;
+            }
+
+            node = self.node(node.children[i]);
+        }
+    }
+
+    /**
+     * removes one occurrence of key
+     * returns status of operation: did element remove
+     */
+    pub fn delete(&mut self, key: &K) -> bool {
+        let probe = key.as_key_bytes();
+        let removed = node_store::infallible(node_store::delete(self, |_, key| key.as_slice().cmp(probe))).is_some();
+
+        if removed {
+            self.len -= 1;
+        }
+
+        removed
+    }
+
+    fn collect_into(&self, id: NodeId, out: &mut Vec<K>) {
+        let node = self.node(id);
+
+        for i in 0..node.count() {
+            if !node.leaf {
+                self.collect_into(node.children[i], out);
+            }
+
+            out.push(K::from_key_bytes(node.key(i)));
+        }
+
+        if !node.leaf {
+            self.collect_into(node.children[node.count()], out);
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<K> {
+        let mut out = Vec::with_capacity(self.len);
+
+        self.collect_into(self.root, &mut out);
+
+        out
+    }
+
+    /**
+     * key bytes are prefixes and suffixes actually stored, payload_bytes is their heap capacity
+     */
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let key = core::mem::size_of::<Vec<u8>>();
+        let child = core::mem::size_of::<NodeId>();
+
+        let mut usage = MemoryUsage {
+            node_bytes: self.nodes.capacity() * core::mem::size_of::<PrefixNode>() + self.free.capacity() * child,
+            ..MemoryUsage::default()
+        };
+
+        let mut stack = vec![self.root];
+
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+
+            usage.nodes += 1;
+            usage.key_bytes += node.suffixes.len() * key;
+            usage.key_capacity_bytes += node.suffixes.capacity() * key;
+            usage.child_bytes += node.children.len() * child;
+            usage.child_capacity_bytes += node.children.capacity() * child;
+            usage.payload_bytes +=
+                node.prefix.capacity() + node.suffixes.iter().map(|suffix| suffix.capacity()).sum::<usize>();
+
+            stack.extend(node.children.iter());
+        }
+
+        usage.total = usage.node_bytes + usage.key_capacity_bytes + usage.child_capacity_bytes + usage.payload_bytes;
+
+        usage
+    }
+
+    /**
+     * checks key order, node sizes and equal leaf depth
+     */
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let t = self.t;
+        let mut stack: Vec<(NodeId, usize)> = vec![(self.root, 0)];
+        let mut leaf_depth: Option<usize> = None;
+        let mut total = 0;
+
+        while let Some((id, depth)) = stack.pop() {
+            let node = self.node(id);
+            let keys: Vec<Vec<u8>> = (0..node.count()).map(|i| node.key(i)).collect();
+
+            if keys.len() > 2 * t - 1 || (id != self.root && keys.len() < t - 1) {
+                return Err(format!("node {}: {} keys is out of bounds", id, keys.len()));
+            }
+
+            if keys.windows(2).any(|pair| pair[0] > pair[1]) {
+                return Err(format!("node {}: keys are not sorted", id));
+            }
+
+            total += keys.len();
+
+            if node.leaf {
+                match leaf_depth {
+                    None => leaf_depth = Some(depth),
+                    Some(expected) if expected != depth => {
+                        return Err(format!("node {}: leaf at depth {}, expected {}", id, depth, expected));
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            if node.children.len() != keys.len() + 1 {
+                return Err(format!("node {}: {} keys but {} children", id, keys.len(), node.children.len()));
+            }
+
+            for (i, child) in node.children.iter().enumerate() {
+                let child_node = self.node(*child);
+                let child_keys = (0..child_node.count()).map(|j| child_node.key(j));
+
+                for child_key in child_keys {
+                    if (i > 0 && child_key < keys[i - 1]) || (i < keys.len() && child_key > keys[i]) {
+                        return Err(format!("node {}: key of child {} is out of delimeters", id, child));
+                    }
+                }
+
+                stack.push((*child, depth + 1));
+            }
+        }
+
+        if total != self.len {
+            return Err(format!("tree has {} keys, but len is {}", total, self.len));
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * arena as store of shared insert and delete, loaded node is expanded into full keys
+ */
+impl<K: PrefixKey> NodeStore<Vec<u8>> for PrefixBTree<K> {
+    type Error = Infallible;
+
+    fn t(&self) -> usize {
+        self.t
+    }
+
+    fn root(&self) -> NodeId {
+        self.root
+    }
+
+    fn set_root(&mut self, id: NodeId) {
+        self.root = id;
+    }
+
+    fn compare(&self, a: &Vec<u8>, b: &Vec<u8>) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn allocator(&self) -> &() {
+        &()
+    }
+
+    fn load(&mut self, id: NodeId) -> Result<Node<Vec<u8>>, Infallible> {
+        let node = self.node_mut(id);
+        let keys = node.expand();
+
+        Ok(Node::from_parts(node.leaf, keys, core::mem::take(&mut node.children)))
+    }
+
+    fn store(&mut self, id: NodeId, node: Node<Vec<u8>>) -> Result<(), Infallible> {
+        *self.node_mut(id) = PrefixNode::compressed(node);
+
+        Ok(())
+    }
+
+    fn unload(&mut self, id: NodeId, node: Node<Vec<u8>>) {
+        *self.node_mut(id) = PrefixNode::compressed(node);
+    }
+
+    fn count(&mut self, id: NodeId) -> Result<usize, Infallible> {
+        Ok(self.node(id).count())
+    }
+
+    fn alloc(&mut self, node: Node<Vec<u8>>) -> Result<NodeId, Infallible> {
+        Ok(PrefixBTree::alloc(self, PrefixNode::compressed(node)))
+    }
+
+    fn free(&mut self, id: NodeId) -> Result<(), Infallible> {
+        self.release(id);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+use std::sync::Arc;
+
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyInt, PyIterator, PyList, PyString, PyTuple};
+
+use crate::{BTree, SharedBTree, SharedMap};
+
+/**
+ * key of python trees, int, str or bytes, keys of different types are ordered ints, strs, bytes
+ */
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    Int(i64),
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+/**
+ * value of Map, only its reference count is touched without GIL
+ */
+type Value = Arc<Py<PyAny>>;
+
+fn key(object: &Bound<'_, PyAny>) -> PyResult<Key> {
+    if let Ok(bytes) = object.cast::<PyBytes>() {
+        Ok(Key::Bytes(bytes.as_bytes().to_vec()))
+    } else if let Ok(string) = object.cast::<PyString>() {
+        Ok(Key::Str(string.to_str()?.to_owned()))
+    } else if object.is_instance_of::<PyInt>() {
+        Ok(Key::Int(object.extract()?))
+    } else {
+        Err(PyTypeError::new_err(format!("keys are int, str or bytes, not {}", object.get_type().name()?)))
+    }
+}
+
+fn keys(objects: &Bound<'_, PyAny>) -> PyResult<Vec<Key>> {
+    objects.try_iter()?.map(|object| key(&object?)).collect()
+}
+
+fn object<'py>(py: Python<'py>, key: &Key) -> Bound<'py, PyAny> {
+    match key {
+        Key::Int(int) => PyInt::new(py, *int).into_any(),
+        Key::Str(string) => PyString::new(py, string).into_any(),
+        Key::Bytes(bytes) => PyBytes::new(py, bytes).into_any(),
+    }
+}
+
+fn list<'py>(py: Python<'py>, keys: &[Key]) -> PyResult<Bound<'py, PyList>> {
+    PyList::new(py, keys.iter().map(|key| object(py, key)))
+}
+
+fn check_t(t: usize) -> PyResult<usize> {
+    if t < 2 {
+        return Err(PyValueError::new_err("t must be at least 2"));
+    }
+
+    Ok(t)
+}
+
+/**
+ * srdb.BTree, sorted multiset of keys over SharedBTree, so calls from several python threads are safe
+ * bulk loads and scans run without GIL, other python threads go on meanwhile
+ */
+#[pyclass(name = "BTree", module = "srdb", frozen)]
+pub struct PyBTree {
+    tree: SharedBTree<Key>,
+}
+
+#[pymethods]
+impl PyBTree {
+    #[new]
+    #[pyo3(signature = (t = 16))]
+    fn new(t: usize) -> PyResult<Self> {
+        Ok(PyBTree {
+            tree: SharedBTree::new(check_t(t)?),
+        })
+    }
+
+    /**
+     * tree of keys in any order, they are sorted and loaded into full nodes without GIL
+     */
+    #[staticmethod]
+    #[pyo3(signature = (keys, t = 16))]
+    fn bulk_load(py: Python<'_>, keys: &Bound<'_, PyAny>, t: usize) -> PyResult<Self> {
+        let (t, mut keys) = (check_t(t)?, self::keys(keys)?);
+
+        let tree = py.detach(|| {
+            keys.sort();
+
+            BTree::from_sorted(t, keys, 1.0)
+        });
+
+        Ok(PyBTree { tree: tree.into() })
+    }
+
+    /**
+     * duplicates are kept
+     */
+    fn insert(&self, key: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.tree.insert(self::key(key)?);
+
+        Ok(())
+    }
+
+    /**
+     * inserts all keys of iterable at once without GIL, readers see either none or all of them
+     */
+    fn update(&self, py: Python<'_>, keys: &Bound<'_, PyAny>) -> PyResult<()> {
+        let keys = self::keys(keys)?;
+
+        py.detach(|| self.tree.insert_batch(keys));
+
+        Ok(())
+    }
+
+    /**
+     * removes one occurrence of key, returns whether it was there
+     */
+    fn remove(&self, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.tree.delete(&self::key(key)?))
+    }
+
+    fn __contains__(&self, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.tree.contains(self::key(key)?))
+    }
+
+    fn __len__(&self) -> usize {
+        self.tree.len()
+    }
+
+    /**
+     * walks copy of keys as of its start in order
+     */
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        self.to_list(py)?.try_iter()
+    }
+
+    /**
+     * keys from lo inclusive to hi exclusive in order, None is no bound, scanned without GIL
+     */
+    #[pyo3(signature = (lo = None, hi = None))]
+    fn range<'py>(
+        &self,
+        py: Python<'py>,
+        lo: Option<&Bound<'py, PyAny>>,
+        hi: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyList>> {
+        let (lo, hi) = (lo.map(key).transpose()?, hi.map(key).transpose()?);
+
+        let keys: Vec<Key> = py.detach(|| {
+            self.tree.read_with(|tree| {
+                tree.iter()
+                    .skip_while(|key| lo.as_ref().is_some_and(|lo| *key < lo))
+                    .take_while(|key| hi.as_ref().is_none_or(|hi| *key < hi))
+                    .cloned()
+                    .collect()
+            })
+        });
+
+        list(py, &keys)
+    }
+
+    fn to_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let keys = py.detach(|| self.tree.to_vec());
+
+        list(py, &keys)
+    }
+
+    /**
+     * raises ValueError naming broken invariant of tree
+     */
+    fn check_invariants(&self) -> PyResult<()> {
+        self.tree.check_invariants().map_err(PyValueError::new_err)
+    }
+}
+
+/**
+ * srdb.Map, dict with keys in order over SharedMap, values are any python objects
+ * iteration is by keys, keys, values and items are lists as of one moment, copied without GIL
+ */
+#[pyclass(name = "Map", module = "srdb", frozen)]
+pub struct PyMap {
+    map: SharedMap<Key, Value>,
+}
+
+#[pymethods]
+impl PyMap {
+    #[new]
+    #[pyo3(signature = (t = 16))]
+    fn new(t: usize) -> PyResult<Self> {
+        Ok(PyMap {
+            map: SharedMap::new(check_t(t)?),
+        })
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        match self.map.get(&self::key(key)?) {
+            Some(value) => Ok(value.clone_ref(py)),
+            None => Err(PyKeyError::new_err(key.clone().unbind())),
+        }
+    }
+
+    fn __setitem__(&self, key: &Bound<'_, PyAny>, value: Py<PyAny>) -> PyResult<()> {
+        self.map.insert(self::key(key)?, Arc::new(value));
+
+        Ok(())
+    }
+
+    fn __delitem__(&self, key: &Bound<'_, PyAny>) -> PyResult<()> {
+        match self.map.remove(&self::key(key)?) {
+            Some(_) => Ok(()),
+            None => Err(PyKeyError::new_err(key.clone().unbind())),
+        }
+    }
+
+    fn __contains__(&self, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.map.contains_key(&self::key(key)?))
+    }
+
+    fn __len__(&self) -> usize {
+        self.map.len()
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        self.keys(py)?.try_iter()
+    }
+
+    #[pyo3(signature = (key, default = None))]
+    fn get(&self, py: Python<'_>, key: &Bound<'_, PyAny>, default: Option<Py<PyAny>>) -> PyResult<Option<Py<PyAny>>> {
+        Ok(self.map.get(&self::key(key)?).map(|value| value.clone_ref(py)).or(default))
+    }
+
+    fn keys<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let keys: Vec<Key> =
+            py.detach(|| self.map.read_with(|tree| tree.iter().map(|entry| entry.key.clone()).collect()));
+
+        list(py, &keys)
+    }
+
+    fn values<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let entries = py.detach(|| self.map.to_vec());
+
+        PyList::new(py, entries.iter().map(|(_, value)| value.bind(py)))
+    }
+
+    fn items<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let entries = py.detach(|| self.map.to_vec());
+        let items = entries
+            .iter()
+            .map(|(key, value)| PyTuple::new(py, [object(py, key), value.bind(py).clone()]))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        PyList::new(py, items)
+    }
+}
+
+/**
+ * python module srdb, crate srdb-python builds it as extension: cargo build --release -p srdb-python,
+ * then libsrdb_python.so copied as srdb.so into python path is imported as srdb
+ */
+#[pymodule]
+#[pyo3(name = "srdb")]
+pub fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyBTree>()?;
+    module.add_class::<PyMap>()?;
+
+    Ok(())
+}
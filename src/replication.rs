@@ -0,0 +1,330 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::{PoisonError, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::batch::WriteBatch;
+use crate::codec::Codec;
+use crate::crc32::checksum;
+use crate::db::Db;
+use crate::error::{Error, Result};
+use crate::options::SrdbOptions;
+
+/**
+ * replication protocol, follower speaks first with one line:
+ *   FOLLOW <seq>   asks for batches after seq as they are committed
+ *   SNAPSHOT       asks for copy of database
+ * primary answers with frames: kind (u8), seq (u64), payload length (u32), payload, crc32 of all before it (u32)
+ *   BATCH      batch with its seq, payload is encoded WriteBatch
+ *   HEARTBEAT  seq of primary, sent while follower is caught up
+ *   RESYNC     seq of primary, backlog no longer holds batches follower needs, payload is reason, stream ends
+ *   SNAPSHOT   seq copy is taken at, dump in DumpWriter format follows, stream ends after it
+ */
+const BATCH: u8 = 1;
+const HEARTBEAT: u8 = 2;
+const RESYNC: u8 = 3;
+const SNAPSHOT: u8 = 4;
+
+const FRAME_HEADER_SIZE: usize = 13;
+
+/**
+ * payload of one frame, at most, so corrupt length does not allocate whole memory
+ */
+const MAX_PAYLOAD: usize = 256 << 20;
+
+/**
+ * length of request line, at most
+ */
+const MAX_REQUEST: u64 = 64;
+
+/**
+ * how often primary looks for new batches while follower is caught up
+ */
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/**
+ * silence after which primary sends heartbeat, follower may take a few of them missing as lost connection
+ */
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/**
+ * batches primary takes from backlog under one lock
+ */
+const SHIP_BATCHES: usize = 256;
+
+/**
+ * batches committed by primary, kept for followers to catch up from, oldest ones go once they take over limit bytes
+ * batches are consecutive, start is seq of the one before the oldest, so positions from start on can be served
+ */
+#[derive(Debug)]
+pub(crate) struct Backlog {
+    batches: VecDeque<(u64, WriteBatch, usize)>,
+    bytes: u64,
+    limit: u64,
+    start: u64,
+}
+
+impl Backlog {
+    pub fn new(limit: u64, start: u64) -> Backlog {
+        Backlog {
+            batches: VecDeque::new(),
+            bytes: 0,
+            limit,
+            start,
+        }
+    }
+
+    /**
+     * adds batch following the newest one
+     */
+    pub fn push(&mut self, seq: u64, batch: &WriteBatch) {
+        let mut payload = vec![];
+
+        batch.encode(&mut payload);
+        debug_assert_eq!(seq, self.start + self.batches.len() as u64 + 1);
+
+        self.batches.push_back((seq, batch.clone(), payload.len()));
+        self.bytes += payload.len() as u64;
+
+        while self.bytes > self.limit {
+            let Some((seq, _, len)) = self.batches.pop_front() else {
+                break;
+            };
+
+            self.bytes -= len as u64;
+            self.start = seq;
+        }
+    }
+
+    /**
+     * forgets every batch, positions before seq can no longer be served
+     */
+    pub fn reset(&mut self, seq: u64) {
+        self.batches.clear();
+        self.bytes = 0;
+        self.start = seq;
+    }
+
+    /**
+     * up to max batches after position, last is seq of primary
+     * position before backlog or past primary is Error::ResyncNeeded
+     */
+    pub fn since(&self, position: u64, last: u64, max: usize) -> Result<Vec<(u64, WriteBatch)>> {
+        if position < self.start {
+            return Err(Error::ResyncNeeded(format!(
+                "follower is at {}, but backlog of primary starts after {}",
+                position, self.start
+            )));
+        }
+
+        if position > last {
+            return Err(Error::ResyncNeeded(format!("follower is at {}, ahead of primary at {}", position, last)));
+        }
+
+        let skip = (position - self.start) as usize;
+
+        Ok(self.batches.iter().skip(skip).take(max).map(|(seq, batch, _)| (*seq, batch.clone())).collect())
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::Io(io::Error::new(ErrorKind::InvalidData, message))
+}
+
+fn write_frame(w: &mut impl Write, kind: u8, seq: u64, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len() + 4);
+
+    frame.push(kind);
+    frame.extend_from_slice(&seq.to_le_bytes());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame.extend_from_slice(&checksum(&frame).to_le_bytes());
+
+    w.write_all(&frame)
+}
+
+/**
+ * kind, seq and payload of the next frame, None when stream ends before it
+ */
+fn read_frame(r: &mut impl Read) -> Result<Option<(u8, u64, Vec<u8>)>> {
+    let mut header = [0; FRAME_HEADER_SIZE];
+
+    match r.read_exact(&mut header) {
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+
+    let seq = u64::from_le_bytes(header[1..9].try_into().unwrap());
+    let len = u32::from_le_bytes(header[9..13].try_into().unwrap()) as usize;
+
+    if len > MAX_PAYLOAD {
+        return Err(invalid(format!("frame of {} bytes is over {}", len, MAX_PAYLOAD)));
+    }
+
+    let mut rest = vec![0; len + 4];
+
+    r.read_exact(&mut rest)?;
+
+    let crc = u32::from_le_bytes(rest[len..].try_into().unwrap());
+    let mut frame = header.to_vec();
+
+    frame.extend_from_slice(&rest[..len]);
+
+    if checksum(&frame) != crc {
+        return Err(invalid(format!("frame of batch {} fails checksum", seq)));
+    }
+
+    rest.truncate(len);
+
+    Ok(Some((header[0], seq, rest)))
+}
+
+/**
+ * primary side of replication connection, see protocol above, db must have replication backlog turned on
+ * answers FOLLOW with batches after position as they are committed and with heartbeats while there are none,
+ * SNAPSHOT with dump of database, db is read locked while it is written, so writes wait for follower to take it
+ * returns once follower goes away, after snapshot or resync, or when stop returns true
+ */
+pub fn serve_follower(db: &RwLock<Db>, mut r: impl BufRead, mut w: impl Write, stop: impl Fn() -> bool) -> Result<()> {
+    let lock = || db.read().unwrap_or_else(PoisonError::into_inner);
+    let mut line = String::new();
+
+    (&mut r).take(MAX_REQUEST).read_line(&mut line)?;
+
+    let request = line.trim_end();
+    let mut position = match request.split_once(' ') {
+        Some(("FOLLOW", seq)) => seq.parse().map_err(|_| invalid(format!("invalid position {:?}", seq)))?,
+        None if request == "SNAPSHOT" => {
+            let db = lock();
+
+            write_frame(&mut w, SNAPSHOT, db.seq(), &[])?;
+            db.dump(&mut w)?;
+
+            return Ok(w.flush()?);
+        }
+        _ => return Err(invalid(format!("unknown request {:?}", request))),
+    };
+
+    let mut quiet = Instant::now();
+
+    while !stop() {
+        let (seq, batches) = {
+            let db = lock();
+
+            (db.seq(), db.changes_since(position, SHIP_BATCHES))
+        };
+
+        let batches = match batches {
+            Ok(batches) => batches,
+            Err(Error::ResyncNeeded(reason)) => {
+                write_frame(&mut w, RESYNC, seq, reason.as_bytes())?;
+
+                return Ok(w.flush()?);
+            }
+            Err(error) => return Err(error),
+        };
+
+        if batches.is_empty() {
+            if quiet.elapsed() >= HEARTBEAT_INTERVAL {
+                write_frame(&mut w, HEARTBEAT, seq, &[])?;
+                w.flush()?;
+                quiet = Instant::now();
+            }
+
+            thread::sleep(POLL_INTERVAL);
+
+            continue;
+        }
+
+        for (seq, batch) in batches {
+            let mut payload = vec![];
+
+            batch.encode(&mut payload);
+            write_frame(&mut w, BATCH, seq, &payload)?;
+            position = seq;
+        }
+
+        w.flush()?;
+        quiet = Instant::now();
+    }
+
+    Ok(())
+}
+
+/**
+ * follower side of replication connection: asks primary for batches after seq of db and applies them in order,
+ * each is committed through log of db, so seq of db is position to resume from after restart or reconnect
+ * db is checkpointed on heartbeat after batches, so readers of file opened without lock see them
+ * returns when primary closes connection or stop returns true after frame, Error::ResyncNeeded when primary
+ * no longer has batches db needs, see bootstrap_follower
+ */
+pub fn follow_primary(db: &mut Db, mut r: impl Read, mut w: impl Write, stop: impl Fn() -> bool) -> Result<()> {
+    writeln!(w, "FOLLOW {}", db.seq())?;
+    w.flush()?;
+
+    let mut applied = false;
+
+    while let Some((kind, seq, payload)) = read_frame(&mut r)? {
+        match kind {
+            BATCH => {
+                let batch = WriteBatch::decode(&payload).ok_or_else(|| invalid(format!("batch {} can not be decoded", seq)))?;
+
+                db.replicate(seq, &batch)?;
+                applied = true;
+            }
+            HEARTBEAT if applied => {
+                db.checkpoint()?;
+                applied = false;
+            }
+            HEARTBEAT => {}
+            RESYNC => return Err(Error::ResyncNeeded(String::from_utf8_lossy(&payload).into_owned())),
+            kind => return Err(invalid(format!("unexpected frame of kind {}", kind))),
+        }
+
+        if stop() {
+            break;
+        }
+    }
+
+    if applied {
+        db.checkpoint()?;
+    }
+
+    Ok(())
+}
+
+/**
+ * copies database of primary into new file at path and returns it at seq of primary when copy was taken,
+ * follow_primary on new connection continues from there, fails if file exists, file is removed if copy fails
+ * database of follower may also start as copy of backup of primary, backup keeps seq of its state
+ */
+pub fn bootstrap_follower(
+    path: impl AsRef<Path>,
+    options: &SrdbOptions,
+    mut r: impl Read,
+    mut w: impl Write,
+) -> Result<Db> {
+    let path = path.as_ref();
+
+    w.write_all(b"SNAPSHOT\n")?;
+    w.flush()?;
+
+    let seq = match read_frame(&mut r)? {
+        Some((SNAPSHOT, seq, _)) => seq,
+        Some((kind, _, _)) => return Err(invalid(format!("expected snapshot, got frame of kind {}", kind))),
+        None => return Err(Error::Io(ErrorKind::UnexpectedEof.into())),
+    };
+
+    let (mut db, _) = Db::restore_with(r, path, None, options)?;
+
+    if let Err(error) = db.set_seq(seq) {
+        drop(db);
+        Db::remove(path)?;
+
+        return Err(error);
+    }
+
+    Ok(db)
+}
@@ -0,0 +1,218 @@
+use std::fmt::{self, Display};
+use std::panic::{self, AssertUnwindSafe};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::BTree;
+
+/**
+ * successful shrink steps made on failing script, at most
+ */
+const MAX_SHRINKS: usize = 1000;
+
+/**
+ * operation of self test, applied to both tree and model
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelfTestOp {
+    Insert(u32),
+    Delete(u32),
+    Contains(u32),
+}
+
+/**
+ * failure of BTree::self_test, op is index of failing operation in sequence generated from seed,
+ * repro is the shortest script found which still fails, it fails on its last operation
+ */
+#[derive(Clone, Debug)]
+pub struct SelfTestFailure {
+    pub t: usize,
+    pub seed: u64,
+    pub op: usize,
+    pub message: String,
+    pub repro: Vec<SelfTestOp>,
+}
+
+impl Display for SelfTestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "self test with t {} and seed {} failed at op {}: {}",
+            self.t, self.seed, self.op, self.message
+        )?;
+        write!(f, "repro of {} ops:", self.repro.len())?;
+
+        for op in &self.repro {
+            write!(f, " {:?}", op)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for SelfTestFailure {}
+
+impl BTree<u32> {
+    /**
+     * runs n_ops random inserts, deletes and lookups generated from seed against tree with branching factor t
+     * and sorted Vec holding the same keys, invariants are checked after every insert and delete,
+     * lookups and deletes must agree with Vec and panics of tree count as failures,
+     * e.g. to check tree from health check after upgrade, a failure is the same for the same arguments
+     */
+    pub fn self_test(t: usize, seed: u64, n_ops: usize) -> Result<(), SelfTestFailure> {
+        let ops = self_test_ops(seed, n_ops);
+
+        let Err((op, message)) = run(t, &ops) else {
+            return Ok(());
+        };
+
+        let (repro, message) = minimize(t, ops[..=op].to_vec(), message);
+
+        Err(SelfTestFailure {
+            t,
+            seed,
+            op,
+            message,
+            repro,
+        })
+    }
+}
+
+/**
+ * keys are below n_ops / 2, so lookups and deletes often hit, half of deletes take key present in model
+ */
+fn self_test_ops(seed: u64, n_ops: usize) -> Vec<SelfTestOp> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let keys = (n_ops as u32 / 2).max(16);
+    let mut model: Vec<u32> = Vec::new();
+    let mut ops = Vec::with_capacity(n_ops);
+
+    for _ in 0..n_ops {
+        let op = match rng.gen_range(0..5) {
+            0 | 1 => SelfTestOp::Insert(rng.gen_range(0..keys)),
+            2 | 3 if !model.is_empty() && rng.gen_bool(0.5) => SelfTestOp::Delete(model[rng.gen_range(0..model.len())]),
+            2 | 3 => SelfTestOp::Delete(rng.gen_range(0..keys)),
+            _ => SelfTestOp::Contains(rng.gen_range(0..keys)),
+        };
+
+        apply(&mut model, op);
+        ops.push(op);
+    }
+
+    ops
+}
+
+/**
+ * applies op to sorted Vec keeping duplicates as tree does, returns whether key was found
+ */
+fn apply(model: &mut Vec<u32>, op: SelfTestOp) -> bool {
+    match op {
+        SelfTestOp::Insert(key) => {
+            let i = model.partition_point(|x| *x <= key);
+
+            model.insert(i, key);
+
+            true
+        }
+        SelfTestOp::Delete(key) => match model.binary_search(&key) {
+            Ok(i) => {
+                model.remove(i);
+
+                true
+            }
+            Err(_) => false,
+        },
+        SelfTestOp::Contains(key) => model.binary_search(&key).is_ok(),
+    }
+}
+
+/**
+ * index of first failing op and reason
+ */
+fn run(t: usize, ops: &[SelfTestOp]) -> Result<(), (usize, String)> {
+    let mut tree = BTree::new(t);
+    let mut model = Vec::new();
+
+    for (i, op) in ops.iter().enumerate() {
+        let expected = apply(&mut model, *op);
+
+        let step = panic::catch_unwind(AssertUnwindSafe(|| match *op {
+            SelfTestOp::Insert(key) => {
+                tree.insert(key);
+
+                tree.check_invariants()
+            }
+            SelfTestOp::Delete(key) => match tree.delete(&key) {
+                found if found != expected => Err(format!("delete returned {}, expected {}", found, expected)),
+                _ => tree.check_invariants(),
+            },
+            SelfTestOp::Contains(key) => match tree.contains(key) {
+                found if found != expected => Err(format!("contains returned {}, expected {}", found, expected)),
+                _ => Ok(()),
+            },
+        }));
+
+        let result = match step {
+            Ok(result) => result,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+
+                return Err((i, format!("panicked: {}", message)));
+            }
+        };
+
+        result.map_err(|message| (i, message))?;
+
+        if tree.len() != model.len() {
+            return Err((i, format!("len is {}, expected {}", tree.len(), model.len())));
+        }
+    }
+
+    let keys = tree.to_vec();
+
+    if keys != model {
+        return Err((ops.len().saturating_sub(1), format!("keys {:?}, expected {:?}", keys, model)));
+    }
+
+    Ok(())
+}
+
+/**
+ * removes chunks of ops, halves first and single ops last, while script still fails,
+ * kept script is cut right after its failing op
+ */
+fn minimize(t: usize, mut ops: Vec<SelfTestOp>, mut message: String) -> (Vec<SelfTestOp>, String) {
+    let mut shrinks = 0;
+    let mut chunk = ops.len() / 2;
+
+    while chunk > 0 && shrinks < MAX_SHRINKS {
+        let mut start = 0;
+        let mut shrunk = false;
+
+        while start < ops.len() && shrinks < MAX_SHRINKS {
+            let end = (start + chunk).min(ops.len());
+            let candidate: Vec<SelfTestOp> = ops[..start].iter().chain(&ops[end..]).copied().collect();
+
+            match run(t, &candidate) {
+                Err((op, reason)) => {
+                    ops = candidate[..=op].to_vec();
+                    message = reason;
+                    shrinks += 1;
+                    shrunk = true;
+                }
+                Ok(()) => start = end,
+            }
+        }
+
+        if !shrunk {
+            chunk /= 2;
+        }
+    }
+
+    (ops, message)
+}
@@ -0,0 +1,132 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use ::serde::de::Error;
+use ::serde::ser::{SerializeSeq, SerializeStruct};
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{BTree, Comparator, NodeLayout, MAX_T};
+#[cfg(feature = "std")]
+use crate::{MapEntry, SharedMap};
+
+/**
+ * tree is written as its logical contents { t, keys }, keys in sorted order,
+ * so the format does not depend on shape of nodes
+ */
+impl<T: Serialize + Debug, C: Comparator<T>, L: NodeLayout> Serialize for BTree<T, C, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("BTree", 2)?;
+
+        state.serialize_field("t", &self.t)?;
+        state.serialize_field("keys", &Keys(self))?;
+        state.end()
+    }
+}
+
+struct Keys<'a, T: Debug, C: Comparator<T>, L: NodeLayout>(&'a BTree<T, C, L>);
+
+impl<T: Serialize + Debug, C: Comparator<T>, L: NodeLayout> Serialize for Keys<'_, T, C, L> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        self.0.iter().try_for_each(|key| seq.serialize_element(key))?;
+        seq.end()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "BTree")]
+struct Contents<T> {
+    t: u64,
+    keys: Vec<T>,
+}
+
+/**
+ * bulk loads tree from { t, keys }, t and key order are validated
+ */
+impl<'de, T: Deserialize<'de> + Ord + Debug> Deserialize<'de> for BTree<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Contents { t, keys } = Contents::deserialize(deserializer)?;
+
+        check_t(t)?;
+
+        if let Some(i) = keys.windows(2).position(|pair| pair[0] > pair[1]) {
+            return Err(D::Error::custom(format!("key {} is out of order", i + 1)));
+        }
+
+        Ok(BTree::from_sorted(t as usize, keys, 1.0))
+    }
+}
+
+fn check_t<E: Error>(t: u64) -> Result<(), E> {
+    if !(2..=MAX_T as u64).contains(&t) {
+        return Err(E::custom(format!("branching factor {} is out of range", t)));
+    }
+
+    Ok(())
+}
+
+/**
+ * map is written as { t, entries }, entries are (key, value) pairs in key order, taken under one read lock
+ */
+#[cfg(feature = "std")]
+impl<K: Ord + Clone + Debug + Serialize, V: Clone + Debug + Serialize> Serialize for SharedMap<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.read_with(|tree| {
+            let mut state = serializer.serialize_struct("SharedMap", 2)?;
+
+            state.serialize_field("t", &tree.t())?;
+            state.serialize_field("entries", &Entries(tree))?;
+            state.end()
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+struct Entries<'a, K: Ord + Clone + Debug, V: Clone + Debug>(&'a BTree<MapEntry<K, V>>);
+
+#[cfg(feature = "std")]
+impl<K: Ord + Clone + Debug + Serialize, V: Clone + Debug + Serialize> Serialize for Entries<'_, K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        self.0.iter().try_for_each(|entry| seq.serialize_element(&(&entry.key, &entry.value)))?;
+        seq.end()
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+#[serde(rename = "SharedMap")]
+struct MapContents<K, V> {
+    t: u64,
+    entries: Vec<(K, V)>,
+}
+
+/**
+ * bulk loads map from { t, entries }, keys must be unique and in order
+ */
+#[cfg(feature = "std")]
+impl<'de, K, V> Deserialize<'de> for SharedMap<K, V>
+where
+    K: Ord + Clone + Debug + Deserialize<'de>,
+    V: Clone + Debug + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let MapContents { t, entries } = MapContents::deserialize(deserializer)?;
+
+        check_t(t)?;
+
+        if let Some(i) = entries.windows(2).position(|pair| pair[0].0 >= pair[1].0) {
+            return Err(D::Error::custom(format!("key {} is out of order or repeated", i + 1)));
+        }
+
+        let entries = entries.into_iter().map(|(key, value)| MapEntry { key, value }).collect();
+        let map = SharedMap::new(t as usize);
+
+        map.write_with(|tree| *tree = BTree::from_sorted(t as usize, entries, 1.0));
+
+        Ok(map)
+    }
+}
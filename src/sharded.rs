@@ -0,0 +1,220 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::iter::Peekable;
+use std::sync::Arc;
+
+use crate::{SharedBTree, SnapshotIter, Stats};
+
+/**
+ * how key finds its shard: by count of bounds not after it or by hash
+ */
+enum Router<T> {
+    Range(Vec<T>),
+    Hash(fn(&T) -> u64),
+}
+
+fn hash_key<T: Hash>(key: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct Inner<T: Ord + Clone + Debug> {
+    shards: Vec<SharedBTree<T>>,
+    router: Router<T>,
+}
+
+/**
+ * keys partitioned across independent locked trees behind one handle, handle is cloned into every thread
+ * every key lives in exactly one shard, so writes to different shards never wait for each other
+ * shards are SharedBTree, see it for locking, operations of several shards, len, iter and to_vec, see each shard
+ * at its own moment, not all at one moment
+ */
+pub struct ShardedBTree<T: Ord + Clone + Debug> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T: Ord + Clone + Debug> Clone for ShardedBTree<T> {
+    fn clone(&self) -> Self {
+        ShardedBTree {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: Ord + Clone + Debug> Debug for ShardedBTree<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord + Clone + Debug + Hash> ShardedBTree<T> {
+    /**
+     * shards keys by hash, at least one shard, equal keys must have equal hashes
+     */
+    pub fn new(shards: usize, t: usize) -> ShardedBTree<T> {
+        ShardedBTree::with_router(shards.max(1), t, Router::Hash(hash_key::<T>))
+    }
+}
+
+impl<T: Ord + Clone + Debug> ShardedBTree<T> {
+    /**
+     * shards keys by range: shard i holds keys from bounds[i - 1] inclusive up to bounds[i], so bounds.len() + 1
+     * shards, bounds must be strictly increasing
+     * keeps neighbour keys together, but writes of sequential keys all go to one shard
+     */
+    pub fn with_bounds(t: usize, bounds: Vec<T>) -> ShardedBTree<T> {
+        assert!(
+            bounds.windows(2).all(|pair| pair[0] < pair[1]),
+            "shard bounds must be strictly increasing"
+        );
+
+        ShardedBTree::with_router(bounds.len() + 1, t, Router::Range(bounds))
+    }
+
+    fn with_router(shards: usize, t: usize, router: Router<T>) -> ShardedBTree<T> {
+        ShardedBTree {
+            inner: Arc::new(Inner {
+                shards: (0..shards).map(|_| SharedBTree::new(t)).collect(),
+                router,
+            }),
+        }
+    }
+
+    fn shard_of(&self, value: &T) -> usize {
+        match &self.inner.router {
+            Router::Range(bounds) => bounds.partition_point(|bound| bound <= value),
+            Router::Hash(hash) => (hash(value) % self.inner.shards.len() as u64) as usize,
+        }
+    }
+
+    fn shard(&self, value: &T) -> &SharedBTree<T> {
+        &self.inner.shards[self.shard_of(value)]
+    }
+
+    pub fn shards(&self) -> usize {
+        self.inner.shards.len()
+    }
+
+    pub fn t(&self) -> usize {
+        self.inner.shards[0].t()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.shards.iter().map(SharedBTree::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.shards.iter().all(SharedBTree::is_empty)
+    }
+
+    /**
+     * number of keys in every shard, shows how even partition is
+     */
+    pub fn shard_lens(&self) -> Vec<usize> {
+        self.inner.shards.iter().map(SharedBTree::len).collect()
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.shard(&value).contains(value)
+    }
+
+    pub fn insert(&self, value: T) {
+        self.shard(&value).insert(value)
+    }
+
+    pub fn delete(&self, value: &T) -> bool {
+        self.shard(value).delete(value)
+    }
+
+    /**
+     * copies of keys in order, merged from snapshot_iter of every shard, so holds no lock
+     */
+    pub fn iter(&self) -> ShardedIter<T> {
+        ShardedIter {
+            heads: self.inner.shards.iter().map(|shard| shard.snapshot_iter().peekable()).collect(),
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+
+    /**
+     * shapes of shards summed up, height is of the highest shard
+     */
+    pub fn stats(&self) -> Stats {
+        let mut total = Stats {
+            len: 0,
+            height: 0,
+            nodes: 0,
+            leaves: 0,
+            fill: 0.0,
+        };
+
+        for shard in &self.inner.shards {
+            let stats = shard.stats();
+
+            total.len += stats.len;
+            total.height = total.height.max(stats.height);
+            total.nodes += stats.nodes;
+            total.leaves += stats.leaves;
+        }
+
+        total.fill = total.len as f64 / (total.nodes * (2 * self.t() - 1)) as f64;
+
+        total
+    }
+
+    /**
+     * invariants of every shard, and every key is in shard it is routed to
+     */
+    pub fn check_invariants(&self) -> Result<(), String> {
+        for (i, shard) in self.inner.shards.iter().enumerate() {
+            shard.check_invariants().map_err(|error| format!("shard {}: {}", i, error))?;
+
+            let mut stray = None;
+
+            shard.for_each(|key| {
+                if stray.is_none() && self.shard_of(key) != i {
+                    stray = Some(format!("key {:?} is in shard {} but belongs to shard {}", key, i, self.shard_of(key)));
+                }
+            });
+
+            if let Some(stray) = stray {
+                return Err(stray);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * k-way merge of shards, see ShardedBTree::iter, equal keys of one shard come out together
+ */
+pub struct ShardedIter<T: Ord + Clone + Debug> {
+    heads: Vec<Peekable<SnapshotIter<T>>>,
+}
+
+impl<T: Ord + Clone + Debug> Iterator for ShardedIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let mut least: Option<(usize, &T)> = None;
+
+        for (i, head) in self.heads.iter_mut().enumerate() {
+            if let Some(key) = head.peek() {
+                if least.is_none_or(|(_, least)| key < least) {
+                    least = Some((i, key));
+                }
+            }
+        }
+
+        let (i, _) = least?;
+
+        self.heads[i].next()
+    }
+}
@@ -0,0 +1,325 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{BTree, CompactReport, MemoryUsage, NodeId, Stats};
+
+/**
+ * handle to BTree shared between threads, clones refer to the same tree
+ * reads take read lock for their own duration, so they go on in parallel, writes take write lock
+ * nothing returned borrows tree: iteration is to_vec copy, for_each closure or snapshot_iter,
+ * read_with and write_with keep one lock over several calls
+ * panic of thread holding lock does not stop others from using tree
+ */
+pub struct SharedBTree<T: Ord + Clone + Debug> {
+    tree: Arc<RwLock<Arc<BTree<T>>>>,
+}
+
+impl<T: Ord + Clone + Debug> Clone for SharedBTree<T> {
+    fn clone(&self) -> Self {
+        SharedBTree { tree: self.tree.clone() }
+    }
+}
+
+impl<T: Ord + Clone + Debug> Debug for SharedBTree<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.read().fmt(f)
+    }
+}
+
+impl<T: Ord + Clone + Debug> From<BTree<T>> for SharedBTree<T> {
+    fn from(tree: BTree<T>) -> Self {
+        SharedBTree {
+            tree: Arc::new(RwLock::new(Arc::new(tree))),
+        }
+    }
+}
+
+impl<T: Ord + Clone + Debug> SharedBTree<T> {
+    pub fn new(t: usize) -> SharedBTree<T> {
+        BTree::new(t).into()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Arc<BTree<T>>> {
+        self.tree.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /**
+     * tree for change, copied first if snapshot iterator still walks it
+     */
+    fn write(&self) -> impl DerefMut<Target = BTree<T>> + '_ {
+        TreeWriter(self.tree.write().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /**
+     * runs f with tree under read lock, writes of others wait until it returns
+     * f must not write through any handle of this tree, that deadlocks
+     */
+    pub fn read_with<R>(&self, f: impl FnOnce(&BTree<T>) -> R) -> R {
+        f(&self.read())
+    }
+
+    /**
+     * runs f with tree under write lock, others see all its changes at once
+     * f must not use any handle of this tree, that deadlocks
+     */
+    pub fn write_with<R>(&self, f: impl FnOnce(&mut BTree<T>) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    pub fn t(&self) -> usize {
+        self.read().t()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.read().contains(value)
+    }
+
+    /**
+     * copy of stored key, see BTree::find
+     */
+    pub fn find(&self, cmp: impl Fn(&T) -> Ordering) -> Option<T> {
+        self.read().find(cmp).cloned()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.read().stats()
+    }
+
+    pub fn levels(&self) -> Vec<usize> {
+        self.read().levels()
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.read().memory_usage()
+    }
+
+    pub fn check_invariants(&self) -> Result<(), String> {
+        self.read().check_invariants()
+    }
+
+    /**
+     * keys in order as of one moment
+     */
+    pub fn to_vec(&self) -> Vec<T> {
+        self.read().to_vec()
+    }
+
+    /**
+     * calls f with keys in order under read lock, see read_with
+     */
+    pub fn for_each(&self, f: impl FnMut(&T)) {
+        self.read().iter().for_each(f)
+    }
+
+    /**
+     * iterator over copies of keys as of now, which holds no lock, so writers go on while it walks
+     * taking it is O(1): it shares tree with handle until the next write, which copies tree for itself once
+     */
+    pub fn snapshot_iter(&self) -> SnapshotIter<T> {
+        let tree = self.read().clone();
+        let mut stack = vec![];
+
+        tree.descend(tree.root, &mut stack);
+
+        SnapshotIter { tree, stack }
+    }
+
+    pub fn insert(&self, value: T) {
+        self.write().insert(value)
+    }
+
+    /**
+     * see BTree::insert_batch, readers see either none or all of chunk
+     */
+    pub fn insert_batch(&self, chunk: Vec<T>) {
+        self.write().insert_batch(chunk)
+    }
+
+    pub fn delete(&self, value: &T) -> bool {
+        self.write().delete(value)
+    }
+
+    pub fn compact(&self) -> CompactReport {
+        self.write().compact()
+    }
+}
+
+struct TreeWriter<'a, T: Ord + Clone + Debug>(RwLockWriteGuard<'a, Arc<BTree<T>>>);
+
+impl<T: Ord + Clone + Debug> Deref for TreeWriter<'_, T> {
+    type Target = BTree<T>;
+
+    fn deref(&self) -> &BTree<T> {
+        &self.0
+    }
+}
+
+impl<T: Ord + Clone + Debug> DerefMut for TreeWriter<'_, T> {
+    fn deref_mut(&mut self) -> &mut BTree<T> {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+/**
+ * keys of tree as of SharedBTree::snapshot_iter in order, tree stays alive until iterator is dropped
+ */
+pub struct SnapshotIter<T: Ord + Clone + Debug> {
+    tree: Arc<BTree<T>>,
+    stack: Vec<(NodeId, usize)>,
+}
+
+impl<T: Ord + Clone + Debug> Iterator for SnapshotIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.tree.step(&mut self.stack).cloned()
+    }
+}
+
+/**
+ * entry of SharedMap, entries are compared by key only
+ */
+#[derive(Clone, Debug)]
+pub struct MapEntry<K: Ord + Clone + Debug, V: Clone + Debug> {
+    pub key: K,
+    pub value: V,
+}
+
+impl<K: Ord + Clone + Debug, V: Clone + Debug> PartialEq for MapEntry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Ord + Clone + Debug, V: Clone + Debug> Eq for MapEntry<K, V> {}
+
+impl<K: Ord + Clone + Debug, V: Clone + Debug> PartialOrd for MapEntry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord + Clone + Debug, V: Clone + Debug> Ord for MapEntry<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/**
+ * map over SharedBTree of entries with unique keys, see SharedBTree for locking
+ * values are returned as copies, read_with and write_with give tree of entries itself
+ */
+pub struct SharedMap<K: Ord + Clone + Debug, V: Clone + Debug> {
+    tree: SharedBTree<MapEntry<K, V>>,
+}
+
+impl<K: Ord + Clone + Debug, V: Clone + Debug> Clone for SharedMap<K, V> {
+    fn clone(&self) -> Self {
+        SharedMap { tree: self.tree.clone() }
+    }
+}
+
+impl<K: Ord + Clone + Debug, V: Clone + Debug> Debug for SharedMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.to_vec()).finish()
+    }
+}
+
+/**
+ * entry of tree with key
+ */
+fn entry<'a, K: Ord + Clone + Debug, V: Clone + Debug>(
+    tree: &'a BTree<MapEntry<K, V>>,
+    key: &K,
+) -> Option<&'a MapEntry<K, V>> {
+    tree.find(|entry| entry.key.cmp(key))
+}
+
+impl<K: Ord + Clone + Debug, V: Clone + Debug> SharedMap<K, V> {
+    pub fn new(t: usize) -> SharedMap<K, V> {
+        SharedMap {
+            tree: SharedBTree::new(t),
+        }
+    }
+
+    /**
+     * see SharedBTree::read_with
+     */
+    pub fn read_with<R>(&self, f: impl FnOnce(&BTree<MapEntry<K, V>>) -> R) -> R {
+        self.tree.read_with(f)
+    }
+
+    /**
+     * see SharedBTree::write_with, f must keep keys unique
+     */
+    pub fn write_with<R>(&self, f: impl FnOnce(&mut BTree<MapEntry<K, V>>) -> R) -> R {
+        self.tree.write_with(f)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.read_with(|tree| entry(tree, key).map(|entry| entry.value.clone()))
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.read_with(|tree| entry(tree, key).is_some())
+    }
+
+    /**
+     * entries in order as of one moment
+     */
+    pub fn to_vec(&self) -> Vec<(K, V)> {
+        self.read_with(|tree| tree.iter().map(|entry| (entry.key.clone(), entry.value.clone())).collect())
+    }
+
+    /**
+     * calls f with entries in order under read lock, see SharedBTree::read_with
+     */
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        self.read_with(|tree| tree.iter().for_each(|entry| f(&entry.key, &entry.value)))
+    }
+
+    /**
+     * returns value key had before, readers see either old or new value
+     */
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.write_with(|tree| {
+            let old = entry(tree, &key).cloned();
+
+            if let Some(old) = &old {
+                tree.delete(old);
+            }
+
+            tree.insert(MapEntry { key, value });
+
+            old.map(|old| old.value)
+        })
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.write_with(|tree| {
+            let old = entry(tree, key).cloned()?;
+
+            tree.delete(&old);
+
+            Some(old.value)
+        })
+    }
+}
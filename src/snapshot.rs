@@ -0,0 +1,204 @@
+use std::fmt::{Debug, Display};
+use std::io::{self, Read, Write};
+
+use crate::codec::Codec;
+use crate::crc32::Crc32;
+use crate::{BTree, MAX_T};
+
+/**
+ * snapshot layout, all integers are little-endian:
+ * magic (8 bytes), version (u32), t (u64), key count (u64),
+ * keys in sorted order as (length u32, encoded bytes),
+ * crc32 of everything before it (u32)
+ */
+const MAGIC: &[u8; 8] = b"SRDBSNAP";
+const VERSION: u32 = 1;
+
+/**
+ * keys are read in chunks of this size, so broken length does not allocate gigabytes upfront
+ */
+const READ_CHUNK: u64 = 64 * 1024;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    WrongMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    Corrupt(String),
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "snapshot i/o error: {}", error),
+            SnapshotError::WrongMagic => write!(f, "not a srdb snapshot: wrong magic bytes"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}, expected {}", version, VERSION)
+            }
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::Corrupt(reason) => write!(f, "snapshot is corrupt: {}", reason),
+            SnapshotError::ChecksumMismatch { expected, actual } => {
+                write!(f, "snapshot checksum mismatch: stored {:08x}, computed {:08x}", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => SnapshotError::Truncated,
+            _ => SnapshotError::Io(error),
+        }
+    }
+}
+
+/**
+ * passes bytes through, keeping checksum of them
+ */
+pub(crate) struct Checksummed<S> {
+    pub(crate) inner: S,
+    pub(crate) crc: Crc32,
+}
+
+impl<S> Checksummed<S> {
+    pub(crate) fn new(inner: S) -> Checksummed<S> {
+        Checksummed { inner, crc: Crc32::new() }
+    }
+}
+
+impl<W: Write> Checksummed<W> {
+    pub(crate) fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.crc.update(bytes);
+        self.inner.write_all(bytes)
+    }
+}
+
+impl<R: Read> Checksummed<R> {
+    pub(crate) fn read_exact<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+
+        self.inner.read_exact(&mut buf)?;
+        self.crc.update(&buf);
+
+        Ok(buf)
+    }
+
+    pub(crate) fn read_u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.read_exact()?))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.read_exact()?))
+    }
+
+    /**
+     * reads len bytes into buf, growing it only as data actually arrives
+     */
+    pub(crate) fn read_bytes(&mut self, len: u64, buf: &mut Vec<u8>) -> io::Result<()> {
+        buf.clear();
+
+        let mut rest = len;
+
+        while rest > 0 {
+            let chunk = rest.min(READ_CHUNK);
+            let start = buf.len();
+
+            buf.resize(start + chunk as usize, 0);
+            self.inner.read_exact(&mut buf[start..])?;
+            rest -= chunk;
+        }
+
+        self.crc.update(buf);
+
+        Ok(())
+    }
+}
+
+impl<T: Ord + Debug + Codec> BTree<T> {
+    /**
+     * writes keys to w in snapshot format, keys are streamed one by one
+     */
+    pub fn save_to(&self, w: impl Write) -> io::Result<()> {
+        let mut out = Checksummed::new(w);
+
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(self.t as u64).to_le_bytes())?;
+        out.write_all(&(self.len as u64).to_le_bytes())?;
+
+        let mut buf = vec![];
+
+        for key in self.iter() {
+            buf.clear();
+            key.encode(&mut buf);
+
+            out.write_all(&(buf.len() as u32).to_le_bytes())?;
+            out.write_all(&buf)?;
+        }
+
+        let checksum = out.crc.finish();
+
+        out.inner.write_all(&checksum.to_le_bytes())?;
+        out.inner.flush()
+    }
+
+    /**
+     * reads snapshot written by save_to and bulk loads tree from it
+     * header, key order and checksum are validated, any mismatch is an error
+     */
+    pub fn load_from(r: impl Read) -> Result<BTree<T>, SnapshotError> {
+        let mut input = Checksummed::new(r);
+
+        if &input.read_exact::<8>()? != MAGIC {
+            return Err(SnapshotError::WrongMagic);
+        }
+
+        let version = input.read_u32()?;
+
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let t = input.read_u64()?;
+
+        if !(2..=MAX_T as u64).contains(&t) {
+            return Err(SnapshotError::Corrupt(format!("branching factor {} is out of range", t)));
+        }
+
+        let count = input.read_u64()?;
+        let mut keys: Vec<T> = Vec::with_capacity(count.min(READ_CHUNK) as usize);
+        let mut buf = vec![];
+
+        for i in 0..count {
+            let len = input.read_u32()?;
+
+            input.read_bytes(len as u64, &mut buf)?;
+
+            let key = T::decode(&buf).ok_or_else(|| SnapshotError::Corrupt(format!("key {} can not be decoded", i)))?;
+
+            if keys.last().is_some_and(|last| *last > key) {
+                return Err(SnapshotError::Corrupt(format!("key {} is out of order", i)));
+            }
+
+            keys.push(key);
+        }
+
+        let actual = input.crc.finish();
+        let mut stored = [0u8; 4];
+
+        input.inner.read_exact(&mut stored)?;
+
+        let expected = u32::from_le_bytes(stored);
+
+        if expected != actual {
+            return Err(SnapshotError::ChecksumMismatch { expected, actual });
+        }
+
+        Ok(BTree::from_sorted(t as usize, keys, 1.0))
+    }
+}
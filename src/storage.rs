@@ -0,0 +1,67 @@
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::mmap::Mmap;
+
+/**
+ * byte addressed file used by Pager and write ahead log
+ * lets tests run engine over memory and inject faults
+ * storage is Send, so database can be flushed from background thread
+ */
+pub trait Storage: Debug + Send {
+    fn len(&self) -> io::Result<u64>;
+
+    fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+
+    /**
+     * fills buf completely, fails with UnexpectedEof past the end
+     */
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()>;
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()>;
+
+    /**
+     * makes everything written so far durable
+     */
+    fn sync(&mut self) -> io::Result<()>;
+
+    /**
+     * maps whole storage for reading, storages which can not be mapped fail with ErrorKind::Unsupported
+     */
+    fn map(&self) -> io::Result<Mmap> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl Storage for File {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.read_exact(buf)
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.write_all(buf)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.sync_data()
+    }
+
+    fn map(&self) -> io::Result<Mmap> {
+        Mmap::map(self, Storage::len(self)? as usize)
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{BTree, MemStorage, SrdbOptions};
+
+/**
+ * operations of one script, at most
+ */
+pub(crate) const SCRIPT_OPS: usize = 200;
+
+/**
+ * keys of store scripts are below it, so deletes often hit inserted keys
+ */
+pub(crate) const SCRIPT_KEYS: u16 = 64;
+
+/**
+ * operation of StoreScript
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptOp {
+    Insert(u16),
+    Delete(u16),
+}
+
+/**
+ * operations run against in-memory BTree and on-disk Db, both with branching factor t
+ * scripts come from seeded or, with arbitrary feature, from proptest strategy of check_stores
+ */
+#[derive(Clone, Debug)]
+pub struct StoreScript {
+    pub t: usize,
+    pub ops: Vec<ScriptOp>,
+}
+
+impl StoreScript {
+    /**
+     * runs script against both stores and a set, after every operation both trees must hold invariants
+     * and keys of the set, inserts of present keys are skipped for BTree as Db keeps one entry per key
+     */
+    pub fn run(&self) -> Result<(), String> {
+        let mut tree = BTree::new(self.t);
+        let mut db = SrdbOptions::new()
+            .branching_factor(self.t)
+            .create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new()))
+            .map_err(|error| format!("database can not be created: {}", error))?;
+        let mut model = BTreeSet::new();
+
+        for (i, op) in self.ops.iter().enumerate() {
+            let failed = |store: &str, error: &dyn Display| format!("{} after op {} {:?}: {}", store, i, op, error);
+
+            match *op {
+                ScriptOp::Insert(key) => {
+                    if model.insert(key) {
+                        tree.insert(key);
+                    }
+
+                    db.insert(&key.to_be_bytes(), &[]).map_err(|error| failed("disk", &error))?;
+                }
+                ScriptOp::Delete(key) => {
+                    let expected = model.remove(&key);
+
+                    if tree.delete(&key) != expected {
+                        return Err(failed("memory", &format!("delete did not return {}", expected)));
+                    }
+
+                    if db.delete(&key.to_be_bytes()).map_err(|error| failed("disk", &error))? != expected {
+                        return Err(failed("disk", &format!("delete did not return {}", expected)));
+                    }
+                }
+            }
+
+            tree.check_invariants().map_err(|error| failed("memory", &error))?;
+            db.check_invariants().map_err(|error| failed("disk", &error))?;
+
+            let keys: Vec<u16> = model.iter().copied().collect();
+
+            if tree.to_vec() != keys {
+                return Err(failed("memory", &format!("keys {:?}, expected {:?}", tree.to_vec(), keys)));
+            }
+
+            let disk_keys: Vec<u16> = db
+                .to_vec()
+                .map_err(|error| failed("disk", &error))?
+                .iter()
+                .map(|(key, _)| u16::from_be_bytes([key[0], key[1]]))
+                .collect();
+
+            if disk_keys != keys {
+                return Err(failed("disk", &format!("keys {:?}, expected {:?}", disk_keys, keys)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * script generated from seed alone, t in 2..=8, about every third operation is delete,
+     * so failure is reproduced by its seed alone
+     */
+    pub fn seeded(seed: u64) -> StoreScript {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let ops = (0..rng.gen_range(0..SCRIPT_OPS))
+            .map(|_| {
+                let key = rng.gen_range(0..SCRIPT_KEYS);
+
+                if rng.gen_ratio(1, 3) {
+                    ScriptOp::Delete(key)
+                } else {
+                    ScriptOp::Insert(key)
+                }
+            })
+            .collect();
+
+        StoreScript {
+            t: rng.gen_range(2..=8),
+            ops,
+        }
+    }
+}
@@ -0,0 +1,524 @@
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::batch::WriteBatch;
+use crate::codec::{take, Codec};
+use crate::crc32::checksum;
+use crate::db::sync_parent;
+use crate::encryption::{Cipher, SEAL_SIZE};
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+
+/**
+ * record layout, integers are little-endian:
+ * body length (u32), crc32 of body (u32),
+ * body as kind (u8), sequence number (u64) and payload
+ *
+ * BATCH payload is encoded WriteBatch
+ * log of encrypted database seals payload with kind and sequence number as aad, see Cipher
+ */
+const RECORD_HEADER_SIZE: usize = 8;
+const BODY_HEADER_SIZE: usize = 9;
+
+const BATCH: u8 = 1;
+
+/**
+ * number of the first segment of new log
+ */
+pub const FIRST_SEGMENT: u64 = 1;
+
+/**
+ * segments of write ahead log live next to database file, with "-wal-" and their number appended to its name
+ */
+pub fn segment_path(path: &Path, number: u64) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+
+    name.push(format!("-wal-{:08}", number));
+
+    PathBuf::from(name)
+}
+
+/**
+ * numbers of segments of database at path found on disk, in ascending order
+ */
+fn segments(path: &Path) -> Result<Vec<u64>> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut prefix = path.file_name().map(OsString::from).unwrap_or_default();
+
+    prefix.push("-wal-");
+
+    let prefix = prefix.to_string_lossy().into_owned();
+    let mut numbers = vec![];
+
+    for entry in fs::read_dir(parent)? {
+        let name = entry?.file_name();
+        let number = name.to_str().and_then(|name| name.strip_prefix(&prefix)).and_then(|number| number.parse::<u64>().ok());
+
+        numbers.extend(number);
+    }
+
+    numbers.sort_unstable();
+
+    Ok(numbers)
+}
+
+fn open_segment(path: &Path, number: u64, truncate: bool) -> Result<Box<dyn Storage>> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(truncate)
+        .open(segment_path(path, number))?;
+
+    Ok(Box::new(file))
+}
+
+fn remove_segment(path: &Path, number: u64) -> Result<()> {
+    match fs::remove_file(segment_path(path, number)) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug)]
+pub enum Record {
+    /**
+     * batch with sequence number, logged before any page is modified
+     */
+    Batch { seq: u64, batch: WriteBatch },
+}
+
+/**
+ * append only log of batches, every batch reaches it before any page is modified
+ * log is split into numbered segments, the active one rolls over to the next once it grows past segment size,
+ * sequence numbers continue across segments
+ * checkpoint starts new segment with rotate, records header naming it as the oldest one needed,
+ * then removes older ones with remove_old
+ */
+#[derive(Debug)]
+pub struct Wal {
+    /**
+     * database file segments are named after, None for log over single storage, it never rolls over
+     * and rotate empties it instead
+     */
+    path: Option<PathBuf>,
+    /**
+     * active segment
+     */
+    file: Box<dyn Storage>,
+    /**
+     * oldest segment on disk and the active one
+     */
+    first: u64,
+    number: u64,
+    /**
+     * bytes in all segments and in the active one
+     */
+    len: u64,
+    active_len: u64,
+    segment_size: Option<u64>,
+    cipher: Option<Cipher>,
+}
+
+fn take_u32(bytes: &mut &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+}
+
+impl Wal {
+    fn new(path: Option<PathBuf>, file: Box<dyn Storage>, first: u64, cipher: Option<&Cipher>) -> Wal {
+        Wal {
+            path,
+            file,
+            first,
+            number: first,
+            len: 0,
+            active_len: 0,
+            segment_size: None,
+            cipher: cipher.cloned(),
+        }
+    }
+
+    /**
+     * creates empty log of database at path starting with segment first, existing segments are removed
+     * cipher is the one of encrypted database file
+     */
+    pub fn create(path: &Path, first: u64, cipher: Option<&Cipher>) -> Result<Wal> {
+        Wal::remove(path)?;
+
+        let file = open_segment(path, first, true)?;
+
+        sync_parent(path)?;
+
+        Ok(Wal::new(Some(path.to_path_buf()), file, first, cipher))
+    }
+
+    /**
+     * removes all segments of log of database at path
+     */
+    pub fn remove(path: &Path) -> Result<()> {
+        for number in segments(path)? {
+            remove_segment(path, number)?;
+        }
+
+        sync_parent(path)
+    }
+
+    /**
+     * bytes in segments of log of database at path, segment which can not be read counts as empty
+     */
+    pub fn disk_len(path: &Path) -> u64 {
+        segments(path)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|number| fs::metadata(segment_path(path, number)).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /**
+     * same as create over given storage, its contents are discarded
+     */
+    pub fn create_with_storage(mut file: Box<dyn Storage>, cipher: Option<&Cipher>) -> Result<Wal> {
+        file.set_len(0)?;
+
+        Ok(Wal::new(None, file, FIRST_SEGMENT, cipher))
+    }
+
+    /**
+     * opens log of database at path from segment first recorded in header and reads all complete records
+     * older segments are removed, missing segment in between is Error::Corrupt
+     * records are read up to the first torn or corrupt one or gap in sequence numbers,
+     * the rest of its segment is cut off and later segments are removed
+     */
+    pub fn open(path: &Path, first: u64, cipher: Option<&Cipher>) -> Result<(Wal, Vec<Record>)> {
+        let mut numbers = segments(path)?;
+
+        for &number in numbers.iter().filter(|&&number| number < first) {
+            remove_segment(path, number)?;
+        }
+
+        numbers.retain(|&number| number >= first);
+        Wal::check_segments(&numbers, first)?;
+
+        if numbers.is_empty() {
+            let file = open_segment(path, first, false)?;
+
+            return Ok((Wal::new(Some(path.to_path_buf()), file, first, cipher), vec![]));
+        }
+
+        let mut records = vec![];
+        let mut len = 0;
+        let mut active = None;
+
+        for (i, &number) in numbers.iter().enumerate() {
+            let mut file = open_segment(path, number, false)?;
+            let (mut found, offset, file_len) = Self::read(file.as_mut(), records.last(), cipher)?;
+            let torn = offset != file_len;
+
+            if torn {
+                file.set_len(offset as u64)?;
+                file.sync()?;
+            }
+
+            len += offset as u64;
+            records.append(&mut found);
+            active = Some((file, number, offset as u64));
+
+            if torn {
+                for &number in &numbers[i + 1..] {
+                    remove_segment(path, number)?;
+                }
+
+                break;
+            }
+        }
+
+        let (file, number, active_len) = active.unwrap();
+        let mut wal = Wal::new(Some(path.to_path_buf()), file, first, cipher);
+
+        wal.number = number;
+        wal.len = len;
+        wal.active_len = active_len;
+
+        Ok((wal, records))
+    }
+
+    /**
+     * segments from first must all be there
+     */
+    fn check_segments(numbers: &[u64], first: u64) -> Result<()> {
+        for (expected, &number) in (first..).zip(numbers) {
+            if number != expected {
+                return Err(Error::Corrupt(format!("log segment {} is missing", expected)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * same as open over given storage holding the only segment, first is its number
+     */
+    pub fn open_with_storage(
+        mut file: Box<dyn Storage>,
+        first: u64,
+        cipher: Option<&Cipher>,
+    ) -> Result<(Wal, Vec<Record>)> {
+        let (records, offset, len) = Self::read(file.as_mut(), None, cipher)?;
+
+        if offset != len {
+            file.set_len(offset as u64)?;
+            file.sync()?;
+        }
+
+        let mut wal = Wal::new(None, file, first, cipher);
+
+        wal.len = offset as u64;
+        wal.active_len = offset as u64;
+
+        Ok((wal, records))
+    }
+
+    /**
+     * reads complete records of log of database at path from segment first without modifying it
+     */
+    pub fn read_segments(path: &Path, first: u64, cipher: Option<&Cipher>) -> Result<Vec<Record>> {
+        let mut numbers = segments(path)?;
+
+        numbers.retain(|&number| number >= first);
+        Wal::check_segments(&numbers, first)?;
+
+        let mut records = vec![];
+
+        for number in numbers {
+            let mut file = match OpenOptions::new().read(true).open(segment_path(path, number)) {
+                Ok(file) => file,
+                Err(error) if error.kind() == ErrorKind::NotFound => break,
+                Err(error) => return Err(error.into()),
+            };
+
+            let (mut found, offset, len) = Self::read(&mut file, records.last(), cipher)?;
+
+            records.append(&mut found);
+
+            if offset != len {
+                break;
+            }
+        }
+
+        Ok(records)
+    }
+
+    /**
+     * returns complete records following previous one, offset where they end and length of file
+     */
+    fn read(
+        file: &mut dyn Storage,
+        previous: Option<&Record>,
+        cipher: Option<&Cipher>,
+    ) -> Result<(Vec<Record>, usize, usize)> {
+        let mut bytes = vec![0; file.len()? as usize];
+
+        file.read_at(0, &mut bytes)?;
+
+        let mut records = vec![];
+        let mut last_seq = previous.map(|Record::Batch { seq, .. }| *seq);
+        let mut offset = 0;
+
+        while let Some((record, next)) = Self::parse(&bytes, offset, cipher) {
+            let Record::Batch { seq, .. } = record;
+
+            if last_seq.is_some_and(|last_seq| seq != last_seq + 1) {
+                break;
+            }
+
+            last_seq = Some(seq);
+
+            records.push(record);
+            offset = next;
+        }
+
+        Ok((records, offset, bytes.len()))
+    }
+
+    /**
+     * returns record at offset and offset of the next one, sealed payload which fails to unseal ends log
+     * like corrupt record does
+     */
+    fn parse(bytes: &[u8], offset: usize, cipher: Option<&Cipher>) -> Option<(Record, usize)> {
+        let mut header = bytes.get(offset..offset + RECORD_HEADER_SIZE)?;
+        let len = take_u32(&mut header)? as usize;
+        let crc = take_u32(&mut header)?;
+
+        let start = offset + RECORD_HEADER_SIZE;
+        let body = bytes.get(start..start + len)?;
+
+        if len < BODY_HEADER_SIZE || checksum(body) != crc {
+            return None;
+        }
+
+        let seq = u64::from_le_bytes(body[1..BODY_HEADER_SIZE].try_into().unwrap());
+        let mut payload = &body[BODY_HEADER_SIZE..];
+        let mut unsealed = vec![];
+
+        if let Some(cipher) = cipher {
+            unsealed.resize(payload.len().checked_sub(SEAL_SIZE)?, 0);
+
+            if !cipher.unseal(&body[..BODY_HEADER_SIZE], payload, &mut unsealed) {
+                return None;
+            }
+
+            payload = &unsealed;
+        }
+
+        let record = match body[0] {
+            BATCH => Record::Batch {
+                seq,
+                batch: WriteBatch::decode(payload)?,
+            },
+            _ => return None,
+        };
+
+        Some((record, start + len))
+    }
+
+    /**
+     * size of log in bytes, all segments together
+     */
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /**
+     * number of segments on disk
+     */
+    pub fn segments(&self) -> u64 {
+        self.number - self.first + 1
+    }
+
+    pub fn segment_size(&self) -> Option<u64> {
+        self.segment_size
+    }
+
+    /**
+     * size after which active segment rolls over, None keeps one segment until checkpoint
+     */
+    pub fn set_segment_size(&mut self, segment_size: Option<u64>) {
+        self.segment_size = segment_size;
+    }
+
+    /**
+     * syncs active segment and starts the next one, so records of older segments are durable
+     * before any of its records are
+     */
+    fn roll(&mut self, path: &Path) -> Result<()> {
+        self.file.sync()?;
+        self.file = open_segment(path, self.number + 1, true)?;
+        self.number += 1;
+        self.active_len = 0;
+
+        sync_parent(path)
+    }
+
+    fn append(&mut self, kind: u8, seq: u64, payload: &[u8]) -> Result<()> {
+        let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + BODY_HEADER_SIZE + payload.len() + SEAL_SIZE);
+
+        record.extend_from_slice(&[0; RECORD_HEADER_SIZE]);
+        record.push(kind);
+        record.extend_from_slice(&seq.to_le_bytes());
+
+        match &self.cipher {
+            Some(cipher) => {
+                let aad = record[RECORD_HEADER_SIZE..].to_vec();
+
+                cipher.seal(&aad, payload, &mut record);
+            }
+            None => record.extend_from_slice(payload),
+        }
+
+        let len = record.len() - RECORD_HEADER_SIZE;
+
+        record[..4].copy_from_slice(&(len as u32).to_le_bytes());
+
+        let crc = checksum(&record[RECORD_HEADER_SIZE..]);
+
+        record[4..RECORD_HEADER_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+        let full = self
+            .segment_size
+            .is_some_and(|segment_size| self.active_len > 0 && self.active_len + record.len() as u64 > segment_size);
+
+        if let Some(path) = self.path.clone().filter(|_| full) {
+            self.roll(&path)?;
+        }
+
+        self.file.write_at(self.active_len, &record)?;
+        self.active_len += record.len() as u64;
+        self.len += record.len() as u64;
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(kind, seq, bytes = record.len(), segment = self.number, "wal append");
+
+        Ok(())
+    }
+
+    /**
+     * appends batch, it is durable only after sync
+     */
+    pub fn append_batch(&mut self, seq: u64, batch: &WriteBatch) -> Result<()> {
+        let mut payload = vec![];
+
+        batch.encode(&mut payload);
+
+        self.append(BATCH, seq, &payload)
+    }
+
+    /**
+     * starts new empty segment and returns its number, older segments stay until remove_old
+     * changes of their records must be committed to pages before
+     * log over single storage is emptied here instead
+     */
+    pub fn rotate(&mut self) -> Result<u64> {
+        match self.path.clone() {
+            Some(path) => self.roll(&path)?,
+            None => {
+                self.file.set_len(0)?;
+                self.file.sync()?;
+                self.number += 1;
+                self.first = self.number;
+                self.len = 0;
+                self.active_len = 0;
+            }
+        }
+
+        Ok(self.number)
+    }
+
+    /**
+     * removes segments older than the active one, header must already name it as the oldest one needed
+     */
+    pub fn remove_old(&mut self) -> Result<()> {
+        if let Some(path) = &self.path {
+            for number in self.first..self.number {
+                remove_segment(path, number)?;
+            }
+        }
+
+        self.first = self.number;
+        self.len = self.active_len;
+
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync()?;
+
+        Ok(())
+    }
+}
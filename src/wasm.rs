@@ -0,0 +1,123 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::*;
+
+use crate::BTree;
+
+/**
+ * BTree<i64> as JavaScript class WasmBTree, keys cross as BigInt
+ * toStructureJson is BTree::to_json_structure as text, so frontend can draw nodes and animate splits and merges
+ */
+#[wasm_bindgen]
+pub struct WasmBTree {
+    tree: BTree<i64>,
+}
+
+#[wasm_bindgen]
+impl WasmBTree {
+    /**
+     * empty tree, throws if t < 2
+     */
+    #[wasm_bindgen(constructor)]
+    pub fn new(t: usize) -> Result<WasmBTree, JsError> {
+        if t < 2 {
+            return Err(JsError::new("t must be at least 2"));
+        }
+
+        Ok(WasmBTree { tree: BTree::new(t) })
+    }
+
+    pub fn insert(&mut self, key: i64) {
+        self.tree.insert(key);
+    }
+
+    /**
+     * removes one occurrence of key, returns whether it was there
+     */
+    pub fn delete(&mut self, key: i64) -> bool {
+        self.tree.delete(&key)
+    }
+
+    pub fn contains(&self, key: i64) -> bool {
+        self.tree.contains(key)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    #[wasm_bindgen(js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /**
+     * keys in order as BigInt64Array
+     */
+    #[wasm_bindgen(js_name = toArray)]
+    pub fn to_array(&self) -> Vec<i64> {
+        self.tree.to_vec()
+    }
+
+    #[wasm_bindgen(js_name = toStructureJson)]
+    pub fn to_structure_json(&self) -> String {
+        self.tree.to_json_structure().to_string()
+    }
+}
+
+/**
+ * string variant of WasmBTree, keys are ordered by their UTF-8 bytes
+ */
+#[wasm_bindgen]
+pub struct WasmStringBTree {
+    tree: BTree<String>,
+}
+
+#[wasm_bindgen]
+impl WasmStringBTree {
+    #[wasm_bindgen(constructor)]
+    pub fn new(t: usize) -> Result<WasmStringBTree, JsError> {
+        if t < 2 {
+            return Err(JsError::new("t must be at least 2"));
+        }
+
+        Ok(WasmStringBTree { tree: BTree::new(t) })
+    }
+
+    pub fn insert(&mut self, key: String) {
+        self.tree.insert(key);
+    }
+
+    pub fn delete(&mut self, key: String) -> bool {
+        self.tree.delete(&key)
+    }
+
+    pub fn contains(&self, key: String) -> bool {
+        self.tree.contains(key)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    #[wasm_bindgen(js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /**
+     * keys in order as Array of strings
+     */
+    #[wasm_bindgen(js_name = toArray)]
+    pub fn to_array(&self) -> Vec<String> {
+        self.tree.to_vec()
+    }
+
+    #[wasm_bindgen(js_name = toStructureJson)]
+    pub fn to_structure_json(&self) -> String {
+        self.tree.to_json_structure().to_string()
+    }
+}
@@ -0,0 +1,121 @@
+use std::ops::Bound;
+
+use srdb::{AggBTree, Count, MaxBy, MinBy, Monoid, SumBy};
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+const PRIME: u64 = (1 << 61) - 1;
+
+/**
+ * polynomial hash of keys in order, (hash, base to power of count), combine is not commutative,
+ * so any fold of keys out of order shows
+ */
+struct Hash;
+
+impl Monoid<u64> for Hash {
+    type Value = (u64, u64);
+
+    fn identity(&self) -> (u64, u64) {
+        (0, 1)
+    }
+
+    fn lift(&self, key: &u64) -> (u64, u64) {
+        (key + 1, 131)
+    }
+
+    fn combine(&self, a: &(u64, u64), b: &(u64, u64)) -> (u64, u64) {
+        let mul = |x: u64, y: u64| ((x as u128 * y as u128) % PRIME as u128) as u64;
+
+        ((mul(a.0, b.1) + b.0) % PRIME, mul(a.1, b.1))
+    }
+}
+
+fn fold<M: Monoid<u64>>(monoid: &M, keys: &[u64]) -> M::Value {
+    keys.iter().fold(monoid.identity(), |acc, key| monoid.combine(&acc, &monoid.lift(key)))
+}
+
+/**
+ * grows tree to peak keys and shrinks it to a few keys in rounds of random inserts and deletes,
+ * every op caches are checked against folds recomputed bottom-up and random ranges against sorted model,
+ * shrinking rounds merge nodes and collapse root again and again
+ */
+fn against_model<M: Monoid<u64>>(t: usize, monoid: M, seed: u64)
+where
+    M::Value: PartialEq + std::fmt::Debug,
+{
+    let mut tree = AggBTree::new(t, monoid);
+    let mut model: Vec<u64> = vec![];
+    let mut next = lcg(seed);
+    let (mut merges, mut collapses) = (0, 0);
+
+    for round in 0..6 {
+        let peak = 200 + round * 100;
+
+        while model.len() < peak {
+            let key = next() % 1000;
+
+            tree.insert(key);
+            model.insert(model.partition_point(|k| *k <= key), key);
+            tree.check_invariants().unwrap();
+        }
+
+        while model.len() > 3 {
+            let key = if next().is_multiple_of(4) { next() % 1000 } else { model[next() as usize % model.len()] };
+            let stats = tree.tree().stats();
+            let present = model.binary_search(&key);
+
+            assert_eq!(tree.delete(&key), present.is_ok(), "delete of {}", key);
+
+            if let Ok(i) = present {
+                model.remove(i);
+            }
+
+            let after = tree.tree().stats();
+
+            merges += usize::from(after.nodes < stats.nodes);
+            collapses += usize::from(after.height < stats.height);
+
+            tree.check_invariants().unwrap_or_else(|message| panic!("t = {}, round {}: {}", t, round, message));
+
+            let (a, b) = (next() % 1000, next() % 1000);
+            let (lo, hi) = (a.min(b), a.max(b));
+            let inside = &model[model.partition_point(|k| *k < lo)..model.partition_point(|k| *k <= hi)];
+            let before_hi = &model[..model.partition_point(|k| *k < hi)];
+            let after_lo = &model[model.partition_point(|k| *k <= lo)..];
+
+            assert_eq!(tree.aggregate_range(lo..=hi), fold(tree.monoid(), inside), "{}..={}", lo, hi);
+            assert_eq!(tree.aggregate_range(..hi), fold(tree.monoid(), before_hi), "..{}", hi);
+            assert_eq!(
+                tree.aggregate_range((Bound::Excluded(lo), Bound::Unbounded)),
+                fold(tree.monoid(), after_lo),
+                "{}..",
+                lo
+            );
+        }
+
+        assert_eq!(tree.aggregate(), fold(tree.monoid(), &model));
+    }
+
+    assert!(merges > 100, "{} deletes merged nodes", merges);
+    assert!(collapses >= 6, "{} root collapses", collapses);
+}
+
+#[test]
+fn aggregates_survive_merges_and_root_collapse() {
+    for t in [2, 3, 5] {
+        for seed in 0..2 {
+            against_model(t, Count, seed);
+            against_model(t, SumBy(|key: &u64| key * key), seed);
+            against_model(t, MinBy(|key: &u64| key % 97), seed);
+            against_model(t, MaxBy(|key: &u64| key % 89), seed);
+            against_model(t, Hash, seed);
+        }
+    }
+}
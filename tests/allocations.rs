@@ -0,0 +1,425 @@
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+#[cfg(feature = "allocator_api")]
+use std::alloc::{AllocError, Allocator};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::ptr;
+#[cfg(feature = "allocator_api")]
+use std::ptr::NonNull;
+#[cfg(feature = "allocator_api")]
+use std::rc::Rc;
+
+use srdb::{BTree, FixedT, NaturalOrder, StrTree};
+
+/**
+ * global allocator counting allocations of current thread, so tests running in parallel don't disturb each other
+ * it fails allocations of thread once they exceed its quota of bytes, see with_quota
+ */
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static BYTES: Cell<usize> = const { Cell::new(0) };
+    static FREES: Cell<usize> = const { Cell::new(0) };
+    static QUOTA: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if !admit(layout.size()) {
+            return ptr::null_mut();
+        }
+
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if !admit(layout.size()) {
+            return ptr::null_mut();
+        }
+
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if !admit(new_size) {
+            return ptr::null_mut();
+        }
+
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = FREES.try_with(|frees| frees.set(frees.get() + 1));
+
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/**
+ * counts allocation unless it exceeds quota
+ */
+fn admit(size: usize) -> bool {
+    let left = QUOTA.try_with(Cell::get).unwrap_or(usize::MAX);
+
+    if size > left {
+        return false;
+    }
+
+    let _ = QUOTA.try_with(|quota| quota.set(left - size));
+    let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+    let _ = BYTES.try_with(|bytes| bytes.set(bytes.get() + size));
+
+    true
+}
+
+/**
+ * runs f and returns its result with number and total size of allocations it made
+ */
+fn counted<R>(f: impl FnOnce() -> R) -> (R, usize, usize) {
+    let (allocations, bytes) = (ALLOCATIONS.with(Cell::get), BYTES.with(Cell::get));
+    let result = f();
+
+    (result, ALLOCATIONS.with(Cell::get) - allocations, BYTES.with(Cell::get) - bytes)
+}
+
+/**
+ * runs f with allocations of current thread limited to quota bytes, f must not panic meanwhile
+ */
+fn with_quota<R>(quota: usize, f: impl FnOnce() -> R) -> R {
+    QUOTA.with(|left| left.set(quota));
+
+    let result = f();
+
+    QUOTA.with(|left| left.set(usize::MAX));
+
+    result
+}
+
+/**
+ * runs f and returns number of frees it made
+ */
+fn freed(f: impl FnOnce()) -> usize {
+    let frees = FREES.with(Cell::get);
+
+    f();
+
+    FREES.with(Cell::get) - frees
+}
+
+#[test]
+fn to_vec_allocates_once() {
+    for t in [2, 6, 32] {
+        let mut tree = BTree::new(t);
+
+        for i in 0..100_000u64 {
+            tree.insert(i * 7919 % 100_000);
+        }
+
+        let (keys, allocations, bytes) = counted(|| tree.to_vec());
+
+        assert_eq!(keys, (0..100_000).collect::<Vec<_>>());
+        assert_eq!((allocations, bytes), (1, 100_000 * size_of::<u64>()), "t = {}", t);
+    }
+}
+
+/**
+ * nodes live in one arena, so tree of inline nodes allocates for paths of operations and for growth of arena,
+ * never per node, and it drops in a few frees however many nodes it has
+ */
+#[test]
+fn fixed_nodes_share_one_arena() {
+    let mut tree = BTree::<u64, NaturalOrder, FixedT<8>>::new_fixed();
+
+    let ((), allocations, _) = counted(|| {
+        for i in 0..100_000u64 {
+            tree.insert(i * 7919 % 100_000);
+        }
+    });
+
+    let nodes = tree.stats().nodes;
+
+    assert!(nodes > 5_000, "nodes = {}", nodes);
+    assert!(allocations < 2 * 100_000 + 100, "{} allocations for {} nodes", allocations, nodes);
+
+    let ((), deletes, _) = counted(|| {
+        for i in 0..50_000u64 {
+            tree.delete(&(i * 2));
+        }
+    });
+
+    assert!(deletes < 2 * 50_000 + 100, "{} allocations for deletes", deletes);
+    tree.check_invariants().unwrap();
+
+    assert!(freed(|| drop(tree)) < 10);
+}
+
+/**
+ * heap nodes cost two buffers each, arena of nodes grows by doubling
+ * and node structs take one contiguous slice of it, see memory_usage
+ */
+#[cfg(not(feature = "smallvec"))]
+#[test]
+fn runtime_nodes_allocate_keys_and_children_only() {
+    let mut tree = BTree::new(8);
+
+    let ((), allocations, _) = counted(|| {
+        for i in 0..100_000u64 {
+            tree.insert(i * 7919 % 100_000);
+        }
+    });
+
+    let usage = tree.memory_usage();
+    let arena = usage.node_bytes / usage.nodes;
+
+    assert!(allocations <= 2 * 100_000 + 2 * usage.nodes + 100, "{} allocations for {} nodes", allocations, usage.nodes);
+    // node struct is 64 bytes: two Vecs, count, leaf flag and two ids, arena at most doubles it
+    assert!(arena <= 2 * 64, "{} arena bytes per node", arena);
+    let frees = freed(|| drop(tree));
+
+    assert!((2 * usage.nodes..2 * usage.nodes + 5).contains(&frees), "{} frees for {} nodes", frees, usage.nodes);
+}
+
+/**
+ * with smallvec nodes of t = 8 keep 15 keys and 16 children inline, so they allocate nothing of their own,
+ * node struct holds both arrays with their lengths, count, leaf flag and two ids, arena at most doubles it,
+ * tree drops in a few frees however many nodes it has
+ */
+#[cfg(feature = "smallvec")]
+#[test]
+fn runtime_nodes_keep_keys_and_children_inline() {
+    let mut tree = BTree::new(8);
+
+    let ((), allocations, _) = counted(|| {
+        for i in 0..100_000u64 {
+            tree.insert(i * 7919 % 100_000);
+        }
+    });
+
+    let usage = tree.memory_usage();
+    let arena = usage.node_bytes / usage.nodes;
+    let node = 15 * size_of::<u64>() + 16 * size_of::<u32>() + 32;
+
+    assert!(allocations <= 2 * 100_000 + 100, "{} allocations for {} nodes", allocations, usage.nodes);
+    assert!(arena <= 2 * node, "{} arena bytes per node", arena);
+    assert!(freed(|| drop(tree)) < 10);
+}
+
+
+/**
+ * once arena has free slots, insert into fixed tree allocates its path only, one buffer of 4 node ids
+ * for height up to 4, however many nodes it splits, while heap nodes add buffers of keys and children per new node,
+ * runtime nodes of smallvec layout are inline like fixed ones
+ */
+#[test]
+fn fixed_nodes_allocate_nothing_per_node() {
+    let mut fixed = BTree::<u64, NaturalOrder, FixedT<8>>::new_fixed();
+    let mut runtime = BTree::new(8);
+
+    for i in 0..10_000u64 {
+        fixed.insert(i * 7919 % 10_000);
+        runtime.insert(i * 7919 % 10_000);
+    }
+
+    for i in (0..10_000u64).step_by(2) {
+        assert!(fixed.delete(&i));
+        assert!(runtime.delete(&i));
+    }
+
+    let nodes = fixed.stats().nodes;
+
+    assert!(fixed.stats().height <= 4);
+
+    for i in (0..10_000u64).step_by(2) {
+        let ((), allocations, bytes) = counted(|| fixed.insert(i));
+
+        assert_eq!((allocations, bytes), (1, 4 * size_of::<u32>()), "insert of {}", i);
+    }
+
+    let ((), runtime_allocations, _) = counted(|| {
+        for i in (0..10_000u64).step_by(2) {
+            runtime.insert(i);
+        }
+    });
+
+    let new_nodes = fixed.stats().nodes - nodes;
+
+    fixed.check_invariants().unwrap();
+    assert!(new_nodes > 100, "{} nodes split off", new_nodes);
+    assert_eq!(fixed.stats(), runtime.stats(), "layouts build the same tree");
+
+    if cfg!(feature = "smallvec") {
+        assert_eq!(runtime_allocations, 5_000, "inline runtime nodes allocate paths only, as fixed ones do");
+    } else {
+        assert!(runtime_allocations >= 5_000 + 2 * new_nodes, "{} allocations of heap nodes", runtime_allocations);
+    }
+}
+
+/**
+ * keys of StrTree borrow from backing string and are moved by splits, never cloned,
+ * so it allocates exactly as tree of u64 keys in the same order does, that is for nodes and paths only
+ */
+#[test]
+fn str_tree_allocates_no_keys() {
+    let text: String = (0..20_000).map(|i| format!("word{:05} ", i)).collect();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let order: Vec<usize> = (0..words.len()).map(|i| i * 7919 % words.len()).collect();
+
+    for t in [2, 5, 16] {
+        let mut tree = StrTree::new(t);
+        let mut ranks = BTree::new(t);
+
+        let ((), allocations, _) = counted(|| order.iter().for_each(|&i| tree.insert(Cow::Borrowed(words[i]))));
+        let ((), rank_allocations, _) = counted(|| order.iter().for_each(|&i| ranks.insert(i as u64)));
+
+        assert_eq!(allocations, rank_allocations, "t = {}", t);
+
+        let removals = words.iter().step_by(3);
+        let (removed, allocations, _) = counted(|| removals.filter_map(|word| tree.remove(*word)).count());
+        let rank_removals = (0..words.len() as u64).step_by(3);
+        let (_, rank_allocations, _) = counted(|| rank_removals.filter(|i| ranks.delete(i)).count());
+
+        assert_eq!(removed, words.len().div_ceil(3));
+        assert_eq!(allocations, rank_allocations, "t = {}", t);
+
+        tree.check_invariants().unwrap();
+        assert!(tree.contains_key("word00001") && !tree.contains_key("word00003"));
+
+        let backing = text.as_bytes().as_ptr_range();
+
+        assert!(tree.iter().all(|key| matches!(key, Cow::Borrowed(word) if backing.contains(&word.as_ptr()))));
+
+        let owned: StrTree<'static> = tree.into_owned();
+
+        assert_eq!(owned.len(), words.len() - removed);
+        assert!(owned.iter().all(|key| matches!(key, Cow::Owned(_))));
+    }
+}
+
+/**
+ * allocator counting what tree takes from it and gives back, clones share counts,
+ * it takes memory from System directly, so Counting sees only what bypasses it
+ */
+#[cfg(feature = "allocator_api")]
+#[derive(Clone, Default)]
+struct Tracked(Rc<Counts>);
+
+#[cfg(feature = "allocator_api")]
+#[derive(Default)]
+struct Counts {
+    allocations: Cell<usize>,
+    frees: Cell<usize>,
+    live_bytes: Cell<usize>,
+}
+
+#[cfg(feature = "allocator_api")]
+unsafe impl Allocator for Tracked {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.0.allocations.set(self.0.allocations.get() + 1);
+        self.0.live_bytes.set(self.0.live_bytes.get() + layout.size());
+
+        System.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.frees.set(self.0.frees.get() + 1);
+        self.0.live_bytes.set(self.0.live_bytes.get() - layout.size());
+
+        unsafe { System.deallocate(ptr, layout) }
+    }
+}
+
+/**
+ * tree in allocator takes every node, key buffer and path of insert and delete from it and none from global heap,
+ * dropping tree gives back all it took
+ */
+#[cfg(feature = "allocator_api")]
+#[test]
+fn tree_in_allocator_takes_all_memory_from_it() {
+    for t in [2, 5, 32] {
+        let tracked = Tracked::default();
+        let mut tree = BTree::new_in(t, tracked.clone());
+
+        let ((), global, _) = counted(|| {
+            for i in 0..20_000u64 {
+                tree.insert(i * 7919 % 20_000);
+            }
+
+            for i in (0..20_000u64).step_by(2) {
+                assert!(tree.delete(&i));
+            }
+
+            for i in (1..20_000u64).step_by(4) {
+                assert_eq!(tree.remove(&i), Some(i));
+            }
+
+            for i in 0..1_000u64 {
+                tree.try_insert(i * 4).unwrap();
+                assert!(tree.contains(i * 4 + 3));
+            }
+        });
+
+        let inserted = tracked.0.allocations.get();
+
+        assert_eq!(global, 0, "t = {}", t);
+        assert!(inserted >= tree.stats().nodes, "{} allocations for {} nodes", inserted, tree.stats().nodes);
+        assert_eq!(tree.len(), 6_000);
+        tree.check_invariants().unwrap();
+
+        let (copy, global, _) = counted(|| tree.clone());
+
+        assert_eq!(global, 0, "clone of t = {}", t);
+        assert!(tracked.0.allocations.get() > inserted);
+
+        let frees = freed(|| {
+            drop(tree);
+            drop(copy);
+        });
+
+        assert_eq!(frees, 0, "every free goes to allocator");
+        assert_eq!(tracked.0.allocations.get(), tracked.0.frees.get());
+        assert_eq!(tracked.0.live_bytes.get(), 0);
+    }
+}
+
+/**
+ * try_insert under quota too small for its reservations fails and leaves keys and shape of tree as they were,
+ * memory reserved before failure stays with tree, so retries with growing quota get further until one succeeds
+ */
+#[test]
+fn failed_try_insert_leaves_tree_unchanged() {
+    for t in [2, 3, 16] {
+        let mut tree = BTree::new(t);
+        let mut model = BTree::new(t);
+        let mut failures = 0;
+
+        for i in 0..3_000u64 {
+            let key = i * 7919 % 3_000;
+
+            for quota in [0, 16, 64, 256, 1024, 4096, usize::MAX] {
+                let (keys, stats) = (tree.to_vec(), tree.stats());
+
+                if with_quota(quota, || tree.try_insert(key)).is_ok() {
+                    break;
+                }
+
+                failures += 1;
+
+                assert_eq!(tree.to_vec(), keys, "t = {}, failed insert of {}", t, key);
+                assert_eq!(tree.stats(), stats, "t = {}, failed insert of {}", t, key);
+            }
+
+            model.insert(key);
+        }
+
+        tree.check_invariants().unwrap();
+        assert!(failures > 3_000, "{} failures for t = {}", failures, t);
+        assert_eq!(tree.to_vec(), model.to_vec());
+        assert_eq!(tree.stats(), model.stats());
+    }
+}
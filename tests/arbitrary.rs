@@ -0,0 +1,62 @@
+#![cfg(feature = "arbitrary")]
+
+use std::collections::BTreeMap;
+
+use proptest::prelude::*;
+use proptest::test_runner::{Config, RngAlgorithm, TestError, TestRng, TestRunner};
+use srdb::{BTree, Shape, SharedMap};
+
+proptest! {
+    #![proptest_config(Config { failure_persistence: None, ..Config::default() })]
+
+    #[test]
+    fn arbitrary_trees_hold_invariants(tree in any::<BTree<u16>>()) {
+        prop_assert!(tree.check_invariants().is_ok());
+        prop_assert!(tree.to_vec().windows(2).all(|pair| pair[0] <= pair[1]));
+        prop_assert!((2..=8).contains(&tree.t()));
+    }
+
+    #[test]
+    fn arbitrary_maps_agree_with_std(map in any::<SharedMap<u8, u32>>()) {
+        let entries = map.to_vec();
+        let std: BTreeMap<u8, u32> = entries.iter().cloned().collect();
+
+        prop_assert!(map.read_with(|tree| tree.check_invariants()).is_ok());
+        prop_assert_eq!(entries.len(), std.len());
+
+        for (key, value) in std {
+            prop_assert_eq!(map.get(&key), Some(value));
+        }
+    }
+
+    #[test]
+    fn leaf_shape_has_root_only(tree in BTree::<String>::arbitrary_shaped(Shape::Leaf)) {
+        prop_assert_eq!(tree.stats().height, 1);
+        prop_assert!(!tree.is_empty());
+    }
+}
+
+/**
+ * failing tree is shrunk until dropping any operation makes property hold, so it keeps just enough keys
+ */
+#[test]
+fn failing_tree_shrinks_to_smallest_size() {
+    for seed in 0..8u8 {
+        let config = Config {
+            failure_persistence: None,
+            max_shrink_iters: 100_000,
+            ..Config::default()
+        };
+        let mut runner = TestRunner::new_with_rng(config, TestRng::from_seed(RngAlgorithm::ChaCha, &[seed; 32]));
+        let result = runner.run(&any::<BTree<u32>>(), |tree| {
+            prop_assert!(tree.len() < 10);
+
+            Ok(())
+        });
+
+        match result {
+            Err(TestError::Fail(_, tree)) => assert_eq!(tree.len(), 10, "seed {}: {:?}", seed, tree),
+            result => panic!("property did not fail for seed {}: {:?}", seed, result),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+#![cfg(feature = "async")]
+
+use std::collections::BTreeMap;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use srdb::{AsyncDb, MemStorage, SrdbOptions, SyncMode, WriteBatch};
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn memory() -> AsyncDb {
+    let options = SrdbOptions::new().sync_mode(SyncMode::Off);
+    let db = options.create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new())).unwrap();
+
+    AsyncDb::with_workers(db, 4)
+}
+
+/**
+ * polls future once, so its operation is queued, and leaves it pending
+ */
+async fn poll_once<F: Future + Unpin>(future: &mut F) {
+    poll_fn(|cx| {
+        let _ = Pin::new(&mut *future).poll(cx);
+
+        Poll::Ready(())
+    })
+    .await
+}
+
+/**
+ * tasks own keys equal to their number modulo tasks, each keeps model of its keys, together they are database
+ */
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_tasks_against_model() {
+    const TASKS: u32 = 16;
+
+    let db = memory();
+
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|task| {
+            let db = db.clone();
+
+            tokio::spawn(async move {
+                let mut model = BTreeMap::new();
+
+                for round in 0..200u32 {
+                    let i = (round * 7 + task) % 100 * TASKS + task;
+
+                    if round % 5 == 4 {
+                        assert_eq!(db.remove(&key(i)).await.unwrap(), model.remove(&key(i)).is_some());
+                    } else {
+                        let value = format!("{} {}", task, round).into_bytes();
+
+                        db.put(&key(i), &value).await.unwrap();
+                        model.insert(key(i), value);
+                    }
+
+                    assert_eq!(db.get(&key(i)).await.unwrap(), model.get(&key(i)).cloned());
+                }
+
+                model
+            })
+        })
+        .collect();
+
+    let mut expected = BTreeMap::new();
+
+    for task in tasks {
+        expected.extend(task.await.unwrap());
+    }
+
+    db.flush().await.unwrap();
+
+    assert_eq!(db.scan::<[u8]>(..).await.unwrap(), expected.into_iter().collect::<Vec<_>>());
+}
+
+/**
+ * runtime of one thread keeps running other tasks while large write and scan are done by workers
+ */
+#[tokio::test(flavor = "current_thread")]
+async fn operations_do_not_block_executor() {
+    let db = memory();
+    let done = Arc::new(AtomicBool::new(false));
+    let ticks = Arc::new(AtomicUsize::new(0));
+
+    let ticker = tokio::spawn({
+        let (done, ticks) = (done.clone(), ticks.clone());
+
+        async move {
+            while !done.load(Ordering::SeqCst) {
+                ticks.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_micros(100)).await;
+            }
+        }
+    });
+
+    let mut batch = WriteBatch::new();
+
+    for i in 0..10_000 {
+        batch.put(&key(i), &[b'x'; 100]);
+    }
+
+    tokio::task::yield_now().await;
+
+    let before = ticks.load(Ordering::SeqCst);
+
+    db.write(batch).await.unwrap();
+
+    assert_eq!(db.scan(key(0)..key(10_000)).await.unwrap().len(), 10_000);
+    assert!(ticks.load(Ordering::SeqCst) > before, "ticker ran while operations were waited for");
+
+    done.store(true, Ordering::SeqCst);
+    ticker.await.unwrap();
+}
+
+/**
+ * future dropped before it is polled does nothing, dropped after it applies its batch whole,
+ * so every batch is there with all of its keys or not at all, timeout polls future before it gives up on it
+ */
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dropped_futures_never_leave_half_applied_batch() {
+    let db = memory();
+    let mut polled = vec![];
+
+    for batch_number in 0..100u32 {
+        let mut batch = WriteBatch::new();
+
+        for i in 0..100 {
+            batch.put(&key(batch_number * 100 + i), &batch_number.to_le_bytes());
+        }
+
+        let mut write = db.write(batch);
+
+        match batch_number % 3 {
+            0 => drop(write),
+            1 => {
+                poll_once(&mut write).await;
+                drop(write);
+                polled.push(batch_number);
+            }
+            _ => {
+                let timeout = Duration::from_micros(batch_number as u64 * 10);
+                let _ = tokio::time::timeout(timeout, write).await;
+
+                polled.push(batch_number);
+            }
+        }
+    }
+
+    db.flush().await.unwrap();
+
+    let entries = db.scan::<[u8]>(..).await.unwrap();
+
+    assert_eq!(entries.len(), polled.len() * 100);
+
+    for batch_number in 0..100u32 {
+        let present = entries.iter().filter(|(_, value)| value[..] == batch_number.to_le_bytes()).count();
+
+        assert_eq!(present, if polled.contains(&batch_number) { 100 } else { 0 }, "batch {}", batch_number);
+    }
+
+    let mut remove = db.remove(&key(100));
+
+    poll_once(&mut remove).await;
+    drop(remove);
+
+    assert_eq!(db.get(&key(100)).await.unwrap(), None, "dropped remove is done too");
+}
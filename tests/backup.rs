@@ -0,0 +1,187 @@
+#![cfg(feature = "std")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+use srdb::{BackupManifest, Db, SrdbOptions, SyncMode, VerifyMode, WriteBatch};
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn value(i: u32) -> Vec<u8> {
+    format!("value of key {}", i).into_bytes()
+}
+
+/**
+ * empty directory of one test, database, backup and their logs and manifests go there
+ */
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("srdb-backup-{}-{}", name, std::process::id()));
+
+    let _ = fs::remove_dir_all(&dir);
+
+    fs::create_dir(&dir).unwrap();
+
+    dir
+}
+
+fn batch(from: u32, to: u32) -> WriteBatch {
+    let mut batch = WriteBatch::new();
+
+    for i in from..to {
+        batch.put(&key(i), &value(i));
+    }
+
+    batch
+}
+
+/**
+ * backup copies pages on another thread while handle inserts, overwrites and flushes,
+ * backup opens on its own, passes verify and holds exactly the keys written before it started
+ */
+#[test]
+fn backup_during_concurrent_inserts_is_consistent() {
+    let dir = temp_dir("concurrent");
+    let mut db = SrdbOptions::new().sync_mode(SyncMode::Off).create(dir.join("db")).unwrap();
+
+    db.write(&batch(0, 5000)).unwrap();
+
+    for round in 0..3u32 {
+        let path = dir.join(format!("backup{}", round));
+        let mut backup = db.backup(&path).unwrap();
+        let before = db.to_vec().unwrap();
+
+        let copier = thread::spawn(move || {
+            while !backup.step(4).unwrap() {
+                thread::yield_now();
+            }
+
+            backup.finish().unwrap()
+        });
+
+        let from = 5000 + round * 4000;
+
+        for i in from..from + 4000 {
+            db.insert(&key(i), &value(i)).unwrap();
+
+            if i % 500 == 0 {
+                db.delete(&key(i / 2)).unwrap();
+                db.flush().unwrap();
+            }
+        }
+
+        let report = copier.join().unwrap();
+        let mut copy = Db::open(&path).unwrap();
+
+        assert!(copy.verify(VerifyMode::Full).is_ok(), "round {}", round);
+        assert!(copy.to_vec().unwrap() == before, "round {}", round);
+        assert_eq!(report.seq, copy.seq(), "round {}", round);
+    }
+
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    drop(db);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dropped_backup_removes_its_file() {
+    let dir = temp_dir("dropped");
+    let mut db = SrdbOptions::new().sync_mode(SyncMode::Off).create(dir.join("db")).unwrap();
+
+    db.write(&batch(0, 5000)).unwrap();
+
+    let mut backup = db.backup(dir.join("backup")).unwrap();
+
+    backup.step(1).unwrap();
+    db.write(&batch(5000, 6000)).unwrap();
+    drop(backup);
+
+    assert!(!dir.join("backup").exists());
+    assert_eq!(db.len(), 6000);
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    drop(db);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * round of random inserts, overwrites with long values, deletes and table changes
+ */
+fn random_round(db: &mut Db, next: &mut impl FnMut() -> u64) {
+    let mut batch = WriteBatch::new();
+
+    for _ in 0..next() % 1000 {
+        let i = (next() % 5000) as u32;
+
+        match next() % 10 {
+            0..=4 => batch.put(&key(i), &value(i)),
+            5 if i.is_multiple_of(4) => batch.put(&key(i), &vec![i as u8; 10_000]),
+            5 => batch.put(&key(i), &value(i)),
+            6 | 7 => batch.delete(&key(i)),
+            8 => batch.put_in("table", &key(i), &value(i)),
+            _ => batch.delete_in("table", &key(i)),
+        }
+    }
+
+    db.write(&batch).unwrap();
+
+    if next().is_multiple_of(2) {
+        db.flush().unwrap();
+    }
+}
+
+/**
+ * after every round of random changes delta is made on top of the one before and full backup is made too,
+ * base with deltas up to that round restores the same database as full backup of it
+ */
+#[test]
+fn base_with_deltas_equals_full_backup() {
+    for seed in 0..3 {
+        let dir = temp_dir(&format!("deltas-{}", seed));
+        let mut db = SrdbOptions::new().sync_mode(SyncMode::Off).create(dir.join("db")).unwrap();
+        let mut next = lcg(seed);
+
+        db.create_table("table").unwrap();
+        random_round(&mut db, &mut next);
+        db.backup_to(dir.join("base")).unwrap();
+
+        let mut deltas = vec![];
+
+        for round in 0..4 {
+            random_round(&mut db, &mut next);
+
+            let delta = dir.join(format!("delta{}", round));
+            let before = deltas.last().unwrap_or(&dir.join("base")).clone();
+            let full = dir.join(format!("full{}", round));
+
+            db.backup_incremental(BackupManifest::path_for(&before), &delta).unwrap();
+            db.backup_to(&full).unwrap();
+            deltas.push(delta);
+
+            let restored_path = dir.join(format!("restored{}", round));
+            let mut restored = Db::restore_backup(dir.join("base"), &deltas, &restored_path).unwrap();
+            let mut full = Db::open(&full).unwrap();
+
+            assert!(restored.verify(VerifyMode::Full).is_ok(), "seed {}, round {}", seed, round);
+            assert_eq!(restored.digests().unwrap(), full.digests().unwrap(), "seed {}, round {}", seed, round);
+            assert_eq!(restored.digests().unwrap(), db.digests().unwrap(), "seed {}, round {}", seed, round);
+            assert!(restored.to_vec().unwrap() == full.to_vec().unwrap());
+            assert_eq!(restored.seq(), full.seq());
+        }
+
+        drop(db);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,606 @@
+use std::cell::Cell;
+
+use srdb::{BTree, Comparator, PersistentBTree, PrefixBTree};
+
+thread_local! {
+    static CLONES: Cell<usize> = const { Cell::new(0) };
+}
+
+/**
+ * key counting its clones, counter is per thread as tests run in parallel
+ */
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Counted(u32);
+
+impl Clone for Counted {
+    fn clone(&self) -> Self {
+        CLONES.with(|clones| clones.set(clones.get() + 1));
+
+        Counted(self.0)
+    }
+}
+
+fn clones() -> usize {
+    CLONES.with(Cell::get)
+}
+
+/**
+ * permutation of 0..n, deletes in it hit leaves, internal keys, borrows and merges
+ */
+fn shuffled(n: u32, step: u32) -> Vec<u32> {
+    (0..n).map(|i| (i as u64 * step as u64 % n as u64) as u32).collect()
+}
+
+#[test]
+fn delete_does_not_clone_keys() {
+    for t in [2, 3, 5] {
+        for step in [1, 7919, 104729] {
+            let mut tree = BTree::new(t);
+
+            for key in shuffled(2000, 7) {
+                tree.insert(Counted(key));
+            }
+
+            let before = clones();
+
+            for (i, key) in shuffled(2000, step).into_iter().enumerate() {
+                if i % 2 == 0 {
+                    assert!(tree.delete(&Counted(key)));
+                } else {
+                    assert_eq!(tree.remove(&Counted(key)), Some(Counted(key)));
+                }
+            }
+
+            assert!(!tree.delete(&Counted(0)));
+            assert_eq!(clones(), before, "t = {}, step = {}", t, step);
+            assert!(tree.is_empty());
+            tree.check_invariants().unwrap();
+        }
+    }
+}
+
+/**
+ * keys compared by first field only, second field tells equal keys apart
+ */
+fn by_key(a: &(u32, u32), b: &(u32, u32)) -> std::cmp::Ordering {
+    a.0.cmp(&b.0)
+}
+
+#[test]
+fn insert_batch_keeps_duplicates_like_insert() {
+    let mut seed = 0x9e37_79b9_u64;
+    let mut next = move || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as u32
+    };
+
+    for t in [2, 3, 7] {
+        let mut batched = BTree::new_with_comparator(t, by_key as fn(&_, &_) -> _);
+        let mut looped = BTree::new_with_comparator(t, by_key as fn(&_, &_) -> _);
+        let mut model = Vec::new();
+        let mut seq = 0;
+
+        for len in [10, 300, 5, 64, 1000, 63, 2000] {
+            let chunk: Vec<_> = (0..len)
+                .map(|_| {
+                    seq += 1;
+                    (next() % 50, seq)
+                })
+                .collect();
+
+            for &value in &chunk {
+                looped.insert(value);
+            }
+
+            model.extend_from_slice(&chunk);
+            model.sort_by(by_key);
+            batched.insert_batch(chunk);
+
+            batched.check_invariants().unwrap();
+            assert_eq!(batched.len(), model.len());
+            assert!(looped.iter().eq(model.iter()), "insert, t = {}, len = {}", t, len);
+            assert!(batched.iter().eq(model.iter()), "insert_batch, t = {}, len = {}", t, len);
+        }
+    }
+}
+
+#[test]
+fn insert_batch_merges_into_existing_nodes() {
+    for t in [2, 4, 16] {
+        let mut tree = BTree::new(t);
+
+        tree.insert_batch((0..5000u32).map(|i| i * 2).collect());
+        tree.insert_batch((0..5000u32).rev().map(|i| i * 2 + 1).collect());
+        tree.insert_batch((0..100u32).map(|i| i * 97).collect());
+
+        tree.check_invariants().unwrap();
+
+        let mut expected: Vec<u32> = (0..10_000).chain((0..100).map(|i| i * 97)).collect();
+
+        expected.sort();
+
+        assert_eq!(tree.to_vec(), expected, "t = {}", t);
+    }
+}
+
+thread_local! {
+    static COMPARISONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/**
+ * key counting its comparisons
+ */
+#[derive(Debug, PartialEq, Eq)]
+struct Compared(u32);
+
+impl PartialOrd for Compared {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Compared {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        COMPARISONS.with(|comparisons| comparisons.set(comparisons.get() + 1));
+
+        self.0.cmp(&other.0)
+    }
+}
+
+/**
+ * comparisons each lookup of one key made
+ */
+fn comparisons(keys: impl Iterator<Item = u32>, lookup: impl Fn(u32) -> bool) -> Vec<usize> {
+    keys.map(|key| {
+        let before = COMPARISONS.with(Cell::get);
+
+        lookup(key);
+
+        COMPARISONS.with(Cell::get) - before
+    })
+    .collect()
+}
+
+/**
+ * most comparisons lookup of one key made
+ */
+fn max_comparisons(keys: impl Iterator<Item = u32>, lookup: impl Fn(u32) -> bool) -> usize {
+    comparisons(keys, lookup).into_iter().max().unwrap()
+}
+
+#[test]
+fn lookups_check_bounds_of_every_level() {
+    for t in [2, 6, 32] {
+        let mut tree = BTree::new(t);
+
+        for i in 0..10_000 {
+            tree.insert(Compared(1000 + i * 2));
+        }
+
+        let height = tree.stats().height;
+        let search = (2 * t - 1).next_power_of_two().ilog2() as usize + 1;
+        let descent = 2 + height * (search + 2);
+        let hits = || (0..10_000).map(|i| 1000 + i * 2);
+        let misses = || (0..10_000).map(|i| 1001 + i * 2);
+        let outside = || (0..1000).chain(30_000..31_000);
+
+        assert!(max_comparisons(hits(), |key| tree.contains(Compared(key))) <= descent, "t = {}", t);
+        assert!(max_comparisons(misses(), |key| tree.contains(Compared(key))) <= descent, "t = {}", t);
+        assert!(max_comparisons(misses(), |key| tree.get(&Compared(key)).is_some()) <= descent, "t = {}", t);
+        assert!(max_comparisons(outside(), |key| tree.contains(Compared(key))) <= 2, "t = {}", t);
+        assert!(max_comparisons(outside(), |key| tree.get(&Compared(key)).is_some()) <= 2, "t = {}", t);
+    }
+}
+
+/**
+ * probe between delimeter of root and nearest key of child is rejected by cached bounds of child,
+ * after bounds and search of root and one more comparison, far before reaching leaf
+ */
+#[test]
+fn probes_in_interior_gaps_stop_above_leaves() {
+    for t in [2, 6, 32] {
+        let mut tree = BTree::new(t);
+
+        for i in 0..10_000 {
+            tree.insert(Compared(1000 + i * 2));
+        }
+
+        for i in (0..10_000).filter(|i| i % 7 < 3) {
+            tree.remove(&Compared(1000 + i * 2));
+        }
+
+        let height = tree.stats().height;
+        let search = (2 * t - 1).next_power_of_two().ilog2() as usize + 1;
+        let gap = 2 + search + 1;
+        let probes = || (0..10_000).map(|i| 1001 + i * 2);
+        let fewest = comparisons(probes(), |key| tree.contains(Compared(key))).into_iter().min().unwrap();
+
+        assert!(height >= 3, "t = {}", t);
+        assert!(fewest <= gap, "t = {}", t);
+        assert!(probes().all(|key| !tree.contains(Compared(key))), "t = {}", t);
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_bulk_load_equals_bulk_load() {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+
+    for t in [2, 3, 16] {
+        for len in [0, 1, 5, 100, 10_000, 100_003] {
+            let data: Vec<u64> = (0..len).map(|i| i * 7919 % len.max(1) / 2).collect();
+            let mut sorted = data.clone();
+
+            sorted.sort();
+
+            let sequential = BTree::bulk_load(t, &sorted);
+            let parallel = pool.install(|| BTree::par_bulk_load(t, data));
+
+            parallel.check_invariants().unwrap();
+            assert_eq!(parallel.to_vec(), sorted, "t = {}, len = {}", t, len);
+            assert_eq!(parallel.to_ascii_string(), sequential.to_ascii_string(), "t = {}, len = {}", t, len);
+        }
+    }
+}
+
+/**
+ * deterministic stream of pseudo random numbers, the same for the same seed
+ */
+fn lcg(mut seed: u64) -> impl FnMut() -> u64 {
+    move || {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        seed >> 33
+    }
+}
+
+#[test]
+fn bulk_loaded_tree_takes_random_operations() {
+    for t in [2, 3, 8, 32] {
+        let mut packed = 0;
+
+        for fill in [1.0, 0.7, 0.0] {
+            let sorted: Vec<u64> = (0..5000).map(|i| i * 2).collect();
+            let mut tree = BTree::bulk_load_with_fill(t, &sorted, fill);
+            let mut model = sorted.clone();
+            let mut next = lcg(t as u64);
+
+            tree.check_invariants().unwrap();
+            assert_eq!(tree.to_vec(), model, "t = {}, fill = {}", t, fill);
+            assert!(tree.stats().nodes > packed, "lower fill takes more nodes, t = {}, fill = {}", t, fill);
+
+            packed = tree.stats().nodes;
+
+            for i in 0..20_000u32 {
+                let key = next() % 12_000;
+
+                if next().is_multiple_of(2) {
+                    tree.insert(key);
+                    model.insert(model.partition_point(|x| *x <= key), key);
+                } else {
+                    let found = model.binary_search(&key).ok();
+
+                    assert_eq!(tree.delete(&key), found.is_some(), "t = {}, fill = {}, key = {}", t, fill, key);
+                    found.map(|at| model.remove(at));
+                }
+
+                if i.is_multiple_of(1000) {
+                    tree.check_invariants().unwrap();
+                }
+            }
+
+            tree.check_invariants().unwrap();
+            assert_eq!(tree.to_vec(), model, "t = {}, fill = {}", t, fill);
+        }
+    }
+}
+
+#[test]
+fn auto_t_follows_key_size() {
+    assert_eq!(BTree::<u8>::with_auto_t().t(), 256);
+    assert_eq!(BTree::<u32>::with_auto_t().t(), 64);
+    assert_eq!(BTree::<u64>::with_auto_t().t(), 32);
+    assert_eq!(BTree::<[u64; 4]>::with_auto_t().t(), 8);
+    assert_eq!(BTree::<[u8; 200]>::with_auto_t().t(), 2);
+    assert_eq!(BTree::<[u8; 4096]>::with_auto_t().t(), 2);
+    assert_eq!(BTree::<()>::with_auto_t().t(), 256);
+    assert_eq!(BTree::<u64>::with_node_bytes(256).t(), 16);
+    assert_eq!(BTree::<u64>::with_node_bytes(0).t(), 2);
+
+    let mut small = BTree::with_auto_t();
+    let mut wide = BTree::with_auto_t();
+    let mut next = lcg(108);
+
+    for i in 0..20_000u32 {
+        let key = next() % 5000;
+
+        if i % 3 == 2 {
+            small.delete(&key);
+            wide.delete(&[key as u8; 200]);
+        } else {
+            small.insert(key);
+            wide.insert([key as u8; 200]);
+        }
+    }
+
+    small.check_invariants().unwrap();
+    wide.check_invariants().unwrap();
+}
+
+#[test]
+fn compact_packs_nodes_after_deletes() {
+    for t in [2, 5, 16] {
+        let mut tree = BTree::new(t);
+
+        for key in shuffled(10_000, 7919) {
+            tree.insert(Counted(key));
+        }
+
+        for key in shuffled(10_000, 104729) {
+            if key % 5 != 0 {
+                tree.delete(&Counted(key));
+            }
+        }
+
+        let sparse = tree.stats();
+        let before = clones();
+        let report = tree.compact();
+
+        assert_eq!(clones(), before, "compact moves keys, t = {}", t);
+        assert_eq!(report.nodes_before, sparse.nodes);
+        assert_eq!(report.nodes_after, tree.stats().nodes);
+        assert!(report.nodes_after < report.nodes_before, "t = {}, {:?}", t, report);
+        assert!(tree.stats().fill > sparse.fill, "t = {}", t);
+        assert!(tree.stats().height <= sparse.height, "t = {}", t);
+        assert!(tree.iter().map(|key| key.0).eq((0..10_000).filter(|key| key % 5 == 0)), "t = {}", t);
+        tree.check_invariants().unwrap();
+
+        for key in 0..2000 {
+            tree.insert(Counted(key * 5 + 1));
+        }
+
+        assert_eq!(tree.len(), 4000);
+        tree.check_invariants().unwrap();
+    }
+}
+
+#[test]
+fn prefix_tree_saves_memory_of_common_prefix() {
+    let prefix = "https://example.com/api/v2/organizations/acme/projects/";
+    let keys: Vec<String> = shuffled(100_000, 7919).into_iter().map(|i| format!("{}{:06}/issues", prefix, i)).collect();
+    let mut plain = BTree::new(16);
+    let mut packed = PrefixBTree::new(16);
+
+    for key in &keys {
+        plain.insert(key.clone());
+        packed.insert(key.clone());
+    }
+
+    for key in keys.iter().step_by(3) {
+        assert!(plain.delete(key));
+        assert!(packed.delete(key));
+    }
+
+    packed.check_invariants().unwrap();
+    assert_eq!(packed.to_vec(), plain.to_vec());
+    assert!(packed.contains(&keys[1]) && !packed.contains(&keys[0]));
+
+    let plain = plain.memory_usage_with(String::capacity);
+    let packed = packed.memory_usage();
+
+    assert!(packed.payload_bytes * 3 < plain.payload_bytes, "{:?} vs {:?}", packed, plain);
+    assert!(packed.total < plain.total, "{:?} vs {:?}", packed, plain);
+}
+
+#[test]
+fn persistent_clones_keep_their_contents() {
+    for t in [2, 3, 8] {
+        let mut tree = PersistentBTree::new(t);
+        let mut model = Vec::new();
+        let mut clones = Vec::new();
+        let mut next = lcg(t as u64 + 113);
+
+        for i in 0..20_000u32 {
+            let key = next() % 3000;
+
+            if i % 3 == 2 {
+                let found = model.binary_search(&key).ok();
+
+                assert_eq!(tree.delete(&key), found.is_some(), "t = {}, key = {}", t, key);
+                found.map(|at| model.remove(at));
+            } else {
+                tree.insert(key);
+                model.insert(model.partition_point(|x| *x <= key), key);
+            }
+
+            if i % 997 == 0 {
+                let clone = tree.clone();
+
+                assert!(clone.ptr_eq(&tree));
+                clones.push((clone, model.clone()));
+            }
+        }
+
+        assert!(!clones.last().unwrap().0.ptr_eq(&tree));
+
+        for (clone, contents) in &mut clones {
+            clone.check_invariants().unwrap();
+            assert_eq!(&clone.to_vec(), contents, "t = {}", t);
+            assert_eq!(clone.len(), contents.len());
+        }
+
+        let (mut clone, contents) = clones.pop().unwrap();
+
+        for key in contents.iter().step_by(2) {
+            assert!(clone.delete(key));
+        }
+
+        clone.insert(u64::MAX);
+        clone.check_invariants().unwrap();
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.to_vec(), model, "mutating clone changed original, t = {}", t);
+        assert!(clones.iter().all(|(clone, contents)| &clone.to_vec() == contents), "t = {}", t);
+    }
+}
+
+/**
+ * descending order, zero-sized, so tree with it is as big as one with Ord of keys
+ */
+#[derive(Clone, Copy)]
+struct Descending;
+
+impl Comparator<u64> for Descending {
+    fn compare(&self, a: &u64, b: &u64) -> std::cmp::Ordering {
+        b.cmp(a)
+    }
+}
+
+#[test]
+fn reverse_comparator_orders_tree_descending() {
+    assert_eq!(std::mem::size_of::<BTree<u64, Descending>>(), std::mem::size_of::<BTree<u64>>());
+
+    for t in [2, 3, 8] {
+        let mut tree = BTree::new_with_comparator(t, Descending);
+        let mut model = std::collections::BTreeSet::new();
+        let mut next = lcg(t as u64);
+
+        for i in 0..10_000u32 {
+            let key = next() % 2000;
+
+            if next().is_multiple_of(3) {
+                assert_eq!(tree.delete(&key), model.remove(&key), "t = {}, key = {}", t, key);
+            } else if model.insert(key) {
+                tree.insert(key);
+            }
+
+            if i.is_multiple_of(1000) {
+                tree.check_invariants().unwrap();
+            }
+        }
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.to_vec(), model.iter().rev().copied().collect::<Vec<_>>(), "t = {}", t);
+        assert!(tree.iter().copied().eq(model.iter().rev().copied()));
+        assert!(model.iter().all(|key| tree.contains(*key)));
+        assert!(!tree.contains(2000));
+
+        let mut low = BTree::new_with_comparator(t, Descending);
+
+        for key in (2000..2100).rev() {
+            low.insert(key);
+        }
+
+        low.append(&mut tree);
+
+        assert!(tree.is_empty());
+        low.check_invariants().unwrap();
+        assert_eq!(low.to_vec(), (2000..2100).rev().chain(model.iter().rev().copied()).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn case_insensitive_comparator_finds_keys_in_any_case() {
+    let ignore_case = |a: &String, b: &String| a.to_lowercase().cmp(&b.to_lowercase());
+    let mut tree = BTree::new_with_comparator(2, ignore_case);
+
+    for word in ["banana", "Apple", "cherry", "DATE", "elderberry", "Fig", "grape"] {
+        tree.insert(word.to_string());
+    }
+
+    tree.check_invariants().unwrap();
+    assert_eq!(tree.to_vec(), ["Apple", "banana", "cherry", "DATE", "elderberry", "Fig", "grape"]);
+    assert!(tree.contains("APPLE".to_string()));
+    assert!(tree.contains("date".to_string()));
+    assert!(!tree.contains("apples".to_string()));
+    assert_eq!(tree.find(|key| key.to_lowercase().as_str().cmp("fig")), Some(&"Fig".to_string()));
+
+    assert!(tree.delete(&"BANANA".to_string()));
+    assert!(tree.delete(&"Grape".to_string()));
+    assert!(!tree.delete(&"banana".to_string()));
+
+    tree.check_invariants().unwrap();
+    assert_eq!(tree.to_vec(), ["Apple", "cherry", "DATE", "elderberry", "Fig"]);
+
+    let bytewise = BTree::from(std::collections::BTreeSet::from(["b".to_string(), "B".to_string(), "a".to_string()]));
+
+    assert_eq!(bytewise.to_vec(), ["B", "a", "b"], "the same key type in natural order of another tree");
+}
+
+/**
+ * set goes to tree, to set and to vec and back again, with nothing lost or reordered on the way
+ */
+#[test]
+fn conversions_round_trip_through_std_collections() {
+    use std::collections::BTreeSet;
+
+    for n in [0, 1, 100, 10_000u64] {
+        let set: BTreeSet<u64> = (0..n).map(|i| i * 3).collect();
+        let tree = BTree::from(set.clone());
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.len(), set.len());
+        assert!(tree.iter().eq(set.iter()), "n = {}", n);
+
+        let back = BTreeSet::from(tree.clone());
+
+        assert_eq!(back, set, "n = {}", n);
+
+        let keys = Vec::from(tree);
+
+        assert_eq!(keys, set.iter().copied().collect::<Vec<_>>());
+
+        let tree = BTree::from(keys.into_iter().collect::<BTreeSet<_>>());
+
+        tree.check_invariants().unwrap();
+        assert_eq!(BTreeSet::from(tree), set, "set, tree, vec, set, tree and set again, n = {}", n);
+    }
+}
+
+/**
+ * Vec keeps every duplicate in order of tree, BTreeSet keeps one of equal keys
+ */
+#[test]
+fn conversions_keep_duplicates_as_documented() {
+    use std::collections::BTreeSet;
+
+    let mut tree = BTree::new(2);
+    let mut next = lcg(7);
+    let mut model = vec![];
+
+    for _ in 0..2000 {
+        let key = next() % 100;
+
+        tree.insert(key);
+        model.push(key);
+    }
+
+    model.sort();
+
+    assert_eq!(BTreeSet::from(tree.clone()), model.iter().copied().collect::<BTreeSet<_>>());
+    assert_eq!(Vec::from(tree), model);
+
+    let mut descending = BTree::new_with_comparator(3, Descending);
+
+    for key in [5, 1, 5, 3, 1] {
+        descending.insert(key);
+    }
+
+    assert_eq!(Vec::from(descending), [5, 5, 3, 1, 1], "order of comparator is kept");
+}
+
+/**
+ * self test of every t from 2 to 8 and some wide ones over several seeds and lengths of script,
+ * failure shows its minimized repro
+ */
+#[cfg(feature = "std")]
+#[test]
+fn self_test_passes_for_t_and_seeds() {
+    for t in (2..=8).chain([16, 64]) {
+        for seed in 0..6 {
+            for n_ops in [50, 3000] {
+                if let Err(failure) = BTree::self_test(t, seed * 7919 + t as u64, n_ops) {
+                    panic!("{}", failure);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+#![cfg(feature = "std")]
+
+use srdb::{Error, MemStorage, PageCache, Pager, SrdbOptions, MIN_CACHE_PAGES, PAGE_SIZE};
+
+/**
+ * bytes of page left to caller, the rest is checksum, see Pager::page_size
+ */
+const DATA: usize = PAGE_SIZE - 4;
+
+fn page(page_id: u32, version: u8) -> Vec<u8> {
+    let mut data = vec![version; DATA];
+
+    data[..4].copy_from_slice(&page_id.to_le_bytes());
+
+    data
+}
+
+/**
+ * cache of 4 pages over 64 pages written and read in scattered order, every access may evict
+ */
+fn small_cache() -> PageCache {
+    let mut cache = PageCache::new(Pager::create_with_storage(Box::new(MemStorage::new())).unwrap(), 4);
+
+    assert_eq!(cache.page_size(), DATA);
+
+    for page_id in 1..64 {
+        assert_eq!(cache.allocate_page().unwrap(), page_id);
+
+        cache.write(page_id, &page(page_id, 0)).unwrap();
+    }
+
+    cache
+}
+
+#[test]
+fn pages_survive_constant_eviction() {
+    let mut cache = small_cache();
+    let mut versions = [0u8; 64];
+    let mut state = 118u64;
+
+    for _ in 0..5000 {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+
+        let page_id = 1 + (state >> 33) as u32 % 63;
+
+        if state >> 63 == 1 {
+            versions[page_id as usize] = versions[page_id as usize].wrapping_add(1);
+
+            cache.write(page_id, &page(page_id, versions[page_id as usize])).unwrap();
+        } else {
+            let expected = page(page_id, versions[page_id as usize]);
+
+            assert_eq!(cache.read(page_id).unwrap(), expected, "page {}", page_id);
+        }
+
+        assert!(cache.dirty_pages() <= 4);
+    }
+
+    assert!(cache.misses() > 2000, "{} misses", cache.misses());
+
+    cache.flush().unwrap();
+    assert_eq!(cache.dirty_pages(), 0);
+
+    for page_id in 1..64 {
+        let expected = page(page_id, versions[page_id as usize]);
+
+        assert_eq!(cache.read(page_id).unwrap(), expected, "page {} after flush", page_id);
+    }
+}
+
+#[test]
+fn recent_pages_hit() {
+    let mut cache = small_cache();
+
+    cache.flush().unwrap();
+
+    for page_id in [1, 2, 3, 4] {
+        cache.read(page_id).unwrap();
+    }
+
+    let (hits, misses) = (cache.hits(), cache.misses());
+
+    for page_id in [4, 3, 2, 1, 1, 4] {
+        cache.read(page_id).unwrap();
+    }
+
+    assert_eq!((cache.hits() - hits, cache.misses() - misses), (6, 0));
+
+    cache.read(5).unwrap();
+    cache.read(2).unwrap();
+    cache.read(1).unwrap();
+
+    assert_eq!((cache.hits() - hits, cache.misses() - misses), (8, 1), "page 5 takes place of least recent page 3");
+
+    cache.read(3).unwrap();
+    cache.read(5).unwrap();
+    cache.read(4).unwrap();
+
+    assert_eq!((cache.hits() - hits, cache.misses() - misses), (9, 3), "page 3 takes place of page 4");
+}
+
+#[test]
+fn pinned_pages_are_not_evicted() {
+    let mut cache = small_cache();
+
+    for page_id in [10, 20, 30] {
+        cache.pin(page_id).unwrap();
+    }
+
+    for page_id in 1..64 {
+        if ![10, 20, 30].contains(&page_id) {
+            cache.write(page_id, &page(page_id, 7)).unwrap();
+        }
+    }
+
+    let misses = cache.misses();
+
+    for page_id in [10, 20, 30] {
+        assert_eq!(cache.read(page_id).unwrap(), page(page_id, 0));
+    }
+
+    assert_eq!(cache.misses(), misses, "pinned pages are served from cache");
+    assert!(matches!(cache.free_page(20), Err(Error::PagePinned(20))));
+
+    cache.pin(40).unwrap();
+
+    assert!(matches!(cache.read(41), Err(Error::CacheFull(4))), "all frames are pinned");
+
+    cache.pin(40).unwrap();
+    cache.unpin(40);
+
+    assert!(cache.read(41).is_err(), "pins are counted");
+
+    cache.unpin(40);
+
+    assert_eq!(cache.read(41).unwrap(), page(41, 7));
+
+    for page_id in [10, 20, 30] {
+        cache.unpin(page_id);
+    }
+
+    cache.free_page(20).unwrap();
+}
+
+#[test]
+fn without_steal_dirty_pages_wait_for_flush() {
+    let mut cache = small_cache();
+
+    cache.flush().unwrap();
+    cache.set_steal(false);
+
+    for page_id in 1..=4 {
+        cache.write(page_id, &page(page_id, 3)).unwrap();
+    }
+
+    assert!(matches!(cache.read(5), Err(Error::CacheFull(4))));
+    assert_eq!(cache.flush_pages(2).unwrap(), 2);
+    assert_eq!(cache.read(5).unwrap(), page(5, 0));
+}
+
+/**
+ * tree many times bigger than cache of the smallest allowed size
+ */
+#[test]
+fn database_works_through_smallest_cache() {
+    let mut db = SrdbOptions::new()
+        .page_size(1024)
+        .cache_pages(MIN_CACHE_PAGES)
+        .create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new()))
+        .unwrap();
+
+    for i in 0..8_000u32 {
+        db.insert(&(i * 7919 % 8_000).to_be_bytes(), &i.to_le_bytes()).unwrap();
+    }
+
+    for i in (0..8_000u32).step_by(3) {
+        assert!(db.delete(&i.to_be_bytes()).unwrap());
+    }
+
+    for i in 0..8_000u32 {
+        assert_eq!(db.contains(&i.to_be_bytes()).unwrap(), i % 3 != 0, "key {}", i);
+    }
+
+    db.check_invariants().unwrap();
+}
@@ -0,0 +1,146 @@
+#![cfg(feature = "std")]
+
+use srdb::{Db, Error, MemStorage, Storage, PAGE_SIZE};
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn value(i: u32) -> Vec<u8> {
+    format!("value of key {}", i).into_bytes()
+}
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+fn entries() -> Vec<(Vec<u8>, Vec<u8>)> {
+    (0..3000).map(|i| (key(i), value(i))).collect()
+}
+
+/**
+ * closed database of entries, log is empty, so everything is read from pages
+ */
+fn database() -> MemStorage {
+    let storage = MemStorage::new();
+    let mut db = Db::create_with_storage(Box::new(storage.clone()), Box::new(MemStorage::new())).unwrap();
+
+    for (key, value) in entries() {
+        db.insert(&key, &value).unwrap();
+    }
+
+    db.close().unwrap();
+
+    storage
+}
+
+/**
+ * copy of file with one bit flipped at offset
+ */
+fn flipped(file: &[u8], offset: usize, bit: u32) -> MemStorage {
+    let mut storage = MemStorage::new();
+    let mut bytes = file.to_vec();
+
+    bytes[offset] ^= 1 << bit;
+    storage.write_at(0, &bytes).unwrap();
+
+    storage
+}
+
+/**
+ * flip anywhere past superblocks either fails loudly or hits page nothing references,
+ * reads never return changed entries
+ */
+#[test]
+fn flipped_bits_fail_reads_instead_of_returning_garbage() {
+    let file = database().to_vec();
+    let expected = entries();
+    let mut next = lcg(7);
+    let mut mismatches = 0;
+
+    for _ in 0..100 {
+        let offset = 2 * PAGE_SIZE + next() as usize % (file.len() - 2 * PAGE_SIZE);
+        let storage = flipped(&file, offset, (next() % 8) as u32);
+
+        match Db::open_with_storage(Box::new(storage), Box::new(MemStorage::new())) {
+            Err(Error::Corrupt(reason)) => assert!(reason.contains("fails checksum"), "offset {}: {}", offset, reason),
+            Err(error) => panic!("offset {}: {:?}", offset, error),
+            Ok(mut db) => match db.to_vec() {
+                Ok(entries) => assert!(entries == expected, "offset {}: entries are changed", offset),
+                Err(Error::ChecksumMismatch { page_id }) => {
+                    assert!(page_id < db.page_count(), "offset {}", offset);
+                    mismatches += 1;
+                }
+                Err(error) => panic!("offset {}: {:?}", offset, error),
+            },
+        }
+    }
+
+    assert!(mismatches > 50, "{} of 100 flips are caught by page checksum", mismatches);
+}
+
+#[test]
+fn every_bit_of_page_is_covered() {
+    let file = database().to_vec();
+    let expected = entries();
+    let page = (2..file.len() / PAGE_SIZE)
+        .find(|&page| {
+            let storage = flipped(&file, page * PAGE_SIZE, 0);
+            let mut db = Db::open_with_storage(Box::new(storage), Box::new(MemStorage::new())).unwrap();
+
+            db.to_vec().is_err()
+        })
+        .unwrap();
+
+    for offset in (page * PAGE_SIZE..(page + 1) * PAGE_SIZE).step_by(97) {
+        for bit in [0, 7] {
+            let storage = flipped(&file, offset, bit);
+            let mut db = Db::open_with_storage(Box::new(storage), Box::new(MemStorage::new())).unwrap();
+
+            assert!(matches!(db.to_vec(), Err(Error::ChecksumMismatch { .. })), "offset {}, bit {}", offset, bit);
+        }
+    }
+
+    let mut db = Db::open_with_storage(Box::new(flipped(&file, 0, 0)), Box::new(MemStorage::new())).unwrap();
+
+    assert!(db.to_vec().unwrap() == expected, "flip in older superblock slot leaves the other one");
+}
+
+#[test]
+fn unverified_open_reads_damaged_pages() {
+    let path = std::env::temp_dir().join(format!("srdb-checksum-{}", std::process::id()));
+
+    let _ = Db::remove(&path);
+
+    let mut db = Db::create(&path).unwrap();
+
+    for (key, value) in entries() {
+        db.insert(&key, &value).unwrap();
+    }
+
+    db.close().unwrap();
+
+    let file = std::fs::read(&path).unwrap();
+    let page = (2..file.len() / PAGE_SIZE)
+        .find(|&page| {
+            let storage = flipped(&file, page * PAGE_SIZE + PAGE_SIZE / 2, 0);
+            let mut db = Db::open_with_storage(Box::new(storage), Box::new(MemStorage::new())).unwrap();
+
+            db.to_vec().is_err()
+        })
+        .unwrap();
+    let mut damaged = file.clone();
+
+    damaged[page * PAGE_SIZE + PAGE_SIZE - 1] ^= 0xff;
+    std::fs::write(&path, damaged).unwrap();
+
+    assert!(matches!(Db::open_read_only(&path).unwrap().to_vec(), Err(Error::ChecksumMismatch { .. })));
+    assert!(Db::open_unverified(&path).unwrap().to_vec().unwrap() == entries(), "only trailer is damaged");
+
+    Db::remove(&path).unwrap();
+}
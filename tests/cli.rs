@@ -0,0 +1,698 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use serde_json::{json, Value};
+use srdb::{Db, Pager, HEADER_PAGE};
+
+use common::temp_path;
+
+fn srdb(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_srdb")).args(args).output().unwrap()
+}
+
+/**
+ * command with input written to its stdin
+ */
+fn srdb_with_stdin(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_srdb"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/**
+ * stdout of command which must succeed, parsed as json
+ */
+fn json_of(args: &[&str]) -> Value {
+    let output = srdb(args);
+
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+fn database(name: &str) -> (PathBuf, String) {
+    let path = temp_path(name);
+    let file = path.to_str().unwrap().to_string();
+
+    for (key, value) in [("apple", "red"), ("apricot", "orange"), ("banana", "yellow")] {
+        assert!(srdb(&["put", &file, key, value]).status.success());
+    }
+
+    (path, file)
+}
+
+#[test]
+fn exit_codes_tell_failures_apart() {
+    let (path, file) = database("exit");
+
+    assert_eq!(srdb(&["get", &file, "apple"]).status.code(), Some(0));
+    assert_eq!(srdb(&["get", &file, "cherry"]).status.code(), Some(1), "key not found");
+    assert_eq!(srdb(&["del", &file, "cherry"]).status.code(), Some(1), "key not found");
+
+    let missing = temp_path("missing").join("db");
+
+    assert_eq!(srdb(&["get", missing.to_str().unwrap(), "apple"]).status.code(), Some(3), "i/o error");
+    assert_eq!(srdb(&["stats", std::env::temp_dir().to_str().unwrap()]).status.code(), Some(3), "i/o error");
+
+    assert_eq!(srdb(&["get", &file]).status.code(), Some(2), "usage error");
+    assert_eq!(srdb(&["scan", &file, "--limit", "many"]).status.code(), Some(2), "usage error");
+    assert_eq!(srdb(&["verify", &file, "--in-place"]).status.code(), Some(2), "usage error");
+    assert_eq!(srdb(&["frobnicate", &file]).status.code(), Some(2), "usage error");
+
+    let not_found = srdb(&["get", &file, "cherry"]);
+
+    assert!(not_found.stdout.is_empty());
+    assert_eq!(String::from_utf8_lossy(&not_found.stderr).trim(), "srdb: key cherry not found");
+
+    Db::remove(&path).unwrap();
+}
+
+#[test]
+fn json_output_of_commands() {
+    let (path, file) = database("json");
+
+    assert_eq!(json_of(&["put", &file, "cherry", "dark red", "--json"]), json!({"key": "cherry"}));
+    assert_eq!(json_of(&["get", &file, "cherry", "--json"]), json!({"key": "cherry", "value": "dark red"}));
+    assert_eq!(json_of(&["del", &file, "cherry", "--json"]), json!({"key": "cherry"}));
+
+    assert_eq!(
+        json_of(&["scan", &file, "--prefix", "ap", "--json"]),
+        json!([{"key": "apple", "value": "red"}, {"key": "apricot", "value": "orange"}])
+    );
+    assert_eq!(json_of(&["scan", &file, "--limit", "1", "--json"]), json!([{"key": "apple", "value": "red"}]));
+
+    let stats = json_of(&["stats", &file, "--json"]);
+
+    assert_eq!(stats["entries"], 3);
+    assert_eq!(stats["tables"], json!([]));
+    assert!(stats["page_size"].as_u64().unwrap() > 0);
+
+    let verified = json_of(&["verify", &file, "--full", "--json"]);
+
+    assert_eq!(verified["ok"], true);
+    assert_eq!(verified["entries"], 3);
+    assert_eq!(verified["problems"], json!([]));
+
+    let dump = temp_path("json-dump");
+    let restored = temp_path("json-restored");
+    let dumped = json_of(&["dump", &file, dump.to_str().unwrap(), "--json"]);
+
+    assert_eq!(dumped["entries"], 3);
+    assert_eq!(dumped["bytes"], std::fs::metadata(&dump).unwrap().len());
+    assert_eq!(
+        json_of(&["restore", restored.to_str().unwrap(), dump.to_str().unwrap(), "--verify", "--json"]),
+        json!({"entries": 3, "tables": 0, "problems": []})
+    );
+    assert!(json_of(&["vacuum", &file, "--json"])["shrunk_bytes"].is_u64());
+
+    std::fs::remove_file(&dump).unwrap();
+    Db::remove(&restored).unwrap();
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * closed database of 5000 entries with pages damaged through pager, checksums of pages stay valid
+ */
+fn damaged(name: &str, damage: impl FnOnce(&mut Pager)) -> (PathBuf, String) {
+    let path = temp_path(name);
+    let mut db = Db::create(&path).unwrap();
+
+    for i in 0..5000 {
+        db.insert(format!("key{:06}", i).as_bytes(), b"value").unwrap();
+    }
+
+    db.close().unwrap();
+
+    let mut pager = Pager::open(&path).unwrap();
+
+    damage(&mut pager);
+    pager.commit(true).unwrap();
+
+    let file = path.to_str().unwrap().to_string();
+
+    (path, file)
+}
+
+#[test]
+fn verify_lists_problems_and_repairs_only_in_place() {
+    let (path, file) = damaged("leaked", |pager| {
+        for _ in 0..3 {
+            let page_id = pager.allocate_page().unwrap();
+
+            pager.write_page(page_id, &vec![0; pager.page_size()]).unwrap();
+        }
+    });
+    let before = std::fs::read(&path).unwrap();
+    let found = srdb(&["verify", &file, "--full"]);
+    let stdout = String::from_utf8_lossy(&found.stdout);
+
+    assert_eq!(found.status.code(), Some(4), "problems found");
+    assert_eq!(stdout.lines().filter(|line| line.contains("neither reachable nor free")).count(), 3, "{}", stdout);
+    assert!(stdout.lines().any(|line| line.trim_start().starts_with("page ")), "{}", stdout);
+
+    assert_eq!(srdb(&["verify", &file, "--repair"]).status.code(), Some(4));
+    assert!(std::fs::read(&path).unwrap() == before, "file is not written without --in-place");
+
+    let repaired = json_of(&["verify", &file, "--repair", "--in-place", "--json"]);
+
+    assert_eq!(repaired["ok"], true);
+    assert_eq!(repaired["repairs"], json!(["rebuild free list"]));
+    assert_eq!(repaired["remaining"], json!([]));
+    assert_eq!(srdb(&["verify", &file, "--full"]).status.code(), Some(0));
+
+    Db::remove(&path).unwrap();
+}
+
+#[test]
+fn verify_salvages_readable_entries_of_damaged_tree() {
+    let (path, file) = damaged("salvage", |pager| {
+        let mut page = vec![0; pager.page_size()];
+        let leaf = (HEADER_PAGE + 1..pager.page_count())
+            .find(|&page_id| {
+                pager.read_page(page_id, &mut page).unwrap();
+
+                page[0] == 1
+            })
+            .unwrap();
+
+        pager.write_page(leaf, &vec![1; pager.page_size()]).unwrap();
+    });
+    let before = std::fs::read(&path).unwrap();
+    let out = temp_path("salvaged");
+    let output = srdb(&["verify", &file, "--salvage", out.to_str().unwrap(), "--json"]);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let salvaged = report["salvage"]["entries"].as_u64().unwrap();
+
+    assert_eq!(output.status.code(), Some(4), "original still has problems");
+    assert_eq!(report["repairs"], json!(["salvage"]));
+    assert!(salvaged > 4500 && salvaged < 5000, "{}", report);
+    assert!(!report["salvage"]["problems"].as_array().unwrap().is_empty());
+    assert!(std::fs::read(&path).unwrap() == before, "salvage only reads original");
+
+    let copy = json_of(&["verify", out.to_str().unwrap(), "--full", "--json"]);
+
+    assert_eq!(copy["ok"], true);
+    assert_eq!(copy["entries"], salvaged);
+
+    Db::remove(&out).unwrap();
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * config file in directory of its own, removed with it by caller
+ */
+fn config_file(name: &str, text: &str) -> (PathBuf, String) {
+    let dir = temp_path(name);
+
+    let _ = std::fs::remove_dir_all(&dir);
+
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("srdb.toml"), text).unwrap();
+
+    let file = dir.join("srdb.toml").to_str().unwrap().to_string();
+
+    (dir, file)
+}
+
+const CONFIG: &str = r#"# served database
+db = "data.srdb"
+bind = "0.0.0.0:9000"
+cache_pages = 4_096
+sync = "always"   # every write
+read_only = false
+max_connections = 8
+idle_timeout = 30
+log_level = "warn"
+protocol = "resp"
+"#;
+
+#[test]
+fn config_file_is_overridden_by_flags() {
+    let (dir, config) = config_file("config", CONFIG);
+    let db = dir.join("data.srdb").to_str().unwrap().to_string();
+
+    assert_eq!(
+        json_of(&["config-check", "--config", &config, "--json"]),
+        json!({
+            "db": db, "bind": "0.0.0.0:9000", "cache_pages": 4096, "sync": "always", "read_only": false,
+            "max_connections": 8, "idle_timeout": 30, "log_level": "warn", "protocol": "resp",
+            "replication_bind": "", "replication_backlog": 64 << 20
+        }),
+        "relative db is taken from directory of config"
+    );
+
+    let flags = [
+        "config-check", "other.srdb", "--config", &config, "--bind", "127.0.0.1:1", "--cache-pages", "512", "--sync",
+        "off", "--read-only", "--max-connections", "2", "--log-level", "error", "--protocol", "text", "--json",
+    ];
+
+    assert_eq!(
+        json_of(&flags),
+        json!({
+            "db": "other.srdb", "bind": "127.0.0.1:1", "cache_pages": 512, "sync": "off", "read_only": true,
+            "max_connections": 2, "idle_timeout": 30, "log_level": "error", "protocol": "text",
+            "replication_bind": "", "replication_backlog": 64 << 20
+        }),
+        "every flag wins over file, settings without flag are kept"
+    );
+    assert_eq!(json_of(&["config-check", "x", "--json"])["bind"], "127.0.0.1:7878", "defaults without file");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_config_names_line_and_key() {
+    let cases = [
+        ("db = \"a\"\ncolour = \"blue\"\n", "2: unknown key colour"),
+        ("db = \"a\"\ndb = \"b\"\n", "2: key db is given twice"),
+        ("cache_pages = \"many\"\n", "1: cache_pages must be non-negative integer"),
+        ("read_only = 1\n", "1: read_only must be true or false"),
+        ("sync = \"sometimes\"\n", "1: unknown sync mode sometimes, expected always, commit or off"),
+        ("[server]\n", "1: tables are not supported, keys go at top level"),
+        ("db \"a\"\n", "1: expected key = value"),
+        ("db = \"a\n", "1: string is not closed"),
+        ("db = \"a\"\nbind = \"localhost\"\n", "bind address localhost is not host:port"),
+        ("db = \"a\"\ncache_pages = 1\n", "cache of 1 pages is smaller than"),
+    ];
+
+    for (i, (text, message)) in cases.into_iter().enumerate() {
+        let (dir, config) = config_file(&format!("invalid-{}", i), text);
+        let output = srdb(&["config-check", "--config", &config]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert_eq!(output.status.code(), Some(2), "{:?}: {}", text, stderr);
+        assert!(stderr.contains(message), "{:?}: {}", text, stderr);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    assert_eq!(srdb(&["config-check", "--json"]).status.code(), Some(2), "no database at all");
+}
+
+/**
+ * file of import input in temp dir, removed by caller
+ */
+fn input_file(name: &str, text: &str) -> (PathBuf, String) {
+    let path = temp_path(name);
+
+    std::fs::write(&path, text).unwrap();
+
+    let file = path.to_str().unwrap().to_string();
+
+    (path, file)
+}
+
+fn entries(file: &str) -> Value {
+    json_of(&["scan", file, "--json"])
+}
+
+#[test]
+fn import_parses_lines_and_tsv_and_reports_bad_lines() {
+    let (lines, lines_file) = input_file("import-lines", "banana\napple\n\ncherry\r\n");
+    let path = temp_path("import-lines-db");
+    let file = path.to_str().unwrap();
+
+    assert_eq!(
+        json_of(&["import", &lines_file, file, "--json"]),
+        json!({
+            "lines": 4, "rows": 3, "duplicates": 0, "error_count": 1,
+            "errors": [{"line": 3, "message": "key is empty"}]
+        })
+    );
+    assert_eq!(
+        entries(file),
+        json!([{"key": "apple", "value": ""}, {"key": "banana", "value": ""}, {"key": "cherry", "value": ""}])
+    );
+
+    let (tsv, tsv_file) = input_file("import-tsv", "k1\tv1\nno tab\nk2\tv2\twith tab\n\tempty key\nk3\t\n");
+    let path_tsv = temp_path("import-tsv-db");
+    let file_tsv = path_tsv.to_str().unwrap();
+
+    assert_eq!(
+        json_of(&["import", &tsv_file, file_tsv, "--format", "tsv", "--json"]),
+        json!({
+            "lines": 5, "rows": 3, "duplicates": 0, "error_count": 2,
+            "errors": [{"line": 2, "message": "no tab between key and value"}, {"line": 4, "message": "key is empty"}]
+        })
+    );
+    assert_eq!(
+        entries(file_tsv),
+        json!([{"key": "k1", "value": "v1"}, {"key": "k2", "value": "v2\twith tab"}, {"key": "k3", "value": ""}])
+    );
+
+    let text = String::from_utf8(srdb(&["import", &tsv_file, file, "--format", "tsv"]).stdout).unwrap();
+
+    assert_eq!(
+        text,
+        "5 lines read, 3 rows imported, 0 duplicates skipped, 2 parse errors\n\
+         line 2: no tab between key and value\nline 4: key is empty\n"
+    );
+    assert_eq!(srdb(&["import", &tsv_file, file, "--format", "csv"]).status.code(), Some(2));
+
+    std::fs::remove_file(&lines).unwrap();
+    std::fs::remove_file(&tsv).unwrap();
+    Db::remove(&path).unwrap();
+    Db::remove(&path_tsv).unwrap();
+}
+
+/**
+ * keys already in database and keys repeated in input are skipped, inside one batch and across batches
+ */
+#[test]
+fn import_skips_duplicates_from_stdin() {
+    let (path, file) = database("import-duplicates");
+    let input = b"cherry\napple\ncherry\ndate\ndate\nbanana\n";
+
+    for (batch, expected) in [("100", 2), ("1", 0)] {
+        let output = srdb_with_stdin(&["import", "-", &file, "--batch", batch, "--json"], input);
+        let summary: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(summary["rows"], expected, "batch {}", batch);
+        assert_eq!(summary["duplicates"], 6 - expected, "batch {}", batch);
+    }
+
+    let keys: Vec<Value> = entries(&file).as_array().unwrap().iter().map(|entry| entry["key"].clone()).collect();
+
+    assert_eq!(keys, ["apple", "apricot", "banana", "cherry", "date"]);
+    assert_eq!(json_of(&["get", &file, "apple", "--json"])["value"], "red", "existing value is kept");
+
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * --sorted bulk loads empty database, counts repeated keys and bad lines like batched import
+ */
+#[test]
+fn import_sorted_bulk_loads_empty_database() {
+    let (input, input_file_name) = input_file("import-sorted", "a\tone\nb\ttwo\nb\tagain\nbad\nc\tthree\n");
+    let path = temp_path("import-sorted-db");
+    let file = path.to_str().unwrap();
+    let args = ["import", &input_file_name, file, "--sorted", "--format", "tsv", "--json"];
+
+    assert_eq!(
+        json_of(&args),
+        json!({
+            "lines": 5, "rows": 3, "duplicates": 1, "error_count": 1,
+            "errors": [{"line": 4, "message": "no tab between key and value"}]
+        })
+    );
+    assert_eq!(
+        entries(file),
+        json!([{"key": "a", "value": "one"}, {"key": "b", "value": "two"}, {"key": "c", "value": "three"}])
+    );
+    assert_eq!(json_of(&["verify", file, "--full", "--json"])["ok"], true);
+    assert_eq!(srdb(&args).status.code(), Some(3), "database is not empty");
+
+    let (unsorted, unsorted_file) = input_file("import-unsorted", "b\na\n");
+    let empty = temp_path("import-unsorted-db");
+    let empty_file = empty.to_str().unwrap();
+
+    assert_eq!(srdb(&["import", &unsorted_file, empty_file, "--sorted"]).status.code(), Some(3));
+    assert_eq!(entries(empty_file), json!([]), "failed load leaves database empty");
+
+    assert_eq!(srdb_with_stdin(&["import", "-", file, "--sorted"], b"d\n").status.code(), Some(2));
+    assert_eq!(srdb(&["import", &input_file_name, file, "--sorted", "--skip", "1"]).status.code(), Some(2));
+
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&unsorted).unwrap();
+    Db::remove(&path).unwrap();
+    Db::remove(&empty).unwrap();
+}
+
+/**
+ * import stopped after line 4 is resumed with --skip 4, lines are numbered from the start of input
+ */
+#[test]
+fn import_resumes_after_skipped_lines() {
+    let text: String = (1..=8).map(|i| if i == 6 { "\n".to_string() } else { format!("key{}\n", i) }).collect();
+    let (input, input_file_name) = input_file("import-resume", &text);
+    let path = temp_path("import-resume-db");
+    let file = path.to_str().unwrap();
+    let first: String = text.lines().take(4).map(|line| format!("{}\n", line)).collect();
+
+    assert_eq!(srdb_with_stdin(&["import", "-", file, "--json"], first.as_bytes()).status.code(), Some(0));
+    assert_eq!(
+        json_of(&["import", &input_file_name, file, "--skip", "4", "--json"]),
+        json!({
+            "lines": 8, "rows": 3, "duplicates": 0, "error_count": 1,
+            "errors": [{"line": 6, "message": "key is empty"}]
+        })
+    );
+
+    let keys: Vec<Value> = entries(file).as_array().unwrap().iter().map(|entry| entry["key"].clone()).collect();
+
+    assert_eq!(keys, ["key1", "key2", "key3", "key4", "key5", "key7", "key8"]);
+
+    std::fs::remove_file(&input).unwrap();
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * closed database of default tree and tables users, orders and logs, made through library
+ */
+fn with_tables(name: &str) -> (PathBuf, String) {
+    let path = temp_path(name);
+    let mut db = Db::create(&path).unwrap();
+
+    db.insert(b"default", b"entry").unwrap();
+
+    for (table, count) in [("users", 3), ("orders", 5), ("logs", 2)] {
+        db.create_table(table).unwrap();
+
+        for i in 0..count {
+            db.table(table).unwrap().insert(format!("{}{}", table, i).as_bytes(), b"value").unwrap();
+        }
+    }
+
+    db.close().unwrap();
+
+    let file = path.to_str().unwrap().to_string();
+
+    (path, file)
+}
+
+type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/**
+ * default tree and every table as entries, tables in name order
+ */
+fn contents(path: &Path) -> Vec<(String, Entries)> {
+    let mut db = Db::open(path).unwrap();
+    let mut trees = vec![(String::new(), db.to_vec().unwrap())];
+
+    for name in db.tables().unwrap() {
+        let entries = db.table(&name).unwrap().to_vec().unwrap();
+
+        trees.push((name, entries));
+    }
+
+    trees
+}
+
+#[test]
+fn dump_streams_to_stdout_and_restore_from_stdin() {
+    let (path, file) = with_tables("stream");
+    let dumped = srdb(&["dump", &file]);
+
+    assert!(dumped.status.success(), "{}", String::from_utf8_lossy(&dumped.stderr));
+    assert!(!dumped.stdout.is_empty());
+
+    let restored = temp_path("stream-restored");
+    let output = srdb_with_stdin(&["restore", restored.to_str().unwrap(), "--verify", "--json"], &dumped.stdout);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(
+        serde_json::from_slice::<Value>(&output.stdout).unwrap(),
+        json!({"entries": 11, "tables": 3, "problems": []})
+    );
+    assert_eq!(contents(&restored), contents(&path));
+
+    Db::remove(&restored).unwrap();
+    Db::remove(&path).unwrap();
+}
+
+#[test]
+fn dump_and_restore_only_listed_tables() {
+    let (path, file) = with_tables("tables");
+    let partial = temp_path("tables-partial");
+    let full = temp_path("tables-full");
+
+    assert_eq!(json_of(&["dump", &file, partial.to_str().unwrap(), "--table", "users,orders", "--json"])["entries"], 8);
+    assert!(srdb(&["dump", &file, full.to_str().unwrap()]).status.success());
+
+    let from_partial = temp_path("tables-from-partial");
+
+    assert_eq!(
+        json_of(&["restore", from_partial.to_str().unwrap(), partial.to_str().unwrap(), "--json"]),
+        json!({"entries": 8, "tables": 2})
+    );
+
+    let expected = contents(&path);
+    let restored = contents(&from_partial);
+
+    assert_eq!(restored[0], (String::new(), vec![]), "default tree is dumped empty");
+    assert_eq!(restored[1..], [expected[2].clone(), expected[3].clone()], "orders and users");
+
+    let from_full = temp_path("tables-from-full");
+
+    assert_eq!(
+        json_of(&["restore", from_full.to_str().unwrap(), full.to_str().unwrap(), "--table", "logs", "--json"]),
+        json!({"entries": 2, "tables": 1})
+    );
+    assert_eq!(contents(&from_full), [(String::new(), vec![]), expected[1].clone()]);
+
+    let missing = temp_path("tables-missing");
+
+    assert_eq!(srdb(&["dump", &file, missing.to_str().unwrap(), "--table", "nope"]).status.code(), Some(3));
+
+    for path in [&partial, &full, &missing] {
+        let _ = std::fs::remove_file(path);
+    }
+
+    Db::remove(&from_partial).unwrap();
+    Db::remove(&from_full).unwrap();
+    Db::remove(&path).unwrap();
+}
+
+#[test]
+fn restore_and_dump_refuse_to_overwrite() {
+    let (path, file) = with_tables("overwrite");
+    let (target, target_file) = database("overwrite-target");
+    let dump = temp_path("overwrite-dump");
+    let dump_file = dump.to_str().unwrap();
+
+    assert!(srdb(&["dump", &file, dump_file]).status.success());
+
+    let before = std::fs::read(&target).unwrap();
+    let refused = srdb(&["restore", &target_file, dump_file]);
+
+    assert_eq!(refused.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("exists, --force replaces it"));
+    assert!(std::fs::read(&target).unwrap() == before, "existing database is kept");
+
+    assert!(srdb(&["restore", &target_file, dump_file, "--force"]).status.success());
+    assert_eq!(contents(&target), contents(&path));
+
+    let dumped = std::fs::read(&dump).unwrap();
+
+    assert_eq!(srdb(&["dump", &target_file, dump_file]).status.code(), Some(3), "dump file exists");
+    assert!(std::fs::read(&dump).unwrap() == dumped);
+
+    std::fs::remove_file(&dump).unwrap();
+    Db::remove(&target).unwrap();
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * every workload runs in memory, read workloads preload every key, percentiles do not decrease
+ */
+#[test]
+fn bench_runs_every_workload_in_memory() {
+    for (workload, preloaded) in [("fillrandom", 0), ("fillseq", 0), ("readrandom", 300), ("readwrite", 300)] {
+        let args = ["bench", "--memory", "--workload", workload, "--ops", "300", "--threads", "2", "--json"];
+        let report = json_of(&args);
+        let latencies: Vec<f64> =
+            ["p50_us", "p99_us", "p999_us", "max_us"].iter().map(|field| report[field].as_f64().unwrap()).collect();
+        let entries = report["stats"]["entries"].as_u64().unwrap();
+
+        assert_eq!(report["ops"], 300, "{}", workload);
+        assert_eq!(report["threads"], 2, "{}", workload);
+        assert_eq!(report["preloaded"], preloaded, "{}", workload);
+        assert!(latencies.windows(2).all(|pair| pair[0] <= pair[1]), "{}: {:?}", workload, latencies);
+        assert!(entries > 0 && entries <= 300, "{}: {}", workload, entries);
+
+        if workload == "fillseq" || workload == "readrandom" {
+            assert_eq!(entries, 300, "{}", workload);
+        }
+    }
+
+    assert_eq!(srdb(&["bench", "--memory", "--workload", "scan"]).status.code(), Some(2));
+    assert_eq!(srdb(&["bench", "--memory", "--ops", "1000", "--keysize", "2"]).status.code(), Some(2));
+}
+
+/**
+ * output of every command of script run by repl, split at prompts starting lines, help line printed first is dropped
+ */
+fn repl(args: &[&str], script: &str) -> Vec<String> {
+    let output = srdb_with_stdin(&[&["repl"], args].concat(), script.as_bytes());
+    let mut outputs: Vec<String> = Vec::new();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    for mut line in String::from_utf8(output.stdout).unwrap().split_inclusive('\n').skip(1) {
+        while let Some(rest) = line.strip_prefix("> ") {
+            outputs.push(String::new());
+            line = rest;
+        }
+
+        outputs.last_mut().unwrap().push_str(line);
+    }
+
+    outputs
+}
+
+/**
+ * every change marks changed nodes and reports invariants, unchanged nodes stay plain, lookups print no tree
+ */
+#[test]
+fn repl_watch_marks_changed_nodes_and_checks_invariants() {
+    let script = "insert 1\ninsert 2\ninsert 3\ninsert 4\ninsert 5\ninsert 6\ndelete 6\ncontains 5\nseed 1 3\n";
+    let outputs = repl(&["--watch"], script);
+
+    assert_eq!(outputs.len(), 10);
+    assert_eq!(outputs[0], "[1] *\ninvariants ok\n");
+    assert_eq!(outputs[5], "[3] *\n├── [1, 2] *\n└── [4, 5, 6] *\ninvariants ok\n");
+    assert_eq!(outputs[6], "true\n[3]\n├── [1, 2]\n└── [4, 5] *\ninvariants ok\n");
+    assert_eq!(outputs[7], "true\n");
+    assert_eq!(outputs[8].matches("\nop ").count(), 2);
+    assert!(outputs[8].starts_with("op 0: "), "{}", outputs[8]);
+    assert!(outputs[8].lines().last().unwrap().starts_with("len "), "{}", outputs[8]);
+    assert_eq!(outputs[9], "");
+}
+
+/**
+ * commands print their results, errors are reported and reading goes on, nothing after quit runs
+ */
+#[test]
+fn repl_runs_commands_and_reports_errors() {
+    let script = "insert 5\ninsert 1\ndelete 1\ndelete 1\ncontains 5\ncontains 1\nrange 0 9\nprint\nstats\nclear\n\
+                  print\nfrobnicate\ninsert\ninsert x\nseed 7 20\nclear\nseed 7 20\nhelp\nquit\ninsert 9\nprint\n";
+    let outputs = repl(&[], script);
+    let help = &outputs[17];
+
+    assert_eq!(outputs.len(), 19);
+    assert_eq!(outputs[..8], ["", "", "true\n", "false\n", "true\n", "false\n", "[5]\n", "[5]\n"]);
+    assert!(outputs[8].starts_with("Stats { len: 1, height: 1"), "{}", outputs[8]);
+    assert_eq!(outputs[9..11], ["", "[]\n"]);
+    assert!(outputs[11].starts_with("error: unknown command frobnicate, commands: "), "{}", outputs[11]);
+    assert!(outputs[12].starts_with("error: insert takes 1 arguments, "), "{}", outputs[12]);
+    assert_eq!(outputs[13], "error: x is not a valid key\n");
+    assert!(outputs[14].starts_with("len "), "{}", outputs[14]);
+    assert_eq!(outputs[16], outputs[14]);
+    assert!(help.starts_with("commands: insert <key>") && help.ends_with("help, quit\n"), "{}", help);
+    assert_eq!(outputs[18], "");
+}
+
+/**
+ * with --str keys are strings, with --trace tree is printed after changes only
+ */
+#[test]
+fn repl_takes_string_keys_and_traces_changes() {
+    let outputs = repl(&["--str", "--trace"], "insert b\ninsert a\nrange a b\ncontains a\ninsert 1\n");
+
+    assert_eq!(outputs[..5], ["[\"b\"]\n", "[\"a\", \"b\"]\n", "[a, b]\n", "true\n", "[\"1\", \"a\", \"b\"]\n"]);
+    assert_eq!(repl(&[], "insert b\n")[0], "error: b is not a valid key\n");
+}
@@ -0,0 +1,14 @@
+use std::path::PathBuf;
+
+use srdb::Db;
+
+/**
+ * path in temp dir named after test file, name and process, database left there by a previous run is removed
+ */
+pub fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("srdb-{}-{}-{}", env!("CARGO_CRATE_NAME"), name, std::process::id()));
+
+    let _ = Db::remove(&path);
+
+    path
+}
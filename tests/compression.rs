@@ -0,0 +1,154 @@
+#![cfg(feature = "lz4")]
+
+mod common;
+
+use std::fs;
+
+use srdb::{Db, MemStorage, SrdbOptions, Storage, SyncMode, VerifyMode, PAGE_SIZE};
+
+use common::temp_path;
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * value of kind i % 4 and given length: zeros, text, random bytes, or random runs of repeated bytes
+ */
+fn value(i: u32, len: usize) -> Vec<u8> {
+    let mut next = lcg(i as u64);
+
+    match i % 4 {
+        0 => vec![0; len],
+        1 => {
+            let record = format!("{{\"id\": {}, \"name\": \"user {}\", \"active\": true}}, ", i, i);
+
+            record.bytes().cycle().take(len).collect()
+        }
+        2 => (0..len).map(|_| next() as u8).collect(),
+        _ => {
+            let mut value = Vec::with_capacity(len);
+
+            while value.len() < len {
+                let byte = next() as u8;
+                let run = 1 + next() as usize % 40;
+
+                value.extend(std::iter::repeat_n(byte, run.min(len - value.len())));
+            }
+
+            value
+        }
+    }
+}
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn lengths() -> impl Iterator<Item = (u32, usize)> {
+    let sizes = [0, 10, PAGE_SIZE / 2, PAGE_SIZE, 3 * PAGE_SIZE, 70_000];
+    let mut next = lcg(3);
+
+    (0..400).map(move |i| (i, sizes[i as usize % 6] + next() as usize % 100))
+}
+
+/**
+ * database of values of every kind and length, with compression on or off, file size after close
+ */
+fn filled(compression: bool) -> (MemStorage, u64) {
+    let storage = MemStorage::new();
+    let mut db = SrdbOptions::new()
+        .compression(compression)
+        .sync_mode(SyncMode::Off)
+        .create_with_storage(Box::new(storage.clone()), Box::new(MemStorage::new()))
+        .unwrap();
+
+    for (i, len) in lengths() {
+        db.insert(&key(i), &value(i, len)).unwrap();
+    }
+
+    db.close().unwrap();
+
+    let len = storage.len().unwrap();
+
+    (storage, len)
+}
+
+#[test]
+fn values_round_trip_with_compression() {
+    let (storage, _) = filled(true);
+    let mut db = Db::open_with_storage(Box::new(storage), Box::new(MemStorage::new())).unwrap();
+
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    for (i, len) in lengths() {
+        assert!(db.get(&key(i)).unwrap() == Some(value(i, len)), "key {}, length {}", i, len);
+    }
+
+    for (i, _) in lengths().filter(|(i, _)| i % 3 == 0) {
+        db.insert(&key(i), &value(i + 1, 5 * PAGE_SIZE)).unwrap();
+    }
+
+    for (i, len) in lengths() {
+        let expected = if i % 3 == 0 { value(i + 1, 5 * PAGE_SIZE) } else { value(i, len) };
+
+        assert!(db.get(&key(i)).unwrap() == Some(expected), "key {} after overwrite", i);
+    }
+
+    assert!(db.verify(VerifyMode::Full).is_ok());
+}
+
+#[test]
+fn compressible_values_take_much_less_space() {
+    let (_, compressed) = filled(true);
+    let (_, plain) = filled(false);
+
+    assert!(compressed * 3 < plain, "{} bytes compressed, {} plain", compressed, plain);
+}
+
+/**
+ * unverified open hands damaged compressed blocks to decompression, which fails or returns
+ * some value, but never panics or allocates length it reads from damaged page
+ */
+#[test]
+fn damaged_compressed_values_do_not_panic() {
+    let path = temp_path("damaged");
+    let mut db = SrdbOptions::new().sync_mode(SyncMode::Off).create(&path).unwrap();
+
+    for i in (0..40).map(|i| i * 4 + 1) {
+        db.insert(&key(i), &value(i, 3 * PAGE_SIZE)).unwrap();
+    }
+
+    db.close().unwrap();
+
+    let file = fs::read(&path).unwrap();
+    let mut next = lcg(11);
+
+    for _ in 0..200 {
+        let mut damaged = file.clone();
+
+        for _ in 0..4 {
+            let offset = 2 * PAGE_SIZE + next() as usize % (file.len() - 2 * PAGE_SIZE);
+
+            damaged[offset] = next() as u8;
+        }
+
+        fs::write(&path, &damaged).unwrap();
+
+        if let Ok(db) = Db::open_unverified(&path) {
+            for i in (0..40).map(|i| i * 4 + 1) {
+                if let Ok(Some(found)) = db.get(&key(i)) {
+                    assert!(found.len() <= 255 * file.len());
+                }
+            }
+        }
+    }
+
+    fs::write(&path, &file).unwrap();
+    Db::remove(&path).unwrap();
+}
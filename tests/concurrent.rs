@@ -0,0 +1,171 @@
+#![cfg(feature = "std")]
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+
+use srdb::ConcurrentBTree;
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * writer moves keys in pairs 2k and 2k + 1 with one write_batch, so every version holds both or neither
+ */
+fn assert_pairs(keys: &[u64]) {
+    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]), "keys out of order");
+
+    for key in keys {
+        assert!(keys.binary_search(&(key ^ 1)).is_ok(), "{} is without its pair", key);
+    }
+}
+
+/**
+ * readers spin on contains, range and snapshots while writer churns, every version they see is valid
+ */
+#[test]
+fn readers_spinning_during_writer_churn_see_valid_versions() {
+    let tree = ConcurrentBTree::new(2);
+    let done = AtomicBool::new(false);
+    let reads = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for reader in 0..8 {
+            let tree = tree.clone();
+            let (done, reads) = (&done, &reads);
+
+            scope.spawn(move || {
+                let mut next = lcg(reader);
+
+                while !done.load(Ordering::SeqCst) {
+                    let snapshot = tree.snapshot();
+
+                    snapshot.check_invariants().unwrap();
+                    assert_pairs(&snapshot.to_vec());
+                    assert_eq!(snapshot.len(), snapshot.to_vec().len());
+
+                    let start = next() % 1000;
+                    let range = tree.range(start..start + 50);
+
+                    assert!(range.iter().all(|key| (start..start + 50).contains(key)));
+                    assert!(range.windows(2).all(|pair| pair[0] < pair[1]));
+
+                    let pair = next() % 500 * 2;
+
+                    if snapshot.contains(&pair) {
+                        assert!(snapshot.contains(&(pair + 1)), "snapshot changed under reader");
+                    }
+
+                    tree.contains(&pair);
+                    reads.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        }
+
+        let mut next = lcg(100);
+
+        for _ in 0..3000 {
+            let pair = next() % 500 * 2;
+
+            tree.write_batch(|tree| {
+                if tree.delete(&pair) {
+                    assert!(tree.delete(&(pair + 1)));
+                } else {
+                    tree.insert(pair + 1);
+                    tree.insert(pair);
+                }
+            });
+        }
+
+        done.store(true, Ordering::SeqCst);
+    });
+
+    assert!(reads.load(Ordering::SeqCst) > 0);
+    assert_pairs(&tree.to_vec());
+    tree.snapshot().check_invariants().unwrap();
+}
+
+/**
+ * writer stops in the middle of write_batch holding its mutex, reads must still finish,
+ * and see the version before it
+ */
+#[test]
+fn readers_do_not_wait_for_writer() {
+    let tree = ConcurrentBTree::new(3);
+
+    for key in 0..1000u64 {
+        tree.insert(key);
+    }
+
+    let (entered, wait_entered) = channel();
+    let (release, wait_release) = channel::<()>();
+
+    thread::scope(|scope| {
+        let writer = tree.clone();
+
+        scope.spawn(move || {
+            writer.write_batch(|tree| {
+                for key in 1000..2000 {
+                    tree.insert(key);
+                }
+
+                entered.send(()).unwrap();
+                wait_release.recv().unwrap();
+            })
+        });
+
+        wait_entered.recv().unwrap();
+
+        for key in (0..2000).step_by(7) {
+            assert_eq!(tree.contains(&key), key < 1000);
+        }
+
+        assert_eq!(tree.len(), 1000);
+        assert_eq!(tree.range(990..1010), (990..1000).collect::<Vec<u64>>());
+
+        release.send(()).unwrap();
+    });
+
+    assert_eq!(tree.len(), 2000);
+}
+
+/**
+ * old version lives as long as its last snapshot, then it is freed
+ */
+#[test]
+fn old_versions_are_freed_with_their_last_snapshot() {
+    let tree = ConcurrentBTree::new(2);
+
+    for key in 0..100u64 {
+        tree.insert(key);
+    }
+
+    let snapshot = tree.snapshot();
+    let old = Arc::downgrade(&snapshot);
+
+    for key in 100..200 {
+        tree.insert(key);
+    }
+
+    assert_eq!(snapshot.len(), 100, "snapshot keeps its version");
+    assert!(old.upgrade().is_some());
+
+    drop(snapshot);
+
+    assert!(old.upgrade().is_none(), "version without snapshots is freed");
+
+    let current = Arc::downgrade(&tree.snapshot());
+
+    assert!(current.upgrade().is_some(), "current version is kept by tree");
+
+    tree.delete(&0);
+
+    assert!(current.upgrade().is_none());
+}
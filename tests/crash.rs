@@ -0,0 +1,621 @@
+#![cfg(feature = "std")]
+
+use std::collections::BTreeMap;
+
+use srdb::{
+    Db, Error, FaultyStorage, Faults, MemStorage, Op, PageId, Pager, SyncMode, VerifyMode, WriteBatch, MIN_PAGE_SIZE,
+};
+
+type Contents = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/**
+ * files of database and its log, they outlive handles opened over them like files on disk do
+ */
+#[derive(Clone, Default)]
+struct Files {
+    db: MemStorage,
+    wal: MemStorage,
+}
+
+impl Files {
+    /**
+     * handle whose every operation counts against faults
+     */
+    fn open_faulty(&self, faults: &Faults) -> Db {
+        Db::open_with_storage(
+            Box::new(FaultyStorage::new(self.db.clone(), faults.clone())),
+            Box::new(FaultyStorage::new(self.wal.clone(), faults.clone())),
+        )
+        .unwrap()
+    }
+
+    fn create_faulty(&self, faults: &Faults) -> Db {
+        Db::create_with_storage(
+            Box::new(FaultyStorage::new(self.db.clone(), faults.clone())),
+            Box::new(FaultyStorage::new(self.wal.clone(), faults.clone())),
+        )
+        .unwrap()
+    }
+
+    fn open(&self) -> Db {
+        Db::open_with_storage(Box::new(self.db.clone()), Box::new(self.wal.clone())).unwrap()
+    }
+
+    /**
+     * power loss, everything written after the last sync is gone
+     */
+    fn power_loss(&self) {
+        self.db.crash();
+        self.wal.crash();
+    }
+}
+
+/**
+ * handle is dropped as if process was killed, nothing it still had to write reaches files
+ */
+fn kill(db: Db, faults: &Faults) {
+    faults.fail_after(0);
+    drop(db);
+}
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:04}", i).into_bytes()
+}
+
+/**
+ * i-th batch of workload: puts, overwrites, deletes and one value in overflow pages
+ */
+fn batch(i: u32) -> WriteBatch {
+    let mut batch = WriteBatch::new();
+
+    for j in 0..8 {
+        batch.put(&key(i * 5 + j), format!("value {} of batch {}", j, i).as_bytes());
+    }
+
+    batch.delete(&key(i * 5 + 1));
+    batch.put(&key(i * 5 + 2), &vec![i as u8; 5000]);
+
+    if i > 0 {
+        batch.delete(&key(i * 5 - 2));
+    }
+
+    batch
+}
+
+fn apply(contents: &mut Contents, batch: &WriteBatch) {
+    for op in batch.ops() {
+        match op {
+            Op::Put { key, value } => {
+                contents.insert(key.clone(), value.clone());
+            }
+            Op::Delete { key } => {
+                contents.remove(key);
+            }
+            op => panic!("workload has no {:?}", op),
+        }
+    }
+}
+
+/**
+ * contents after first n batches
+ */
+fn after(n: u32) -> Contents {
+    let mut contents = Contents::new();
+
+    (0..n).for_each(|i| apply(&mut contents, &batch(i)));
+
+    contents
+}
+
+fn contents(db: &mut Db) -> Contents {
+    db.to_vec().unwrap().into_iter().collect()
+}
+
+fn assert_healthy(db: &mut Db, expected: &Contents, case: &str) {
+    let report = db.verify(VerifyMode::Full);
+
+    assert!(report.is_ok(), "{}: {:?}", case, report.problems);
+    assert_eq!(db.len(), expected.len(), "{}", case);
+    assert!(contents(db) == *expected, "{}", case);
+}
+
+/**
+ * operations write of batch and flush of its pages make when nothing fails
+ */
+fn operations_of_write(files: &Files, prefix: u32) -> u64 {
+    let faults = Faults::new();
+    let mut db = files.create_faulty(&faults);
+
+    (0..prefix).for_each(|i| db.write(&batch(i)).unwrap());
+
+    let before = faults.done();
+
+    db.write(&batch(prefix)).unwrap();
+    db.flush().unwrap();
+
+    faults.done() - before
+}
+
+#[test]
+fn torn_log_tail_is_dropped_and_replay_is_idempotent() {
+    let operations = operations_of_write(&Files::default(), 5);
+
+    assert!(operations > 2);
+
+    for n in 0..=operations {
+        for torn in [0, 1, 9, 100, 3000] {
+            for power_loss in [false, true] {
+                let case = format!("fail after {} of {}, torn {}, power loss {}", n, operations, torn, power_loss);
+                let files = Files::default();
+                let faults = Faults::new();
+                let mut db = files.create_faulty(&faults);
+
+                (0..3).for_each(|i| db.write(&batch(i)).unwrap());
+                db.flush().unwrap();
+                (3..5).for_each(|i| db.write(&batch(i)).unwrap());
+
+                faults.tear_write(torn);
+                faults.fail_after(n);
+
+                let acknowledged = db.write(&batch(5)).is_ok();
+                let _ = db.flush();
+
+                kill(db, &faults);
+
+                if power_loss {
+                    files.power_loss();
+                }
+
+                let faults = Faults::new();
+                let mut db = files.open_faulty(&faults);
+                let recovered = contents(&mut db);
+
+                assert!(recovered == after(5) || recovered == after(6), "{}: batch is applied partly", case);
+                assert!(!acknowledged || recovered == after(6), "{}: acknowledged batch is lost", case);
+                assert_healthy(&mut db, &recovered, &case);
+
+                db.flush().unwrap();
+                kill(db, &faults);
+
+                let mut db = files.open();
+
+                assert_healthy(&mut db, &recovered, &format!("{}, replayed twice", case));
+
+                db.write(&batch(6)).unwrap();
+                db.close().unwrap();
+
+                let mut expected = recovered.clone();
+
+                apply(&mut expected, &batch(6));
+                assert_healthy(&mut files.open(), &expected, &format!("{}, written after recovery", case));
+            }
+        }
+    }
+}
+
+/**
+ * step of scripted workload, every write is one batch to recovery, checkpoint moves log into file
+ */
+enum Step {
+    Put(u32),
+    Delete(u32),
+    Batch(u32),
+    Checkpoint,
+}
+
+fn script() -> Vec<Step> {
+    let mut steps = vec![];
+
+    for i in 0..6 {
+        steps.push(Step::Batch(i));
+        steps.push(Step::Put(i * 3));
+        steps.push(Step::Delete(i * 5 + 3));
+
+        if i % 2 == 1 {
+            steps.push(Step::Checkpoint);
+        }
+    }
+
+    steps
+}
+
+impl Step {
+    fn run(&self, db: &mut Db) -> Result<(), Error> {
+        match *self {
+            Step::Put(i) => db.insert(&key(i), &vec![i as u8; 300 + i as usize]),
+            Step::Delete(i) => db.delete(&key(i)).map(drop),
+            Step::Batch(i) => db.write(&batch(i)),
+            Step::Checkpoint => db.checkpoint(),
+        }
+    }
+
+    fn batch(&self) -> WriteBatch {
+        let mut batch = WriteBatch::new();
+
+        match *self {
+            Step::Put(i) => batch.put(&key(i), &vec![i as u8; 300 + i as usize]),
+            Step::Delete(i) => batch.delete(&key(i)),
+            Step::Batch(i) => batch = self::batch(i),
+            Step::Checkpoint => {}
+        }
+
+        batch
+    }
+}
+
+/**
+ * contents after first n steps of script
+ */
+fn after_steps(steps: &[Step], n: usize) -> Contents {
+    let mut contents = Contents::new();
+
+    steps[..n].iter().for_each(|step| apply(&mut contents, &step.batch()));
+
+    contents
+}
+
+#[test]
+fn recovers_prefix_of_acknowledged_writes_after_failure_at_every_operation() {
+    let steps = script();
+    let operations = {
+        let files = Files::default();
+        let faults = Faults::new();
+        let mut db = files.create_faulty(&faults);
+        let before = faults.done();
+
+        steps.iter().for_each(|step| step.run(&mut db).unwrap());
+
+        faults.done() - before
+    };
+
+    for n in 0..=operations {
+        for torn in [0, 1, 600, 5000] {
+            for power_loss in [false, true] {
+                let case = format!("fail after {} of {}, torn {}, power loss {}", n, operations, torn, power_loss);
+                let files = Files::default();
+                let faults = Faults::new();
+                let mut db = files.create_faulty(&faults);
+
+                faults.tear_write(torn);
+                faults.fail_after(n);
+
+                let acknowledged = steps.iter().take_while(|step| step.run(&mut db).is_ok()).count();
+
+                kill(db, &faults);
+
+                if power_loss {
+                    files.power_loss();
+                }
+
+                let mut db = files.open();
+                let recovered = contents(&mut db);
+                let last = (acknowledged + 1).min(steps.len());
+
+                assert!(
+                    (acknowledged..=last).any(|k| recovered == after_steps(&steps, k)),
+                    "{}: recovered is no prefix of script from {} acknowledged steps",
+                    case,
+                    acknowledged
+                );
+                assert_healthy(&mut db, &recovered, &case);
+            }
+        }
+    }
+}
+
+/**
+ * commits of pager test, the last one is interrupted
+ */
+const COMMITS: u8 = 4;
+
+/**
+ * k-th commit rewrites every other page, frees nothing and adds two pages, so shadow pages of earlier commits
+ * are reused by later ones
+ */
+fn commit_pages(pager: &mut Pager, k: u8, pages: &mut Vec<Vec<u8>>) -> Result<(), Error> {
+    let page_size = pager.page_size();
+
+    for _ in 0..2 {
+        pager.allocate_page()?;
+        pages.push(vec![0; page_size]);
+    }
+
+    for (page_id, page) in pages.iter_mut().enumerate().skip(1) {
+        if (page_id + k as usize).is_multiple_of(2) || page.iter().all(|&byte| byte == 0) {
+            page.fill(k);
+            page[..4].copy_from_slice(&(page_id as u32).to_le_bytes());
+            pager.write_page(page_id as PageId, page)?;
+        }
+    }
+
+    pager.commit(true)
+}
+
+/**
+ * contents of pages after first k commits
+ */
+fn committed_pages(page_size: usize, k: u8) -> Vec<Vec<u8>> {
+    let mut pager = Pager::create_with_page_size(Box::new(MemStorage::new()), page_size).unwrap();
+    let mut pages = vec![vec![0; pager.page_size()]];
+
+    (1..=k).for_each(|k| commit_pages(&mut pager, k, &mut pages).unwrap());
+
+    pages
+}
+
+fn read_pages(pager: &mut Pager) -> Vec<Vec<u8>> {
+    (0..pager.page_count())
+        .map(|page_id| {
+            let mut page = vec![0; pager.page_size()];
+
+            pager.read_page(page_id, &mut page).unwrap();
+            page
+        })
+        .collect()
+}
+
+/**
+ * interrupts the last commit at every operation of it, tearing write which fails to torn bytes,
+ * reopened file must hold exactly pages of one of the two commits, never mix of them
+ */
+fn commit_is_old_or_new(torn_writes: &[usize]) {
+    let page_size = MIN_PAGE_SIZE;
+    let (old, new) = (committed_pages(page_size, COMMITS - 1), committed_pages(page_size, COMMITS));
+    let operations = {
+        let faults = Faults::new();
+        let file = FaultyStorage::new(MemStorage::new(), faults.clone());
+        let mut pager = Pager::create_with_page_size(Box::new(file), page_size).unwrap();
+        let mut pages = vec![vec![0; pager.page_size()]];
+
+        (1..COMMITS).for_each(|k| commit_pages(&mut pager, k, &mut pages).unwrap());
+
+        let before = faults.done();
+
+        commit_pages(&mut pager, COMMITS, &mut pages).unwrap();
+        faults.done() - before
+    };
+
+    for n in 0..=operations {
+        for &torn in torn_writes {
+            for power_loss in [false, true] {
+                let case = format!("fail after {} of {}, torn {}, power loss {}", n, operations, torn, power_loss);
+                let storage = MemStorage::new();
+                let faults = Faults::new();
+                let file = FaultyStorage::new(storage.clone(), faults.clone());
+                let mut pager = Pager::create_with_page_size(Box::new(file), page_size).unwrap();
+                let mut pages = vec![vec![0; pager.page_size()]];
+
+                (1..COMMITS).for_each(|k| commit_pages(&mut pager, k, &mut pages).unwrap());
+
+                faults.tear_write(torn);
+                faults.fail_after(n);
+
+                let acknowledged = commit_pages(&mut pager, COMMITS, &mut pages).is_ok();
+
+                drop(pager);
+
+                if power_loss {
+                    storage.crash();
+                }
+
+                let mut pager = Pager::open_with_storage(Box::new(storage.clone())).unwrap();
+                let recovered = read_pages(&mut pager);
+
+                assert!(recovered == old || recovered == new, "{}: pages of two commits are mixed", case);
+                assert!(!acknowledged || recovered == new, "{}: acknowledged commit is lost", case);
+
+                let generation = pager.generation();
+
+                commit_pages(&mut pager, COMMITS + 1, &mut recovered.clone()).unwrap();
+                drop(pager);
+
+                let pager = Pager::open_with_storage(Box::new(storage)).unwrap();
+
+                assert_eq!(pager.generation(), generation + 1, "{}: commit after recovery is lost", case);
+            }
+        }
+    }
+}
+
+#[test]
+fn shadow_paging_commit_is_old_or_new() {
+    commit_is_old_or_new(&[0]);
+}
+
+/**
+ * write cut short leaves page half new, half old, superblock slots included
+ */
+#[test]
+fn torn_page_write_leaves_old_or_new_pages() {
+    commit_is_old_or_new(&[1, 8, 24, MIN_PAGE_SIZE / 2, MIN_PAGE_SIZE - 1]);
+}
+
+/**
+ * default tree and every table with its name, what one batch changes together
+ */
+type State = Vec<(Option<String>, Contents)>;
+
+fn state(db: &mut Db) -> State {
+    let mut state = vec![(None, contents(db))];
+
+    for name in db.tables().unwrap() {
+        let entries = db.table(&name).unwrap().to_vec().unwrap();
+
+        state.push((Some(name), entries.into_iter().collect()));
+    }
+
+    state
+}
+
+/**
+ * batch applied through table "users", its plain puts and deletes go there, it also writes table "orders"
+ * and creates table "audit"
+ */
+fn multi_table_batch() -> WriteBatch {
+    let mut batch = WriteBatch::new();
+
+    for i in 0..20 {
+        batch.put(&key(i), format!("user {}", i).as_bytes());
+        batch.put_in("orders", &key(i), &vec![i as u8; 100 * i as usize]);
+    }
+
+    batch.delete(&key(100));
+    batch.delete_in("orders", &key(101));
+    batch.create_table("audit");
+    batch.put_in("audit", b"event", &vec![1; 6000]);
+
+    batch
+}
+
+/**
+ * tables with entries batch overwrites and deletes, all flushed
+ */
+fn with_tables(files: &Files, faults: &Faults) -> Db {
+    let mut db = files.create_faulty(faults);
+    let mut batch = WriteBatch::new();
+
+    batch.create_table("users");
+    batch.create_table("orders");
+
+    for i in 0..110 {
+        batch.put_in("users", &key(i), b"old user");
+        batch.put_in("orders", &key(i), b"old order");
+        batch.put(&key(i), b"old default");
+    }
+
+    db.write(&batch).unwrap();
+    db.flush().unwrap();
+
+    db
+}
+
+#[test]
+fn multi_table_batch_is_all_or_nothing() {
+    let (before, after, operations) = {
+        let faults = Faults::new();
+        let mut db = with_tables(&Files::default(), &faults);
+        let before = state(&mut db);
+        let done = faults.done();
+
+        db.table("users").unwrap().apply(&multi_table_batch()).unwrap();
+        db.flush().unwrap();
+
+        (before, state(&mut db), faults.done() - done)
+    };
+
+    assert_ne!(before, after);
+
+    for n in 0..=operations {
+        for torn in [0, 700] {
+            for power_loss in [false, true] {
+                let case = format!("fail after {} of {}, torn {}, power loss {}", n, operations, torn, power_loss);
+                let files = Files::default();
+                let faults = Faults::new();
+                let mut db = with_tables(&files, &faults);
+
+                faults.tear_write(torn);
+                faults.fail_after(n);
+
+                let acknowledged = db.table("users").unwrap().apply(&multi_table_batch()).is_ok();
+                let _ = db.flush();
+
+                kill(db, &faults);
+
+                if power_loss {
+                    files.power_loss();
+                }
+
+                let mut db = files.open();
+                let recovered = state(&mut db);
+
+                assert!(recovered == before || recovered == after, "{}: batch is applied partly", case);
+                assert!(!acknowledged || recovered == after, "{}: acknowledged batch is lost", case);
+                assert!(db.verify(VerifyMode::Full).is_ok(), "{}", case);
+            }
+        }
+    }
+}
+
+/**
+ * inserts n keys one by one, then power is lost without flush
+ */
+fn inserts_after_power_loss(sync_mode: SyncMode, n: u32) -> Contents {
+    let files = Files::default();
+    let faults = Faults::new();
+    let mut db = files.create_faulty(&faults).with_sync_mode(sync_mode).unwrap();
+
+    for i in 0..n {
+        db.insert(&key(i), b"value").unwrap();
+    }
+
+    kill(db, &faults);
+    files.power_loss();
+
+    let mut db = files.open();
+
+    assert!(db.verify(VerifyMode::Full).is_ok(), "{:?}", sync_mode);
+
+    contents(&mut db)
+}
+
+#[test]
+fn sync_mode_always_keeps_every_insert_and_off_loses_unsynced() {
+    let all: Contents = (0..100).map(|i| (key(i), b"value".to_vec())).collect();
+
+    assert!(inserts_after_power_loss(SyncMode::Always, 100) == all);
+    assert!(inserts_after_power_loss(SyncMode::OnCommit, 100).is_empty());
+    assert!(inserts_after_power_loss(SyncMode::Off, 100).is_empty());
+}
+
+#[test]
+fn sync_mode_on_commit_keeps_written_batches() {
+    for sync_mode in [SyncMode::Always, SyncMode::OnCommit, SyncMode::Off] {
+        let files = Files::default();
+        let faults = Faults::new();
+        let mut db = files.create_faulty(&faults).with_sync_mode(sync_mode).unwrap();
+
+        (0..3).for_each(|i| db.write(&batch(i)).unwrap());
+        db.insert(b"single", b"insert").unwrap();
+
+        kill(db, &faults);
+        files.power_loss();
+
+        let recovered = contents(&mut files.open());
+        let mut written = after(3);
+
+        match sync_mode {
+            SyncMode::Always => {
+                written.insert(b"single".to_vec(), b"insert".to_vec());
+                assert!(recovered == written, "{:?}", sync_mode);
+            }
+            SyncMode::OnCommit => assert!(recovered == written, "{:?}", sync_mode),
+            SyncMode::Off => assert!(recovered.is_empty(), "{:?}", sync_mode),
+        }
+    }
+}
+
+#[test]
+fn flush_with_sync_mode_off_is_lost_and_checkpoint_is_kept() {
+    let files = Files::default();
+    let faults = Faults::new();
+    let mut db = files.create_faulty(&faults).with_sync_mode(SyncMode::Off).unwrap();
+
+    db.write(&batch(0)).unwrap();
+    db.flush().unwrap();
+
+    kill(db, &faults);
+    files.power_loss();
+
+    assert!(contents(&mut files.open()).is_empty());
+
+    let faults = Faults::new();
+    let mut db = files.open_faulty(&faults).with_sync_mode(SyncMode::Off).unwrap();
+
+    db.write(&batch(0)).unwrap();
+    db.checkpoint().unwrap();
+
+    kill(db, &faults);
+    files.power_loss();
+
+    assert!(contents(&mut files.open()) == after(1));
+}
@@ -0,0 +1,330 @@
+#![cfg(feature = "std")]
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use srdb::{Db, Error, MemStorage, Pager, Storage, VerifyMode, WriteBatch, PAGE_SIZE};
+
+/**
+ * kind byte overflow pages start with
+ */
+const OVERFLOW: u8 = 4;
+
+/**
+ * superblock fields end with cipher at 36, salt and key check, crc32 of them follows at 72
+ */
+const CIPHER_OFFSET: usize = 36;
+const CHECKSUM_OFFSET: usize = 72;
+
+/**
+ * storage counting reads passed to inner one, reads of overflow pages also separately, clones share counters
+ */
+#[derive(Clone, Debug)]
+struct Counting {
+    inner: MemStorage,
+    reads: Arc<AtomicUsize>,
+    overflow_reads: Arc<AtomicUsize>,
+}
+
+impl Counting {
+    fn new(inner: MemStorage) -> Counting {
+        Counting { inner, reads: Arc::new(AtomicUsize::new(0)), overflow_reads: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    fn reads(&self) -> usize {
+        self.reads.load(Ordering::SeqCst)
+    }
+
+    fn overflow_reads(&self) -> usize {
+        self.overflow_reads.load(Ordering::SeqCst)
+    }
+}
+
+impl Storage for Counting {
+    fn len(&self) -> io::Result<u64> {
+        self.inner.len()
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.inner.set_len(len)
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.inner.read_at(offset, buf)?;
+
+        if buf.first() == Some(&OVERFLOW) {
+            self.overflow_reads.fetch_add(1, Ordering::SeqCst);
+        }
+
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_at(offset, buf)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.inner.sync()
+    }
+}
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+/**
+ * database of n small entries and one with value spanning pages of overflow chain, closed so log is empty,
+ * value is random, so lz4 does not shorten it
+ * returns its storage and size of big value in pages
+ */
+fn with_overflow_value(n: u32) -> (MemStorage, usize) {
+    let storage = MemStorage::new();
+    let mut db = Db::create_with_storage(Box::new(storage.clone()), Box::new(MemStorage::new())).unwrap();
+    let big = random_bytes(10 * PAGE_SIZE, 0x9e37_79b9_7f4a_7c15);
+
+    for i in 0..n {
+        db.insert(&key(i), b"small").unwrap();
+    }
+
+    db.insert(b"big", &big).unwrap();
+    db.close().unwrap();
+
+    (storage, big.len().div_ceil(PAGE_SIZE - 7))
+}
+
+/**
+ * opens database with cold cache, reads of open itself are not counted
+ */
+fn open_counted(storage: &MemStorage) -> (Db, Counting) {
+    let counting = Counting::new(storage.clone());
+    let db = Db::open_with_storage(Box::new(counting.clone()), Box::new(MemStorage::new())).unwrap();
+
+    counting.reads.store(0, Ordering::SeqCst);
+    counting.overflow_reads.store(0, Ordering::SeqCst);
+
+    (db, counting)
+}
+
+#[test]
+fn contains_and_delete_do_not_load_overflow_value() {
+    let (storage, chain) = with_overflow_value(2000);
+    let height = open_counted(&storage).0.height().unwrap();
+
+    let (db, counting) = open_counted(&storage);
+
+    assert!(db.contains(b"big").unwrap());
+    assert_eq!((counting.reads(), counting.overflow_reads()), (height, 0));
+
+    let (mut db, counting) = open_counted(&storage);
+
+    assert!(!db.delete(b"absent").unwrap());
+    assert_eq!(counting.reads(), height);
+
+    let (mut db, counting) = open_counted(&storage);
+
+    assert!(db.delete(b"big").unwrap());
+    assert_eq!(counting.overflow_reads(), chain, "overflow chain is read once, to free its pages");
+    assert!(!db.contains(b"big").unwrap());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+/**
+ * open reads start of file for page size, superblocks, page table and header, nothing of tree,
+ * lookup then reads one node per level
+ */
+#[test]
+fn lookup_after_open_reads_header_and_path() {
+    for n in [100, 2000, 20000] {
+        let (storage, _) = with_overflow_value(n);
+        let metadata = Pager::open_with_storage(Box::new(storage.clone())).unwrap().metadata_pages() as usize;
+        let height = open_counted(&storage).0.height().unwrap();
+
+        let counting = Counting::new(storage.clone());
+        let db = Db::open_with_storage(Box::new(counting.clone()), Box::new(MemStorage::new())).unwrap();
+
+        assert_eq!(counting.reads(), 1 + metadata + 1, "n = {}", n);
+        assert_eq!(db.get(&key(n / 3)).unwrap(), Some(b"small".to_vec()));
+        assert_eq!(counting.reads(), 1 + metadata + 1 + height, "n = {}", n);
+    }
+}
+
+/**
+ * database whose superblocks name cipher, made by rewriting them in plain one, so build without
+ * encryption feature can check it is refused too
+ */
+#[test]
+fn file_with_cipher_is_not_opened_without_key() {
+    let (storage, wal) = (MemStorage::new(), MemStorage::new());
+
+    Db::create_with_storage(Box::new(storage.clone()), Box::new(wal.clone())).unwrap().close().unwrap();
+
+    let mut file = storage.clone();
+
+    for slot in 0..2 {
+        let offset = (slot * PAGE_SIZE) as u64;
+        let mut superblock = vec![0; CHECKSUM_OFFSET];
+
+        file.read_at(offset, &mut superblock).unwrap();
+        superblock[CIPHER_OFFSET..CIPHER_OFFSET + 4].copy_from_slice(&1u32.to_le_bytes());
+        file.write_at(offset, &superblock).unwrap();
+        file.write_at(offset + CHECKSUM_OFFSET as u64, &crc32(&superblock).to_le_bytes()).unwrap();
+    }
+
+    assert!(matches!(Db::open_with_storage(Box::new(storage), Box::new(wal)), Err(Error::KeyRequired)));
+}
+
+fn insert_all(db: &mut Db, n: u32) {
+    for i in 0..n {
+        db.insert(&key(i), format!("value of {}", i).as_bytes()).unwrap();
+    }
+
+    db.flush().unwrap();
+}
+
+#[test]
+fn file_does_not_grow_when_deleted_keys_are_inserted_again() {
+    let storage = MemStorage::new();
+    let mut db = Db::create_with_storage(Box::new(storage.clone()), Box::new(MemStorage::new())).unwrap();
+
+    insert_all(&mut db, 20000);
+
+    let size = storage.len().unwrap();
+    let pages = db.page_count();
+
+    for i in 0..20000 {
+        assert!(db.delete(&key(i)).unwrap());
+    }
+
+    db.flush().unwrap();
+
+    assert!(db.is_empty());
+    assert!(db.free_pages() > pages as usize / 2, "{} of {} pages are free", db.free_pages(), pages);
+
+    insert_all(&mut db, 20000);
+
+    assert!(storage.len().unwrap() < size + size / 4, "{} after reinsert, {} before", storage.len().unwrap(), size);
+}
+
+#[test]
+fn free_list_survives_reopen() {
+    let storage = MemStorage::new();
+    let mut db = Db::create_with_storage(Box::new(storage.clone()), Box::new(MemStorage::new())).unwrap();
+
+    insert_all(&mut db, 20000);
+
+    for i in 0..20000 {
+        db.delete(&key(i)).unwrap();
+    }
+
+    let free = db.free_pages();
+    let pages = db.page_count();
+
+    db.close().unwrap();
+
+    let mut db = Db::open_with_storage(Box::new(storage.clone()), Box::new(MemStorage::new())).unwrap();
+
+    assert_eq!(db.free_pages(), free);
+    assert_eq!(db.page_count(), pages);
+
+    insert_all(&mut db, 20000);
+
+    assert_eq!(db.page_count(), pages, "pages of free list are reused before file grows");
+    assert!(db.verify(VerifyMode::Full).is_ok());
+}
+
+/**
+ * bytes no compression shortens
+ */
+fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn values_from_bytes_to_megabytes_round_trip() {
+    let sizes = [0, 1, 7, 100, PAGE_SIZE / 2, PAGE_SIZE - 7, PAGE_SIZE, PAGE_SIZE + 1, 10 * PAGE_SIZE, 1 << 20, 5 << 20];
+    let storage = MemStorage::new();
+    let mut db = Db::create_with_storage(Box::new(storage.clone()), Box::new(MemStorage::new())).unwrap();
+
+    for (i, &size) in sizes.iter().enumerate() {
+        db.insert(&key(i as u32), &random_bytes(size, i as u64)).unwrap();
+    }
+
+    db.close().unwrap();
+
+    let mut db = Db::open_with_storage(Box::new(storage.clone()), Box::new(MemStorage::new())).unwrap();
+
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    for (i, &size) in sizes.iter().enumerate() {
+        assert!(db.get(&key(i as u32)).unwrap() == Some(random_bytes(size, i as u64)), "size = {}", size);
+    }
+
+    let pages = db.page_count();
+
+    for i in 0..sizes.len() {
+        assert!(db.delete(&key(i as u32)).unwrap());
+    }
+
+    assert!(db.free_pages() > (6 << 20) / PAGE_SIZE, "chains are freed whole");
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    db.insert(b"big", &random_bytes(5 << 20, 99)).unwrap();
+
+    assert_eq!(db.page_count(), pages, "freed chains are reused");
+    assert!(db.get(b"big").unwrap() == Some(random_bytes(5 << 20, 99)));
+}
+
+/**
+ * delete of absent key merges nodes on its way down like any other and may collapse root of table,
+ * batches of random puts and deletes of table, most deletes find nothing
+ */
+#[test]
+fn deleting_absent_keys_keeps_table_whole() {
+    let mut db = Db::create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new())).unwrap();
+    let mut state = 0u64;
+    let mut next = || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    };
+
+    db.create_table("table").unwrap();
+
+    for _ in 0..2000 {
+        let i = (next() % 20_000) as u32;
+        let mut batch = WriteBatch::new();
+
+        if next() % 2 == 0 {
+            batch.put_in("table", &key(i), format!("value of key {}", i).as_bytes());
+        } else {
+            batch.delete_in("table", &key(i));
+        }
+
+        db.write(&batch).unwrap();
+    }
+
+    assert!(db.verify(VerifyMode::Full).is_ok());
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use std::path::PathBuf;
+
+use srdb::{Db, SrdbOptions, SyncMode, VerifyMode, WriteBatch};
+
+use common::temp_path;
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * default tree and three tables of keys and values of random lengths, some values span several pages
+ */
+fn filled(path: &PathBuf, page_size: usize) -> Db {
+    let mut db = SrdbOptions::new().page_size(page_size).sync_mode(SyncMode::Off).create(path).unwrap();
+    let mut next = lcg(page_size as u64);
+    let mut batch = WriteBatch::new();
+
+    for name in ["accounts", "events", "empty"] {
+        batch.create_table(name);
+    }
+
+    for i in 0..3000 {
+        let key = format!("key{:06}", next() % 100_000).into_bytes();
+        let value: Vec<u8> = (0..next() % 300).map(|_| next() as u8).collect();
+
+        match i % 3 {
+            0 => batch.put(&key, &value),
+            1 => batch.put_in("accounts", &key, &value),
+            _ => batch.put_in("events", &key, &value.repeat(if i % 50 == 2 { 200 } else { 1 })),
+        }
+    }
+
+    db.write(&batch).unwrap();
+
+    db
+}
+
+type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/**
+ * entries of default tree, then of every table with its name
+ */
+fn contents(db: &mut Db) -> Vec<(Option<String>, Entries)> {
+    let mut contents = vec![(None, db.to_vec().unwrap())];
+
+    for name in db.tables().unwrap() {
+        let entries = db.table(&name).unwrap().to_vec().unwrap();
+
+        contents.push((Some(name), entries));
+    }
+
+    contents
+}
+
+/**
+ * dump does not depend on page size, so database of every table is restored at another one entry for entry
+ */
+#[test]
+fn tables_restore_exactly_at_another_page_size() {
+    for (from, to) in [(4096, 16384), (16384, 4096), (8192, 1024)] {
+        let source = temp_path(&format!("source-{}", from));
+        let target = temp_path(&format!("target-{}", to));
+        let mut db = filled(&source, from);
+        let expected = contents(&mut db);
+        let digests = db.digests().unwrap();
+        let mut dump = vec![];
+
+        assert_eq!(expected.len(), 4);
+
+        db.dump(&mut dump).unwrap();
+        drop(db);
+
+        let mut restored = SrdbOptions::new().page_size(to).restore(&dump[..], &target).unwrap();
+
+        assert_eq!(restored.disk_stats().page_size, to);
+        assert!(contents(&mut restored) == expected, "{} to {}", from, to);
+        assert_eq!(restored.digests().unwrap(), digests);
+
+        drop(restored);
+
+        let mut reopened = Db::open(&target).unwrap();
+
+        assert!(reopened.verify(VerifyMode::Full).is_ok());
+        assert!(contents(&mut reopened) == expected, "{} to {} after reopen", from, to);
+
+        drop(reopened);
+        Db::remove(&source).unwrap();
+        Db::remove(&target).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+#![cfg(feature = "encryption")]
+
+use std::fs;
+use std::path::PathBuf;
+
+use srdb::{BackupManifest, Db, Error, MemStorage, SrdbOptions, Storage, VerifyMode, PAGE_SIZE};
+
+const KEY: [u8; 32] = [7; 32];
+
+type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+fn key(i: u32) -> Vec<u8> {
+    format!("secret-key-{:05}", i).into_bytes()
+}
+
+fn value(i: u32) -> Vec<u8> {
+    match i % 100 {
+        0 => format!("secret-value-{:05}", i).repeat(500).into_bytes(),
+        _ => format!("secret-value-{:05}", i).into_bytes(),
+    }
+}
+
+fn open_error(options: &SrdbOptions, db: &MemStorage, wal: &MemStorage) -> Error {
+    match options.open_with_storage(Box::new(db.clone()), Box::new(wal.clone())) {
+        Ok(_) => panic!("database is opened"),
+        Err(error) => error,
+    }
+}
+
+fn options(key: [u8; 32]) -> SrdbOptions {
+    SrdbOptions::new().encryption_key(key)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+/**
+ * encrypted database of n entries, every 100th value is long enough for overflow pages,
+ * the last ones are only in log
+ */
+fn encrypted(n: u32) -> (MemStorage, MemStorage) {
+    let (storage, wal) = (MemStorage::new(), MemStorage::new());
+    let mut db = options(KEY).create_with_storage(Box::new(storage.clone()), Box::new(wal.clone())).unwrap();
+
+    for i in 0..n {
+        db.insert(&key(i), &value(i)).unwrap();
+
+        if i == n / 2 {
+            db.checkpoint().unwrap();
+        }
+    }
+
+    drop(db);
+
+    (storage, wal)
+}
+
+#[test]
+fn round_trip() {
+    let (storage, wal) = encrypted(1000);
+
+    for bytes in [storage.to_vec(), wal.to_vec()] {
+        assert!(!contains(&bytes, b"secret"), "plaintext is written to file");
+    }
+
+    let mut db = options(KEY).open_with_storage(Box::new(storage.clone()), Box::new(wal.clone())).unwrap();
+
+    assert!(db.verify(VerifyMode::Full).is_ok());
+    assert_eq!(db.to_vec().unwrap(), (0..1000).map(|i| (key(i), value(i))).collect::<Vec<_>>());
+
+    db.delete(&key(0)).unwrap();
+    db.close().unwrap();
+
+    let db = options(KEY).open_with_storage(Box::new(storage), Box::new(wal)).unwrap();
+
+    assert_eq!(db.len(), 999);
+    assert_eq!(db.get(&key(100)).unwrap(), Some(value(100)));
+}
+
+#[test]
+fn wrong_key_or_none_is_refused() {
+    let (storage, wal) = encrypted(100);
+    let mut other = KEY;
+
+    other[31] ^= 1;
+
+    assert!(matches!(open_error(&options(other), &storage, &wal), Error::WrongKey));
+    assert!(matches!(open_error(&SrdbOptions::new(), &storage, &wal), Error::KeyRequired));
+
+    let (plain, plain_wal) = (MemStorage::new(), MemStorage::new());
+
+    Db::create_with_storage(Box::new(plain.clone()), Box::new(plain_wal.clone())).unwrap().close().unwrap();
+
+    assert!(matches!(open_error(&options(KEY), &plain, &plain_wal), Error::InvalidOptions(_)));
+}
+
+/**
+ * reads contents of database over tampered copy of file, error is the first one of open, read or verify
+ */
+fn read_tampered(bytes: &[u8]) -> Result<Entries, String> {
+    let mut tampered = MemStorage::new();
+
+    tampered.write_at(0, bytes).unwrap();
+
+    let mut db = options(KEY)
+        .open_with_storage(Box::new(tampered), Box::new(MemStorage::new()))
+        .map_err(|error| error.to_string())?;
+    let entries = db.to_vec().map_err(|error| error.to_string())?;
+    let report = db.verify(VerifyMode::Full);
+
+    match report.problems.first() {
+        Some(problem) => Err(problem.message.clone()),
+        None => Ok(entries),
+    }
+}
+
+/**
+ * closed database and its entries, file has no spare pages left by log
+ */
+fn closed(n: u32) -> (Vec<u8>, Entries) {
+    let (storage, wal) = encrypted(n);
+
+    options(KEY).open_with_storage(Box::new(storage.clone()), Box::new(wal)).unwrap().close().unwrap();
+
+    (storage.to_vec(), (0..n).map(|i| (key(i), value(i))).collect())
+}
+
+/**
+ * changed page is never read as other contents, pages in use fail to unseal on open, read or verify
+ */
+#[test]
+fn tampered_page_is_detected() {
+    let (bytes, expected) = closed(300);
+    let mut detected = 0;
+
+    for page in 2..bytes.len() / PAGE_SIZE {
+        for offset in [0, PAGE_SIZE / 2, PAGE_SIZE - 1] {
+            let mut copy = bytes.clone();
+
+            copy[page * PAGE_SIZE + offset] ^= 0x20;
+
+            match read_tampered(&copy) {
+                Ok(entries) => assert!(entries == expected, "page {} changed at {} is read", page, offset),
+                Err(error) => {
+                    assert!(error.contains("checksum"), "page {} changed at {}: {}", page, offset, error);
+                    detected += 1;
+                }
+            }
+        }
+    }
+
+    assert!(detected > 0);
+}
+
+/**
+ * page copied over another one does not pass for it, aad binds it to its place in file,
+ * so it fails like changed one and not as wrong node
+ */
+#[test]
+fn moved_page_is_detected() {
+    let (bytes, expected) = closed(300);
+    let mut detected = 0;
+
+    for page in 3..bytes.len() / PAGE_SIZE {
+        let mut copy = bytes.clone();
+
+        copy.copy_within(2 * PAGE_SIZE..3 * PAGE_SIZE, page * PAGE_SIZE);
+
+        match read_tampered(&copy) {
+            Ok(entries) => assert!(entries == expected, "page 2 is read in place of page {}", page),
+            Err(error) => {
+                assert!(error.contains("checksum"), "page 2 in place of page {}: {}", page, error);
+                detected += 1;
+            }
+        }
+    }
+
+    assert!(detected > 0);
+}
+
+/**
+ * fresh directory for files of one test
+ */
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("srdb-encryption-{}-{}", name, std::process::id()));
+
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+/**
+ * every file in directory, database, its log segments and backups
+ */
+fn assert_no_plaintext(dir: &PathBuf) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+
+        assert!(!contains(&fs::read(&path).unwrap(), b"secret"), "plaintext is written to {}", path.display());
+    }
+}
+
+#[test]
+fn backup_and_vacuum_stay_encrypted() {
+    let dir = temp_dir("backup");
+    let (path, backup) = (dir.join("db"), dir.join("backup"));
+    let mut db = options(KEY).create(&path).unwrap();
+
+    for i in 0..500 {
+        db.insert(&key(i), &value(i)).unwrap();
+    }
+
+    for i in 0..250 {
+        db.delete(&key(i)).unwrap();
+    }
+
+    db.backup_to(&backup).unwrap();
+
+    db.vacuum().unwrap();
+    assert_no_plaintext(&dir);
+    assert!(matches!(db.backup_incremental(BackupManifest::path_for(&backup), dir.join("delta")), Err(Error::InvalidBackup(_))));
+    db.close().unwrap();
+
+    let expected = (250..500).map(|i| (key(i), value(i))).collect::<Vec<_>>();
+
+    for path in [&path, &backup] {
+        assert!(matches!(SrdbOptions::new().open(path), Err(Error::KeyRequired)));
+
+        let mut db = options(KEY).open(path).unwrap();
+
+        assert!(db.verify(VerifyMode::Full).is_ok());
+        assert_eq!(db.to_vec().unwrap(), expected);
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,209 @@
+use std::cmp::Ordering;
+
+use srdb::{BTree, Comparator, FixedT, NaturalOrder};
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * random operations on FixedT<N> tree, tree of runtime t = N and sorted model,
+ * both trees run the same algorithms, so besides keys they must agree on shape after every step
+ */
+fn against_runtime_tree<const N: usize>(seed: u64, ops: usize, range: u64) {
+    let mut fixed = BTree::<u64, NaturalOrder, FixedT<N>>::new_fixed();
+    let mut runtime = BTree::new(N);
+    let mut model: Vec<u64> = vec![];
+    let mut next = lcg(seed);
+
+    assert_eq!(fixed.t(), N);
+
+    for op in 0..ops {
+        let key = next() % range;
+
+        match next() % 10 {
+            0..=3 => {
+                fixed.insert(key);
+                runtime.insert(key);
+                model.insert(model.partition_point(|k| *k <= key), key);
+            }
+            4 => {
+                fixed.try_insert(key).unwrap();
+                runtime.try_insert(key).unwrap();
+                model.insert(model.partition_point(|k| *k <= key), key);
+            }
+            5 | 6 => {
+                let present = model.binary_search(&key).is_ok();
+
+                assert_eq!(fixed.delete(&key), present, "delete of {}", key);
+                assert_eq!(runtime.delete(&key), present);
+
+                if present {
+                    model.remove(model.binary_search(&key).unwrap());
+                }
+            }
+            7 => {
+                let removed = model.binary_search(&key).ok().map(|i| model.remove(i));
+
+                assert_eq!(fixed.remove(&key), removed);
+                assert_eq!(runtime.remove(&key), removed);
+            }
+            8 => {
+                let chunk: Vec<u64> = (0..next() % 100).map(|_| next() % range).collect();
+
+                fixed.insert_batch(chunk.clone());
+                runtime.insert_batch(chunk.clone());
+                model.extend(chunk);
+                model.sort();
+            }
+            _ => {
+                let present = model.binary_search(&key).is_ok();
+
+                assert_eq!(fixed.contains(key), present);
+                assert_eq!(fixed.contains_key(&key), present);
+                assert_eq!(fixed.get(&key), present.then_some(&key));
+                assert_eq!(fixed.find(|k| k.cmp(&key)), present.then_some(&key));
+            }
+        }
+
+        assert_eq!(fixed.len(), model.len());
+
+        if op % 64 == 0 {
+            fixed.check_invariants().unwrap();
+
+            assert!(fixed.iter().eq(model.iter()), "N = {}, op {}", N, op);
+            assert_eq!(fixed.stats(), runtime.stats(), "N = {}, op {}", N, op);
+            assert_eq!(fixed.levels(), runtime.levels());
+        }
+    }
+
+    let mut other = BTree::<u64, NaturalOrder, FixedT<N>>::new_fixed();
+
+    for key in 0..500 {
+        other.insert(key * 3);
+    }
+
+    fixed.append(&mut other);
+    model.extend((0..500).map(|key| key * 3));
+    model.sort();
+
+    assert!(other.is_empty());
+
+    let copy = fixed.clone();
+    let report = fixed.compact();
+
+    fixed.check_invariants().unwrap();
+    copy.check_invariants().unwrap();
+    assert!(report.nodes_after <= report.nodes_before);
+    assert_eq!(fixed.to_vec(), model);
+    assert_eq!(copy.to_vec(), model, "clone keeps keys after original is compacted");
+    assert_eq!(Vec::from(fixed), model);
+}
+
+#[test]
+fn fixed_tree_behaves_like_runtime_tree() {
+    for seed in 0..2 {
+        against_runtime_tree::<2>(seed, 3000, 300);
+        against_runtime_tree::<3>(seed, 3000, 1000);
+        against_runtime_tree::<4>(seed, 3000, 50);
+        against_runtime_tree::<8>(seed, 3000, 5000);
+        against_runtime_tree::<16>(seed, 3000, 5000);
+    }
+}
+
+/**
+ * empties tree with deletes in order, each one merges or borrows at the left edge, then fills it again
+ */
+#[test]
+fn fixed_tree_empties_and_refills() {
+    let mut tree = BTree::<u64, NaturalOrder, FixedT<3>>::new_fixed();
+
+    for round in 0..3 {
+        for key in 0..2000 {
+            tree.insert(key);
+        }
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.len(), 2000, "round {}", round);
+
+        for key in 0..2000 {
+            assert!(tree.delete(&key));
+        }
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.stats().height, 1);
+        assert!(!tree.delete(&0));
+        tree.check_invariants().unwrap();
+    }
+}
+
+/**
+ * keys are dropped once, by delete, by remove handing them out or with tree
+ */
+#[test]
+fn fixed_tree_drops_every_key_once() {
+    use std::rc::Rc;
+
+    let key = Rc::new(());
+    let mut tree = BTree::<(u32, Rc<()>), NaturalOrder, FixedT<4>>::new_fixed();
+
+    for i in 0..1000 {
+        tree.insert((i, key.clone()));
+    }
+
+    assert_eq!(Rc::strong_count(&key), 1001);
+
+    for i in (0..1000).step_by(2) {
+        assert!(tree.delete(&(i, key.clone())));
+    }
+
+    assert_eq!(Rc::strong_count(&key), 501);
+    assert!(tree.remove(&(1, key.clone())).is_some());
+    assert_eq!(Rc::strong_count(&key), 500);
+
+    drop(tree);
+
+    assert_eq!(Rc::strong_count(&key), 1);
+}
+
+struct Descending;
+
+impl Comparator<u64> for Descending {
+    fn compare(&self, a: &u64, b: &u64) -> Ordering {
+        b.cmp(a)
+    }
+}
+
+#[test]
+fn fixed_tree_with_comparator() {
+    let mut tree = BTree::<u64, Descending, FixedT<5>>::new_fixed_with_comparator(Descending);
+    let mut next = lcg(11);
+    let mut model = vec![];
+
+    for _ in 0..3000 {
+        let key = next() % 1000;
+
+        if next().is_multiple_of(3) {
+            let i = model.iter().position(|k| *k == key);
+
+            assert_eq!(tree.delete(&key), i.is_some());
+
+            if let Some(i) = i {
+                model.remove(i);
+            }
+        } else {
+            tree.insert(key);
+            model.push(key);
+        }
+    }
+
+    model.sort_by(|a, b| b.cmp(a));
+
+    tree.check_invariants().unwrap();
+    assert_eq!(tree.to_vec(), model);
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "std")]
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use srdb::{BackgroundFlush, Db, FaultyStorage, Faults, MemStorage, Storage, WriteBatch};
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn policy(interval: Duration) -> BackgroundFlush {
+    BackgroundFlush {
+        interval,
+        ..BackgroundFlush::default()
+    }
+}
+
+/**
+ * database whose file and log count against faults
+ */
+fn faulty(storage: &MemStorage, faults: &Faults) -> Db {
+    Db::create_with_storage(
+        Box::new(FaultyStorage::new(storage.clone(), faults.clone())),
+        Box::new(FaultyStorage::new(MemStorage::new(), faults.clone())),
+    )
+    .unwrap()
+}
+
+/**
+ * entries committed to pages of file, log is left out, so nothing only in it is seen
+ */
+fn committed(storage: &MemStorage) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut copy = MemStorage::new();
+
+    copy.write_at(0, &storage.to_vec()).unwrap();
+
+    Db::open_with_storage(Box::new(copy), Box::new(MemStorage::new())).unwrap().to_vec().unwrap()
+}
+
+fn batch(from: u32, to: u32) -> WriteBatch {
+    let mut batch = WriteBatch::new();
+
+    for i in from..to {
+        batch.put(&key(i), b"value");
+    }
+
+    batch
+}
+
+#[test]
+fn data_reaches_file_without_flush() {
+    let storage = MemStorage::new();
+    let faults = Faults::new();
+    let mut db = faulty(&storage, &faults);
+
+    db.set_background_flush(Some(policy(Duration::from_millis(5)))).unwrap();
+    db.write(&batch(0, 1000)).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+
+    while committed(&storage).len() < 1000 {
+        assert!(Instant::now() < deadline, "background flush did not commit batch");
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    faults.fail_after(0);
+    drop(db);
+
+    assert_eq!(committed(&storage).len(), 1000);
+}
+
+#[test]
+fn dirty_pages_wake_flusher_before_interval() {
+    let storage = MemStorage::new();
+    let mut db = faulty(&storage, &Faults::new());
+    let policy = BackgroundFlush {
+        dirty_pages: 8,
+        ..policy(Duration::from_secs(3600))
+    };
+
+    db.set_background_flush(Some(policy)).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut i = 0;
+
+    while committed(&storage).is_empty() {
+        assert!(Instant::now() < deadline, "dirty pages did not wake flusher");
+
+        db.write(&batch(i, i + 200)).unwrap();
+        i += 200;
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/**
+ * flusher makes its last flush when stopped, so batch written just before is in file
+ * even though handle is killed right after
+ */
+#[test]
+fn stopping_flusher_keeps_last_batch() {
+    let storage = MemStorage::new();
+    let faults = Faults::new();
+    let mut db = faulty(&storage, &faults);
+
+    db.set_background_flush(Some(policy(Duration::from_secs(3600)))).unwrap();
+    db.write(&batch(0, 500)).unwrap();
+    db.write(&batch(500, 600)).unwrap();
+    db.set_background_flush(None).unwrap();
+
+    faults.fail_after(0);
+    drop(db);
+
+    assert_eq!(committed(&storage).len(), 600);
+}
+
+#[test]
+fn error_of_background_flush_fails_next_operation() {
+    let storage = MemStorage::new();
+    let faults = Faults::new();
+    let mut db = faulty(&storage, &faults);
+
+    db.write(&batch(0, 100)).unwrap();
+    faults.fail_after(0);
+    db.set_background_flush(Some(policy(Duration::from_millis(1)))).unwrap();
+
+    thread::sleep(Duration::from_millis(50));
+
+    assert!(db.get(&key(1)).is_err(), "error of flusher is reported");
+}
@@ -0,0 +1,207 @@
+#![cfg(feature = "std")]
+
+use srdb::{Db, Error, MemStorage, Pager, SrdbOptions, Storage, FORMAT_VERSION, HEADER_PAGE, PAGE_SIZE};
+
+/**
+ * superblock is magic, version (u32), page size (u32), generation (u64), page count (u32), ...
+ * with crc32 of its first 72 bytes at 72, it is kept in physical pages 0 and 1
+ */
+const VERSION_OFFSET: usize = 4;
+const PAGE_SIZE_OFFSET: usize = 8;
+const SUPERBLOCK_CHECKSUM_OFFSET: usize = 72;
+
+/**
+ * header page is key codec, value codec, t (u32 each), root (u32), len, seq (u64 each),
+ * free head (u32), free count (u64), catalog (u32), ... with crc32 of its first 64 bytes at 64
+ */
+const VALUE_CODEC_OFFSET: usize = 4;
+const T_OFFSET: usize = 8;
+const ROOT_OFFSET: usize = 12;
+const FREE_HEAD_OFFSET: usize = 32;
+const CATALOG_OFFSET: usize = 44;
+const HEADER_CHECKSUM_OFFSET: usize = 64;
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+/**
+ * closed database of a few keys, its file and log
+ */
+fn database() -> (MemStorage, MemStorage) {
+    let (storage, wal) = (MemStorage::new(), MemStorage::new());
+    let mut db = Db::create_with_storage(Box::new(storage.clone()), Box::new(wal.clone())).unwrap();
+
+    for i in 0..100u32 {
+        db.insert(&i.to_be_bytes(), b"value").unwrap();
+    }
+
+    db.close().unwrap();
+
+    (storage, wal)
+}
+
+fn open((storage, wal): (MemStorage, MemStorage)) -> Result<Db, Error> {
+    Db::open_with_storage(Box::new(storage), Box::new(wal))
+}
+
+/**
+ * changes both superblock slots, checksum is recomputed unless change is to keep it stale
+ */
+fn with_superblocks(change: impl Fn(&mut [u8]), recompute: bool) -> (MemStorage, MemStorage) {
+    let (mut storage, wal) = database();
+
+    for slot in 0..2 {
+        let offset = (slot * PAGE_SIZE) as u64;
+        let mut superblock = vec![0; SUPERBLOCK_CHECKSUM_OFFSET + 4];
+
+        storage.read_at(offset, &mut superblock).unwrap();
+        change(&mut superblock);
+
+        if recompute {
+            let crc = crc32(&superblock[..SUPERBLOCK_CHECKSUM_OFFSET]);
+
+            superblock[SUPERBLOCK_CHECKSUM_OFFSET..].copy_from_slice(&crc.to_le_bytes());
+        }
+
+        storage.write_at(offset, &superblock).unwrap();
+    }
+
+    (storage, wal)
+}
+
+/**
+ * changes header page and commits it through pager, so only header itself is wrong
+ */
+fn with_header(change: impl Fn(&mut [u8]), recompute: bool) -> (MemStorage, MemStorage) {
+    let (storage, wal) = database();
+    let mut pager = Pager::open_with_storage(Box::new(storage.clone())).unwrap();
+    let mut page = vec![0; pager.page_size()];
+
+    pager.read_page(HEADER_PAGE, &mut page).unwrap();
+    change(&mut page);
+
+    if recompute {
+        let crc = crc32(&page[..HEADER_CHECKSUM_OFFSET]);
+
+        page[HEADER_CHECKSUM_OFFSET..HEADER_CHECKSUM_OFFSET + 4].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    pager.write_page(HEADER_PAGE, &page).unwrap();
+    pager.commit(true).unwrap();
+
+    (storage, wal)
+}
+
+fn set_u32(offset: usize, value: u32) -> impl Fn(&mut [u8]) {
+    move |page: &mut [u8]| page[offset..offset + 4].copy_from_slice(&value.to_le_bytes())
+}
+
+#[test]
+fn untouched_file_opens() {
+    let db = open(database()).unwrap();
+
+    assert_eq!(db.len(), 100);
+    assert_eq!(db.get(&7u32.to_be_bytes()).unwrap(), Some(b"value".to_vec()));
+}
+
+#[test]
+fn wrong_magic() {
+    let files = with_superblocks(|superblock| superblock[..4].copy_from_slice(b"SQLi"), true);
+
+    assert!(matches!(open(files), Err(Error::WrongMagic)));
+}
+
+#[test]
+fn future_version() {
+    let files = with_superblocks(set_u32(VERSION_OFFSET, FORMAT_VERSION + 1), true);
+
+    assert!(matches!(open(files), Err(Error::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1));
+}
+
+#[test]
+fn superblock_checksum_mismatch() {
+    let files = with_superblocks(|superblock| superblock[PAGE_SIZE_OFFSET + 1] ^= 1, false);
+
+    assert!(matches!(open(files), Err(Error::CorruptHeader(reason)) if reason.contains("checksum")));
+}
+
+#[test]
+fn one_broken_superblock_falls_back_to_the_other() {
+    let (mut storage, wal) = database();
+
+    storage.write_at(0, b"junk").unwrap();
+
+    assert_eq!(open((storage.clone(), wal.clone())).unwrap().len(), 100);
+
+    storage.write_at(PAGE_SIZE as u64, b"junk").unwrap();
+
+    assert!(matches!(open((storage, wal)), Err(Error::WrongMagic)));
+}
+
+#[test]
+fn page_size_mismatch() {
+    let (storage, wal) = database();
+    let opened = SrdbOptions::new().page_size(2 * PAGE_SIZE).open_with_storage(Box::new(storage), Box::new(wal));
+
+    assert!(matches!(
+        opened,
+        Err(Error::PageSizeMismatch { expected, found }) if expected == 2 * PAGE_SIZE && found == PAGE_SIZE
+    ));
+}
+
+#[test]
+fn header_checksum_mismatch() {
+    let files = with_header(|page| page[ROOT_OFFSET] ^= 1, false);
+
+    assert!(matches!(open(files), Err(Error::CorruptHeader(reason)) if reason.contains("checksum")));
+}
+
+#[test]
+fn unknown_codec() {
+    let files = with_header(set_u32(VALUE_CODEC_OFFSET, 77), true);
+
+    assert!(matches!(open(files), Err(Error::CorruptHeader(reason)) if reason.contains("codecs")));
+}
+
+#[test]
+fn branching_factor_out_of_range() {
+    for t in [0, 1, 1 << 20] {
+        let opened = open(with_header(set_u32(T_OFFSET, t), true));
+
+        assert!(matches!(opened, Err(Error::CorruptHeader(reason)) if reason.contains("branching factor")), "t = {}", t);
+    }
+}
+
+#[test]
+fn root_out_of_bounds() {
+    for root in [HEADER_PAGE, 1 << 30] {
+        let opened = open(with_header(set_u32(ROOT_OFFSET, root), true));
+
+        assert!(matches!(opened, Err(Error::CorruptHeader(reason)) if reason.contains("root page")), "root = {}", root);
+    }
+}
+
+#[test]
+fn catalog_out_of_bounds() {
+    let files = with_header(set_u32(CATALOG_OFFSET, 1 << 30), true);
+
+    assert!(matches!(open(files), Err(Error::CorruptHeader(reason)) if reason.contains("catalog page")));
+}
+
+#[test]
+fn free_list_out_of_bounds() {
+    let files = with_header(set_u32(FREE_HEAD_OFFSET, 1 << 30), true);
+
+    assert!(matches!(open(files), Err(Error::CorruptHeader(reason)) if reason.contains("free list")));
+}
@@ -0,0 +1,85 @@
+use srdb::{BTree, LevelHistogram};
+
+fn level(nodes: usize, min: usize, max: usize, keys: usize, buckets: &[usize]) -> LevelHistogram {
+    LevelHistogram { nodes, min, max, avg: keys as f64 / nodes as f64, buckets: buckets.to_vec() }
+}
+
+/**
+ * 100 keys packed into nodes of 5 keys: 17 leaves hold 84 keys and 16 separators go up,
+ * 3 nodes of level 1 hold 14 of them and root the other 2
+ */
+#[test]
+fn histogram_of_packed_tree() {
+    let keys: Vec<u32> = (0..100).collect();
+    let histogram = BTree::bulk_load_with_fill(3, &keys, 1.0).histogram();
+
+    assert_eq!((histogram.t, histogram.bucket_width), (3, 1));
+    assert_eq!(
+        histogram.levels,
+        [
+            level(1, 2, 2, 2, &[0, 0, 1, 0, 0, 0]),
+            level(3, 4, 5, 14, &[0, 0, 0, 0, 1, 2]),
+            level(17, 4, 5, 84, &[0, 0, 0, 0, 1, 16]),
+        ]
+    );
+}
+
+/**
+ * fill 0.5 of 5 slots leaves 3 keys per node, so 100 keys take 26 leaves with 25 separators above them,
+ * nodes which share keys unevenly get one less
+ */
+#[test]
+fn histogram_of_half_filled_tree() {
+    let keys: Vec<u32> = (0..100).collect();
+    let histogram = BTree::bulk_load_with_fill(3, &keys, 0.5).histogram();
+
+    assert_eq!(
+        histogram.levels,
+        [
+            level(1, 1, 1, 1, &[0, 1, 0, 0, 0, 0]),
+            level(2, 2, 3, 5, &[0, 0, 1, 1, 0, 0]),
+            level(7, 2, 3, 19, &[0, 0, 2, 5, 0, 0]),
+            level(26, 2, 3, 75, &[0, 0, 3, 23, 0, 0]),
+        ]
+    );
+}
+
+/**
+ * t = 8 has 16 key counts in 8 buckets of 2, fill 0.75 of 15 slots leaves 11 keys per leaf,
+ * 9 leaves share 92 keys as 11, 11 and seven 10s, root holds 8 separators
+ */
+#[test]
+fn histogram_buckets_of_wide_nodes() {
+    let keys: Vec<u32> = (0..100).collect();
+    let histogram = BTree::bulk_load_with_fill(8, &keys, 0.75).histogram();
+
+    assert_eq!((histogram.t, histogram.bucket_width), (8, 2));
+    assert_eq!(
+        histogram.levels,
+        [level(1, 8, 8, 8, &[0, 0, 0, 0, 1, 0, 0, 0]), level(9, 10, 11, 92, &[0, 0, 0, 0, 0, 9, 0, 0])]
+    );
+}
+
+#[test]
+fn histogram_display() {
+    let keys: Vec<u32> = (0..10).collect();
+    let text = BTree::bulk_load_with_fill(2, &keys, 1.0).histogram().to_string();
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+
+    assert_eq!(
+        lines,
+        [
+            "t = 2, 3 key slots per node",
+            "level 0: 1 nodes, keys min 2 avg 2.00 max 2",
+            "        0 | 0",
+            "        1 | 0",
+            "        2 | 1      ########################################",
+            "        3 | 0",
+            "level 1: 3 nodes, keys min 2 avg 2.67 max 3",
+            "        0 | 0",
+            "        1 | 0",
+            "        2 | 1      ####################",
+            "        3 | 2      ########################################",
+        ]
+    );
+}
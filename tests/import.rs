@@ -0,0 +1,140 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use std::fmt::Debug;
+
+use srdb::{BTree, Codec, Db, Error, SrdbOptions, SyncMode, VerifyMode};
+
+use common::temp_path;
+
+fn encoded<T: Codec>(key: &T) -> Vec<u8> {
+    let mut out = vec![];
+
+    key.encode(&mut out);
+
+    out
+}
+
+/**
+ * imports tree, reopens file and compares point lookups and full scan with iterator of tree
+ */
+fn assert_imported<T: Ord + Clone + Debug + Codec>(name: &str, tree: &BTree<T>, absent: &[T]) {
+    let path = temp_path(name);
+    let mut db = SrdbOptions::new().sync_mode(SyncMode::Off).create(&path).unwrap();
+
+    db.import_tree(tree).unwrap();
+
+    if !tree.is_empty() {
+        assert!(matches!(db.import_tree(tree), Err(Error::NotEmpty(len)) if len == tree.len()));
+    }
+
+    drop(db);
+
+    let mut db = Db::open(&path).unwrap();
+
+    assert!(db.verify(VerifyMode::Full).is_ok(), "{}", name);
+    assert_eq!(db.len(), tree.len(), "{}", name);
+
+    for key in tree.iter().step_by(97) {
+        assert_eq!(db.get(&encoded(key)).unwrap(), Some(vec![]), "{}: {:?}", name, key);
+    }
+
+    for key in absent {
+        assert_eq!(db.get(&encoded(key)).unwrap(), None, "{}: {:?}", name, key);
+    }
+
+    let mut scanned: Vec<T> = db
+        .to_vec()
+        .unwrap()
+        .into_iter()
+        .map(|(key, value)| {
+            assert!(value.is_empty());
+
+            T::decode(&key).unwrap()
+        })
+        .collect();
+
+    scanned.sort();
+
+    assert!(scanned.iter().eq(tree.iter()), "{}", name);
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * little-endian integers are not in byte order, so import sorts encoded keys first
+ */
+#[test]
+fn imported_integers_reopen_and_scan() {
+    let keys: Vec<u64> = (0..100_000).map(|i| i * 3 + 1).collect();
+    let tree = BTree::bulk_load(4, &keys);
+    let absent: Vec<u64> = (0..1000).map(|i| i * 3).collect();
+
+    assert_imported("integers", &tree, &absent);
+}
+
+#[test]
+fn imported_strings_reopen_and_scan() {
+    let keys: Vec<String> = (0..100_000).map(|i| format!("key{:08}", i * 2)).collect();
+    let tree = BTree::bulk_load(16, &keys);
+    let absent: Vec<String> = (0..1000).map(|i| format!("key{:08}", i * 2 + 1)).collect();
+
+    assert_imported("strings", &tree, &absent);
+}
+
+#[test]
+fn empty_tree_imports_empty_database() {
+    assert_imported("empty", &BTree::<u32>::new(8), &[1, 2, 3]);
+}
+
+/**
+ * table gets imported keys while default tree and other tables keep theirs, reopen reads table from catalog
+ */
+#[test]
+fn imported_table_reopens_and_scans() {
+    let path = temp_path("table");
+    let keys: Vec<u64> = (0..50_000).map(|i| i * 3 + 1).collect();
+    let tree = BTree::bulk_load(8, &keys);
+    let mut db = SrdbOptions::new().sync_mode(SyncMode::Off).create(&path).unwrap();
+
+    db.insert(b"default", b"value").unwrap();
+    db.create_table("keys").unwrap();
+    db.create_table("other").unwrap();
+    db.table("other").unwrap().insert(b"other", b"value").unwrap();
+    db.table("keys").unwrap().import_tree(&tree).unwrap();
+
+    assert!(matches!(db.table("keys").unwrap().import_tree(&tree), Err(Error::NotEmpty(len)) if len == tree.len()));
+    assert!(matches!(db.table("missing").map(|_| ()), Err(Error::TableNotFound(_))));
+
+    drop(db);
+
+    let mut db = Db::open(&path).unwrap();
+
+    assert!(db.verify(VerifyMode::Full).is_ok());
+    assert_eq!(db.to_vec().unwrap(), vec![(b"default".to_vec(), b"value".to_vec())]);
+    assert_eq!(db.table("other").unwrap().to_vec().unwrap(), vec![(b"other".to_vec(), b"value".to_vec())]);
+
+    let mut table = db.table("keys").unwrap();
+
+    for key in tree.iter().step_by(97) {
+        assert_eq!(table.get(&encoded(key)).unwrap(), Some(vec![]), "{}", key);
+    }
+
+    assert_eq!(table.get(&encoded(&0u64)).unwrap(), None);
+
+    let mut scanned: Vec<u64> = table
+        .to_vec()
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| u64::decode(&key).unwrap())
+        .collect();
+
+    scanned.sort();
+
+    assert!(scanned.iter().eq(tree.iter()));
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
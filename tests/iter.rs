@@ -0,0 +1,122 @@
+#![cfg(feature = "std")]
+
+use std::ops::Bound;
+
+use srdb::{Db, MemStorage, SrdbOptions, SyncMode, WriteBatch, MIN_CACHE_PAGES};
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn value(i: u32) -> Vec<u8> {
+    format!("value of key {}", i).into_bytes()
+}
+
+/**
+ * tree of small nodes behind the smallest cache, its leaf level alone is many times the cache
+ */
+fn small_cache(n: u32) -> Db {
+    let mut db = SrdbOptions::new()
+        .branching_factor(4)
+        .cache_pages(MIN_CACHE_PAGES)
+        .sync_mode(SyncMode::Off)
+        .create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new()))
+        .unwrap();
+    let mut batch = WriteBatch::new();
+
+    for i in 0..n {
+        batch.put(&key(i), &value(i));
+    }
+
+    db.write(&batch).unwrap();
+    db.flush().unwrap();
+
+    db
+}
+
+fn expected(range: std::ops::Range<u32>) -> Vec<(Vec<u8>, Vec<u8>)> {
+    range.map(|i| (key(i), value(i))).collect()
+}
+
+#[test]
+fn full_scan_through_cache_smaller_than_one_level() {
+    let mut db = small_cache(20000);
+    let height = db.height().unwrap();
+
+    assert!(height >= 4);
+    assert!(db.page_count() as usize > 20 * MIN_CACHE_PAGES);
+
+    let before = db.cache().misses();
+    let scanned: Vec<_> = db.iter().unwrap().map(Result::unwrap).collect();
+    let misses = db.cache().misses() - before;
+
+    assert!(scanned == expected(0..20000));
+    assert!(misses > 10 * MIN_CACHE_PAGES as u64, "scan reads pages as it goes");
+    assert!(misses < db.page_count() as u64, "every page is read once");
+}
+
+#[test]
+fn ranges_through_small_cache() {
+    let mut db = small_cache(20000);
+
+    let scanned: Vec<_> = db.range(&key(7000)[..]..&key(13000)[..]).unwrap().map(Result::unwrap).collect();
+
+    assert!(scanned == expected(7000..13000));
+
+    let bounds = (Bound::Excluded(&key(19990)[..]), Bound::Unbounded);
+    let scanned: Vec<_> = db.range::<[u8]>(bounds).unwrap().map(Result::unwrap).collect();
+
+    assert!(scanned == expected(19991..20000));
+
+    let mut iter = db.range(&b"key0050"[..]..=&b"key0051"[..]).unwrap();
+
+    assert!(iter.next().unwrap().unwrap() == (key(5000), value(5000)));
+    assert_eq!(iter.count(), 99);
+}
+
+#[test]
+fn batches_end_with_leaves() {
+    let mut db = small_cache(20000);
+    let mut iter = db.iter().unwrap();
+    let mut scanned = vec![];
+    let mut batches = 0;
+
+    loop {
+        let batch = iter.next_batch().unwrap();
+
+        if batch.is_empty() {
+            break;
+        }
+
+        assert!(batch.len() <= 2 * 4, "leaf holds at most 2t - 1 keys, batch may start at key above it");
+
+        batches += 1;
+        scanned.extend(batch);
+    }
+
+    drop(iter);
+
+    assert!(scanned == expected(0..20000));
+    assert!(batches > 20000 / 8);
+}
+
+#[test]
+fn scan_after_changes_sees_them() {
+    let mut db = small_cache(5000);
+
+    for i in (0..5000).step_by(3) {
+        db.delete(&key(i)).unwrap();
+    }
+
+    for i in (1..5000).step_by(3) {
+        db.insert(&key(i), b"changed").unwrap();
+    }
+
+    let scanned: Vec<_> = db.iter().unwrap().map(Result::unwrap).collect();
+    let expected: Vec<_> = (0..5000)
+        .filter(|i| i % 3 != 0)
+        .map(|i| (key(i), if i % 3 == 1 { b"changed".to_vec() } else { value(i) }))
+        .collect();
+
+    assert!(scanned == expected);
+}
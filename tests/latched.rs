@@ -0,0 +1,177 @@
+#![cfg(feature = "latch")]
+
+use srdb::LatchedBTree;
+
+/**
+ * tree of t = 2 with root over leaves, so two operations on it split, merge and borrow
+ */
+#[cfg(loom)]
+fn two_levels() -> LatchedBTree<u32> {
+    let tree = LatchedBTree::new(2);
+
+    for key in [10, 20, 30, 40, 50] {
+        tree.insert(key);
+    }
+
+    tree
+}
+
+/**
+ * loom runs every interleaving of latches of two writers, the result must be that of either order
+ */
+#[cfg(loom)]
+#[test]
+fn loom_concurrent_insert_and_delete() {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    let mut builder = loom::model::Builder::new();
+
+    builder.preemption_bound = Some(2);
+    builder.check(|| {
+        let tree = Arc::new(two_levels());
+        let inserter = {
+            let tree = tree.clone();
+
+            thread::spawn(move || {
+                tree.insert(35);
+                tree.insert(36);
+            })
+        };
+        let deleter = {
+            let tree = tree.clone();
+
+            thread::spawn(move || assert!(tree.delete(&10)))
+        };
+
+        assert!(tree.contains(&50));
+
+        inserter.join().unwrap();
+        deleter.join().unwrap();
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.to_vec(), [20, 30, 35, 36, 40, 50]);
+    });
+}
+
+#[cfg(loom)]
+#[test]
+fn loom_concurrent_deletes_collapse_root() {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    let mut builder = loom::model::Builder::new();
+
+    builder.preemption_bound = Some(2);
+    builder.check(|| {
+        let tree = Arc::new(two_levels());
+        let deleters: Vec<_> = [[10, 30], [50, 20]]
+            .into_iter()
+            .map(|keys| {
+                let tree = tree.clone();
+
+                thread::spawn(move || keys.iter().for_each(|key| assert!(tree.delete(key))))
+            })
+            .collect();
+
+        for deleter in deleters {
+            deleter.join().unwrap();
+        }
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.to_vec(), [40]);
+    });
+}
+
+#[cfg(not(loom))]
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * writers own keys equal to their number modulo writers, so each of them keeps exact model of its keys,
+ * keys of all models together are the tree once they are done, readers check order meanwhile
+ */
+#[cfg(not(loom))]
+#[test]
+fn writers_against_model() {
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    const WRITERS: u64 = 8;
+
+    for t in [2, 3, 8] {
+        let tree = LatchedBTree::new(t);
+        let done = AtomicBool::new(false);
+
+        let models: Vec<BTreeMap<u64, usize>> = thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    while !done.load(Ordering::SeqCst) {
+                        let keys = tree.range(1000..3000);
+
+                        assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", keys);
+                        assert!(keys.iter().all(|key| (1000..3000).contains(key)));
+                    }
+                });
+            }
+
+            let writers: Vec<_> = (0..WRITERS)
+                .map(|writer| {
+                    let tree = &tree;
+
+                    scope.spawn(move || {
+                        let mut next = lcg(writer + t as u64);
+                        let mut model = BTreeMap::new();
+
+                        for _ in 0..3000 {
+                            let key = next() % 500 * WRITERS + writer;
+
+                            if next().is_multiple_of(3) {
+                                let present = model.get(&key).is_some_and(|count| *count > 0);
+
+                                assert_eq!(tree.delete(&key), present, "delete of {}", key);
+
+                                if present {
+                                    *model.get_mut(&key).unwrap() -= 1;
+                                }
+                            } else {
+                                tree.insert(key);
+                                *model.entry(key).or_insert(0) += 1;
+                            }
+
+                            if next().is_multiple_of(16) {
+                                assert_eq!(tree.contains(&key), model.get(&key).is_some_and(|count| *count > 0));
+                            }
+                        }
+
+                        model
+                    })
+                })
+                .collect();
+
+            let models = writers.into_iter().map(|writer| writer.join().unwrap()).collect();
+
+            done.store(true, Ordering::SeqCst);
+
+            models
+        });
+
+        let mut expected: Vec<u64> = models
+            .iter()
+            .flat_map(|model| model.iter().flat_map(|(key, count)| std::iter::repeat_n(*key, *count)))
+            .collect();
+
+        expected.sort();
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.len(), expected.len());
+        assert_eq!(tree.to_vec(), expected, "t = {}", t);
+    }
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use std::panic;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use srdb::{Db, Error};
+
+use common::temp_path;
+
+/**
+ * path of fresh closed database, removed first if a previous run left it
+ */
+fn database(name: &str) -> PathBuf {
+    let path = temp_path(name);
+
+    let mut db = Db::create(&path).unwrap();
+
+    db.insert(b"key", b"value").unwrap();
+    db.close().unwrap();
+
+    path
+}
+
+#[test]
+fn second_writer_is_refused() {
+    let path = database("writers");
+    let db = Db::open(&path).unwrap();
+
+    assert!(matches!(Db::open(&path), Err(Error::DatabaseLocked)));
+    assert!(matches!(Db::open_read_only(&path), Err(Error::DatabaseLocked)));
+    assert!(matches!(Db::remove(&path), Err(Error::DatabaseLocked)));
+
+    drop(db);
+
+    Db::open(&path).unwrap().close().unwrap();
+    Db::remove(&path).unwrap();
+}
+
+#[test]
+fn readers_share_lock_and_keep_writer_out() {
+    let path = database("readers");
+    let first = Db::open_read_only(&path).unwrap();
+    let second = Db::open_read_only(&path).unwrap();
+
+    assert_eq!(first.get(b"key").unwrap(), Some(b"value".to_vec()));
+    assert_eq!(second.get(b"key").unwrap(), Some(b"value".to_vec()));
+    assert!(matches!(Db::open(&path), Err(Error::DatabaseLocked)));
+
+    drop(first);
+
+    assert!(matches!(Db::open(&path), Err(Error::DatabaseLocked)));
+
+    drop(second);
+
+    Db::open(&path).unwrap().close().unwrap();
+    Db::remove(&path).unwrap();
+}
+
+#[test]
+fn open_with_timeout_waits_for_release() {
+    let path = database("timeout");
+    let db = Db::open(&path).unwrap();
+    let started = Instant::now();
+
+    assert!(matches!(Db::open_with_lock_timeout(&path, Duration::from_millis(50)), Err(Error::DatabaseLocked)));
+    assert!(started.elapsed() >= Duration::from_millis(50));
+
+    let holder = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        drop(db);
+    });
+
+    let db = Db::open_with_lock_timeout(&path, Duration::from_secs(10)).unwrap();
+
+    holder.join().unwrap();
+
+    assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
+
+#[test]
+fn lock_is_released_when_holder_panics() {
+    let path = database("panic");
+    let held = path.clone();
+
+    let result = panic::catch_unwind(move || {
+        let mut db = Db::open(&held).unwrap();
+
+        db.insert(b"other", b"value").unwrap();
+
+        panic!("holder of lock panics");
+    });
+
+    assert!(result.is_err());
+
+    let db = Db::open(&path).unwrap();
+
+    assert_eq!(db.get(b"other").unwrap(), Some(b"value".to_vec()));
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
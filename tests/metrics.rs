@@ -0,0 +1,150 @@
+#![cfg(feature = "metrics")]
+
+use srdb::{BTree, Metrics};
+
+/**
+ * inserts of 1..=10 in order into tree of t = 2 build
+ *
+ * [4]
+ * ├── [2]
+ * │   ├── [1]
+ * │   └── [3]
+ * └── [6, 8]
+ *     ├── [5]
+ *     ├── [7]
+ *     └── [9, 10]
+ *
+ * every split adds one node, split of root adds new root too, so 8 nodes of height 3 take 5 splits
+ */
+#[test]
+fn metrics_of_inserts_in_order() {
+    let mut tree = BTree::new(2);
+
+    for key in 1..=10u64 {
+        tree.insert(key);
+    }
+
+    let stats = tree.stats();
+
+    assert_eq!((stats.nodes, stats.height), (8, 3));
+    assert_eq!(
+        tree.metrics(),
+        Metrics {
+            splits: 5,
+            merges: 0,
+            borrows: 0,
+            comparisons: 27,
+            visited: 19,
+        }
+    );
+    assert_eq!(tree.metrics().splits as usize, stats.nodes - stats.height);
+    assert_eq!(tree.clone().metrics(), Metrics::default(), "clone starts counting anew");
+}
+
+/**
+ * contains checks bounds of root with two comparisons, binary search of [4] takes 1, [6, 8] is reached right
+ * of 4 so only its min is checked, its search takes 2, min of [7] is checked again and answers without visit
+ * key out of bounds visits no node
+ */
+#[test]
+fn metrics_of_searches() {
+    let mut tree = BTree::new(2);
+
+    for key in 1..=10u64 {
+        tree.insert(key);
+    }
+
+    tree.reset_metrics();
+
+    assert!(tree.contains(7));
+    assert_eq!(
+        tree.metrics(),
+        Metrics {
+            comparisons: 7,
+            visited: 2,
+            ..Metrics::default()
+        }
+    );
+
+    tree.reset_metrics();
+
+    assert!(!tree.contains(100));
+    assert_eq!(
+        tree.metrics(),
+        Metrics {
+            comparisons: 2,
+            ..Metrics::default()
+        }
+    );
+}
+
+/**
+ * inserts of 2, 4, ..., 20 build the tree above with doubled keys, 9 lies between delimeter 8 of root
+ * and min 10 of [12, 16], so it is rejected by bounds of child after visiting root only
+ */
+#[test]
+fn metrics_of_search_in_interior_gap() {
+    let mut tree = BTree::new(2);
+
+    for key in 1..=10u64 {
+        tree.insert(key * 2);
+    }
+
+    tree.reset_metrics();
+
+    assert!(!tree.contains(9));
+    assert_eq!(
+        tree.metrics(),
+        Metrics {
+            comparisons: 4,
+            visited: 1,
+            ..Metrics::default()
+        }
+    );
+}
+
+/**
+ * deletes from the left edge of tree above, merges and borrows after each delete are
+ *
+ * key      1  2  3  4  5  6  7  8  9  10
+ * merges   1  1  1  0  1  0  0  1  0  0
+ * borrows  1  0  0  0  0  0  1  0  0  0
+ *
+ * every merge frees one node and so does every collapse of root, 8 nodes of height 3 end in 1 after 5 merges
+ */
+#[test]
+fn metrics_of_deletes_in_order() {
+    let mut tree = BTree::new(2);
+
+    for key in 1..=10u64 {
+        tree.insert(key);
+    }
+
+    tree.reset_metrics();
+
+    let mut merges = vec![];
+    let mut borrows = vec![];
+
+    for key in 1..=10u64 {
+        let before = tree.metrics();
+
+        assert!(tree.delete(&key));
+
+        merges.push(tree.metrics().merges - before.merges);
+        borrows.push(tree.metrics().borrows - before.borrows);
+    }
+
+    assert_eq!(merges, [1, 1, 1, 0, 1, 0, 0, 1, 0, 0]);
+    assert_eq!(borrows, [1, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+    assert_eq!(
+        tree.metrics(),
+        Metrics {
+            splits: 0,
+            merges: 5,
+            borrows: 2,
+            comparisons: 60,
+            visited: 20,
+        }
+    );
+    assert_eq!(tree.stats().nodes, 1);
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use srdb::{BackgroundFlush, Db, ReadPath, SrdbOptions, SyncMode, VerifyMode};
+
+use common::temp_path;
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn value(i: u32, round: u32) -> Vec<u8> {
+    format!("value {} of key {}", round, i).into_bytes()
+}
+
+#[test]
+fn mapped_reads_see_what_was_written() {
+    let path = temp_path("reads");
+    let mut db = SrdbOptions::new().sync_mode(SyncMode::Off).create(&path).unwrap();
+
+    for i in 0..5000 {
+        db.insert(&key(i), &value(i, 0)).unwrap();
+    }
+
+    db.close().unwrap();
+
+    let db = SrdbOptions::new().read_path(ReadPath::Mmap).open(&path).unwrap();
+
+    assert_eq!(db.read_path(), ReadPath::Mmap);
+
+    for i in (0..5000).step_by(7) {
+        assert_eq!(db.get(&key(i)).unwrap(), Some(value(i, 0)));
+    }
+
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * background flusher commits pages and grows file while handle reads through mapping
+ * and read transactions on other threads read their snapshots, every read sees a consistent state
+ */
+#[test]
+fn concurrent_flush_and_mapped_reads() {
+    let path = temp_path("concurrent");
+    let flush = BackgroundFlush {
+        interval: Duration::from_millis(1),
+        dirty_pages: 4,
+        batch_pages: 4,
+    };
+    let mut db = SrdbOptions::new()
+        .sync_mode(SyncMode::Off)
+        .read_path(ReadPath::Mmap)
+        .background_flush(Some(flush))
+        .create(&path)
+        .unwrap();
+    let mut readers = vec![];
+
+    assert_eq!(db.read_path(), ReadPath::Mmap);
+
+    for round in 0..4 {
+        for i in 0..2000 {
+            db.insert(&key(i), &value(i, round)).unwrap();
+
+            if i % 10 == 0 {
+                let j = i / 2;
+
+                assert_eq!(db.get(&key(j)).unwrap(), Some(value(j, round)), "round {}, key {}", round, j);
+            }
+        }
+
+        let txn = db.begin_read().unwrap();
+
+        readers.push(thread::spawn(move || {
+            for i in 0..2000 {
+                assert_eq!(txn.get(&key(i)).unwrap(), Some(value(i, round)), "round {}, key {}", round, i);
+            }
+        }));
+    }
+
+    for reader in readers {
+        reader.join().unwrap();
+    }
+
+    db.close().unwrap();
+
+    let db = SrdbOptions::new().read_path(ReadPath::Mmap).open(&path).unwrap();
+
+    for i in 0..2000 {
+        assert_eq!(db.get(&key(i)).unwrap(), Some(value(i, 3)));
+    }
+
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
@@ -0,0 +1,129 @@
+use std::sync::{Arc, Mutex};
+
+use srdb::{BTree, RootChange, Side, TreeObserver};
+
+/**
+ * change as observer saw it, with keys copied at the moment of call
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Recorded {
+    Split(usize, Vec<u64>, u64, Vec<u64>),
+    Merge(usize, Vec<u64>, u64, Vec<u64>),
+    Rotate(usize, Side, u64, u64),
+    Root(RootChange),
+}
+
+use Recorded::*;
+
+/**
+ * observer appending changes to log shared with test, tree owns observer itself
+ */
+struct Recorder(Arc<Mutex<Vec<Recorded>>>);
+
+impl TreeObserver<u64> for Recorder {
+    fn on_split(&mut self, depth: usize, left: &[u64], median: &u64, right: &[u64]) {
+        self.0.lock().unwrap().push(Split(depth, left.to_vec(), *median, right.to_vec()));
+    }
+
+    fn on_merge(&mut self, depth: usize, left: &[u64], separator: &u64, right: &[u64]) {
+        self.0.lock().unwrap().push(Merge(depth, left.to_vec(), *separator, right.to_vec()));
+    }
+
+    fn on_rotate(&mut self, depth: usize, side: Side, up: &u64, down: &u64) {
+        self.0.lock().unwrap().push(Rotate(depth, side, *up, *down));
+    }
+
+    fn on_root_change(&mut self, change: RootChange) {
+        self.0.lock().unwrap().push(Root(change));
+    }
+}
+
+/**
+ * tree of t with recorder and its log
+ */
+fn recorded(t: usize) -> (BTree<u64>, Arc<Mutex<Vec<Recorded>>>) {
+    let log = Arc::new(Mutex::new(vec![]));
+    let mut tree = BTree::new(t);
+
+    tree.set_observer(Box::new(Recorder(log.clone())));
+
+    (tree, log)
+}
+
+fn take(log: &Mutex<Vec<Recorded>>) -> Vec<Recorded> {
+    std::mem::take(&mut *log.lock().unwrap())
+}
+
+/**
+ * changes after each operation of script on tree of t = 2, ascending inserts split right edge,
+ * 9th key splits full root [2, 4, 6], so the tree has height 3 when deletes begin
+ */
+#[test]
+fn observer_sees_exact_sequence_of_changes() {
+    let (mut tree, log) = recorded(2);
+    let mut changes = vec![];
+
+    for key in 1..=10 {
+        tree.insert(key);
+        changes.push(take(&log));
+    }
+
+    for key in [5, 1, 2, 3, 4, 6, 7, 8, 9, 10] {
+        assert!(tree.delete(&key));
+        changes.push(take(&log));
+    }
+
+    assert_eq!(
+        changes,
+        [
+            vec![],
+            vec![],
+            vec![],
+            vec![Split(1, vec![1], 2, vec![3]), Root(RootChange::Grow)],
+            vec![],
+            vec![Split(1, vec![3], 4, vec![5])],
+            vec![],
+            vec![Split(1, vec![5], 6, vec![7])],
+            vec![Split(1, vec![2], 4, vec![6]), Root(RootChange::Grow)],
+            vec![Split(2, vec![7], 8, vec![9])],
+            vec![Merge(2, vec![5], 6, vec![7])],
+            vec![Merge(1, vec![2], 4, vec![8]), Merge(2, vec![1], 2, vec![3]), Root(RootChange::Collapse)],
+            vec![],
+            vec![Rotate(1, Side::Right, 6, 4)],
+            vec![Merge(1, vec![4], 6, vec![7])],
+            vec![],
+            vec![Rotate(1, Side::Right, 9, 8)],
+            vec![Merge(1, vec![8], 9, vec![10]), Root(RootChange::Collapse)],
+            vec![],
+            vec![],
+        ]
+    );
+    assert!(tree.is_empty());
+}
+
+/**
+ * clone has no observer, taken observer is told nothing more
+ */
+#[test]
+fn observer_stays_with_its_tree() {
+    let (mut tree, log) = recorded(2);
+
+    tree.insert_batch((0..100).collect());
+
+    let changes = take(&log).len();
+    let mut copy = tree.clone();
+
+    assert!(changes > 20, "{} changes of batch", changes);
+
+    for key in 100..200 {
+        copy.insert(key);
+    }
+
+    assert!(tree.take_observer().is_some());
+
+    for key in 0..100 {
+        tree.delete(&key);
+    }
+
+    assert_eq!(take(&log), []);
+}
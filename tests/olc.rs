@@ -0,0 +1,230 @@
+#![cfg(feature = "std")]
+
+use srdb::OlcBTree;
+
+#[cfg(loom)]
+fn tree_of(t: usize, keys: &[u32]) -> OlcBTree<u32> {
+    let tree = OlcBTree::new(t);
+
+    for key in keys {
+        tree.insert(*key);
+    }
+
+    tree
+}
+
+/**
+ * insert of writer splits full root, loom runs reader against every interleaving of versions,
+ * reader sees tree before or after split and never half of it
+ */
+#[cfg(loom)]
+#[test]
+fn loom_reader_during_root_split() {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    let mut builder = loom::model::Builder::new();
+
+    builder.preemption_bound = Some(2);
+    builder.check(|| {
+        let tree = Arc::new(tree_of(2, &[10, 20, 30]));
+        let writer = {
+            let tree = tree.clone();
+
+            thread::spawn(move || tree.insert(40))
+        };
+
+        assert!(tree.contains(&20));
+        assert!(!tree.contains(&25));
+
+        let keys = tree.to_vec();
+
+        assert!(keys == [10, 20, 30] || keys == [10, 20, 30, 40], "{:?}", keys);
+
+        writer.join().unwrap();
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.to_vec(), [10, 20, 30, 40]);
+    });
+}
+
+/**
+ * delete of writer merges leaves and collapses root, retired nodes are freed at once under loom,
+ * so reader also runs against writer waiting for its epoch
+ */
+#[cfg(loom)]
+#[test]
+fn loom_reader_during_merge_and_root_collapse() {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    let mut builder = loom::model::Builder::new();
+
+    builder.preemption_bound = Some(2);
+    builder.check(|| {
+        let tree = Arc::new(tree_of(2, &[10, 20, 30, 40]));
+
+        assert!(tree.delete(&40));
+
+        let writer = {
+            let tree = tree.clone();
+
+            thread::spawn(move || assert!(tree.delete(&10)))
+        };
+
+        let keys = tree.range(15..);
+
+        assert_eq!(keys, [20, 30]);
+        assert!(tree.contains(&30));
+
+        writer.join().unwrap();
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.to_vec(), [20, 30]);
+    });
+}
+
+/**
+ * two writers and reader, writers wait for each other on mutex and reader for both of them
+ */
+#[cfg(loom)]
+#[test]
+fn loom_two_writers_and_reader() {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    let mut builder = loom::model::Builder::new();
+
+    builder.preemption_bound = Some(1);
+    builder.check(|| {
+        let tree = Arc::new(tree_of(2, &[10, 20, 30]));
+        let writers: Vec<_> = [(40, 10), (50, 20)]
+            .into_iter()
+            .map(|(insert, delete)| {
+                let tree = tree.clone();
+
+                thread::spawn(move || {
+                    tree.insert(insert);
+                    assert!(tree.delete(&delete));
+                })
+            })
+            .collect();
+
+        assert!(tree.contains(&30));
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.to_vec(), [30, 40, 50]);
+    });
+}
+
+#[cfg(not(loom))]
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * writers own keys equal to their number modulo writers and keep exact model of them,
+ * readers check keys nobody writes, which must always be there, and order of ranges meanwhile
+ */
+#[cfg(not(loom))]
+#[test]
+fn writers_and_optimistic_readers_against_model() {
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    const WRITERS: u64 = 6;
+    const STABLE: u64 = 1_000_000;
+
+    for t in [2, 3, 16] {
+        let tree = OlcBTree::new(t);
+        let done = AtomicBool::new(false);
+
+        for key in 0..200 {
+            tree.insert(STABLE + key * 2);
+        }
+
+        let models: Vec<BTreeMap<u64, usize>> = thread::scope(|scope| {
+            for reader in 0..4 {
+                let (tree, done) = (&tree, &done);
+
+                scope.spawn(move || {
+                    let mut next = lcg(reader);
+
+                    while !done.load(Ordering::SeqCst) {
+                        let stable = STABLE + next() % 200 * 2;
+
+                        assert!(tree.contains(&stable), "{} is lost", stable);
+                        assert!(!tree.contains(&(stable + 1)));
+
+                        let keys = tree.range(STABLE - 500..);
+
+                        assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", keys);
+                        assert!(keys.ends_with(&(0..200).map(|key| STABLE + key * 2).collect::<Vec<_>>()));
+                    }
+                });
+            }
+
+            let writers: Vec<_> = (0..WRITERS)
+                .map(|writer| {
+                    let tree = &tree;
+
+                    scope.spawn(move || {
+                        let mut next = lcg(writer + 100 * t as u64);
+                        let mut model = BTreeMap::new();
+
+                        for _ in 0..2000 {
+                            let key = next() % 400 * WRITERS + writer;
+
+                            if next().is_multiple_of(3) {
+                                let present = model.get(&key).is_some_and(|count| *count > 0);
+
+                                assert_eq!(tree.delete(&key), present, "delete of {}", key);
+
+                                if present {
+                                    *model.get_mut(&key).unwrap() -= 1;
+                                }
+                            } else {
+                                tree.insert(key);
+                                *model.entry(key).or_insert(0) += 1;
+                            }
+
+                            if next().is_multiple_of(8) {
+                                assert_eq!(tree.contains(&key), model.get(&key).is_some_and(|count| *count > 0));
+                            }
+                        }
+
+                        model
+                    })
+                })
+                .collect();
+
+            let models = writers.into_iter().map(|writer| writer.join().unwrap()).collect();
+
+            done.store(true, Ordering::SeqCst);
+
+            models
+        });
+
+        let mut expected: Vec<u64> = models
+            .iter()
+            .flat_map(|model| model.iter().flat_map(|(key, count)| std::iter::repeat_n(*key, *count)))
+            .chain((0..200).map(|key| STABLE + key * 2))
+            .collect();
+
+        expected.sort();
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.len(), expected.len());
+        assert_eq!(tree.to_vec(), expected, "t = {}", t);
+    }
+}
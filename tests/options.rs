@@ -0,0 +1,143 @@
+#![cfg(feature = "std")]
+
+use std::time::Duration;
+
+use srdb::{
+    max_t, BackgroundFlush, Db, Error, MemStorage, ReadPath, SrdbOptions, SyncMode, DEFAULT_CACHE_PAGES,
+    DEFAULT_WAL_LIMIT, DEFAULT_WAL_SEGMENT_SIZE, MAX_ENTRY_SIZE, MIN_CACHE_PAGES, PAGE_SIZE,
+};
+
+fn create(options: &SrdbOptions) -> Result<Db, Error> {
+    options.create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new()))
+}
+
+/**
+ * closed database created with options, its file and log
+ */
+fn created(options: &SrdbOptions) -> (MemStorage, MemStorage) {
+    let (storage, wal) = (MemStorage::new(), MemStorage::new());
+    let mut db = options.create_with_storage(Box::new(storage.clone()), Box::new(wal.clone())).unwrap();
+
+    db.insert(b"key", b"value").unwrap();
+    db.close().unwrap();
+
+    (storage, wal)
+}
+
+fn open(options: &SrdbOptions, (storage, wal): &(MemStorage, MemStorage)) -> Result<Db, Error> {
+    options.open_with_storage(Box::new(storage.clone()), Box::new(wal.clone()))
+}
+
+fn is_invalid(result: Result<Db, Error>) -> bool {
+    matches!(result, Err(Error::InvalidOptions(_)))
+}
+
+#[test]
+fn defaults() {
+    let db = create(&SrdbOptions::new()).unwrap();
+
+    assert_eq!(SrdbOptions::new(), SrdbOptions::default());
+    assert_eq!(db.disk_stats().page_size, PAGE_SIZE);
+    assert_eq!(db.t(), max_t(PAGE_SIZE - 4, MAX_ENTRY_SIZE));
+    assert_eq!(db.cache().capacity(), DEFAULT_CACHE_PAGES);
+    assert_eq!(db.sync_mode(), SyncMode::Always);
+    assert_eq!(db.read_path(), ReadPath::Io);
+    assert_eq!(db.wal_limit(), Some(DEFAULT_WAL_LIMIT));
+    assert_eq!(db.wal_segment_size(), Some(DEFAULT_WAL_SEGMENT_SIZE));
+    assert_eq!(db.background_flush(), None);
+    assert!(!db.is_read_only());
+    assert!(db.wait_for_writer());
+}
+
+#[test]
+fn invalid_combinations_are_rejected() {
+    let flush = Some(BackgroundFlush::default());
+
+    assert!(is_invalid(create(&SrdbOptions::new().cache_pages(MIN_CACHE_PAGES - 1))));
+    assert!(is_invalid(create(&SrdbOptions::new().read_only(true))));
+    assert!(is_invalid(create(&SrdbOptions::new().verify(false))));
+    assert!(is_invalid(create(&SrdbOptions::new().lock(false))));
+    assert!(is_invalid(create(&SrdbOptions::new().branching_factor(1))));
+    assert!(is_invalid(create(&SrdbOptions::new().branching_factor(max_t(PAGE_SIZE - 4, MAX_ENTRY_SIZE) + 1))));
+
+    for page_size in [0, 1000, 3000, 256, 1 << 30] {
+        assert!(is_invalid(create(&SrdbOptions::new().page_size(page_size))), "page size {}", page_size);
+    }
+
+    let files = created(&SrdbOptions::new());
+
+    assert!(is_invalid(open(&SrdbOptions::new().read_only(true).background_flush(flush), &files)));
+    assert!(is_invalid(open(&SrdbOptions::new().read_only(true).replication_backlog(Some(1 << 20)), &files)));
+    assert!(is_invalid(open(&SrdbOptions::new().cache_pages(0), &files)));
+}
+
+/**
+ * options chosen at creation are read back from file, open needs none of them
+ * and fails if one is set to something else
+ */
+#[test]
+fn creation_options_round_trip_through_header() {
+    let options = SrdbOptions::new().page_size(2 * PAGE_SIZE).branching_factor(10);
+    let files = created(&options);
+    let db = open(&SrdbOptions::new(), &files).unwrap();
+
+    assert_eq!(db.disk_stats().page_size, 2 * PAGE_SIZE);
+    assert_eq!(db.t(), 10);
+    assert_eq!(db.get(b"key").unwrap(), Some(b"value".to_vec()));
+
+    drop(db);
+
+    assert_eq!(open(&options, &files).unwrap().t(), 10);
+    assert!(matches!(
+        open(&SrdbOptions::new().page_size(PAGE_SIZE), &files),
+        Err(Error::PageSizeMismatch { expected, found }) if expected == PAGE_SIZE && found == 2 * PAGE_SIZE
+    ));
+    assert!(is_invalid(open(&SrdbOptions::new().branching_factor(11), &files)));
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn compression_is_chosen_at_creation() {
+    let files = created(&SrdbOptions::new().compression(false));
+
+    assert!(open(&SrdbOptions::new(), &files).is_ok());
+    assert!(is_invalid(open(&SrdbOptions::new().compression(true), &files)));
+
+    let files = created(&SrdbOptions::new());
+
+    assert!(open(&SrdbOptions::new(), &files).is_ok());
+    assert!(open(&SrdbOptions::new().compression(true), &files).is_ok());
+    assert!(is_invalid(open(&SrdbOptions::new().compression(false), &files)));
+}
+
+#[test]
+fn handle_options_apply_to_one_open() {
+    let files = created(&SrdbOptions::new().sync_mode(SyncMode::Off).cache_pages(64));
+    let flush = BackgroundFlush {
+        interval: Duration::from_millis(10),
+        ..BackgroundFlush::default()
+    };
+    let options = SrdbOptions::new()
+        .cache_pages(MIN_CACHE_PAGES)
+        .sync_mode(SyncMode::OnCommit)
+        .wal_limit(None)
+        .wal_segment_size(Some(1 << 20))
+        .background_flush(Some(flush))
+        .wait_for_writer(false);
+    let db = open(&options, &files).unwrap();
+
+    assert_eq!(db.cache().capacity(), MIN_CACHE_PAGES);
+    assert_eq!(db.sync_mode(), SyncMode::OnCommit);
+    assert_eq!(db.wal_limit(), None);
+    assert_eq!(db.wal_segment_size(), Some(1 << 20));
+    assert_eq!(db.background_flush(), Some(flush));
+    assert!(!db.wait_for_writer());
+
+    drop(db);
+
+    let db = open(&SrdbOptions::new(), &files).unwrap();
+
+    assert_eq!(db.cache().capacity(), DEFAULT_CACHE_PAGES);
+    assert_eq!(db.sync_mode(), SyncMode::Always);
+    assert_eq!(db.background_flush(), None);
+}
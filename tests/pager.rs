@@ -0,0 +1,144 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use srdb::{Error, MemStorage, Pager, HEADER_PAGE, PAGE_SIZE};
+
+use common::temp_path;
+
+fn filled(pager: &Pager, byte: u8) -> Vec<u8> {
+    vec![byte; pager.page_size()]
+}
+
+fn read(pager: &mut Pager, page_id: u32) -> Vec<u8> {
+    let mut buf = vec![0; pager.page_size()];
+
+    pager.read_page(page_id, &mut buf).unwrap();
+
+    buf
+}
+
+#[test]
+fn allocates_pages_after_header() {
+    let mut pager = Pager::create_with_storage(Box::new(MemStorage::new())).unwrap();
+
+    assert_eq!(pager.page_count(), 1, "header page is allocated at creation");
+    assert_eq!(pager.file_page_size(), PAGE_SIZE);
+    assert_eq!(pager.page_size(), PAGE_SIZE - 4, "last bytes of page hold its checksum");
+
+    for expected in 1..=5 {
+        assert_eq!(pager.allocate_page().unwrap(), expected);
+    }
+
+    assert_eq!(read(&mut pager, 3), filled(&pager, 0), "new page reads as zeros");
+
+    for page_id in 0..6 {
+        pager.write_page(page_id, &filled(&pager, page_id as u8 + 1)).unwrap();
+    }
+
+    for page_id in 0..6 {
+        assert_eq!(read(&mut pager, page_id), filled(&pager, page_id as u8 + 1));
+    }
+}
+
+#[test]
+fn ids_are_bounds_checked() {
+    let mut pager = Pager::create_with_storage(Box::new(MemStorage::new())).unwrap();
+    let mut buf = filled(&pager, 0);
+
+    pager.allocate_page().unwrap();
+
+    assert!(matches!(pager.read_page(2, &mut buf), Err(Error::PageOutOfBounds { page_id: 2, page_count: 2 })));
+    assert!(matches!(pager.write_page(7, &buf), Err(Error::PageOutOfBounds { page_id: 7, page_count: 2 })));
+    assert!(matches!(pager.free_page(2), Err(Error::PageOutOfBounds { .. })));
+    assert!(matches!(pager.free_page(HEADER_PAGE), Err(Error::ReservedPage(HEADER_PAGE))));
+}
+
+#[test]
+fn freed_pages_are_reused() {
+    let mut pager = Pager::create_with_storage(Box::new(MemStorage::new())).unwrap();
+
+    for _ in 0..5 {
+        pager.allocate_page().unwrap();
+    }
+
+    pager.free_page(2).unwrap();
+    pager.free_page(4).unwrap();
+
+    let mut reused = [pager.allocate_page().unwrap(), pager.allocate_page().unwrap()];
+
+    reused.sort();
+
+    assert_eq!(reused, [2, 4]);
+    assert_eq!(pager.page_count(), 6, "file does not grow while freed pages are left");
+    assert_eq!(pager.allocate_page().unwrap(), 6);
+}
+
+#[test]
+fn committed_pages_persist_across_reopen() {
+    let storage = MemStorage::new();
+    let mut pager = Pager::create_with_storage(Box::new(storage.clone())).unwrap();
+
+    for page_id in 1..=100 {
+        assert_eq!(pager.allocate_page().unwrap(), page_id);
+        pager.write_page(page_id, &filled(&pager, page_id as u8)).unwrap();
+    }
+
+    pager.commit(true).unwrap();
+    pager.write_page(7, &filled(&pager, 0xff)).unwrap();
+    pager.allocate_page().unwrap();
+    drop(pager);
+
+    let mut pager = Pager::open_with_storage(Box::new(storage.clone())).unwrap();
+
+    assert_eq!(pager.page_count(), 101, "pages allocated after commit are lost");
+    assert_eq!(pager.generation(), 2);
+
+    for page_id in 1..=100 {
+        assert_eq!(read(&mut pager, page_id), filled(&pager, page_id as u8), "page {}", page_id);
+    }
+
+    pager.write_page(7, &filled(&pager, 0xee)).unwrap();
+    pager.commit(true).unwrap();
+    storage.crash();
+
+    let mut pager = Pager::open_with_storage(Box::new(storage)).unwrap();
+
+    assert_eq!(read(&mut pager, 7), filled(&pager, 0xee));
+}
+
+#[test]
+fn file_reopens_with_its_page_size() {
+    let path = temp_path("page-size");
+    let mut pager = Pager::create(&path).unwrap();
+
+    pager.allocate_page().unwrap();
+    pager.write_page(1, &filled(&pager, 42)).unwrap();
+    pager.commit(true).unwrap();
+    drop(pager);
+
+    assert!(matches!(Pager::create(&path), Err(Error::Io(_))), "create does not overwrite file");
+
+    let mut pager = Pager::open(&path).unwrap();
+
+    assert_eq!(read(&mut pager, 1), filled(&pager, 42));
+    std::fs::remove_file(&path).unwrap();
+
+    let storage = MemStorage::new();
+    let mut pager = Pager::create_with_page_size(Box::new(storage.clone()), 16384).unwrap();
+
+    pager.allocate_page().unwrap();
+    pager.write_page(1, &filled(&pager, 9)).unwrap();
+    pager.commit(true).unwrap();
+
+    let mut pager = Pager::open_with_storage(Box::new(storage)).unwrap();
+
+    assert_eq!(pager.file_page_size(), 16384);
+    assert_eq!(read(&mut pager, 1), filled(&pager, 9));
+
+    for page_size in [0, 512, 4000, 131072] {
+        let result = Pager::create_with_page_size(Box::new(MemStorage::new()), page_size);
+
+        assert!(matches!(result, Err(Error::InvalidOptions(_))), "page size {}", page_size);
+    }
+}
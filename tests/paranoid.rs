@@ -0,0 +1,77 @@
+#![cfg(all(srdb_delete_bug, any(debug_assertions, feature = "paranoid")))]
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use srdb::{BTree, SelfTestOp, Side, TreeObserver};
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * counts borrows from left sibling, the only change the bug breaks
+ */
+struct LeftBorrows(Arc<Mutex<usize>>);
+
+impl TreeObserver<u64> for LeftBorrows {
+    fn on_rotate(&mut self, _depth: usize, side: Side, _up: &u64, _down: &u64) {
+        if side == Side::Left {
+            *self.0.lock().unwrap() += 1;
+        }
+    }
+}
+
+/**
+ * with cfg srdb_delete_bug borrow from left puts key of parent at the end of node instead of its front,
+ * validation of touched nodes panics in the very delete which borrows first, ops before it pass
+ */
+#[test]
+fn validation_panics_at_first_broken_delete() {
+    for t in [2, 3, 5] {
+        let borrows = Arc::new(Mutex::new(0));
+        let mut tree = BTree::new(t);
+        let mut next = lcg(t as u64);
+
+        tree.set_observer(Box::new(LeftBorrows(borrows.clone())));
+
+        let (op, message) = (0..10_000)
+            .find_map(|op| {
+                let key = next() % 500;
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    if next().is_multiple_of(2) {
+                        tree.insert(key);
+                    } else {
+                        tree.delete(&key);
+                    }
+                }));
+
+                let message = result.err().map(|payload| *payload.downcast::<String>().unwrap());
+
+                assert_eq!(message.is_some(), *borrows.lock().unwrap() > 0, "t = {}, op {}", t, op);
+
+                message.map(|message| (op, message))
+            })
+            .expect("script borrows from left");
+
+        assert!(message.starts_with("delete broke tree: node "), "t = {}, op {}: {}", t, op, message);
+        assert!(message.contains("keys are not sorted"), "{}", message);
+    }
+}
+
+/**
+ * self test reports the bug with repro ending in delete which borrows from left
+ */
+#[test]
+fn self_test_finds_delete_bug() {
+    let failure = BTree::self_test(2, 1, 2000).unwrap_err();
+
+    assert!(failure.message.starts_with("panicked: delete broke tree"), "{}", failure);
+    assert!(matches!(failure.repro.last(), Some(SelfTestOp::Delete(_))), "{}", failure);
+    assert!(failure.repro.len() < 20, "{}", failure);
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "postcard")]
+
+use srdb::{BTree, SnapshotError};
+
+/**
+ * encoding of small trees pinned byte by byte, any change of it breaks files of users
+ */
+#[test]
+fn encoding_is_stable() {
+    let numbers = BTree::from_sorted(2, vec![1u32, 2, 300], 1.0);
+    let numbers_bytes = [
+        0x02, 0x03, // t, key count
+        0x04, 0x01, 0x00, 0x00, 0x00, // 1
+        0x04, 0x02, 0x00, 0x00, 0x00, // 2
+        0x04, 0x2c, 0x01, 0x00, 0x00, // 300
+    ];
+
+    assert_eq!(numbers.to_bytes_postcard(), numbers_bytes);
+    assert_eq!(BTree::<u32>::from_bytes_postcard(&numbers_bytes).unwrap().to_vec(), [1, 2, 300]);
+
+    let words = BTree::from_sorted(200, vec!["a".to_string(), "bc".to_string()], 1.0);
+    let words_bytes = [0xc8, 0x01, 0x02, 0x01, b'a', 0x02, b'b', b'c'];
+
+    assert_eq!(words.to_bytes_postcard(), words_bytes);
+    assert_eq!(BTree::<String>::from_bytes_postcard(&words_bytes).unwrap().t(), 200);
+    assert_eq!(BTree::<u8>::new(3).to_bytes_postcard(), [0x03, 0x00]);
+}
+
+#[test]
+fn round_trip() {
+    for t in [2, 7, 300] {
+        for n in [0, 1, 2000] {
+            let tree = BTree::from_sorted(t, (0..n).map(|i| i * 1_000_003).collect::<Vec<u64>>(), 0.8);
+            let loaded = BTree::<u64>::from_bytes_postcard(&tree.to_bytes_postcard()).unwrap();
+
+            loaded.check_invariants().unwrap();
+            assert_eq!(loaded.t(), t);
+            assert_eq!(loaded.to_vec(), tree.to_vec());
+        }
+    }
+}
+
+#[test]
+fn huge_t_is_corrupt() {
+    let inputs: [&[u8]; 4] = [
+        &[0xff, 0xff, 0xff, 0xff, 0x0f, 0x00],
+        &[0x81, 0x20, 0x00],
+        &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0x00],
+        &[0x01, 0x00],
+    ];
+
+    for bytes in inputs {
+        match BTree::<u64>::from_bytes_postcard(bytes) {
+            Err(SnapshotError::Corrupt(reason)) => assert!(reason.contains("branching factor"), "{}", reason),
+            result => panic!("{:02x?} gave {:?}", bytes, result.map(|tree| tree.t())),
+        }
+    }
+
+    assert_eq!(BTree::<u64>::from_bytes_postcard(&[0x80, 0x20, 0x00]).unwrap().t(), srdb::MAX_T);
+}
+
+#[test]
+fn broken_input_is_typed_error() {
+    let bytes = BTree::from_sorted(3, (0..50u64).collect(), 1.0).to_bytes_postcard();
+
+    for len in 0..bytes.len() {
+        assert!(matches!(BTree::<u64>::from_bytes_postcard(&bytes[..len]), Err(SnapshotError::Truncated)), "len {}", len);
+    }
+
+    let mut longer = bytes.clone();
+
+    longer.push(0);
+
+    assert!(matches!(BTree::<u64>::from_bytes_postcard(&longer), Err(SnapshotError::Corrupt(_))));
+
+    let mut unordered = bytes.clone();
+
+    unordered[3] = 0xff;
+
+    assert!(matches!(BTree::<u64>::from_bytes_postcard(&unordered), Err(SnapshotError::Corrupt(_))));
+
+    let mut short_key = bytes.clone();
+
+    short_key[2] = 0x07;
+
+    assert!(BTree::<u64>::from_bytes_postcard(&short_key).is_err());
+    assert!(matches!(
+        BTree::<u64>::from_bytes_postcard(&[0x02, 0x01, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01]),
+        Err(SnapshotError::Corrupt(_))
+    ));
+}
@@ -0,0 +1,249 @@
+#![cfg(feature = "std")]
+
+use std::io::{self, BufRead, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use srdb::{
+    bootstrap_follower, follow_primary, serve_follower, Db, Error, MemStorage, SrdbOptions, SyncMode, VerifyMode,
+    WriteBatch,
+};
+
+/**
+ * kind byte of frame carrying batch, see replication module
+ */
+const BATCH: u8 = 1;
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * writing half of in-memory pipe standing in for tcp, primary writes every frame at once,
+ * so every message is one frame and seq of the last batch shipped is read off its header
+ */
+struct PipeWriter {
+    sender: Sender<Vec<u8>>,
+    shipped: Arc<AtomicU64>,
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() >= 13 && buf[0] == BATCH {
+            self.shipped.store(u64::from_le_bytes(buf[1..9].try_into().unwrap()), Ordering::SeqCst);
+        }
+
+        self.sender.send(buf.to_vec()).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/**
+ * reading half of pipe, input ends once writer is dropped
+ */
+struct PipeReader {
+    receiver: Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.fill_buf()?.read(buf)?;
+
+        self.consume(n);
+
+        Ok(n)
+    }
+}
+
+impl BufRead for PipeReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.buffer.len() {
+            self.buffer = self.receiver.recv().unwrap_or_default();
+            self.pos = 0;
+        }
+
+        Ok(&self.buffer[self.pos..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos += amount;
+    }
+}
+
+fn pipe(shipped: &Arc<AtomicU64>) -> (PipeWriter, PipeReader) {
+    let (sender, receiver) = channel();
+
+    (PipeWriter { sender, shipped: shipped.clone() }, PipeReader { receiver, buffer: vec![], pos: 0 })
+}
+
+fn memory(options: SrdbOptions) -> Db {
+    let options = options.sync_mode(SyncMode::Off);
+
+    options.create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new())).unwrap()
+}
+
+fn primary(backlog: u64) -> Arc<RwLock<Db>> {
+    let mut db = memory(SrdbOptions::new().replication_backlog(Some(backlog)));
+
+    db.create_table("events").unwrap();
+
+    Arc::new(RwLock::new(db))
+}
+
+/**
+ * one connection of follower to primary over pipes, primary ends it once batch at target is shipped,
+ * so follower has applied everything up to target when it returns
+ */
+fn session(primary: &RwLock<Db>, follower: &mut Db, target: u64) -> Result<(), Error> {
+    let start = follower.seq();
+    let shipped = Arc::new(AtomicU64::new(0));
+    let (request, requests) = pipe(&shipped);
+    let (frames, received) = pipe(&shipped);
+
+    thread::scope(|scope| {
+        let shipped = &shipped;
+        let server = scope.spawn(move || {
+            serve_follower(primary, requests, frames, || shipped.load(Ordering::SeqCst).max(start) >= target)
+        });
+        let result = follow_primary(follower, received, request, || false);
+
+        server.join().unwrap().unwrap();
+
+        result
+    })
+}
+
+/**
+ * batches of puts and deletes in default tree and table, some of them with values over a page
+ */
+fn burst(db: &RwLock<Db>, next: &mut impl FnMut() -> u64, batches: u64) {
+    for _ in 0..batches {
+        let mut batch = WriteBatch::new();
+
+        for _ in 0..1 + next() % 20 {
+            let key = format!("key{:05}", next() % 3000).into_bytes();
+            let value = format!("value {}", next()).repeat(if next().is_multiple_of(50) { 1000 } else { 1 });
+
+            match next() % 4 {
+                0 => batch.delete(&key),
+                1 => batch.put_in("events", &key, value.as_bytes()),
+                2 => batch.delete_in("events", &key),
+                _ => batch.put(&key, value.as_bytes()),
+            }
+        }
+
+        db.write().unwrap().write(&batch).unwrap();
+    }
+}
+
+fn assert_converged(primary: &RwLock<Db>, follower: &mut Db) {
+    let mut primary = primary.write().unwrap();
+
+    assert_eq!(follower.seq(), primary.seq());
+    assert_eq!(follower.digests().unwrap(), primary.digests().unwrap());
+    assert!(follower.to_vec().unwrap() == primary.to_vec().unwrap());
+    assert!(follower.table("events").unwrap().to_vec().unwrap() == primary.table("events").unwrap().to_vec().unwrap());
+    assert!(follower.verify(VerifyMode::Full).is_ok());
+}
+
+/**
+ * follower reconnects after every burst and resumes from its seq, then follows while primary is written
+ */
+#[test]
+fn follower_converges_after_bursts_of_writes() {
+    let primary = primary(64 << 20);
+    let mut follower = memory(SrdbOptions::new());
+    let mut next = lcg(0);
+
+    for round in 0..8 {
+        let batches = 1 + next() % 150;
+
+        burst(&primary, &mut next, batches);
+
+        let target = primary.read().unwrap().seq();
+
+        session(&primary, &mut follower, target).unwrap();
+        assert_converged(&primary, &mut follower);
+
+        if round == 3 {
+            session(&primary, &mut follower, target).unwrap();
+            assert_converged(&primary, &mut follower);
+        }
+    }
+
+    let target = primary.read().unwrap().seq() + 300;
+
+    thread::scope(|scope| {
+        let writer = scope.spawn(|| burst(&primary, &mut lcg(1), 300));
+
+        session(&primary, &mut follower, target).unwrap();
+        writer.join().unwrap();
+    });
+
+    assert_converged(&primary, &mut follower);
+}
+
+/**
+ * follower behind more than backlog holds is told to resync, copy of primary taken over pipe catches it up
+ */
+#[test]
+fn follower_behind_backlog_resyncs_from_snapshot() {
+    let primary = primary(16 << 10);
+    let mut follower = memory(SrdbOptions::new());
+    let mut next = lcg(2);
+
+    burst(&primary, &mut next, 20);
+
+    let target = primary.read().unwrap().seq();
+
+    session(&primary, &mut follower, target).unwrap();
+    burst(&primary, &mut next, 500);
+
+    let target = primary.read().unwrap().seq();
+
+    match session(&primary, &mut follower, target) {
+        Err(Error::ResyncNeeded(_)) => {}
+        result => panic!("{:?}", result.map(|()| follower.seq())),
+    }
+
+    let path = std::env::temp_dir().join(format!("srdb-replication-resync-{}", std::process::id()));
+
+    let _ = Db::remove(&path);
+
+    let shipped = Arc::new(AtomicU64::new(0));
+    let (request, requests) = pipe(&shipped);
+    let (frames, received) = pipe(&shipped);
+    let server = {
+        let primary = primary.clone();
+
+        thread::spawn(move || serve_follower(&primary, requests, frames, || false))
+    };
+    let mut follower = bootstrap_follower(&path, &SrdbOptions::new(), received, request).unwrap();
+
+    server.join().unwrap().unwrap();
+    assert_converged(&primary, &mut follower);
+
+    burst(&primary, &mut next, 5);
+
+    let target = primary.read().unwrap().seq();
+
+    session(&primary, &mut follower, target).unwrap();
+    assert_converged(&primary, &mut follower);
+
+    drop(follower);
+    Db::remove(&path).unwrap();
+}
@@ -0,0 +1,243 @@
+#![cfg(feature = "serde")]
+
+use serde::{Deserialize, Serialize};
+use srdb::{BTree, SharedMap};
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct Point {
+    x: i32,
+    y: i32,
+    name: String,
+}
+
+impl std::fmt::Display for Point {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}, {})", self.name, self.x, self.y)
+    }
+}
+
+fn points(n: i32) -> Vec<Point> {
+    (0..n).map(|i| Point { x: i % 7, y: -i, name: format!("p{}", i) }).collect()
+}
+
+fn words(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("w{}", i * 7919 % 1000)).collect()
+}
+
+/**
+ * tree built key by key, so its nodes are shaped differently from bulk loaded one of deserialize
+ */
+fn inserted<T: Ord + std::fmt::Debug>(t: usize, keys: Vec<T>) -> BTree<T> {
+    let mut tree = BTree::new(t);
+
+    keys.into_iter().for_each(|key| tree.insert(key));
+
+    tree
+}
+
+fn assert_same<T: Ord + Clone + std::fmt::Debug>(a: &BTree<T>, b: &BTree<T>) {
+    b.check_invariants().unwrap();
+    assert_eq!(a.t(), b.t());
+    assert_eq!(a.len(), b.len());
+    assert_eq!(a.to_vec(), b.to_vec());
+}
+
+#[test]
+fn json_round_trip() {
+    for n in [0, 1, 100, 1000] {
+        let tree = inserted(3, words(n));
+        let json = serde_json::to_string(&tree).unwrap();
+
+        assert_same(&tree, &serde_json::from_str(&json).unwrap());
+
+        let tree = inserted(2, points(n as i32));
+        let json = serde_json::to_string(&tree).unwrap();
+
+        assert_same(&tree, &serde_json::from_str(&json).unwrap());
+    }
+}
+
+#[test]
+fn bincode_round_trip() {
+    for n in [0, 1, 100, 1000] {
+        let tree = inserted(4, words(n));
+        let bytes = bincode::serialize(&tree).unwrap();
+
+        assert_same(&tree, &bincode::deserialize(&bytes).unwrap());
+
+        let tree = inserted(5, points(n as i32));
+        let bytes = bincode::serialize(&tree).unwrap();
+
+        assert_same(&tree, &bincode::deserialize(&bytes).unwrap());
+    }
+}
+
+#[test]
+fn json_is_logical_contents() {
+    let keys = vec![3, 1, 2, 2, 5];
+    let mut sorted = keys.clone();
+
+    sorted.sort();
+
+    assert_eq!(serde_json::to_string(&inserted(2, keys)).unwrap(), r#"{"t":2,"keys":[1,2,2,3,5]}"#);
+    assert_eq!(serde_json::to_string(&BTree::from_sorted(2, sorted, 0.5)).unwrap(), r#"{"t":2,"keys":[1,2,2,3,5]}"#);
+    assert_eq!(serde_json::to_string(&BTree::<u8>::new(7)).unwrap(), r#"{"t":7,"keys":[]}"#);
+}
+
+#[test]
+fn invalid_input_is_rejected() {
+    let error = |json| serde_json::from_str::<BTree<u32>>(json).unwrap_err().to_string();
+
+    assert!(error(r#"{"t":1,"keys":[]}"#).contains("branching factor 1 is out of range"));
+    assert!(error(r#"{"t":4097,"keys":[]}"#).contains("branching factor 4097 is out of range"));
+    assert!(error(r#"{"t":4294967295,"keys":[]}"#).contains("branching factor 4294967295 is out of range"));
+    assert!(error(r#"{"t":18446744073709551615,"keys":[1]}"#).contains("out of range"));
+    assert!(error(r#"{"t":2,"keys":[1,3,2]}"#).contains("key 2 is out of order"));
+    assert!(error(r#"{"t":2}"#).contains("missing field `keys`"));
+}
+
+#[test]
+fn largest_t_round_trips() {
+    let tree = inserted(srdb::MAX_T, words(100));
+    let json = serde_json::to_string(&tree).unwrap();
+
+    assert_same(&tree, &serde_json::from_str(&json).unwrap());
+}
+
+#[test]
+fn shared_map_round_trip() {
+    for n in [0, 1, 500] {
+        let map = SharedMap::new(3);
+
+        for (i, word) in words(n).into_iter().enumerate() {
+            map.insert(word, points(i as i32 % 3));
+        }
+
+        let json = serde_json::to_string(&map).unwrap();
+        let from_json: SharedMap<String, Vec<Point>> = serde_json::from_str(&json).unwrap();
+        let bytes = bincode::serialize(&map).unwrap();
+        let from_bincode: SharedMap<String, Vec<Point>> = bincode::deserialize(&bytes).unwrap();
+
+        for copy in [from_json, from_bincode] {
+            copy.read_with(|tree| tree.check_invariants()).unwrap();
+            assert_eq!(copy.read_with(|tree| tree.t()), 3);
+            assert_eq!(copy.to_vec(), map.to_vec());
+        }
+    }
+}
+
+#[test]
+fn shared_map_rejects_repeated_keys() {
+    let json = r#"{"t":2,"entries":[["a",1],["a",2]]}"#;
+    let error = serde_json::from_str::<SharedMap<String, u32>>(json).unwrap_err().to_string();
+
+    assert!(error.contains("key 1 is out of order or repeated"), "{}", error);
+
+    let json = r#"{"t":4294967295,"entries":[]}"#;
+    let error = serde_json::from_str::<SharedMap<String, u32>>(json).unwrap_err().to_string();
+
+    assert!(error.contains("branching factor 4294967295 is out of range"), "{}", error);
+    assert_eq!(serde_json::to_string(&SharedMap::<String, u32>::new(2)).unwrap(), r#"{"t":2,"entries":[]}"#);
+}
+
+/**
+ * keys of every level of structure, root level first
+ */
+fn json_levels(structure: &serde_json::Value) -> Vec<usize> {
+    let mut levels = vec![];
+    let mut level = vec![structure];
+
+    while !level.is_empty() {
+        levels.push(level.iter().map(|node| node["keys"].as_array().unwrap().len()).sum());
+        level = level.iter().flat_map(|node| node["children"].as_array().unwrap()).collect();
+    }
+
+    levels
+}
+
+#[test]
+fn json_structure_mirrors_levels() {
+    for t in [2, 3, 16] {
+        let tree = inserted(t, (0..5000).map(|i| i * 7919 % 5000).collect::<Vec<i32>>());
+        let structure = tree.to_json_structure();
+
+        assert_eq!(json_levels(&structure), tree.levels(), "t = {}", t);
+        assert_eq!(structure["leaf"], tree.levels().len() == 1);
+
+        let text = structure.to_string();
+        let parsed: serde_json::Value = serde_json::from_str(&text).unwrap();
+
+        assert_eq!(json_levels(&parsed), tree.levels());
+    }
+
+    let points = inserted(3, points(200));
+    let structure = points.to_json_structure();
+
+    assert_eq!(json_levels(&structure), points.levels());
+    assert!(structure["keys"][0].is_object(), "keys go through Serialize");
+
+    let empty = BTree::<String>::new(4).to_json_structure();
+
+    assert_eq!(empty, serde_json::json!({"keys": [], "leaf": true, "children": []}));
+}
+
+#[test]
+fn histogram_serializes_levels() {
+    let keys: Vec<u32> = (0..10).collect();
+    let histogram = BTree::bulk_load_with_fill(2, &keys, 1.0).histogram();
+
+    assert_eq!(
+        serde_json::to_value(&histogram).unwrap(),
+        serde_json::json!({
+            "t": 2,
+            "bucket_width": 1,
+            "levels": [
+                {"nodes": 1, "min": 2, "max": 2, "avg": 2.0, "buckets": [0, 0, 1, 0]},
+                {"nodes": 3, "min": 2, "max": 3, "avg": 8.0 / 3.0, "buckets": [0, 0, 1, 2]},
+            ],
+        })
+    );
+}
+
+/**
+ * key whose Serialize always fails, Opaque has no Display, Labeled has one
+ */
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Opaque(u32);
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Labeled(u32);
+
+impl Serialize for Opaque {
+    fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("opaque"))
+    }
+}
+
+impl Serialize for Labeled {
+    fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("labeled"))
+    }
+}
+
+impl std::fmt::Display for Labeled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "label {}", self.0)
+    }
+}
+
+#[test]
+fn json_structure_falls_back_to_debug_or_display_text() {
+    let opaque = inserted(2, vec![Opaque(1)]);
+
+    assert_eq!(opaque.to_json_structure()["keys"], serde_json::json!(["Opaque(1)"]));
+
+    let labeled = inserted(2, vec![Labeled(1)]);
+
+    assert_eq!(labeled.to_json_structure()["keys"], serde_json::json!(["Labeled(1)"]));
+    assert_eq!(labeled.to_json_structure_display()["keys"], serde_json::json!(["label 1"]));
+
+    let points = inserted(2, points(1));
+
+    assert_eq!(points.to_json_structure_display(), points.to_json_structure());
+}
@@ -0,0 +1,506 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+
+use srdb::Db;
+
+use common::temp_path;
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * srdb serve on ephemeral port of loopback, killed and its database removed on drop
+ */
+struct Server {
+    child: Child,
+    address: String,
+    path: PathBuf,
+}
+
+impl Server {
+    fn start(name: &str, flags: &[&str]) -> Server {
+        Server::start_at(temp_path(name), flags)
+    }
+
+    fn start_at(path: PathBuf, flags: &[&str]) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_srdb"))
+            .args(["serve", path.to_str().unwrap(), "--bind", "127.0.0.1:0", "--log-level", "off"])
+            .args(flags)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+
+        BufReader::new(child.stdout.as_mut().unwrap()).read_line(&mut line).unwrap();
+
+        let address = line.trim().strip_prefix("listening on ").unwrap_or_else(|| panic!("{:?}", line)).to_string();
+
+        Server { child, address, path }
+    }
+
+    fn connect(&self) -> TcpStream {
+        TcpStream::connect(&self.address).unwrap()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = Db::remove(&self.path);
+    }
+}
+
+/**
+ * client of line protocol, every command is answered by one line, SCAN by lines up to OK,
+ * command goes out in one write, so it does not wait for ack of its first half
+ */
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    fn new(stream: TcpStream) -> Client {
+        Client { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream }
+    }
+
+    fn line(&mut self) -> String {
+        let mut line = String::new();
+
+        self.reader.read_line(&mut line).unwrap();
+
+        line.trim_end_matches('\n').to_string()
+    }
+
+    fn send(&mut self, command: &str) -> String {
+        self.writer.write_all(format!("{}\n", command).as_bytes()).unwrap();
+
+        self.line()
+    }
+
+    fn scan(&mut self, command: &str) -> Vec<String> {
+        self.writer.write_all(format!("{}\n", command).as_bytes()).unwrap();
+
+        let mut lines = vec![];
+
+        loop {
+            match self.line() {
+                line if line == "OK" => return lines,
+                line if line.starts_with("ERR") => return vec![line],
+                line => lines.push(line),
+            }
+        }
+    }
+}
+
+#[test]
+fn line_protocol_over_raw_socket() {
+    let server = Server::start("line", &[]);
+    let mut client = Client::new(server.connect());
+
+    assert_eq!(client.send("PING"), "OK");
+    assert_eq!(client.send("SET apple red and ripe"), "OK");
+    assert_eq!(client.send("SET apricot orange"), "OK");
+    assert_eq!(client.send("set banana yellow"), "OK", "commands are case insensitive");
+    assert_eq!(client.send("GET apple"), "VALUE red and ripe");
+    assert_eq!(client.send("GET cherry"), "NOT_FOUND");
+    assert_eq!(client.scan("SCAN ap"), ["VALUE apple red and ripe", "VALUE apricot orange"]);
+    assert_eq!(client.scan("SCAN a 1"), ["VALUE apple red and ripe"]);
+    assert_eq!(client.scan("SCAN"), ["VALUE apple red and ripe", "VALUE apricot orange", "VALUE banana yellow"]);
+    assert_eq!(client.send("DEL banana"), "OK");
+    assert_eq!(client.send("DEL banana"), "NOT_FOUND");
+
+    assert_eq!(client.send("FROB"), "ERR unknown command \"FROB\"");
+    assert_eq!(client.send("GET"), "ERR wrong number of arguments for GET");
+    assert_eq!(client.send("SCAN a lots"), "ERR lots is not a valid limit");
+    assert_eq!(client.send("GET apple"), "VALUE red and ripe", "errors leave connection open");
+
+    let mut other = Client::new(server.connect());
+
+    assert_eq!(other.send("GET apricot"), "VALUE orange", "clients share database");
+}
+
+/**
+ * clients on threads write their own keys, then every key is read back by one more client
+ */
+#[test]
+fn concurrent_clients_and_dropped_connections() {
+    let server = Server::start("concurrent", &[]);
+
+    let writers: Vec<_> = (0..8)
+        .map(|client| {
+            let mut client_stream = Client::new(server.connect());
+
+            thread::spawn(move || {
+                for i in 0..100 {
+                    assert_eq!(client_stream.send(&format!("SET key{}-{} value{}", client, i, i)), "OK");
+                }
+            })
+        })
+        .collect();
+
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let mut half_written = server.connect();
+
+    half_written.write_all(b"SET unfinished").unwrap();
+    drop(half_written);
+
+    let mut client = Client::new(server.connect());
+
+    assert_eq!(client.send("PING"), "OK", "server outlives client dropped in the middle of command");
+
+    for c in 0..8 {
+        assert_eq!(client.scan(&format!("SCAN key{}- 1000", c)).len(), 100);
+    }
+
+    assert_eq!(client.send("GET key7-99"), "VALUE value99");
+
+    let mut closed = server.connect();
+    let mut rest = vec![];
+
+    closed.write_all(b"PING\n").unwrap();
+    closed.shutdown(std::net::Shutdown::Write).unwrap();
+    closed.read_to_end(&mut rest).unwrap();
+
+    assert_eq!(rest, b"OK\n", "server closes connection when client does");
+}
+
+/**
+ * reply of RESP server up to its end, bulk strings and arrays read as a whole
+ */
+fn resp_reply(reader: &mut impl BufRead) -> String {
+    let mut line = String::new();
+
+    reader.read_line(&mut line).unwrap();
+
+    match line.as_bytes().first() {
+        Some(b'$') if line != "$-1\r\n" => {
+            let len: usize = line[1..].trim().parse().unwrap();
+            let mut bulk = vec![0; len + 2];
+
+            reader.read_exact(&mut bulk).unwrap();
+            line.push_str(&String::from_utf8(bulk).unwrap());
+        }
+        Some(b'*') => {
+            for _ in 0..line[1..].trim().parse().unwrap() {
+                line.push_str(&resp_reply(reader));
+            }
+        }
+        _ => {}
+    }
+
+    line
+}
+
+#[test]
+fn resp_over_raw_socket() {
+    let server = Server::start("resp", &["--resp"]);
+    let stream = server.connect();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    writer.write_all(b"*3\r\n$3\r\nSET\r\n$3\r\nk\r\n\r\n$4\r\nv\0\r\n\r\n").unwrap();
+    writer.write_all(b"PING\r\n*2\r\n$3\r\nGET\r\n$3\r\nk\r\n\r\n").unwrap();
+
+    assert_eq!(resp_reply(&mut reader), "+OK\r\n");
+    assert_eq!(resp_reply(&mut reader), "+PONG\r\n");
+    assert_eq!(resp_reply(&mut reader), "$4\r\nv\0\r\n\r\n", "bulk strings carry any bytes");
+
+    writer.write_all(b"GET missing\r\nKEYS *\r\nDBSIZE\r\n").unwrap();
+    writer.write_all(b"*3\r\n$3\r\nDEL\r\n$3\r\nk\r\n\r\n$7\r\nmissing\r\nFROB\r\n").unwrap();
+
+    assert_eq!(resp_reply(&mut reader), "$-1\r\n");
+    assert_eq!(resp_reply(&mut reader), "*1\r\n$3\r\nk\r\n\r\n");
+    assert_eq!(resp_reply(&mut reader), ":1\r\n");
+    assert_eq!(resp_reply(&mut reader), ":1\r\n");
+    assert_eq!(resp_reply(&mut reader), "-ERR unknown command 'frob'\r\n");
+}
+
+/**
+ * clients sending garbage get error reply and are disconnected, server keeps serving others
+ */
+#[test]
+fn resp_garbage_closes_only_its_connection() {
+    let server = Server::start("resp-garbage", &["--resp"]);
+    let mut next = lcg(7);
+
+    for round in 0..50u32 {
+        let mut garbage: Vec<u8> = b"*2\r\n$".to_vec();
+
+        garbage.extend((0..1 + next() % 64).map(|_| b"*$\r\n-:9x\0\xff"[next() as usize % 10]));
+
+        if round.is_multiple_of(2) {
+            garbage.extend(b"\r\n");
+        }
+
+        let mut stream = server.connect();
+        let mut reply = vec![];
+
+        stream.write_all(&garbage).unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        stream.read_to_end(&mut reply).unwrap();
+
+        let reply = String::from_utf8(reply).unwrap();
+
+        assert!(reply.starts_with("-ERR Protocol error: ") && reply.ends_with("\r\n"), "{:?}", reply);
+        assert_eq!(reply.matches("\r\n").count(), 1, "{:?}", reply);
+    }
+
+    let stream = server.connect();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    writer.write_all(b"PING\r\n").unwrap();
+
+    assert_eq!(resp_reply(&mut reader), "+PONG\r\n");
+}
+
+/**
+ * status and body of http response, request is sent on its own connection with Connection: close
+ */
+#[cfg(feature = "http")]
+fn http(server: &Server, method: &str, target: &str, body: &str) -> (u16, serde_json::Value) {
+    let mut stream = server.connect();
+    let mut response = String::new();
+
+    let head = format!("{} {} HTTP/1.1\r\nConnection: close\r\nContent-Length: {}\r\n", method, target, body.len());
+
+    write!(stream, "{}\r\n{}", head, body).unwrap();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+
+    assert!(head.contains("Content-Type: application/json"), "{}", head);
+
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn every_http_route() {
+    use serde_json::json;
+
+    let server = Server::start("http", &["--http"]);
+
+    assert_eq!(http(&server, "PUT", "/keys/apple", "red"), (200, json!({"key": "apple"})));
+    assert_eq!(http(&server, "PUT", "/keys/apricot", "orange"), (200, json!({"key": "apricot"})));
+    assert_eq!(http(&server, "PUT", "/keys/big%20banana", "yellow"), (200, json!({"key": "big banana"})));
+    assert_eq!(http(&server, "GET", "/keys/apple", ""), (200, json!({"key": "apple", "value": "red"})));
+    assert_eq!(http(&server, "GET", "/keys/big%20banana", ""), (200, json!({"key": "big banana", "value": "yellow"})));
+    assert_eq!(http(&server, "GET", "/keys/cherry", ""), (404, json!({"error": "key not found"})));
+    assert_eq!(http(&server, "DELETE", "/keys/apricot", ""), (200, json!({"key": "apricot"})));
+    assert_eq!(http(&server, "DELETE", "/keys/apricot", ""), (404, json!({"error": "key not found"})));
+
+    assert_eq!(
+        http(&server, "GET", "/range", ""),
+        (200, json!([{"key": "apple", "value": "red"}, {"key": "big banana", "value": "yellow"}]))
+    );
+    assert_eq!(http(&server, "GET", "/range?start=b", ""), (200, json!([{"key": "big banana", "value": "yellow"}])));
+    assert_eq!(http(&server, "GET", "/range?end=b", ""), (200, json!([{"key": "apple", "value": "red"}])));
+    assert_eq!(http(&server, "GET", "/range?limit=1", ""), (200, json!([{"key": "apple", "value": "red"}])));
+    assert_eq!(http(&server, "GET", "/range?limit=x", ""), (400, json!({"error": "limit is not a number"})));
+    assert_eq!(http(&server, "GET", "/range?color=red", ""), (400, json!({"error": "unknown parameter color"})));
+
+    let (status, stats) = http(&server, "GET", "/stats", "");
+
+    assert_eq!(status, 200);
+    assert!(stats["page_size"].as_u64().unwrap() > 0, "{}", stats);
+
+    assert_eq!(http(&server, "POST", "/keys/apple", "").0, 405);
+    assert_eq!(http(&server, "DELETE", "/stats", "").0, 405);
+    assert_eq!(http(&server, "GET", "/nowhere", ""), (404, json!({"error": "no route /nowhere"})));
+    assert_eq!(http(&server, "GET", "/keys/bad%zz", "").0, 400);
+
+    let mut malformed = server.connect();
+    let mut response = String::new();
+
+    malformed.write_all(b"GET\r\n\r\n").unwrap();
+    malformed.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", response);
+}
+
+/**
+ * requests on threads go through one shared database, read-only server answers writes with 409
+ */
+#[cfg(feature = "http")]
+#[test]
+fn concurrent_http_requests_and_conflicts() {
+    use serde_json::json;
+
+    let server = Server::start("http-concurrent", &["--http"]);
+
+    thread::scope(|scope| {
+        for client in 0..8 {
+            let server = &server;
+
+            scope.spawn(move || {
+                for i in 0..50 {
+                    assert_eq!(http(server, "PUT", &format!("/keys/key{}-{:02}", client, i), "v").0, 200);
+                    assert_eq!(http(server, "GET", &format!("/keys/key{}-{:02}", client, i), "").0, 200);
+                }
+            });
+        }
+    });
+
+    let (status, entries) = http(&server, "GET", "/range?limit=1000", "");
+
+    assert_eq!(status, 200);
+    assert_eq!(entries.as_array().unwrap().len(), 400);
+
+    let path = server.path.clone();
+
+    drop(server);
+    Db::create(&path).unwrap().insert(b"apple", b"red").unwrap();
+
+    let server = Server::start_at(path, &["--http", "--read-only"]);
+
+    assert_eq!(http(&server, "GET", "/keys/apple", ""), (200, json!({"key": "apple", "value": "red"})));
+    assert_eq!(http(&server, "PUT", "/keys/apple", "green").0, 409);
+    assert_eq!(http(&server, "DELETE", "/keys/apple", "").0, 409);
+}
+
+/**
+ * clients own disjoint keys, so order of their commands on server is the order of replay,
+ * every client checks its reads against its model while the others write and scan
+ */
+#[test]
+fn many_clients_equal_serial_replay() {
+    let server = Server::start("replay", &[]);
+
+    let logs: Vec<Vec<(String, Option<String>)>> = thread::scope(|scope| {
+        let clients: Vec<_> = (0..16)
+            .map(|c| {
+                let mut client = Client::new(server.connect());
+
+                scope.spawn(move || {
+                    let mut next = lcg(c);
+                    let mut model = std::collections::BTreeMap::new();
+                    let mut log = vec![];
+
+                    for i in 0..300 {
+                        let key = format!("c{:02}-{:03}", c, next() % 60);
+
+                        match next() % 10 {
+                            0..=3 => {
+                                let value = format!("v{}", i);
+
+                                assert_eq!(client.send(&format!("SET {} {}", key, value)), "OK");
+                                model.insert(key.clone(), value.clone());
+                                log.push((key, Some(value)));
+                            }
+                            4 | 5 => {
+                                let reply = if model.remove(&key).is_some() { "OK" } else { "NOT_FOUND" };
+
+                                assert_eq!(client.send(&format!("DEL {}", key)), reply);
+                                log.push((key, None));
+                            }
+                            6 | 7 => {
+                                let reply = model.get(&key).map_or("NOT_FOUND".to_string(), |v| format!("VALUE {}", v));
+
+                                assert_eq!(client.send(&format!("GET {}", key)), reply);
+                            }
+                            8 => {
+                                let own: Vec<_> = model.iter().map(|(k, v)| format!("VALUE {} {}", k, v)).collect();
+
+                                assert_eq!(client.scan(&format!("SCAN c{:02}- 1000", c)), own);
+                            }
+                            _ => assert!(!client.scan("SCAN c 200").iter().any(|line| line.starts_with("ERR"))),
+                        }
+                    }
+
+                    log
+                })
+            })
+            .collect();
+
+        clients.into_iter().map(|client| client.join().unwrap()).collect()
+    });
+
+    let mut replay = std::collections::BTreeMap::new();
+
+    for (key, value) in logs.into_iter().flatten() {
+        match value {
+            Some(value) => replay.insert(key, value),
+            None => replay.remove(&key),
+        };
+    }
+
+    let expected: Vec<_> = replay.iter().map(|(key, value)| format!("VALUE {} {}", key, value)).collect();
+
+    assert_eq!(Client::new(server.connect()).scan("SCAN c 100000"), expected);
+}
+
+#[test]
+fn connection_limit_refuses_extra_clients() {
+    let server = Server::start("limit", &["--max-connections", "1"]);
+    let mut first = Client::new(server.connect());
+
+    assert_eq!(first.send("PING"), "OK");
+
+    let mut refused = Client::new(server.connect());
+
+    assert_eq!(refused.line(), "ERR too many connections");
+    assert_eq!(first.send("PING"), "OK");
+
+    drop(first);
+    drop(refused);
+
+    for _ in 0..100 {
+        let mut again = server.connect();
+        let mut reply = String::new();
+
+        let answered = again.write_all(b"PING\n").is_ok() && BufReader::new(again).read_line(&mut reply).is_ok();
+
+        if answered && reply == "OK\n" {
+            return;
+        }
+
+        thread::sleep(std::time::Duration::from_millis(20));
+    }
+
+    panic!("slot of closed client is not given back");
+}
+
+/**
+ * SIGTERM lets server finish and checkpoint, so database opens without replaying log
+ */
+#[cfg(all(feature = "signals", unix))]
+#[test]
+fn sigterm_checkpoints_before_exit() {
+    let mut server = Server::start("sigterm", &[]);
+    let mut client = Client::new(server.connect());
+
+    for i in 0..500 {
+        assert_eq!(client.send(&format!("SET key{:03} value{}", i, i)), "OK");
+    }
+
+    let killed = Command::new("kill").args(["-TERM", &server.child.id().to_string()]).status().unwrap();
+
+    assert!(killed.success());
+    assert!(server.child.wait().unwrap().success());
+
+    let db = Db::open(&server.path).unwrap();
+
+    assert!(!db.open_stats().recovered);
+    assert_eq!(db.len(), 500);
+    assert_eq!(db.get(b"key499").unwrap(), Some(b"value499".to_vec()));
+}
@@ -0,0 +1,137 @@
+#![cfg(feature = "std")]
+
+use std::cmp::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use srdb::{ShardedBTree, SharedBTree};
+
+const THREADS: u64 = 8;
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * key of thread: threads own ranges of keys, so with range bounds every thread writes shard of its own
+ */
+fn key_of(thread: u64, next: &mut impl FnMut() -> u64) -> u64 {
+    (thread << 32) | (next() % 1_000_000)
+}
+
+fn bounds() -> Vec<u64> {
+    (1..THREADS).map(|thread| thread << 32).collect()
+}
+
+/**
+ * time for threads to do ops inserts each, insert is whatever writes one key
+ */
+fn timed(threads: u64, ops: u64, insert: impl Fn(u64) + Sync) -> Duration {
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for thread in 0..threads {
+            let insert = &insert;
+
+            scope.spawn(move || {
+                let mut next = lcg(thread);
+
+                for _ in 0..ops {
+                    insert(key_of(thread, &mut next));
+                }
+            });
+        }
+    });
+
+    start.elapsed()
+}
+
+/**
+ * writes per second of sharded tree grow with threads up to number of cores, those of one locked tree do not,
+ * on one core there is nothing to scale, so only correctness is checked there
+ */
+#[test]
+fn writes_scale_with_threads() {
+    const OPS: u64 = 20_000;
+
+    let cores = thread::available_parallelism().map_or(1, |cores| cores.get() as u64).min(THREADS);
+    let rate = |threads: u64, elapsed: Duration| (threads * OPS) as f64 / elapsed.as_secs_f64();
+
+    let sharded = ShardedBTree::with_bounds(16, bounds());
+    let single_thread = rate(1, timed(1, OPS, |key| sharded.insert(key)));
+    let sharded = ShardedBTree::with_bounds(16, bounds());
+    let all_threads = rate(cores, timed(cores, OPS, |key| sharded.insert(key)));
+    let locked = SharedBTree::new(16);
+    let locked_threads = rate(cores, timed(cores, OPS, |key| locked.insert(key)));
+
+    sharded.check_invariants().unwrap();
+    locked.check_invariants().unwrap();
+    assert_eq!(sharded.len(), locked.len());
+    assert_eq!(sharded.to_vec(), locked.to_vec());
+
+    if cores >= 2 {
+        assert!(
+            all_threads >= single_thread * cores as f64 * 0.5,
+            "{} threads write {:.0}/s, one thread {:.0}/s",
+            cores,
+            all_threads,
+            single_thread
+        );
+        assert!(
+            all_threads >= locked_threads * 1.5,
+            "sharded {:.0}/s, one lock {:.0}/s",
+            all_threads,
+            locked_threads
+        );
+    }
+}
+
+/**
+ * key which takes time to compare, like long strings do, so time of insert is time its shard is locked
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Slow(u64);
+
+impl PartialOrd for Slow {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Slow {
+    fn cmp(&self, other: &Self) -> Ordering {
+        thread::sleep(Duration::from_micros(20));
+
+        self.0.cmp(&other.0)
+    }
+}
+
+/**
+ * threads holding locks of different shards do not wait for each other, so time of writes is that of one thread,
+ * while behind one lock it is that of all of them, this holds on any number of cores
+ */
+#[test]
+fn shard_locks_are_held_independently() {
+    const OPS: u64 = 40;
+
+    let bounds: Vec<Slow> = bounds().into_iter().map(Slow).collect();
+    let sharded = ShardedBTree::with_bounds(4, bounds);
+    let sharded_time = timed(THREADS, OPS, |key| sharded.insert(Slow(key)));
+    let locked = SharedBTree::new(4);
+    let locked_time = timed(THREADS, OPS, |key| locked.insert(Slow(key)));
+
+    assert_eq!(sharded.shard_lens(), vec![OPS as usize; THREADS as usize]);
+    assert_eq!(locked.len(), (THREADS * OPS) as usize);
+    assert!(
+        sharded_time * (THREADS as u32) / 2 <= locked_time,
+        "{} threads took {:?} sharded and {:?} behind one lock",
+        THREADS,
+        sharded_time,
+        locked_time
+    );
+}
@@ -0,0 +1,193 @@
+#![cfg(feature = "std")]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use srdb::{MapEntry, SharedBTree, SharedMap};
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * keys go in and out in pairs 2k and 2k + 1 under one write lock, so any read sees both or neither
+ */
+fn assert_pairs(keys: &[u64]) {
+    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]), "keys out of order");
+
+    for key in keys {
+        assert!(keys.binary_search(&(key ^ 1)).is_ok(), "{} is without its pair", key);
+    }
+}
+
+/**
+ * one writer inserts and deletes pairs of keys, readers on other threads check every way of reading
+ */
+#[test]
+fn readers_never_see_torn_writes() {
+    let tree = SharedBTree::new(3);
+    let done = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        for _ in 0..8 {
+            let tree = tree.clone();
+            let done = &done;
+
+            scope.spawn(move || {
+                let mut reads = 0;
+
+                while !done.load(Ordering::SeqCst) || reads == 0 {
+                    assert_pairs(&tree.to_vec());
+                    assert_pairs(&tree.snapshot_iter().collect::<Vec<u64>>());
+                    assert!(tree.len().is_multiple_of(2));
+
+                    tree.read_with(|tree| {
+                        tree.check_invariants().unwrap();
+
+                        assert_eq!(tree.iter().count(), tree.len());
+                    });
+
+                    let mut seen = vec![];
+
+                    tree.for_each(|key| seen.push(*key));
+                    assert_pairs(&seen);
+
+                    reads += 1;
+                }
+            });
+        }
+
+        let mut next = lcg(0);
+
+        for _ in 0..5000 {
+            let pair = next() % 500 * 2;
+
+            match next() % 3 {
+                0 => {
+                    tree.write_with(|tree| {
+                        if tree.delete(&pair) {
+                            assert!(tree.delete(&(pair + 1)));
+                        }
+                    });
+                }
+                1 => {
+                    if !tree.contains(pair) {
+                        tree.insert_batch(vec![pair, pair + 1]);
+                    }
+                }
+                _ => {
+                    tree.write_with(|tree| {
+                        if !tree.contains(pair) {
+                            tree.insert(pair + 1);
+                            tree.insert(pair);
+                        }
+                    });
+                }
+            }
+        }
+
+        done.store(true, Ordering::SeqCst);
+    });
+
+    assert_pairs(&tree.to_vec());
+    tree.check_invariants().unwrap();
+}
+
+/**
+ * writer moves every key to the next version at once, so readers see one version across keys,
+ * which never goes back, and values are never half written
+ */
+#[test]
+fn map_readers_see_one_version_of_all_keys() {
+    let map: SharedMap<u32, Vec<u64>> = SharedMap::new(2);
+    let done = AtomicBool::new(false);
+
+    for key in 0..16 {
+        map.insert(key, vec![0; 64]);
+    }
+
+    thread::scope(|scope| {
+        for _ in 0..8 {
+            let map = map.clone();
+            let done = &done;
+
+            scope.spawn(move || {
+                let mut last = 0;
+
+                while !done.load(Ordering::SeqCst) {
+                    let entries = map.to_vec();
+                    let version = entries[0].1[0];
+
+                    assert_eq!(entries.len(), 16);
+                    assert!(entries.iter().all(|(_, value)| value.iter().all(|v| *v == version)), "torn version");
+                    assert!(version >= last, "version went back from {} to {}", last, version);
+
+                    let single = map.get(&(version as u32 % 16)).unwrap();
+
+                    assert!(single[0] >= version && single.iter().all(|v| *v == single[0]));
+
+                    last = single[0];
+                }
+            });
+        }
+
+        for version in 1..2000 {
+            map.write_with(|tree| {
+                for key in 0..16 {
+                    let old = MapEntry { key, value: vec![] };
+
+                    assert!(tree.delete(&old));
+                    tree.insert(MapEntry { key, value: vec![version; 64] });
+                }
+            });
+        }
+
+        done.store(true, Ordering::SeqCst);
+    });
+
+    assert_eq!(map.get(&7), Some(vec![1999; 64]));
+}
+
+/**
+ * scan is started before writer and finished after it, writer must not wait for it,
+ * and scan sees exactly the keys of the moment it started
+ */
+#[test]
+fn snapshot_scan_sees_only_keys_before_concurrent_writes() {
+    let tree = SharedBTree::new(4);
+
+    for key in (0..20_000u64).step_by(2) {
+        tree.insert(key);
+    }
+
+    let mut scan = tree.snapshot_iter();
+    let mut seen: Vec<u64> = scan.by_ref().take(100).collect();
+
+    thread::spawn({
+        let tree = tree.clone();
+
+        move || {
+            for key in (1..20_000u64).step_by(2) {
+                tree.insert(key);
+            }
+
+            for key in (0..1000u64).step_by(2) {
+                assert!(tree.delete(&key));
+            }
+        }
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(tree.len(), 19_500);
+
+    seen.extend(scan);
+
+    assert_eq!(seen, (0..20_000u64).step_by(2).collect::<Vec<_>>());
+    tree.check_invariants().unwrap();
+}
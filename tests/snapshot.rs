@@ -0,0 +1,120 @@
+#![cfg(feature = "std")]
+
+use srdb::{BTree, SnapshotError};
+
+/**
+ * plain bitwise CRC-32 (IEEE), to forge checksums of crafted snapshots
+ */
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+fn snapshot(tree: &BTree<u64>) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    tree.save_to(&mut bytes).unwrap();
+
+    bytes
+}
+
+/**
+ * replaces field at offset and puts checksum of changed bytes at the end, so only validation of field can reject it
+ */
+fn forged(bytes: &[u8], offset: usize, field: &[u8]) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    let body = bytes.len() - 4;
+
+    bytes[offset..offset + field.len()].copy_from_slice(field);
+
+    let checksum = crc32(&bytes[..body]);
+
+    bytes[body..].copy_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+/**
+ * magic (8), version (4), t (8), count (8), then keys
+ */
+const T_OFFSET: usize = 12;
+const COUNT_OFFSET: usize = 20;
+
+#[test]
+fn round_trip() {
+    for t in [2, 5, 64] {
+        for n in [0, 1, 1000] {
+            let tree = BTree::from_sorted(t, (0..n).map(|i| i * 3).collect(), 0.7);
+            let loaded = BTree::<u64>::load_from(&snapshot(&tree)[..]).unwrap();
+
+            loaded.check_invariants().unwrap();
+            assert_eq!(loaded.t(), t);
+            assert_eq!(loaded.to_vec(), tree.to_vec());
+        }
+    }
+}
+
+#[test]
+fn every_bit_flip_is_rejected() {
+    let bytes = snapshot(&BTree::from_sorted(3, (0..200).collect(), 1.0));
+
+    for i in 0..bytes.len() {
+        for bit in 0..8 {
+            let mut flipped = bytes.clone();
+
+            flipped[i] ^= 1 << bit;
+
+            match BTree::<u64>::load_from(&flipped[..]) {
+                Err(SnapshotError::ChecksumMismatch { .. }) => {}
+                Err(SnapshotError::WrongMagic) => assert!(i < 8),
+                Err(SnapshotError::UnsupportedVersion(_)) => assert!((8..T_OFFSET).contains(&i)),
+                Err(SnapshotError::Corrupt(_) | SnapshotError::Truncated) => {}
+                result => panic!("flip of bit {} in byte {} gave {:?}", bit, i, result.map(|tree| tree.len())),
+            }
+        }
+    }
+}
+
+#[test]
+fn every_truncation_is_rejected() {
+    let bytes = snapshot(&BTree::from_sorted(4, (0..200).collect(), 1.0));
+
+    for len in 0..bytes.len() {
+        assert!(
+            matches!(BTree::<u64>::load_from(&bytes[..len]), Err(SnapshotError::Truncated)),
+            "snapshot cut to {} of {} bytes",
+            len,
+            bytes.len()
+        );
+    }
+}
+
+#[test]
+fn forged_header_is_corrupt() {
+    let bytes = snapshot(&BTree::from_sorted(3, (0..10).collect(), 1.0));
+
+    assert!(BTree::<u64>::load_from(&forged(&bytes, 0, b"x")[..]).is_err());
+
+    for t in [0, 1, srdb::MAX_T as u64 + 1, u32::MAX as u64, u64::MAX] {
+        match BTree::<u64>::load_from(&forged(&bytes, T_OFFSET, &t.to_le_bytes())[..]) {
+            Err(SnapshotError::Corrupt(reason)) => assert!(reason.contains("branching factor"), "{}", reason),
+            result => panic!("t = {} gave {:?}", t, result.map(|tree| tree.len())),
+        }
+    }
+
+    let more = forged(&bytes, COUNT_OFFSET, &u64::MAX.to_le_bytes());
+
+    assert!(matches!(BTree::<u64>::load_from(&more[..]), Err(SnapshotError::Truncated)));
+
+    let largest = forged(&bytes, T_OFFSET, &(srdb::MAX_T as u64).to_le_bytes());
+
+    assert_eq!(BTree::<u64>::load_from(&largest[..]).unwrap().t(), srdb::MAX_T);
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "std")]
+
+#[cfg(feature = "arbitrary")]
+use srdb::check_stores;
+use srdb::{ScriptOp, StoreScript};
+
+/**
+ * seeded scripts against in-memory BTree and on-disk Db, failure prints seed, script and store which broke
+ */
+#[test]
+fn seeded_scripts_agree_on_memory_and_disk() {
+    for seed in 0..100 {
+        let script = StoreScript::seeded(seed);
+
+        script.run().unwrap_or_else(|error| panic!("seed {}: {}\n{:?}", seed, error, script));
+    }
+}
+
+/**
+ * random scripts of proptest, failure prints shrunk script and store which broke
+ */
+#[cfg(feature = "arbitrary")]
+#[test]
+fn random_scripts_agree_on_memory_and_disk() {
+    for seed in 0..4 {
+        check_stores(40, seed).unwrap_or_else(|counterexample| panic!("{}", counterexample));
+    }
+}
+
+/**
+ * fill in order and empty in reverse, so both stores split and collapse root at every height
+ */
+#[test]
+fn fill_and_empty_agree_on_memory_and_disk() {
+    for t in 2..=8 {
+        let ops = (0..300)
+            .map(ScriptOp::Insert)
+            .chain((0..300).rev().map(ScriptOp::Delete))
+            .chain((0..100).map(|key| ScriptOp::Insert(key * 7 % 100)))
+            .chain((0..100).map(|key| ScriptOp::Delete(key * 13 % 100)))
+            .collect();
+
+        StoreScript { t, ops }.run().unwrap_or_else(|error| panic!("t = {}: {}", t, error));
+    }
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "std")]
+
+use srdb::{Db, MemStorage, SrdbOptions, StreamProgress, SyncMode, VerifyMode, WriteBatch, MIN_CACHE_PAGES, PROGRESS_KEYS};
+
+const KEYS: u32 = 30_000;
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn value(i: u32) -> Vec<u8> {
+    format!("value of key {} {}", i, "-".repeat(i as usize % 60)).into_bytes()
+}
+
+/**
+ * database behind the smallest cache, with table "big" of pages many times the cache
+ */
+fn small_cache() -> Db {
+    SrdbOptions::new()
+        .cache_pages(MIN_CACHE_PAGES)
+        .sync_mode(SyncMode::Off)
+        .create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new()))
+        .unwrap()
+}
+
+/**
+ * progress reports of one stream, they grow by PROGRESS_KEYS keys and end with the total
+ */
+fn assert_progress(reports: &[StreamProgress], total: StreamProgress) {
+    assert_eq!(reports.last(), Some(&total));
+    assert_eq!(reports.len() as u64, total.keys / PROGRESS_KEYS + 1);
+
+    for (i, pair) in reports.windows(2).enumerate() {
+        assert!(pair[0].bytes < pair[1].bytes, "{:?}", pair);
+
+        if i + 2 < reports.len() {
+            assert_eq!(pair[1].keys - pair[0].keys, PROGRESS_KEYS);
+        }
+    }
+}
+
+#[test]
+fn table_larger_than_cache_streams_out_and_in() {
+    let mut source = small_cache();
+    let mut batch = WriteBatch::new();
+
+    batch.create_table("big");
+
+    for i in 0..KEYS {
+        batch.put_in("big", &key(i), &value(i));
+    }
+
+    source.write(&batch).unwrap();
+    source.flush().unwrap();
+
+    assert!(source.page_count() as usize > 20 * MIN_CACHE_PAGES);
+
+    let mut stream = vec![];
+    let mut reports = vec![];
+    let exported = source.table("big").unwrap().export_stream(&mut stream, |progress| reports.push(progress)).unwrap();
+
+    assert_eq!(exported.keys, KEYS as u64);
+    assert_eq!(exported.bytes, stream.len() as u64);
+    assert_progress(&reports, exported);
+
+    let mut target = small_cache();
+    let mut reports = vec![];
+
+    target.create_table("big").unwrap();
+
+    let imported = target.table("big").unwrap().import_stream(&stream[..], |progress| reports.push(progress)).unwrap();
+
+    assert_eq!(imported, exported);
+    assert_progress(&reports, imported);
+    assert!(target.verify(VerifyMode::Full).is_ok());
+    assert!(target.page_count() as usize > 20 * MIN_CACHE_PAGES);
+    assert!(target.table("big").unwrap().to_vec().unwrap() == source.table("big").unwrap().to_vec().unwrap());
+
+    let digest = |db: &Db| db.digests().unwrap().into_iter().find(|digest| digest.table.as_deref() == Some("big"));
+
+    assert_eq!(digest(&target), digest(&source));
+
+    let path = std::env::temp_dir().join(format!("srdb-stream-restored-{}", std::process::id()));
+
+    let _ = Db::remove(&path);
+
+    let mut restored = Db::restore(&stream[..], &path).unwrap();
+
+    assert!(restored.tables().unwrap().is_empty());
+    assert!(restored.to_vec().unwrap() == source.table("big").unwrap().to_vec().unwrap(), "stream is a dump too");
+
+    drop(restored);
+    Db::remove(&path).unwrap();
+}
@@ -0,0 +1,200 @@
+use std::collections::BTreeSet;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+use srdb::{BTree, PersistentBTree, PrefixBTree};
+
+fn lcg(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed;
+
+    move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    }
+}
+
+/**
+ * tree goes around threads through channel, every thread changes it before it passes it on
+ */
+#[test]
+fn tree_moves_between_threads() {
+    let (first, mut input) = channel::<(BTree<String>, BTreeSet<String>)>();
+    let mut threads = vec![];
+
+    for stage in 0..4 {
+        let (output, next_input) = channel();
+
+        threads.push(thread::spawn(move || {
+            let mut next = lcg(stage);
+
+            for (mut tree, mut model) in input {
+                tree.check_invariants().unwrap();
+
+                for _ in 0..500 {
+                    let key = format!("key{:04}", next() % 2000);
+
+                    if next().is_multiple_of(3) {
+                        assert_eq!(tree.delete(&key), model.remove(&key));
+                    } else if model.insert(key.clone()) {
+                        tree.insert(key);
+                    }
+                }
+
+                output.send((tree, model)).unwrap();
+            }
+        }));
+
+        input = next_input;
+    }
+
+    for t in [2, 3, 16] {
+        first.send((BTree::new(t), BTreeSet::new())).unwrap();
+    }
+
+    drop(first);
+
+    for (tree, model) in input {
+        tree.check_invariants().unwrap();
+
+        assert_eq!(tree.to_vec(), model.into_iter().collect::<Vec<_>>());
+    }
+
+    for handle in threads {
+        handle.join().unwrap();
+    }
+}
+
+/**
+ * threads change one tree behind mutex, each one its own keys, so their models together are the tree
+ */
+#[test]
+fn tree_shared_behind_mutex() {
+    for t in [2, 5] {
+        let tree = Arc::new(Mutex::new(BTree::new(t)));
+
+        let models: Vec<BTreeSet<u64>> = (0..8)
+            .map(|writer| {
+                let tree = tree.clone();
+
+                thread::spawn(move || {
+                    let mut next = lcg(writer);
+                    let mut model = BTreeSet::new();
+
+                    for _ in 0..2000 {
+                        let key = next() % 300 * 8 + writer;
+                        let mut tree = tree.lock().unwrap();
+
+                        if next().is_multiple_of(3) {
+                            assert_eq!(tree.delete(&key), model.remove(&key));
+                        } else if model.insert(key) {
+                            tree.insert(key);
+                        }
+                    }
+
+                    model
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        let tree = Arc::into_inner(tree).unwrap().into_inner().unwrap();
+        let expected: BTreeSet<u64> = models.into_iter().flatten().collect();
+
+        tree.check_invariants().unwrap();
+        assert_eq!(tree.to_vec(), expected.into_iter().collect::<Vec<_>>());
+    }
+}
+
+/**
+ * readers share tree behind RwLock with writer and check it whole on every read
+ */
+#[test]
+fn tree_shared_behind_rwlock() {
+    let tree = RwLock::new(BTree::new(3));
+
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..200 {
+                    let tree = tree.read().unwrap();
+
+                    tree.check_invariants().unwrap();
+                    assert_eq!(tree.to_vec().len(), tree.len());
+                }
+            });
+        }
+
+        let mut next = lcg(9);
+
+        for _ in 0..5000 {
+            let key = next() % 1000;
+            let mut tree = tree.write().unwrap();
+
+            if !tree.delete(&key) {
+                tree.insert(key);
+            }
+        }
+    });
+
+    tree.read().unwrap().check_invariants().unwrap();
+}
+
+/**
+ * versions of persistent tree share nodes, threads change their own copies of one version
+ */
+#[test]
+fn persistent_and_prefix_trees_cross_threads() {
+    let mut base = PersistentBTree::new(2);
+
+    for key in 0..1000u64 {
+        base.insert(key);
+    }
+
+    let handles: Vec<_> = (0..4u64)
+        .map(|thread| {
+            let mut tree = base.clone();
+
+            thread::spawn(move || {
+                for key in (thread..1000).step_by(4) {
+                    assert!(tree.delete(&key));
+                }
+
+                tree.check_invariants().unwrap();
+
+                tree
+            })
+        })
+        .collect();
+
+    for (thread, handle) in handles.into_iter().enumerate() {
+        let tree = handle.join().unwrap();
+
+        assert_eq!(tree.len(), 750);
+        assert!(!tree.contains(&(thread as u64)));
+    }
+
+    base.check_invariants().unwrap();
+    assert_eq!(base.len(), 1000, "copies do not change the version they were taken from");
+
+    let mut prefix = PrefixBTree::new(3);
+
+    for i in 0..500 {
+        prefix.insert(format!("user/{:04}/profile", i).into_bytes());
+    }
+
+    let prefix = thread::spawn(move || {
+        for i in (0..500).step_by(2) {
+            assert!(prefix.delete(&format!("user/{:04}/profile", i).into_bytes()));
+        }
+
+        prefix
+    })
+    .join()
+    .unwrap();
+
+    prefix.check_invariants().unwrap();
+    assert_eq!(prefix.len(), 250);
+}
@@ -0,0 +1,128 @@
+#![cfg(feature = "tracing")]
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use srdb::{BTree, MemStorage, SrdbOptions, MIN_CACHE_PAGES};
+use tracing::Level;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/**
+ * output of subscriber, shared with its writers
+ */
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/**
+ * lines fmt subscriber of level writes while f runs on this thread, spans are written once they are created
+ */
+fn traced(level: Level, f: impl FnOnce()) -> Vec<String> {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(move || writer.clone())
+        .with_span_events(FmtSpan::NEW)
+        .with_target(false)
+        .without_time()
+        .finish();
+
+    tracing::subscriber::with_default(subscriber, f);
+
+    let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+
+    output.lines().map(str::to_owned).collect()
+}
+
+fn fill_and_empty() {
+    let mut tree = BTree::new(2);
+
+    for key in 1..=4u64 {
+        tree.insert(key);
+    }
+
+    for key in 1..=4u64 {
+        assert!(tree.delete(&key));
+    }
+}
+
+/**
+ * 4th key splits root [1, 2, 3] of tree of t = 2, then deletes borrow, merge and collapse root back
+ */
+#[test]
+fn structure_changes_are_traced_inside_operations() {
+    let lines = traced(Level::TRACE, fill_and_empty);
+
+    assert_eq!(
+        lines,
+        [
+            "DEBUG insert{key=1}: new",
+            "DEBUG insert{key=2}: new",
+            "DEBUG insert{key=3}: new",
+            "DEBUG insert{key=4}: new",
+            "TRACE insert{key=4}: split depth=1 left=1 right=1 median=2",
+            "TRACE insert{key=4}: root grow",
+            "DEBUG delete{key=1}: new",
+            "TRACE delete{key=1}: borrow depth=1 side=Right up=3 down=2",
+            "DEBUG delete{key=2}: new",
+            "TRACE delete{key=2}: merge depth=1 left=1 right=1 separator=3",
+            "TRACE delete{key=2}: root collapse",
+            "DEBUG delete{key=3}: new",
+            "DEBUG delete{key=4}: new",
+        ]
+    );
+}
+
+#[test]
+fn debug_level_shows_operations_only() {
+    let lines = traced(Level::DEBUG, fill_and_empty);
+
+    assert_eq!(lines.len(), 8);
+    assert!(lines.iter().all(|line| line.starts_with("DEBUG ") && line.ends_with(": new")), "{:?}", lines);
+}
+
+/**
+ * every write of database appends one record to log, cache of MIN_CACHE_PAGES pages evicts while tree grows
+ */
+#[test]
+fn log_appends_and_evictions_are_traced() {
+    const KEYS: usize = 3000;
+
+    let lines = traced(Level::TRACE, || {
+        let mut db = SrdbOptions::new()
+            .cache_pages(MIN_CACHE_PAGES)
+            .create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new()))
+            .unwrap();
+
+        for i in 0..KEYS {
+            db.insert(format!("key{:06}", i).as_bytes(), &[7; 100]).unwrap();
+        }
+
+        assert!(db.delete(b"key000001").unwrap());
+        assert_eq!(db.range(b"key000001".as_slice()..b"key000004".as_slice()).unwrap().count(), 2);
+    });
+
+    let count = |part: &str| lines.iter().filter(|line| line.contains(part)).count();
+
+    assert_eq!(count("}: wal append kind=1 "), KEYS + 1);
+    assert_eq!(count("insert{key=\"key000042\"}: wal append kind=1 seq=43 "), 1);
+    assert_eq!(count("delete{key=\"key000001\"}: wal append kind=1 seq=3001 "), 1);
+    assert!(count("}: evict page=") > 100, "{} evictions", count("}: evict page="));
+    assert!(count("}: split depth=") > 100);
+    assert_eq!(count("DEBUG range{start=Included(\"key000001\") end=Excluded(\"key000004\")}: new"), 1);
+    let scan_writes = lines.iter().any(|line| line.starts_with("TRACE range") && line.contains("wal append"));
+
+    assert!(!scan_writes, "scan writes nothing");
+}
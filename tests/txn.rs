@@ -0,0 +1,330 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+use srdb::{Db, Error, MemStorage, SrdbOptions, SyncMode, VerifyMode};
+
+use common::temp_path;
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn memory() -> Db {
+    let options = SrdbOptions::new().sync_mode(SyncMode::Off);
+
+    options.create_with_storage(Box::new(MemStorage::new()), Box::new(MemStorage::new())).unwrap()
+}
+
+/**
+ * commit r writes keys of round r and moves counter to r, all in one transaction,
+ * so reader sees rounds 1..=counter and nothing of later ones
+ */
+fn assert_rounds(entries: &[(Vec<u8>, Vec<u8>)], rounds: u32) {
+    let counter = entries.iter().find(|(key, _)| key == b"counter").map(|(_, value)| value.clone());
+
+    assert_eq!(counter, (rounds > 0).then(|| rounds.to_string().into_bytes()));
+    assert_eq!(entries.len() as u32, rounds * 10 + (rounds > 0) as u32, "rounds {}", rounds);
+}
+
+/**
+ * readers begun between commits see all commits before them and none after, in order they were made
+ */
+#[test]
+fn commits_become_visible_whole_and_in_order() {
+    let db = memory();
+    let done = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        for _ in 0..2 {
+            scope.spawn(|| {
+                let mut last = 0;
+
+                while !done.load(Ordering::SeqCst) {
+                    let txn = db.begin_read().unwrap();
+                    let entries = txn.to_vec().unwrap();
+                    let rounds = txn.get(b"counter").unwrap().map_or(0, |value| {
+                        String::from_utf8(value).unwrap().parse().unwrap()
+                    });
+
+                    assert_rounds(&entries, rounds);
+                    assert!(rounds >= last, "round went back from {} to {}", last, rounds);
+                    assert_eq!(txn.to_vec().unwrap(), entries, "read transaction changed");
+
+                    last = rounds;
+                }
+            });
+        }
+
+        let early = db.begin_read().unwrap();
+
+        for round in 1..=50u32 {
+            let mut txn = db.begin_write().unwrap();
+
+            for i in 0..10 {
+                txn.insert(&key(round * 10 + i), &round.to_le_bytes());
+            }
+
+            txn.insert(b"counter", round.to_string().as_bytes());
+
+            assert_eq!(txn.get(b"counter").unwrap(), Some(round.to_string().into_bytes()), "own changes are seen");
+            assert_rounds(&db.begin_read().unwrap().to_vec().unwrap(), round - 1);
+
+            txn.commit().unwrap();
+
+            assert_rounds(&db.begin_read().unwrap().to_vec().unwrap(), round);
+        }
+
+        assert!(early.is_empty(), "transaction begun before commits sees none of them");
+
+        done.store(true, Ordering::SeqCst);
+    });
+}
+
+/**
+ * rolled back and dropped transactions leave entries, seq, pages and file as they were
+ */
+#[test]
+fn rollback_leaves_no_trace() {
+    let path = temp_path("rollback");
+    let mut db = Db::create(&path).unwrap();
+
+    for i in 0..2000 {
+        db.insert(&key(i), b"committed").unwrap();
+    }
+
+    let entries = db.to_vec().unwrap();
+    let (seq, pages, free) = (db.seq(), db.page_count(), db.free_pages());
+
+    for rollback in [true, false] {
+        let mut txn = db.begin_write().unwrap();
+
+        for i in 0..3000 {
+            txn.insert(&key(i), b"staged");
+        }
+
+        for i in (0..3000).step_by(3) {
+            assert!(txn.delete(&key(i)).unwrap());
+        }
+
+        if rollback {
+            txn.rollback();
+        } else {
+            drop(txn);
+        }
+
+        assert!(db.to_vec().unwrap() == entries);
+        assert_eq!((db.seq(), db.page_count(), db.free_pages()), (seq, pages, free));
+    }
+
+    db.close().unwrap();
+
+    let mut db = Db::open(&path).unwrap();
+
+    assert!(db.to_vec().unwrap() == entries);
+    assert_eq!(db.seq(), seq);
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * panic unwinding through write transaction rolls it back and releases writer, tree stays valid on disk
+ */
+#[test]
+fn panic_inside_write_txn_does_not_corrupt_tree() {
+    let path = temp_path("panic");
+    let mut db = Db::create(&path).unwrap();
+
+    for i in 0..1000 {
+        db.insert(&key(i), b"before").unwrap();
+    }
+
+    let entries = db.to_vec().unwrap();
+
+    db.set_wait_for_writer(false);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut txn = db.begin_write().unwrap();
+
+        for i in 0..5000 {
+            txn.insert(&key(i), b"after");
+
+            if i == 4000 {
+                panic!("failure inside transaction");
+            }
+        }
+
+        txn.commit().unwrap();
+    }));
+
+    assert!(result.is_err());
+    assert!(db.to_vec().unwrap() == entries);
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    let mut txn = db.begin_write().expect("writer is released by panic");
+
+    txn.insert(&key(5000), b"after panic");
+    txn.commit().unwrap();
+
+    drop(db);
+
+    let db = Db::open(&path).unwrap();
+
+    assert_eq!(db.len(), 1001);
+    assert_eq!(db.get(&key(5000)).unwrap(), Some(b"after panic".to_vec()));
+    assert_eq!(db.get(&key(1000)).unwrap(), None);
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * second writer fails at once when handle does not wait, and waits for the first one to end otherwise
+ */
+#[test]
+fn second_writer_errors_or_waits() {
+    let mut db = memory();
+
+    db.set_wait_for_writer(false);
+
+    let txn = db.begin_write().unwrap();
+
+    assert!(matches!(db.begin_write(), Err(Error::WriteTxnActive)));
+
+    drop(txn);
+
+    db.set_wait_for_writer(true);
+
+    let mut txn = db.begin_write().unwrap();
+    let (started, wait_started) = channel();
+
+    thread::scope(|scope| {
+        let waiter = scope.spawn(|| {
+            started.send(()).unwrap();
+
+            let txn = db.begin_write().unwrap();
+
+            assert_eq!(txn.get(b"first").unwrap(), Some(b"1".to_vec()), "second writer begins after commit");
+        });
+
+        wait_started.recv().unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        assert!(!waiter.is_finished());
+
+        txn.insert(b"first", b"1");
+        txn.commit().unwrap();
+        waiter.join().unwrap();
+    });
+}
+
+/**
+ * transaction pins its version while 1000 writes commit, pages they replace are kept for it,
+ * once it is dropped gc makes them free and writes reuse them instead of growing file
+ */
+#[test]
+fn pinned_version_is_kept_until_dropped_then_collected() {
+    let path = temp_path("gc");
+    let mut db = Db::create(&path).unwrap();
+
+    for i in 0..2000 {
+        db.insert(&key(i), b"old").unwrap();
+    }
+
+    let old = db.to_vec().unwrap();
+    let txn = db.begin_read().unwrap();
+    let file_pages = db.disk_stats().file_pages;
+
+    assert_eq!(db.oldest_pinned_version(), Some(txn.version()));
+    assert_eq!(db.retained_pages(), 0);
+
+    for i in 0..1000 {
+        db.insert(&key(i * 2), b"new").unwrap();
+        db.delete(&key(i * 2 + 1)).unwrap();
+    }
+
+    let newer = db.begin_read().unwrap();
+
+    assert!(newer.version() > txn.version());
+    assert_eq!(db.oldest_pinned_version(), Some(txn.version()));
+    assert!(txn.to_vec().unwrap() == old, "pinned version reads data as of its start");
+    assert_eq!(newer.len(), 1000);
+
+    let retained = db.retained_pages();
+
+    assert!(retained > 0);
+    assert!(db.disk_stats().file_pages >= file_pages + retained as u64, "file grows around pinned pages");
+    assert_eq!(db.gc(), 0, "pages read by transactions are not collected");
+
+    drop(txn);
+
+    assert_eq!(db.oldest_pinned_version(), Some(newer.version()));
+
+    drop(newer);
+
+    assert_eq!(db.oldest_pinned_version(), None);
+    assert!(db.gc() >= retained);
+    assert_eq!(db.retained_pages(), 0);
+
+    let file_pages = db.disk_stats().file_pages;
+
+    for i in 0..1000 {
+        db.insert(&key(i * 2), b"again").unwrap();
+    }
+
+    drop(db.begin_read().unwrap());
+
+    assert_eq!(db.disk_stats().file_pages, file_pages, "collected pages are reused instead of growing file");
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * limited ranges taken one after another add up to whole range, changes after begin_read do not show in them
+ */
+#[test]
+fn limited_ranges_add_up_to_range() {
+    let mut db = memory();
+
+    for i in 0..500 {
+        db.insert(&key(i), &[b'v'; 10][..1 + i as usize % 10]).unwrap();
+    }
+
+    let txn = db.begin_read().unwrap();
+
+    db.delete(&key(7)).unwrap();
+    db.insert(b"key000100a", b"late").unwrap();
+
+    let whole = txn.range(key(3)..key(480)).unwrap();
+    let mut chunks = vec![];
+    let mut from = key(3);
+
+    loop {
+        let chunk = txn.range_limit(from.as_slice()..key(480).as_slice(), 64).unwrap();
+
+        assert!(chunk.len() <= 64);
+
+        let Some((last, _)) = chunk.last() else {
+            break;
+        };
+
+        from = [last.as_slice(), b"\0"].concat();
+        chunks.extend(chunk);
+    }
+
+    assert_eq!(whole.len(), 477);
+    assert_eq!(chunks, whole);
+    assert!(txn.range_limit::<[u8]>(.., 0).unwrap().is_empty());
+    assert_eq!(txn.range_limit::<[u8]>(.., 1000).unwrap(), txn.to_vec().unwrap());
+}
@@ -0,0 +1,143 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use srdb::{Db, VerifyMode, WriteBatch};
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn value(i: u32) -> Vec<u8> {
+    format!("value of key {}", i).into_bytes()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = common::temp_path(name);
+
+    let _ = fs::remove_file(leftover(&path));
+    let _ = fs::remove_dir(leftover(&path));
+
+    path
+}
+
+/**
+ * file vacuum builds copy in, see Db::vacuum
+ */
+fn leftover(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+
+    name.push("-vacuum");
+
+    PathBuf::from(name)
+}
+
+/**
+ * database of 5000 keys with all but every tenth deleted
+ */
+fn thinned(path: &Path) -> Db {
+    let mut db = Db::create(path).unwrap();
+    let mut inserts = WriteBatch::new();
+    let mut deletes = WriteBatch::new();
+
+    for i in 0..5000 {
+        inserts.put(&key(i), &value(i));
+
+        if i % 10 != 0 {
+            deletes.delete(&key(i));
+        }
+    }
+
+    db.write(&inserts).unwrap();
+    db.write(&deletes).unwrap();
+    db.checkpoint().unwrap();
+
+    db
+}
+
+#[test]
+fn vacuum_shrinks_file_and_keeps_contents() {
+    let path = temp_path("shrink");
+    let mut db = thinned(&path);
+    let before = db.to_vec().unwrap();
+    let size = fs::metadata(&path).unwrap().len();
+
+    let reclaimed = db.vacuum().unwrap();
+    let vacuumed = fs::metadata(&path).unwrap().len();
+
+    assert_eq!(reclaimed, size - vacuumed);
+    assert!(vacuumed < size / 4, "{} bytes after vacuum, {} before", vacuumed, size);
+    assert_eq!(db.free_pages(), 0);
+    assert!(db.to_vec().unwrap() == before);
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    db.insert(&key(1), b"after vacuum").unwrap();
+    db.close().unwrap();
+
+    let db = Db::open(&path).unwrap();
+
+    assert_eq!(db.len(), before.len() + 1);
+    assert_eq!(db.get(&key(1)).unwrap(), Some(b"after vacuum".to_vec()));
+    assert!(!leftover(&path).exists());
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
+
+/**
+ * crash halfway through vacuum leaves partly written copy next to untouched original,
+ * it is never opened and the next vacuum replaces it
+ */
+#[test]
+fn crash_during_vacuum_leaves_original_intact() {
+    let path = temp_path("crash");
+
+    thinned(&path).close().unwrap();
+
+    let before = fs::read(&path).unwrap();
+
+    fs::write(leftover(&path), &before[..before.len() / 2]).unwrap();
+
+    let mut db = Db::open(&path).unwrap();
+
+    assert_eq!(db.len(), 500);
+    assert!(db.verify(VerifyMode::Full).is_ok());
+    assert!(fs::read(&path).unwrap() == before);
+
+    db.vacuum().unwrap();
+
+    assert!(!leftover(&path).exists());
+    assert_eq!(db.len(), 500);
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
+
+#[test]
+fn failed_vacuum_keeps_handle_on_original() {
+    let path = temp_path("failed");
+    let mut db = thinned(&path);
+    let before = db.to_vec().unwrap();
+    let size = fs::metadata(&path).unwrap().len();
+
+    fs::create_dir(leftover(&path)).unwrap();
+
+    assert!(db.vacuum().is_err());
+    assert_eq!(fs::metadata(&path).unwrap().len(), size);
+    assert!(db.to_vec().unwrap() == before);
+
+    db.insert(&key(1), b"after failed vacuum").unwrap();
+    db.close().unwrap();
+
+    fs::remove_dir(leftover(&path)).unwrap();
+
+    let db = Db::open(&path).unwrap();
+
+    assert_eq!(db.get(&key(1)).unwrap(), Some(b"after failed vacuum".to_vec()));
+
+    drop(db);
+    Db::remove(&path).unwrap();
+}
@@ -0,0 +1,240 @@
+#![cfg(feature = "std")]
+
+use srdb::{Db, MemStorage, PageId, Pager, Problem, Repair, SrdbOptions, VerifyMode, WriteBatch, HEADER_PAGE};
+
+/**
+ * kind byte node pages start with, see page module
+ */
+const LEAF: u8 = 1;
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+/**
+ * closed database of 5000 entries, log is empty
+ */
+fn database() -> MemStorage {
+    let storage = MemStorage::new();
+    let mut db = Db::create_with_storage(Box::new(storage.clone()), Box::new(MemStorage::new())).unwrap();
+    let mut batch = WriteBatch::new();
+
+    for i in 0..5000 {
+        batch.put(&key(i), b"value");
+    }
+
+    db.write(&batch).unwrap();
+    db.close().unwrap();
+
+    storage
+}
+
+fn read(pager: &mut Pager, page_id: PageId) -> Vec<u8> {
+    let mut page = vec![0; pager.page_size()];
+
+    pager.read_page(page_id, &mut page).unwrap();
+
+    page
+}
+
+fn leaves(pager: &mut Pager) -> Vec<PageId> {
+    (HEADER_PAGE + 1..pager.page_count()).filter(|&page_id| read(pager, page_id)[0] == LEAF).collect()
+}
+
+fn problems_of(problems: &[Problem], page_id: PageId) -> Vec<&Problem> {
+    problems.iter().filter(|problem| problem.page_id == Some(page_id)).collect()
+}
+
+#[test]
+fn healthy_database_has_no_problems() {
+    let db = Db::open_with_storage(Box::new(database()), Box::new(MemStorage::new())).unwrap();
+
+    for mode in [VerifyMode::Quick, VerifyMode::Full] {
+        let report = db.verify(mode);
+
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert!(report.repairs.is_empty());
+        assert_eq!(report.entries, 5000);
+    }
+}
+
+/**
+ * pages are damaged with valid checksums, so only verify of structure finds them,
+ * every damaged page is reported by its id, walk goes on past the first problem
+ */
+#[test]
+fn every_problem_is_reported_with_its_page() {
+    let storage = database();
+    let mut pager = Pager::open_with_storage(Box::new(storage.clone())).unwrap();
+    let leaves = leaves(&mut pager);
+    let (garbage, copied, source) = (leaves[3], leaves[10], leaves[20]);
+    let copy = read(&mut pager, source);
+    let leaked = pager.allocate_page().unwrap();
+
+    pager.write_page(garbage, &vec![LEAF; pager.page_size()]).unwrap();
+    pager.write_page(copied, &copy).unwrap();
+    pager.write_page(leaked, &vec![0; pager.page_size()]).unwrap();
+    pager.commit(true).unwrap();
+    drop(pager);
+
+    let db = Db::open_with_storage(Box::new(storage), Box::new(MemStorage::new())).unwrap();
+    let report = db.verify(VerifyMode::Quick);
+
+    assert!(!problems_of(&report.problems, garbage).is_empty(), "{:?}", report.problems);
+    assert!(
+        problems_of(&report.problems, copied).iter().any(|problem| problem.message.contains("out of delimeters")),
+        "{:?}",
+        report.problems
+    );
+    assert!(
+        problems_of(&report.problems, leaked).iter().any(|problem| problem.message.contains("neither reachable nor free")),
+        "{:?}",
+        report.problems
+    );
+    assert!(report.problems.iter().any(|problem| problem.to_string().starts_with(&format!("page {}:", garbage))));
+    assert!(report.repairs.contains(&Repair::Salvage), "{:?}", report.repairs);
+    assert!(report.entries < 5000);
+}
+
+#[test]
+fn leaked_page_alone_asks_for_free_list_rebuild() {
+    let storage = database();
+    let mut pager = Pager::open_with_storage(Box::new(storage.clone())).unwrap();
+    let leaked = [pager.allocate_page().unwrap(), pager.allocate_page().unwrap()];
+
+    for page_id in leaked {
+        pager.write_page(page_id, &vec![0; pager.page_size()]).unwrap();
+    }
+
+    pager.commit(true).unwrap();
+    drop(pager);
+
+    let db = Db::open_with_storage(Box::new(storage), Box::new(MemStorage::new())).unwrap();
+    let report = db.verify(VerifyMode::Full);
+    let reported: Vec<Option<PageId>> = report.problems.iter().map(|problem| problem.page_id).collect();
+
+    assert_eq!(reported, leaked.map(Some).to_vec(), "{:?}", report.problems);
+    assert_eq!(report.repairs, vec![Repair::RebuildFreeList]);
+    assert_eq!(report.entries, 5000);
+}
+
+/**
+ * keys lost with damaged leaves are a run of neighbours per leaf, everything else is salvaged intact
+ */
+#[test]
+fn salvage_copies_every_readable_entry() {
+    let storage = database();
+    let mut pager = Pager::open_with_storage(Box::new(storage.clone())).unwrap();
+    let leaves = leaves(&mut pager);
+    let damaged = [leaves[5], leaves[30]];
+
+    for page_id in damaged {
+        pager.write_page(page_id, &vec![LEAF; pager.page_size()]).unwrap();
+    }
+
+    pager.commit(true).unwrap();
+    drop(pager);
+
+    let db = Db::open_with_storage(Box::new(storage), Box::new(MemStorage::new())).unwrap();
+    let report = db.verify(VerifyMode::Full);
+
+    assert_eq!(report.repairs, vec![Repair::Salvage]);
+    assert!(damaged.iter().all(|&page_id| !problems_of(&report.problems, page_id).is_empty()), "{:?}", report.problems);
+
+    let dest = std::env::temp_dir().join(format!("srdb-verify-salvage-{}", std::process::id()));
+
+    let _ = Db::remove(&dest);
+
+    let salvaged = db.salvage(&dest).unwrap();
+
+    assert_eq!(salvaged.entries, report.entries);
+    assert!(salvaged.entries < 5000 && salvaged.entries > 4000, "{} entries", salvaged.entries);
+    assert!(damaged.iter().all(|&page_id| !problems_of(&salvaged.problems, page_id).is_empty()));
+
+    let mut copy = Db::open(&dest).unwrap();
+    let entries = copy.to_vec().unwrap();
+    let present: Vec<bool> = (0..5000).map(|i| copy.get(&key(i)).unwrap().is_some()).collect();
+    let runs = present.windows(2).filter(|pair| pair[0] && !pair[1]).count();
+
+    assert!(copy.verify(VerifyMode::Full).is_ok());
+    assert_eq!(entries.len() as u64, salvaged.entries);
+    assert!(entries.iter().all(|(_, value)| value == b"value"));
+    assert_eq!(runs, damaged.len(), "every damaged leaf loses one run of keys");
+
+    drop(copy);
+    Db::remove(&dest).unwrap();
+}
+
+#[test]
+fn rebuilt_free_list_makes_leaked_pages_free_again() {
+    let storage = database();
+    let mut pager = Pager::open_with_storage(Box::new(storage.clone())).unwrap();
+    let leaked: Vec<PageId> = (0..5).map(|_| pager.allocate_page().unwrap()).collect();
+
+    for &page_id in &leaked {
+        pager.write_page(page_id, &vec![0; pager.page_size()]).unwrap();
+    }
+
+    pager.commit(true).unwrap();
+    drop(pager);
+
+    let mut db = Db::open_with_storage(Box::new(storage), Box::new(MemStorage::new())).unwrap();
+    let mut intact = Db::open_with_storage(Box::new(database()), Box::new(MemStorage::new())).unwrap();
+
+    assert_eq!(db.verify(VerifyMode::Full).repairs, vec![Repair::RebuildFreeList]);
+    assert_eq!(db.rebuild_free_list().unwrap(), leaked.len() as u64);
+    assert!(db.verify(VerifyMode::Full).is_ok());
+    assert_eq!(db.page_count(), intact.page_count() + leaked.len() as u32);
+
+    for i in 5000..5100 {
+        db.insert(&key(i), b"value").unwrap();
+        intact.insert(&key(i), b"value").unwrap();
+    }
+
+    db.flush().unwrap();
+    intact.flush().unwrap();
+
+    assert_eq!(db.page_count(), intact.page_count(), "freed pages are used before file grows");
+    assert!(db.verify(VerifyMode::Full).is_ok());
+}
+
+#[test]
+fn torn_page_at_end_of_file_is_trimmed() {
+    let path = std::env::temp_dir().join(format!("srdb-verify-torn-{}", std::process::id()));
+
+    let _ = Db::remove(&path);
+
+    let mut db = Db::create(&path).unwrap();
+
+    for i in 0..1000 {
+        db.insert(&key(i), b"value").unwrap();
+    }
+
+    db.close().unwrap();
+
+    let length = std::fs::metadata(&path).unwrap().len();
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+
+    std::io::Write::write_all(&mut file, &[7; 1000]).unwrap();
+    drop(file);
+
+    let db = SrdbOptions::new().read_only(true).open(&path).unwrap();
+    let report = db.verify(VerifyMode::Full);
+
+    assert_eq!(report.repairs, vec![Repair::TrimTornPage], "{:?}", report.problems);
+    assert_eq!(report.problems[0].message, "file ends with 1000 bytes of torn page");
+    assert_eq!(report.entries, 1000);
+
+    db.close().unwrap();
+
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), length + 1000, "read only handle leaves file as it is");
+
+    let mut db = Db::open(&path).unwrap();
+
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), length, "writable handle trims torn page on open");
+    assert_eq!(db.trim_torn_page().unwrap(), 0);
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    db.close().unwrap();
+    Db::remove(&path).unwrap();
+}
@@ -0,0 +1,196 @@
+#![cfg(feature = "std")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use srdb::{Db, OpenStats, SrdbOptions, VerifyMode};
+
+fn key(i: u32) -> Vec<u8> {
+    format!("key{:06}", i).into_bytes()
+}
+
+fn value(i: u32) -> Vec<u8> {
+    format!("value of key {} padded to a hundred bytes {}", i, "-".repeat(50)).into_bytes()
+}
+
+/**
+ * empty directory of one test
+ */
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("srdb-wal-{}-{}", name, std::process::id()));
+
+    let _ = fs::remove_dir_all(&dir);
+
+    fs::create_dir(&dir).unwrap();
+
+    dir
+}
+
+/**
+ * names of log segments of database in dir, in order
+ */
+fn segments(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("db-wal-"))
+        .collect();
+
+    names.sort();
+
+    names
+}
+
+/**
+ * files of database as they are on disk now, log is synced after every record, so this is what crash leaves
+ */
+fn crash_copy(dir: &Path, to: &Path) {
+    fs::create_dir(to).unwrap();
+
+    for entry in fs::read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+
+        if entry.file_type().unwrap().is_file() {
+            fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+        }
+    }
+}
+
+#[test]
+fn rotated_log_recovers_after_crash_and_checkpoint_removes_old_segments() {
+    let dir = temp_dir("rotation");
+    let options = SrdbOptions::new().wal_limit(None).wal_segment_size(Some(16 << 10)).cache_pages(4096);
+    let mut db = options.create(dir.join("db")).unwrap();
+
+    for i in 0..1500 {
+        db.insert(&key(i), &value(i)).unwrap();
+
+        if i == 500 {
+            db.flush().unwrap();
+        }
+    }
+
+    for i in (0..1500).step_by(5) {
+        db.delete(&key(i)).unwrap();
+    }
+
+    let expected = db.to_vec().unwrap();
+    let written = db.seq();
+
+    assert_eq!(written, 1500 + 300, "every insert and delete takes next seq");
+    assert!(db.wal_segments() >= 8, "{} segments", db.wal_segments());
+    assert_eq!(segments(&dir).len() as u64, db.wal_segments());
+
+    crash_copy(&dir, &dir.join("crashed"));
+
+    let mut recovered = Db::open(dir.join("crashed").join("db")).unwrap();
+
+    assert!(recovered.open_stats().recovered);
+    assert_eq!(recovered.open_stats().replayed, written - 501, "batches after flush are replayed from segments");
+    assert_eq!(recovered.seq(), written);
+    assert!(recovered.verify(VerifyMode::Full).is_ok());
+    assert!(recovered.to_vec().unwrap() == expected);
+
+    recovered.insert(b"after recovery", b"value").unwrap();
+
+    assert_eq!(recovered.seq(), written + 1, "seq goes on from the last replayed batch");
+
+    drop(recovered);
+
+    db.checkpoint().unwrap();
+
+    assert_eq!(db.wal_segments(), 1);
+    assert_eq!(segments(&dir).len(), 1);
+
+    db.insert(&key(1), b"after checkpoint").unwrap();
+    db.close().unwrap();
+
+    let db = Db::open(dir.join("db")).unwrap();
+
+    assert!(!db.open_stats().recovered);
+    assert_eq!(db.get(&key(1)).unwrap(), Some(b"after checkpoint".to_vec()));
+    assert_eq!(db.seq(), written + 1);
+
+    drop(db);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/**
+ * with limit, log is checkpointed as it grows past it, so segments never pile up
+ */
+#[test]
+fn wal_limit_keeps_segments_few() {
+    let dir = temp_dir("limit");
+    let options = SrdbOptions::new().wal_limit(Some(64 << 10)).wal_segment_size(Some(16 << 10));
+    let mut db = options.create(dir.join("db")).unwrap();
+
+    for i in 0..3000 {
+        db.insert(&key(i), &value(i)).unwrap();
+
+        assert!(db.wal_segments() <= 6, "{} segments after {} inserts", db.wal_segments(), i);
+    }
+
+    assert!(segments(&dir).len() <= 6);
+
+    db.close().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/**
+ * copy taken before close has log to replay, database closed by close has none and opens as it was left
+ */
+#[test]
+fn close_checkpoints_so_reopen_needs_no_recovery() {
+    let dir = temp_dir("close");
+    let mut db = SrdbOptions::new().cache_pages(4096).create(dir.join("db")).unwrap();
+
+    db.create_table("events").unwrap();
+
+    for i in 0..2000 {
+        db.insert(&key(i), &value(i)).unwrap();
+
+        if i.is_multiple_of(3) {
+            db.table("events").unwrap().insert(&key(i), b"event").unwrap();
+        }
+
+        if i == 1000 {
+            db.flush().unwrap();
+        }
+    }
+
+    for i in (0..2000).step_by(7) {
+        db.delete(&key(i)).unwrap();
+    }
+
+    let expected = db.to_vec().unwrap();
+    let events = db.table("events").unwrap().to_vec().unwrap();
+    let written = db.seq();
+
+    crash_copy(&dir, &dir.join("crashed"));
+
+    let crashed = Db::open(dir.join("crashed").join("db")).unwrap();
+
+    assert!(crashed.open_stats().recovered, "without close, writes after flush are replayed");
+    assert!(crashed.open_stats().replayed > 0);
+
+    drop(crashed);
+    db.close().unwrap();
+
+    let mut db = Db::open(dir.join("db")).unwrap();
+
+    assert_eq!(db.open_stats(), OpenStats { recovered: false, replayed: 0 });
+    assert_eq!(db.disk_stats().wal_bytes, 0);
+    assert_eq!(db.seq(), written);
+    assert!(db.to_vec().unwrap() == expected);
+    assert!(db.table("events").unwrap().to_vec().unwrap() == events);
+    assert!(db.verify(VerifyMode::Full).is_ok());
+
+    db.close().unwrap();
+
+    let db = SrdbOptions::new().read_only(true).open(dir.join("db")).unwrap();
+
+    assert!(!db.open_stats().recovered);
+
+    db.close().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "wasm")]
+
+use serde_json::Value;
+use srdb::{WasmBTree, WasmStringBTree};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+/**
+ * keys of every level of structure, from root down, nodes of a level left to right
+ */
+fn levels(structure: &str) -> Vec<Vec<Vec<i64>>> {
+    let mut level = vec![serde_json::from_str::<Value>(structure).unwrap()];
+    let mut levels = vec![];
+
+    while !level.is_empty() {
+        levels.push(
+            level
+                .iter()
+                .map(|node| node["keys"].as_array().unwrap().iter().map(|key| key.as_i64().unwrap()).collect())
+                .collect(),
+        );
+        level = level.iter().flat_map(|node| node["children"].as_array().unwrap().clone()).collect();
+    }
+
+    levels
+}
+
+/**
+ * on wasm32 it runs under wasm-pack test --node or --headless, elsewhere it is plain test
+ */
+#[wasm_bindgen_test(unsupported = test)]
+fn wasm_tree_shows_splits_and_merges() {
+    let mut tree = WasmBTree::new(2).unwrap();
+
+    assert!(tree.is_empty());
+
+    for key in [10, 20, 30] {
+        tree.insert(key);
+    }
+
+    assert_eq!(levels(&tree.to_structure_json()), [vec![vec![10, 20, 30]]]);
+
+    tree.insert(40);
+
+    assert_eq!(levels(&tree.to_structure_json()), [vec![vec![20]], vec![vec![10], vec![30, 40]]]);
+    assert_eq!(tree.to_array(), [10, 20, 30, 40]);
+    assert_eq!(tree.len(), 4);
+    assert!(tree.contains(30));
+    assert!(!tree.contains(25));
+
+    assert!(tree.delete(40));
+    assert!(tree.delete(10));
+    assert!(!tree.delete(10));
+
+    assert_eq!(levels(&tree.to_structure_json()), [vec![vec![20, 30]]], "leaves are merged and root collapses");
+    assert_eq!(tree.to_array(), [20, 30]);
+
+    let structure: Value = serde_json::from_str(&tree.to_structure_json()).unwrap();
+
+    assert_eq!(structure["leaf"], Value::Bool(true));
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn wasm_string_tree_keeps_keys_in_order() {
+    let mut tree = WasmStringBTree::new(3).unwrap();
+
+    for word in ["pear", "apple", "fig", "kiwi", "banana", "cherry"] {
+        tree.insert(word.to_string());
+    }
+
+    assert_eq!(tree.to_array(), ["apple", "banana", "cherry", "fig", "kiwi", "pear"]);
+    assert!(tree.contains("kiwi".to_string()));
+    assert!(tree.delete("kiwi".to_string()));
+    assert!(!tree.contains("kiwi".to_string()));
+    assert_eq!(tree.len(), 5);
+
+    let structure: Value = serde_json::from_str(&tree.to_structure_json()).unwrap();
+
+    assert_eq!(structure["keys"], serde_json::json!(["cherry"]));
+    assert_eq!(structure["children"][0]["keys"], serde_json::json!(["apple", "banana"]));
+}